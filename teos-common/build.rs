@@ -11,6 +11,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "#[serde(with = \"crate::ser::serde_vec_bytes\")]",
         )
         .field_attribute("encrypted_blob", "#[serde(with = \"hex::serde\")]")
+        .field_attribute("payment_hash", "#[serde(with = \"hex::serde\")]")
+        .field_attribute("tier", "#[serde(default)]")
+        .field_attribute("RegistrationToken.token", "#[serde(with = \"hex::serde\")]")
+        .field_attribute(
+            "RegistrationToken.signature",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute(
+            "RegistrationChallenge.challenge",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute(
+            "RegistrationTokenKey.key",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute(
+            "RegisterRequest.proof",
+            "#[serde(default, skip_serializing_if = \"Option::is_none\")]",
+        )
         .field_attribute("dispute_txid", "#[serde(with = \"crate::ser::serde_be\")]")
         .field_attribute("penalty_txid", "#[serde(with = \"crate::ser::serde_be\")]")
         .field_attribute("penalty_rawtx", "#[serde(with = \"hex::serde\")]")
@@ -18,6 +37,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "GetAppointmentResponse.status",
             "#[serde(with = \"crate::ser::serde_status\")]",
         )
+        .field_attribute(
+            "AddAppointmentResult.response",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute(
+            "AddAppointmentResult.error",
+            "#[serde(default, skip_serializing_if = \"String::is_empty\")]",
+        )
+        .field_attribute(
+            "AddAppointmentResult.rejection",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute(
+            "AppointmentRejection.reason",
+            "#[serde(with = \"crate::ser::serde_rejection_reason\")]",
+        )
         .compile(
             &[
                 "proto/common/teos/v2/appointment.proto",
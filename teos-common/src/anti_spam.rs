@@ -0,0 +1,376 @@
+//! Primitives used to keep towers from being spammed with registrations: hashcash challenges and blinded tokens.
+//!
+//! Tokens follow a blind signing scheme over secp256k1. The user hashes a random token to a curve point `T` and hands
+//! the tower a blinded version of it (`r·T`). The tower signs it with its token key (`k·r·T`), and the user unblinds
+//! the result to get the token signature (`k·T`). The tower can check the signature when the token is redeemed, but
+//! cannot link it to its issuance.
+//!
+//! Each blinded signature comes with a [TokenProof] that it has been made with the key behind the tower's published
+//! token key (`k·G`). Users check it before unblinding, so a tower cannot sign each user's tokens with a different key
+//! to tell them apart when they are redeemed.
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::constants::CURVE_ORDER;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::cryptography::{get_random_bytes, get_random_keypair};
+
+/// Size of registration tokens, in bytes.
+pub const TOKEN_SIZE: usize = 32;
+
+/// Maximum proof of work difficulty, in bits. Harder challenges would take users too long to solve.
+pub const MAX_POW_DIFFICULTY: u8 = 32;
+
+/// Size of serialized [TokenProof]s, in bytes.
+pub const TOKEN_PROOF_SIZE: usize = 64;
+
+/// Tag used when hashing tokens to curve points.
+const TOKEN_TAG: &[u8] = b"teos/registration_token";
+
+/// Tag used when computing the challenge of a [TokenProof].
+const TOKEN_PROOF_TAG: &[u8] = b"teos/registration_token_proof";
+
+/// Proof that a blinded token has been signed with the key behind a given token key, that is, that the discrete
+/// logarithm of the signature with respect to the blinded token equals the one of the token key with respect to the
+/// generator (Chaum-Pedersen proof).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenProof {
+    challenge: SecretKey,
+    response: SecretKey,
+}
+
+impl TokenProof {
+    /// Serializes the proof as `challenge || response`.
+    pub fn serialize(&self) -> [u8; TOKEN_PROOF_SIZE] {
+        let mut data = [0; TOKEN_PROOF_SIZE];
+        data[..32].copy_from_slice(&self.challenge.secret_bytes());
+        data[32..].copy_from_slice(&self.response.secret_bytes());
+        data
+    }
+
+    /// Deserializes a proof. Returns `None` if the data does not encode a valid proof.
+    pub fn from_slice(data: &[u8]) -> Option<Self> {
+        if data.len() != TOKEN_PROOF_SIZE {
+            return None;
+        }
+        Some(TokenProof {
+            challenge: SecretKey::from_slice(&data[..32]).ok()?,
+            response: SecretKey::from_slice(&data[32..]).ok()?,
+        })
+    }
+}
+
+/// Hashes a challenge alongside a nonce.
+pub fn pow_hash(challenge: &[u8], nonce: u64) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(challenge);
+    engine.input(&nonce.to_be_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// Counts the leading zero bits of some data.
+fn leading_zero_bits(data: &[u8]) -> u32 {
+    let mut count = 0;
+    for byte in data {
+        count += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    count
+}
+
+/// Checks whether a nonce solves a challenge, that is, whether hashing them together results in, at least,
+/// `difficulty` leading zero bits.
+pub fn check_pow(challenge: &[u8], nonce: u64, difficulty: u8) -> bool {
+    leading_zero_bits(&pow_hash(challenge, nonce)) >= difficulty as u32
+}
+
+/// Finds a nonce that solves a challenge. This takes `2^difficulty` hashes on average.
+pub fn solve_pow(challenge: &[u8], difficulty: u8) -> u64 {
+    (0..u64::MAX)
+        .find(|nonce| check_pow(challenge, *nonce, difficulty))
+        .unwrap()
+}
+
+/// Creates a fresh random token.
+pub fn new_token() -> Vec<u8> {
+    get_random_bytes(TOKEN_SIZE)
+}
+
+/// Hashes a token to a curve point (try-and-increment).
+pub fn hash_to_curve(token: &[u8]) -> PublicKey {
+    let mut candidate = [2; 33];
+    for counter in 0..u32::MAX {
+        let mut engine = sha256::Hash::engine();
+        engine.input(TOKEN_TAG);
+        engine.input(token);
+        engine.input(&counter.to_be_bytes());
+        candidate[1..].copy_from_slice(&sha256::Hash::from_engine(engine));
+
+        if let Ok(point) = PublicKey::from_slice(&candidate) {
+            return point;
+        }
+    }
+    unreachable!("Roughly half of the candidates are valid points")
+}
+
+/// Blinds a token so it can be signed by the tower without the tower learning it.
+///
+/// Returns the blinded token alongside the blinding factor, which is needed to unblind the signature.
+pub fn blind_token(token: &[u8]) -> (PublicKey, SecretKey) {
+    let blinding_factor = get_random_keypair().0;
+    (
+        mul(&hash_to_curve(token), &blinding_factor),
+        blinding_factor,
+    )
+}
+
+/// Computes the token key to be published for a given signing key.
+pub fn token_key(key: &SecretKey) -> PublicKey {
+    PublicKey::from_secret_key(&Secp256k1::signing_only(), key)
+}
+
+/// Computes the challenge of a [TokenProof] out of the statement being proven and the nonce commitments. Returns `None`
+/// in the (negligible) case the resulting hash is not a valid scalar.
+fn proof_challenge(
+    token_key: &PublicKey,
+    blinded_token: &PublicKey,
+    blinded_signature: &PublicKey,
+    commitments: (&PublicKey, &PublicKey),
+) -> Option<SecretKey> {
+    let mut engine = sha256::Hash::engine();
+    engine.input(TOKEN_PROOF_TAG);
+    for point in [
+        token_key,
+        blinded_token,
+        blinded_signature,
+        commitments.0,
+        commitments.1,
+    ] {
+        engine.input(&point.serialize());
+    }
+    SecretKey::from_slice(&sha256::Hash::from_engine(engine)).ok()
+}
+
+/// Signs a blinded token with the given key, alongside a proof that the signature has been made with the key behind
+/// its [token_key].
+pub fn sign_token(blinded_token: &PublicKey, key: &SecretKey) -> (PublicKey, TokenProof) {
+    let signature = mul(blinded_token, key);
+    let public_key = token_key(key);
+
+    loop {
+        let nonce = get_random_keypair().0;
+        let challenge = match proof_challenge(
+            &public_key,
+            blinded_token,
+            &signature,
+            (&token_key(&nonce), &mul(blinded_token, &nonce)),
+        ) {
+            Some(challenge) => challenge,
+            None => continue,
+        };
+
+        // response = nonce - challenge·key. A zero response is not a valid key, so a new nonce is picked
+        let mut response = challenge;
+        response.mul_assign(&key.secret_bytes()).unwrap();
+        response.negate_assign();
+        if response.add_assign(&nonce.secret_bytes()).is_ok() {
+            return (
+                signature,
+                TokenProof {
+                    challenge,
+                    response,
+                },
+            );
+        }
+    }
+}
+
+/// Checks whether the signature of a blinded token has been made with the key behind the given token key.
+pub fn verify_token_proof(
+    blinded_token: &PublicKey,
+    blinded_signature: &PublicKey,
+    proof: &TokenProof,
+    token_key: &PublicKey,
+) -> bool {
+    // Honest signatures recover the nonce commitments: response·G + challenge·K and response·T + challenge·S
+    let commitments = self::token_key(&proof.response)
+        .combine(&mul(token_key, &proof.challenge))
+        .and_then(|base_commitment| {
+            mul(blinded_token, &proof.response)
+                .combine(&mul(blinded_signature, &proof.challenge))
+                .map(|token_commitment| (base_commitment, token_commitment))
+        });
+
+    match commitments {
+        Ok((base_commitment, token_commitment)) => {
+            proof_challenge(
+                token_key,
+                blinded_token,
+                blinded_signature,
+                (&base_commitment, &token_commitment),
+            ) == Some(proof.challenge)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Unblinds the signature of a blinded token, using the factor the token was blinded with.
+///
+/// The signature is only unblinded if its proof shows it has been made with the key behind the published token key.
+/// Returns `None` otherwise.
+pub fn unblind_signature(
+    blinded_token: &PublicKey,
+    blinded_signature: &PublicKey,
+    proof: &TokenProof,
+    token_key: &PublicKey,
+    blinding_factor: &SecretKey,
+) -> Option<PublicKey> {
+    verify_token_proof(blinded_token, blinded_signature, proof, token_key)
+        .then(|| mul(blinded_signature, &invert(blinding_factor)))
+}
+
+/// Checks whether a token signature has been issued using the given key.
+pub fn verify_token(token: &[u8], signature: &PublicKey, key: &SecretKey) -> bool {
+    mul(&hash_to_curve(token), key) == *signature
+}
+
+/// Multiplies a curve point by a scalar.
+fn mul(point: &PublicKey, scalar: &SecretKey) -> PublicKey {
+    let mut result = *point;
+    // This cannot fail given valid secret keys are non-zero scalars smaller than the curve order
+    result
+        .mul_assign(&Secp256k1::verification_only(), &scalar.secret_bytes())
+        .unwrap();
+    result
+}
+
+/// Computes the inverse of a scalar modulo the curve order `n`, as `scalar^(n-2)` (given `n` is prime).
+fn invert(scalar: &SecretKey) -> SecretKey {
+    let mut exponent = CURVE_ORDER;
+    // The curve order ends in 0x41, so there is no borrow
+    exponent[31] -= 2;
+
+    let mut one = [0; 32];
+    one[31] = 1;
+    let mut result = SecretKey::from_slice(&one).unwrap();
+    for byte in exponent {
+        for bit in (0..8).rev() {
+            let square = result.secret_bytes();
+            result.mul_assign(&square).unwrap();
+            if (byte >> bit) & 1 == 1 {
+                result.mul_assign(&scalar.secret_bytes()).unwrap();
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff, 0x00]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0xff]), 7);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn test_solve_check_pow() {
+        let challenge = get_random_bytes(32);
+        let nonce = solve_pow(&challenge, 8);
+        assert!(check_pow(&challenge, nonce, 8));
+        assert!(leading_zero_bits(&pow_hash(&challenge, nonce)) >= 8);
+
+        // The first valid nonce is returned
+        assert!((0..nonce).all(|n| !check_pow(&challenge, n, 8)));
+
+        // Any nonce is valid if there is no difficulty
+        assert!(check_pow(&challenge, 0, 0));
+    }
+
+    #[test]
+    fn test_invert() {
+        let scalar = get_random_keypair().0;
+        let mut product = scalar;
+        product.mul_assign(&invert(&scalar).secret_bytes()).unwrap();
+
+        let mut one = [0; 32];
+        one[31] = 1;
+        assert_eq!(product.secret_bytes(), one);
+    }
+
+    #[test]
+    fn test_blind_tokens() {
+        let key = get_random_keypair().0;
+        let token = new_token();
+
+        // The tower signs the blinded token, and the user unblinds the signature
+        let (blinded_token, blinding_factor) = blind_token(&token);
+        assert_ne!(blinded_token, hash_to_curve(&token));
+        let (blinded_signature, proof) = sign_token(&blinded_token, &key);
+        let signature = unblind_signature(
+            &blinded_token,
+            &blinded_signature,
+            &proof,
+            &token_key(&key),
+            &blinding_factor,
+        )
+        .unwrap();
+        assert_ne!(signature, blinded_signature);
+
+        // The resulting signature is valid for the token, but not for any other token or key
+        assert!(verify_token(&token, &signature, &key));
+        assert!(!verify_token(&new_token(), &signature, &key));
+        assert!(!verify_token(&token, &signature, &get_random_keypair().0));
+    }
+
+    #[test]
+    fn test_token_proof() {
+        let key = get_random_keypair().0;
+        let (blinded_token, blinding_factor) = blind_token(&new_token());
+        let (blinded_signature, proof) = sign_token(&blinded_token, &key);
+        assert!(verify_token_proof(
+            &blinded_token,
+            &blinded_signature,
+            &proof,
+            &token_key(&key)
+        ));
+        assert_eq!(TokenProof::from_slice(&proof.serialize()), Some(proof));
+        assert_eq!(TokenProof::from_slice(&proof.serialize()[1..]), None);
+        assert_eq!(TokenProof::from_slice(&[0; TOKEN_PROOF_SIZE]), None);
+
+        // Signatures made with a key other than the published one are not unblinded
+        let other_key = get_random_keypair().0;
+        let (other_signature, other_proof) = sign_token(&blinded_token, &other_key);
+        assert!(unblind_signature(
+            &blinded_token,
+            &other_signature,
+            &other_proof,
+            &token_key(&key),
+            &blinding_factor
+        )
+        .is_none());
+        assert!(unblind_signature(
+            &blinded_token,
+            &other_signature,
+            &proof,
+            &token_key(&key),
+            &blinding_factor
+        )
+        .is_none());
+
+        // Nor are proofs valid for a different blinded token
+        let (other_token, _) = blind_token(&new_token());
+        assert!(!verify_token_proof(
+            &other_token,
+            &blinded_signature,
+            &proof,
+            &token_key(&key)
+        ));
+    }
+}
@@ -7,6 +7,7 @@ use std::{convert::TryInto, fmt};
 use bitcoin::Txid;
 
 use crate::protos as msgs;
+use crate::TowerId;
 
 pub const LOCATOR_LEN: usize = 16;
 
@@ -111,6 +112,64 @@ impl fmt::Display for AppointmentStatus {
     }
 }
 
+/// Represents all the possible reasons why the tower may reject an appointment.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    Unknown = 0,
+    AuthenticationFailure = 1,
+    NotEnoughSlots = 2,
+    SubscriptionExpired = 3,
+    AlreadyTriggered = 4,
+    ToSelfDelayTooSmall = 5,
+    RateLimited = 6,
+}
+
+impl From<i32> for RejectionReason {
+    fn from(x: i32) -> Self {
+        match x {
+            1 => RejectionReason::AuthenticationFailure,
+            2 => RejectionReason::NotEnoughSlots,
+            3 => RejectionReason::SubscriptionExpired,
+            4 => RejectionReason::AlreadyTriggered,
+            5 => RejectionReason::ToSelfDelayTooSmall,
+            6 => RejectionReason::RateLimited,
+            _ => RejectionReason::Unknown,
+        }
+    }
+}
+
+impl std::str::FromStr for RejectionReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(RejectionReason::Unknown),
+            "authentication_failure" => Ok(RejectionReason::AuthenticationFailure),
+            "not_enough_slots" => Ok(RejectionReason::NotEnoughSlots),
+            "subscription_expired" => Ok(RejectionReason::SubscriptionExpired),
+            "already_triggered" => Ok(RejectionReason::AlreadyTriggered),
+            "to_self_delay_too_small" => Ok(RejectionReason::ToSelfDelayTooSmall),
+            "rate_limited" => Ok(RejectionReason::RateLimited),
+            _ => Err(format!("Unknown rejection reason: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RejectionReason::Unknown => "unknown",
+            RejectionReason::AuthenticationFailure => "authentication_failure",
+            RejectionReason::NotEnoughSlots => "not_enough_slots",
+            RejectionReason::SubscriptionExpired => "subscription_expired",
+            RejectionReason::AlreadyTriggered => "already_triggered",
+            RejectionReason::ToSelfDelayTooSmall => "to_self_delay_too_small",
+            RejectionReason::RateLimited => "rate_limited",
+        };
+        write!(f, "{s}")
+    }
+}
+
 impl Appointment {
     /// Creates a new [Appointment] instance.
     pub fn new(locator: Locator, encrypted_blob: Vec<u8>, to_self_delay: u32) -> Self {
@@ -151,3 +210,11 @@ impl From<Appointment> for msgs::Appointment {
 pub fn compute_appointment_slots(blob_size: usize, blob_max_size: usize) -> u32 {
     (blob_size as f32 / blob_max_size as f32).ceil() as u32
 }
+
+/// Builds the message a user signs to request the deletion of an appointment from a tower.
+///
+/// The message commits to the block the tower started watching the appointment at (as found in the appointment receipt)
+/// and to the tower id, so the request cannot be replayed once the appointment is sent again, nor sent to other towers.
+pub fn deletion_message(locator: &Locator, start_block: u32, tower_id: &TowerId) -> String {
+    format!("delete appointment {locator} from block {start_block} at {tower_id}")
+}
@@ -7,11 +7,20 @@ use chacha20poly1305::aead::{Aead, NewAead};
 use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 
 use bitcoin::consensus;
-use bitcoin::hashes::{sha256, Hash};
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
 use bitcoin::secp256k1::{Error, PublicKey, Secp256k1, SecretKey};
 use bitcoin::{Transaction, Txid};
 use lightning::util::message_signing;
 
+/// Number of PBKDF2 rounds used to derive encryption keys from passphrases.
+pub const PASSPHRASE_KDF_ROUNDS: u32 = 100_000;
+
+/// Size of the salt used to derive encryption keys from passphrases.
+const SALT_SIZE: usize = 16;
+
+/// Size of the `chacha20poly1305` nonce.
+const NONCE_SIZE: usize = 12;
+
 /// Enum representing the possible errors when decrypting an encrypted blob.
 #[derive(Debug)]
 pub enum DecryptingError {
@@ -75,6 +84,62 @@ pub fn decrypt(encrypted_blob: &[u8], secret: &Txid) -> Result<Transaction, Decr
     }
 }
 
+/// Derives a 32-byte key from a passphrase and a salt using `PBKDF2-HMAC-SHA256`.
+pub fn derive_key(passphrase: &[u8], salt: &[u8], rounds: u32) -> [u8; 32] {
+    // The derived key is exactly one block long, so only the first block of PBKDF2 needs to be computed
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(passphrase);
+    engine.input(salt);
+    engine.input(&1u32.to_be_bytes());
+    let mut u = hmac::Hmac::from_engine(engine).into_inner();
+    let mut key = u;
+
+    for _ in 1..rounds {
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(passphrase);
+        engine.input(&u);
+        u = hmac::Hmac::from_engine(engine).into_inner();
+        key.iter_mut().zip(u.iter()).for_each(|(k, x)| *k ^= x);
+    }
+
+    key
+}
+
+/// Encrypts some data under a passphrase using `chacha20poly1305`.
+///
+/// The key material used is:
+/// - A key derived from the passphrase and a random salt (see [derive_key]).
+/// - A random IV.
+///
+/// The output is `salt | IV | ciphertext`, so it can be decrypted with nothing but the passphrase.
+pub fn encrypt_with_passphrase(data: &[u8], passphrase: &str) -> Vec<u8> {
+    let salt = get_random_bytes(SALT_SIZE);
+    let raw_nonce = get_random_bytes(NONCE_SIZE);
+    let key = derive_key(passphrase.as_bytes(), &salt, PASSPHRASE_KDF_ROUNDS);
+
+    let cypher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    // Encrypting with a freshly derived key and nonce cannot fail
+    let ciphertext = cypher.encrypt(Nonce::from_slice(&raw_nonce), data).unwrap();
+
+    [salt, raw_nonce, ciphertext].concat()
+}
+
+/// Decrypts some data encrypted by [encrypt_with_passphrase].
+///
+/// Fails if the passphrase is not the one the data was encrypted under, or if the data has been tampered with.
+pub fn decrypt_with_passphrase(
+    encrypted: &[u8],
+    passphrase: &str,
+) -> Result<Vec<u8>, chacha20poly1305::aead::Error> {
+    if encrypted.len() < SALT_SIZE + NONCE_SIZE {
+        return Err(chacha20poly1305::aead::Error);
+    }
+    let (salt, rest) = encrypted.split_at(SALT_SIZE);
+    let (raw_nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    let key = derive_key(passphrase.as_bytes(), salt, PASSPHRASE_KDF_ROUNDS);
+
+    let cypher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    cypher.decrypt(Nonce::from_slice(raw_nonce), ciphertext)
+}
+
 /// Utility function to create a vector of pseudo random bytes.
 ///
 /// Mainly used for testing purposes.
@@ -125,4 +190,37 @@ mod tests {
         let txid = Txid::from_hex(HEX_TXID).unwrap();
         assert_eq!(decrypt(&encrypted_blob, &txid).unwrap(), expected_tx);
     }
+
+    #[test]
+    fn test_derive_key() {
+        // PBKDF2-HMAC-SHA256 test vectors
+        assert_eq!(
+            derive_key(b"password", b"salt", 1).to_vec(),
+            Vec::from_hex("120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b")
+                .unwrap()
+        );
+        assert_eq!(
+            derive_key(b"password", b"salt", 4096).to_vec(),
+            Vec::from_hex("c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_with_passphrase() {
+        let data = get_random_bytes(32);
+        let encrypted = encrypt_with_passphrase(&data, "passphrase");
+        assert_eq!(encrypted.len(), SALT_SIZE + NONCE_SIZE + data.len() + 16);
+        assert_eq!(
+            decrypt_with_passphrase(&encrypted, "passphrase").unwrap(),
+            data
+        );
+
+        // Decrypting under a different passphrase, or tampered data, fails
+        assert!(decrypt_with_passphrase(&encrypted, "wrong passphrase").is_err());
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt_with_passphrase(&tampered, "passphrase").is_err());
+        assert!(decrypt_with_passphrase(&encrypted[..SALT_SIZE], "passphrase").is_err());
+    }
 }
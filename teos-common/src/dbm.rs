@@ -2,8 +2,19 @@
 //! that can be used by both clients and towers.
 //!
 
+use std::fmt;
+
 use rusqlite::ffi::{SQLITE_CONSTRAINT_FOREIGNKEY, SQLITE_CONSTRAINT_PRIMARYKEY};
-use rusqlite::{Connection, Error as SqliteError, ErrorCode, Params};
+use rusqlite::{params, Connection, Error as SqliteError, ErrorCode, Params};
+
+use bitcoin::hashes::{sha256, Hash};
+
+/// Table keeping track of the migrations applied to a database.
+pub const SCHEMA_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    checksum TEXT NOT NULL
+)";
 
 /// Packs the errors than can raise when interacting with the underlying database.
 #[derive(Debug)]
@@ -13,6 +24,109 @@ pub enum Error {
     MissingField,
     NotFound,
     Unknown(SqliteError),
+    /// Errors coming from database backends other than `SQLite`.
+    Backend(String),
+}
+
+/// A step in the history of a database schema.
+///
+/// Migrations are applied in order, and only once. The version of a migration is its position in the list of
+/// migrations of a database, starting at 1. Released migrations must not be modified, given the checksum of the applied
+/// ones is checked every time the database is opened. Changes to the schema go into new migrations instead.
+#[derive(Debug)]
+pub struct Migration {
+    /// Short description of the changes made by the migration.
+    pub description: &'static str,
+    /// The statements run by the migration.
+    pub statements: &'static [&'static str],
+}
+
+impl Migration {
+    /// Computes the checksum of the migration, that is, the hex encoded SHA256 of its statements.
+    pub fn checksum(&self) -> String {
+        sha256::Hash::hash(self.statements.join(";\n").as_bytes()).to_string()
+    }
+}
+
+/// Errors that can raise when migrating a database schema.
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database has been migrated past the latest known migration (e.g. by a newer version of the software).
+    NewerSchema {
+        version: u32,
+        latest: u32,
+    },
+    /// An applied migration does not match the known one.
+    ChecksumMismatch(u32),
+    /// The versions of the applied migrations are not contiguous starting at 1.
+    UnexpectedVersion {
+        version: u32,
+        expected: u32,
+    },
+    Sqlite(SqliteError),
+    /// Errors coming from database backends other than `SQLite`.
+    Backend(String),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::NewerSchema { version, latest } => write!(
+                f,
+                "database schema version ({version}) is newer than the latest supported one ({latest})"
+            ),
+            MigrationError::ChecksumMismatch(version) => write!(
+                f,
+                "applied migration {version} does not match the expected one"
+            ),
+            MigrationError::UnexpectedVersion { version, expected } => write!(
+                f,
+                "applied migration {version} found where migration {expected} was expected"
+            ),
+            MigrationError::Sqlite(e) => write!(f, "{e}"),
+            MigrationError::Backend(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<SqliteError> for MigrationError {
+    fn from(e: SqliteError) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+/// Checks the migrations applied to a database, as (version, checksum) pairs sorted by version, against the known ones.
+///
+/// Returns the migrations pending to be applied alongside their version. Fails if the applied versions are not
+/// contiguous starting at 1, given the schema cannot be told apart otherwise.
+pub fn pending_migrations<'a>(
+    applied: &[(u32, String)],
+    migrations: &'a [Migration],
+) -> Result<Vec<(u32, &'a Migration)>, MigrationError> {
+    for ((version, _), expected) in applied.iter().zip(1..) {
+        if *version != expected {
+            return Err(MigrationError::UnexpectedVersion {
+                version: *version,
+                expected,
+            });
+        }
+    }
+
+    let latest = migrations.len() as u32;
+    let version = applied.last().map_or(0, |(version, _)| *version);
+    if version > latest {
+        return Err(MigrationError::NewerSchema { version, latest });
+    }
+
+    for (version, checksum) in applied {
+        if migrations[*version as usize - 1].checksum() != *checksum {
+            return Err(MigrationError::ChecksumMismatch(*version));
+        }
+    }
+
+    Ok((version + 1..)
+        .zip(migrations[version as usize..].iter())
+        .collect())
 }
 
 pub trait DatabaseConnection {
@@ -22,6 +136,9 @@ pub trait DatabaseConnection {
 
 pub trait DatabaseManager: Sized {
     fn create_tables(&mut self, tables: Vec<&str>) -> Result<(), SqliteError>;
+    fn add_missing_columns(&self, columns: &[(&str, &str, &str)]) -> Result<(), SqliteError>;
+    fn schema_version(&self) -> Result<u32, SqliteError>;
+    fn migrate(&mut self, migrations: &[Migration]) -> Result<(), MigrationError>;
     fn store_data<P: Params>(&self, query: &str, params: P) -> Result<(), Error>;
     fn remove_data<P: Params>(&self, query: &str, params: P) -> Result<(), Error>;
     fn update_data<P: Params>(&self, query: &str, params: P) -> Result<(), Error>;
@@ -37,6 +154,72 @@ impl<T: DatabaseConnection> DatabaseManager for T {
         tx.commit()
     }
 
+    /// Adds the given columns, as (table, column, definition), to the database tables if not present.
+    ///
+    /// Used for columns added to tables after they were first released, given `create_tables` does not modify existing tables.
+    fn add_missing_columns(&self, columns: &[(&str, &str, &str)]) -> Result<(), SqliteError> {
+        for (table, column, definition) in columns.iter() {
+            if self
+                .get_connection()
+                .prepare(&format!("SELECT {column} FROM {table} LIMIT 0"))
+                .is_err()
+            {
+                self.get_connection().execute(
+                    &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                    [],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the schema version of the database, that is, the version of the latest migration applied to it.
+    ///
+    /// Databases created before their schema was versioned are at version 0.
+    fn schema_version(&self) -> Result<u32, SqliteError> {
+        let versioned: bool = self.get_connection().query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name='schema_version')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !versioned {
+            return Ok(0);
+        }
+
+        self.get_connection().query_row(
+            "SELECT IFNULL(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Applies the pending migrations to the database, in a single transaction.
+    ///
+    /// Fails if the database has been migrated past the given migrations, or if any of the applied ones has been modified.
+    fn migrate(&mut self, migrations: &[Migration]) -> Result<(), MigrationError> {
+        let tx = self.get_mut_connection().transaction()?;
+        tx.execute(SCHEMA_VERSION_TABLE, [])?;
+
+        let applied = tx
+            .prepare("SELECT version, checksum FROM schema_version ORDER BY version")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(u32, String)>, SqliteError>>()?;
+
+        for (version, migration) in pending_migrations(&applied, migrations)? {
+            for statement in migration.statements {
+                tx.execute(statement, [])?;
+            }
+            tx.execute(
+                "INSERT INTO schema_version (version, description, checksum) VALUES (?1, ?2, ?3)",
+                params![version, migration.description, migration.checksum()],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Generic method to store data into the database.
     fn store_data<P: Params>(&self, query: &str, params: P) -> Result<(), Error> {
         match self.get_connection().execute(query, params) {
@@ -57,9 +240,10 @@ impl<T: DatabaseConnection> DatabaseManager for T {
 
     /// Generic method to remove data from the database.
     fn remove_data<P: Params>(&self, query: &str, params: P) -> Result<(), Error> {
-        match self.get_connection().execute(query, params).unwrap() {
-            0 => Err(Error::NotFound),
-            _ => Ok(()),
+        match self.get_connection().execute(query, params) {
+            Ok(0) => Err(Error::NotFound),
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Unknown(e)),
         }
     }
 
@@ -70,3 +254,139 @@ impl<T: DatabaseConnection> DatabaseManager for T {
         self.remove_data(query, params)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: [Migration; 2] = [
+        Migration {
+            description: "Create the users table",
+            statements: &["CREATE TABLE users (user_id INT PRIMARY KEY)"],
+        },
+        Migration {
+            description: "Add the slots to the users table",
+            statements: &[
+                "ALTER TABLE users ADD COLUMN slots INT NOT NULL DEFAULT 0",
+                "CREATE TABLE banned_users (user_id INT PRIMARY KEY)",
+            ],
+        },
+    ];
+
+    struct TestDBM {
+        connection: Connection,
+    }
+
+    impl DatabaseConnection for TestDBM {
+        fn get_connection(&self) -> &Connection {
+            &self.connection
+        }
+
+        fn get_mut_connection(&mut self) -> &mut Connection {
+            &mut self.connection
+        }
+    }
+
+    impl TestDBM {
+        fn in_memory() -> Self {
+            TestDBM {
+                connection: Connection::open_in_memory().unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut dbm = TestDBM::in_memory();
+        assert_eq!(dbm.schema_version().unwrap(), 0);
+
+        // Migrations are applied up to the latest one
+        dbm.migrate(&MIGRATIONS[..1]).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), 1);
+        dbm.migrate(&MIGRATIONS).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), 2);
+        dbm.store_data("INSERT INTO users (user_id, slots) VALUES (1, 10)", [])
+            .unwrap();
+
+        // Migrating again is a no-op
+        dbm.migrate(&MIGRATIONS).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_migrate_newer_schema() {
+        let mut dbm = TestDBM::in_memory();
+        dbm.migrate(&MIGRATIONS).unwrap();
+
+        // Databases migrated by a newer version cannot be opened
+        assert!(matches!(
+            dbm.migrate(&MIGRATIONS[..1]),
+            Err(MigrationError::NewerSchema {
+                version: 2,
+                latest: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_checksum_mismatch() {
+        let mut dbm = TestDBM::in_memory();
+        dbm.migrate(&MIGRATIONS[..1]).unwrap();
+
+        // Applied migrations cannot be modified
+        let modified = [Migration {
+            description: "Create the users table",
+            statements: &["CREATE TABLE users (user_id BLOB PRIMARY KEY)"],
+        }];
+        assert!(matches!(
+            dbm.migrate(&modified),
+            Err(MigrationError::ChecksumMismatch(1))
+        ));
+    }
+
+    #[test]
+    fn test_pending_migrations_unexpected_version() {
+        let checksum = MIGRATIONS[0].checksum();
+
+        // Versions start at 1
+        assert!(matches!(
+            pending_migrations(&[(0, checksum.clone())], &MIGRATIONS),
+            Err(MigrationError::UnexpectedVersion {
+                version: 0,
+                expected: 1
+            })
+        ));
+
+        // And have no gaps
+        assert!(matches!(
+            pending_migrations(&[(1, checksum.clone()), (3, checksum)], &MIGRATIONS),
+            Err(MigrationError::UnexpectedVersion {
+                version: 3,
+                expected: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_failure_rolls_back() {
+        let mut dbm = TestDBM::in_memory();
+        let migrations = [
+            Migration {
+                description: "Create the users table",
+                statements: &["CREATE TABLE users (user_id INT PRIMARY KEY)"],
+            },
+            Migration {
+                description: "Broken migration",
+                statements: &["ALTER TABLE unknown ADD COLUMN slots INT"],
+            },
+        ];
+
+        // None of the migrations is applied if any of them fails
+        assert!(matches!(
+            dbm.migrate(&migrations),
+            Err(MigrationError::Sqlite(..))
+        ));
+        assert_eq!(dbm.schema_version().unwrap(), 0);
+        assert!(dbm.get_connection().prepare("SELECT * FROM users").is_err());
+    }
+}
@@ -6,6 +6,7 @@ pub const WRONG_FIELD_SIZE: u8 = 4;
 pub const WRONG_FIELD_FORMAT: u8 = 5;
 pub const INVALID_REQUEST_FORMAT: u8 = 6;
 pub const INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR: u8 = 7;
+pub const RATE_LIMITED: u8 = 31;
 pub const SERVICE_UNAVAILABLE: u8 = 32;
 
 /// Appointment errors [33, 64]
@@ -13,9 +14,12 @@ pub const APPOINTMENT_FIELD_TOO_SMALL: u8 = 33;
 pub const APPOINTMENT_FIELD_TOO_BIG: u8 = 34;
 pub const APPOINTMENT_ALREADY_TRIGGERED: u8 = 35;
 pub const APPOINTMENT_NOT_FOUND: u8 = 36;
+pub const APPOINTMENT_TO_SELF_DELAY_TOO_SMALL: u8 = 37;
 
 /// Registration errors [65, 96]
 pub const REGISTRATION_RESOURCE_EXHAUSTED: u8 = 65;
+pub const REGISTRATION_PAYMENT_REQUIRED: u8 = 66;
+pub const REGISTRATION_PROOF_REQUIRED: u8 = 67;
 
 /// UNHANDLED
 pub const UNEXPECTED_ERROR: u8 = 255;
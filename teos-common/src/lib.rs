@@ -8,6 +8,7 @@ pub mod protos {
     tonic::include_proto!("common.teos.v2");
 }
 
+pub mod anti_spam;
 pub mod appointment;
 pub mod constants;
 pub mod cryptography;
@@ -1,8 +1,13 @@
 pub enum Endpoint {
     Register,
+    Renew,
+    TopUp,
     AddAppointment,
+    AddAppointments,
     GetAppointment,
     GetSubscriptionInfo,
+    GetSubscriptionTiers,
+    DeleteAppointment,
     Ping,
 }
 
@@ -13,9 +18,14 @@ impl std::fmt::Display for Endpoint {
             "{}",
             match self {
                 Endpoint::Register => "register",
+                Endpoint::Renew => "renew",
+                Endpoint::TopUp => "top_up",
                 Endpoint::AddAppointment => "add_appointment",
+                Endpoint::AddAppointments => "add_appointments",
                 Endpoint::GetAppointment => "get_appointment",
                 Endpoint::GetSubscriptionInfo => "get_subscription_info",
+                Endpoint::GetSubscriptionTiers => "get_subscription_tiers",
+                Endpoint::DeleteAppointment => "delete_appointment",
                 Endpoint::Ping => "ping",
             }
         )
@@ -4,7 +4,16 @@ use serde::Serialize;
 
 use bitcoin::secp256k1::SecretKey;
 
-use crate::{cryptography, UserId};
+use crate::appointment::Locator;
+use crate::{cryptography, TowerId, UserId};
+
+/// Builds the message a user signs to request a registration with a tower.
+///
+/// Registrations are signed so no one else can register a user (e.g. to a tier they did not pick). The message commits
+/// to the tower id, so the request cannot be sent to other towers.
+pub fn registration_message(tier: &str, tower_id: &TowerId) -> String {
+    format!("register to tier '{tier}' at {tower_id}")
+}
 
 /// Proof that a user has registered with a tower. This serves two purposes:
 ///
@@ -25,6 +34,8 @@ pub struct RegistrationReceipt {
     available_slots: u32,
     subscription_start: u32,
     subscription_expiry: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    tier: String,
     #[serde(rename = "subscription_signature")]
     signature: Option<String>,
 }
@@ -35,12 +46,14 @@ impl RegistrationReceipt {
         available_slots: u32,
         subscription_start: u32,
         subscription_expiry: u32,
+        tier: String,
     ) -> Self {
         RegistrationReceipt {
             user_id,
             available_slots,
             subscription_start,
             subscription_expiry,
+            tier,
             signature: None,
         }
     }
@@ -50,6 +63,7 @@ impl RegistrationReceipt {
         available_slots: u32,
         subscription_start: u32,
         subscription_expiry: u32,
+        tier: String,
         signature: String,
     ) -> Self {
         RegistrationReceipt {
@@ -57,6 +71,7 @@ impl RegistrationReceipt {
             available_slots,
             subscription_start,
             subscription_expiry,
+            tier,
             signature: Some(signature),
         }
     }
@@ -77,6 +92,10 @@ impl RegistrationReceipt {
         self.subscription_expiry
     }
 
+    pub fn tier(&self) -> &str {
+        &self.tier
+    }
+
     pub fn signature(&self) -> Option<String> {
         self.signature.clone()
     }
@@ -87,6 +106,161 @@ impl RegistrationReceipt {
         ser.extend_from_slice(&self.available_slots.to_be_bytes());
         ser.extend_from_slice(&self.subscription_start.to_be_bytes());
         ser.extend_from_slice(&self.subscription_expiry.to_be_bytes());
+        // Unnamed tiers add nothing, so receipts of towers with no tiers are serialized as they always have
+        ser.extend_from_slice(self.tier.as_bytes());
+
+        ser
+    }
+
+    pub fn sign(&mut self, sk: &SecretKey) {
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        self.signature = Some(cryptography::sign(&self.to_vec(), sk).unwrap());
+    }
+
+    pub fn verify(&self, id: &UserId) -> bool {
+        if let Some(signature) = self.signature() {
+            cryptography::verify(&self.to_vec(), &signature, &id.0)
+        } else {
+            false
+        }
+    }
+}
+
+/// Proof that a user has renewed their subscription with a tower.
+///
+/// The receipt covers the subscription period after the renewal (`subscription_start` - `subscription_expiry`). Notice the
+/// start only moves forward if the subscription had already expired when renewed, given there is a gap between the two.
+#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+pub struct RenewalReceipt {
+    user_id: UserId,
+    subscription_start: u32,
+    subscription_expiry: u32,
+    #[serde(rename = "subscription_signature")]
+    signature: Option<String>,
+}
+
+impl RenewalReceipt {
+    pub fn new(user_id: UserId, subscription_start: u32, subscription_expiry: u32) -> Self {
+        RenewalReceipt {
+            user_id,
+            subscription_start,
+            subscription_expiry,
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(
+        user_id: UserId,
+        subscription_start: u32,
+        subscription_expiry: u32,
+        signature: String,
+    ) -> Self {
+        RenewalReceipt {
+            user_id,
+            subscription_start,
+            subscription_expiry,
+            signature: Some(signature),
+        }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn subscription_start(&self) -> u32 {
+        self.subscription_start
+    }
+
+    pub fn subscription_expiry(&self) -> u32 {
+        self.subscription_expiry
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        // Tagged so the signature cannot be passed off as the one of a top up (which has the same layout)
+        let mut ser = b"renewal".to_vec();
+        ser.extend_from_slice(&self.user_id.to_vec());
+        ser.extend_from_slice(&self.subscription_start.to_be_bytes());
+        ser.extend_from_slice(&self.subscription_expiry.to_be_bytes());
+
+        ser
+    }
+
+    pub fn sign(&mut self, sk: &SecretKey) {
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        self.signature = Some(cryptography::sign(&self.to_vec(), sk).unwrap());
+    }
+
+    pub fn verify(&self, id: &UserId) -> bool {
+        if let Some(signature) = self.signature() {
+            cryptography::verify(&self.to_vec(), &signature, &id.0)
+        } else {
+            false
+        }
+    }
+}
+
+/// Proof that a user has topped up their subscription with a tower.
+///
+/// The receipt states the slots available to the user after the top up, which can be used until `subscription_expiry`.
+#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+pub struct TopUpReceipt {
+    user_id: UserId,
+    available_slots: u32,
+    subscription_expiry: u32,
+    #[serde(rename = "subscription_signature")]
+    signature: Option<String>,
+}
+
+impl TopUpReceipt {
+    pub fn new(user_id: UserId, available_slots: u32, subscription_expiry: u32) -> Self {
+        TopUpReceipt {
+            user_id,
+            available_slots,
+            subscription_expiry,
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(
+        user_id: UserId,
+        available_slots: u32,
+        subscription_expiry: u32,
+        signature: String,
+    ) -> Self {
+        TopUpReceipt {
+            user_id,
+            available_slots,
+            subscription_expiry,
+            signature: Some(signature),
+        }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn available_slots(&self) -> u32 {
+        self.available_slots
+    }
+
+    pub fn subscription_expiry(&self) -> u32 {
+        self.subscription_expiry
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        // Tagged so the signature cannot be passed off as the one of a renewal (which has the same layout)
+        let mut ser = b"top_up".to_vec();
+        ser.extend_from_slice(&self.user_id.to_vec());
+        ser.extend_from_slice(&self.available_slots.to_be_bytes());
+        ser.extend_from_slice(&self.subscription_expiry.to_be_bytes());
 
         ser
     }
@@ -165,3 +339,78 @@ impl AppointmentReceipt {
         }
     }
 }
+
+/// Proof that an appointment was deleted from the tower at the user's request.
+///
+/// Deletion receipts can be used by the tower to prove it was released from watching for a given appointment after
+/// `deletion_block`, and by the user to prove the slots used by the appointment were given back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeletionReceipt {
+    locator: Locator,
+    user_signature: String,
+    deletion_block: u32,
+    signature: Option<String>,
+}
+
+impl DeletionReceipt {
+    pub fn new(locator: Locator, user_signature: String, deletion_block: u32) -> Self {
+        DeletionReceipt {
+            locator,
+            user_signature,
+            deletion_block,
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(
+        locator: Locator,
+        user_signature: String,
+        deletion_block: u32,
+        signature: String,
+    ) -> Self {
+        DeletionReceipt {
+            locator,
+            user_signature,
+            deletion_block,
+            signature: Some(signature),
+        }
+    }
+
+    pub fn locator(&self) -> Locator {
+        self.locator
+    }
+
+    pub fn user_signature(&self) -> &str {
+        &self.user_signature
+    }
+
+    pub fn deletion_block(&self) -> u32 {
+        self.deletion_block
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut ser = Vec::new();
+        ser.extend_from_slice(&self.locator.to_vec());
+        ser.extend_from_slice(self.user_signature.as_bytes());
+        ser.extend_from_slice(&self.deletion_block.to_be_bytes());
+
+        ser
+    }
+
+    pub fn sign(&mut self, sk: &SecretKey) {
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        self.signature = Some(cryptography::sign(&self.to_vec(), sk).unwrap());
+    }
+
+    pub fn verify(&self, id: &UserId) -> bool {
+        if let Some(signature) = self.signature() {
+            cryptography::verify(&self.to_vec(), &signature, &id.0)
+        } else {
+            false
+        }
+    }
+}
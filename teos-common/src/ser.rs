@@ -144,3 +144,44 @@ pub mod serde_status {
         deserializer.deserialize_any(StatusVisitor)
     }
 }
+
+pub mod serde_rejection_reason {
+    use super::*;
+    use serde::de::{self, Deserializer};
+    use std::str::FromStr;
+
+    use crate::appointment::RejectionReason;
+
+    pub fn serialize<S>(reason: &i32, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&RejectionReason::from(*reason).to_string())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<i32, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ReasonVisitor;
+
+        impl<'de> de::Visitor<'de> for ReasonVisitor {
+            type Value = i32;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a string containing the rejection reason")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let reason = RejectionReason::from_str(v)
+                    .map_err(|_| E::custom("given rejection reason is unknown"))?;
+                Ok(reason as i32)
+            }
+        }
+
+        deserializer.deserialize_any(ReasonVisitor)
+    }
+}
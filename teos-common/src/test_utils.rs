@@ -55,8 +55,13 @@ pub fn generate_random_appointment(dispute_txid: Option<&Txid>) -> Appointment {
 pub fn get_random_registration_receipt() -> RegistrationReceipt {
     let (sk, _) = cryptography::get_random_keypair();
     let start = get_random_int();
-    let mut receipt =
-        RegistrationReceipt::new(get_random_user_id(), get_random_int(), start, start + 420);
+    let mut receipt = RegistrationReceipt::new(
+        get_random_user_id(),
+        get_random_int(),
+        start,
+        start + 420,
+        String::new(),
+    );
     receipt.sign(&sk);
 
     receipt
@@ -69,6 +74,7 @@ pub fn get_registration_receipt_from_previous(r: &RegistrationReceipt) -> Regist
         r.available_slots() + 1 + get_random_int::<u8>() as u32,
         r.subscription_start(),
         r.subscription_expiry() + 1 + get_random_int::<u8>() as u32,
+        r.tier().to_owned(),
     );
     receipt.sign(&sk);
 
@@ -8,6 +8,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "user_ids",
             "#[serde(serialize_with = \"teos_common::ser::serde_vec_bytes::serialize\")]",
         )
+        .field_attribute(
+            "blinded_tokens",
+            "#[serde(with = \"teos_common::ser::serde_vec_bytes\")]",
+        )
+        .field_attribute(
+            "blinded_signatures",
+            "#[serde(with = \"teos_common::ser::serde_vec_bytes\")]",
+        )
+        .field_attribute(
+            "IssueRegistrationTokensResponse.proofs",
+            "#[serde(with = \"teos_common::ser::serde_vec_bytes\")]",
+        )
+        .field_attribute(
+            "IssueRegistrationTokensResponse.token_key",
+            "#[serde(with = \"hex::serde\")]",
+        )
         .field_attribute(
             "GetUserResponse.appointments",
             "#[serde(serialize_with = \"teos_common::ser::serde_vec_bytes::serialize\")]",
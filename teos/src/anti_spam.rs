@@ -0,0 +1,415 @@
+//! Logic related to gating user registrations, so the tower cannot be filled with throwaway users for free.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+
+use teos_common::anti_spam::{
+    check_pow, sign_token, token_key, verify_token, TokenProof, TOKEN_SIZE,
+};
+use teos_common::dbm::Error as DBError;
+use teos_common::UserId;
+
+use crate::dbm::Storage;
+use crate::payments::unix_time;
+
+/// Challenge a user has to solve in order to register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// The data to be hashed alongside the nonce.
+    pub challenge: [u8; 32],
+    /// The number of leading zero bits the resulting hash must have.
+    pub difficulty: u8,
+    /// Unix time (in seconds) after which the challenge cannot be solved anymore.
+    pub expires_at: u64,
+}
+
+/// Proof attached by a user to their registration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationProof {
+    /// The solution to a [Challenge] issued by the tower.
+    ProofOfWork { expires_at: u64, nonce: u64 },
+    /// A token issued by the tower, alongside its (unblinded) signature.
+    Token {
+        token: Vec<u8>,
+        signature: PublicKey,
+    },
+}
+
+/// Reasons why a registration is not let through the gate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GateRejection {
+    /// The user needs to solve a challenge. Contains a fresh challenge and why the provided proof (if any) is not valid.
+    ChallengeRequired(Challenge, &'static str),
+    /// The user needs to redeem a token. Contains the key tokens are signed with and why the provided proof (if any) is
+    /// not valid.
+    TokenRequired(PublicKey, &'static str),
+}
+
+impl GateRejection {
+    /// Gets a human readable description of the rejection.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            GateRejection::ChallengeRequired(_, reason)
+            | GateRejection::TokenRequired(_, reason) => reason,
+        }
+    }
+}
+
+/// A batch of blinded tokens signed by the tower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuedTokens {
+    /// The key the tokens are signed with (see [token_key]).
+    pub token_key: PublicKey,
+    /// The signatures of the blinded tokens, alongside the proofs they have been made with the token key.
+    pub signatures: Vec<(PublicKey, TokenProof)>,
+}
+
+/// Gate in front of user registrations.
+///
+/// Users that are not registered yet need to provide a proof for the tower to accept their registration.
+pub trait RegistrationGate: Send + Sync + fmt::Debug {
+    /// Checks the proof attached to the registration of a given user. Proofs that can only be used once are consumed.
+    fn admit(
+        &self,
+        user_id: UserId,
+        proof: Option<&RegistrationProof>,
+    ) -> Result<(), GateRejection>;
+
+    /// Gives back a proof consumed by a registration that could not be completed.
+    fn release(&self, _proof: &RegistrationProof) {}
+
+    /// Signs a batch of blinded tokens, to be handed to users out of band. Returns `None` if the gate does not work
+    /// with tokens.
+    fn issue_tokens(&self, _blinded_tokens: &[PublicKey]) -> Option<IssuedTokens> {
+        None
+    }
+}
+
+/// Derives a secret bound to the tower from its secret key, so it survives restarts without being stored.
+pub fn derive_secret(tower_sk: &SecretKey, tag: &str) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_bytes());
+    engine.input(&tower_sk.secret_bytes());
+    sha256::Hash::from_engine(engine).into_inner()
+}
+
+/// Gate requiring users to solve a hashcash challenge to register.
+///
+/// Challenges are derived from a tower secret, the user id and their expiry, so they do not need to be stored.
+#[derive(Debug)]
+pub struct ProofOfWorkGate {
+    /// Secret challenges are derived from.
+    secret: [u8; 32],
+    /// Number of leading zero bits required for a challenge to be solved.
+    difficulty: u8,
+    /// Number of seconds users have to solve a challenge.
+    challenge_expiry: u64,
+}
+
+impl ProofOfWorkGate {
+    /// Creates a new [ProofOfWorkGate] instance.
+    pub fn new(secret: [u8; 32], difficulty: u8, challenge_expiry: u64) -> Self {
+        ProofOfWorkGate {
+            secret,
+            difficulty,
+            challenge_expiry,
+        }
+    }
+
+    /// Builds the challenge of a given user for a given expiry.
+    fn challenge(&self, user_id: UserId, expires_at: u64) -> Challenge {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&self.secret);
+        engine.input(&user_id.to_vec());
+        engine.input(&expires_at.to_be_bytes());
+
+        Challenge {
+            challenge: sha256::Hash::from_engine(engine).into_inner(),
+            difficulty: self.difficulty,
+            expires_at,
+        }
+    }
+
+    /// Checks the proof attached to the registration of a given user at a given unix time.
+    fn admit_at(
+        &self,
+        user_id: UserId,
+        proof: Option<&RegistrationProof>,
+        now: u64,
+    ) -> Result<(), GateRejection> {
+        let reason = match proof {
+            Some(RegistrationProof::ProofOfWork { expires_at, nonce }) => {
+                if now >= *expires_at {
+                    "Registration challenge expired"
+                } else if !check_pow(
+                    &self.challenge(user_id, *expires_at).challenge,
+                    *nonce,
+                    self.difficulty,
+                ) {
+                    "Invalid registration challenge solution"
+                } else {
+                    return Ok(());
+                }
+            }
+            _ => "Registration requires solving a challenge",
+        };
+
+        Err(GateRejection::ChallengeRequired(
+            self.challenge(user_id, now + self.challenge_expiry),
+            reason,
+        ))
+    }
+}
+
+impl RegistrationGate for ProofOfWorkGate {
+    fn admit(
+        &self,
+        user_id: UserId,
+        proof: Option<&RegistrationProof>,
+    ) -> Result<(), GateRejection> {
+        self.admit_at(user_id, proof, unix_time())
+    }
+}
+
+/// Gate requiring users to redeem a token issued by the tower to register.
+///
+/// Tokens are signed blindly (see [teos_common::anti_spam]), so the tower can hand them out in exchange for something
+/// else (e.g. a payment) without being able to link them to the users redeeming them. Redeemed tokens are stored so
+/// they cannot be used twice.
+#[derive(Debug)]
+pub struct TokenGate {
+    /// Key used to sign tokens.
+    key: SecretKey,
+    /// The public counterpart of the signing key. Published so users can check their tokens are signed with it.
+    token_key: PublicKey,
+    /// A [Storage] instance, used to keep track of redeemed tokens.
+    dbm: Arc<Mutex<dyn Storage>>,
+}
+
+impl TokenGate {
+    /// Creates a new [TokenGate] instance.
+    pub fn new(key: SecretKey, dbm: Arc<Mutex<dyn Storage>>) -> Self {
+        TokenGate {
+            key,
+            token_key: token_key(&key),
+            dbm,
+        }
+    }
+}
+
+impl RegistrationGate for TokenGate {
+    fn admit(
+        &self,
+        _user_id: UserId,
+        proof: Option<&RegistrationProof>,
+    ) -> Result<(), GateRejection> {
+        match proof {
+            Some(RegistrationProof::Token { token, signature }) => {
+                if token.len() != TOKEN_SIZE || !verify_token(token, signature, &self.key) {
+                    Err(GateRejection::TokenRequired(
+                        self.token_key,
+                        "Invalid registration token",
+                    ))
+                } else {
+                    match self.dbm.lock().unwrap().store_redeemed_token(token) {
+                        Ok(()) => Ok(()),
+                        Err(DBError::AlreadyExists) => Err(GateRejection::TokenRequired(
+                            self.token_key,
+                            "Registration token already redeemed",
+                        )),
+                        Err(e) => {
+                            log::error!("Cannot store the redeemed token: {e:?}");
+                            Err(GateRejection::TokenRequired(
+                                self.token_key,
+                                "Registration token cannot be redeemed at the moment",
+                            ))
+                        }
+                    }
+                }
+            }
+            _ => Err(GateRejection::TokenRequired(
+                self.token_key,
+                "Registration requires a token",
+            )),
+        }
+    }
+
+    fn release(&self, proof: &RegistrationProof) {
+        if let RegistrationProof::Token { token, .. } = proof {
+            self.dbm.lock().unwrap().remove_redeemed_token(token);
+        }
+    }
+
+    fn issue_tokens(&self, blinded_tokens: &[PublicKey]) -> Option<IssuedTokens> {
+        Some(IssuedTokens {
+            token_key: self.token_key,
+            signatures: blinded_tokens
+                .iter()
+                .map(|blinded_token| sign_token(blinded_token, &self.key))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dbm::DBM;
+    use teos_common::anti_spam::{blind_token, new_token, solve_pow, unblind_signature};
+    use teos_common::cryptography::get_random_keypair;
+    use teos_common::test_utils::get_random_user_id;
+
+    const DIFFICULTY: u8 = 8;
+    const CHALLENGE_EXPIRY: u64 = 600;
+
+    fn get_challenge(gate: &ProofOfWorkGate, user_id: UserId, now: u64) -> Challenge {
+        match gate.admit_at(user_id, None, now) {
+            Err(GateRejection::ChallengeRequired(challenge, _)) => challenge,
+            r => panic!("Unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_pow_admit() {
+        let gate = ProofOfWorkGate::new([1; 32], DIFFICULTY, CHALLENGE_EXPIRY);
+        let user_id = get_random_user_id();
+        let now = 1_000_000;
+
+        // Users with no proof get a challenge to be solved
+        let challenge = get_challenge(&gate, user_id, now);
+        assert_eq!(challenge.difficulty, DIFFICULTY);
+        assert_eq!(challenge.expires_at, now + CHALLENGE_EXPIRY);
+
+        // Solving it grants access until it expires
+        let proof = RegistrationProof::ProofOfWork {
+            expires_at: challenge.expires_at,
+            nonce: solve_pow(&challenge.challenge, DIFFICULTY),
+        };
+        assert_eq!(gate.admit_at(user_id, Some(&proof), now), Ok(()));
+        assert_eq!(
+            gate.admit_at(user_id, Some(&proof), challenge.expires_at - 1),
+            Ok(())
+        );
+        assert!(matches!(
+            gate.admit_at(user_id, Some(&proof), challenge.expires_at),
+            Err(GateRejection::ChallengeRequired(
+                _,
+                "Registration challenge expired"
+            ))
+        ));
+
+        // Wrong solutions are rejected
+        let proof = RegistrationProof::ProofOfWork {
+            expires_at: challenge.expires_at,
+            nonce: (0..)
+                .find(|nonce| !check_pow(&challenge.challenge, *nonce, DIFFICULTY))
+                .unwrap(),
+        };
+        assert!(matches!(
+            gate.admit_at(user_id, Some(&proof), now),
+            Err(GateRejection::ChallengeRequired(
+                _,
+                "Invalid registration challenge solution"
+            ))
+        ));
+
+        // Challenges are bound to the user, their expiry, and the tower secret
+        assert_ne!(get_challenge(&gate, get_random_user_id(), now), challenge);
+        assert_ne!(
+            get_challenge(&gate, user_id, now + 1).challenge,
+            challenge.challenge
+        );
+        let other_gate = ProofOfWorkGate::new([2; 32], DIFFICULTY, CHALLENGE_EXPIRY);
+        assert_ne!(get_challenge(&other_gate, user_id, now), challenge);
+
+        // Tokens are no good to solve challenges
+        assert!(matches!(
+            gate.admit_at(
+                user_id,
+                Some(&RegistrationProof::Token {
+                    token: new_token(),
+                    signature: get_random_keypair().1
+                }),
+                now
+            ),
+            Err(GateRejection::ChallengeRequired(
+                _,
+                "Registration requires solving a challenge"
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_token_admit() {
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let key = get_random_keypair().0;
+        let gate = TokenGate::new(key, dbm);
+        let user_id = get_random_user_id();
+
+        // Users are told the key tokens are signed with
+        assert_eq!(
+            gate.admit(user_id, None),
+            Err(GateRejection::TokenRequired(
+                token_key(&key),
+                "Registration requires a token"
+            ))
+        );
+
+        // Get a token issued by the tower
+        let token = new_token();
+        let (blinded_token, blinding_factor) = blind_token(&token);
+        let issued = gate.issue_tokens(&[blinded_token]).unwrap();
+        assert_eq!(issued.token_key, token_key(&key));
+        let (blinded_signature, signing_proof) = issued.signatures[0];
+        let signature = unblind_signature(
+            &blinded_token,
+            &blinded_signature,
+            &signing_proof,
+            &issued.token_key,
+            &blinding_factor,
+        )
+        .unwrap();
+        let proof = RegistrationProof::Token {
+            token: token.clone(),
+            signature,
+        };
+
+        // Tokens can only be redeemed once, unless they are given back
+        assert_eq!(gate.admit(user_id, Some(&proof)), Ok(()));
+        assert_eq!(
+            gate.admit(user_id, Some(&proof)),
+            Err(GateRejection::TokenRequired(
+                token_key(&key),
+                "Registration token already redeemed"
+            ))
+        );
+        gate.release(&proof);
+        assert_eq!(gate.admit(get_random_user_id(), Some(&proof)), Ok(()));
+
+        // Tokens not signed by the tower are rejected
+        let forged_proof = RegistrationProof::Token {
+            token: new_token(),
+            signature,
+        };
+        assert_eq!(
+            gate.admit(user_id, Some(&forged_proof)),
+            Err(GateRejection::TokenRequired(
+                token_key(&key),
+                "Invalid registration token"
+            ))
+        );
+        let other_key = get_random_keypair().0;
+        let other_gate = TokenGate::new(other_key, Arc::new(Mutex::new(DBM::in_memory().unwrap())));
+        assert_eq!(
+            other_gate.admit(user_id, Some(&proof)),
+            Err(GateRejection::TokenRequired(
+                token_key(&other_key),
+                "Invalid registration token"
+            ))
+        );
+    }
+}
@@ -1,37 +1,115 @@
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::error::Error;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use tokio::time::Duration;
 use tonic::transport::Channel;
 use triggered::{Listener, Trigger};
 use warp::{http::StatusCode, reject, reply, Filter, Rejection, Reply};
 
 use teos_common::appointment::LOCATOR_LEN;
+use teos_common::constants::MAX_APPOINTMENTS_PER_BATCH;
 use teos_common::net::http::Endpoint;
 use teos_common::protos as common_msgs;
 use teos_common::{errors, USER_ID_LEN};
 
+use crate::api::internal::{REGISTRATION_GATE, RETRY_AFTER};
+use crate::gatekeeper::MAX_TIER_NAME_LEN;
 use crate::protos::public_tower_services_client::PublicTowerServicesClient;
+use crate::rate_limiter::RateLimiter;
 
 // TODO: Limit the body length for /add_appointment should not be needed, since slots are consumed proportionally to it.
 // Setting a limit for now just to prevent spam to some extend, but this is likely to be lifted.
-const REGISTER_BODY_LEN: u64 = 87;
+// Registration proofs (if any) add, at most, a hex encoded token and its signature, plus some field names.
+const REGISTRATION_PROOF_LEN: u64 = 256;
+// Registration requests are signed, so they carry a signature field on top of the rest.
+const REGISTER_BODY_LEN: u64 =
+    99 + MAX_TIER_NAME_LEN as u64 + REGISTRATION_PROOF_LEN + GET_SUBSCRIPTION_INFO_BODY_LEN;
+const RENEW_BODY_LEN: u64 = 99;
+const TOP_UP_BODY_LEN: u64 = 99;
 const ADD_APPOINTMENT_BODY_LEN: u64 = 2048;
+const ADD_APPOINTMENTS_BODY_LEN: u64 = ADD_APPOINTMENT_BODY_LEN * MAX_APPOINTMENTS_PER_BATCH as u64;
 const GET_APPOINTMENT_BODY_LEN: u64 = 178;
 const GET_SUBSCRIPTION_INFO_BODY_LEN: u64 = 127;
+// Deletion requests add the start block of the appointment (as a u32 plus its field name) to a locator + signature body.
+const DELETE_APPOINTMENT_BODY_LEN: u64 = 178 + 26;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub(crate) struct ApiError {
     error: String,
     error_code: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rejection: Option<common_msgs::AppointmentRejection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    invoice: Option<common_msgs::RegistrationInvoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    challenge: Option<common_msgs::RegistrationChallenge>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_key: Option<common_msgs::RegistrationTokenKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
 }
 
 impl reject::Reject for ApiError {}
 
 impl ApiError {
     fn new(error: String, error_code: u8) -> Self {
-        ApiError { error, error_code }
+        ApiError {
+            error,
+            error_code,
+            rejection: None,
+            invoice: None,
+            challenge: None,
+            token_key: None,
+            retry_after: None,
+        }
+    }
+
+    fn with_rejection(mut self, rejection: common_msgs::AppointmentRejection) -> Self {
+        self.rejection = Some(rejection);
+        self
+    }
+
+    fn with_invoice(mut self, invoice: common_msgs::RegistrationInvoice) -> Self {
+        self.invoice = Some(invoice);
+        self
+    }
+
+    fn with_challenge(mut self, challenge: common_msgs::RegistrationChallenge) -> Self {
+        self.challenge = Some(challenge);
+        self
+    }
+
+    fn with_token_key(mut self, token_key: common_msgs::RegistrationTokenKey) -> Self {
+        self.token_key = Some(token_key);
+        self
+    }
+
+    fn with_retry_after(mut self, retry_after: u64) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    fn rate_limited(retry_after: u64) -> Rejection {
+        reject::custom(
+            Self::new(
+                format!("Too many requests. Try again in {retry_after} seconds"),
+                errors::RATE_LIMITED,
+            )
+            .with_retry_after(retry_after),
+        )
+    }
+
+    /// Builds the reply sent to the user. Rate limited users are also told when to retry via the `Retry-After` header.
+    fn into_reply(self, status_code: StatusCode) -> reply::Response {
+        let retry_after = self.retry_after;
+        let reply = reply::with_status(reply::json(&self), status_code);
+        match retry_after {
+            Some(x) => reply::with_header(reply, "retry-after", x).into_response(),
+            None => reply.into_response(),
+        }
     }
 
     fn missing_field(field_name: &str) -> Rejection {
@@ -64,6 +142,32 @@ fn with_grpc(
     warp::any().map(move || grpc_endpoint.clone())
 }
 
+/// Limits the requests that can be made from each address, if a [RateLimiter] is given.
+///
+/// Requests coming from loopback addresses are not limited. Those coming through the onion service are all seen as
+/// coming from the (local) Tor daemon, so limiting them by address would make every Tor user share the same limit.
+/// They are covered by the per-user limit instead (see [Gatekeeper::with_rate_limit](crate::gatekeeper::Gatekeeper::with_rate_limit)).
+fn with_rate_limit(
+    rate_limiter: Option<Arc<RateLimiter<IpAddr>>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and_then(move |addr: Option<SocketAddr>| {
+            let rate_limiter = rate_limiter.clone();
+            async move {
+                match (rate_limiter, addr) {
+                    (Some(rate_limiter), Some(addr)) if !addr.ip().is_loopback() => {
+                        rate_limiter.check(addr.ip()).map_err(|e| {
+                            log::debug!("Rate limiting requests from {}", addr.ip());
+                            ApiError::rate_limited(e.0)
+                        })
+                    }
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
 fn match_status(s: &tonic::Status) -> (StatusCode, u8) {
     let mut status_code = StatusCode::BAD_REQUEST;
     let error_code = match s.code() {
@@ -73,11 +177,32 @@ fn match_status(s: &tonic::Status) -> (StatusCode, u8) {
             errors::APPOINTMENT_NOT_FOUND
         }
         tonic::Code::AlreadyExists => errors::APPOINTMENT_ALREADY_TRIGGERED,
-        tonic::Code::ResourceExhausted => errors::REGISTRATION_RESOURCE_EXHAUSTED,
+        tonic::Code::OutOfRange => errors::APPOINTMENT_TO_SELF_DELAY_TOO_SMALL,
+        tonic::Code::ResourceExhausted => {
+            if s.metadata().contains_key(RETRY_AFTER) {
+                status_code = StatusCode::TOO_MANY_REQUESTS;
+                errors::RATE_LIMITED
+            } else {
+                errors::REGISTRATION_RESOURCE_EXHAUSTED
+            }
+        }
+        tonic::Code::FailedPrecondition => {
+            if s.metadata().contains_key(REGISTRATION_GATE) {
+                status_code = StatusCode::FORBIDDEN;
+                errors::REGISTRATION_PROOF_REQUIRED
+            } else {
+                status_code = StatusCode::PAYMENT_REQUIRED;
+                errors::REGISTRATION_PAYMENT_REQUIRED
+            }
+        }
         tonic::Code::Unauthenticated => {
             status_code = StatusCode::UNAUTHORIZED;
             errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
         }
+        tonic::Code::PermissionDenied => {
+            status_code = StatusCode::FORBIDDEN;
+            errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
+        }
         tonic::Code::Unavailable => {
             status_code = StatusCode::SERVICE_UNAVAILABLE;
             errors::SERVICE_UNAVAILABLE
@@ -93,22 +218,54 @@ fn match_status(s: &tonic::Status) -> (StatusCode, u8) {
 
 fn parse_grpc_response<T: serde::Serialize>(
     result: Result<tonic::Response<T>, tonic::Status>,
-) -> (reply::Json, StatusCode) {
+) -> reply::Response {
     match result {
         Ok(r) => {
             let inner = r.into_inner();
             log::debug!("Request succeeded");
             log::debug!("Response: {}", serde_json::json!(inner));
-            (reply::json(&inner), StatusCode::OK)
+            reply::with_status(reply::json(&inner), StatusCode::OK).into_response()
         }
         Err(s) => {
             let (status_code, error_code) = match_status(&s);
             log::debug!("Request failed, error_code={error_code}");
             log::debug!("Response: {}", serde_json::json!(s.message()));
-            (
-                reply::json(&ApiError::new(s.message().into(), error_code)),
-                status_code,
-            )
+
+            let mut api_error = ApiError::new(s.message().into(), error_code);
+            // Rejected appointments come with details about the reason of the rejection, while registrations pending
+            // payment come with the invoice to be paid, and the ones held back by the registration gate come with either
+            // a challenge to be solved or the key tokens are signed with
+            if !s.details().is_empty() {
+                let details = if let Some(gate) = s.metadata().get(REGISTRATION_GATE) {
+                    if gate == "token" {
+                        common_msgs::RegistrationTokenKey::decode(s.details())
+                            .map(|token_key| api_error.with_token_key(token_key))
+                    } else {
+                        common_msgs::RegistrationChallenge::decode(s.details())
+                            .map(|challenge| api_error.with_challenge(challenge))
+                    }
+                } else if s.code() == tonic::Code::FailedPrecondition {
+                    common_msgs::RegistrationInvoice::decode(s.details())
+                        .map(|invoice| api_error.with_invoice(invoice))
+                } else {
+                    common_msgs::AppointmentRejection::decode(s.details())
+                        .map(|rejection| api_error.with_rejection(rejection))
+                };
+                api_error = details.unwrap_or_else(|e| {
+                    log::debug!("Cannot decode the status details: {e}");
+                    ApiError::new(s.message().into(), error_code)
+                });
+            }
+            // Rate limited requests come with the seconds to wait before retrying
+            if let Some(retry_after) = s
+                .metadata()
+                .get(RETRY_AFTER)
+                .and_then(|x| x.to_str().ok())
+                .and_then(|x| x.parse().ok())
+            {
+                api_error = api_error.with_retry_after(retry_after);
+            }
+            api_error.into_reply(status_code)
         }
     }
 }
@@ -123,7 +280,13 @@ async fn register(
         addr.map_or("an unknown address".to_owned(), |a| a.to_string())
     );
 
-    let user_id = req.user_id.clone();
+    check_user_id(&req.user_id)?;
+
+    Ok(parse_grpc_response(grpc_conn.register(req).await))
+}
+
+/// Checks that the user id of a subscription request is well formed.
+fn check_user_id(user_id: &[u8]) -> Result<(), Rejection> {
     if user_id.is_empty() {
         return Err(ApiError::empty_field("user_id"));
     }
@@ -135,8 +298,37 @@ async fn register(
         ));
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.register(req).await);
-    Ok(reply::with_status(body, status))
+    Ok(())
+}
+
+async fn renew(
+    req: common_msgs::RenewRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    log::debug!(
+        "Received a renew request from {}",
+        addr.map_or("an unknown address".to_owned(), |a| a.to_string())
+    );
+
+    check_user_id(&req.user_id)?;
+
+    Ok(parse_grpc_response(grpc_conn.renew(req).await))
+}
+
+async fn top_up(
+    req: common_msgs::TopUpRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    log::debug!(
+        "Received a top_up request from {}",
+        addr.map_or("an unknown address".to_owned(), |a| a.to_string())
+    );
+
+    check_user_id(&req.user_id)?;
+
+    Ok(parse_grpc_response(grpc_conn.top_up(req).await))
 }
 
 async fn add_appointment(
@@ -149,6 +341,44 @@ async fn add_appointment(
         addr.map_or("an unknown address".to_owned(), |a| a.to_string())
     );
 
+    check_add_appointment_request(&req)?;
+
+    Ok(parse_grpc_response(grpc_conn.add_appointment(req).await))
+}
+
+async fn add_appointments(
+    req: common_msgs::AddAppointmentsRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    log::debug!(
+        "Received an add_appointments request from {}",
+        addr.map_or("an unknown address".to_owned(), |a| a.to_string())
+    );
+
+    if req.appointments.is_empty() {
+        return Err(ApiError::empty_field("appointments"));
+    }
+    if req.appointments.len() > MAX_APPOINTMENTS_PER_BATCH {
+        return Err(reject::custom(ApiError::new(
+            format!(
+                "Too many appointments. Expected at most {MAX_APPOINTMENTS_PER_BATCH}, received {}",
+                req.appointments.len()
+            ),
+            errors::INVALID_REQUEST_FORMAT,
+        )));
+    }
+    for r in req.appointments.iter() {
+        check_add_appointment_request(r)?;
+    }
+
+    Ok(parse_grpc_response(grpc_conn.add_appointments(req).await))
+}
+
+/// Checks that the fields of an add appointment request are well formed.
+fn check_add_appointment_request(
+    req: &common_msgs::AddAppointmentRequest,
+) -> Result<(), Rejection> {
     if let Some(a) = &req.appointment {
         if a.locator.is_empty() {
             return Err(ApiError::empty_field("locator"));
@@ -167,8 +397,7 @@ async fn add_appointment(
         return Err(ApiError::empty_field("signature"));
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.add_appointment(req).await);
-    Ok(reply::with_status(body, status))
+    Ok(())
 }
 
 async fn get_appointment(
@@ -195,8 +424,7 @@ async fn get_appointment(
         return Err(ApiError::empty_field("signature"));
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.get_appointment(req).await);
-    Ok(reply::with_status(body, status))
+    Ok(parse_grpc_response(grpc_conn.get_appointment(req).await))
 }
 
 async fn get_subscription_info(
@@ -213,8 +441,50 @@ async fn get_subscription_info(
         return Err(ApiError::empty_field("signature"));
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.get_subscription_info(req).await);
-    Ok(reply::with_status(body, status))
+    Ok(parse_grpc_response(
+        grpc_conn.get_subscription_info(req).await,
+    ))
+}
+
+async fn get_subscription_tiers(
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    log::debug!(
+        "Received a get_subscription_tiers request from {}",
+        addr.map_or("an unknown address".to_owned(), |a| a.to_string())
+    );
+
+    Ok(parse_grpc_response(
+        grpc_conn.get_subscription_tiers(()).await,
+    ))
+}
+
+async fn delete_appointment(
+    req: common_msgs::DeleteAppointmentRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    log::debug!(
+        "Received a delete_appointment request from {}",
+        addr.map_or("an unknown address".to_owned(), |a| a.to_string())
+    );
+
+    if req.locator.is_empty() {
+        return Err(ApiError::empty_field("locator"));
+    }
+    if req.locator.len() != LOCATOR_LEN {
+        return Err(ApiError::wrong_field_length(
+            "locator",
+            req.locator.len(),
+            LOCATOR_LEN,
+        ));
+    }
+    if req.signature.is_empty() {
+        return Err(ApiError::empty_field("signature"));
+    }
+
+    Ok(parse_grpc_response(grpc_conn.delete_appointment(req).await))
 }
 
 async fn ping(addr: Option<SocketAddr>) -> Result<impl Reply, Rejection> {
@@ -227,6 +497,7 @@ async fn ping(addr: Option<SocketAddr>) -> Result<impl Reply, Rejection> {
 
 fn router(
     grpc_conn: PublicTowerServicesClient<Channel>,
+    rate_limiter: Option<Arc<RateLimiter<IpAddr>>>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let register = warp::post()
         .and(warp::path(Endpoint::Register.to_string()))
@@ -235,6 +506,20 @@ fn router(
         .and(with_grpc(grpc_conn.clone()))
         .and_then(register);
 
+    let renew = warp::post()
+        .and(warp::path(Endpoint::Renew.to_string()))
+        .and(warp::body::content_length_limit(RENEW_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(renew);
+
+    let top_up = warp::post()
+        .and(warp::path(Endpoint::TopUp.to_string()))
+        .and(warp::body::content_length_limit(TOP_UP_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(top_up);
+
     let add_appointment = warp::post()
         .and(warp::path(Endpoint::AddAppointment.to_string()))
         .and(warp::body::content_length_limit(ADD_APPOINTMENT_BODY_LEN).and(warp::body::json()))
@@ -242,6 +527,13 @@ fn router(
         .and(with_grpc(grpc_conn.clone()))
         .and_then(add_appointment);
 
+    let add_appointments = warp::post()
+        .and(warp::path(Endpoint::AddAppointments.to_string()))
+        .and(warp::body::content_length_limit(ADD_APPOINTMENTS_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(add_appointments);
+
     let get_appointment = warp::post()
         .and(warp::path(Endpoint::GetAppointment.to_string()))
         .and(warp::body::content_length_limit(GET_APPOINTMENT_BODY_LEN).and(warp::body::json()))
@@ -256,23 +548,44 @@ fn router(
                 .and(warp::body::json()),
         )
         .and(warp::addr::remote())
-        .and(with_grpc(grpc_conn))
+        .and(with_grpc(grpc_conn.clone()))
         .and_then(get_subscription_info);
 
+    let get_subscription_tiers = warp::get()
+        .and(warp::path(Endpoint::GetSubscriptionTiers.to_string()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_subscription_tiers);
+
+    let delete_appointment = warp::post()
+        .and(warp::path(Endpoint::DeleteAppointment.to_string()))
+        .and(warp::body::content_length_limit(DELETE_APPOINTMENT_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn))
+        .and_then(delete_appointment);
+
     let ping = warp::get()
         .and(warp::path(Endpoint::Ping.to_string()))
         .and(warp::addr::remote())
         .and_then(ping);
 
-    register
-        .or(add_appointment)
-        .or(get_appointment)
-        .or(get_subscription_info)
-        .or(ping)
+    with_rate_limit(rate_limiter)
+        .and(
+            register
+                .or(renew)
+                .or(top_up)
+                .or(add_appointment)
+                .or(add_appointments)
+                .or(get_appointment)
+                .or(get_subscription_info)
+                .or(get_subscription_tiers)
+                .or(delete_appointment)
+                .or(ping),
+        )
         .recover(handle_rejection)
 }
 
-async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
+async fn handle_rejection(err: Rejection) -> Result<reply::Response, Rejection> {
     match err.find::<warp::body::BodyDeserializeError>() {
         Some(e) => {
             let mut error = e
@@ -290,13 +603,17 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
             } else {
                 errors::INVALID_REQUEST_FORMAT
             };
-            Ok(reply::with_status(
-                reply::json(&ApiError { error, error_code }),
-                StatusCode::BAD_REQUEST,
-            ))
+            Ok(ApiError::new(error, error_code).into_reply(StatusCode::BAD_REQUEST))
         }
         None => match err.find::<ApiError>() {
-            Some(x) => Ok(reply::with_status(reply::json(x), StatusCode::BAD_REQUEST)),
+            Some(x) => {
+                let status_code = if x.error_code == errors::RATE_LIMITED {
+                    StatusCode::TOO_MANY_REQUESTS
+                } else {
+                    StatusCode::BAD_REQUEST
+                };
+                Ok(x.clone().into_reply(status_code))
+            }
             None => Err(err),
         },
     }
@@ -305,6 +622,7 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
 pub async fn serve(
     http_bind: SocketAddr,
     grpc_bind: SocketAddr,
+    rate_limiter: Option<Arc<RateLimiter<IpAddr>>>,
     service_ready: Trigger,
     shutdown_signal: Listener,
 ) {
@@ -317,8 +635,8 @@ pub async fn serve(
             }
         }
     };
-    let (_, server) =
-        warp::serve(router(grpc_conn)).bind_with_graceful_shutdown(http_bind, shutdown_signal);
+    let (_, server) = warp::serve(router(grpc_conn, rate_limiter))
+        .bind_with_graceful_shutdown(http_bind, shutdown_signal);
     service_ready.trigger();
     server.await
 }
@@ -403,7 +721,7 @@ mod test_helpers {
                 .body(b),
         };
 
-        let res = req.reply(&router(grpc_conn)).await;
+        let res = req.reply(&router(grpc_conn, None)).await;
         (
             serde_json::from_slice::<ApiError>(res.body()).unwrap(),
             res.status(),
@@ -431,7 +749,7 @@ mod test_helpers {
             .method("POST")
             .path(&endpoint.path())
             .json(&serde_json::json!(body))
-            .reply(&router(grpc_conn))
+            .reply(&router(grpc_conn, None))
             .await;
 
         serde_json::from_slice::<T>(res.body())
@@ -488,7 +806,7 @@ mod tests_failures {
         let (server_addr, _s) = run_tower_in_background().await;
         let (api_error, status) = check_api_error(
             Endpoint::Register,
-            RequestBody::Jsonify(r#"{"user_id": ""}"#),
+            RequestBody::Jsonify(r#"{"user_id": "", "signature": ""}"#),
             server_addr,
         )
         .await;
@@ -530,7 +848,7 @@ mod tests_failures {
         let (server_addr, _s) = run_tower_in_background().await;
         let (api_error, status) = check_api_error(
             Endpoint::Register,
-            RequestBody::Jsonify(r#"{"user_id": "aa"}"#),
+            RequestBody::Jsonify(r#"{"user_id": "aa", "signature": ""}"#),
             server_addr,
         )
         .await;
@@ -540,6 +858,23 @@ mod tests_failures {
         assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 
+    #[tokio::test]
+    async fn test_wrong_field_size_renew_top_up() {
+        let (server_addr, _s) = run_tower_in_background().await;
+        for endpoint in [Endpoint::Renew, Endpoint::TopUp] {
+            let (api_error, status) = check_api_error(
+                endpoint,
+                RequestBody::Jsonify(r#"{"user_id": "aa"}"#),
+                server_addr,
+            )
+            .await;
+
+            assert!(api_error.error.contains("Wrong `user_id` field size"));
+            assert_eq!(api_error.error_code, errors::WRONG_FIELD_SIZE);
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
     #[tokio::test]
     async fn test_wrong_field_type() {
         let (server_addr, _s) = run_tower_in_background().await;
@@ -585,7 +920,7 @@ mod tests_failures {
         let res = warp::test::request()
             .method("POST")
             .path(&Endpoint::Register.path())
-            .reply(&router(grpc_conn))
+            .reply(&router(grpc_conn, None))
             .await;
 
         assert_eq!(res.status(), StatusCode::LENGTH_REQUIRED);
@@ -605,8 +940,8 @@ mod tests_failures {
         let res = warp::test::request()
             .method("POST")
             .path(&Endpoint::Register.path())
-            .json(&format!("{}{}", get_random_user_id(), get_random_user_id()))
-            .reply(&router(grpc_conn))
+            .json(&get_random_user_id().to_string().repeat(10))
+            .reply(&router(grpc_conn, None))
             .await;
 
         assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
@@ -626,12 +961,95 @@ mod tests_failures {
         let res = warp::test::request()
             .method("POST")
             .json(&"")
-            .reply(&router(grpc_conn))
+            .reply(&router(grpc_conn, None))
             .await;
 
         assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
 
+    #[tokio::test]
+    async fn test_rate_limit_per_address() {
+        let (server_addr, _s) = run_tower_in_background().await;
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+        let api = router(grpc_conn, Some(Arc::new(RateLimiter::new(1, 1))));
+        let ping = |addr: &str| {
+            warp::test::request()
+                .path(&Endpoint::Ping.path())
+                .remote_addr(addr.parse().unwrap())
+        };
+
+        // Each address can send a single request, no matter the endpoint it is sent to
+        let res = ping("1.1.1.1:9814").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = ping("1.1.1.1:9815").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "60");
+        let api_error = serde_json::from_slice::<ApiError>(res.body()).unwrap();
+        assert_eq!(api_error.error_code, errors::RATE_LIMITED);
+        assert_eq!(api_error.retry_after, Some(60));
+
+        let res = warp::test::request()
+            .method("POST")
+            .path(&Endpoint::Register.path())
+            .remote_addr("1.1.1.1:9814".parse().unwrap())
+            .json(&serde_json::json!(common_msgs::RegisterRequest {
+                user_id: get_random_user_id().to_vec(),
+                tier: String::new(),
+                proof: None,
+                signature: String::new(),
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Other addresses are not affected
+        let res = ping("2.2.2.2:9814").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_exempts_loopback() {
+        let (server_addr, _s) = run_tower_in_background().await;
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+        let api = router(grpc_conn, Some(Arc::new(RateLimiter::new(1, 1))));
+        let ping = |addr: &str| {
+            warp::test::request()
+                .path(&Endpoint::Ping.path())
+                .remote_addr(addr.parse().unwrap())
+        };
+
+        // Requests coming through Tor are all seen as coming from the local Tor daemon, so one user going over the limit
+        // does not lock the rest of them out
+        for addr in [
+            "127.0.0.1:9814",
+            "127.0.0.1:9815",
+            "[::1]:9814",
+            "[::1]:9815",
+        ] {
+            let res = ping(addr).reply(&api).await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        // Remote addresses are still limited
+        let res = ping("1.1.1.1:9814").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = ping("1.1.1.1:9814").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
     #[tokio::test]
     async fn test_wrong_method() {
         let (server_addr, _s) = run_tower_in_background().await;
@@ -645,7 +1063,7 @@ mod tests_failures {
 
         let res = warp::test::request()
             .json(&"")
-            .reply(&router(grpc_conn))
+            .reply(&router(grpc_conn, None))
             .await;
 
         assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
@@ -660,21 +1078,36 @@ mod tests_methods {
     };
     use super::*;
 
+    use std::convert::TryInto;
+    use std::sync::{Arc, Mutex};
+
+    use crate::anti_spam::{ProofOfWorkGate, TokenGate};
+    use crate::dbm::DBM;
     use crate::extended_appointment::UUID;
-    use crate::test_utils::{generate_dummy_appointment, ApiConfig, DURATION, SLOTS};
+    use crate::gatekeeper::SubscriptionTier;
+    use crate::test_utils::{
+        generate_dummy_appointment, generate_register_request, ApiConfig, MockPaymentVerifier,
+        DURATION, MIN_TO_SELF_DELAY, PRICE_MSAT, SLOTS, START_HEIGHT,
+    };
 
+    use teos_common::anti_spam::{solve_pow, token_key};
+    use teos_common::appointment::{deletion_message, RejectionReason};
     use teos_common::test_utils::get_random_user_id;
     use teos_common::{cryptography, UserId};
 
     #[tokio::test]
     async fn test_register() {
-        let (server_addr, _s) = run_tower_in_background().await;
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
         let response =
             request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
                 Endpoint::Register,
-                common_msgs::RegisterRequest {
-                    user_id: get_random_user_id().to_vec(),
-                },
+                generate_register_request(
+                    &internal_api.get_watcher().tower_id,
+                    &cryptography::get_random_keypair().0,
+                    "",
+                    None,
+                ),
                 server_addr,
             )
             .await;
@@ -683,16 +1116,14 @@ mod tests_methods {
 
     #[tokio::test]
     async fn test_register_max_slots() {
-        let (server_addr, _, _s) =
+        let (server_addr, internal_api, _s) =
             run_tower_in_background_with_config(ApiConfig::new(u32::MAX, DURATION)).await;
-        let user_id = get_random_user_id();
+        let (user_sk, _) = cryptography::get_random_keypair();
 
         // Register once, this should go trough and set slots to the limit
         request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_id.to_vec(),
-            },
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
             server_addr,
         )
         .await
@@ -702,9 +1133,12 @@ mod tests_methods {
         assert_eq!(
             check_api_error(
                 Endpoint::Register,
-                RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
-                    user_id: user_id.to_vec(),
-                })),
+                RequestBody::Json(serde_json::json!(generate_register_request(
+                    &internal_api.get_watcher().tower_id,
+                    &user_sk,
+                    "",
+                    None
+                ))),
                 server_addr,
             )
             .await,
@@ -719,62 +1153,381 @@ mod tests_methods {
     }
 
     #[tokio::test]
-    async fn test_register_service_unavailable() {
-        let (server_addr, _, _s) = run_tower_in_background_with_config(
-            ApiConfig::new(SLOTS, DURATION).bitcoind_unreachable(),
-        )
-        .await;
-        let user_id = get_random_user_id();
+    async fn test_register_rate_limited() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default().with_rate_limit(1, 1)).await;
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+        let (user_sk, _) = cryptography::get_random_keypair();
+        let register = || {
+            warp::test::request()
+                .method("POST")
+                .path(&Endpoint::Register.path())
+                .json(&serde_json::json!(generate_register_request(
+                    &internal_api.get_watcher().tower_id,
+                    &user_sk,
+                    "",
+                    None
+                )))
+        };
 
-        // Register with bitcoind down
+        // The first registration goes through, but the user is over the limit after it
+        let res = register().reply(&router(grpc_conn.clone(), None)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = register().reply(&router(grpc_conn, None)).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "60");
         assert_eq!(
-            check_api_error(
-                Endpoint::Register,
-                RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
-                    user_id: user_id.to_vec(),
-                })),
-                server_addr,
-            )
-            .await,
-            (
-                ApiError::new(
-                    "Service currently unavailable".into(),
-                    errors::SERVICE_UNAVAILABLE
-                ),
-                StatusCode::SERVICE_UNAVAILABLE
+            serde_json::from_slice::<ApiError>(res.body()).unwrap(),
+            ApiError::new(
+                "Too many requests. Try again in 60 seconds".into(),
+                errors::RATE_LIMITED
             )
+            .with_retry_after(60)
         );
     }
 
     #[tokio::test]
-    async fn test_add_appointment() {
-        let (server_addr, _s) = run_tower_in_background().await;
+    async fn test_register_payment_required() {
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let (server_addr, internal_api, _s) = run_tower_in_background_with_config(
+            ApiConfig::new(SLOTS, DURATION).with_payments(verifier.clone()),
+        )
+        .await;
+        let (user_sk, _) = cryptography::get_random_keypair();
 
-        // Register first
-        let (user_sk, user_pk) = cryptography::get_random_keypair();
-        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+        // The invoice to be paid is sent alongside the error
+        let (api_error, status) = check_api_error(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_pk.serialize().to_vec(),
-            },
+            RequestBody::Json(serde_json::json!(generate_register_request(
+                &internal_api.get_watcher().tower_id,
+                &user_sk,
+                "",
+                None
+            ))),
             server_addr,
         )
-        .await
-        .unwrap();
+        .await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(api_error.error_code, errors::REGISTRATION_PAYMENT_REQUIRED);
+        let invoice = api_error.invoice.unwrap();
+        assert_eq!(invoice.amount_msat, PRICE_MSAT);
 
-        // Then try to add an appointment
-        let appointment = generate_dummy_appointment(None).inner;
-        let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        // Once paid, the registration goes through
+        verifier.settle(&invoice.payment_hash.try_into().unwrap());
+        let response =
+            request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+                Endpoint::Register,
+                generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+                server_addr,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.available_slots, SLOTS);
+    }
 
-        let response = request_to_api::<
-            common_msgs::AddAppointmentRequest,
-            common_msgs::AddAppointmentResponse,
-        >(
-            Endpoint::AddAppointment,
-            common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.into()),
-                signature,
-            },
+    #[tokio::test]
+    async fn test_register_proof_required() {
+        let (server_addr, internal_api, _s) = run_tower_in_background_with_config(
+            ApiConfig::default()
+                .with_registration_gate(Arc::new(ProofOfWorkGate::new([1; 32], 8, 600))),
+        )
+        .await;
+        let (user_sk, _) = cryptography::get_random_keypair();
+        let register_request = |proof| {
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", proof)
+        };
+
+        // The challenge to be solved is sent alongside the error
+        let (api_error, status) = check_api_error(
+            Endpoint::Register,
+            RequestBody::Json(serde_json::json!(register_request(None))),
+            server_addr,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(api_error.error_code, errors::REGISTRATION_PROOF_REQUIRED);
+        let challenge = api_error.challenge.unwrap();
+
+        // Once solved, the registration goes through
+        let response =
+            request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+                Endpoint::Register,
+                register_request(Some(common_msgs::RegistrationProof {
+                    pow: Some(common_msgs::ProofOfWork {
+                        expires_at: challenge.expires_at,
+                        nonce: solve_pow(&challenge.challenge, challenge.difficulty as u8),
+                    }),
+                    token: None,
+                })),
+                server_addr,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.available_slots, SLOTS);
+    }
+
+    #[tokio::test]
+    async fn test_register_token_required() {
+        let key = cryptography::get_random_keypair().0;
+        let (server_addr, internal_api, _s) = run_tower_in_background_with_config(
+            ApiConfig::default().with_registration_gate(Arc::new(TokenGate::new(
+                key,
+                Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+            ))),
+        )
+        .await;
+
+        // The key tokens are signed with is sent alongside the error
+        let (api_error, status) = check_api_error(
+            Endpoint::Register,
+            RequestBody::Json(serde_json::json!(generate_register_request(
+                &internal_api.get_watcher().tower_id,
+                &cryptography::get_random_keypair().0,
+                "",
+                None
+            ))),
+            server_addr,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(api_error.error_code, errors::REGISTRATION_PROOF_REQUIRED);
+        assert_eq!(
+            api_error.token_key.unwrap().key,
+            token_key(&key).serialize().to_vec()
+        );
+        assert!(api_error.challenge.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_service_unavailable() {
+        let (server_addr, internal_api, _s) = run_tower_in_background_with_config(
+            ApiConfig::new(SLOTS, DURATION).bitcoind_unreachable(),
+        )
+        .await;
+        let (user_sk, _) = cryptography::get_random_keypair();
+
+        // Register with bitcoind down
+        assert_eq!(
+            check_api_error(
+                Endpoint::Register,
+                RequestBody::Json(serde_json::json!(generate_register_request(
+                    &internal_api.get_watcher().tower_id,
+                    &user_sk,
+                    "",
+                    None
+                ))),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "Service currently unavailable".into(),
+                    errors::SERVICE_UNAVAILABLE
+                ),
+                StatusCode::SERVICE_UNAVAILABLE
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_tiers() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default().with_tiers(vec![
+                SubscriptionTier::new("premium".to_owned(), SLOTS * 2, DURATION, 0, 4096),
+            ]))
+            .await;
+
+        // Requests that do not pick a tier (e.g. from clients unaware of them) get the default one
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let request =
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None);
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+        let res = warp::test::request()
+            .method("POST")
+            .path(&Endpoint::Register.path())
+            .json(&serde_json::json!({
+                "user_id": UserId(user_pk).to_string(),
+                "signature": request.signature,
+            }))
+            .reply(&router(grpc_conn, None))
+            .await;
+        let response = serde_json::from_slice::<common_msgs::RegisterResponse>(res.body()).unwrap();
+        assert_eq!(response.tier, "premium");
+        assert_eq!(response.available_slots, SLOTS * 2);
+
+        // Unknown tiers are rejected
+        assert_eq!(
+            check_api_error(
+                Endpoint::Register,
+                RequestBody::Json(serde_json::json!(generate_register_request(
+                    &internal_api.get_watcher().tower_id,
+                    &cryptography::get_random_keypair().0,
+                    "gold",
+                    None
+                ))),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "Subscription tier not found: gold".into(),
+                    errors::WRONG_FIELD_FORMAT
+                ),
+                StatusCode::BAD_REQUEST
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_tiers() {
+        let (server_addr, _s) = run_tower_in_background().await;
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+
+        let res = warp::test::request()
+            .method("GET")
+            .path(&Endpoint::GetSubscriptionTiers.path())
+            .reply(&router(grpc_conn, None))
+            .await;
+        let response =
+            serde_json::from_slice::<common_msgs::GetSubscriptionTiersResponse>(res.body())
+                .unwrap();
+        assert_eq!(response.tiers.len(), 1);
+        assert_eq!(response.tiers[0].slots, SLOTS);
+        assert_eq!(response.tiers[0].duration, DURATION);
+    }
+
+    #[tokio::test]
+    async fn test_renew_too_early() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let user_id = UserId(user_pk);
+        let receipt =
+            request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+                Endpoint::Register,
+                generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+                server_addr,
+            )
+            .await
+            .unwrap();
+
+        // Subscriptions cannot be renewed right after registering
+        assert_eq!(
+            check_api_error(
+                Endpoint::Renew,
+                RequestBody::Json(serde_json::json!(common_msgs::RenewRequest {
+                    user_id: user_id.to_vec(),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    format!(
+                        "Subscription cannot be renewed until block {}",
+                        receipt.subscription_expiry - DURATION + 1
+                    ),
+                    errors::REGISTRATION_RESOURCE_EXHAUSTED
+                ),
+                StatusCode::BAD_REQUEST
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_up() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let user_id = UserId(user_pk);
+
+        // Users need to be registered to top up
+        assert_eq!(
+            check_api_error(
+                Endpoint::TopUp,
+                RequestBody::Json(serde_json::json!(common_msgs::TopUpRequest {
+                    user_id: user_id.to_vec(),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "User not found. Register before trying to renew or top up".into(),
+                    errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
+                ),
+                StatusCode::UNAUTHORIZED
+            )
+        );
+
+        let receipt =
+            request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+                Endpoint::Register,
+                generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+                server_addr,
+            )
+            .await
+            .unwrap();
+        let response = request_to_api::<common_msgs::TopUpRequest, common_msgs::TopUpResponse>(
+            Endpoint::TopUp,
+            common_msgs::TopUpRequest {
+                user_id: user_id.to_vec(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.available_slots, SLOTS * 2);
+        assert_eq!(response.subscription_expiry, receipt.subscription_expiry);
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+
+        // Register first
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Then try to add an appointment
+        let appointment = generate_dummy_appointment(None).inner;
+        let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        let response = request_to_api::<
+            common_msgs::AddAppointmentRequest,
+            common_msgs::AddAppointmentResponse,
+        >(
+            Endpoint::AddAppointment,
+            common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.into()),
+                signature,
+            },
             server_addr,
         )
         .await;
@@ -806,7 +1559,56 @@ mod tests_methods {
                 ApiError::new(
                     "Invalid signature or user does not have enough slots available".into(),
                     errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
-                ),
+                )
+                .with_rejection(common_msgs::AppointmentRejection {
+                    reason: RejectionReason::AuthenticationFailure as i32,
+                    ..Default::default()
+                }),
+                StatusCode::UNAUTHORIZED
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_not_enough_slots() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(0, DURATION)).await;
+
+        // Register
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Try to add an appointment without having any slots available
+        let appointment = generate_dummy_appointment(None).inner;
+        let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::AddAppointment,
+                RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.into()),
+                    signature,
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "Invalid signature or user does not have enough slots available".into(),
+                    errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
+                )
+                .with_rejection(common_msgs::AppointmentRejection {
+                    reason: RejectionReason::NotEnoughSlots as i32,
+                    required_slots: 1,
+                    available_slots: 0,
+                    ..Default::default()
+                }),
                 StatusCode::UNAUTHORIZED
             )
         );
@@ -822,9 +1624,7 @@ mod tests_methods {
         let (user_sk, user_pk) = cryptography::get_random_keypair();
         request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_pk.serialize().to_vec(),
-            },
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
             server_addr,
         )
         .await
@@ -852,7 +1652,58 @@ mod tests_methods {
                 ApiError::new(
                     "The provided appointment has already been triggered".into(),
                     errors::APPOINTMENT_ALREADY_TRIGGERED
-                ),
+                )
+                .with_rejection(common_msgs::AppointmentRejection {
+                    reason: RejectionReason::AlreadyTriggered as i32,
+                    ..Default::default()
+                }),
+                StatusCode::BAD_REQUEST
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_to_self_delay_too_small() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(u32::MAX, DURATION)).await;
+
+        // Register
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Try to add an appointment with a to_self_delay below the tower's minimum
+        let mut appointment = generate_dummy_appointment(None).inner;
+        appointment.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+        let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::AddAppointment,
+                RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.into()),
+                    signature,
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    format!(
+                        "The provided to_self_delay is too small (minimum: {MIN_TO_SELF_DELAY})"
+                    ),
+                    errors::APPOINTMENT_TO_SELF_DELAY_TOO_SMALL
+                )
+                .with_rejection(common_msgs::AppointmentRejection {
+                    reason: RejectionReason::ToSelfDelayTooSmall as i32,
+                    min_to_self_delay: MIN_TO_SELF_DELAY as u32,
+                    ..Default::default()
+                }),
                 StatusCode::BAD_REQUEST
             )
         );
@@ -889,21 +1740,129 @@ mod tests_methods {
     }
 
     #[tokio::test]
-    async fn test_get_appointment() {
-        let (server_addr, _s) = run_tower_in_background().await;
+    async fn test_add_appointments() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
 
         // Register first
-        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let (user_sk, _) = cryptography::get_random_keypair();
         request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_pk.serialize().to_vec(),
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Then add a batch with a valid appointment and one with a to_self_delay that is too small
+        let appointment = generate_dummy_appointment(None).inner;
+        let mut short_delay = generate_dummy_appointment(None).inner;
+        short_delay.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+
+        let response = request_to_api::<
+            common_msgs::AddAppointmentsRequest,
+            common_msgs::AddAppointmentsResponse,
+        >(
+            Endpoint::AddAppointments,
+            common_msgs::AddAppointmentsRequest {
+                appointments: [&appointment, &short_delay]
+                    .iter()
+                    .map(|a| common_msgs::AddAppointmentRequest {
+                        appointment: Some((*a).clone().into()),
+                        signature: cryptography::sign(&a.to_vec(), &user_sk).unwrap(),
+                    })
+                    .collect(),
             },
             server_addr,
         )
         .await
         .unwrap();
 
+        assert_eq!(response.available_slots, SLOTS - 1);
+        assert_eq!(response.results.len(), 2);
+        assert!(response.results[0].response.is_some());
+        assert!(response.results[0].rejection.is_none());
+        assert!(response.results[1].response.is_none());
+        assert_eq!(
+            RejectionReason::from(response.results[1].rejection.as_ref().unwrap().reason),
+            RejectionReason::ToSelfDelayTooSmall
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_empty() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::AddAppointments,
+                RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentsRequest {
+                    appointments: Vec::new(),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new("`appointments` field is empty".into(), errors::EMPTY_FIELD),
+                StatusCode::BAD_REQUEST
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_too_many() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        let (user_sk, _) = cryptography::get_random_keypair();
+        let appointments = (0..MAX_APPOINTMENTS_PER_BATCH + 1)
+            .map(|_| {
+                let mut appointment = generate_dummy_appointment(None).inner;
+                // Keep the request small so it is not rejected for its size
+                appointment.encrypted_blob.truncate(1);
+                common_msgs::AddAppointmentRequest {
+                    signature: cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                    appointment: Some(appointment.into()),
+                }
+            })
+            .collect();
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::AddAppointments,
+                RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentsRequest {
+                    appointments
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    format!(
+                        "Too many appointments. Expected at most {MAX_APPOINTMENTS_PER_BATCH}, received {}",
+                        MAX_APPOINTMENTS_PER_BATCH + 1
+                    ),
+                    errors::INVALID_REQUEST_FORMAT
+                ),
+                StatusCode::BAD_REQUEST
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_appointment() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+
+        // Register first
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
         // Add an appointment
         let appointment = generate_dummy_appointment(None).inner;
         let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
@@ -978,15 +1937,14 @@ mod tests_methods {
 
     #[tokio::test]
     async fn test_get_appointment_not_found() {
-        let (server_addr, _s) = run_tower_in_background().await;
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
 
         // Register first
-        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let (user_sk, _) = cryptography::get_random_keypair();
         request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_pk.serialize().to_vec(),
-            },
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
             server_addr,
         )
         .await
@@ -1056,15 +2014,14 @@ mod tests_methods {
 
     #[tokio::test]
     async fn test_get_subscription_info() {
-        let (server_addr, _s) = run_tower_in_background().await;
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
 
         // Register first
-        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let (user_sk, _) = cryptography::get_random_keypair();
         request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
             Endpoint::Register,
-            common_msgs::RegisterRequest {
-                user_id: user_pk.serialize().to_vec(),
-            },
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
             server_addr,
         )
         .await
@@ -1144,4 +2101,139 @@ mod tests_methods {
             )
         );
     }
+
+    #[tokio::test]
+    async fn test_delete_appointment() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+
+        // Register first
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Add an appointment
+        let appointment = generate_dummy_appointment(None).inner;
+        let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        let start_block = request_to_api::<
+            common_msgs::AddAppointmentRequest,
+            common_msgs::AddAppointmentResponse,
+        >(
+            Endpoint::AddAppointment,
+            common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature,
+            },
+            server_addr,
+        )
+        .await
+        .unwrap()
+        .start_block;
+
+        // Delete it
+        let message = deletion_message(
+            &appointment.locator,
+            start_block,
+            &internal_api.get_watcher().tower_id,
+        );
+        let response = request_to_api::<
+            common_msgs::DeleteAppointmentRequest,
+            common_msgs::DeleteAppointmentResponse,
+        >(
+            Endpoint::DeleteAppointment,
+            common_msgs::DeleteAppointmentRequest {
+                locator: appointment.locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block,
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.locator, appointment.locator.to_vec());
+        assert_eq!(response.available_slots, SLOTS);
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_not_found() {
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::default()).await;
+
+        // Register first
+        let (user_sk, _) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            Endpoint::Register,
+            generate_register_request(&internal_api.get_watcher().tower_id, &user_sk, "", None),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // Try to delete an appointment that does not exist
+        let locator = generate_dummy_appointment(None).locator();
+        let message = deletion_message(
+            &locator,
+            START_HEIGHT as u32,
+            &internal_api.get_watcher().tower_id,
+        );
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::DeleteAppointment,
+                RequestBody::Json(serde_json::json!(common_msgs::DeleteAppointmentRequest {
+                    locator: locator.to_vec(),
+                    signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                    start_block: START_HEIGHT as u32,
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "Appointment not found".into(),
+                    errors::APPOINTMENT_NOT_FOUND
+                ),
+                StatusCode::NOT_FOUND
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_service_unavailable() {
+        let (server_addr, _, _s) = run_tower_in_background_with_config(
+            ApiConfig::new(SLOTS, DURATION).bitcoind_unreachable(),
+        )
+        .await;
+
+        let (user_sk, _) = cryptography::get_random_keypair();
+        let locator = generate_dummy_appointment(None).locator();
+        let message = deletion_message(&locator, START_HEIGHT as u32, &get_random_user_id());
+
+        assert_eq!(
+            check_api_error(
+                Endpoint::DeleteAppointment,
+                RequestBody::Json(serde_json::json!(common_msgs::DeleteAppointmentRequest {
+                    locator: locator.to_vec(),
+                    signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                    start_block: START_HEIGHT as u32,
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "Service currently unavailable".into(),
+                    errors::SERVICE_UNAVAILABLE
+                ),
+                StatusCode::SERVICE_UNAVAILABLE
+            )
+        );
+    }
 }
@@ -1,19 +1,307 @@
+use prost::Message;
+use std::path::PathBuf;
 use std::sync::{Arc, Condvar, Mutex};
 use tonic::{Code, Request, Response, Status};
 use triggered::Trigger;
 
+use bitcoin::secp256k1::PublicKey;
+
+use crate::anti_spam::{GateRejection, RegistrationGate, RegistrationProof};
+use crate::backup::{Backup, BackupError};
+use crate::bitcoin_cli::BitcoindBackends;
+use crate::gatekeeper::{RegistrationFailure, UserInfo, UserManagementFailure};
 use crate::protos as msgs;
 use crate::protos::private_tower_services_server::PrivateTowerServices;
 use crate::protos::public_tower_services_server::PublicTowerServices;
 use crate::watcher::{
-    AddAppointmentFailure, AppointmentInfo, GetAppointmentFailure, GetSubscriptionInfoFailure,
-    Watcher,
+    AddAppointmentFailure, AppointmentInfo, DeleteAppointmentFailure, GetAppointmentFailure,
+    GetSubscriptionInfoFailure, Watcher,
 };
 
-use teos_common::appointment::{Appointment, AppointmentStatus, Locator};
+use teos_common::appointment::{Appointment, AppointmentStatus, Locator, RejectionReason};
+use teos_common::constants::MAX_APPOINTMENTS_PER_BATCH;
+use teos_common::cryptography;
 use teos_common::protos as common_msgs;
+use teos_common::receipts::registration_message;
 use teos_common::UserId;
 
+/// Metadata key used to let rate limited users know how many seconds to wait before sending a new request.
+pub(crate) const RETRY_AFTER: &str = "retry-after";
+
+/// Metadata key used to let users know their registration has been held back by the registration gate. The value
+/// tells the kind of proof they need to provide (`pow` or `token`).
+pub(crate) const REGISTRATION_GATE: &str = "registration-gate";
+
+/// Maps an [AddAppointmentFailure] to the status code, error message, and rejection details reported to the user.
+fn add_appointment_rejection(
+    e: AddAppointmentFailure,
+) -> (Code, String, common_msgs::AppointmentRejection) {
+    match e {
+        AddAppointmentFailure::AuthenticationFailure => (
+            Code::Unauthenticated,
+            "Invalid signature or user does not have enough slots available".to_owned(),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::AuthenticationFailure as i32,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::NotEnoughSlots {
+            required_slots,
+            available_slots,
+        } => (
+            Code::Unauthenticated,
+            "Invalid signature or user does not have enough slots available".to_owned(),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::NotEnoughSlots as i32,
+                required_slots,
+                available_slots,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::SubscriptionExpired {
+            expiry,
+            current_height,
+        } => (
+            Code::Unauthenticated,
+            format!("Your subscription expired at {expiry}"),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::SubscriptionExpired as i32,
+                subscription_expiry: expiry,
+                current_height,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::AlreadyTriggered => (
+            Code::AlreadyExists,
+            "The provided appointment has already been triggered".to_owned(),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::AlreadyTriggered as i32,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::ToSelfDelayTooSmall(x) => (
+            Code::OutOfRange,
+            format!("The provided to_self_delay is too small (minimum: {x})"),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::ToSelfDelayTooSmall as i32,
+                min_to_self_delay: x as u32,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::RateLimited(x) => (
+            Code::ResourceExhausted,
+            rate_limited_message(x),
+            common_msgs::AppointmentRejection {
+                reason: RejectionReason::RateLimited as i32,
+                ..Default::default()
+            },
+        ),
+        AddAppointmentFailure::StorageFailure => (
+            Code::Unavailable,
+            "The appointments cannot be stored at the moment. Try again later".to_owned(),
+            common_msgs::AppointmentRejection::default(),
+        ),
+    }
+}
+
+/// Builds the [Status] returned when an appointment is rejected, attaching the rejection details to it
+/// so they can be forwarded to the user.
+fn rejection_status(e: AddAppointmentFailure) -> Status {
+    let retry_after = match e {
+        AddAppointmentFailure::RateLimited(x) => Some(x),
+        _ => None,
+    };
+    let (code, message, rejection) = add_appointment_rejection(e);
+    let status = Status::with_details(code, message, rejection.encode_to_vec().into());
+
+    match retry_after {
+        Some(x) => with_retry_after(status, x),
+        None => status,
+    }
+}
+
+/// Builds the error message returned to users that have made too many requests.
+fn rate_limited_message(retry_after: u64) -> String {
+    format!("Too many requests. Try again in {retry_after} seconds")
+}
+
+/// Builds the [Status] returned to users that have made too many requests.
+fn rate_limited_status(retry_after: u64) -> Status {
+    with_retry_after(
+        Status::new(Code::ResourceExhausted, rate_limited_message(retry_after)),
+        retry_after,
+    )
+}
+
+/// Attaches the seconds the user needs to wait before sending a new request to a [Status], as [RETRY_AFTER] metadata.
+fn with_retry_after(mut status: Status, retry_after: u64) -> Status {
+    status
+        .metadata_mut()
+        .insert(RETRY_AFTER, retry_after.into());
+    status
+}
+
+/// Builds the [Status] returned when a registration (or renewal / top up) fails. Payment requests carry the invoice to
+/// be paid as details, and tell the user to repeat the `request` once paid.
+fn registration_failure_status(e: RegistrationFailure, request: &str) -> Status {
+    match e {
+        RegistrationFailure::MaxSlotsReached => Status::new(
+            Code::ResourceExhausted,
+            "Subscription maximum slots count reached",
+        ),
+        RegistrationFailure::PaymentRequired(invoice) => Status::with_details(
+            Code::FailedPrecondition,
+            format!(
+                "Subscription payment required ({} msat). Pay the provided invoice and {request} again",
+                invoice.amount_msat
+            ),
+            common_msgs::RegistrationInvoice {
+                invoice: invoice.bolt11,
+                payment_hash: invoice.payment_hash.to_vec(),
+                amount_msat: invoice.amount_msat,
+                expires_at: invoice.expires_at,
+            }
+            .encode_to_vec()
+            .into(),
+        ),
+        RegistrationFailure::PaymentsUnavailable => Status::new(
+            Code::Unavailable,
+            "Subscription payments cannot be processed at the moment. Try again later",
+        ),
+        RegistrationFailure::UnknownTier(tier) => Status::new(
+            Code::InvalidArgument,
+            format!("Subscription tier not found: {tier}"),
+        ),
+        RegistrationFailure::TierMismatch(tier) => Status::new(
+            Code::InvalidArgument,
+            format!("User already registered to a different subscription tier ({tier})"),
+        ),
+        RegistrationFailure::UserNotFound => Status::new(
+            Code::Unauthenticated,
+            "User not found. Register before trying to renew or top up",
+        ),
+        RegistrationFailure::SubscriptionExpired(expiry) => Status::new(
+            Code::Unauthenticated,
+            format!("Your subscription expired at {expiry}. Renew it before topping it up"),
+        ),
+        RegistrationFailure::RenewalTooEarly(height) => Status::new(
+            Code::ResourceExhausted,
+            format!("Subscription cannot be renewed until block {height}"),
+        ),
+        RegistrationFailure::RateLimited(x) => rate_limited_status(x),
+        RegistrationFailure::UserBanned => {
+            Status::new(Code::PermissionDenied, "User banned from the tower")
+        }
+    }
+}
+
+/// Builds the [Status] returned when a registration is not let through the registration gate. Challenges to be solved,
+/// or the key tokens are signed with, are sent as details.
+fn gate_rejection_status(rejection: GateRejection) -> Status {
+    let (mut status, kind) = match rejection {
+        GateRejection::ChallengeRequired(challenge, reason) => (
+            Status::with_details(
+                Code::FailedPrecondition,
+                format!("{reason}. Solve the provided challenge and register again"),
+                common_msgs::RegistrationChallenge {
+                    challenge: challenge.challenge.to_vec(),
+                    difficulty: challenge.difficulty as u32,
+                    expires_at: challenge.expires_at,
+                }
+                .encode_to_vec()
+                .into(),
+            ),
+            "pow",
+        ),
+        GateRejection::TokenRequired(token_key, reason) => (
+            Status::with_details(
+                Code::FailedPrecondition,
+                reason,
+                common_msgs::RegistrationTokenKey {
+                    key: token_key.serialize().to_vec(),
+                }
+                .encode_to_vec()
+                .into(),
+            ),
+            "token",
+        ),
+    };
+    status
+        .metadata_mut()
+        .insert(REGISTRATION_GATE, kind.parse().unwrap());
+    status
+}
+
+/// Parses the proof attached to a registration request, if any.
+fn parse_registration_proof(
+    proof: Option<common_msgs::RegistrationProof>,
+) -> Result<Option<RegistrationProof>, &'static str> {
+    match proof {
+        None => Ok(None),
+        Some(common_msgs::RegistrationProof {
+            pow: Some(pow),
+            token: None,
+        }) => Ok(Some(RegistrationProof::ProofOfWork {
+            expires_at: pow.expires_at,
+            nonce: pow.nonce,
+        })),
+        Some(common_msgs::RegistrationProof {
+            pow: None,
+            token: Some(token),
+        }) => Ok(Some(RegistrationProof::Token {
+            token: token.token,
+            signature: PublicKey::from_slice(&token.signature).map_err(|_| {
+                "Provided token signature does not match expected format (33-byte compressed point)"
+            })?,
+        })),
+        _ => Err("Registration proof must contain either a proof of work or a token"),
+    }
+}
+
+/// Builds the [Status] returned when an admin action over a user fails.
+fn user_management_failure_status(e: UserManagementFailure) -> Status {
+    match e {
+        UserManagementFailure::UserNotFound => Status::new(Code::NotFound, "User not found"),
+        UserManagementFailure::AlreadyBanned => {
+            Status::new(Code::AlreadyExists, "User already banned")
+        }
+        UserManagementFailure::NotBanned => {
+            Status::new(Code::FailedPrecondition, "User is not banned")
+        }
+        UserManagementFailure::MaxSlotsReached => Status::new(
+            Code::ResourceExhausted,
+            "Subscription maximum slots count reached",
+        ),
+        UserManagementFailure::ExpiryInThePast(height) => Status::new(
+            Code::InvalidArgument,
+            format!("Subscription expiry cannot be in the past (current height: {height})"),
+        ),
+        UserManagementFailure::PendingTriggers => Status::new(
+            Code::Unavailable,
+            "Some of the user appointments are being handed to the Responder. Try again later",
+        ),
+    }
+}
+
+/// Builds the [Status] returned when the user id of a private API request cannot be parsed.
+fn invalid_user_id_status() -> Status {
+    Status::new(
+        Code::InvalidArgument,
+        "Provided public key does not match expected format (33-byte compressed key)",
+    )
+}
+
+/// Builds a [GetUserResponse](msgs::GetUserResponse) out of the data the tower has about a user.
+fn user_response(info: UserInfo, banned: bool) -> msgs::GetUserResponse {
+    msgs::GetUserResponse {
+        available_slots: info.available_slots,
+        subscription_expiry: info.subscription_expiry,
+        appointments: info.appointments.keys().map(|uuid| uuid.to_vec()).collect(),
+        tier: info.tier,
+        banned,
+    }
+}
+
 /// Internal API of the tower.
 /// Holds the [Watcher] (which is the single entry point of the tower's core) and offers interfaces
 /// to all available methods. The [InternalAPI] has two interfaces, a public one, reachable from the [API]
@@ -25,6 +313,12 @@ pub struct InternalAPI {
     addresses: Vec<msgs::NetworkAddress>,
     /// A flag that indicates wether bitcoind is reachable or not.
     bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
+    /// The bitcoind backends the tower is connected to, if running on top of bitcoind.
+    bitcoind_backends: Option<Arc<BitcoindBackends>>,
+    /// The gate new users need to go through to register. Registrations are open if not set.
+    registration_gate: Option<Arc<dyn RegistrationGate>>,
+    /// The component in charge of backing the tower data up. Backups are not supported if not set.
+    backup: Option<Arc<Backup>>,
     /// A signal indicating the tower is shuting down.
     shutdown_trigger: Trigger,
 }
@@ -41,10 +335,31 @@ impl InternalAPI {
             watcher,
             addresses,
             bitcoind_reachable,
+            bitcoind_backends: None,
+            registration_gate: None,
+            backup: None,
             shutdown_trigger,
         }
     }
 
+    /// Sets the bitcoind backends the tower is connected to, so the active one can be reported.
+    pub fn with_bitcoind_backends(mut self, bitcoind_backends: Arc<BitcoindBackends>) -> Self {
+        self.bitcoind_backends = Some(bitcoind_backends);
+        self
+    }
+
+    /// Sets the gate new users need to go through to register.
+    pub fn with_registration_gate(mut self, registration_gate: Arc<dyn RegistrationGate>) -> Self {
+        self.registration_gate = Some(registration_gate);
+        self
+    }
+
+    /// Sets the component in charge of backing the tower data up.
+    pub fn with_backup(mut self, backup: Arc<Backup>) -> Self {
+        self.backup = Some(backup);
+        self
+    }
+
     pub fn get_addresses(&self) -> &Vec<msgs::NetworkAddress> {
         &self.addresses
     }
@@ -81,21 +396,123 @@ impl PublicTowerServices for Arc<InternalAPI> {
             )
         })?;
 
-        match self.watcher.register(user_id) {
+        // Anyone could register a user otherwise, e.g. to a tier that user would not be able to switch from
+        let message = registration_message(&req_data.tier, &self.watcher.tower_id);
+        if cryptography::recover_pk(message.as_bytes(), &req_data.signature).ok() != Some(user_id.0)
+        {
+            return Err(Status::new(
+                Code::Unauthenticated,
+                "Wrong message or signature.",
+            ));
+        }
+
+        let proof = parse_registration_proof(req_data.proof)
+            .map_err(|e| Status::new(Code::InvalidArgument, e))?;
+
+        // Only users that are not registered yet need to go through the gate
+        let gate = self
+            .registration_gate
+            .as_ref()
+            .filter(|_| self.watcher.get_user_info(user_id).is_none());
+        if let Some(gate) = gate {
+            gate.admit(user_id, proof.as_ref())
+                .map_err(gate_rejection_status)?;
+        }
+
+        match self.watcher.register(user_id, &req_data.tier) {
             Ok(receipt) => Ok(Response::new(common_msgs::RegisterResponse {
                 user_id: req_data.user_id,
                 available_slots: receipt.available_slots(),
                 subscription_start: receipt.subscription_start(),
                 subscription_expiry: receipt.subscription_expiry(),
                 subscription_signature: receipt.signature().unwrap(),
+                tier: receipt.tier().to_owned(),
             })),
-            Err(_) => Err(Status::new(
-                Code::ResourceExhausted,
-                "Subscription maximum slots count reached",
-            )),
+            Err(e) => {
+                // Proofs are given back if the registration does not go through (e.g. if it needs to be paid first)
+                if let (Some(gate), Some(proof)) = (gate, &proof) {
+                    gate.release(proof);
+                }
+                Err(registration_failure_status(e, "register"))
+            }
+        }
+    }
+
+    /// Renew endpoint. Part of the public API. Internally calls [Watcher::renew].
+    async fn renew(
+        &self,
+        request: Request<common_msgs::RenewRequest>,
+    ) -> Result<Response<common_msgs::RenewResponse>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+
+        let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+
+        match self.watcher.renew(user_id) {
+            Ok(receipt) => Ok(Response::new(common_msgs::RenewResponse {
+                user_id: req_data.user_id,
+                subscription_start: receipt.subscription_start(),
+                subscription_expiry: receipt.subscription_expiry(),
+                subscription_signature: receipt.signature().unwrap(),
+            })),
+            Err(e) => Err(registration_failure_status(e, "renew")),
+        }
+    }
+
+    /// Top up endpoint. Part of the public API. Internally calls [Watcher::top_up].
+    async fn top_up(
+        &self,
+        request: Request<common_msgs::TopUpRequest>,
+    ) -> Result<Response<common_msgs::TopUpResponse>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+
+        let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+
+        match self.watcher.top_up(user_id) {
+            Ok(receipt) => Ok(Response::new(common_msgs::TopUpResponse {
+                user_id: req_data.user_id,
+                available_slots: receipt.available_slots(),
+                subscription_expiry: receipt.subscription_expiry(),
+                subscription_signature: receipt.signature().unwrap(),
+            })),
+            Err(e) => Err(registration_failure_status(e, "top up")),
         }
     }
 
+    /// Get subscription tiers endpoint. Part of the public API. Internally calls [Watcher::get_subscription_tiers].
+    async fn get_subscription_tiers(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<common_msgs::GetSubscriptionTiersResponse>, Status> {
+        let tiers = self
+            .watcher
+            .get_subscription_tiers()
+            .into_iter()
+            .map(|tier| common_msgs::SubscriptionTier {
+                name: tier.name,
+                slots: tier.slots,
+                duration: tier.duration,
+                price_msat: tier.price,
+                max_blob_size: tier.max_blob_size as u32,
+            })
+            .collect();
+
+        Ok(Response::new(common_msgs::GetSubscriptionTiersResponse {
+            tiers,
+        }))
+    }
+
     /// Add appointment endpoint. Part of the public API. Internally calls [Watcher::add_appointment].
     async fn add_appointment(
         &self,
@@ -125,22 +542,88 @@ impl PublicTowerServices for Arc<InternalAPI> {
                     subscription_expiry,
                 }))
             }
-            Err(e) => match e {
-                AddAppointmentFailure::AuthenticationFailure
-                | AddAppointmentFailure::NotEnoughSlots => Err(Status::new(
-                    Code::Unauthenticated,
-                    "Invalid signature or user does not have enough slots available",
-                )),
-                AddAppointmentFailure::SubscriptionExpired(x) => Err(Status::new(
-                    Code::Unauthenticated,
-                    format!("Your subscription expired at {x}"),
-                )),
-                AddAppointmentFailure::AlreadyTriggered => Err(Status::new(
-                    Code::AlreadyExists,
-                    "The provided appointment has already been triggered",
-                )),
-            },
+            Err(e) => Err(rejection_status(e)),
+        }
+    }
+
+    /// Add appointments endpoint. Part of the public API. Internally calls [Watcher::add_appointments].
+    async fn add_appointments(
+        &self,
+        request: Request<common_msgs::AddAppointmentsRequest>,
+    ) -> Result<Response<common_msgs::AddAppointmentsResponse>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+
+        if req_data.appointments.is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "No appointments were provided",
+            ));
+        }
+        if req_data.appointments.len() > MAX_APPOINTMENTS_PER_BATCH {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!(
+                    "Too many appointments. Expected at most {MAX_APPOINTMENTS_PER_BATCH}, received {}",
+                    req_data.appointments.len()
+                ),
+            ));
         }
+
+        let appointments: Vec<(Appointment, String)> = req_data
+            .appointments
+            .into_iter()
+            .map(|r| {
+                let app_data = r.appointment.unwrap();
+                (
+                    Appointment::new(
+                        Locator::from_slice(&app_data.locator).unwrap(),
+                        app_data.encrypted_blob,
+                        app_data.to_self_delay,
+                    ),
+                    r.signature,
+                )
+            })
+            .collect();
+        let locators: Vec<Locator> = appointments.iter().map(|(a, _)| a.locator).collect();
+
+        let (results, available_slots, subscription_expiry) = self
+            .watcher
+            .add_appointments(appointments)
+            .map_err(rejection_status)?;
+
+        let results = locators
+            .into_iter()
+            .zip(results)
+            .map(|(locator, result)| match result {
+                Ok((receipt, available_slots)) => common_msgs::AddAppointmentResult {
+                    locator: locator.to_vec(),
+                    response: Some(common_msgs::AddAppointmentResponse {
+                        locator: locator.to_vec(),
+                        start_block: receipt.start_block(),
+                        signature: receipt.signature().unwrap(),
+                        available_slots,
+                        subscription_expiry,
+                    }),
+                    ..Default::default()
+                },
+                Err(e) => {
+                    let (_, error, rejection) = add_appointment_rejection(e);
+                    common_msgs::AddAppointmentResult {
+                        locator: locator.to_vec(),
+                        error,
+                        rejection: Some(rejection),
+                        ..Default::default()
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Response::new(common_msgs::AddAppointmentsResponse {
+            results,
+            available_slots,
+            subscription_expiry,
+        }))
     }
 
     /// Get appointment endpoint. Part of the public API. Internally calls [Watcher::get_appointment].
@@ -193,6 +676,7 @@ impl PublicTowerServices for Arc<InternalAPI> {
                     Code::Unauthenticated,
                     format!("Your subscription expired at {x}"),
                 )),
+                GetAppointmentFailure::RateLimited(x) => Err(rate_limited_status(x)),
             },
         }
     }
@@ -215,6 +699,7 @@ impl PublicTowerServices for Arc<InternalAPI> {
                     Code::Unauthenticated,
                     format!("Your subscription expired at {x}"),
                 ),
+                GetSubscriptionInfoFailure::RateLimited(x) => rate_limited_status(x),
             })?;
 
         Ok(Response::new(common_msgs::GetSubscriptionInfoResponse {
@@ -223,6 +708,48 @@ impl PublicTowerServices for Arc<InternalAPI> {
             locators: locators.iter().map(|x| x.to_vec()).collect(),
         }))
     }
+
+    /// Delete appointment endpoint. Part of the public API. Internally calls [Watcher::delete_appointment].
+    async fn delete_appointment(
+        &self,
+        request: Request<common_msgs::DeleteAppointmentRequest>,
+    ) -> Result<Response<common_msgs::DeleteAppointmentResponse>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+        let locator = Locator::from_slice(&req_data.locator).unwrap();
+
+        match self
+            .watcher
+            .delete_appointment(locator, req_data.start_block, req_data.signature)
+        {
+            Ok((receipt, available_slots)) => {
+                Ok(Response::new(common_msgs::DeleteAppointmentResponse {
+                    locator: locator.to_vec(),
+                    deletion_block: receipt.deletion_block(),
+                    signature: receipt.signature().unwrap(),
+                    available_slots,
+                }))
+            }
+            Err(e) => match e {
+                DeleteAppointmentFailure::NotFound => {
+                    Err(Status::new(Code::NotFound, "Appointment not found"))
+                }
+                DeleteAppointmentFailure::AuthenticationFailure => Err(Status::new(
+                    Code::Unauthenticated,
+                    "User cannot be authenticated",
+                )),
+                DeleteAppointmentFailure::SubscriptionExpired(x) => Err(Status::new(
+                    Code::Unauthenticated,
+                    format!("Your subscription expired at {x}"),
+                )),
+                DeleteAppointmentFailure::AlreadyTriggered => Err(Status::new(
+                    Code::AlreadyExists,
+                    "The requested appointment has already been triggered",
+                )),
+                DeleteAppointmentFailure::RateLimited(x) => Err(rate_limited_status(x)),
+            },
+        }
+    }
 }
 
 /// Private tower API. Only accessible by the tower admin via RPC.
@@ -339,6 +866,11 @@ impl PrivateTowerServices for Arc<InternalAPI> {
             n_watcher_appointments: self.watcher.get_appointments_count() as u32,
             n_responder_trackers: self.watcher.get_trackers_count() as u32,
             bitcoind_reachable: self.check_service_unavailable().is_ok(),
+            min_to_self_delay: self.watcher.get_min_to_self_delay() as u32,
+            bitcoind_backend: self
+                .bitcoind_backends
+                .as_ref()
+                .map_or(String::new(), |backends| backends.active_endpoint().url()),
         }))
     }
 
@@ -378,99 +910,364 @@ impl PrivateTowerServices for Arc<InternalAPI> {
                 .map_or("an unknown address".to_owned(), |a| a.to_string())
         );
 
-        let user_id = UserId::from_slice(&request.into_inner().user_id).map_err(|_| {
-            Status::new(
-                Code::InvalidArgument,
-                "Provided public key does not match expected format (33-byte compressed key)",
-            )
-        })?;
+        let user_id = UserId::from_slice(&request.into_inner().user_id)
+            .map_err(|_| invalid_user_id_status())?;
 
         match self.watcher.get_user_info(user_id) {
-            Some(info) => Ok(Response::new(msgs::GetUserResponse {
-                available_slots: info.available_slots,
-                subscription_expiry: info.subscription_expiry,
-                appointments: info.appointments.keys().map(|uuid| uuid.to_vec()).collect(),
-            })),
+            Some(info) => Ok(Response::new(user_response(
+                info,
+                self.watcher.is_user_banned(user_id),
+            ))),
             None => Err(Status::new(Code::NotFound, "User not found")),
         }
     }
 
-    /// Stop endpoint. Stops the tower daemon. Part of the private API.
-    async fn stop(&self, request: Request<()>) -> Result<Response<()>, Status> {
-        self.shutdown_trigger.trigger();
-
+    /// Ban user endpoint. Bans a user from the tower. Part of the private API.
+    async fn ban_user(
+        &self,
+        request: Request<msgs::BanUserRequest>,
+    ) -> Result<Response<()>, Status> {
         log::debug!(
-            "Received a shutting down request from {}, notifying components",
+            "Received a ban_user request from {}",
             request
                 .remote_addr()
                 .map_or("an unknown address".to_owned(), |a| a.to_string())
         );
-        Ok(Response::new(()))
-    }
-}
 
-#[cfg(test)]
-mod tests_private_helpers {
-    use super::*;
+        let user_id = UserId::from_slice(&request.into_inner().user_id)
+            .map_err(|_| invalid_user_id_status())?;
+        self.watcher
+            .ban_user(user_id)
+            .map_err(user_management_failure_status)?;
 
-    impl InternalAPI {
-        pub(crate) fn get_watcher(&self) -> &Watcher {
-            &self.watcher
-        }
+        Ok(Response::new(()))
     }
-}
-
-#[cfg(test)]
-mod tests_private_api {
-    use super::*;
-    use std::collections::HashSet;
-    use std::iter::FromIterator;
 
-    use bitcoin::hashes::Hash;
-    use bitcoin::Txid;
+    /// Unban user endpoint. Lifts the ban of a user. Part of the private API.
+    async fn unban_user(
+        &self,
+        request: Request<msgs::UnbanUserRequest>,
+    ) -> Result<Response<()>, Status> {
+        log::debug!(
+            "Received an unban_user request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
 
-    use crate::extended_appointment::UUID;
-    use crate::responder::{ConfirmationStatus, TransactionTracker};
-    use crate::test_utils::{
-        create_api, generate_dummy_appointment, generate_uuid, get_random_tx, DURATION, SLOTS,
-        START_HEIGHT,
-    };
-    use crate::watcher::Breach;
+        let user_id = UserId::from_slice(&request.into_inner().user_id)
+            .map_err(|_| invalid_user_id_status())?;
+        self.watcher
+            .unban_user(user_id)
+            .map_err(user_management_failure_status)?;
 
-    use teos_common::cryptography::{self, get_random_keypair};
-    use teos_common::test_utils::get_random_user_id;
+        Ok(Response::new(()))
+    }
 
-    #[tokio::test]
-    async fn test_get_all_appointments() {
-        let (internal_api, _s) = create_api().await;
+    /// Delete user endpoint. Deletes a user alongside all their appointments. Part of the private API.
+    async fn delete_user(
+        &self,
+        request: Request<msgs::DeleteUserRequest>,
+    ) -> Result<Response<msgs::DeleteUserResponse>, Status> {
+        log::debug!(
+            "Received a delete_user request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
 
-        let response = internal_api
-            .get_all_appointments(Request::new(()))
-            .await
-            .unwrap()
-            .into_inner();
+        let user_id = UserId::from_slice(&request.into_inner().user_id)
+            .map_err(|_| invalid_user_id_status())?;
+        let n_appointments = self
+            .watcher
+            .delete_user(user_id)
+            .map_err(user_management_failure_status)?;
 
-        assert!(matches!(response, msgs::GetAllAppointmentsResponse { .. }));
+        Ok(Response::new(msgs::DeleteUserResponse {
+            n_appointments: n_appointments as u32,
+        }))
     }
 
-    #[tokio::test]
-    async fn test_get_all_appointments_watcher() {
-        let (internal_api, _s) = create_api().await;
-
-        // Add data to the Watcher so we can retrieve it later on
-        let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+    /// Grant slots endpoint. Grants extra appointment slots to a user. Part of the private API.
+    async fn grant_slots(
+        &self,
+        request: Request<msgs::GrantSlotsRequest>,
+    ) -> Result<Response<msgs::GetUserResponse>, Status> {
+        log::debug!(
+            "Received a grant_slots request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
 
-        let appointment = generate_dummy_appointment(None).inner;
-        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
-        internal_api
+        let req_data = request.into_inner();
+        let user_id =
+            UserId::from_slice(&req_data.user_id).map_err(|_| invalid_user_id_status())?;
+        let info = self
             .watcher
-            .add_appointment(appointment.clone(), user_signature)
-            .unwrap();
+            .grant_slots(user_id, req_data.slots)
+            .map_err(user_management_failure_status)?;
 
-        let response = internal_api
-            .get_all_appointments(Request::new(()))
-            .await
+        Ok(Response::new(user_response(
+            info,
+            self.watcher.is_user_banned(user_id),
+        )))
+    }
+
+    /// Set subscription expiry endpoint. Sets the subscription expiry of a user. Part of the private API.
+    async fn set_subscription_expiry(
+        &self,
+        request: Request<msgs::SetSubscriptionExpiryRequest>,
+    ) -> Result<Response<msgs::GetUserResponse>, Status> {
+        log::debug!(
+            "Received a set_subscription_expiry request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
+
+        let req_data = request.into_inner();
+        let user_id =
+            UserId::from_slice(&req_data.user_id).map_err(|_| invalid_user_id_status())?;
+        let info = self
+            .watcher
+            .set_subscription_expiry(user_id, req_data.subscription_expiry)
+            .map_err(user_management_failure_status)?;
+
+        Ok(Response::new(user_response(
+            info,
+            self.watcher.is_user_banned(user_id),
+        )))
+    }
+
+    /// Get audit log endpoint. Gets the admin actions performed over users. Part of the private API.
+    async fn get_audit_log(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<msgs::GetAuditLogResponse>, Status> {
+        log::debug!(
+            "Received a get_audit_log request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
+
+        let entries = self
+            .watcher
+            .get_audit_log()
+            .into_iter()
+            .map(|entry| msgs::AuditLogEntry {
+                timestamp: entry.timestamp,
+                user_id: entry.user_id.to_vec(),
+                action: entry.action,
+                details: entry.details,
+            })
+            .collect();
+
+        Ok(Response::new(msgs::GetAuditLogResponse { entries }))
+    }
+
+    /// Issue registration tokens endpoint. Signs a batch of blinded tokens, to be handed to users out of band alongside
+    /// the proofs they have been signed with the tower token key. Part of the private API.
+    async fn issue_registration_tokens(
+        &self,
+        request: Request<msgs::IssueRegistrationTokensRequest>,
+    ) -> Result<Response<msgs::IssueRegistrationTokensResponse>, Status> {
+        log::debug!(
+            "Received an issue_registration_tokens request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
+
+        let blinded_tokens = request
+            .into_inner()
+            .blinded_tokens
+            .iter()
+            .map(|blinded_token| PublicKey::from_slice(blinded_token))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                Status::new(
+                    Code::InvalidArgument,
+                    "Provided blinded tokens do not match expected format (33-byte compressed point)",
+                )
+            })?;
+
+        match self
+            .registration_gate
+            .as_ref()
+            .and_then(|gate| gate.issue_tokens(&blinded_tokens))
+        {
+            Some(issued) => {
+                log::info!("Issued {} registration tokens", issued.signatures.len());
+                Ok(Response::new(msgs::IssueRegistrationTokensResponse {
+                    blinded_signatures: issued
+                        .signatures
+                        .iter()
+                        .map(|(signature, _)| signature.serialize().to_vec())
+                        .collect(),
+                    proofs: issued
+                        .signatures
+                        .iter()
+                        .map(|(_, proof)| proof.serialize().to_vec())
+                        .collect(),
+                    token_key: issued.token_key.serialize().to_vec(),
+                }))
+            }
+            None => Err(Status::new(
+                Code::FailedPrecondition,
+                "The tower is not gating registrations with tokens",
+            )),
+        }
+    }
+
+    /// Backup endpoint. Backs the tower data up to the given path while the tower keeps running. Part of the private
+    /// API.
+    async fn backup(
+        &self,
+        request: Request<msgs::BackupRequest>,
+    ) -> Result<Response<msgs::BackupResponse>, Status> {
+        log::debug!(
+            "Received a backup request from {}",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
+
+        let backup = self.backup.clone().ok_or_else(|| {
+            Status::new(
+                Code::FailedPrecondition,
+                "Backups are only supported by the sqlite database backend",
+            )
+        })?;
+        let path = PathBuf::from(request.into_inner().path);
+        if !path.is_absolute() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "The backup path must be absolute",
+            ));
+        }
+
+        let backup_path = path.clone();
+        match tokio::task::spawn_blocking(move || backup.create(&backup_path))
+            .await
+            .unwrap()
+        {
+            Ok(manifest) => {
+                log::info!("Tower data backed up to {}", path.display());
+                Ok(Response::new(msgs::BackupResponse {
+                    tower_id: manifest.tower_id.to_vec(),
+                    network: manifest.network,
+                    last_known_block: manifest
+                        .last_known_block
+                        .map_or(String::new(), |block_hash| block_hash.to_string()),
+                    encrypted_database: manifest.encrypted_database,
+                    encrypted_tower_key: manifest.encrypted_tower_key,
+                    created_at: manifest.created_at,
+                    path: path.display().to_string(),
+                }))
+            }
+            Err(BackupError::AlreadyExists(_)) => Err(Status::new(
+                Code::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )),
+            Err(e) => {
+                log::error!("Cannot back the tower data up. Error: {e}");
+                Err(Status::new(
+                    Code::Internal,
+                    format!("Cannot back the tower data up: {e}"),
+                ))
+            }
+        }
+    }
+
+    /// Stop endpoint. Stops the tower daemon. Part of the private API.
+    async fn stop(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        self.shutdown_trigger.trigger();
+
+        log::debug!(
+            "Received a shutting down request from {}, notifying components",
+            request
+                .remote_addr()
+                .map_or("an unknown address".to_owned(), |a| a.to_string())
+        );
+        Ok(Response::new(()))
+    }
+}
+
+#[cfg(test)]
+mod tests_private_helpers {
+    use super::*;
+
+    impl InternalAPI {
+        pub(crate) fn get_watcher(&self) -> &Watcher {
+            &self.watcher
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_private_api {
+    use super::*;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::iter::FromIterator;
+    use tempdir::TempDir;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use crate::anti_spam::TokenGate;
+    use crate::backup::DB_FILE;
+    use crate::bitcoin_cli::RpcEndpoint;
+    use crate::dbm::{Storage, DBM};
+    use crate::encryption::TowerKey;
+    use crate::extended_appointment::UUID;
+    use crate::responder::{ConfirmationStatus, TransactionTracker};
+    use crate::test_utils::{
+        create_api, create_api_with_config, generate_dummy_appointment, generate_uuid,
+        get_random_tx, ApiConfig, DURATION, MIN_TO_SELF_DELAY, SLOTS, START_HEIGHT,
+    };
+    use crate::watcher::Breach;
+
+    use teos_common::anti_spam::{
+        blind_token, new_token, token_key, unblind_signature, verify_token, TokenProof,
+    };
+    use teos_common::cryptography::{self, get_random_keypair};
+    use teos_common::test_utils::get_random_user_id;
+    use teos_common::TowerId;
+
+    #[tokio::test]
+    async fn test_get_all_appointments() {
+        let (internal_api, _s) = create_api().await;
+
+        let response = internal_api
+            .get_all_appointments(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(matches!(response, msgs::GetAllAppointmentsResponse { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_get_all_appointments_watcher() {
+        let (internal_api, _s) = create_api().await;
+
+        // Add data to the Watcher so we can retrieve it later on
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .watcher
+            .add_appointment(appointment.clone(), user_signature)
+            .unwrap();
+
+        let response = internal_api
+            .get_all_appointments(Request::new(()))
+            .await
             .unwrap()
             .into_inner();
 
@@ -531,7 +1328,7 @@ mod tests_private_api {
             // Add that many appointments to the watcher.
             for _ in 0..appointments_to_create {
                 let (user_sk, user_pk) = get_random_keypair();
-                internal_api.watcher.register(UserId(user_pk)).unwrap();
+                internal_api.watcher.register(UserId(user_pk), "").unwrap();
                 let appointment = generate_dummy_appointment(Some(&dispute_txid)).inner;
                 let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
                 internal_api
@@ -632,6 +1429,42 @@ mod tests_private_api {
         assert_eq!(response.n_registered_users, 0);
         assert_eq!(response.n_watcher_appointments, 0);
         assert_eq!(response.n_responder_trackers, 0);
+        assert_eq!(response.min_to_self_delay, MIN_TO_SELF_DELAY as u32);
+        assert_eq!(response.bitcoind_backend, "");
+    }
+
+    #[tokio::test]
+    async fn test_get_tower_info_bitcoind_backend() {
+        let (internal_api, _s) = create_api().await;
+        let backends = Arc::new(
+            BitcoindBackends::new(vec![
+                RpcEndpoint::new("localhost", 8332, "user", "pass"),
+                RpcEndpoint::new("10.0.0.2", 8332, "user", "pass"),
+            ])
+            .unwrap(),
+        );
+        let internal_api = Arc::new(
+            Arc::try_unwrap(internal_api)
+                .ok()
+                .unwrap()
+                .with_bitcoind_backends(backends.clone()),
+        );
+
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.bitcoind_backend, "http://localhost:8332");
+
+        // The active backend is reported after failing over
+        backends.switch(0, 1);
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.bitcoind_backend, "http://10.0.0.2:8332");
     }
 
     #[tokio::test]
@@ -641,7 +1474,7 @@ mod tests_private_api {
         // Register a user
         let (user_sk, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        internal_api.watcher.register(user_id).unwrap();
+        internal_api.watcher.register(user_id, "").unwrap();
 
         // Add data to the Watcher
         for _ in 0..2 {
@@ -682,7 +1515,7 @@ mod tests_private_api {
         for _ in 0..2 {
             let (_, user_pk) = get_random_keypair();
             let user_id = UserId(user_pk);
-            internal_api.watcher.register(user_id).unwrap();
+            internal_api.watcher.register(user_id, "").unwrap();
             users.insert(user_id.to_vec());
         }
 
@@ -715,7 +1548,7 @@ mod tests_private_api {
         // Register a user and get it back
         let (user_sk, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        internal_api.watcher.register(user_id).unwrap();
+        internal_api.watcher.register(user_id, "").unwrap();
 
         let response = internal_api
             .get_user(Request::new(msgs::GetUserRequest {
@@ -773,131 +1606,883 @@ mod tests_private_api {
     }
 
     #[tokio::test]
-    async fn test_stop() {
-        let (internal_api, _s) = create_api().await;
-
-        assert!(!internal_api.shutdown_trigger.is_triggered());
-        internal_api.stop(Request::new(())).await.unwrap();
-        assert!(internal_api.shutdown_trigger.is_triggered());
-    }
-}
-
-#[cfg(test)]
-mod tests_public_api {
-    use super::*;
-
-    use crate::extended_appointment::UUID;
-    use crate::test_utils::{
-        create_api, create_api_with_config, generate_dummy_appointment, ApiConfig, DURATION, SLOTS,
-    };
-    use teos_common::cryptography::{self, get_random_keypair};
-
-    #[tokio::test]
-    async fn test_register() {
-        let (internal_api, _s) = create_api().await;
-
-        let (_, user_pk) = get_random_keypair();
-
-        // Registering (even multiple times) should work
-        for _ in 0..2 {
-            let response = internal_api
-                .register(Request::new(common_msgs::RegisterRequest {
-                    user_id: UserId(user_pk).to_vec(),
-                }))
-                .await
-                .unwrap()
-                .into_inner();
-
-            assert!(matches!(response, common_msgs::RegisterResponse { .. }))
-        }
-    }
-
-    #[tokio::test]
-    async fn test_register_wrong_user_id() {
+    async fn test_ban_unban_user() {
         let (internal_api, _s) = create_api().await;
+        let user_id = get_random_user_id();
+        internal_api.watcher.register(user_id, "").unwrap();
 
-        let mut user_ids = Vec::new();
-
-        // Wrong user id size
-        let (_, user_pk) = get_random_keypair();
-        let mut user_id_vec = UserId(user_pk).to_vec();
-        user_id_vec.pop();
-        user_ids.push(user_id_vec);
-
-        // Wrong format (does not start with 2 nor 3)
-        user_id_vec = UserId(user_pk).to_vec();
-        user_id_vec[0] = 1;
-        user_ids.push(user_id_vec);
-
-        for user_id in user_ids {
-            match internal_api
-                .register(Request::new(common_msgs::RegisterRequest { user_id }))
-                .await
-            {
-                Err(status) => {
-                    assert_eq!(status.code(), Code::InvalidArgument);
-                    assert_eq!(status.message(), "Provided public key does not match expected format (33-byte compressed key)")
-                }
-                _ => panic!("Test should have returned Err"),
-            }
-        }
-    }
-
-    #[tokio::test]
-    async fn test_register_max_slots() {
-        let (internal_api, _s) = create_api_with_config(ApiConfig::new(u32::MAX, DURATION)).await;
-
-        let (_, user_pk) = get_random_keypair();
-        let user_id = UserId(user_pk).to_vec();
-
-        // First registration should go trough
         internal_api
-            .register(Request::new(common_msgs::RegisterRequest {
-                user_id: user_id.clone(),
+            .ban_user(Request::new(msgs::BanUserRequest {
+                user_id: user_id.to_vec(),
             }))
             .await
             .unwrap();
+        let response = internal_api
+            .get_user(Request::new(msgs::GetUserRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.banned);
 
-        // Trying to add more slots (re-register) must fail
         match internal_api
-            .register(Request::new(common_msgs::RegisterRequest { user_id }))
+            .ban_user(Request::new(msgs::BanUserRequest {
+                user_id: user_id.to_vec(),
+            }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::ResourceExhausted);
-                assert_eq!(status.message(), "Subscription maximum slots count reached")
+                assert_eq!(status.code(), Code::AlreadyExists);
+                assert_eq!(status.message(), "User already banned")
             }
             _ => panic!("Test should have returned Err"),
         }
-    }
 
-    #[tokio::test]
-    async fn test_register_service_unavailable() {
-        let (internal_api, _s) =
-            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
+        internal_api
+            .unban_user(Request::new(msgs::UnbanUserRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap();
+        match internal_api
+            .unban_user(Request::new(msgs::UnbanUserRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(status.message(), "User is not banned")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ban_user_wrong_user_id() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .ban_user(Request::new(msgs::BanUserRequest {
+                user_id: vec![1; 20],
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    "Provided public key does not match expected format (33-byte compressed key)"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .watcher
+            .add_appointment(appointment, user_signature)
+            .unwrap();
+
+        let response = internal_api
+            .delete_user(Request::new(msgs::DeleteUserRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.n_appointments, 1);
+        assert!(internal_api.watcher.get_user_info(user_id).is_none());
+        assert_eq!(internal_api.watcher.get_appointments_count(), 0);
+
+        match internal_api
+            .delete_user(Request::new(msgs::DeleteUserRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::NotFound);
+                assert_eq!(status.message(), "User not found")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grant_slots() {
+        let (internal_api, _s) = create_api().await;
+        let user_id = get_random_user_id();
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let response = internal_api
+            .grant_slots(Request::new(msgs::GrantSlotsRequest {
+                user_id: user_id.to_vec(),
+                slots: 10,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.available_slots, SLOTS + 10);
+        assert_eq!(response.subscription_expiry, START_HEIGHT as u32 + DURATION);
+
+        match internal_api
+            .grant_slots(Request::new(msgs::GrantSlotsRequest {
+                user_id: user_id.to_vec(),
+                slots: u32::MAX,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::ResourceExhausted);
+                assert_eq!(status.message(), "Subscription maximum slots count reached")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_subscription_expiry() {
+        let (internal_api, _s) = create_api().await;
+        let user_id = get_random_user_id();
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let response = internal_api
+            .set_subscription_expiry(Request::new(msgs::SetSubscriptionExpiryRequest {
+                user_id: user_id.to_vec(),
+                subscription_expiry: START_HEIGHT as u32 + 2 * DURATION,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.available_slots, SLOTS);
+        assert_eq!(
+            response.subscription_expiry,
+            START_HEIGHT as u32 + 2 * DURATION
+        );
+
+        match internal_api
+            .set_subscription_expiry(Request::new(msgs::SetSubscriptionExpiryRequest {
+                user_id: user_id.to_vec(),
+                subscription_expiry: START_HEIGHT as u32 - 1,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "Subscription expiry cannot be in the past (current height: {})",
+                        START_HEIGHT
+                    )
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_audit_log() {
+        let (internal_api, _s) = create_api().await;
+        let response = internal_api
+            .get_audit_log(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.entries.is_empty());
+
+        // Admin actions are logged in order
+        let user_id = get_random_user_id();
+        internal_api.watcher.register(user_id, "").unwrap();
+        internal_api.watcher.grant_slots(user_id, 1).unwrap();
+        internal_api.watcher.ban_user(user_id).unwrap();
+        internal_api.watcher.delete_user(user_id).unwrap();
+
+        let response = internal_api
+            .get_audit_log(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response
+                .entries
+                .iter()
+                .map(|entry| (entry.user_id.clone(), entry.action.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (user_id.to_vec(), "grant_slots"),
+                (user_id.to_vec(), "ban"),
+                (user_id.to_vec(), "delete")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issue_registration_tokens() {
+        let key = get_random_keypair().0;
+        let gate = Arc::new(TokenGate::new(
+            key,
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        ));
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_registration_gate(gate)).await;
+
+        let token = new_token();
+        let (blinded_token, blinding_factor) = blind_token(&token);
+        let response = internal_api
+            .issue_registration_tokens(Request::new(msgs::IssueRegistrationTokensRequest {
+                blinded_tokens: vec![blinded_token.serialize().to_vec()],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        // The user can check the signature has been made with the tower token key, unblind it and redeem the token
+        assert_eq!(response.blinded_signatures.len(), 1);
+        assert_eq!(response.proofs.len(), 1);
+        assert_eq!(response.token_key, token_key(&key).serialize().to_vec());
+        let blinded_signature = PublicKey::from_slice(&response.blinded_signatures[0]).unwrap();
+        let signature = unblind_signature(
+            &blinded_token,
+            &blinded_signature,
+            &TokenProof::from_slice(&response.proofs[0]).unwrap(),
+            &PublicKey::from_slice(&response.token_key).unwrap(),
+            &blinding_factor,
+        )
+        .unwrap();
+        assert!(verify_token(&token, &signature, &key));
+
+        // Malformed blinded tokens are rejected
+        match internal_api
+            .issue_registration_tokens(Request::new(msgs::IssueRegistrationTokensRequest {
+                blinded_tokens: vec![vec![0; 33]],
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert!(status.message().starts_with("Provided blinded tokens"));
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_issue_registration_tokens_no_token_gate() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .issue_registration_tokens(Request::new(msgs::IssueRegistrationTokensRequest {
+                blinded_tokens: vec![blind_token(&new_token()).0.serialize().to_vec()],
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(
+                    status.message(),
+                    "The tower is not gating registrations with tokens"
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backup() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let data_dir = tmp_dir.path().join("tower");
+        fs::create_dir_all(data_dir.join("regtest")).unwrap();
+        let dbm = DBM::new(data_dir.join("regtest").join(DB_FILE)).unwrap();
+        let (sk, pk) = get_random_keypair();
+        dbm.store_tower_key(&TowerKey::Plaintext(sk)).unwrap();
+
+        let backup = Backup::new(data_dir, "regtest".to_owned(), TowerId(pk));
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_backup(backup)).await;
+
+        let path = tmp_dir.path().join("backup");
+        let response = internal_api
+            .backup(Request::new(msgs::BackupRequest {
+                path: path.display().to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.tower_id, pk.serialize().to_vec());
+        assert_eq!(response.network, "regtest");
+        assert!(response.last_known_block.is_empty());
+        assert!(!response.encrypted_database);
+        assert!(path.join("regtest").join(DB_FILE).exists());
+
+        // Existing backups are not overwritten
+        match internal_api
+            .backup(Request::new(msgs::BackupRequest {
+                path: path.display().to_string(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::AlreadyExists),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Relative paths are rejected, since they would be relative to the tower working directory
+        match internal_api
+            .backup(Request::new(msgs::BackupRequest {
+                path: "backup".to_owned(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(status.message(), "The backup path must be absolute");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backup_not_supported() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .backup(Request::new(msgs::BackupRequest {
+                path: "/backup".to_owned(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(
+                    status.message(),
+                    "Backups are only supported by the sqlite database backend"
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop() {
+        let (internal_api, _s) = create_api().await;
+
+        assert!(!internal_api.shutdown_trigger.is_triggered());
+        internal_api.stop(Request::new(())).await.unwrap();
+        assert!(internal_api.shutdown_trigger.is_triggered());
+    }
+}
+
+#[cfg(test)]
+mod tests_public_api {
+    use super::*;
+    use std::convert::TryInto;
+
+    use bitcoin::secp256k1::SecretKey;
+
+    use crate::anti_spam::{ProofOfWorkGate, TokenGate};
+    use crate::dbm::DBM;
+    use crate::extended_appointment::UUID;
+    use crate::gatekeeper::SubscriptionTier;
+    use crate::test_utils::{
+        create_api, create_api_with_config, generate_dummy_appointment, generate_register_request,
+        ApiConfig, MockPaymentVerifier, DURATION, MIN_TO_SELF_DELAY, PRICE_MSAT, SLOTS,
+        START_HEIGHT,
+    };
+    use teos_common::anti_spam::{
+        check_pow, hash_to_curve, new_token, sign_token, solve_pow, token_key,
+    };
+    use teos_common::appointment::deletion_message;
+    use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
+    use teos_common::cryptography::{self, get_random_keypair};
+    use teos_common::receipts::{RenewalReceipt, TopUpReceipt};
+    use teos_common::test_utils::get_random_user_id;
+
+    /// Builds a registration request signed by the given user.
+    fn register_request(
+        internal_api: &InternalAPI,
+        user_sk: &SecretKey,
+        tier: &str,
+        proof: Option<common_msgs::RegistrationProof>,
+    ) -> Request<common_msgs::RegisterRequest> {
+        Request::new(generate_register_request(
+            &internal_api.watcher.tower_id,
+            user_sk,
+            tier,
+            proof,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, _) = get_random_keypair();
+
+        // Registering (even multiple times) should work
+        for _ in 0..2 {
+            let response = internal_api
+                .register(register_request(&internal_api, &user_sk, "", None))
+                .await
+                .unwrap()
+                .into_inner();
+
+            assert!(matches!(response, common_msgs::RegisterResponse { .. }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_wrong_user_id() {
+        let (internal_api, _s) = create_api().await;
+
+        let mut user_ids = Vec::new();
+
+        // Wrong user id size
+        let (_, user_pk) = get_random_keypair();
+        let mut user_id_vec = UserId(user_pk).to_vec();
+        user_id_vec.pop();
+        user_ids.push(user_id_vec);
+
+        // Wrong format (does not start with 2 nor 3)
+        user_id_vec = UserId(user_pk).to_vec();
+        user_id_vec[0] = 1;
+        user_ids.push(user_id_vec);
+
+        for user_id in user_ids {
+            match internal_api
+                .register(Request::new(common_msgs::RegisterRequest {
+                    user_id,
+                    tier: String::new(),
+                    proof: None,
+                    signature: String::new(),
+                }))
+                .await
+            {
+                Err(status) => {
+                    assert_eq!(status.code(), Code::InvalidArgument);
+                    assert_eq!(status.message(), "Provided public key does not match expected format (33-byte compressed key)")
+                }
+                _ => panic!("Test should have returned Err"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_wrong_signature() {
+        let tiers = vec![
+            SubscriptionTier::new("basic".to_owned(), SLOTS, DURATION, 0, 2048),
+            SubscriptionTier::new("premium".to_owned(), SLOTS * 2, DURATION, 0, 4096),
+        ];
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_tiers(tiers)).await;
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+
+        // Users cannot be registered by someone else
+        let mut request = register_request(&internal_api, &get_random_keypair().0, "", None);
+        request.get_mut().user_id = user_id.to_vec();
+
+        // Nor to a tier other than the one they signed for
+        let mut other_tier_request = register_request(&internal_api, &user_sk, "premium", None);
+        other_tier_request.get_mut().tier = "basic".to_owned();
+
+        // Nor using a request meant for another tower
+        let message = registration_message("", &get_random_user_id());
+        let other_tower_request = Request::new(common_msgs::RegisterRequest {
+            user_id: user_id.to_vec(),
+            tier: String::new(),
+            proof: None,
+            signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+        });
+
+        for request in [request, other_tier_request, other_tower_request] {
+            match internal_api.register(request).await {
+                Err(status) => {
+                    assert_eq!(status.code(), Code::Unauthenticated);
+                    assert_eq!(status.message(), "Wrong message or signature.")
+                }
+                _ => panic!("Test should have returned Err"),
+            }
+        }
+        assert!(internal_api.watcher.get_user_info(user_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_max_slots() {
+        let (internal_api, _s) = create_api_with_config(ApiConfig::new(u32::MAX, DURATION)).await;
+
+        let (user_sk, _) = get_random_keypair();
+
+        // First registration should go trough
+        internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+            .unwrap();
+
+        // Trying to add more slots (re-register) must fail
+        match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::ResourceExhausted);
+                assert_eq!(status.message(), "Subscription maximum slots count reached")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_payment_required() {
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_payments(verifier.clone())).await;
+
+        let (user_sk, _) = get_random_keypair();
+
+        // If subscriptions are paid, the invoice to pay is sent alongside the status
+        let invoice = match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                common_msgs::RegistrationInvoice::decode(status.details()).unwrap()
+            }
+            _ => panic!("Test should have returned Err"),
+        };
+        assert_eq!(invoice.amount_msat, PRICE_MSAT);
+
+        // Once paid, the registration goes through
+        verifier.settle(&invoice.payment_hash.try_into().unwrap());
+        let response = internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.available_slots, SLOTS);
+    }
+
+    #[tokio::test]
+    async fn test_register_payments_unavailable() {
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        verifier.set_unreachable(true);
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_payments(verifier)).await;
+
+        let (user_sk, _) = get_random_keypair();
+        match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(
+                    status.message(),
+                    "Subscription payments cannot be processed at the moment. Try again later"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_tiers() {
+        let tiers = vec![
+            SubscriptionTier::new("basic".to_owned(), SLOTS, DURATION, 0, 2048),
+            SubscriptionTier::new("premium".to_owned(), SLOTS * 2, DURATION, 0, 4096),
+        ];
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_tiers(tiers)).await;
+
+        // The response reports the tier the user has been registered to
+        let response = internal_api
+            .register(register_request(
+                &internal_api,
+                &get_random_keypair().0,
+                "premium",
+                None,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.tier, "premium");
+        assert_eq!(response.available_slots, SLOTS * 2);
+
+        // Unknown tiers are rejected
+        match internal_api
+            .register(register_request(
+                &internal_api,
+                &get_random_keypair().0,
+                "gold",
+                None,
+            ))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(status.message(), "Subscription tier not found: gold")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_tiers() {
+        // Towers with no tiers offer a single unnamed one
+        let (internal_api, _s) = create_api().await;
+        let response = internal_api
+            .get_subscription_tiers(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response.tiers,
+            vec![common_msgs::SubscriptionTier {
+                name: String::new(),
+                slots: SLOTS,
+                duration: DURATION,
+                price_msat: 0,
+                max_blob_size: ENCRYPTED_BLOB_MAX_SIZE as u32,
+            }]
+        );
+
+        let tiers = vec![
+            SubscriptionTier::new("basic".to_owned(), SLOTS, DURATION, 0, 2048),
+            SubscriptionTier::new("premium".to_owned(), SLOTS * 2, DURATION, PRICE_MSAT, 4096),
+        ];
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_tiers(tiers.clone())).await;
+        let response = internal_api
+            .get_subscription_tiers(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response
+                .tiers
+                .iter()
+                .map(|tier| (tier.name.as_str(), tier.price_msat))
+                .collect::<Vec<_>>(),
+            vec![("basic", 0), ("premium", PRICE_MSAT)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_service_unavailable() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
+
+        let (user_sk, _) = get_random_keypair();
+
+        match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(status.message(), "Service currently unavailable")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renew() {
+        // Zero-length subscriptions expire right away, so they can be renewed straightaway
+        let (internal_api, _s) = create_api_with_config(ApiConfig::new(SLOTS, 0)).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let response = internal_api
+            .renew(Request::new(common_msgs::RenewRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.user_id, user_id.to_vec());
+        assert_eq!(response.subscription_start, response.subscription_expiry);
+        assert!(RenewalReceipt::with_signature(
+            user_id,
+            response.subscription_start,
+            response.subscription_expiry,
+            response.subscription_signature
+        )
+        .verify(&internal_api.watcher.tower_id));
+    }
+
+    #[tokio::test]
+    async fn test_renew_too_early() {
+        let (internal_api, _s) = create_api().await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let receipt = internal_api.watcher.register(user_id, "").unwrap();
+
+        match internal_api
+            .renew(Request::new(common_msgs::RenewRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::ResourceExhausted);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "Subscription cannot be renewed until block {}",
+                        receipt.subscription_expiry() - DURATION + 1
+                    )
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renew_user_not_found() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .renew(Request::new(common_msgs::RenewRequest {
+                user_id: get_random_user_id().to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(
+                    status.message(),
+                    "User not found. Register before trying to renew or top up"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_top_up() {
+        let (internal_api, _s) = create_api().await;
 
         let (_, user_pk) = get_random_keypair();
-        let user_id = UserId(user_pk).to_vec();
+        let user_id = UserId(user_pk);
+        let receipt = internal_api.watcher.register(user_id, "").unwrap();
+
+        let response = internal_api
+            .top_up(Request::new(common_msgs::TopUpRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.user_id, user_id.to_vec());
+        assert_eq!(response.available_slots, SLOTS * 2);
+        assert_eq!(response.subscription_expiry, receipt.subscription_expiry());
+        assert!(TopUpReceipt::with_signature(
+            user_id,
+            response.available_slots,
+            response.subscription_expiry,
+            response.subscription_signature
+        )
+        .verify(&internal_api.watcher.tower_id));
+    }
+
+    #[tokio::test]
+    async fn test_top_up_expired_subscription() {
+        let (internal_api, _s) = create_api_with_config(ApiConfig::new(SLOTS, 0)).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let receipt = internal_api.watcher.register(user_id, "").unwrap();
 
         match internal_api
-            .register(Request::new(common_msgs::RegisterRequest { user_id }))
+            .top_up(Request::new(common_msgs::TopUpRequest {
+                user_id: user_id.to_vec(),
+            }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::Unavailable);
-                assert_eq!(status.message(), "Service currently unavailable")
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "Your subscription expired at {}. Renew it before topping it up",
+                        receipt.subscription_expiry()
+                    )
+                )
             }
             _ => panic!("Test should have returned Err"),
         }
     }
 
+    #[tokio::test]
+    async fn test_top_up_payment_required() {
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_payments(verifier.clone())).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        if let Err(RegistrationFailure::PaymentRequired(invoice)) =
+            internal_api.watcher.register(user_id, "")
+        {
+            verifier.settle(&invoice.payment_hash);
+        }
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        // Top ups are charged as registrations are
+        let invoice = match internal_api
+            .top_up(Request::new(common_msgs::TopUpRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "Subscription payment required ({PRICE_MSAT} msat). Pay the provided invoice and top up again"
+                    )
+                );
+                common_msgs::RegistrationInvoice::decode(status.details()).unwrap()
+            }
+            _ => panic!("Test should have returned Err"),
+        };
+
+        verifier.settle(&invoice.payment_hash.try_into().unwrap());
+        let response = internal_api
+            .top_up(Request::new(common_msgs::TopUpRequest {
+                user_id: user_id.to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.available_slots, SLOTS * 2);
+    }
+
     #[tokio::test]
     async fn test_add_appointment() {
         let (internal_api, _s) = create_api().await;
 
         // User must be registered
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         let appointment = generate_dummy_appointment(None).inner;
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
@@ -939,6 +2524,13 @@ mod tests_public_api {
                 assert_eq!(
                     status.message(),
                     "Invalid signature or user does not have enough slots available"
+                );
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::AuthenticationFailure as i32,
+                        ..Default::default()
+                    }
                 )
             }
             _ => panic!("Test should have returned Err"),
@@ -951,88 +2543,369 @@ mod tests_public_api {
 
         // User is registered but has no slots
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(
+                    status.message(),
+                    "Invalid signature or user does not have enough slots available"
+                );
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::NotEnoughSlots as i32,
+                        required_slots: 1,
+                        available_slots: 0,
+                        ..Default::default()
+                    }
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_rate_limited() {
+        // Users are allowed a single request (registering does not count towards it)
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_rate_limit(1, 1)).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+            .unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::ResourceExhausted);
+                assert_eq!(
+                    status.message(),
+                    "Too many requests. Try again in 60 seconds"
+                );
+                assert_eq!(status.metadata().get(RETRY_AFTER).unwrap(), "60");
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::RateLimited as i32,
+                        ..Default::default()
+                    }
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_subscription_expired() {
+        let (internal_api, _s) = create_api_with_config(ApiConfig::new(SLOTS, 0)).await;
+
+        // User is registered but subscription is expired
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert!(status.message().starts_with("Your subscription expired at"));
+
+                let rejection =
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap();
+                assert_eq!(
+                    rejection.reason,
+                    RejectionReason::SubscriptionExpired as i32
+                );
+                assert!(rejection.current_height > 0);
+                assert!(rejection.subscription_expiry <= rejection.current_height);
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_already_triggered() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .watcher
+            .add_random_tracker_to_responder(UUID::new(appointment.locator, user_id));
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::AlreadyExists);
+                assert!(status
+                    .message()
+                    .starts_with("The provided appointment has already been triggered"));
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::AlreadyTriggered as i32,
+                        ..Default::default()
+                    }
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_to_self_delay_too_small() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let mut appointment = generate_dummy_appointment(None).inner;
+        appointment.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::OutOfRange);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "The provided to_self_delay is too small (minimum: {MIN_TO_SELF_DELAY})"
+                    )
+                );
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::ToSelfDelayTooSmall as i32,
+                        min_to_self_delay: MIN_TO_SELF_DELAY as u32,
+                        ..Default::default()
+                    }
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_service_unavailable() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
+
+        let (user_sk, _) = get_random_keypair();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(status.message(), "Service currently unavailable")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let (internal_api, _s) = create_api().await;
+
+        // User must be registered
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let mut short_delay = generate_dummy_appointment(None).inner;
+        short_delay.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+
+        let response = internal_api
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                appointments: [&appointment, &short_delay]
+                    .iter()
+                    .map(|a| common_msgs::AddAppointmentRequest {
+                        appointment: Some((*a).clone().into()),
+                        signature: cryptography::sign(&a.to_vec(), &user_sk).unwrap(),
+                    })
+                    .collect(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.available_slots, SLOTS - 1);
+        assert_eq!(response.results.len(), 2);
+
+        // The first appointment is accepted
+        let accepted = &response.results[0];
+        assert_eq!(accepted.locator, appointment.locator.to_vec());
+        assert_eq!(
+            accepted.response.as_ref().unwrap().available_slots,
+            SLOTS - 1
+        );
+        assert!(accepted.rejection.is_none());
+
+        // The second is rejected
+        let rejected = &response.results[1];
+        assert_eq!(rejected.locator, short_delay.locator.to_vec());
+        assert!(rejected.response.is_none());
+        assert_eq!(
+            rejected.error,
+            format!("The provided to_self_delay is too small (minimum: {MIN_TO_SELF_DELAY})")
+        );
+        assert_eq!(
+            rejected.rejection,
+            Some(common_msgs::AppointmentRejection {
+                reason: RejectionReason::ToSelfDelayTooSmall as i32,
+                min_to_self_delay: MIN_TO_SELF_DELAY as u32,
+                ..Default::default()
+            })
+        );
+    }
 
-        let appointment = generate_dummy_appointment(None).inner;
-        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+    #[tokio::test]
+    async fn test_add_appointments_empty() {
+        let (internal_api, _s) = create_api().await;
 
         match internal_api
-            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.clone().into()),
-                signature: user_signature.clone(),
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                appointments: Vec::new(),
             }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::Unauthenticated);
-                assert_eq!(
-                    status.message(),
-                    "Invalid signature or user does not have enough slots available"
-                )
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(status.message(), "No appointments were provided");
             }
             _ => panic!("Test should have returned Err"),
         }
     }
 
     #[tokio::test]
-    async fn test_add_appointment_subscription_expired() {
-        let (internal_api, _s) = create_api_with_config(ApiConfig::new(SLOTS, 0)).await;
+    async fn test_add_appointments_too_many() {
+        let (internal_api, _s) = create_api().await;
 
-        // User is registered but subscription is expired
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
-
-        let appointment = generate_dummy_appointment(None).inner;
-        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
-
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let appointments = (0..MAX_APPOINTMENTS_PER_BATCH + 1)
+            .map(|_| {
+                let appointment = generate_dummy_appointment(None).inner;
+                common_msgs::AddAppointmentRequest {
+                    signature: cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                    appointment: Some(appointment.into()),
+                }
+            })
+            .collect();
         match internal_api
-            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.clone().into()),
-                signature: user_signature.clone(),
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                appointments,
             }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::Unauthenticated);
-                assert!(status.message().starts_with("Your subscription expired at"),)
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "Too many appointments. Expected at most {MAX_APPOINTMENTS_PER_BATCH}, received {}",
+                        MAX_APPOINTMENTS_PER_BATCH + 1
+                    )
+                );
             }
             _ => panic!("Test should have returned Err"),
         }
+        assert!(internal_api
+            .watcher
+            .get_all_watcher_appointments()
+            .is_empty());
     }
 
     #[tokio::test]
-    async fn test_add_appointment_already_triggered() {
+    async fn test_add_appointments_non_registered() {
         let (internal_api, _s) = create_api().await;
 
-        let (user_sk, user_pk) = get_random_keypair();
-        let user_id = UserId(user_pk);
-        internal_api.watcher.register(user_id).unwrap();
+        // User is not registered this time
+        let (user_sk, _) = get_random_keypair();
 
         let appointment = generate_dummy_appointment(None).inner;
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
-        internal_api
-            .watcher
-            .add_random_tracker_to_responder(UUID::new(appointment.locator, user_id));
 
         match internal_api
-            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.clone().into()),
-                signature: user_signature.clone(),
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                appointments: vec![common_msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.into()),
+                    signature: user_signature,
+                }],
             }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::AlreadyExists);
-                assert!(status
-                    .message()
-                    .starts_with("The provided appointment has already been triggered"),)
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(
+                    common_msgs::AppointmentRejection::decode(status.details()).unwrap(),
+                    common_msgs::AppointmentRejection {
+                        reason: RejectionReason::AuthenticationFailure as i32,
+                        ..Default::default()
+                    }
+                )
             }
             _ => panic!("Test should have returned Err"),
         }
     }
 
     #[tokio::test]
-    async fn test_add_appointment_service_unavailable() {
+    async fn test_add_appointments_service_unavailable() {
         let (internal_api, _s) =
             create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
 
@@ -1041,9 +2914,11 @@ mod tests_public_api {
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
 
         match internal_api
-            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.clone().into()),
-                signature: user_signature.clone(),
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                appointments: vec![common_msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.into()),
+                    signature: user_signature,
+                }],
             }))
             .await
         {
@@ -1061,7 +2936,7 @@ mod tests_public_api {
 
         // The user must be registered
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // Add the appointment
         let appointment = generate_dummy_appointment(None).inner;
@@ -1094,7 +2969,7 @@ mod tests_public_api {
 
         // Add a first user to link the appointment to him
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // There's no need to add the appointment given the subscription status is checked first
         let appointment = generate_dummy_appointment(None).inner;
@@ -1122,7 +2997,7 @@ mod tests_public_api {
 
         // The user is registered but the appointment does not exist
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // Try to get the appointment through the API
         let appointment = generate_dummy_appointment(None).inner;
@@ -1149,7 +3024,7 @@ mod tests_public_api {
 
         // Register the user
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // There s no need to add the appointment given the subscription status is checked first.
         let appointment = generate_dummy_appointment(None).inner;
@@ -1200,7 +3075,7 @@ mod tests_public_api {
 
         // The user must be registered
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // Get the subscription info though the API
         let message = "get subscription info".to_string();
@@ -1247,7 +3122,7 @@ mod tests_public_api {
 
         // The user is registered but the subscription has expired
         let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
 
         // Try to get the subscription info though the API
         let message = "get subscription info".to_string();
@@ -1265,6 +3140,213 @@ mod tests_public_api {
         }
     }
 
+    #[tokio::test]
+    async fn test_register_banned() {
+        let (internal_api, _s) = create_api().await;
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.ban_user(UserId(user_pk)).unwrap();
+
+        match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::PermissionDenied);
+                assert_eq!(status.message(), "User banned from the tower")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_proof_of_work() {
+        let gate = Arc::new(ProofOfWorkGate::new([1; 32], 8, 600));
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_registration_gate(gate)).await;
+        let (user_sk, _) = get_random_keypair();
+
+        // Users are handed a challenge if they register without solving one
+        let challenge = match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(status.metadata().get(REGISTRATION_GATE).unwrap(), "pow");
+                common_msgs::RegistrationChallenge::decode(status.details()).unwrap()
+            }
+            _ => panic!("Test should have returned Err"),
+        };
+        assert_eq!(challenge.difficulty, 8);
+
+        // Wrong solutions are rejected
+        let nonce = solve_pow(&challenge.challenge, 8);
+        let register = |nonce| {
+            internal_api.register(register_request(
+                &internal_api,
+                &user_sk,
+                "",
+                Some(common_msgs::RegistrationProof {
+                    pow: Some(common_msgs::ProofOfWork {
+                        expires_at: challenge.expires_at,
+                        nonce,
+                    }),
+                    token: None,
+                }),
+            ))
+        };
+        let wrong_nonce = (0..)
+            .find(|n| !check_pow(&challenge.challenge, *n, 8))
+            .unwrap();
+        match register(wrong_nonce).await {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert!(status
+                    .message()
+                    .starts_with("Invalid registration challenge solution"));
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // While the right one lets the user through
+        register(nonce).await.unwrap();
+
+        // Registered users do not need to go through the gate anymore
+        internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_token() {
+        let key = get_random_keypair().0;
+        let gate = Arc::new(TokenGate::new(
+            key,
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        ));
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::default()
+                .with_registration_gate(gate)
+                .with_payments(verifier.clone()),
+        )
+        .await;
+
+        let token = new_token();
+        let (signature, _) = sign_token(&hash_to_curve(&token), &key);
+        let request = |user_sk: &SecretKey| {
+            register_request(
+                &internal_api,
+                user_sk,
+                "",
+                Some(common_msgs::RegistrationProof {
+                    pow: None,
+                    token: Some(common_msgs::RegistrationToken {
+                        token: token.clone(),
+                        signature: signature.serialize().to_vec(),
+                    }),
+                }),
+            )
+        };
+
+        // Registrations with no token are rejected
+        let (user_sk, _) = get_random_keypair();
+        match internal_api
+            .register(register_request(&internal_api, &user_sk, "", None))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(status.message(), "Registration requires a token");
+                assert_eq!(status.metadata().get(REGISTRATION_GATE).unwrap(), "token");
+                assert_eq!(
+                    common_msgs::RegistrationTokenKey::decode(status.details())
+                        .unwrap()
+                        .key,
+                    token_key(&key).serialize().to_vec()
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // The token is given back if the registration needs to be paid first, so it can be used once paid
+        let invoice = match internal_api.register(request(&user_sk)).await {
+            Err(status) => common_msgs::RegistrationInvoice::decode(status.details()).unwrap(),
+            _ => panic!("Test should have returned Err"),
+        };
+        verifier.settle(&invoice.payment_hash.try_into().unwrap());
+        internal_api.register(request(&user_sk)).await.unwrap();
+
+        // Tokens can only be redeemed once
+        match internal_api
+            .register(request(&get_random_keypair().0))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(status.message(), "Registration token already redeemed");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_invalid_proof() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .register(register_request(
+                &internal_api,
+                &get_random_keypair().0,
+                "",
+                Some(common_msgs::RegistrationProof {
+                    pow: None,
+                    token: None,
+                }),
+            ))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    "Registration proof must contain either a proof of work or a token"
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_info_rate_limited() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().with_rate_limit(1, 1)).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        // The first request goes through, but the second one is over the limit
+        let message = "get subscription info".to_string();
+        let request = common_msgs::GetSubscriptionInfoRequest {
+            signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+        };
+        internal_api
+            .get_subscription_info(Request::new(request.clone()))
+            .await
+            .unwrap();
+        match internal_api
+            .get_subscription_info(Request::new(request))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::ResourceExhausted);
+                assert_eq!(status.metadata().get(RETRY_AFTER).unwrap(), "60");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
     #[tokio::test]
     async fn test_get_subscription_info_service_unavailable() {
         let (internal_api, _s) =
@@ -1285,4 +3367,170 @@ mod tests_public_api {
             _ => panic!("Test should have returned Err"),
         }
     }
+
+    #[tokio::test]
+    async fn test_delete_appointment() {
+        let (internal_api, _s) = create_api().await;
+
+        // The user must be registered and the appointment must exist
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let start_block = internal_api
+            .watcher
+            .add_appointment(
+                appointment.clone(),
+                cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+            )
+            .unwrap()
+            .0
+            .start_block();
+
+        let message = deletion_message(
+            &appointment.locator,
+            start_block,
+            &internal_api.watcher.tower_id,
+        );
+        let response = internal_api
+            .delete_appointment(Request::new(common_msgs::DeleteAppointmentRequest {
+                locator: appointment.locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.locator, appointment.locator.to_vec());
+        assert_eq!(response.available_slots, SLOTS);
+        assert!(internal_api
+            .watcher
+            .get_watcher_appointments_with_locator(appointment.locator)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_not_found() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk), "").unwrap();
+
+        let locator = generate_dummy_appointment(None).locator();
+        let message = deletion_message(
+            &locator,
+            START_HEIGHT as u32,
+            &internal_api.watcher.tower_id,
+        );
+        match internal_api
+            .delete_appointment(Request::new(common_msgs::DeleteAppointmentRequest {
+                locator: locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block: START_HEIGHT as u32,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::NotFound);
+                assert_eq!(status.message(), "Appointment not found");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_non_registered() {
+        let (internal_api, _s) = create_api().await;
+
+        // The user is not registered
+        let (user_sk, _) = get_random_keypair();
+
+        let locator = generate_dummy_appointment(None).locator();
+        let message = deletion_message(
+            &locator,
+            START_HEIGHT as u32,
+            &internal_api.watcher.tower_id,
+        );
+        match internal_api
+            .delete_appointment(Request::new(common_msgs::DeleteAppointmentRequest {
+                locator: locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block: START_HEIGHT as u32,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(status.message(), "User cannot be authenticated");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_already_triggered() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id, "").unwrap();
+
+        let locator = generate_dummy_appointment(None).locator();
+        internal_api
+            .watcher
+            .add_random_tracker_to_responder(UUID::new(locator, user_id));
+
+        let message = deletion_message(
+            &locator,
+            START_HEIGHT as u32,
+            &internal_api.watcher.tower_id,
+        );
+        match internal_api
+            .delete_appointment(Request::new(common_msgs::DeleteAppointmentRequest {
+                locator: locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block: START_HEIGHT as u32,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::AlreadyExists);
+                assert_eq!(
+                    status.message(),
+                    "The requested appointment has already been triggered"
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment_service_unavailable() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, DURATION).bitcoind_unreachable()).await;
+
+        let (user_sk, _) = get_random_keypair();
+        let locator = generate_dummy_appointment(None).locator();
+        let message = deletion_message(
+            &locator,
+            START_HEIGHT as u32,
+            &internal_api.watcher.tower_id,
+        );
+        match internal_api
+            .delete_appointment(Request::new(common_msgs::DeleteAppointmentRequest {
+                locator: locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                start_block: START_HEIGHT as u32,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(status.message(), "Service currently unavailable");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
 }
@@ -0,0 +1,292 @@
+//! Logic related to the additional backends penalties are broadcast through, on top of the tower's own `bitcoind`.
+//!
+//! Pushing transactions through several independent backends prevents a single node with a poor view of the mempool (or
+//! an eclipsed one) from silently sinking a penalty.
+
+use std::time::Duration;
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::Transaction;
+use bitcoincore_rpc::{
+    jsonrpc::error::Error::Rpc as RpcError, jsonrpc::error::Error::Transport as TransportError,
+    Auth, Client as BitcoindClient, Error::JsonRpc as JsonRpcError, RpcApi,
+};
+
+use crate::responder::ConfirmationStatus;
+use crate::{errors, rpc_errors};
+
+/// Time to wait for an Esplora backend to answer before considering it unreachable.
+const ESPLORA_TIMEOUT: u64 = 10;
+
+/// A backend transactions can be pushed through.
+pub trait BroadcastBackend: Send + Sync {
+    /// A human readable identifier of the backend. Used for logging purposes.
+    fn name(&self) -> &str;
+
+    /// Pushes a [Transaction] through the backend.
+    ///
+    /// Returns the resulting [ConfirmationStatus], or [None] if the backend could not be reached.
+    fn send_transaction(&self, tx: &Transaction, height: u32) -> Option<ConfirmationStatus>;
+}
+
+/// Maps an error code returned by `sendrawtransaction` to a [ConfirmationStatus].
+fn status_from_error_code(code: i32) -> ConfirmationStatus {
+    match code {
+        // See Carrier::send_transaction
+        rpc_errors::RPC_VERIFY_ALREADY_IN_CHAIN => ConfirmationStatus::IrrevocablyResolved,
+        rpc_errors::RPC_VERIFY_REJECTED
+        | rpc_errors::RPC_VERIFY_ERROR
+        | rpc_errors::RPC_DESERIALIZATION_ERROR => ConfirmationStatus::Rejected(code),
+        _ => ConfirmationStatus::Rejected(errors::UNKNOWN_JSON_RPC_EXCEPTION),
+    }
+}
+
+/// A `bitcoind` node, other than the one the tower is connected to, reachable via RPC.
+#[derive(Debug)]
+pub struct BitcoindBackend {
+    url: String,
+    client: BitcoindClient,
+}
+
+impl BitcoindBackend {
+    /// Creates a new [BitcoindBackend] instance.
+    pub fn new(url: &str, auth: Auth) -> Result<Self, bitcoincore_rpc::Error> {
+        Ok(BitcoindBackend {
+            url: url.to_owned(),
+            client: BitcoindClient::new(url, auth)?,
+        })
+    }
+}
+
+impl BroadcastBackend for BitcoindBackend {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn send_transaction(&self, tx: &Transaction, height: u32) -> Option<ConfirmationStatus> {
+        match self.client.send_raw_transaction(tx) {
+            Ok(_) => Some(ConfirmationStatus::InMempoolSince(height)),
+            Err(JsonRpcError(RpcError(rpcerr))) => Some(status_from_error_code(rpcerr.code)),
+            Err(JsonRpcError(TransportError(_))) => None,
+            Err(e) => {
+                log::error!(
+                    "Unexpected error when calling sendrawtransaction on {}: {e:?}",
+                    self.url
+                );
+                Some(ConfirmationStatus::Rejected(
+                    errors::UNKNOWN_JSON_RPC_EXCEPTION,
+                ))
+            }
+        }
+    }
+}
+
+/// An Esplora-style REST API (`POST /tx`).
+///
+/// Requests are performed using a blocking client, which cannot be created nor used from within an async context.
+/// Hence, the client is built on demand, and transactions must be sent from a thread not managed by the async runtime.
+#[derive(Debug)]
+pub struct EsploraBackend {
+    url: String,
+}
+
+impl EsploraBackend {
+    /// Creates a new [EsploraBackend] instance.
+    pub fn new(url: &str) -> Self {
+        EsploraBackend {
+            url: url.trim_end_matches('/').to_owned(),
+        }
+    }
+}
+
+impl BroadcastBackend for EsploraBackend {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn send_transaction(&self, tx: &Transaction, height: u32) -> Option<ConfirmationStatus> {
+        let client = match reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(ESPLORA_TIMEOUT))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Cannot build an HTTP client to reach {}: {e}", self.url);
+                return None;
+            }
+        };
+
+        let response = match client
+            .post(format!("{}/tx", self.url))
+            .body(serialize_hex(tx))
+            .send()
+        {
+            Ok(response) => response,
+            Err(e) => {
+                log::debug!("Cannot reach {}: {e}", self.url);
+                return None;
+            }
+        };
+
+        if response.status().is_success() {
+            Some(ConfirmationStatus::InMempoolSince(height))
+        } else if response.status().is_client_error() {
+            // Esplora forwards bitcoind's rejection as text, e.g. `sendrawtransaction RPC error: {"code":-26,"message":"..."}`
+            let body = response.text().unwrap_or_default();
+            let code = body
+                .find('{')
+                .and_then(|i| serde_json::from_str::<serde_json::Value>(&body[i..]).ok())
+                .and_then(|error| error["code"].as_i64())
+                .map_or(errors::UNKNOWN_JSON_RPC_EXCEPTION, |code| code as i32);
+            Some(status_from_error_code(code))
+        } else {
+            log::debug!(
+                "Unexpected response from {}: {}",
+                self.url,
+                response.status()
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::thread;
+
+    use warp::Filter;
+
+    use crate::test_utils::{get_random_tx, start_server, BitcoindMock, MockOptions};
+
+    const HEIGHT: u32 = 42;
+
+    // Runs a mocked Esplora API answering every `POST /tx` with the given status and body
+    fn start_esplora_mock(status: u16, body: &'static str) -> SocketAddr {
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async move {
+                let route = warp::post().and(warp::path("tx")).map(move || {
+                    warp::reply::with_status(
+                        body,
+                        warp::http::StatusCode::from_u16(status).unwrap(),
+                    )
+                });
+                let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+                tx.send(addr).unwrap();
+                server.await
+            })
+        });
+
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn test_status_from_error_code() {
+        assert_eq!(
+            status_from_error_code(rpc_errors::RPC_VERIFY_ALREADY_IN_CHAIN),
+            ConfirmationStatus::IrrevocablyResolved
+        );
+        for code in [
+            rpc_errors::RPC_VERIFY_REJECTED,
+            rpc_errors::RPC_VERIFY_ERROR,
+            rpc_errors::RPC_DESERIALIZATION_ERROR,
+        ] {
+            assert_eq!(
+                status_from_error_code(code),
+                ConfirmationStatus::Rejected(code)
+            );
+        }
+        assert_eq!(
+            status_from_error_code(rpc_errors::RPC_MISC_ERROR),
+            ConfirmationStatus::Rejected(errors::UNKNOWN_JSON_RPC_EXCEPTION)
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_backend_send_transaction() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let backend = BitcoindBackend::new(bitcoind_mock.url(), Auth::None).unwrap();
+        start_server(bitcoind_mock.server);
+
+        assert_eq!(
+            backend.send_transaction(&get_random_tx(), HEIGHT),
+            Some(ConfirmationStatus::InMempoolSince(HEIGHT))
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_backend_send_transaction_rejected() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+        ));
+        let backend = BitcoindBackend::new(bitcoind_mock.url(), Auth::None).unwrap();
+        start_server(bitcoind_mock.server);
+
+        assert_eq!(
+            backend.send_transaction(&get_random_tx(), HEIGHT),
+            Some(ConfirmationStatus::Rejected(
+                rpc_errors::RPC_VERIFY_REJECTED
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_backend_unreachable() {
+        // The server is never started
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let backend = BitcoindBackend::new(bitcoind_mock.url(), Auth::None).unwrap();
+        drop(bitcoind_mock);
+
+        assert_eq!(backend.send_transaction(&get_random_tx(), HEIGHT), None);
+    }
+
+    #[test]
+    fn test_esplora_backend_send_transaction() {
+        let addr = start_esplora_mock(200, "txid");
+        let backend = EsploraBackend::new(&format!("http://{addr}/"));
+
+        assert_eq!(
+            backend.send_transaction(&get_random_tx(), HEIGHT),
+            Some(ConfirmationStatus::InMempoolSince(HEIGHT))
+        );
+    }
+
+    #[test]
+    fn test_esplora_backend_send_transaction_rejected() {
+        let addr = start_esplora_mock(
+            400,
+            r#"sendrawtransaction RPC error: {"code":-26,"message":"min relay fee not met"}"#,
+        );
+        let backend = EsploraBackend::new(&format!("http://{addr}"));
+        assert_eq!(
+            backend.send_transaction(&get_random_tx(), HEIGHT),
+            Some(ConfirmationStatus::Rejected(
+                rpc_errors::RPC_VERIFY_REJECTED
+            ))
+        );
+
+        // Rejections that cannot be parsed are flagged as unknown
+        let addr = start_esplora_mock(400, "bad request");
+        let backend = EsploraBackend::new(&format!("http://{addr}"));
+        assert_eq!(
+            backend.send_transaction(&get_random_tx(), HEIGHT),
+            Some(ConfirmationStatus::Rejected(
+                errors::UNKNOWN_JSON_RPC_EXCEPTION
+            ))
+        );
+    }
+
+    #[test]
+    fn test_esplora_backend_unreachable() {
+        let addr = start_esplora_mock(503, "");
+        let backend = EsploraBackend::new(&format!("http://{addr}"));
+        assert_eq!(backend.send_transaction(&get_random_tx(), HEIGHT), None);
+
+        // Nothing listening on the given address
+        let backend = EsploraBackend::new("http://127.0.0.1:1");
+        assert_eq!(backend.send_transaction(&get_random_tx(), HEIGHT), None);
+    }
+}
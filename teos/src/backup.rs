@@ -0,0 +1,674 @@
+//! Logic related to the online backup of the tower data, and to restoring a tower from a backup.
+//!
+//! A backup is a directory mirroring the layout of the tower data directory: the TLS identities at its root, and the
+//! database and the Tor key in the network directory. A [Manifest] describing the backup is stored alongside them.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::backup::{Backup as SqliteBackup, StepResult};
+use rusqlite::{Connection, Error as SqliteError, OpenFlags};
+use serde::{Deserialize, Serialize};
+
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::BlockHash;
+
+use teos_common::dbm::Error as DBError;
+use teos_common::TowerId;
+
+use crate::dbm::{Storage, DBM};
+use crate::encryption::TowerKey;
+use crate::payments::unix_time;
+
+/// Name of the file describing a backup.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Name of the tower database file, within the network directory.
+pub const DB_FILE: &str = "teos_db.sql3";
+
+/// Name of the Tor secret key file, within the network directory (see [TorAPI](crate::api::tor::TorAPI)).
+const TOR_KEY_FILE: &str = "onion_v3_sk";
+
+/// Number of database pages copied per backup step. The database is only locked while a step runs, so the tower can
+/// keep writing to it in between.
+const PAGES_PER_STEP: i32 = 100;
+
+/// How long to wait between backup steps.
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+/// How long to wait before retrying to copy the database if it is locked.
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Maximum number of times a backup step is retried if the database is locked.
+const MAX_BUSY_RETRIES: u32 = 100;
+
+/// Number of times the copy can be restarted (because the tower wrote to the database in between steps) before copying
+/// the remaining pages in a single step.
+const MAX_RESTARTS: u32 = 3;
+
+/// Names of the TLS identity files of the private API, within the data directory (see [tls_init](crate::tls::tls_init)).
+const TLS_FILES: [&str; 6] = [
+    "ca.pem",
+    "ca-key.pem",
+    "server.pem",
+    "server-key.pem",
+    "client.pem",
+    "client-key.pem",
+];
+
+/// Packs the errors than can raise when backing up or restoring the tower data.
+#[derive(Debug)]
+pub enum BackupError {
+    /// The backup destination, or the restored database, already exists.
+    AlreadyExists(PathBuf),
+    /// The backup is missing some data, or it cannot be read.
+    InvalidBackup(String),
+    /// The backup belongs to a tower running on a different network.
+    WrongNetwork(String),
+    /// The tower key held by the backup does not match the tower id of its manifest. Contains the id of the key found.
+    TowerIdMismatch(TowerId),
+    /// The last known block held by the backup does not match the chain tip of its manifest.
+    ChainTipMismatch {
+        expected: Option<BlockHash>,
+        found: Option<BlockHash>,
+    },
+    Database(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::AlreadyExists(path) => write!(f, "{} already exists", path.display()),
+            BackupError::InvalidBackup(e) => write!(f, "Invalid backup: {e}"),
+            BackupError::WrongNetwork(network) => {
+                write!(f, "The backup belongs to a tower running on {network}")
+            }
+            BackupError::TowerIdMismatch(tower_id) => write!(
+                f,
+                "The backup holds the key of tower {tower_id} instead of the one in its manifest"
+            ),
+            BackupError::ChainTipMismatch { expected, found } => write!(
+                f,
+                "The backup was taken at block {expected:?}, but its database is at block {found:?}"
+            ),
+            BackupError::Database(e) => write!(f, "Database error: {e}"),
+            BackupError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<SqliteError> for BackupError {
+    fn from(e: SqliteError) -> Self {
+        BackupError::Database(e.to_string())
+    }
+}
+
+impl From<DBError> for BackupError {
+    fn from(e: DBError) -> Self {
+        BackupError::Database(format!("{e:?}"))
+    }
+}
+
+/// Describes a backup of the tower data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The id of the tower the backup belongs to.
+    pub tower_id: TowerId,
+    /// The network the tower runs on.
+    pub network: String,
+    /// The last block processed by the tower when the backup was taken.
+    pub last_known_block: Option<BlockHash>,
+    /// Whether the database is encrypted.
+    pub encrypted_database: bool,
+    /// Whether the tower key is encrypted.
+    pub encrypted_tower_key: bool,
+    /// When the backup was taken, in seconds since the epoch.
+    pub created_at: u64,
+}
+
+/// Opens a `SQLite` connection, keyed with the passphrase the database is encrypted under, if any.
+fn open_connection(
+    db_path: &Path,
+    passphrase: Option<&str>,
+    flags: OpenFlags,
+) -> Result<Connection, SqliteError> {
+    let connection = Connection::open_with_flags(db_path, flags)?;
+    if let Some(passphrase) = passphrase {
+        connection.pragma_update(None, "key", passphrase)?;
+    }
+    Ok(connection)
+}
+
+/// Opens a [DBM] on top of a database, encrypted under the given passphrase, if any.
+fn open_dbm(db_path: PathBuf, passphrase: Option<&str>) -> Result<DBM, BackupError> {
+    match passphrase {
+        Some(passphrase) => DBM::new_encrypted(db_path, passphrase),
+        None => DBM::new(db_path),
+    }
+    .map_err(|e| BackupError::Database(e.to_string()))
+}
+
+/// Copies a file, if it exists.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    if from.exists() {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Copies the TLS identities and the Tor key from one data directory to another.
+fn copy_identities(from: &Path, to: &Path, network: &str) -> io::Result<()> {
+    for file in TLS_FILES {
+        copy_file(&from.join(file), &to.join(file))?;
+    }
+    copy_file(
+        &from.join(network).join(TOR_KEY_FILE),
+        &to.join(network).join(TOR_KEY_FILE),
+    )
+}
+
+/// Copies a database using the `SQLite` online backup, a given number of pages at a time.
+///
+/// The source database is only locked while a step runs, so other connections can write to it in between. Writes
+/// restart the copy, so, to make sure it completes, the remaining pages are copied in a single step after
+/// [MAX_RESTARTS] restarts. Steps are retried if the database is locked, up to [MAX_BUSY_RETRIES] times.
+fn copy_database(sqlite_backup: &SqliteBackup, pages_per_step: i32) -> Result<(), BackupError> {
+    let mut pages_per_step = pages_per_step;
+    let mut remaining = None;
+    let mut restarts = 0;
+    let mut busy_retries = 0;
+
+    loop {
+        match sqlite_backup.step(pages_per_step)? {
+            StepResult::Done => return Ok(()),
+            StepResult::More => {
+                // Having more pages left than after the previous step means the copy has been restarted
+                let progress = sqlite_backup.progress();
+                if matches!(remaining, Some(remaining) if progress.remaining > remaining) {
+                    restarts += 1;
+                    if restarts >= MAX_RESTARTS {
+                        pages_per_step = -1;
+                    }
+                }
+                remaining = Some(progress.remaining);
+                thread::sleep(STEP_PAUSE);
+            }
+            _ => {
+                busy_retries += 1;
+                if busy_retries > MAX_BUSY_RETRIES {
+                    return Err(BackupError::Database(
+                        "The database has been locked for too long".to_owned(),
+                    ));
+                }
+                thread::sleep(BUSY_RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// Component in charge of backing up the tower data while the tower is running.
+#[derive(Clone)]
+pub struct Backup {
+    /// The tower data directory.
+    data_dir: PathBuf,
+    /// The network the tower runs on, which names the directory the database is stored in.
+    network: String,
+    /// The id of the tower.
+    tower_id: TowerId,
+    /// The passphrase the database is encrypted under, if any.
+    passphrase: Option<String>,
+    /// Number of database pages copied per backup step.
+    pages_per_step: i32,
+}
+
+impl Backup {
+    /// Creates a new [Backup] instance, for the data of the given tower.
+    pub fn new(data_dir: PathBuf, network: String, tower_id: TowerId) -> Self {
+        Self {
+            data_dir,
+            network,
+            tower_id,
+            passphrase: None,
+            pages_per_step: PAGES_PER_STEP,
+        }
+    }
+
+    /// Sets the passphrase the database is encrypted under. Backups of encrypted databases are encrypted under the
+    /// same passphrase.
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Backs the tower data up to the given path, which must not exist.
+    ///
+    /// The database is copied using the `SQLite` online backup, a few pages at a time, so the tower can keep running
+    /// (and writing to it) in the meantime. Nothing is left behind if the backup fails.
+    pub fn create(&self, path: &Path) -> Result<Manifest, BackupError> {
+        if path.exists() {
+            return Err(BackupError::AlreadyExists(path.to_owned()));
+        }
+
+        let result = self.write(path);
+        if result.is_err() {
+            fs::remove_dir_all(path).ok();
+        }
+        result
+    }
+
+    /// Writes the backup files to the given path.
+    fn write(&self, path: &Path) -> Result<Manifest, BackupError> {
+        let passphrase = self.passphrase.as_deref();
+        let backup_db_path = path.join(&self.network).join(DB_FILE);
+        fs::create_dir_all(path.join(&self.network))?;
+
+        let source = open_connection(
+            &self.data_dir.join(&self.network).join(DB_FILE),
+            passphrase,
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?;
+        let mut destination = open_connection(&backup_db_path, passphrase, OpenFlags::default())?;
+        let sqlite_backup = SqliteBackup::new(&source, &mut destination)?;
+        copy_database(&sqlite_backup, self.pages_per_step)?;
+        drop(sqlite_backup);
+        drop(destination);
+
+        // The manifest is built out of the copy, so it describes the backed up data
+        let dbm = open_dbm(backup_db_path, passphrase)?;
+        let manifest = Manifest {
+            tower_id: self.tower_id,
+            network: self.network.clone(),
+            last_known_block: dbm.load_last_known_block()?,
+            encrypted_database: passphrase.is_some(),
+            encrypted_tower_key: matches!(dbm.load_tower_key()?, Some(TowerKey::Encrypted(_))),
+            created_at: unix_time(),
+        };
+        drop(dbm);
+
+        copy_identities(&self.data_dir, path, &self.network)?;
+        fs::write(
+            path.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest).unwrap(),
+        )?;
+
+        Ok(manifest)
+    }
+}
+
+/// Reads the [Manifest] of the backup at the given path.
+pub fn read_manifest(path: &Path) -> Result<Manifest, BackupError> {
+    let manifest = fs::read(path.join(MANIFEST_FILE))
+        .map_err(|e| BackupError::InvalidBackup(format!("Cannot read the manifest: {e}")))?;
+    serde_json::from_slice(&manifest)
+        .map_err(|e| BackupError::InvalidBackup(format!("Cannot parse the manifest: {e}")))
+}
+
+/// Checks that the database of a backup holds the key of the tower in its manifest, and that it is at the chain tip
+/// the backup was taken at.
+fn validate_database(
+    db_path: &Path,
+    manifest: &Manifest,
+    passphrase: Option<&str>,
+) -> Result<(), BackupError> {
+    let dbm = open_dbm(
+        db_path.to_owned(),
+        passphrase.filter(|_| manifest.encrypted_database),
+    )?;
+
+    let sk = match dbm.load_tower_key()? {
+        Some(TowerKey::Plaintext(sk)) => sk,
+        Some(key) => {
+            let passphrase = passphrase.ok_or_else(|| {
+                BackupError::InvalidBackup("The tower key is encrypted".to_owned())
+            })?;
+            key.unlock(passphrase).map_err(|e| {
+                BackupError::InvalidBackup(format!("Cannot decrypt the tower key: {e}"))
+            })?
+        }
+        None => {
+            return Err(BackupError::InvalidBackup(
+                "The database holds no tower key".to_owned(),
+            ))
+        }
+    };
+    let tower_id = TowerId(PublicKey::from_secret_key(&Secp256k1::new(), &sk));
+    if tower_id != manifest.tower_id {
+        return Err(BackupError::TowerIdMismatch(tower_id));
+    }
+
+    let last_known_block = dbm.load_last_known_block()?;
+    if last_known_block != manifest.last_known_block {
+        return Err(BackupError::ChainTipMismatch {
+            expected: manifest.last_known_block,
+            found: last_known_block,
+        });
+    }
+
+    Ok(())
+}
+
+/// Restores the backup at the given path into the tower data directory.
+///
+/// The backup is validated before restoring anything: it must belong to a tower running on the given network, and its
+/// database must hold the key of the tower and be at the chain tip stated by its manifest. The passphrase is only
+/// used if the database or the tower key are encrypted.
+///
+/// An existing database is never overwritten, whereas the TLS identities and the Tor key are replaced by the backed up
+/// ones.
+pub fn restore(
+    path: &Path,
+    data_dir: &Path,
+    network: &str,
+    passphrase: Option<&str>,
+) -> Result<Manifest, BackupError> {
+    let manifest = read_manifest(path)?;
+    if manifest.network != network {
+        return Err(BackupError::WrongNetwork(manifest.network));
+    }
+
+    let backup_db_path = path.join(network).join(DB_FILE);
+    if !backup_db_path.exists() {
+        return Err(BackupError::InvalidBackup(
+            "The backup holds no database".to_owned(),
+        ));
+    }
+    let db_path = data_dir.join(network).join(DB_FILE);
+    if db_path.exists() {
+        return Err(BackupError::AlreadyExists(db_path));
+    }
+
+    // The database is validated on a copy, so the backup is left untouched and nothing is restored if it is not valid
+    fs::create_dir_all(data_dir.join(network))?;
+    let staged_db_path = db_path.with_extension("restoring");
+    fs::copy(&backup_db_path, &staged_db_path)?;
+    if let Err(e) = validate_database(&staged_db_path, &manifest, passphrase) {
+        fs::remove_file(&staged_db_path).ok();
+        return Err(e);
+    }
+    fs::rename(&staged_db_path, &db_path)?;
+
+    copy_identities(path, data_dir, network)?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempdir::TempDir;
+
+    use bitcoin::hashes::Hash;
+
+    use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+    use teos_common::test_utils::get_random_user_id;
+
+    use crate::gatekeeper::UserInfo;
+    use crate::test_utils::{AVAILABLE_SLOTS, SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START};
+
+    const NETWORK: &str = "regtest";
+
+    /// Sets up the data directory of a running tower, returning the [Backup] instance in charge of it.
+    fn setup_tower(data_dir: &Path, passphrase: Option<&str>) -> Backup {
+        fs::create_dir_all(data_dir.join(NETWORK)).unwrap();
+        let dbm = open_dbm(data_dir.join(NETWORK).join(DB_FILE), passphrase).unwrap();
+
+        let (sk, pk) = get_random_keypair();
+        dbm.store_tower_key(&TowerKey::Plaintext(sk)).unwrap();
+        dbm.store_last_known_block(&BlockHash::from_slice(&get_random_bytes(32)).unwrap())
+            .unwrap();
+        dbm.store_user(
+            get_random_user_id(),
+            &UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY),
+        )
+        .unwrap();
+
+        for file in TLS_FILES {
+            fs::write(data_dir.join(file), get_random_bytes(32)).unwrap();
+        }
+        fs::write(
+            data_dir.join(NETWORK).join(TOR_KEY_FILE),
+            get_random_bytes(64),
+        )
+        .unwrap();
+
+        let backup = Backup::new(data_dir.to_owned(), NETWORK.to_owned(), TowerId(pk));
+        match passphrase {
+            Some(passphrase) => backup.with_passphrase(passphrase.to_owned()),
+            None => backup,
+        }
+    }
+
+    /// Checks that two data directories hold the same identities.
+    fn assert_same_identities(a: &Path, b: &Path) {
+        for file in TLS_FILES {
+            assert_eq!(
+                fs::read(a.join(file)).unwrap(),
+                fs::read(b.join(file)).unwrap()
+            );
+        }
+        assert_eq!(
+            fs::read(a.join(NETWORK).join(TOR_KEY_FILE)).unwrap(),
+            fs::read(b.join(NETWORK).join(TOR_KEY_FILE)).unwrap()
+        );
+    }
+
+    /// Overwrites the manifest of a backup.
+    fn write_manifest(path: &Path, manifest: &Manifest) {
+        fs::write(
+            path.join(MANIFEST_FILE),
+            serde_json::to_string(manifest).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_create_restore() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let data_dir = tmp_dir.path().join("tower");
+        let backup_path = tmp_dir.path().join("backup");
+        let backup = setup_tower(&data_dir, None);
+
+        // The tower keeps its database open while the backup is taken
+        let dbm = DBM::new(data_dir.join(NETWORK).join(DB_FILE)).unwrap();
+        let manifest = backup.create(&backup_path).unwrap();
+        assert_eq!(manifest, read_manifest(&backup_path).unwrap());
+        assert_eq!(manifest.tower_id, backup.tower_id);
+        assert_eq!(manifest.network, NETWORK);
+        assert_eq!(
+            manifest.last_known_block,
+            dbm.load_last_known_block().unwrap()
+        );
+        assert!(!manifest.encrypted_database);
+        assert!(!manifest.encrypted_tower_key);
+        assert_same_identities(&data_dir, &backup_path);
+
+        // Backups are not overwritten
+        assert!(matches!(
+            backup.create(&backup_path),
+            Err(BackupError::AlreadyExists(_))
+        ));
+
+        // The backup is restored into a fresh data directory
+        let restored_dir = tmp_dir.path().join("restored");
+        assert_eq!(
+            restore(&backup_path, &restored_dir, NETWORK, None).unwrap(),
+            manifest
+        );
+        assert_same_identities(&data_dir, &restored_dir);
+        let restored_dbm = DBM::new(restored_dir.join(NETWORK).join(DB_FILE)).unwrap();
+        assert_eq!(restored_dbm.load_all_users(), dbm.load_all_users());
+        assert_eq!(
+            restored_dbm.load_tower_key().unwrap(),
+            dbm.load_tower_key().unwrap()
+        );
+        assert!(!restored_dir
+            .join(NETWORK)
+            .join(DB_FILE)
+            .with_extension("restoring")
+            .exists());
+
+        // Existing databases are not overwritten
+        assert!(matches!(
+            restore(&backup_path, &restored_dir, NETWORK, None),
+            Err(BackupError::AlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_create_while_writing() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let data_dir = tmp_dir.path().join("tower");
+        let backup_path = tmp_dir.path().join("backup");
+        let mut backup = setup_tower(&data_dir, None);
+        // Copy a page per step, so the tower gets to write while the backup is being taken
+        backup.pages_per_step = 1;
+
+        // The tower keeps storing users until the backup is done
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let db_path = data_dir.join(NETWORK).join(DB_FILE);
+            let done = done.clone();
+            thread::spawn(move || {
+                let dbm = DBM::new(db_path).unwrap();
+                let mut stored = 0;
+                while !done.load(Ordering::Relaxed) {
+                    dbm.store_user(
+                        get_random_user_id(),
+                        &UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY),
+                    )
+                    .unwrap();
+                    stored += 1;
+                }
+                stored
+            })
+        };
+
+        let manifest = backup.create(&backup_path);
+        done.store(true, Ordering::Relaxed);
+        assert!(writer.join().unwrap() > 0);
+
+        // The backup is consistent, so it can be restored
+        assert_eq!(
+            restore(
+                &backup_path,
+                &tmp_dir.path().join("restored"),
+                NETWORK,
+                None
+            )
+            .unwrap(),
+            manifest.unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_create_restore_encrypted() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let data_dir = tmp_dir.path().join("tower");
+        let backup_path = tmp_dir.path().join("backup");
+        let backup = setup_tower(&data_dir, Some("passphrase"));
+
+        let manifest = backup.create(&backup_path).unwrap();
+        assert!(manifest.encrypted_database);
+
+        // The backed up database is as encrypted as the original one
+        assert!(DBM::new(backup_path.join(NETWORK).join(DB_FILE)).is_err());
+        let restored_dir = tmp_dir.path().join("restored");
+        assert!(matches!(
+            restore(
+                &backup_path,
+                &restored_dir,
+                NETWORK,
+                Some("wrong passphrase")
+            ),
+            Err(BackupError::Database(_))
+        ));
+        restore(&backup_path, &restored_dir, NETWORK, Some("passphrase")).unwrap();
+        DBM::new_encrypted(restored_dir.join(NETWORK).join(DB_FILE), "passphrase").unwrap();
+    }
+
+    #[test]
+    fn test_create_missing_database() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let backup_path = tmp_dir.path().join("backup");
+        let backup = Backup::new(
+            tmp_dir.path().join("tower"),
+            NETWORK.to_owned(),
+            TowerId(get_random_keypair().1),
+        );
+
+        // Failed backups leave nothing behind
+        assert!(matches!(
+            backup.create(&backup_path),
+            Err(BackupError::Database(_))
+        ));
+        assert!(!backup_path.exists());
+    }
+
+    #[test]
+    fn test_restore_invalid_backup() {
+        let tmp_dir = TempDir::new("teos").unwrap();
+        let data_dir = tmp_dir.path().join("tower");
+        let backup_path = tmp_dir.path().join("backup");
+        let restored_dir = tmp_dir.path().join("restored");
+        let manifest = setup_tower(&data_dir, None).create(&backup_path).unwrap();
+
+        // Backups of towers running on other networks are rejected
+        assert!(matches!(
+            restore(&backup_path, &restored_dir, "testnet", None),
+            Err(BackupError::WrongNetwork(network)) if network == NETWORK
+        ));
+
+        // So are backups that do not hold the key of the tower they claim to belong to
+        write_manifest(
+            &backup_path,
+            &Manifest {
+                tower_id: TowerId(get_random_keypair().1),
+                ..manifest.clone()
+            },
+        );
+        assert!(matches!(
+            restore(&backup_path, &restored_dir, NETWORK, None),
+            Err(BackupError::TowerIdMismatch(found)) if found == manifest.tower_id
+        ));
+
+        // And backups whose database is not at the chain tip stated by the manifest
+        write_manifest(
+            &backup_path,
+            &Manifest {
+                last_known_block: None,
+                ..manifest.clone()
+            },
+        );
+        assert!(matches!(
+            restore(&backup_path, &restored_dir, NETWORK, None),
+            Err(BackupError::ChainTipMismatch { expected: None, found }) if found == manifest.last_known_block
+        ));
+
+        // Nothing is restored from invalid backups
+        assert!(!restored_dir.join(NETWORK).join(DB_FILE).exists());
+        assert!(!restored_dir.join(TLS_FILES[0]).exists());
+
+        // Backups without a manifest are not backups at all
+        fs::remove_file(backup_path.join(MANIFEST_FILE)).unwrap();
+        assert!(matches!(
+            restore(&backup_path, &restored_dir, NETWORK, None),
+            Err(BackupError::InvalidBackup(_))
+        ));
+    }
+}
@@ -9,8 +9,9 @@
  * at your option.
 */
 
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -23,138 +24,639 @@ use lightning_block_sync::http::{HttpEndpoint, JsonResponse};
 use lightning_block_sync::rpc::RpcClient;
 use lightning_block_sync::{AsyncBlockSourceResult, BlockHeaderData, BlockSource};
 
-/// A simple implementation of a bitcoind client (`bitcoin-cli`) with the minimal functionality required by the tower.
-pub struct BitcoindClient<'a> {
-    /// The underlying RPC client.
-    bitcoind_rpc_client: Arc<Mutex<RpcClient>>,
+use crate::config::ConfigError;
+
+/// How many blocks the active backend can lag behind the rest before the tower switches to a more up to date one.
+pub const MAX_TIP_LAG: u32 = 2;
+
+/// Checks whether an error returned by the RPC client is due to the backend being unreachable, as opposed to the
+/// backend replying with an error (or with data that cannot be parsed).
+fn is_connection_error(e: &Error) -> bool {
+    !matches!(
+        e.kind(),
+        ErrorKind::Other | ErrorKind::InvalidData | ErrorKind::InvalidInput
+    )
+}
+
+/// A wrapper type to extract the "chain" key from a getblockchaininfo JsonResponse.
+struct BtcNetwork(String);
+
+impl TryInto<BtcNetwork> for JsonResponse {
+    type Error = std::io::Error;
+    fn try_into(self) -> std::io::Result<BtcNetwork> {
+        match self.0["chain"].as_str() {
+            Some(chain) => Ok(BtcNetwork(chain.to_owned())),
+            None => Err(Error::new(ErrorKind::InvalidData, "expected chain")),
+        }
+    }
+}
+
+/// A `bitcoind` RPC endpoint, alongside the credentials needed to access it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RpcEndpoint {
     /// The hostname to connect to.
-    host: &'a str,
+    pub host: String,
     /// The port to connect to.
-    port: u16,
+    pub port: u16,
     /// The RPC user `bitcoind` is configured with.
-    rpc_user: &'a str,
+    pub user: String,
     /// The RPC password for the given user.
-    rpc_password: &'a str,
+    pub password: String,
+}
+
+impl std::fmt::Debug for RpcEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Credentials are left out so they don't end up in the logs
+        f.debug_struct("RpcEndpoint")
+            .field("url", &self.url())
+            .finish()
+    }
+}
+
+impl RpcEndpoint {
+    /// Creates a new [RpcEndpoint] instance.
+    pub fn new(host: &str, port: u16, user: &str, password: &str) -> Self {
+        RpcEndpoint {
+            host: host.to_owned(),
+            port,
+            user: user.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    /// Parses a `user:password@host:port` endpoint. Returns [None] if the endpoint is not properly formatted.
+    pub fn parse(endpoint: &str) -> Option<Self> {
+        let (credentials, address) = endpoint.rsplit_once('@')?;
+        let (user, password) = credentials.split_once(':')?;
+        let (host, port) = address.rsplit_once(':')?;
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(RpcEndpoint::new(host, port.parse().ok()?, user, password))
+    }
+
+    /// Gets the url of the endpoint.
+    pub fn url(&self) -> String {
+        let schema = if !self.host.starts_with("http") {
+            "http://"
+        } else {
+            ""
+        };
+        format!("{schema}{}:{}", self.host, self.port)
+    }
+
+    /// Gets a fresh RPC client for the endpoint.
+    pub fn rpc_client(&self) -> std::io::Result<RpcClient> {
+        let http_endpoint = HttpEndpoint::for_host(self.host.clone()).with_port(self.port);
+        let rpc_credentials = base64::encode(&format!("{}:{}", self.user, self.password));
+        RpcClient::new(&rpc_credentials, http_endpoint)
+    }
+}
+
+/// An ordered list of `bitcoind` backends, alongside the one currently in use.
+///
+/// Backends are listed by preference. The first one is used unless it cannot be reached, in which case the tower fails over
+/// to the next healthy one. This is shared by all the components talking to `bitcoind`, so they all switch at once.
+#[derive(Debug)]
+pub struct BitcoindBackends {
+    /// The endpoints of the backends, sorted by preference.
+    endpoints: Vec<RpcEndpoint>,
+    /// The index of the backend currently in use.
+    active: AtomicUsize,
 }
 
-impl BlockSource for &BitcoindClient<'_> {
+impl BitcoindBackends {
+    /// Creates a new [BitcoindBackends] instance. The first endpoint is set as active.
+    ///
+    /// Fails if no endpoint is provided.
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Result<Self, ConfigError> {
+        if endpoints.is_empty() {
+            return Err(ConfigError(
+                "at least one bitcoind endpoint is required".to_owned(),
+            ));
+        }
+        Ok(BitcoindBackends {
+            endpoints,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// Gets the endpoints of the backends, sorted by preference.
+    pub fn endpoints(&self) -> &Vec<RpcEndpoint> {
+        &self.endpoints
+    }
+
+    /// Gets the index of the backend currently in use.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Gets the endpoint of the backend currently in use.
+    pub fn active_endpoint(&self) -> &RpcEndpoint {
+        &self.endpoints[self.active()]
+    }
+
+    /// Gets the indexes of the backends that can take over from a failed one, in the order they should be tried.
+    ///
+    /// Backends listed after the failed one go first, followed by the ones listed before it.
+    pub fn candidates(&self, failed: usize) -> Vec<usize> {
+        let n = self.endpoints.len();
+        (1..n).map(|i| (failed + i) % n).collect()
+    }
+
+    /// Switches from the `from` backend to the `to` one, as long as `from` is still the active one (it may have already been
+    /// switched by some other component).
+    ///
+    /// Returns the index of the active backend after the switch.
+    pub fn switch(&self, from: usize, to: usize) -> usize {
+        match self
+            .active
+            .compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => {
+                log::warn!(
+                    "Switching bitcoind backend from {} to {}",
+                    self.endpoints[from].url(),
+                    self.endpoints[to].url()
+                );
+                to
+            }
+            Err(active) => active,
+        }
+    }
+}
+
+/// A simple implementation of a bitcoind client (`bitcoin-cli`) with the minimal functionality required by the tower.
+///
+/// Requests are sent to the active backend from a set of [BitcoindBackends]. If it cannot be reached, the client fails over
+/// to the next healthy one.
+pub struct BitcoindClient {
+    /// The underlying RPC clients, one per backend. Clients are created on demand, given a backend may not be reachable
+    /// when the [BitcoindClient] is created.
+    rpc_clients: Vec<Mutex<Option<RpcClient>>>,
+    /// The backends the client talks to.
+    backends: Arc<BitcoindBackends>,
+}
+
+impl BlockSource for BitcoindClient {
     /// Gets a block header given its hash.
     fn get_header<'a>(
         &'a self,
         header_hash: &'a BlockHash,
-        height_hint: Option<u32>,
+        _height_hint: Option<u32>,
     ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
         Box::pin(async move {
-            let rpc = self.bitcoind_rpc_client.lock().await;
-            rpc.get_header(header_hash, height_hint).await
+            let header_hash = serde_json::json!(header_hash.to_hex());
+            Ok(self.call_method("getblockheader", &[header_hash]).await?)
         })
     }
 
     /// Gets a block given its hash.
     fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, Block> {
         Box::pin(async move {
-            let rpc = self.bitcoind_rpc_client.lock().await;
-            rpc.get_block(header_hash).await
+            let header_hash = serde_json::json!(header_hash.to_hex());
+            let verbosity = serde_json::json!(0);
+            Ok(self
+                .call_method("getblock", &[header_hash, verbosity])
+                .await?)
         })
     }
 
     /// Get the best block known by our node.
+    ///
+    /// The tip is checked against the rest of the backends (see [BitcoindClient::check_tips]).
     fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
         Box::pin(async move {
-            let rpc = self.bitcoind_rpc_client.lock().await;
-            rpc.get_best_block().await
+            let tip = self.get_best_block_hash_and_height().await?;
+            Ok(self.check_tips(tip).await)
         })
     }
 }
 
-// TODO: This is not being used atm since we're using bitcoincore-rpc.
-// Not deleting it since wd should need it once both get merged.
-impl<'a> BitcoindClient<'a> {
+impl BitcoindClient {
     /// Creates a new [BitcoindClient] instance.
+    ///
+    /// All the backends are checked to be running on the same network as the tower. Backends that cannot be reached are
+    /// skipped, and the first reachable one is set as active. An error is returned if none of them can be reached.
     pub async fn new(
-        host: &'a str,
-        port: u16,
-        rpc_user: &'a str,
-        rpc_password: &'a str,
-        teos_network: &'a str,
-    ) -> std::io::Result<BitcoindClient<'a>> {
-        let http_endpoint = HttpEndpoint::for_host(host.to_owned()).with_port(port);
-        let rpc_credentials = base64::encode(&format!("{rpc_user}:{rpc_password}"));
-        let bitcoind_rpc_client = RpcClient::new(&rpc_credentials, http_endpoint)?;
-
+        backends: Arc<BitcoindBackends>,
+        teos_network: &str,
+    ) -> std::io::Result<BitcoindClient> {
         let client = Self {
-            bitcoind_rpc_client: Arc::new(Mutex::new(bitcoind_rpc_client)),
-            host,
-            port,
-            rpc_user,
-            rpc_password,
+            rpc_clients: backends
+                .endpoints()
+                .iter()
+                .map(|_| Mutex::new(None))
+                .collect(),
+            backends,
         };
 
-        // Test that bitcoind is reachable.
-        let btc_network = client.get_chain().await?;
+        let mut reachable = None;
+        let mut last_error = None;
+        for (i, endpoint) in client.backends.endpoints().iter().enumerate() {
+            match client
+                .call_backend::<BtcNetwork>(i, "getblockchaininfo", &[])
+                .await
+            {
+                // Assert teos runs on the same chain/network as bitcoind.
+                Ok(BtcNetwork(btc_network)) if btc_network != teos_network => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("bitcoind ({}) is running on {btc_network} but teosd is set to run on {teos_network}", endpoint.url()),
+                    ));
+                }
+                Ok(_) => {
+                    reachable.get_or_insert(i);
+                }
+                Err(e) if is_connection_error(&e) => {
+                    log::warn!("Cannot reach bitcoind ({}). Error: {e}", endpoint.url());
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-        // Assert teos runs on the same chain/network as bitcoind.
-        if btc_network != teos_network {
-            Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!("bitcoind is running on {btc_network} but teosd is set to run on {teos_network}"),
-            ))
-        } else {
-            Ok(client)
+        match reachable {
+            Some(i) => {
+                client.backends.switch(client.backends.active(), i);
+                Ok(client)
+            }
+            None => Err(last_error.unwrap()),
         }
     }
 
-    /// Gets a fresh RPC client.
-    pub fn get_new_rpc_client(&self) -> std::io::Result<RpcClient> {
-        let http_endpoint = HttpEndpoint::for_host(self.host.to_owned()).with_port(self.port);
-        let rpc_credentials = base64::encode(&format!("{}:{}", self.rpc_user, self.rpc_password));
-        RpcClient::new(&rpc_credentials, http_endpoint)
+    /// Gets the backends the client talks to.
+    pub fn backends(&self) -> &Arc<BitcoindBackends> {
+        &self.backends
+    }
+
+    /// Calls a method on a given backend, creating its RPC client if needed.
+    async fn call_backend<T>(
+        &self,
+        index: usize,
+        method: &str,
+        params: &[serde_json::Value],
+    ) -> std::io::Result<T>
+    where
+        JsonResponse: TryFrom<Vec<u8>, Error = std::io::Error> + TryInto<T, Error = std::io::Error>,
+    {
+        let mut rpc = self.rpc_clients[index].lock().await;
+        let client = match rpc.take() {
+            Some(client) => client,
+            None => self.backends.endpoints()[index].rpc_client()?,
+        };
+        let result = client.call_method(method, params).await;
+        *rpc = Some(client);
+
+        result
+    }
+
+    /// Calls a method on the active backend. If the backend cannot be reached, the client fails over to the next healthy one
+    /// and the call is retried.
+    async fn call_method<T>(&self, method: &str, params: &[serde_json::Value]) -> std::io::Result<T>
+    where
+        JsonResponse: TryFrom<Vec<u8>, Error = std::io::Error> + TryInto<T, Error = std::io::Error>,
+    {
+        let mut active = self.backends.active();
+        let mut attempts = 1;
+        loop {
+            match self.call_backend(active, method, params).await {
+                Err(e) if is_connection_error(&e) && attempts < self.rpc_clients.len() => {
+                    log::error!(
+                        "Connection lost with bitcoind ({}). Error: {e}",
+                        self.backends.endpoints()[active].url()
+                    );
+                    match self.fail_over(active).await {
+                        Some(i) => active = i,
+                        None => return Err(e),
+                    }
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Looks for a reachable backend to take over from the failed one, and switches to it.
+    ///
+    /// Returns the index of the active backend after the switch, or [None] if no other backend can be reached.
+    async fn fail_over(&self, failed: usize) -> Option<usize> {
+        for i in self.backends.candidates(failed) {
+            if self
+                .call_backend::<(BlockHash, Option<u32>)>(i, "getblockchaininfo", &[])
+                .await
+                .is_ok()
+            {
+                return Some(self.backends.switch(failed, i));
+            }
+        }
+
+        None
+    }
+
+    /// Checks the tip reported by the active backend against the ones reported by the rest of backends.
+    ///
+    /// Backends reporting a different block at the same height are logged. If the active backend lags more than [MAX_TIP_LAG]
+    /// blocks behind the most up to date one, the client switches to the latter.
+    ///
+    /// Returns the tip of the backend that is active after the check.
+    async fn check_tips(&self, tip: (BlockHash, Option<u32>)) -> (BlockHash, Option<u32>) {
+        let active = self.backends.active();
+        let (mut best, mut best_tip) = (active, tip);
+        for i in self.backends.candidates(active) {
+            match self
+                .call_backend::<(BlockHash, Option<u32>)>(i, "getblockchaininfo", &[])
+                .await
+            {
+                Ok(other_tip) => {
+                    if other_tip.1 == tip.1 && other_tip.0 != tip.0 {
+                        log::warn!(
+                            "bitcoind backends disagree on the chain tip at height {} ({}: {}, {}: {})",
+                            tip.1.unwrap_or_default(),
+                            self.backends.endpoints()[active].url(),
+                            tip.0,
+                            self.backends.endpoints()[i].url(),
+                            other_tip.0
+                        );
+                    }
+                    if other_tip.1 > best_tip.1 {
+                        best = i;
+                        best_tip = other_tip;
+                    }
+                }
+                Err(e) => log::debug!(
+                    "Cannot check the chain tip of {}. Error: {e}",
+                    self.backends.endpoints()[i].url()
+                ),
+            }
+        }
+
+        let lag = best_tip.1.unwrap_or_default() - tip.1.unwrap_or_default();
+        if lag > MAX_TIP_LAG {
+            log::warn!(
+                "bitcoind ({}) is {lag} blocks behind {}",
+                self.backends.endpoints()[active].url(),
+                self.backends.endpoints()[best].url()
+            );
+            if self.backends.switch(active, best) == best {
+                return best_tip;
+            }
+        }
+
+        tip
     }
 
     /// Gets the hash of the chain tip and its height.
     pub async fn get_best_block_hash_and_height(
         &self,
     ) -> Result<(BlockHash, Option<u32>), std::io::Error> {
-        let rpc = self.bitcoind_rpc_client.lock().await;
-        rpc.call_method::<(BlockHash, Option<u32>)>("getblockchaininfo", &[])
+        self.call_method::<(BlockHash, Option<u32>)>("getblockchaininfo", &[])
             .await
     }
 
     /// Sends a transaction to the network.
     pub async fn send_raw_transaction(&self, raw_tx: &Transaction) -> Result<Txid, std::io::Error> {
-        let rpc = self.bitcoind_rpc_client.lock().await;
-
         let raw_tx_json = serde_json::json!(raw_tx.encode().to_hex());
-        rpc.call_method::<Txid>("sendrawtransaction", &[raw_tx_json])
+        self.call_method::<Txid>("sendrawtransaction", &[raw_tx_json])
             .await
     }
 
     /// Gets a transaction given its id.
     pub async fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, std::io::Error> {
-        let rpc = self.bitcoind_rpc_client.lock().await;
-
         let txid_hex = serde_json::json!(txid.encode().to_hex());
-        rpc.call_method::<Transaction>("getrawtransaction", &[txid_hex])
+        self.call_method::<Transaction>("getrawtransaction", &[txid_hex])
             .await
     }
 
     /// Gets bitcoind's network.
     pub async fn get_chain(&self) -> std::io::Result<String> {
-        // A wrapper type to extract "chain" key from getblockchaininfo JsonResponse.
-        struct BtcNetwork(String);
-        impl TryInto<BtcNetwork> for JsonResponse {
-            type Error = std::io::Error;
-            fn try_into(self) -> std::io::Result<BtcNetwork> {
-                Ok(BtcNetwork(self.0["chain"].as_str().unwrap().to_string()))
-            }
-        }
-
         // Ask the RPC client for the network bitcoind is running on.
-        let rpc = self.bitcoind_rpc_client.lock().await;
-        let btc_network = rpc
+        let btc_network = self
             .call_method::<BtcNetwork>("getblockchaininfo", &[])
             .await?;
 
         Ok(btc_network.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    use bitcoin::hashes::Hash;
+
+    use crate::test_utils::{start_server, BitcoindMock, MockOptions};
+
+    // Gets an endpoint nothing is listening on
+    fn get_unreachable_endpoint() -> RpcEndpoint {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        RpcEndpoint::new("127.0.0.1", port, "user", "password")
+    }
+
+    fn start_mock(options: MockOptions) -> (BitcoindMock, RpcEndpoint) {
+        let bitcoind_mock = BitcoindMock::new(options);
+        let endpoint = bitcoind_mock.endpoint();
+        (bitcoind_mock, endpoint)
+    }
+
+    #[test]
+    fn test_rpc_endpoint_parse() {
+        assert_eq!(
+            RpcEndpoint::parse("user:pass@localhost:8332"),
+            Some(RpcEndpoint::new("localhost", 8332, "user", "pass"))
+        );
+        // Passwords may contain colons and at signs
+        assert_eq!(
+            RpcEndpoint::parse("user:p@s:s@10.0.0.1:18443"),
+            Some(RpcEndpoint::new("10.0.0.1", 18443, "user", "p@s:s"))
+        );
+
+        for endpoint in [
+            "localhost:8332",
+            "user@localhost:8332",
+            "user:pass@localhost",
+            "user:pass@:8332",
+            "user:pass@localhost:port",
+        ] {
+            assert_eq!(RpcEndpoint::parse(endpoint), None);
+        }
+    }
+
+    #[test]
+    fn test_rpc_endpoint_url() {
+        assert_eq!(
+            RpcEndpoint::new("localhost", 8332, "user", "pass").url(),
+            "http://localhost:8332"
+        );
+        assert_eq!(
+            RpcEndpoint::new("https://localhost", 8332, "user", "pass").url(),
+            "https://localhost:8332"
+        );
+        // Credentials are not leaked when debug printing
+        assert!(
+            !format!("{:?}", RpcEndpoint::new("localhost", 8332, "user", "pass")).contains("pass")
+        );
+    }
+
+    #[test]
+    fn test_backends_candidates() {
+        let backends = BitcoindBackends::new(
+            (0..4)
+                .map(|i| RpcEndpoint::new("localhost", 8332 + i, "user", "pass"))
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(backends.candidates(0), vec![1, 2, 3]);
+        assert_eq!(backends.candidates(2), vec![3, 0, 1]);
+
+        let backends =
+            BitcoindBackends::new(vec![RpcEndpoint::new("localhost", 8332, "user", "pass")])
+                .unwrap();
+        assert!(backends.candidates(0).is_empty());
+
+        // At least one backend is required
+        assert!(BitcoindBackends::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_backends_switch() {
+        let backends = BitcoindBackends::new(
+            (0..3)
+                .map(|i| RpcEndpoint::new("localhost", 8332 + i, "user", "pass"))
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(backends.active(), 0);
+        assert_eq!(backends.active_endpoint().port, 8332);
+
+        assert_eq!(backends.switch(0, 2), 2);
+        assert_eq!(backends.active(), 2);
+
+        // Switching from a backend that is no longer active is a no-op
+        assert_eq!(backends.switch(0, 1), 2);
+        assert_eq!(backends.active(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_new() {
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::default());
+        start_server(bitcoind_mock.server);
+
+        let backends = Arc::new(BitcoindBackends::new(vec![endpoint]).unwrap());
+        let client = BitcoindClient::new(backends, "regtest").await.unwrap();
+        assert_eq!(client.backends().active(), 0);
+        assert_eq!(client.get_chain().await.unwrap(), "regtest");
+    }
+
+    #[tokio::test]
+    async fn test_new_wrong_network() {
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::default());
+        start_server(bitcoind_mock.server);
+
+        let backends = Arc::new(BitcoindBackends::new(vec![endpoint]).unwrap());
+        let e = BitcoindClient::new(backends, "main").await.err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_new_unreachable_backends() {
+        // Unreachable backends are skipped
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::default());
+        start_server(bitcoind_mock.server);
+
+        let backends =
+            Arc::new(BitcoindBackends::new(vec![get_unreachable_endpoint(), endpoint]).unwrap());
+        let client = BitcoindClient::new(backends, "regtest").await.unwrap();
+        assert_eq!(client.backends().active(), 1);
+
+        // But at least one needs to be reachable
+        let backends = Arc::new(
+            BitcoindBackends::new(vec![get_unreachable_endpoint(), get_unreachable_endpoint()])
+                .unwrap(),
+        );
+        assert!(BitcoindClient::new(backends, "regtest").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fail_over() {
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::default());
+        start_server(bitcoind_mock.server);
+
+        // Build the client straightaway so the primary is active despite being unreachable
+        let backends =
+            Arc::new(BitcoindBackends::new(vec![get_unreachable_endpoint(), endpoint]).unwrap());
+        let client = BitcoindClient {
+            rpc_clients: vec![Mutex::new(None), Mutex::new(None)],
+            backends,
+        };
+        assert_eq!(client.backends().active(), 0);
+
+        // The request is served by the fallback, which becomes the active backend
+        assert_eq!(client.get_chain().await.unwrap(), "regtest");
+        assert_eq!(client.backends().active(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fail_over_all_unreachable() {
+        let backends = Arc::new(
+            BitcoindBackends::new(vec![get_unreachable_endpoint(), get_unreachable_endpoint()])
+                .unwrap(),
+        );
+        let client = BitcoindClient {
+            rpc_clients: vec![Mutex::new(None), Mutex::new(None)],
+            backends,
+        };
+
+        assert!(is_connection_error(
+            &client.get_chain().await.err().unwrap()
+        ));
+        assert_eq!(client.backends().active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_best_block() {
+        let hash = BlockHash::from_inner([1; 32]);
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::with_best_block(hash, 10));
+        start_server(bitcoind_mock.server);
+
+        // A backend within MAX_TIP_LAG blocks from the best one is kept
+        let other_hash = BlockHash::from_inner([2; 32]);
+        let (other_mock, other_endpoint) =
+            start_mock(MockOptions::with_best_block(other_hash, 10 + MAX_TIP_LAG));
+        start_server(other_mock.server);
+
+        let backends = Arc::new(BitcoindBackends::new(vec![endpoint, other_endpoint]).unwrap());
+        let client = BitcoindClient::new(backends, "regtest").await.unwrap();
+        assert_eq!(client.get_best_block().await.unwrap(), (hash, Some(10)));
+        assert_eq!(client.backends().active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_best_block_lagging_backend() {
+        let hash = BlockHash::from_inner([1; 32]);
+        let (bitcoind_mock, endpoint) = start_mock(MockOptions::with_best_block(hash, 10));
+        start_server(bitcoind_mock.server);
+
+        // If the active backend lags too far behind, the most up to date one takes over
+        let other_hash = BlockHash::from_inner([2; 32]);
+        let (other_mock, other_endpoint) = start_mock(MockOptions::with_best_block(
+            other_hash,
+            10 + MAX_TIP_LAG + 1,
+        ));
+        start_server(other_mock.server);
+
+        let backends = Arc::new(BitcoindBackends::new(vec![endpoint, other_endpoint]).unwrap());
+        let client = BitcoindClient::new(backends, "regtest").await.unwrap();
+        assert_eq!(
+            client.get_best_block().await.unwrap(),
+            (other_hash, Some(10 + MAX_TIP_LAG + 1))
+        );
+        assert_eq!(client.backends().active(), 1);
+    }
+}
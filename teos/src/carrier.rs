@@ -2,21 +2,73 @@
 
 use std::collections::HashMap;
 use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
 
+use crate::backends::BroadcastBackend;
+use crate::bitcoin_cli::BitcoindBackends;
+use crate::config::ConfigError;
 use crate::responder::ConfirmationStatus;
+use crate::wallet::Utxo;
 use crate::{errors, rpc_errors};
 
-use bitcoin::{Transaction, Txid};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::{Address, OutPoint, Transaction, Txid};
 use bitcoincore_rpc::{
     jsonrpc::error::Error::Rpc as RpcError, jsonrpc::error::Error::Transport as TransportError,
     Client as BitcoindClient, Error::JsonRpc as JsonRpcError, RpcApi,
 };
 
+/// Interface used by the [Responder](crate::responder::Responder) to interact with the Bitcoin network: sending transactions,
+/// looking up the mempool, and estimating fees.
+///
+/// [Carrier] implements it on top of `bitcoind`'s RPC interface, while [EsploraBroadcaster](crate::esplora::EsploraBroadcaster)
+/// implements it on top of an Esplora-compatible REST API.
+pub trait TxBroadcaster: Send + std::fmt::Debug {
+    /// The last known block height.
+    fn block_height(&self) -> u32;
+
+    /// Updates the last known block height.
+    fn update_height(&mut self, height: u32);
+
+    /// Clears the receipts cached by the broadcaster, if any. Called once per block to prevent them from growing unbounded.
+    fn clear_receipts(&mut self);
+
+    /// Sends a [Transaction] to the Bitcoin network.
+    ///
+    /// Returns a [ConfirmationStatus] indicating whether the transaction was accepted or not.
+    fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus;
+
+    /// Sends a package of topologically sorted transactions (parents first) to the Bitcoin network.
+    ///
+    /// Returns a [ConfirmationStatus] per transaction, in the same order they were provided.
+    fn send_package(&mut self, txs: &[Transaction]) -> Vec<ConfirmationStatus>;
+
+    /// Checks whether a given transaction can be found in the mempool.
+    fn in_mempool(&self, txid: &Txid) -> bool;
+
+    /// Gets the ids of all the transactions currently in the mempool.
+    fn get_mempool_txids(&self) -> Vec<Txid>;
+
+    /// Gets a transaction from the mempool given its id. Returns [None] if the transaction cannot be found.
+    fn get_mempool_transaction(&self, txid: &Txid) -> Option<Transaction>;
+
+    /// Estimates the fee rate (in sat/vB) needed for a transaction to confirm within `blocks` blocks.
+    fn estimate_fee_rate(&self, blocks: u16) -> Option<u64>;
+
+    /// Gets the confirmed unspent outputs of a given address.
+    fn get_utxos(&self, address: &Address) -> Vec<Utxo>;
+}
+
 /// Component in charge of the interaction with Bitcoind by sending / querying transactions via RPC.
-#[derive(Debug)]
+///
+/// Transactions can additionally be pushed through a set of independent [BroadcastBackend]s (see [Carrier::with_backends]).
+/// If several bitcoind backends are available, the [Carrier] fails over to the next healthy one when the active one cannot be
+/// reached (see [Carrier::with_failover]).
 pub struct Carrier {
-    /// The underlying bitcoin client used by the [Carrier].
-    bitcoin_cli: Arc<BitcoindClient>,
+    /// The underlying bitcoin clients used by the [Carrier], one per bitcoind backend.
+    bitcoin_clis: Vec<Arc<BitcoindClient>>,
+    /// The bitcoind backends the clients are connected to, if the [Carrier] can fail over between them.
+    bitcoind_backends: Option<Arc<BitcoindBackends>>,
     /// A flag that indicates wether bitcoind is reachable or not.
     bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
     /// A map of receipts already issued by the [Carrier].
@@ -24,6 +76,27 @@ pub struct Carrier {
     issued_receipts: HashMap<Txid, ConfirmationStatus>,
     /// The last known block height.
     block_height: u32,
+    /// Whether bitcoind supports package submission (`submitpackage`).
+    /// Flagged as unsupported the first time bitcoind reports the method as unknown.
+    package_relay: bool,
+    /// Additional backends transactions are pushed through.
+    backends: Vec<Arc<dyn BroadcastBackend>>,
+}
+
+impl std::fmt::Debug for Carrier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Carrier")
+            .field("bitcoin_clis", &self.bitcoin_clis)
+            .field("bitcoind_backends", &self.bitcoind_backends)
+            .field("issued_receipts", &self.issued_receipts)
+            .field("block_height", &self.block_height)
+            .field("package_relay", &self.package_relay)
+            .field(
+                "backends",
+                &self.backends.iter().map(|b| b.name()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
 }
 
 impl Carrier {
@@ -34,29 +107,68 @@ impl Carrier {
         last_known_block_height: u32,
     ) -> Self {
         Carrier {
-            bitcoin_cli,
+            bitcoin_clis: vec![bitcoin_cli],
+            bitcoind_backends: None,
             bitcoind_reachable,
             issued_receipts: HashMap::new(),
             block_height: last_known_block_height,
+            package_relay: true,
+            backends: Vec::new(),
         }
     }
 
-    /// The last known block height.
-    pub(crate) fn block_height(&self) -> u32 {
-        self.block_height
+    /// Sets the additional backends transactions are pushed through, on top of bitcoind.
+    pub fn with_backends(mut self, backends: Vec<Arc<dyn BroadcastBackend>>) -> Self {
+        self.backends = backends;
+        self
     }
 
-    /// Clears the receipts cached by the [Carrier]. Should be called periodically to prevent it from
-    /// growing unbounded.
-    pub(crate) fn clear_receipts(&mut self) {
-        if !self.issued_receipts.is_empty() {
-            self.issued_receipts = HashMap::new()
+    /// Sets the bitcoind backends the [Carrier] can fail over to.
+    ///
+    /// `fallbacks` are the clients for every backend but the first one (which the [Carrier] was created with), in the same
+    /// order they are listed in `bitcoind_backends`. Fails if there is not a client per backend.
+    pub fn with_failover(
+        mut self,
+        fallbacks: Vec<Arc<BitcoindClient>>,
+        bitcoind_backends: Arc<BitcoindBackends>,
+    ) -> Result<Self, ConfigError> {
+        if fallbacks.len() + 1 != bitcoind_backends.endpoints().len() {
+            return Err(ConfigError(format!(
+                "a client is required per bitcoind backend ({} backends, {} clients)",
+                bitcoind_backends.endpoints().len(),
+                fallbacks.len() + 1
+            )));
         }
+        self.bitcoin_clis.extend(fallbacks);
+        self.bitcoind_backends = Some(bitcoind_backends);
+        Ok(self)
     }
 
-    /// Updates the last known block height by the [Carrier].
-    pub(crate) fn update_height(&mut self, height: u32) {
-        self.block_height = height
+    /// Gets the client of the active bitcoind backend.
+    fn bitcoin_cli(&self) -> &BitcoindClient {
+        let active = self
+            .bitcoind_backends
+            .as_ref()
+            .map_or(0, |backends| backends.active());
+        &self.bitcoin_clis[active]
+    }
+
+    /// Looks for a reachable bitcoind backend to take over from the active one, and switches to it.
+    ///
+    /// Returns whether the [Carrier] managed to fail over. If it did not, bitcoind is flagged as unreachable.
+    fn handle_connection_error(&self) -> bool {
+        if let Some(backends) = &self.bitcoind_backends {
+            let failed = backends.active();
+            for i in backends.candidates(failed) {
+                if self.bitcoin_clis[i].get_block_count().is_ok() {
+                    backends.switch(failed, i);
+                    return true;
+                }
+            }
+        }
+
+        self.flag_bitcoind_unreachable();
+        false
     }
 
     /// Hangs the process until bitcoind is reachable. If bitcoind is already reachable it just passes trough.
@@ -68,16 +180,107 @@ impl Carrier {
         }
     }
 
+    /// Checks whether bitcoind is flagged as reachable.
+    fn is_bitcoind_reachable(&self) -> bool {
+        *self.bitcoind_reachable.0.lock().unwrap()
+    }
+
     /// Flags bitcoind as unreachable.
     fn flag_bitcoind_unreachable(&self) {
         let (lock, _) = &*self.bitcoind_reachable;
         *lock.lock().unwrap() = false;
     }
 
+    /// Pushes the given transactions through the additional backends, if any, and aggregates the results with the receipts issued by bitcoind.
+    ///
+    /// Each backend is queried from its own thread, and receives the transactions in the provided order. A transaction is considered accepted
+    /// if it has been accepted by, at least, one of them. Disagreements between bitcoind and the backends are logged, and so are the backends
+    /// that cannot be reached.
+    fn fan_out(
+        &self,
+        txs: &[Transaction],
+        receipts: Vec<ConfirmationStatus>,
+    ) -> Vec<ConfirmationStatus> {
+        if self.backends.is_empty() {
+            return receipts;
+        }
+
+        let handles: Vec<_> = self
+            .backends
+            .iter()
+            .map(|backend| {
+                let backend = backend.clone();
+                let txs = txs.to_vec();
+                let height = self.block_height;
+                thread::spawn(move || {
+                    txs.iter()
+                        .map(|tx| backend.send_transaction(tx, height))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let results: Vec<_> = self
+            .backends
+            .iter()
+            .zip(handles)
+            .map(|(backend, handle)| {
+                (
+                    backend.name(),
+                    handle.join().unwrap_or_else(|_| vec![None; txs.len()]),
+                )
+            })
+            .collect();
+
+        txs.iter()
+            .zip(receipts)
+            .enumerate()
+            .map(|(i, (tx, receipt))| {
+                let mut aggregated = receipt;
+                for (name, statuses) in results.iter() {
+                    match statuses[i] {
+                        Some(status) => {
+                            if status.accepted() != receipt.accepted() {
+                                log::warn!(
+                                    "Backends disagree on {} (bitcoind: {receipt:?}, {name}: {status:?})",
+                                    tx.txid()
+                                );
+                            }
+                            if !aggregated.accepted() && status.accepted() {
+                                aggregated = status;
+                            }
+                        }
+                        None => log::warn!("Cannot reach {name}. {} not sent through it", tx.txid()),
+                    }
+                }
+                aggregated
+            })
+            .collect()
+    }
+}
+
+impl TxBroadcaster for Carrier {
+    /// The last known block height.
+    fn block_height(&self) -> u32 {
+        self.block_height
+    }
+
+    /// Clears the receipts cached by the [Carrier]. Should be called periodically to prevent it from
+    /// growing unbounded.
+    fn clear_receipts(&mut self) {
+        if !self.issued_receipts.is_empty() {
+            self.issued_receipts = HashMap::new()
+        }
+    }
+
+    /// Updates the last known block height by the [Carrier].
+    fn update_height(&mut self, height: u32) {
+        self.block_height = height
+    }
+
     /// Sends a [Transaction] to the Bitcoin network.
     ///
     /// Returns a [ConfirmationStatus] indicating whether the transaction was accepted by the node or not.
-    pub(crate) fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
+    fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
         self.hang_until_bitcoind_reachable();
 
         if let Some(receipt) = self.issued_receipts.get(&tx.txid()) {
@@ -86,7 +289,7 @@ impl Carrier {
         }
 
         log::info!("Pushing transaction to the network: {}", tx.txid());
-        let receipt = match self.bitcoin_cli.send_raw_transaction(tx) {
+        let receipt = match self.bitcoin_cli().send_raw_transaction(tx) {
             Ok(_) => {
                 // Here the transaction could, potentially, have been in mempool before the current height.
                 // This shouldn't really matter though.
@@ -129,8 +332,8 @@ impl Carrier {
             Err(JsonRpcError(TransportError(_))) => {
                 // Connection refused, bitcoind is down.
                 log::error!("Connection lost with bitcoind, retrying request when possible");
-                self.flag_bitcoind_unreachable();
-                self.send_transaction(tx)
+                self.handle_connection_error();
+                return self.send_transaction(tx);
             }
             Err(e) => {
                 // TODO: This may need finer catching.
@@ -139,20 +342,101 @@ impl Carrier {
             }
         };
 
+        let receipt = self.fan_out(std::slice::from_ref(tx), vec![receipt])[0];
         self.issued_receipts.insert(tx.txid(), receipt);
 
         receipt
     }
 
+    /// Sends a package of transactions to the Bitcoin network. Transactions must be topologically sorted (parents first).
+    ///
+    /// The package is submitted via `submitpackage`, so transactions that would not be accepted on their own (e.g. a low fee parent
+    /// with a high fee child) can make it to the mempool. If bitcoind does not support package relay, or the package as a whole
+    /// cannot be evaluated, the transactions that haven't been accepted are sent one by one, in order.
+    ///
+    /// Returns a [ConfirmationStatus] per transaction, in the same order they were provided.
+    fn send_package(&mut self, txs: &[Transaction]) -> Vec<ConfirmationStatus> {
+        self.hang_until_bitcoind_reachable();
+
+        let mut receipts: Vec<Option<ConfirmationStatus>> = txs
+            .iter()
+            .map(|tx| self.issued_receipts.get(&tx.txid()).cloned())
+            .collect();
+        if receipts.iter().all(|receipt| receipt.is_some()) {
+            log::info!("Package already sent");
+            return receipts.into_iter().flatten().collect();
+        }
+
+        if self.package_relay {
+            log::info!(
+                "Pushing package to the network: {:?}",
+                txs.iter().map(|tx| tx.txid()).collect::<Vec<_>>()
+            );
+            let hex_txs = txs.iter().map(serialize_hex).collect::<Vec<_>>();
+            match self
+                .bitcoin_cli()
+                .call::<serde_json::Value>("submitpackage", &[serde_json::json!(hex_txs)])
+            {
+                Ok(response) => {
+                    // Transactions that were not accepted as part of the package are sent on their own so
+                    // the reason why they are rejected can be properly reported
+                    let accepted: Vec<_> = (0..txs.len())
+                        .filter(|i| {
+                            response["tx-results"]
+                                .get(txs[*i].wtxid().to_string())
+                                .is_some_and(|result| result.get("error").is_none())
+                        })
+                        .collect();
+                    receipts = vec![None; txs.len()];
+
+                    // Accepted transactions are also pushed through the additional backends, if any
+                    let accepted_txs: Vec<_> = accepted.iter().map(|i| txs[*i].clone()).collect();
+                    let statuses = self.fan_out(
+                        &accepted_txs,
+                        vec![ConfirmationStatus::InMempoolSince(self.block_height); accepted.len()],
+                    );
+                    for (i, status) in accepted.into_iter().zip(statuses) {
+                        log::info!("Transaction successfully delivered: {}", txs[i].txid());
+                        self.issued_receipts.insert(txs[i].txid(), status);
+                        receipts[i] = Some(status);
+                    }
+                }
+                Err(JsonRpcError(RpcError(rpcerr)))
+                    if rpcerr.code == rpc_errors::RPC_METHOD_NOT_FOUND =>
+                {
+                    log::warn!("bitcoind does not support package relay. Falling back to sending transactions one by one");
+                    self.package_relay = false;
+                }
+                Err(JsonRpcError(TransportError(_))) => {
+                    // Connection refused, bitcoind is down.
+                    log::error!("Connection lost with bitcoind, retrying request when possible");
+                    self.handle_connection_error();
+                    return self.send_package(txs);
+                }
+                Err(e) => {
+                    log::error!(
+                        "Package couldn't be broadcast, sending transactions one by one. {e:?}"
+                    );
+                }
+            }
+        }
+
+        // Send whatever was not accepted as part of the package sequentially
+        txs.iter()
+            .zip(receipts)
+            .map(|(tx, receipt)| receipt.unwrap_or_else(|| self.send_transaction(tx)))
+            .collect()
+    }
+
     /// Checks whether a given transaction can be found in the mempool.
     ///
     /// This uses `getrawtransaction` under the hood and, therefore, its behavior depends on whether `txindex` is enabled in bitcoind.
     /// If `txindex` is disabled (default), it will only pull data from the mempool. Otherwise, it will also pull data from the transaction
     /// index. Hence, we need to check whether the returned struct has any of the block related datum set (such as `blockhash`).
-    pub(crate) fn in_mempool(&self, txid: &Txid) -> bool {
+    fn in_mempool(&self, txid: &Txid) -> bool {
         self.hang_until_bitcoind_reachable();
 
-        match self.bitcoin_cli.get_raw_transaction_info(txid, None) {
+        match self.bitcoin_cli().get_raw_transaction_info(txid, None) {
             Ok(tx) => tx.blockhash.is_none(),
             Err(JsonRpcError(RpcError(rpcerr))) => match rpcerr.code {
                 rpc_errors::RPC_INVALID_ADDRESS_OR_KEY => {
@@ -168,7 +452,7 @@ impl Carrier {
             Err(JsonRpcError(TransportError(_))) => {
                 // Connection refused, bitcoind is down.
                 log::error!("Connection lost with bitcoind, retrying request when possible");
-                self.flag_bitcoind_unreachable();
+                self.handle_connection_error();
                 self.in_mempool(txid)
             }
             // TODO: This may need finer catching.
@@ -179,6 +463,175 @@ impl Carrier {
             }
         }
     }
+
+    /// Gets the ids of all the transactions currently in the mempool.
+    ///
+    /// Mempool data is only used to spot breaches ahead of time, so, unlike the rest of the [Carrier] methods, this does not
+    /// hang until bitcoind is reachable. An empty list is returned instead, the [Watcher](crate::watcher::Watcher) will catch up
+    /// with the breaches once they get confirmed.
+    fn get_mempool_txids(&self) -> Vec<Txid> {
+        if !self.is_bitcoind_reachable() {
+            return Vec::new();
+        }
+
+        match self.bitcoin_cli().get_raw_mempool() {
+            Ok(txids) => txids,
+            Err(JsonRpcError(TransportError(_))) => {
+                // Connection refused, bitcoind is down.
+                log::error!("Connection lost with bitcoind, skipping mempool query");
+                if self.handle_connection_error() {
+                    self.get_mempool_txids()
+                } else {
+                    Vec::new()
+                }
+            }
+            Err(e) => {
+                log::error!("Unexpected error when calling getrawmempool: {e:?}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Gets a transaction from the mempool given its id. Returns [None] if the transaction cannot be found.
+    ///
+    /// As [get_mempool_txids](Self::get_mempool_txids), this does not hang if bitcoind is unreachable.
+    fn get_mempool_transaction(&self, txid: &Txid) -> Option<Transaction> {
+        if !self.is_bitcoind_reachable() {
+            return None;
+        }
+
+        match self.bitcoin_cli().get_raw_transaction(txid, None) {
+            Ok(tx) => Some(tx),
+            Err(JsonRpcError(RpcError(rpcerr))) => match rpcerr.code {
+                rpc_errors::RPC_INVALID_ADDRESS_OR_KEY => {
+                    // The transaction may have left the mempool since the txids were queried.
+                    log::debug!("Transaction not found in mempool: {txid}");
+                    None
+                }
+                e => {
+                    log::error!("Unexpected error code when calling getrawtransaction: {e}");
+                    None
+                }
+            },
+            Err(JsonRpcError(TransportError(_))) => {
+                // Connection refused, bitcoind is down.
+                log::error!("Connection lost with bitcoind, skipping mempool query");
+                if self.handle_connection_error() {
+                    self.get_mempool_transaction(txid)
+                } else {
+                    None
+                }
+            }
+            Err(e) => {
+                log::error!("Unexpected JSONRPCError when calling getrawtransaction: {e}");
+                None
+            }
+        }
+    }
+
+    /// Estimates the fee rate (in sat/vB) needed for a transaction to confirm within `blocks` blocks.
+    ///
+    /// Fee estimation is best effort, so, as [get_mempool_txids](Self::get_mempool_txids), this does not hang if bitcoind
+    /// is unreachable. [None] is returned if no estimation can be obtained.
+    fn estimate_fee_rate(&self, blocks: u16) -> Option<u64> {
+        if !self.is_bitcoind_reachable() {
+            return None;
+        }
+
+        match self.bitcoin_cli().estimate_smart_fee(blocks, None) {
+            // The fee rate is returned in BTC/kvB
+            Ok(estimate) => estimate
+                .fee_rate
+                .map(|fee_rate| fee_rate.as_sat().div_ceil(1000)),
+            Err(JsonRpcError(TransportError(_))) => {
+                // Connection refused, bitcoind is down.
+                log::error!("Connection lost with bitcoind, skipping fee estimation");
+                if self.handle_connection_error() {
+                    self.estimate_fee_rate(blocks)
+                } else {
+                    None
+                }
+            }
+            Err(e) => {
+                log::error!("Unexpected error when calling estimatesmartfee: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Gets the confirmed unspent outputs of a given address. The address needs to be tracked by bitcoind's wallet.
+    ///
+    /// As [estimate_fee_rate](Self::estimate_fee_rate), this does not hang if bitcoind is unreachable.
+    fn get_utxos(&self, address: &Address) -> Vec<Utxo> {
+        if !self.is_bitcoind_reachable() {
+            return Vec::new();
+        }
+
+        match self
+            .bitcoin_cli()
+            .list_unspent(Some(1), None, Some(&[address]), None, None)
+        {
+            Ok(utxos) => utxos
+                .into_iter()
+                .map(|utxo| Utxo::new(OutPoint::new(utxo.txid, utxo.vout), utxo.amount.as_sat()))
+                .collect(),
+            Err(JsonRpcError(TransportError(_))) => {
+                // Connection refused, bitcoind is down.
+                log::error!("Connection lost with bitcoind, skipping utxo query");
+                if self.handle_connection_error() {
+                    self.get_utxos(address)
+                } else {
+                    Vec::new()
+                }
+            }
+            Err(e) => {
+                log::error!("Unexpected error when calling listunspent: {e:?}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl<T: TxBroadcaster + ?Sized> TxBroadcaster for Box<T> {
+    fn block_height(&self) -> u32 {
+        (**self).block_height()
+    }
+
+    fn update_height(&mut self, height: u32) {
+        (**self).update_height(height)
+    }
+
+    fn clear_receipts(&mut self) {
+        (**self).clear_receipts()
+    }
+
+    fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
+        (**self).send_transaction(tx)
+    }
+
+    fn send_package(&mut self, txs: &[Transaction]) -> Vec<ConfirmationStatus> {
+        (**self).send_package(txs)
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> bool {
+        (**self).in_mempool(txid)
+    }
+
+    fn get_mempool_txids(&self) -> Vec<Txid> {
+        (**self).get_mempool_txids()
+    }
+
+    fn get_mempool_transaction(&self, txid: &Txid) -> Option<Transaction> {
+        (**self).get_mempool_transaction(txid)
+    }
+
+    fn estimate_fee_rate(&self, blocks: u16) -> Option<u64> {
+        (**self).estimate_fee_rate(blocks)
+    }
+
+    fn get_utxos(&self, address: &Address) -> Vec<Utxo> {
+        (**self).get_utxos(address)
+    }
 }
 
 #[cfg(test)]
@@ -186,12 +639,43 @@ mod tests {
     use super::*;
     use std::thread;
 
-    use crate::test_utils::{get_random_tx, start_server, BitcoindMock, MockOptions, START_HEIGHT};
+    use crate::test_utils::{
+        get_random_tx, start_server, BitcoindMock, MockOptions, MOCK_FEE_RATE, MOCK_UTXO_VALUE,
+        START_HEIGHT,
+    };
     use teos_common::test_utils::{TXID_HEX, TX_HEX};
 
     use bitcoin::consensus;
     use bitcoin::hashes::hex::FromHex;
+    use bitcoin::{Network, PublicKey};
     use bitcoincore_rpc::Auth;
+    use teos_common::cryptography::get_random_keypair;
+
+    // A backend answering every transaction with the same status, and keeping track of what has been sent through it
+    struct MockBackend {
+        status: Option<ConfirmationStatus>,
+        sent: Mutex<Vec<Txid>>,
+    }
+
+    impl MockBackend {
+        fn new(status: Option<ConfirmationStatus>) -> Arc<Self> {
+            Arc::new(MockBackend {
+                status,
+                sent: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl BroadcastBackend for MockBackend {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn send_transaction(&self, tx: &Transaction, _: u32) -> Option<ConfirmationStatus> {
+            self.sent.lock().unwrap().push(tx.txid());
+            self.status
+        }
+    }
 
     impl Carrier {
         // Helper function to access issued_receipts in tests
@@ -380,6 +864,148 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_send_transaction_backends() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // Transactions are pushed through every backend. Rejections and unreachable backends do not affect the
+        // receipt if bitcoind accepts the transaction
+        let rejecting = MockBackend::new(Some(ConfirmationStatus::Rejected(
+            rpc_errors::RPC_VERIFY_REJECTED,
+        )));
+        let unreachable = MockBackend::new(None);
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height)
+            .with_backends(vec![rejecting.clone(), unreachable.clone()]);
+        let tx = get_random_tx();
+        let r = carrier.send_transaction(&tx);
+
+        assert_eq!(r, ConfirmationStatus::InMempoolSince(start_height));
+        assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), &r);
+        for backend in [rejecting, unreachable] {
+            assert_eq!(*backend.sent.lock().unwrap(), vec![tx.txid()]);
+        }
+    }
+
+    #[test]
+    fn test_send_transaction_backends_accepted() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // If bitcoind rejects the transaction but any of the backends accepts it, the transaction is considered accepted
+        let accepting = MockBackend::new(Some(ConfirmationStatus::InMempoolSince(start_height)));
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height)
+            .with_backends(vec![MockBackend::new(None), accepting]);
+        let tx = get_random_tx();
+        let r = carrier.send_transaction(&tx);
+
+        assert_eq!(r, ConfirmationStatus::InMempoolSince(start_height));
+        assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), &r);
+    }
+
+    #[test]
+    fn test_send_package_backends() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // Packages accepted by bitcoind are also pushed through the backends, in order
+        let backend = MockBackend::new(Some(ConfirmationStatus::InMempoolSince(start_height)));
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height)
+            .with_backends(vec![backend.clone()]);
+        let package = [get_random_tx(), get_random_tx()];
+        let receipts = carrier.send_package(&package);
+
+        assert_eq!(
+            receipts,
+            vec![ConfirmationStatus::InMempoolSince(start_height); 2]
+        );
+        assert_eq!(
+            *backend.sent.lock().unwrap(),
+            package.iter().map(|tx| tx.txid()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_send_package() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let package = [get_random_tx(), get_random_tx()];
+        let receipts = carrier.send_package(&package);
+
+        assert_eq!(
+            receipts,
+            vec![ConfirmationStatus::InMempoolSince(start_height); 2]
+        );
+        assert!(carrier.package_relay);
+
+        // Check the receipts are on the cache
+        for (tx, receipt) in package.iter().zip(receipts.iter()) {
+            assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), receipt);
+        }
+    }
+
+    #[test]
+    fn test_send_package_no_package_relay() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::no_package_relay());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // If bitcoind does not support package relay transactions are sent one by one
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let package = [get_random_tx(), get_random_tx()];
+        let receipts = carrier.send_package(&package);
+
+        assert_eq!(
+            receipts,
+            vec![ConfirmationStatus::InMempoolSince(start_height); 2]
+        );
+        assert!(!carrier.package_relay);
+
+        // Check the receipts are on the cache
+        for (tx, receipt) in package.iter().zip(receipts.iter()) {
+            assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), receipt);
+        }
+    }
+
+    #[test]
+    fn test_send_package_rejected() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // If the package is rejected as a whole each transaction gets its own rejection reason
+        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let receipts = carrier.send_package(&[get_random_tx(), get_random_tx()]);
+
+        assert_eq!(
+            receipts,
+            vec![ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_REJECTED); 2]
+        );
+        assert!(carrier.package_relay);
+    }
+
     #[test]
     fn test_in_mempool() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
@@ -463,4 +1089,245 @@ mod tests {
             delay.as_secs()
         );
     }
+
+    #[test]
+    fn test_get_mempool_txids() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(carrier.get_mempool_txids(), vec![tx.txid()]);
+    }
+
+    #[test]
+    fn test_get_mempool_txids_empty() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        assert!(carrier.get_mempool_txids().is_empty());
+    }
+
+    #[test]
+    fn test_get_mempool_txids_unexpected_error() {
+        let bitcoind_mock =
+            BitcoindMock::new(MockOptions::with_error(rpc_errors::RPC_MISC_ERROR as i64));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        assert!(carrier.get_mempool_txids().is_empty());
+    }
+
+    #[test]
+    fn test_get_mempool_txids_connection_error() {
+        // Try to connect to an offline bitcoind. The query must not hang.
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        drop(bitcoind_mock);
+        let start_height = START_HEIGHT as u32;
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+
+        assert!(carrier.get_mempool_txids().is_empty());
+        assert!(!*bitcoind_reachable.0.lock().unwrap());
+
+        // Once flagged as unreachable, bitcoind is not even queried
+        assert!(carrier.get_mempool_txids().is_empty());
+    }
+
+    // Creates a Carrier whose primary bitcoind backend is offline, and that can fail over to the given one
+    fn create_failover_carrier(
+        fallback: &BitcoindMock,
+        bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
+    ) -> (Carrier, Arc<BitcoindBackends>) {
+        let offline_mock = BitcoindMock::new(MockOptions::default());
+        let primary = Arc::new(BitcoindClient::new(offline_mock.url(), Auth::None).unwrap());
+        let backends = Arc::new(
+            BitcoindBackends::new(vec![offline_mock.endpoint(), fallback.endpoint()]).unwrap(),
+        );
+        drop(offline_mock);
+
+        let fallback = Arc::new(BitcoindClient::new(fallback.url(), Auth::None).unwrap());
+        let carrier = Carrier::new(primary, bitcoind_reachable, START_HEIGHT as u32)
+            .with_failover(vec![fallback], backends.clone())
+            .unwrap();
+
+        (carrier, backends)
+    }
+
+    #[test]
+    fn test_with_failover_missing_client() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let backends = Arc::new(
+            BitcoindBackends::new(vec![bitcoind_mock.endpoint(), bitcoind_mock.endpoint()])
+                .unwrap(),
+        );
+
+        assert!(
+            Carrier::new(bitcoin_cli, bitcoind_reachable, START_HEIGHT as u32)
+                .with_failover(Vec::new(), backends)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_send_transaction_fail_over() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let (mut carrier, backends) =
+            create_failover_carrier(&bitcoind_mock, bitcoind_reachable.clone());
+        start_server(bitcoind_mock.server);
+
+        // The transaction is sent through the fallback straightaway, bitcoind is never flagged as unreachable
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(
+            carrier.send_transaction(&tx),
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32)
+        );
+        assert_eq!(backends.active(), 1);
+        assert!(*bitcoind_reachable.0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_get_mempool_txids_fail_over() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let (carrier, backends) =
+            create_failover_carrier(&bitcoind_mock, bitcoind_reachable.clone());
+        start_server(bitcoind_mock.server);
+
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(carrier.get_mempool_txids(), vec![tx.txid()]);
+        assert_eq!(backends.active(), 1);
+        assert!(*bitcoind_reachable.0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_fail_over_all_backends_unreachable() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let (carrier, backends) =
+            create_failover_carrier(&bitcoind_mock, bitcoind_reachable.clone());
+        drop(bitcoind_mock);
+
+        // If no backend can be reached, bitcoind is flagged as unreachable
+        assert!(carrier.get_mempool_txids().is_empty());
+        assert_eq!(backends.active(), 0);
+        assert!(!*bitcoind_reachable.0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_get_mempool_transaction() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(carrier.get_mempool_transaction(&tx.txid()).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_get_mempool_transaction_not_found() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let txid = Txid::from_hex(TXID_HEX).unwrap();
+        assert!(carrier.get_mempool_transaction(&txid).is_none());
+    }
+
+    #[test]
+    fn test_get_mempool_transaction_connection_error() {
+        // Try to connect to an offline bitcoind. The query must not hang.
+        let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        drop(bitcoind_mock);
+        let start_height = START_HEIGHT as u32;
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+
+        let txid = Txid::from_hex(TXID_HEX).unwrap();
+        assert!(carrier.get_mempool_transaction(&txid).is_none());
+        assert!(!*bitcoind_reachable.0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_estimate_fee_rate() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        assert_eq!(carrier.estimate_fee_rate(6), Some(MOCK_FEE_RATE));
+    }
+
+    #[test]
+    fn test_estimate_fee_rate_connection_error() {
+        // Try to connect to an offline bitcoind. The query must not hang.
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        drop(bitcoind_mock);
+        let start_height = START_HEIGHT as u32;
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+
+        assert!(carrier.estimate_fee_rate(6).is_none());
+        assert!(!*bitcoind_reachable.0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_get_utxos() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let address =
+            Address::p2wpkh(&PublicKey::new(get_random_keypair().1), Network::Regtest).unwrap();
+        assert_eq!(
+            carrier.get_utxos(&address),
+            vec![Utxo::new(
+                OutPoint::new(Txid::from_hex(TXID_HEX).unwrap(), 0),
+                MOCK_UTXO_VALUE
+            )]
+        );
+    }
+
+    #[test]
+    fn test_get_utxos_connection_error() {
+        // Try to connect to an offline bitcoind. The query must not hang.
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        drop(bitcoind_mock);
+        let start_height = START_HEIGHT as u32;
+        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+
+        let address =
+            Address::p2wpkh(&PublicKey::new(get_random_keypair().1), Network::Regtest).unwrap();
+        assert!(carrier.get_utxos(&address).is_empty());
+        assert!(!*bitcoind_reachable.0.lock().unwrap());
+    }
 }
@@ -4,6 +4,7 @@
 use std::ops::Deref;
 use std::sync::{Arc, Condvar, Mutex};
 use std::time;
+use tokio::sync::Notify;
 use tokio::time::timeout;
 use triggered::Listener;
 
@@ -11,12 +12,14 @@ use lightning::chain;
 use lightning_block_sync::poll::{ChainTip, Poll, ValidatedBlockHeader};
 use lightning_block_sync::{BlockSourceErrorKind, Cache, SpvClient};
 
-use crate::dbm::DBM;
+use crate::dbm::Storage;
 
 /// Component in charge of monitoring the chain for new blocks.
 ///
 /// Takes care of polling `bitcoind` for new tips and hand it to subscribers.
 /// It is mainly a wrapper around [chain::Listen] that provides some logging.
+///
+/// Polls can be triggered ahead of time through a block notifier (see [ChainMonitor::with_block_notifier]).
 pub struct ChainMonitor<'a, P, C, L>
 where
     P: Poll,
@@ -28,14 +31,16 @@ where
     spv_client: SpvClient<'a, P, C, L>,
     /// The lat known block header by the [ChainMonitor].
     last_known_block_header: ValidatedBlockHeader,
-    /// A [DBM] (database manager) instance. Used to persist block data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// A [Storage] (database manager) instance. Used to persist block data into disk.
+    dbm: Arc<Mutex<dyn Storage>>,
     /// The time between polls.
     polling_delta: time::Duration,
     /// A signal from the main thread indicating the tower is shuting down.
     shutdown_signal: Listener,
     /// A flag that indicates wether bitcoind is reachable or not.
     bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
+    /// A notifier signaling new blocks (e.g. via ZMQ), if any.
+    block_notifier: Option<Arc<Notify>>,
 }
 
 impl<'a, P, C, L> ChainMonitor<'a, P, C, L>
@@ -49,7 +54,7 @@ where
     pub async fn new(
         spv_client: SpvClient<'a, P, C, L>,
         last_known_block_header: ValidatedBlockHeader,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
         polling_delta_sec: u16,
         shutdown_signal: Listener,
         bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
@@ -61,9 +66,17 @@ where
             polling_delta: time::Duration::from_secs(polling_delta_sec as u64),
             shutdown_signal,
             bitcoind_reachable,
+            block_notifier: None,
         }
     }
 
+    /// Sets a notifier signaling new blocks. The chain is polled as soon as it is notified, on top of every
+    /// [polling_delta](Self::polling_delta), which is kept as a fallback.
+    pub fn with_block_notifier(mut self, block_notifier: Arc<Notify>) -> Self {
+        self.block_notifier = Some(block_notifier);
+        self
+    }
+
     /// Polls the best chain tip from bitcoind. Serves the data to its listeners (through [chain::Listen]) and logs data about the polled tips.
     pub async fn poll_best_tip(&mut self) {
         let (reachable, notifier) = &*self.bitcoind_reachable;
@@ -111,17 +124,24 @@ where
         };
     }
 
-    /// Monitors `bitcoind` polling the best chain tip every [polling_delta](Self::polling_delta).
+    /// Monitors `bitcoind` polling the best chain tip every [polling_delta](Self::polling_delta), or whenever
+    /// a new block is notified.
     pub async fn monitor_chain(&mut self) {
         loop {
             self.poll_best_tip().await;
-            // Sleep for self.polling_delta seconds or shutdown if the signal is received.
-            if timeout(self.polling_delta, self.shutdown_signal.clone())
-                .await
-                .is_ok()
-            {
-                log::debug!("Received shutting down signal. Shutting down");
-                break;
+            let new_block = async {
+                match &self.block_notifier {
+                    Some(notifier) => notifier.notified().await,
+                    None => std::future::pending().await,
+                }
+            };
+            // Sleep for self.polling_delta seconds (or until a new block is notified) or shutdown if the signal is received.
+            tokio::select! {
+                _ = self.shutdown_signal.clone() => {
+                    log::debug!("Received shutting down signal. Shutting down");
+                    break;
+                }
+                _ = timeout(self.polling_delta, new_block) => {}
             }
         }
     }
@@ -130,6 +150,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dbm::DBM;
     use std::cell::RefCell;
     use std::collections::HashSet;
     use std::iter::FromIterator;
@@ -226,7 +247,12 @@ mod tests {
         cm.poll_best_tip().await;
         assert_eq!(cm.last_known_block_header, new_tip);
         assert_eq!(
-            cm.dbm.lock().unwrap().load_last_known_block().unwrap(),
+            cm.dbm
+                .lock()
+                .unwrap()
+                .load_last_known_block()
+                .unwrap()
+                .unwrap(),
             new_tip.deref().header.block_hash()
         );
         assert!(listener
@@ -264,7 +290,13 @@ mod tests {
         // If a new (worse, just one) block gets mined, nothing gets connected nor disconnected
         cm.poll_best_tip().await;
         assert_eq!(cm.last_known_block_header, best_tip);
-        assert!(cm.dbm.lock().unwrap().load_last_known_block().is_none());
+        assert!(cm
+            .dbm
+            .lock()
+            .unwrap()
+            .load_last_known_block()
+            .unwrap()
+            .is_none());
         assert!(listener.connected_blocks.borrow().is_empty());
         assert!(listener.disconnected_blocks.borrow().is_empty());
     }
@@ -304,7 +336,12 @@ mod tests {
         cm.poll_best_tip().await;
         assert_eq!(cm.last_known_block_header, new_best);
         assert_eq!(
-            cm.dbm.lock().unwrap().load_last_known_block().unwrap(),
+            cm.dbm
+                .lock()
+                .unwrap()
+                .load_last_known_block()
+                .unwrap()
+                .unwrap(),
             new_best.deref().header.block_hash()
         );
         assert_eq!(*listener.connected_blocks.borrow(), new_blocks);
@@ -361,4 +398,51 @@ mod tests {
         // This would hang if the cm didn't notify their subscribers about the bitcoind status, so it serves as out assert.
         t.join().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_monitor_chain_block_notifier() {
+        let mut chain = Blockchain::default()
+            .with_height(START_HEIGHT)
+            .unreachable();
+        let chain_offline = chain.unreachable.clone();
+        let tip = chain.tip();
+
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (shutdown_trigger, shutdown_signal) = triggered::trigger();
+        let listener = DummyListener::new();
+
+        let poller = ChainPoller::new(&mut chain, Network::Bitcoin);
+        let cache = &mut UnboundedCache::new();
+        let spv_client = SpvClient::new(tip, poller, cache, &listener);
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let block_notifier = Arc::new(Notify::new());
+
+        // Polling is set way above the test duration, so only notifications can trigger new polls
+        let mut cm = ChainMonitor::new(
+            spv_client,
+            tip,
+            dbm,
+            u16::MAX,
+            shutdown_signal,
+            bitcoind_reachable.clone(),
+        )
+        .await
+        .with_block_notifier(block_notifier.clone());
+
+        let notify = async {
+            // The first poll happens straightaway
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+            assert!(!*bitcoind_reachable.0.lock().unwrap());
+
+            // Once notified, the chain is polled again
+            *chain_offline.lock().unwrap() = false;
+            block_notifier.notify_one();
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+            assert!(*bitcoind_reachable.0.lock().unwrap());
+
+            shutdown_trigger.trigger();
+        };
+
+        tokio::join!(cm.monitor_chain(), notify);
+    }
 }
@@ -1,5 +1,6 @@
 use hex::FromHex;
 use serde_json::to_string_pretty as pretty_json;
+use std::env;
 use std::str::FromStr;
 use structopt::StructOpt;
 use tokio::fs;
@@ -112,6 +113,134 @@ async fn main() {
                 Err(e) => println!("{e}"),
             };
         }
+        Command::BanUser(user) => {
+            match UserId::from_str(&user.user_id) {
+                Ok(user_id) => {
+                    match client
+                        .ban_user(Request::new(msgs::BanUserRequest {
+                            user_id: user_id.to_vec(),
+                        }))
+                        .await
+                    {
+                        Ok(_) => println!("User {user_id} banned"),
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::UnbanUser(user) => {
+            match UserId::from_str(&user.user_id) {
+                Ok(user_id) => {
+                    match client
+                        .unban_user(Request::new(msgs::UnbanUserRequest {
+                            user_id: user_id.to_vec(),
+                        }))
+                        .await
+                    {
+                        Ok(_) => println!("User {user_id} unbanned"),
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::DeleteUser(user) => {
+            match UserId::from_str(&user.user_id) {
+                Ok(user_id) => {
+                    match client
+                        .delete_user(Request::new(msgs::DeleteUserRequest {
+                            user_id: user_id.to_vec(),
+                        }))
+                        .await
+                    {
+                        Ok(response) => {
+                            println!("{}", pretty_json(&response.into_inner()).unwrap())
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::GrantSlots(data) => {
+            match UserId::from_str(&data.user_id) {
+                Ok(user_id) => {
+                    match client
+                        .grant_slots(Request::new(msgs::GrantSlotsRequest {
+                            user_id: user_id.to_vec(),
+                            slots: data.slots,
+                        }))
+                        .await
+                    {
+                        Ok(response) => {
+                            println!("{}", pretty_json(&response.into_inner()).unwrap())
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::SetSubscriptionExpiry(data) => {
+            match UserId::from_str(&data.user_id) {
+                Ok(user_id) => {
+                    match client
+                        .set_subscription_expiry(Request::new(msgs::SetSubscriptionExpiryRequest {
+                            user_id: user_id.to_vec(),
+                            subscription_expiry: data.subscription_expiry,
+                        }))
+                        .await
+                    {
+                        Ok(response) => {
+                            println!("{}", pretty_json(&response.into_inner()).unwrap())
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::GetAuditLog => {
+            let audit_log = client.get_audit_log(Request::new(())).await.unwrap();
+            println!("{}", pretty_json(&audit_log.into_inner()).unwrap());
+        }
+        Command::IssueRegistrationTokens(data) => {
+            match data
+                .blinded_tokens
+                .iter()
+                .map(Vec::from_hex)
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(blinded_tokens) => {
+                    match client
+                        .issue_registration_tokens(Request::new(
+                            msgs::IssueRegistrationTokensRequest { blinded_tokens },
+                        ))
+                        .await
+                    {
+                        Ok(response) => {
+                            println!("{}", pretty_json(&response.into_inner()).unwrap())
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{e}"),
+            };
+        }
+        Command::Backup(data) => {
+            // The path is resolved here, since the tower may be running on a different working directory
+            let path = env::current_dir().unwrap().join(data.path);
+            match client
+                .backup(Request::new(msgs::BackupRequest {
+                    path: path.display().to_string(),
+                }))
+                .await
+            {
+                Ok(response) => println!("{}", pretty_json(&response.into_inner()).unwrap()),
+                Err(status) => println!("{}", status.message()),
+            }
+        }
         Command::Stop => {
             println!("Shutting down tower");
             client.stop(Request::new(())).await.unwrap();
@@ -16,6 +16,22 @@ pub enum Command {
     GetUsers,
     /// Gets information about a specific user
     GetUser(GetUserData),
+    /// Bans a user from the tower
+    BanUser(GetUserData),
+    /// Lifts the ban of a user
+    UnbanUser(GetUserData),
+    /// Deletes a user alongside all their appointments
+    DeleteUser(GetUserData),
+    /// Grants extra appointment slots to a user
+    GrantSlots(GrantSlotsData),
+    /// Sets the subscription expiry (block height) of a user
+    SetSubscriptionExpiry(SetSubscriptionExpiryData),
+    /// Gets the log of admin actions performed over users
+    GetAuditLog,
+    /// Signs a batch of blinded registration tokens, to be handed to users out of band
+    IssueRegistrationTokens(IssueRegistrationTokensData),
+    /// Backs the tower data up to the given (non-existing) directory while the tower keeps running
+    Backup(BackupData),
     /// Requests a graceful shutdown of the tower
     Stop,
 }
@@ -27,6 +43,37 @@ pub struct GetUserData {
     pub user_id: String,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+#[structopt(rename_all = "snake_case")]
+pub struct GrantSlotsData {
+    /// The user identifier (33-byte compressed public key).
+    pub user_id: String,
+    /// The number of slots to grant.
+    pub slots: u32,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+#[structopt(rename_all = "snake_case")]
+pub struct SetSubscriptionExpiryData {
+    /// The user identifier (33-byte compressed public key).
+    pub user_id: String,
+    /// The new subscription expiry (block height).
+    pub subscription_expiry: u32,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+#[structopt(rename_all = "snake_case")]
+pub struct IssueRegistrationTokensData {
+    /// The blinded tokens to sign (33-byte compressed points).
+    pub blinded_tokens: Vec<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct BackupData {
+    /// The directory to write the backup to.
+    pub path: String,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct GetAppointmentsData {
     /// The locator of the appointments (16-byte hexadecimal string).
@@ -4,6 +4,12 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+use teos_common::anti_spam::MAX_POW_DIFFICULTY;
+use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
+
+use crate::bitcoin_cli::RpcEndpoint;
+use crate::gatekeeper::{SubscriptionTier, MAX_TIER_NAME_LEN};
+
 pub fn data_dir_absolute_path(data_dir: String) -> PathBuf {
     if let Some(a) = data_dir.strip_prefix('~') {
         if let Some(b) = data_dir.strip_prefix("~/") {
@@ -29,9 +35,20 @@ pub fn from_file<T: Default + serde::de::DeserializeOwned>(path: &PathBuf) -> T
     }
 }
 
+/// Gets the address (`host:port`) of a `tcp://host:port` ZMQ endpoint, as set in `bitcoind` (e.g. `zmqpubhashblock`).
+pub fn parse_zmq_endpoint(endpoint: &str) -> Option<String> {
+    let address = endpoint.strip_prefix("tcp://")?;
+    let (host, port) = address.rsplit_once(':')?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return None;
+    }
+
+    Some(address.to_owned())
+}
+
 /// Error raised if something is wrong with the configuration.
 #[derive(PartialEq, Eq, Debug)]
-pub struct ConfigError(String);
+pub struct ConfigError(pub(crate) String);
 
 impl std::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -98,12 +115,22 @@ pub struct Opt {
     #[structopt(long)]
     pub overwrite_key: bool,
 
+    /// Changes the passphrase the tower data is encrypted under, and exits. The new passphrase is read from
+    /// TEOS_NEW_PASSPHRASE if set, or prompted otherwise
+    #[structopt(long)]
+    pub change_passphrase: bool,
+
+    /// Restores the tower data from the backup at the given path before starting. The backup is validated first, and
+    /// an existing database is never overwritten
+    #[structopt(long)]
+    pub restore: Option<String>,
+
     /// If set, creates a Tor endpoint to serve API data. This endpoint is additional to the clearnet HTTP API
     #[structopt(long)]
     pub tor_support: bool,
 
-    /// Forces the tower to run even if the underlying chain has gone too far out of sync. This can only happen
-    /// if the node is being run in pruned mode.
+    /// Forces the tower to run even if the underlying chain has gone too far out of sync and the missing blocks could
+    /// have triggered some of the appointments held by the tower. This can only happen if the node is being run in pruned mode.
     #[structopt(long)]
     pub force_update: bool,
 
@@ -128,6 +155,10 @@ pub struct Config {
     // API
     pub api_bind: String,
     pub api_port: u16,
+    pub ip_rate_limit: u32,
+    pub ip_rate_burst: u32,
+    pub user_rate_limit: u32,
+    pub user_rate_burst: u32,
 
     // RPC
     pub rpc_bind: String,
@@ -139,12 +170,37 @@ pub struct Config {
     pub btc_rpc_password: String,
     pub btc_rpc_connect: String,
     pub btc_rpc_port: u16,
+    pub btc_rpc_fallbacks: Vec<String>,
+
+    // ZMQ
+    pub btc_zmq_hashblock: String,
+    pub btc_zmq_rawtx: String,
+    pub btc_zmq_heartbeat: u32,
+
+    // Chain backend
+    pub chain_backend: String,
+    pub esplora_url: String,
+
+    // Database
+    pub database_backend: String,
+    pub postgres_url: String,
+
+    // Encryption at rest
+    pub encrypt_tower_key: bool,
+    pub encrypt_database: bool,
+    pub passphrase_file: String,
+
+    // Broadcast backends
+    pub btc_broadcast_nodes: Vec<String>,
+    pub esplora_broadcast_url: String,
 
     // Flags
     pub debug: bool,
     pub deps_debug: bool,
     pub overwrite_key: bool,
     pub force_update: bool,
+    pub change_passphrase: bool,
+    pub restore: String,
 
     // General
     pub subscription_slots: u32,
@@ -152,6 +208,30 @@ pub struct Config {
     pub expiry_delta: u32,
     pub min_to_self_delay: u16,
     pub polling_delta: u16,
+    pub locator_cache_depth: u32,
+
+    // Payments
+    pub payment_backend: String,
+    pub subscription_price: u64,
+    pub invoice_expiry: u32,
+    pub cln_rpc_path: String,
+    pub lnd_rest_url: String,
+    pub lnd_macaroon_path: String,
+    pub lnd_tls_cert_path: String,
+
+    // Subscription tiers
+    pub tiers: Option<Vec<SubscriptionTier>>,
+
+    // Registration gate
+    pub registration_gate: String,
+    pub pow_difficulty: u8,
+    pub pow_challenge_expiry: u32,
+
+    // Fee bumping
+    pub cpfp: bool,
+    pub cpfp_min_fee_rate: u64,
+    pub cpfp_max_fee_rate: u64,
+    pub cpfp_fee_rate_escalation: u64,
 
     // Internal API
     pub internal_api_bind: String,
@@ -205,22 +285,223 @@ impl Config {
         self.deps_debug |= options.deps_debug;
         self.overwrite_key = options.overwrite_key;
         self.force_update = options.force_update;
+        self.change_passphrase = options.change_passphrase;
+        self.restore = options.restore.unwrap_or_default();
     }
 
     /// Verifies that [Config] is properly built.
     ///
     /// This includes:
-    /// - `bitcoind` credentials have been set
+    /// - The chain backend is either `bitcoind` or `esplora`
+    /// - `bitcoind` credentials have been set (if running on top of `bitcoind`)
+    /// - The Esplora url has been set (if running on top of Esplora)
     /// - The Bitcoin network has been properly set (to either bitcoin, testnet, signet or regtest)
+    /// - The database backend is either `sqlite` or `postgres` (and its connection string has been set)
+    /// - The database is only encrypted as a whole if running on top of `sqlite`
+    /// - The additional `bitcoind` broadcast nodes, if any, are properly formatted (`user:password@host:port`)
+    /// - The ZMQ endpoints, if any, are properly formatted (`tcp://host:port`)
+    /// - The rate limits, if enabled, allow bursts of at least one request
+    /// - The locator cache holds, at least, one block
+    /// - The payment backend is either `cln` or `lnd` (and its credentials have been set) if subscriptions are paid
+    /// - The subscription tiers, if set, are not empty, have unique names and can hold appointments
+    /// - The registration gate, if any, is either `pow` (with a sensible difficulty) or `tokens`
+    /// - The fee rates for CPFP transactions are not zero, and the minimum is not above the maximum (if fee bumping is enabled)
     ///
     /// This will also assign the default `btc_rpc_port` depending on the network if it has not
     /// been overwritten at this point.
     pub fn verify(&mut self) -> Result<(), ConfigError> {
-        if self.btc_rpc_user == String::new() {
-            return Err(ConfigError("btc_rpc_user must be set".to_owned()));
+        match self.chain_backend.as_str() {
+            "bitcoind" => {
+                if self.btc_rpc_user == String::new() {
+                    return Err(ConfigError("btc_rpc_user must be set".to_owned()));
+                }
+                if self.btc_rpc_password == String::new() {
+                    return Err(ConfigError("btc_rpc_password must be set".to_owned()));
+                }
+            }
+            "esplora" => {
+                if self.esplora_url == String::new() {
+                    return Err(ConfigError(
+                        "esplora_url must be set when using the esplora chain backend".to_owned(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ConfigError(format!(
+                    "chain_backend not recognized. Expected {{bitcoind, esplora}}, received {}",
+                    self.chain_backend
+                )))
+            }
+        }
+        match self.database_backend.as_str() {
+            "sqlite" => (),
+            "postgres" => {
+                if self.postgres_url.is_empty() {
+                    return Err(ConfigError(
+                        "postgres_url must be set when using the postgres database backend"
+                            .to_owned(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ConfigError(format!(
+                    "database_backend not recognized. Expected {{sqlite, postgres}}, received {}",
+                    self.database_backend
+                )))
+            }
+        }
+        if self.encrypt_database && self.database_backend != "sqlite" {
+            return Err(ConfigError(
+                "encrypt_database is only supported by the sqlite database backend".to_owned(),
+            ));
+        }
+        if self.encrypt_database && !cfg!(feature = "encryption") {
+            return Err(ConfigError(
+                "encrypt_database requires building teos with the encryption feature".to_owned(),
+            ));
+        }
+        if !self.restore.is_empty() && self.database_backend != "sqlite" {
+            return Err(ConfigError(
+                "restore is only supported by the sqlite database backend".to_owned(),
+            ));
+        }
+        self.bitcoind_endpoints()?;
+        self.broadcast_endpoints()?;
+        if let Some(endpoint) = [&self.btc_zmq_hashblock, &self.btc_zmq_rawtx]
+            .iter()
+            .find(|endpoint| !endpoint.is_empty() && parse_zmq_endpoint(endpoint).is_none())
+        {
+            return Err(ConfigError(format!(
+                "ZMQ endpoints must be formatted as tcp://host:port, received {endpoint}"
+            )));
+        }
+        if (self.ip_rate_limit > 0 && self.ip_rate_burst == 0)
+            || (self.user_rate_limit > 0 && self.user_rate_burst == 0)
+        {
+            return Err(ConfigError(
+                "ip_rate_burst and user_rate_burst must be at least 1 if their rate limit is set"
+                    .to_owned(),
+            ));
+        }
+        if self.btc_zmq_heartbeat == 0 {
+            return Err(ConfigError(
+                "btc_zmq_heartbeat must be at least 1".to_owned(),
+            ));
+        }
+        if self.locator_cache_depth == 0 {
+            return Err(ConfigError(
+                "locator_cache_depth must be at least 1".to_owned(),
+            ));
+        }
+        match self.payment_backend.as_str() {
+            "" => {
+                if self.subscription_price > 0 {
+                    return Err(ConfigError(
+                        "payment_backend must be set for subscriptions to be paid".to_owned(),
+                    ));
+                }
+            }
+            "cln" => {
+                if self.cln_rpc_path.is_empty() {
+                    return Err(ConfigError(
+                        "cln_rpc_path must be set when using the cln payment backend".to_owned(),
+                    ));
+                }
+            }
+            "lnd" => {
+                if self.lnd_rest_url.is_empty() || self.lnd_macaroon_path.is_empty() {
+                    return Err(ConfigError(
+                        "lnd_rest_url and lnd_macaroon_path must be set when using the lnd payment backend"
+                            .to_owned(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ConfigError(format!(
+                    "payment_backend not recognized. Expected {{cln, lnd}}, received {}",
+                    self.payment_backend
+                )))
+            }
+        }
+        if matches!(&self.tiers, Some(tiers) if tiers.is_empty()) {
+            return Err(ConfigError(
+                "tiers must define at least one subscription tier if set".to_owned(),
+            ));
         }
-        if self.btc_rpc_password == String::new() {
-            return Err(ConfigError("btc_rpc_password must be set".to_owned()));
+        let mut tier_names = std::collections::HashSet::new();
+        for tier in self.tiers.iter().flatten() {
+            if tier.name.is_empty() || tier.name.len() > MAX_TIER_NAME_LEN {
+                return Err(ConfigError(format!(
+                    "tier names must be between 1 and {MAX_TIER_NAME_LEN} bytes long, received {:?}",
+                    tier.name
+                )));
+            }
+            if !tier_names.insert(&tier.name) {
+                return Err(ConfigError(format!(
+                    "tier names must be unique, {} is repeated",
+                    tier.name
+                )));
+            }
+            if tier.slots == 0 || tier.duration == 0 || tier.max_blob_size == 0 {
+                return Err(ConfigError(format!(
+                    "tier slots, duration and max_blob_size must be at least 1 (tier {})",
+                    tier.name
+                )));
+            }
+            if tier.price > 0 && self.payment_backend.is_empty() {
+                return Err(ConfigError(format!(
+                    "payment_backend must be set for subscriptions to be paid (tier {})",
+                    tier.name
+                )));
+            }
+        }
+        if !self.payment_backend.is_empty() && self.tiers.is_none() && self.subscription_price == 0
+        {
+            return Err(ConfigError(
+                "subscription_price must be at least 1 msat when a payment_backend is set"
+                    .to_owned(),
+            ));
+        }
+        if self.invoice_expiry == 0 {
+            return Err(ConfigError("invoice_expiry must be at least 1".to_owned()));
+        }
+        match self.registration_gate.as_str() {
+            "" | "tokens" => (),
+            "pow" => {
+                if self.pow_difficulty == 0 || self.pow_difficulty > MAX_POW_DIFFICULTY {
+                    return Err(ConfigError(format!(
+                        "pow_difficulty must be between 1 and {MAX_POW_DIFFICULTY}"
+                    )));
+                }
+                if self.pow_challenge_expiry == 0 {
+                    return Err(ConfigError(
+                        "pow_challenge_expiry must be at least 1".to_owned(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ConfigError(format!(
+                    "registration_gate not recognized. Expected {{pow, tokens}}, received {}",
+                    self.registration_gate
+                )))
+            }
+        }
+        if self.cpfp {
+            if self.cpfp_max_fee_rate == 0 {
+                return Err(ConfigError(
+                    "cpfp_max_fee_rate must be at least 1".to_owned(),
+                ));
+            }
+            if self.cpfp_min_fee_rate == 0 {
+                return Err(ConfigError(
+                    "cpfp_min_fee_rate must be at least 1".to_owned(),
+                ));
+            }
+            if self.cpfp_min_fee_rate > self.cpfp_max_fee_rate {
+                return Err(ConfigError(
+                    "cpfp_min_fee_rate cannot be greater than cpfp_max_fee_rate".to_owned(),
+                ));
+            }
         }
 
         // Normalize the network option to the ones used by bitcoind.
@@ -245,6 +526,59 @@ impl Config {
         Ok(())
     }
 
+    /// Gets the `bitcoind` backends of the tower, sorted by preference: our own `bitcoind` followed by the ones to fail
+    /// over to (`btc_rpc_fallbacks`).
+    ///
+    /// Fails if any of the fallbacks is not formatted as `user:password@host:port`.
+    pub fn bitcoind_endpoints(&self) -> Result<Vec<RpcEndpoint>, ConfigError> {
+        let mut endpoints = vec![RpcEndpoint::new(
+            &self.btc_rpc_connect,
+            self.btc_rpc_port,
+            &self.btc_rpc_user,
+            &self.btc_rpc_password,
+        )];
+        for fallback in self.btc_rpc_fallbacks.iter() {
+            endpoints.push(RpcEndpoint::parse(fallback).ok_or_else(|| {
+                ConfigError(format!(
+                    "btc_rpc_fallbacks entries must be formatted as user:password@host:port, received {fallback}"
+                ))
+            })?);
+        }
+
+        Ok(endpoints)
+    }
+
+    /// Gets the additional `bitcoind` nodes penalties are pushed through (`btc_broadcast_nodes`).
+    ///
+    /// Fails if any of the nodes is not formatted as `user:password@host:port`.
+    pub fn broadcast_endpoints(&self) -> Result<Vec<RpcEndpoint>, ConfigError> {
+        self.btc_broadcast_nodes
+            .iter()
+            .map(|node| {
+                RpcEndpoint::parse(node).ok_or_else(|| {
+                    ConfigError(format!(
+                        "btc_broadcast_nodes entries must be formatted as user:password@host:port, received {node}"
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the subscription tiers offered by the tower. Towers that do not define any offer a single unnamed one,
+    /// built from the `subscription_*` options.
+    pub fn subscription_tiers(&self) -> Vec<SubscriptionTier> {
+        match &self.tiers {
+            Some(tiers) => tiers.clone(),
+            None => vec![SubscriptionTier::new(
+                String::new(),
+                self.subscription_slots,
+                self.subscription_duration,
+                self.subscription_price,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            )],
+        }
+    }
+
     /// Checks whether the config has been set with only with default values.
     pub fn is_default(&self) -> bool {
         self == &Config::default()
@@ -254,7 +588,13 @@ impl Config {
     pub fn log_non_default_options(&self) {
         let json_default_config = serde_json::json!(&Config::default());
         let json_config = serde_json::json!(&self);
-        let sensitive_args = ["btc_rpc_user", "btc_rpc_password"];
+        let sensitive_args = [
+            "btc_rpc_user",
+            "btc_rpc_password",
+            "btc_rpc_fallbacks",
+            "btc_broadcast_nodes",
+            "postgres_url",
+        ];
 
         for (key, value) in json_config.as_object().unwrap().iter() {
             if *value != json_default_config[key] {
@@ -283,6 +623,10 @@ impl Default for Config {
         Self {
             api_bind: "127.0.0.1".into(),
             api_port: 9814,
+            ip_rate_limit: 600,
+            ip_rate_burst: 120,
+            user_rate_limit: 300,
+            user_rate_burst: 60,
             tor_support: false,
             tor_control_port: 9051,
             onion_hidden_service_port: 9814,
@@ -293,16 +637,47 @@ impl Default for Config {
             btc_rpc_password: String::new(),
             btc_rpc_connect: "localhost".into(),
             btc_rpc_port: 0,
+            btc_rpc_fallbacks: Vec::new(),
+            btc_zmq_hashblock: String::new(),
+            btc_zmq_rawtx: String::new(),
+            btc_zmq_heartbeat: 1800,
+            chain_backend: "bitcoind".into(),
+            esplora_url: String::new(),
+            database_backend: "sqlite".into(),
+            postgres_url: String::new(),
+            encrypt_tower_key: false,
+            encrypt_database: false,
+            passphrase_file: String::new(),
+            btc_broadcast_nodes: Vec::new(),
+            esplora_broadcast_url: String::new(),
 
             debug: false,
             deps_debug: false,
             overwrite_key: false,
             force_update: false,
+            change_passphrase: false,
+            restore: String::new(),
             subscription_slots: 10000,
             subscription_duration: 4320,
             expiry_delta: 6,
             min_to_self_delay: 20,
             polling_delta: 60,
+            locator_cache_depth: 6,
+            payment_backend: String::new(),
+            subscription_price: 0,
+            invoice_expiry: 3600,
+            cln_rpc_path: String::new(),
+            lnd_rest_url: String::new(),
+            lnd_macaroon_path: String::new(),
+            lnd_tls_cert_path: String::new(),
+            tiers: None,
+            registration_gate: String::new(),
+            pow_difficulty: 20,
+            pow_challenge_expiry: 600,
+            cpfp: false,
+            cpfp_min_fee_rate: 1,
+            cpfp_max_fee_rate: 500,
+            cpfp_fee_rate_escalation: 50,
             internal_api_bind: "127.0.0.1".into(),
             internal_api_port: 50051,
         }
@@ -334,6 +709,8 @@ mod tests {
                 deps_debug: false,
                 overwrite_key: false,
                 force_update: false,
+                change_passphrase: false,
+                restore: None,
             }
         }
     }
@@ -403,4 +780,438 @@ mod tests {
 
         config.verify().unwrap()
     }
+
+    #[test]
+    fn test_config_verify_rate_limits() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            user_rate_burst: 0,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("user_rate_burst must be at least 1"))
+        );
+
+        // Bursts are not needed if rate limiting is disabled
+        config.user_rate_limit = 0;
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_config_verify_database_backend() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            database_backend: "mysql".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("database_backend not recognized"))
+        );
+
+        config.database_backend = "postgres".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("postgres_url must be set"))
+        );
+
+        config.postgres_url = "postgresql://teos@localhost/teos".to_owned();
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_config_verify_encrypt_database() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            encrypt_database: true,
+            ..Default::default()
+        };
+        if cfg!(feature = "encryption") {
+            config.verify().unwrap();
+        } else {
+            // SQLCipher is only bundled by the encryption feature
+            assert!(
+                matches!(config.verify(), Err(ConfigError(e)) if e.contains("requires building teos with the encryption feature"))
+            );
+        }
+
+        // Only SQLite databases can be encrypted as a whole
+        config.database_backend = "postgres".to_owned();
+        config.postgres_url = "postgresql://teos@localhost/teos".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("encrypt_database is only supported"))
+        );
+
+        // The tower key can still be encrypted
+        config.encrypt_database = false;
+        config.encrypt_tower_key = true;
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_config_verify_restore() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            restore: "/backup".to_owned(),
+            ..Default::default()
+        };
+        config.verify().unwrap();
+
+        // Backups are taken out of SQLite databases, so they cannot be restored into PostgreSQL
+        config.database_backend = "postgres".to_owned();
+        config.postgres_url = "postgresql://teos@localhost/teos".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("restore is only supported"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_registration_gate() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            registration_gate: "captcha".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("registration_gate not recognized"))
+        );
+
+        config.registration_gate = "tokens".to_owned();
+        config.verify().unwrap();
+
+        // The proof of work difficulty is only checked if the gate is enabled
+        config.pow_difficulty = MAX_POW_DIFFICULTY + 1;
+        config.verify().unwrap();
+        config.registration_gate = "pow".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("pow_difficulty must be between 1"))
+        );
+        config.pow_difficulty = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("pow_difficulty must be between 1"))
+        );
+
+        config.pow_difficulty = MAX_POW_DIFFICULTY;
+        config.pow_challenge_expiry = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("pow_challenge_expiry must be at least 1"))
+        );
+        config.pow_challenge_expiry = 1;
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_config_verify_empty_locator_cache() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            locator_cache_depth: 0,
+            ..Default::default()
+        };
+
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("locator_cache_depth must be at least 1"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_payment_backend() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            subscription_price: 1000,
+            ..Default::default()
+        };
+
+        // Subscriptions cannot be paid without a payment backend
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("payment_backend must be set"))
+        );
+
+        config.payment_backend = "wrong_backend".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("payment_backend not recognized"))
+        );
+
+        // Each backend requires its own credentials
+        config.payment_backend = "cln".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("cln_rpc_path must be set"))
+        );
+        config.cln_rpc_path = "~/.lightning/bitcoin/lightning-rpc".to_owned();
+        config.verify().unwrap();
+
+        config.payment_backend = "lnd".to_owned();
+        config.lnd_rest_url = "https://localhost:8080".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("lnd_macaroon_path must be set"))
+        );
+        config.lnd_macaroon_path = "~/.lnd/invoice.macaroon".to_owned();
+        config.verify().unwrap();
+
+        // Paid subscriptions need a price
+        config.subscription_price = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("subscription_price must be at least 1"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_tiers() {
+        let tier = SubscriptionTier::new("basic".to_owned(), 100, 4320, 0, 2048);
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            tiers: Some(vec![tier.clone()]),
+            ..Default::default()
+        };
+        config.verify().unwrap();
+
+        // Tiers cannot be set to an empty list
+        config.tiers = Some(Vec::new());
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("tiers must define at least one subscription tier"))
+        );
+
+        // Tiers need a name, which must be unique
+        config.tiers = Some(vec![SubscriptionTier {
+            name: String::new(),
+            ..tier.clone()
+        }]);
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("tier names must be between 1 and"))
+        );
+        config.tiers = Some(vec![SubscriptionTier {
+            name: "a".repeat(MAX_TIER_NAME_LEN + 1),
+            ..tier.clone()
+        }]);
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("tier names must be between 1 and"))
+        );
+        config.tiers = Some(vec![tier.clone(), tier.clone()]);
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("tier names must be unique"))
+        );
+
+        // And must be able to hold appointments
+        config.tiers = Some(vec![SubscriptionTier {
+            max_blob_size: 0,
+            ..tier.clone()
+        }]);
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("must be at least 1 (tier basic)"))
+        );
+
+        // Paid tiers need a payment backend
+        config.tiers = Some(vec![SubscriptionTier {
+            price: 1000,
+            ..tier
+        }]);
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("payment_backend must be set"))
+        );
+        config.payment_backend = "cln".to_owned();
+        config.cln_rpc_path = "~/.lightning/bitcoin/lightning-rpc".to_owned();
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_subscription_tiers() {
+        // With no tiers, a single unnamed one is built from the subscription options
+        let mut config = Config::default();
+        assert_eq!(
+            config.subscription_tiers(),
+            vec![SubscriptionTier::new(
+                String::new(),
+                config.subscription_slots,
+                config.subscription_duration,
+                config.subscription_price,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            )]
+        );
+
+        // Tiers are read from the config file, defaulting their price and max_blob_size if not set
+        config = toml::from_str(
+            r#"
+            subscription_slots = 1
+            [[tiers]]
+            name = "basic"
+            slots = 100
+            duration = 4320
+            [[tiers]]
+            name = "premium"
+            slots = 1000
+            duration = 4320
+            price = 5000
+            max_blob_size = 4096
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.subscription_tiers(),
+            vec![
+                SubscriptionTier::new("basic".to_owned(), 100, 4320, 0, ENCRYPTED_BLOB_MAX_SIZE),
+                SubscriptionTier::new("premium".to_owned(), 1000, 4320, 5000, 4096),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_verify_cpfp_zero_max_fee_rate() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            cpfp_max_fee_rate: 0,
+            ..Default::default()
+        };
+
+        // The fee rate is only checked if fee bumping is enabled
+        config.verify().unwrap();
+
+        config.cpfp = true;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("cpfp_max_fee_rate must be at least 1"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_cpfp_min_fee_rate() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            cpfp: true,
+            cpfp_min_fee_rate: 10,
+            cpfp_max_fee_rate: 10,
+            ..Default::default()
+        };
+        config.verify().unwrap();
+
+        config.cpfp_min_fee_rate = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("cpfp_min_fee_rate must be at least 1"))
+        );
+
+        config.cpfp_min_fee_rate = 11;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("cpfp_min_fee_rate cannot be greater than cpfp_max_fee_rate"))
+        );
+    }
+
+    #[test]
+    fn test_parse_zmq_endpoint() {
+        assert_eq!(
+            parse_zmq_endpoint("tcp://127.0.0.1:28332"),
+            Some("127.0.0.1:28332".to_owned())
+        );
+
+        for wrong_endpoint in [
+            "127.0.0.1:28332",
+            "ipc:///tmp/bitcoind.sock",
+            "tcp://127.0.0.1",
+            "tcp://:28332",
+            "tcp://127.0.0.1:port",
+        ] {
+            assert_eq!(parse_zmq_endpoint(wrong_endpoint), None);
+        }
+    }
+
+    #[test]
+    fn test_config_verify_wrong_zmq_endpoint() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            btc_zmq_hashblock: "tcp://127.0.0.1:28332".to_owned(),
+            ..Default::default()
+        };
+        config.verify().unwrap();
+
+        config.btc_zmq_rawtx = "127.0.0.1:28333".to_owned();
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("ZMQ endpoints must be formatted"))
+        );
+
+        config.btc_zmq_rawtx = String::new();
+        config.btc_zmq_heartbeat = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("btc_zmq_heartbeat must be at least 1"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_wrong_broadcast_node() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            btc_broadcast_nodes: vec!["user:pass@localhost:8332".to_owned()],
+            ..Default::default()
+        };
+        config.verify().unwrap();
+
+        assert_eq!(
+            config.broadcast_endpoints().unwrap(),
+            vec![RpcEndpoint::new("localhost", 8332, "user", "pass")]
+        );
+
+        config.btc_broadcast_nodes.push("localhost:8332".to_owned());
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("btc_broadcast_nodes entries must be formatted"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_wrong_rpc_fallback() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            btc_rpc_fallbacks: vec!["user:pass@10.0.0.2:8332".to_owned()],
+            ..Default::default()
+        };
+        config.verify().unwrap();
+        assert_eq!(
+            config.bitcoind_endpoints().unwrap(),
+            vec![
+                RpcEndpoint::new("localhost", 8332, "user", "password"),
+                RpcEndpoint::new("10.0.0.2", 8332, "user", "pass")
+            ]
+        );
+
+        // The port is required
+        config
+            .btc_rpc_fallbacks
+            .push("user:pass@10.0.0.3".to_owned());
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("btc_rpc_fallbacks entries must be formatted"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_esplora_backend() {
+        // Running on top of Esplora does not require bitcoind credentials, but an Esplora url
+        let mut config = Config {
+            chain_backend: "esplora".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("esplora_url must be set"))
+        );
+
+        config.esplora_url = "https://blockstream.info/api".to_owned();
+        config.verify().unwrap();
+    }
+
+    #[test]
+    fn test_config_verify_wrong_chain_backend() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            chain_backend: "electrum".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("chain_backend not recognized"))
+        );
+    }
 }
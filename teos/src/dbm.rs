@@ -2,33 +2,44 @@
 //!
 
 use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io::Read;
 use std::iter::FromIterator;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use rusqlite::limits::Limit;
-use rusqlite::{params, params_from_iter, Connection, Error as SqliteError};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
 
 use bitcoin::consensus;
 use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::SecretKey;
-use bitcoin::BlockHash;
+use bitcoin::{BlockHash, Txid};
 
 use teos_common::appointment::{compute_appointment_slots, Appointment, Locator};
 use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
-use teos_common::dbm::{DatabaseConnection, DatabaseManager, Error};
+use teos_common::dbm::{DatabaseConnection, DatabaseManager, Error, Migration, MigrationError};
 use teos_common::UserId;
 
+use crate::encryption::TowerKey;
 use crate::extended_appointment::{ExtendedAppointment, UUID};
-use crate::gatekeeper::UserInfo;
+use crate::gatekeeper::{AuditLogEntry, PendingRegistration, SubscriptionOperation, UserInfo};
+use crate::payments::Invoice;
 use crate::responder::{ConfirmationStatus, TransactionTracker};
+use crate::tx_index::BlockSummary;
+use crate::wallet::FeeBump;
+use crate::watcher::Breach;
 
-const TABLES: [&str; 5] = [
+/// The tables of the first version of the database schema. Changes to the schema go into [MIGRATIONS] instead.
+const TABLES: [&str; 12] = [
     "CREATE TABLE IF NOT EXISTS users (
     user_id INT PRIMARY KEY,
     available_slots INT NOT NULL,
     subscription_start INT NOT NULL,
-    subscription_expiry INT NOT NULL
+    subscription_expiry INT NOT NULL,
+    tier TEXT NOT NULL DEFAULT ''
 )",
     "CREATE TABLE IF NOT EXISTS appointments (
     UUID INT PRIMARY KEY,
@@ -60,11 +71,384 @@ const TABLES: [&str; 5] = [
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     key INT NOT NULL
 )",
+    "CREATE TABLE IF NOT EXISTS wallet_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key INT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS fee_bumps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    UUID INT NOT NULL,
+    child_tx BLOB NOT NULL,
+    fee INT NOT NULL,
+    fee_rate INT NOT NULL,
+    height INT NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES trackers(UUID)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS block_summaries (
+    height INT PRIMARY KEY,
+    header BLOB NOT NULL,
+    txids BLOB NOT NULL,
+    txdata BLOB
+)",
+    "CREATE TABLE IF NOT EXISTS pending_registrations (
+    user_id INT PRIMARY KEY,
+    invoice TEXT NOT NULL,
+    payment_hash BLOB NOT NULL,
+    amount_msat INT NOT NULL,
+    expires_at INT NOT NULL,
+    tier TEXT NOT NULL DEFAULT '',
+    operation INT NOT NULL DEFAULT 0
+)",
+    "CREATE TABLE IF NOT EXISTS banned_users (
+    user_id INT PRIMARY KEY
+)",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INT NOT NULL,
+    user_id INT NOT NULL,
+    action TEXT NOT NULL,
+    details TEXT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS redeemed_tokens (
+    token INT PRIMARY KEY
+)",
+];
+
+/// Columns added to tables before the database schema was versioned, as (table, column, definition).
+///
+/// Used to bring databases created back then up to the first version of the schema.
+const ADDED_COLUMNS: [(&str, &str, &str); 3] = [
+    ("users", "tier", "TEXT NOT NULL DEFAULT ''"),
+    ("pending_registrations", "tier", "TEXT NOT NULL DEFAULT ''"),
+    (
+        "pending_registrations",
+        "operation",
+        "INT NOT NULL DEFAULT 0",
+    ),
+];
+
+/// The migrations of the database schema, from oldest to newest.
+const MIGRATIONS: [Migration; 6] = [
+    Migration {
+        description: "Create the initial schema",
+        statements: &TABLES,
+    },
+    Migration {
+        description: "Allow encrypting the tower keys",
+        statements: &["ALTER TABLE keys ADD COLUMN encrypted INT NOT NULL DEFAULT 0"],
+    },
+    Migration {
+        description: "Persist the breaches queued for the Responder",
+        statements: &["CREATE TABLE IF NOT EXISTS queued_breaches (
+    UUID INT PRIMARY KEY,
+    dispute_tx BLOB NOT NULL,
+    penalty_tx BLOB NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES appointments(UUID)
+        ON DELETE CASCADE
+)"],
+    },
+    Migration {
+        description: "Persist the appointments triggered from the mempool",
+        statements: &["CREATE TABLE IF NOT EXISTS mempool_triggers (
+    UUID INT PRIMARY KEY,
+    dispute_txid BLOB NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES appointments(UUID)
+        ON DELETE CASCADE
+)"],
+    },
+    Migration {
+        description: "Keep every invoice issued to a user",
+        statements: &[
+            "CREATE TABLE pending_registrations_by_invoice (
+    payment_hash BLOB PRIMARY KEY,
+    user_id INT NOT NULL,
+    invoice TEXT NOT NULL,
+    amount_msat INT NOT NULL,
+    expires_at INT NOT NULL,
+    tier TEXT NOT NULL DEFAULT '',
+    operation INT NOT NULL DEFAULT 0
+)",
+            "INSERT INTO pending_registrations_by_invoice (payment_hash, user_id, invoice, amount_msat, expires_at, tier, operation)
+    SELECT payment_hash, user_id, invoice, amount_msat, expires_at, tier, operation FROM pending_registrations",
+            "DROP TABLE pending_registrations",
+            "ALTER TABLE pending_registrations_by_invoice RENAME TO pending_registrations",
+            "CREATE INDEX IF NOT EXISTS pending_registrations_user_id ON pending_registrations (user_id)",
+        ],
+    },
+    Migration {
+        description: "Allow encrypting the wallet keys",
+        statements: &["ALTER TABLE wallet_keys ADD COLUMN encrypted INT NOT NULL DEFAULT 0"],
+    },
 ];
 
-/// Component in charge of interacting with the underlying database.
+/// Header every unencrypted `SQLite` database file starts with.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Encodes a [TowerKey] the way it is kept in the database, that is, as a hex string alongside whether it is encrypted.
+pub(crate) fn encode_tower_key(key: &TowerKey) -> (String, bool) {
+    match key {
+        TowerKey::Plaintext(sk) => (sk.display_secret().to_string(), false),
+        TowerKey::Encrypted(encrypted) => (hex::encode(encrypted), true),
+    }
+}
+
+/// Decodes a [TowerKey] encoded by [encode_tower_key].
+pub(crate) fn decode_tower_key(key: &str, encrypted: bool) -> TowerKey {
+    if encrypted {
+        TowerKey::Encrypted(hex::decode(key).unwrap())
+    } else {
+        TowerKey::Plaintext(SecretKey::from_str(key).unwrap())
+    }
+}
+
+/// Checks whether the file at the given path is an unencrypted `SQLite` database.
+fn is_plaintext_database(db_path: &Path) -> bool {
+    let mut header = [0; 16];
+    fs::File::open(db_path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && &header == SQLITE_HEADER
+}
+
+/// Encrypts an unencrypted `SQLite` database in place, under the given passphrase.
+///
+/// The data is exported to an encrypted copy of the database, which then replaces the original file.
+fn encrypt_database(db_path: &Path, passphrase: &str) -> Result<(), MigrationError> {
+    let encrypted_path = db_path.with_extension("encrypted");
+    if encrypted_path.exists() {
+        fs::remove_file(&encrypted_path)
+            .map_err(|e| MigrationError::Backend(format!("Cannot encrypt the database: {e}")))?;
+    }
+
+    let connection = Connection::open(db_path)?;
+    connection.execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        params![encrypted_path.to_string_lossy(), passphrase],
+    )?;
+    connection.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+    connection.execute("DETACH DATABASE encrypted", [])?;
+    drop(connection);
+
+    fs::rename(&encrypted_path, db_path)
+        .map_err(|e| MigrationError::Backend(format!("Cannot encrypt the database: {e}")))
+}
+
+/// Interface to the storage backend of the tower. Every component that needs to persist data does it through this trait.
 ///
-/// Currently works for `SQLite`. `PostgreSQL` should also be added in the future.
+/// Implemented by [DBM] for `SQLite` and by [PostgresDBM](crate::postgres_dbm::PostgresDBM) for `PostgreSQL`.
+pub trait Storage: Send + fmt::Debug {
+    /// Stores a user ([UserInfo]) into the database.
+    fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error>;
+
+    /// Updates an existing user ([UserInfo]) in the database.
+    fn update_user(&self, user_id: UserId, user_info: &UserInfo);
+
+    /// Loads a user ([UserInfo]) from the database, if found. Fails if the database cannot be read.
+    ///
+    /// The slots taken by the user appointments are computed using [ENCRYPTED_BLOB_MAX_SIZE] as slot size.
+    fn load_user(&self, user_id: UserId) -> Result<Option<UserInfo>, Error>;
+
+    /// Loads the associated appointments ([Appointment]) of a given user ([UserInfo]), alongside the slots they take
+    /// given the blob size a slot covers.
+    fn load_user_appointments(&self, user_id: UserId, slot_size: usize) -> HashMap<UUID, u32>;
+
+    /// Loads all users from the database.
+    ///
+    /// The slots taken by the user appointments are computed using [ENCRYPTED_BLOB_MAX_SIZE] as slot size.
+    fn load_all_users(&self) -> HashMap<UserId, UserInfo>;
+
+    /// Removes some users from the database in batch. Returns the number of queries the removal has been split into.
+    fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize;
+
+    /// Stores an [Appointment] into the database.
+    fn store_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment)
+        -> Result<(), Error>;
+
+    /// Updates an existing [Appointment] in the database.
+    fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment);
+
+    /// Loads an [Appointment] from the database.
+    fn load_appointment(&self, uuid: UUID) -> Option<ExtendedAppointment>;
+
+    /// Loads appointments from the database. If a locator is given, this method loads only the appointments
+    /// matching this locator. If no locator is given, all the appointments in the database would be returned.
+    fn load_appointments(&self, locator: Option<Locator>) -> HashMap<UUID, ExtendedAppointment>;
+
+    /// Removes an [Appointment] from the database.
+    fn remove_appointment(&self, uuid: UUID);
+
+    /// Removes some appointments from the database in batch and updates the associated users giving back
+    /// the freed appointment slots. Returns the number of queries the removal has been split into.
+    fn batch_remove_appointments(
+        &mut self,
+        appointments: &HashSet<UUID>,
+        updated_users: &HashMap<UserId, UserInfo>,
+    ) -> usize;
+
+    /// Stores (or updates) a batch of appointments belonging to the same user alongside the updated user information
+    /// in a single database transaction. Appointments are processed in order, so a later entry for the same [UUID]
+    /// updates an earlier one. The [Breach]es of the appointments that have already been triggered are queued (see
+    /// [Storage::store_queued_breach]) within the same transaction.
+    ///
+    /// If any of the queries fails, the transaction is rolled back and no data is stored.
+    fn batch_store_appointments(
+        &mut self,
+        appointments: &[(UUID, &ExtendedAppointment)],
+        breaches: &[(UUID, &Breach)],
+        user_id: UserId,
+        user_info: &UserInfo,
+    ) -> Result<(), Error>;
+
+    /// Loads the locator associated to a given UUID
+    fn load_locator(&self, uuid: UUID) -> Option<Locator>;
+
+    /// Stores a [TransactionTracker] into the database.
+    fn store_tracker(&self, uuid: UUID, tracker: &TransactionTracker) -> Result<(), Error>;
+
+    /// Loads a [TransactionTracker] from the database.
+    fn load_tracker(&self, uuid: UUID) -> Option<TransactionTracker>;
+
+    /// Loads trackers from the database. If a locator is given, this method loads only the trackers
+    /// matching this locator. If no locator is given, all the trackers in the database would be returned.
+    fn load_trackers(&self, locator: Option<Locator>) -> HashMap<UUID, TransactionTracker>;
+
+    /// Removes a [TransactionTracker] from the database.
+    ///
+    /// The associated appointment is kept, so it can be picked back up by the [Watcher](crate::watcher::Watcher).
+    fn remove_tracker(&self, uuid: UUID);
+
+    /// Stores a [FeeBump] performed for a given tracker into the database.
+    fn store_fee_bump(&self, uuid: UUID, fee_bump: &FeeBump) -> Result<(), Error>;
+
+    /// Loads the [FeeBump]s performed for a given tracker from the database, from oldest to newest.
+    fn load_fee_bumps(&self, uuid: UUID) -> Vec<FeeBump>;
+
+    /// Stores a [Breach] queued to be handed to the [Responder](crate::responder::Responder) for a given appointment,
+    /// so it survives restarts.
+    fn store_queued_breach(&self, uuid: UUID, breach: &Breach) -> Result<(), Error>;
+
+    /// Removes the queued [Breach] of a given appointment from the database, if found.
+    fn remove_queued_breach(&self, uuid: UUID);
+
+    /// Loads all the queued [Breach]es from the database.
+    fn load_queued_breaches(&self) -> HashMap<UUID, Breach>;
+
+    /// Stores the id of the unconfirmed dispute that triggered a given appointment from the mempool, so the
+    /// appointment is still tracked by the [Watcher](crate::watcher::Watcher) after a restart.
+    fn store_mempool_trigger(&self, uuid: UUID, dispute_txid: &Txid) -> Result<(), Error>;
+
+    /// Removes the mempool trigger of a given appointment from the database, if found.
+    fn remove_mempool_trigger(&self, uuid: UUID);
+
+    /// Loads all the mempool triggers from the database.
+    fn load_mempool_triggers(&self) -> HashMap<UUID, Txid>;
+
+    /// Stores a pending registration (the [Invoice] a user has been issued to pay for a subscription operation) into the
+    /// database, alongside the rest of the user's ones.
+    fn store_pending_registration(
+        &self,
+        user_id: UserId,
+        registration: &PendingRegistration,
+    ) -> Result<(), Error>;
+
+    /// Removes the pending registration paid by a given payment hash from the database, if found.
+    fn remove_pending_registration(&self, payment_hash: &[u8; 32]);
+
+    /// Removes all the pending registrations of a given user from the database.
+    fn remove_pending_registrations(&self, user_id: UserId);
+
+    /// Loads all pending registrations from the database, grouped by user, from oldest to newest.
+    fn load_pending_registrations(&self) -> HashMap<UserId, Vec<PendingRegistration>>;
+
+    /// Stores a banned user into the database.
+    fn store_banned_user(&self, user_id: UserId) -> Result<(), Error>;
+
+    /// Removes a banned user from the database, if found.
+    fn remove_banned_user(&self, user_id: UserId);
+
+    /// Loads all banned users from the database.
+    fn load_banned_users(&self) -> HashSet<UserId>;
+
+    /// Appends an entry to the audit log of admin actions.
+    fn store_audit_log_entry(&self, entry: &AuditLogEntry) -> Result<(), Error>;
+
+    /// Loads the audit log of admin actions, from oldest to newest.
+    fn load_audit_log(&self) -> Vec<AuditLogEntry>;
+
+    /// Stores a registration token that has been redeemed.
+    ///
+    /// Fails with [Error::AlreadyExists] if the token had already been redeemed.
+    fn store_redeemed_token(&self, token: &[u8]) -> Result<(), Error>;
+
+    /// Removes a redeemed token from the database, if found, so it can be redeemed again.
+    fn remove_redeemed_token(&self, token: &[u8]);
+
+    /// Stores the last known block into the database.
+    fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error>;
+
+    /// Loads the last known block from the database, if any. Fails if the database cannot be read.
+    fn load_last_known_block(&self) -> Result<Option<BlockHash>, Error>;
+
+    /// Checks whether there is any appointment in the database, either being watched or already triggered.
+    fn has_appointments(&self) -> bool;
+
+    /// Stores the [BlockSummary] of the block at a given height into the database, replacing the one at the same height, if any.
+    fn store_block_summary(&self, height: u32, summary: &BlockSummary) -> Result<(), Error>;
+
+    /// Removes the [BlockSummary] of the block at a given height from the database, if found.
+    fn remove_block_summary(&self, height: u32);
+
+    /// Prunes the [BlockSummary]s stored in the database. Summaries at or below `height` are removed, and so are the
+    /// transactions of the ones at or below `txdata_height`.
+    fn prune_block_summaries(&self, height: u32, txdata_height: u32);
+
+    /// Loads the [BlockSummary]s stored in the database, sorted from the most recent block backwards.
+    fn load_block_summaries(&self) -> Vec<BlockSummary>;
+
+    /// Stores the tower secret key into the database, either in plaintext or encrypted under the tower passphrase.
+    ///
+    /// When a new key is generated, old keys are not overwritten but are not retrievable from the API either.
+    fn store_tower_key(&self, key: &TowerKey) -> Result<(), Error>;
+
+    /// Loads the last known tower secret key from the database.
+    ///
+    /// Loads the key with higher id from the database. Old keys are not overwritten just in case a recovery is needed,
+    /// but they are not accessible from the API either. Fails if the database cannot be read.
+    fn load_tower_key(&self) -> Result<Option<TowerKey>, Error>;
+
+    /// Loads all the tower secret keys stored in the database, old ones included, identified by their id.
+    fn load_all_tower_keys(&self) -> HashMap<u32, TowerKey>;
+
+    /// Replaces a batch of tower secret keys and wallet secret keys, identified by their id, in a single database
+    /// transaction. Used to encrypt the keys, or to re-encrypt them under a new passphrase.
+    ///
+    /// If any of the queries fails, or any of the keys is not found, the transaction is rolled back and no key is
+    /// replaced.
+    fn update_tower_keys(
+        &mut self,
+        keys: &HashMap<u32, TowerKey>,
+        wallet_keys: &HashMap<u32, TowerKey>,
+    ) -> Result<(), Error>;
+
+    /// Stores the tower wallet secret key into the database, either in plaintext or encrypted under the tower
+    /// passphrase.
+    fn store_wallet_key(&self, key: &TowerKey) -> Result<(), Error>;
+
+    /// Loads the last known tower wallet secret key from the database.
+    ///
+    /// As for the tower keys, old wallet keys are not overwritten, so the funds they may hold can be recovered. Fails if
+    /// the database cannot be read.
+    fn load_wallet_key(&self) -> Result<Option<TowerKey>, Error>;
+
+    /// Loads all the wallet secret keys stored in the database, old ones included, identified by their id.
+    fn load_all_wallet_keys(&self) -> HashMap<u32, TowerKey>;
+}
+
+/// Component in charge of interacting with the underlying `SQLite` database.
 #[derive(Debug)]
 pub struct DBM {
     /// The underlying database connection.
@@ -83,19 +467,105 @@ impl DatabaseConnection for DBM {
 
 impl DBM {
     /// Creates a new [DBM] instance.
-    pub fn new(db_path: PathBuf) -> Result<Self, SqliteError> {
+    pub fn new(db_path: PathBuf) -> Result<Self, MigrationError> {
+        DBM::from_connection(Connection::open(db_path)?)
+    }
+
+    /// Creates a new [DBM] instance on top of a database encrypted under the given passphrase.
+    ///
+    /// Existing unencrypted databases are encrypted in place first. Only available if built with the `encryption`
+    /// feature, which bundles SQLCipher instead of SQLite.
+    pub fn new_encrypted(db_path: PathBuf, passphrase: &str) -> Result<Self, MigrationError> {
+        if !cfg!(feature = "encryption") {
+            return Err(MigrationError::Backend(
+                "Database encryption requires building teos with the encryption feature".to_owned(),
+            ));
+        }
+        if is_plaintext_database(&db_path) {
+            encrypt_database(&db_path, passphrase)?;
+        }
+
         let connection = Connection::open(db_path)?;
+        connection.pragma_update(None, "key", passphrase)?;
+        DBM::from_connection(connection)
+    }
+
+    /// Changes the passphrase the database is encrypted under, replacing the tower and wallet keys by the given ones
+    /// (re-encrypted under the new passphrase, see [reencrypt_tower_keys](crate::encryption::reencrypt_tower_keys))
+    /// alongside.
+    ///
+    /// The database is re-encrypted first. If the keys cannot be replaced afterwards, the database is re-encrypted back
+    /// under the current passphrase, so the database and the keys are always left under the same one.
+    ///
+    /// The database must have been opened with [DBM::new_encrypted].
+    pub fn change_passphrase(
+        &mut self,
+        passphrase: &str,
+        new_passphrase: &str,
+        keys: &HashMap<u32, TowerKey>,
+        wallet_keys: &HashMap<u32, TowerKey>,
+    ) -> Result<(), Error> {
+        self.rekey(new_passphrase)?;
+        if let Err(e) = self.update_tower_keys(keys, wallet_keys) {
+            if let Err(rekey_error) = self.rekey(passphrase) {
+                log::error!(
+                    "Couldn't re-encrypt the database back under the current passphrase. Error: {rekey_error:?}"
+                );
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Re-encrypts the database under the given passphrase.
+    fn rekey(&self, passphrase: &str) -> Result<(), Error> {
+        self.connection
+            .pragma_update(None, "rekey", passphrase)
+            .map_err(Error::Unknown)
+    }
+
+    /// Loads all the keys stored in a key table (either `keys` or `wallet_keys`), identified by their id.
+    fn load_all_keys(&self, table: &str) -> HashMap<u32, TowerKey> {
+        let mut stmt = self
+            .connection
+            .prepare(&format!("SELECT id, key, encrypted FROM {table}"))
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut keys = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let key: String = row.get(1).unwrap();
+            keys.insert(
+                row.get(0).unwrap(),
+                decode_tower_key(&key, row.get(2).unwrap()),
+            );
+        }
+
+        keys
+    }
+
+    /// Creates a new [DBM] instance from an open connection, migrating the database schema to the latest version.
+    fn from_connection(connection: Connection) -> Result<Self, MigrationError> {
         connection.execute("PRAGMA foreign_keys=1;", [])?;
         let mut dbm = Self { connection };
-        dbm.create_tables(Vec::from_iter(TABLES))?;
+
+        // Databases created before the schema was versioned may be missing some of the tables and columns of its first
+        // version, so they are brought up to it before migrating
+        if dbm.schema_version()? == 0 {
+            dbm.create_tables(Vec::from_iter(TABLES))?;
+            dbm.add_missing_columns(&ADDED_COLUMNS)?;
+        }
+        dbm.migrate(&MIGRATIONS)?;
 
         Ok(dbm)
     }
+}
 
-    /// Stores a user ([UserInfo]) into the database.
-    pub(crate) fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error> {
+impl Storage for DBM {
+    fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error> {
         let query =
-        "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry) VALUES (?1, ?2, ?3, ?4)";
+        "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry, tier) VALUES (?1, ?2, ?3, ?4, ?5)";
 
         match self.store_data(
             query,
@@ -104,6 +574,7 @@ impl DBM {
                 user_info.available_slots,
                 user_info.subscription_start,
                 user_info.subscription_expiry,
+                user_info.tier,
             ],
         ) {
             Ok(x) => {
@@ -117,16 +588,16 @@ impl DBM {
         }
     }
 
-    /// Updates an existing user ([UserInfo]) in the database.
-    pub(crate) fn update_user(&self, user_id: UserId, user_info: &UserInfo) {
+    fn update_user(&self, user_id: UserId, user_info: &UserInfo) {
         let query =
-        "UPDATE users SET available_slots=(?1), subscription_start=(?2), subscription_expiry=(?3) WHERE user_id=(?4)";
+        "UPDATE users SET available_slots=(?1), subscription_start=(?2), subscription_expiry=(?3), tier=(?4) WHERE user_id=(?5)";
         match self.update_data(
             query,
             params![
                 user_info.available_slots,
                 user_info.subscription_start,
                 user_info.subscription_expiry,
+                user_info.tier,
                 user_id.to_vec(),
             ],
         ) {
@@ -139,8 +610,33 @@ impl DBM {
         }
     }
 
-    /// Loads the associated appointments ([Appointment]) of a given user ([UserInfo]).
-    pub(crate) fn load_user_appointments(&self, user_id: UserId) -> HashMap<UUID, u32> {
+    fn load_user(&self, user_id: UserId) -> Result<Option<UserInfo>, Error> {
+        let key = user_id.to_vec();
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT user_id, available_slots, subscription_start, subscription_expiry, tier
+                    FROM users WHERE user_id=(?)",
+            )
+            .map_err(Error::Unknown)?;
+        stmt.query_row([&key], |row| {
+            let slots = row.get(1).unwrap();
+            let start = row.get(2).unwrap();
+            let expiry = row.get(3).unwrap();
+            let tier: String = row.get(4).unwrap();
+            Ok(UserInfo::with_appointments(
+                slots,
+                start,
+                expiry,
+                self.load_user_appointments(user_id, ENCRYPTED_BLOB_MAX_SIZE),
+            )
+            .with_tier(tier))
+        })
+        .optional()
+        .map_err(Error::Unknown)
+    }
+
+    fn load_user_appointments(&self, user_id: UserId, slot_size: usize) -> HashMap<UUID, u32> {
         let mut stmt = self
             .connection
             .prepare("SELECT UUID, encrypted_blob FROM appointments WHERE user_id=(?)")
@@ -153,21 +649,17 @@ impl DBM {
             let uuid = UUID::from_slice(&raw_uuid[0..20]).unwrap();
             let e_blob: Vec<u8> = inner_row.get(1).unwrap();
 
-            appointments.insert(
-                uuid,
-                compute_appointment_slots(e_blob.len(), ENCRYPTED_BLOB_MAX_SIZE),
-            );
+            appointments.insert(uuid, compute_appointment_slots(e_blob.len(), slot_size));
         }
 
         appointments
     }
 
-    /// Loads all users from the database.
-    pub(crate) fn load_all_users(&self) -> HashMap<UserId, UserInfo> {
+    fn load_all_users(&self) -> HashMap<UserId, UserInfo> {
         let mut users = HashMap::new();
         let mut stmt = self
             .connection
-            .prepare("SELECT user_id, available_slots, subscription_start, subscription_expiry FROM users")
+            .prepare("SELECT user_id, available_slots, subscription_start, subscription_expiry, tier FROM users")
             .unwrap();
         let mut rows = stmt.query([]).unwrap();
 
@@ -177,6 +669,7 @@ impl DBM {
             let slots = row.get(1).unwrap();
             let start = row.get(2).unwrap();
             let expiry = row.get(3).unwrap();
+            let tier: String = row.get(4).unwrap();
 
             users.insert(
                 user_id,
@@ -184,16 +677,16 @@ impl DBM {
                     slots,
                     start,
                     expiry,
-                    self.load_user_appointments(user_id),
-                ),
+                    self.load_user_appointments(user_id, ENCRYPTED_BLOB_MAX_SIZE),
+                )
+                .with_tier(tier),
             );
         }
 
         users
     }
 
-    /// Removes some users from the database in batch.
-    pub(crate) fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize {
+    fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize {
         let limit = self.connection.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER) as usize;
         let tx = self.connection.transaction().unwrap();
         let iter = users
@@ -219,8 +712,7 @@ impl DBM {
         (users.len() as f64 / limit as f64).ceil() as usize
     }
 
-    /// Stores an [Appointment] into the database.
-    pub(crate) fn store_appointment(
+    fn store_appointment(
         &self,
         uuid: UUID,
         appointment: &ExtendedAppointment,
@@ -249,8 +741,7 @@ impl DBM {
         }
     }
 
-    /// Updates an existing [Appointment] in the database.
-    pub(crate) fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment) {
+    fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment) {
         // DISCUSS: Check what fields we'd like to make updatable. e_blob and signature are the obvious, to_self_delay and start_block may not be necessary (or even risky)
         let query =
             "UPDATE appointments SET encrypted_blob=(?1), to_self_delay=(?2), user_signature=(?3), start_block=(?4) WHERE UUID=(?5)";
@@ -273,8 +764,7 @@ impl DBM {
         }
     }
 
-    /// Loads an [Appointment] from the database.
-    pub(crate) fn load_appointment(&self, uuid: UUID) -> Option<ExtendedAppointment> {
+    fn load_appointment(&self, uuid: UUID) -> Option<ExtendedAppointment> {
         let key = uuid.to_vec();
         let mut stmt = self
             .connection
@@ -305,12 +795,7 @@ impl DBM {
         .ok()
     }
 
-    /// Loads appointments from the database. If a locator is given, this method loads only the appointments
-    /// matching this locator. If no locator is given, all the appointments in the database would be returned.
-    pub(crate) fn load_appointments(
-        &self,
-        locator: Option<Locator>,
-    ) -> HashMap<UUID, ExtendedAppointment> {
+    fn load_appointments(&self, locator: Option<Locator>) -> HashMap<UUID, ExtendedAppointment> {
         let mut appointments = HashMap::new();
 
         let mut sql =
@@ -352,8 +837,7 @@ impl DBM {
         appointments
     }
 
-    /// Removes an [Appointment] from the database.
-    pub(crate) fn remove_appointment(&self, uuid: UUID) {
+    fn remove_appointment(&self, uuid: UUID) {
         let query = "DELETE FROM appointments WHERE UUID=(?)";
         match self.remove_data(query, params![uuid.to_vec()]) {
             Ok(_) => {
@@ -365,9 +849,7 @@ impl DBM {
         }
     }
 
-    /// Removes some appointments from the database in batch and updates the associated users giving back
-    /// the freed appointment slots
-    pub(crate) fn batch_remove_appointments(
+    fn batch_remove_appointments(
         &mut self,
         appointments: &HashSet<UUID>,
         updated_users: &HashMap<UserId, UserInfo>,
@@ -405,8 +887,77 @@ impl DBM {
         (appointments.len() as f64 / limit as f64).ceil() as usize
     }
 
-    /// Loads the locator associated to a given UUID
-    pub(crate) fn load_locator(&self, uuid: UUID) -> Option<Locator> {
+    fn batch_store_appointments(
+        &mut self,
+        appointments: &[(UUID, &ExtendedAppointment)],
+        breaches: &[(UUID, &Breach)],
+        user_id: UserId,
+        user_info: &UserInfo,
+    ) -> Result<(), Error> {
+        let tx = self.connection.transaction().map_err(Error::Unknown)?;
+
+        for (uuid, appointment) in appointments {
+            let updated = tx
+                .execute(
+                    "UPDATE appointments SET encrypted_blob=(?1), to_self_delay=(?2), user_signature=(?3), start_block=(?4) WHERE UUID=(?5)",
+                    params![
+                        appointment.encrypted_blob(),
+                        appointment.to_self_delay(),
+                        appointment.user_signature,
+                        appointment.start_block,
+                        uuid.to_vec(),
+                    ],
+                )
+                .map_err(Error::Unknown)?;
+
+            if updated == 0 {
+                tx.execute(
+                    "INSERT INTO appointments (UUID, locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        uuid.to_vec(),
+                        appointment.locator().to_vec(),
+                        appointment.encrypted_blob(),
+                        appointment.to_self_delay(),
+                        appointment.user_signature,
+                        appointment.start_block,
+                        appointment.user_id.to_vec(),
+                    ],
+                )
+                .map_err(Error::Unknown)?;
+            }
+        }
+
+        for (uuid, breach) in breaches {
+            tx.execute(
+                "INSERT INTO queued_breaches (UUID, dispute_tx, penalty_tx) VALUES (?1, ?2, ?3)",
+                params![
+                    uuid.to_vec(),
+                    consensus::serialize(&breach.dispute_tx),
+                    consensus::serialize(&breach.penalty_tx),
+                ],
+            )
+            .map_err(Error::Unknown)?;
+        }
+
+        tx.execute(
+            "UPDATE users SET available_slots=(?1) WHERE user_id=(?2)",
+            params![user_info.available_slots, user_id.to_vec()],
+        )
+        .map_err(Error::Unknown)?;
+
+        match tx.commit() {
+            Ok(_) => {
+                log::debug!("Appointments successfully stored: {}", appointments.len());
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("Couldn't store appointments. Error: {e:?}");
+                Err(Error::Unknown(e))
+            }
+        }
+    }
+
+    fn load_locator(&self, uuid: UUID) -> Option<Locator> {
         let mut stmt = self
             .connection
             .prepare("SELECT locator FROM appointments WHERE UUID=(?)")
@@ -419,12 +970,7 @@ impl DBM {
         .ok()
     }
 
-    /// Stores a [TransactionTracker] into the database.
-    pub(crate) fn store_tracker(
-        &self,
-        uuid: UUID,
-        tracker: &TransactionTracker,
-    ) -> Result<(), Error> {
+    fn store_tracker(&self, uuid: UUID, tracker: &TransactionTracker) -> Result<(), Error> {
         let (height, confirmed) = tracker.status.to_db_data().ok_or(Error::MissingField)?;
 
         let query =
@@ -450,8 +996,7 @@ impl DBM {
         }
     }
 
-    /// Loads a [TransactionTracker] from the database.
-    pub(crate) fn load_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
+    fn load_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
         let key = uuid.to_vec();
         let mut stmt = self
             .connection.prepare(
@@ -481,12 +1026,7 @@ impl DBM {
         .ok()
     }
 
-    /// Loads trackers from the database. If a locator is given, this method loads only the trackers
-    /// matching this locator. If no locator is given, all the trackers in the database would be returned.
-    pub(crate) fn load_trackers(
-        &self,
-        locator: Option<Locator>,
-    ) -> HashMap<UUID, TransactionTracker> {
+    fn load_trackers(&self, locator: Option<Locator>) -> HashMap<UUID, TransactionTracker> {
         let mut trackers = HashMap::new();
 
         let mut sql = "SELECT t.UUID, t.dispute_tx, t.penalty_tx, t.height, t.confirmed, a.user_id
@@ -530,51 +1070,472 @@ impl DBM {
         trackers
     }
 
-    /// Stores the last known block into the database.
-    pub(crate) fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error> {
-        let query = "INSERT OR REPLACE INTO last_known_block (id, block_hash) VALUES (0, ?)";
-        self.store_data(query, params![block_hash.to_vec()])
+    fn remove_tracker(&self, uuid: UUID) {
+        let query = "DELETE FROM trackers WHERE UUID=(?)";
+        match self.remove_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Tracker successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Tracker not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn store_fee_bump(&self, uuid: UUID, fee_bump: &FeeBump) -> Result<(), Error> {
+        let query =
+            "INSERT INTO fee_bumps (UUID, child_tx, fee, fee_rate, height) VALUES (?1, ?2, ?3, ?4, ?5)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                consensus::serialize(&fee_bump.child_tx),
+                fee_bump.fee,
+                fee_bump.fee_rate,
+                fee_bump.height,
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Fee bump successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store fee bump: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
     }
 
-    /// Loads the last known block from the database.
-    pub fn load_last_known_block(&self) -> Option<BlockHash> {
+    fn load_fee_bumps(&self, uuid: UUID) -> Vec<FeeBump> {
         let mut stmt = self
             .connection
-            .prepare("SELECT block_hash FROM last_known_block WHERE id=0")
+            .prepare(
+                "SELECT child_tx, fee, fee_rate, height FROM fee_bumps WHERE UUID=(?) ORDER BY id",
+            )
             .unwrap();
+        let mut rows = stmt.query([uuid.to_vec()]).unwrap();
 
-        stmt.query_row([], |row| {
-            let raw_hash: Vec<u8> = row.get(0).unwrap();
-            Ok(BlockHash::from_slice(&raw_hash).unwrap())
-        })
-        .ok()
-    }
+        let mut fee_bumps = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_child_tx: Vec<u8> = row.get(0).unwrap();
+            fee_bumps.push(FeeBump {
+                child_tx: consensus::deserialize(&raw_child_tx).unwrap(),
+                fee: row.get(1).unwrap(),
+                fee_rate: row.get(2).unwrap(),
+                height: row.get(3).unwrap(),
+            });
+        }
 
-    /// Stores the tower secret key into the database.
-    ///
-    /// When a new key is generated, old keys are not overwritten but are not retrievable from the API either.
-    pub fn store_tower_key(&self, sk: &SecretKey) -> Result<(), Error> {
-        let query = "INSERT INTO keys (key) VALUES (?)";
-        self.store_data(query, params![sk.display_secret().to_string()])
+        fee_bumps
     }
 
-    /// Loads the last known tower secret key from the database.
-    ///
-    /// Loads the key with higher id from the database. Old keys are not overwritten just in case a recovery is needed,
-    /// but they are not accessible from the API either.
-    pub fn load_tower_key(&self) -> Option<SecretKey> {
+    fn store_queued_breach(&self, uuid: UUID, breach: &Breach) -> Result<(), Error> {
+        let query =
+            "INSERT INTO queued_breaches (UUID, dispute_tx, penalty_tx) VALUES (?1, ?2, ?3)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                consensus::serialize(&breach.dispute_tx),
+                consensus::serialize(&breach.penalty_tx),
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Queued breach successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store queued breach: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn remove_queued_breach(&self, uuid: UUID) {
+        let query = "DELETE FROM queued_breaches WHERE UUID=(?)";
+        match self.remove_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Queued breach successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Queued breach not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn load_queued_breaches(&self) -> HashMap<UUID, Breach> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT UUID, dispute_tx, penalty_tx FROM queued_breaches")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut breaches = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            let raw_dispute_tx: Vec<u8> = row.get(1).unwrap();
+            let raw_penalty_tx: Vec<u8> = row.get(2).unwrap();
+            breaches.insert(
+                UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                Breach::new(
+                    consensus::deserialize(&raw_dispute_tx).unwrap(),
+                    consensus::deserialize(&raw_penalty_tx).unwrap(),
+                ),
+            );
+        }
+
+        breaches
+    }
+
+    fn store_mempool_trigger(&self, uuid: UUID, dispute_txid: &Txid) -> Result<(), Error> {
+        let query = "INSERT INTO mempool_triggers (UUID, dispute_txid) VALUES (?1, ?2)";
+        match self.store_data(query, params![uuid.to_vec(), dispute_txid.to_vec()]) {
+            Ok(x) => {
+                log::debug!("Mempool trigger successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store mempool trigger: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn remove_mempool_trigger(&self, uuid: UUID) {
+        let query = "DELETE FROM mempool_triggers WHERE UUID=(?)";
+        match self.remove_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Mempool trigger successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Mempool trigger not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn load_mempool_triggers(&self) -> HashMap<UUID, Txid> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT UUID, dispute_txid FROM mempool_triggers")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut triggers = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            let raw_txid: Vec<u8> = row.get(1).unwrap();
+            triggers.insert(
+                UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                Txid::from_slice(&raw_txid).unwrap(),
+            );
+        }
+
+        triggers
+    }
+
+    fn store_pending_registration(
+        &self,
+        user_id: UserId,
+        registration: &PendingRegistration,
+    ) -> Result<(), Error> {
+        let query = "INSERT INTO pending_registrations (user_id, invoice, payment_hash, amount_msat, expires_at, tier, operation) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+        let invoice = &registration.invoice;
+        self.store_data(
+            query,
+            params![
+                user_id.to_vec(),
+                invoice.bolt11,
+                invoice.payment_hash.to_vec(),
+                invoice.amount_msat,
+                invoice.expires_at,
+                registration.tier,
+                registration.operation as u8,
+            ],
+        )
+    }
+
+    fn remove_pending_registration(&self, payment_hash: &[u8; 32]) {
+        self.connection
+            .execute(
+                "DELETE FROM pending_registrations WHERE payment_hash=(?)",
+                [payment_hash.to_vec()],
+            )
+            .unwrap();
+    }
+
+    fn remove_pending_registrations(&self, user_id: UserId) {
+        self.connection
+            .execute(
+                "DELETE FROM pending_registrations WHERE user_id=(?)",
+                [user_id.to_vec()],
+            )
+            .unwrap();
+    }
+
+    fn load_pending_registrations(&self) -> HashMap<UserId, Vec<PendingRegistration>> {
         let mut stmt = self
             .connection
             .prepare(
-                "SELECT key FROM keys WHERE id = (SELECT seq FROM sqlite_sequence WHERE name=(?))",
+                "SELECT user_id, invoice, payment_hash, amount_msat, expires_at, tier, operation FROM pending_registrations ORDER BY expires_at",
             )
             .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut pending_registrations: HashMap<UserId, Vec<PendingRegistration>> = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_userid: Vec<u8> = row.get(0).unwrap();
+            let raw_payment_hash: Vec<u8> = row.get(2).unwrap();
+            pending_registrations
+                .entry(UserId::from_slice(&raw_userid).unwrap())
+                .or_default()
+                .push(PendingRegistration {
+                    tier: row.get(5).unwrap(),
+                    operation: SubscriptionOperation::from(row.get::<_, u8>(6).unwrap()),
+                    invoice: Invoice {
+                        bolt11: row.get(1).unwrap(),
+                        payment_hash: raw_payment_hash.try_into().unwrap(),
+                        amount_msat: row.get(3).unwrap(),
+                        expires_at: row.get(4).unwrap(),
+                    },
+                });
+        }
+
+        pending_registrations
+    }
+
+    fn store_banned_user(&self, user_id: UserId) -> Result<(), Error> {
+        let query = "INSERT INTO banned_users (user_id) VALUES (?)";
+        self.store_data(query, params![user_id.to_vec()])
+    }
+
+    fn remove_banned_user(&self, user_id: UserId) {
+        self.connection
+            .execute(
+                "DELETE FROM banned_users WHERE user_id=(?)",
+                [user_id.to_vec()],
+            )
+            .unwrap();
+    }
+
+    fn load_banned_users(&self) -> HashSet<UserId> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT user_id FROM banned_users")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut banned_users = HashSet::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_userid: Vec<u8> = row.get(0).unwrap();
+            banned_users.insert(UserId::from_slice(&raw_userid).unwrap());
+        }
+
+        banned_users
+    }
+
+    fn store_audit_log_entry(&self, entry: &AuditLogEntry) -> Result<(), Error> {
+        let query =
+            "INSERT INTO audit_log (timestamp, user_id, action, details) VALUES (?1, ?2, ?3, ?4)";
+        self.store_data(
+            query,
+            params![
+                entry.timestamp,
+                entry.user_id.to_vec(),
+                entry.action,
+                entry.details,
+            ],
+        )
+    }
+
+    fn load_audit_log(&self) -> Vec<AuditLogEntry> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT timestamp, user_id, action, details FROM audit_log ORDER BY id")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut audit_log = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_userid: Vec<u8> = row.get(1).unwrap();
+            audit_log.push(AuditLogEntry {
+                timestamp: row.get(0).unwrap(),
+                user_id: UserId::from_slice(&raw_userid).unwrap(),
+                action: row.get(2).unwrap(),
+                details: row.get(3).unwrap(),
+            });
+        }
+
+        audit_log
+    }
+
+    fn store_redeemed_token(&self, token: &[u8]) -> Result<(), Error> {
+        let query = "INSERT INTO redeemed_tokens (token) VALUES (?)";
+        self.store_data(query, params![token])
+    }
+
+    fn remove_redeemed_token(&self, token: &[u8]) {
+        self.connection
+            .execute("DELETE FROM redeemed_tokens WHERE token=(?)", [token])
+            .unwrap();
+    }
+
+    fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error> {
+        let query = "INSERT OR REPLACE INTO last_known_block (id, block_hash) VALUES (0, ?)";
+        self.store_data(query, params![block_hash.to_vec()])
+    }
+
+    fn load_last_known_block(&self) -> Result<Option<BlockHash>, Error> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT block_hash FROM last_known_block WHERE id=0")
+            .map_err(Error::Unknown)?;
+
+        stmt.query_row([], |row| {
+            let raw_hash: Vec<u8> = row.get(0).unwrap();
+            Ok(BlockHash::from_slice(&raw_hash).unwrap())
+        })
+        .optional()
+        .map_err(Error::Unknown)
+    }
+
+    fn has_appointments(&self) -> bool {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT EXISTS(SELECT 1 FROM appointments)")
+            .unwrap();
+        stmt.query_row([], |row| row.get(0)).unwrap()
+    }
+
+    fn store_block_summary(&self, height: u32, summary: &BlockSummary) -> Result<(), Error> {
+        let query =
+            "INSERT OR REPLACE INTO block_summaries (height, header, txids, txdata) VALUES (?1, ?2, ?3, ?4)";
+        self.store_data(
+            query,
+            params![
+                height,
+                consensus::serialize(&summary.header),
+                summary
+                    .txids
+                    .iter()
+                    .flat_map(|txid| txid.into_inner())
+                    .collect::<Vec<u8>>(),
+                summary.txdata.as_ref().map(consensus::serialize),
+            ],
+        )
+    }
+
+    fn remove_block_summary(&self, height: u32) {
+        self.connection
+            .execute("DELETE FROM block_summaries WHERE height=(?)", [height])
+            .unwrap();
+    }
+
+    fn prune_block_summaries(&self, height: u32, txdata_height: u32) {
+        self.connection
+            .execute("DELETE FROM block_summaries WHERE height<=(?)", [height])
+            .unwrap();
+        self.connection
+            .execute(
+                "UPDATE block_summaries SET txdata=NULL WHERE height<=(?) AND txdata IS NOT NULL",
+                [txdata_height],
+            )
+            .unwrap();
+    }
+
+    fn load_block_summaries(&self) -> Vec<BlockSummary> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT header, txids, txdata FROM block_summaries ORDER BY height DESC")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut summaries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_header: Vec<u8> = row.get(0).unwrap();
+            let raw_txids: Vec<u8> = row.get(1).unwrap();
+            let raw_txdata: Option<Vec<u8>> = row.get(2).unwrap();
+            summaries.push(BlockSummary {
+                header: consensus::deserialize(&raw_header).unwrap(),
+                txids: raw_txids
+                    .chunks(32)
+                    .map(|txid| Txid::from_slice(txid).unwrap())
+                    .collect(),
+                txdata: raw_txdata.map(|txdata| consensus::deserialize(&txdata).unwrap()),
+            });
+        }
+
+        summaries
+    }
+
+    fn store_tower_key(&self, key: &TowerKey) -> Result<(), Error> {
+        let (key, encrypted) = encode_tower_key(key);
+        let query = "INSERT INTO keys (key, encrypted) VALUES (?1, ?2)";
+        self.store_data(query, params![key, encrypted])
+    }
+
+    fn load_tower_key(&self) -> Result<Option<TowerKey>, Error> {
+        let mut stmt = self.connection.prepare(
+            "SELECT key, encrypted FROM keys WHERE id = (SELECT seq FROM sqlite_sequence WHERE name=(?))",
+        ).map_err(Error::Unknown)?;
 
         stmt.query_row(["keys"], |row| {
-            let sk: String = row.get(0).unwrap();
-            Ok(SecretKey::from_str(&sk).unwrap())
+            let key: String = row.get(0).unwrap();
+            Ok(decode_tower_key(&key, row.get(1).unwrap()))
         })
-        .ok()
+        .optional()
+        .map_err(Error::Unknown)
+    }
+
+    fn load_all_tower_keys(&self) -> HashMap<u32, TowerKey> {
+        self.load_all_keys("keys")
+    }
+
+    fn update_tower_keys(
+        &mut self,
+        keys: &HashMap<u32, TowerKey>,
+        wallet_keys: &HashMap<u32, TowerKey>,
+    ) -> Result<(), Error> {
+        let tx = self.connection.transaction().unwrap();
+
+        for (table, keys) in [("keys", keys), ("wallet_keys", wallet_keys)] {
+            for (id, key) in keys {
+                let (key, encrypted) = encode_tower_key(key);
+                let updated = tx
+                    .execute(
+                        &format!("UPDATE {table} SET key=(?1), encrypted=(?2) WHERE id=(?3)"),
+                        params![key, encrypted, id],
+                    )
+                    .map_err(Error::Unknown)?;
+                if updated == 0 {
+                    return Err(Error::NotFound);
+                }
+            }
+        }
+
+        tx.commit().map_err(Error::Unknown)
+    }
+
+    fn store_wallet_key(&self, key: &TowerKey) -> Result<(), Error> {
+        let (key, encrypted) = encode_tower_key(key);
+        let query = "INSERT INTO wallet_keys (key, encrypted) VALUES (?1, ?2)";
+        self.store_data(query, params![key, encrypted])
+    }
+
+    fn load_wallet_key(&self) -> Result<Option<TowerKey>, Error> {
+        let mut stmt = self.connection.prepare(
+            "SELECT key, encrypted FROM wallet_keys WHERE id = (SELECT seq FROM sqlite_sequence WHERE name=(?))",
+        ).map_err(Error::Unknown)?;
+
+        stmt.query_row(["wallet_keys"], |row| {
+            let key: String = row.get(0).unwrap();
+            Ok(decode_tower_key(&key, row.get(1).unwrap()))
+        })
+        .optional()
+        .map_err(Error::Unknown)
+    }
+
+    fn load_all_wallet_keys(&self) -> HashMap<u32, TowerKey> {
+        self.load_all_keys("wallet_keys")
     }
 }
 
@@ -583,49 +1544,130 @@ mod tests {
     use super::*;
     use std::iter::FromIterator;
 
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
     use teos_common::cryptography::{get_random_bytes, get_random_keypair};
     use teos_common::test_utils::get_random_user_id;
 
+    use crate::postgres_dbm::PostgresDBM;
     use crate::test_utils::{
         generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid,
-        get_random_tracker, get_random_tx, AVAILABLE_SLOTS, SUBSCRIPTION_EXPIRY,
-        SUBSCRIPTION_START,
+        get_random_breach, get_random_invoice, get_random_tracker, get_random_tx, AVAILABLE_SLOTS,
+        SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
     };
 
     impl DBM {
-        pub(crate) fn in_memory() -> Result<Self, SqliteError> {
-            let connection = Connection::open_in_memory()?;
-            connection.execute("PRAGMA foreign_keys=1;", [])?;
-            let mut dbm = Self { connection };
-            dbm.create_tables(Vec::from_iter(TABLES))?;
+        pub(crate) fn in_memory() -> Result<Self, MigrationError> {
+            DBM::from_connection(Connection::open_in_memory()?)
+        }
+    }
+
+    /// Test helpers every storage backend has to provide.
+    trait TestStorage: Storage {
+        /// Sets the maximum number of entries a single query can deal with, so batch operations are split into chunks.
+        fn set_batch_size(&mut self, batch_size: usize);
+
+        /// Drops a table (alongside the ones referencing it), so queries using it fail.
+        fn drop_table(&self, table: &str);
+    }
 
-            Ok(dbm)
+    impl TestStorage for DBM {
+        fn set_batch_size(&mut self, batch_size: usize) {
+            self.connection
+                .set_limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER, batch_size as i32);
         }
 
-        pub(crate) fn load_user(&self, user_id: UserId) -> Option<UserInfo> {
-            let key = user_id.to_vec();
-            let mut stmt = self
-                .connection
-                .prepare(
-                    "SELECT user_id, available_slots, subscription_start, subscription_expiry
-                        FROM users WHERE user_id=(?)",
-                )
+        fn drop_table(&self, table: &str) {
+            self.connection
+                .execute_batch(&format!("PRAGMA foreign_keys=OFF; DROP TABLE {table}"))
                 .unwrap();
-            stmt.query_row([&key], |row| {
-                let slots = row.get(1).unwrap();
-                let start = row.get(2).unwrap();
-                let expiry = row.get(3).unwrap();
-                Ok(UserInfo::with_appointments(
-                    slots,
-                    start,
-                    expiry,
-                    self.load_user_appointments(user_id),
-                ))
-            })
-            .ok()
         }
     }
 
+    impl TestStorage for PostgresDBM {
+        fn set_batch_size(&mut self, batch_size: usize) {
+            self.set_max_batch_size(batch_size);
+        }
+
+        fn drop_table(&self, table: &str) {
+            self.drop_table_for_tests(table);
+        }
+    }
+
+    /// Runs the given tests against every storage backend.
+    ///
+    /// `PostgreSQL` tests need a running database, whose connection string is read from `TEOS_TEST_POSTGRES_URL`.
+    /// They are skipped if it is not set.
+    macro_rules! storage_tests {
+        ($($test:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[test]
+                    fn $test() {
+                        super::$test(super::DBM::in_memory().unwrap());
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[test]
+                    #[ignore = "needs a Postgres database (set TEOS_TEST_POSTGRES_URL)"]
+                    fn $test() {
+                        super::$test(super::PostgresDBM::for_tests());
+                    }
+                )*
+            }
+        };
+    }
+
+    storage_tests!(
+        test_store_load_user,
+        test_store_load_user_with_appointments,
+        test_load_nonexistent_user,
+        test_update_user,
+        test_load_all_users,
+        test_batch_remove_users,
+        test_batch_remove_nonexistent_users,
+        test_store_load_appointment,
+        test_store_appointment_missing_user,
+        test_load_nonexistent_appointment,
+        test_update_appointment,
+        test_load_all_appointments,
+        test_load_appointments_with_locator,
+        test_batch_remove_appointments,
+        test_batch_store_appointments,
+        test_batch_remove_appointments_cascade,
+        test_batch_remove_nonexistent_appointments,
+        test_load_locator,
+        test_load_nonexistent_locator,
+        test_store_load_tracker,
+        test_store_duplicate_tracker,
+        test_store_tracker_missing_appointment,
+        test_load_nonexistent_tracker,
+        test_remove_tracker,
+        test_load_all_trackers,
+        test_load_trackers_with_locator,
+        test_store_load_last_known_block,
+        test_loads_fail_on_broken_database,
+        test_store_load_nonexistent_last_known_block,
+        test_has_appointments,
+        test_store_load_pending_registrations,
+        test_store_load_banned_users,
+        test_store_load_audit_log,
+        test_store_remove_redeemed_token,
+        test_store_load_block_summaries,
+        test_prune_block_summaries,
+        test_store_load_tower_key,
+        test_update_tower_keys,
+        test_store_load_wallet_key,
+        test_store_load_fee_bumps,
+        test_store_remove_queued_breaches,
+        test_store_remove_mempool_triggers,
+    );
+
     #[test]
     fn test_create_tables() {
         let connection = Connection::open_in_memory().unwrap();
@@ -633,71 +1675,371 @@ mod tests {
         dbm.create_tables(Vec::from_iter(TABLES)).unwrap();
     }
 
-    #[test]
-    fn test_store_load_user() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_load_user(dbm: impl TestStorage) {
         let user_id = get_random_user_id();
         let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
 
         assert!(matches!(dbm.store_user(user_id, &user), Ok { .. }));
-        assert_eq!(dbm.load_user(user_id).unwrap(), user);
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+
+        // User info should be updatable but only via the update_user method
+        user = UserInfo::new(AVAILABLE_SLOTS * 2, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        assert!(matches!(
+            dbm.store_user(user_id, &user),
+            Err(Error::AlreadyExists)
+        ));
+    }
+
+    fn test_store_load_user_with_appointments(dbm: impl TestStorage) {
+        let user_id = get_random_user_id();
+        let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+
+        dbm.store_user(user_id, &user).unwrap();
+
+        // Add some appointments to the user
+        for _ in 0..10 {
+            let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+            dbm.store_appointment(uuid, &appointment).unwrap();
+            user.appointments.insert(uuid, 1);
+        }
+
+        // Check both loading the whole user info or only the associated appointments
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+        assert_eq!(
+            dbm.load_user_appointments(user_id, ENCRYPTED_BLOB_MAX_SIZE),
+            user.appointments
+        );
+
+        // The slots each appointment takes depend on the given slot size
+        assert!(dbm
+            .load_user_appointments(user_id, 1)
+            .values()
+            .all(|slots| *slots > 1));
+    }
+
+    fn test_load_nonexistent_user(dbm: impl TestStorage) {
+        let user_id = get_random_user_id();
+        assert!(dbm.load_user(user_id).unwrap().is_none());
+    }
+
+    fn test_update_user(dbm: impl TestStorage) {
+        let user_id = get_random_user_id();
+        let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+
+        dbm.store_user(user_id, &user).unwrap();
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+
+        user.available_slots *= 2;
+        dbm.update_user(user_id, &user);
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+
+        user.tier = "premium".to_owned();
+        dbm.update_user(user_id, &user);
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+    }
+
+    #[test]
+    fn test_add_missing_columns() {
+        // Databases created before some columns existed get them added
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute(
+                "CREATE TABLE users (
+                    user_id INT PRIMARY KEY,
+                    available_slots INT NOT NULL,
+                    subscription_start INT NOT NULL,
+                    subscription_expiry INT NOT NULL
+                )",
+                [],
+            )
+            .unwrap();
+        let user_id = get_random_user_id();
+        connection
+            .execute(
+                "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry) VALUES (?1, ?2, ?3, ?4)",
+                params![user_id.to_vec(), AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY],
+            )
+            .unwrap();
+
+        let mut dbm = DBM { connection };
+        dbm.create_tables(Vec::from_iter(TABLES)).unwrap();
+        dbm.add_missing_columns(&ADDED_COLUMNS).unwrap();
+
+        // Existing users are given the default value
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().unwrap(),
+            UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY)
+        );
+
+        // Adding the columns again is a no-op
+        dbm.add_missing_columns(&ADDED_COLUMNS).unwrap();
+    }
+
+    #[test]
+    fn test_upgrade_unversioned_database() {
+        // Databases created before the schema was versioned (and before users and pending registrations had a tier)
+        // are migrated keeping their data
+        let dir = TempDir::new("teos").unwrap();
+        let db_path = dir.path().join("teos_db.sql3");
+
+        let connection = Connection::open(&db_path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE users (
+                    user_id INT PRIMARY KEY,
+                    available_slots INT NOT NULL,
+                    subscription_start INT NOT NULL,
+                    subscription_expiry INT NOT NULL
+                );
+                CREATE TABLE appointments (
+                    UUID INT PRIMARY KEY,
+                    locator INT NOT NULL,
+                    encrypted_blob BLOB NOT NULL,
+                    to_self_delay INT NOT NULL,
+                    user_signature BLOB NOT NULL,
+                    start_block INT NOT NULL,
+                    user_id INT NOT NULL,
+                    FOREIGN KEY(user_id)
+                        REFERENCES users(user_id)
+                        ON DELETE CASCADE
+                );
+                CREATE TABLE trackers (
+                    UUID INT PRIMARY KEY,
+                    dispute_tx BLOB NOT NULL,
+                    penalty_tx BLOB NOT NULL,
+                    height INT NOT NULL,
+                    confirmed BOOL NOT NULL,
+                    FOREIGN KEY(UUID)
+                        REFERENCES appointments(UUID)
+                        ON DELETE CASCADE
+                );
+                CREATE TABLE last_known_block (
+                    id INT PRIMARY KEY,
+                    block_hash INT NOT NULL
+                );
+                CREATE TABLE keys (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    key INT NOT NULL
+                );
+                CREATE TABLE pending_registrations (
+                    user_id INT PRIMARY KEY,
+                    invoice TEXT NOT NULL,
+                    payment_hash BLOB NOT NULL,
+                    amount_msat INT NOT NULL,
+                    expires_at INT NOT NULL
+                );",
+            )
+            .unwrap();
+        let user_id = get_random_user_id();
+        connection
+            .execute(
+                "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    user_id.to_vec(),
+                    AVAILABLE_SLOTS,
+                    SUBSCRIPTION_START,
+                    SUBSCRIPTION_EXPIRY
+                ],
+            )
+            .unwrap();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        connection
+            .execute(
+                "INSERT INTO appointments (UUID, locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    uuid.to_vec(),
+                    appointment.locator().to_vec(),
+                    appointment.encrypted_blob(),
+                    appointment.to_self_delay(),
+                    appointment.user_signature,
+                    appointment.start_block,
+                    appointment.user_id.to_vec(),
+                ],
+            )
+            .unwrap();
+        let (tower_sk, _) = get_random_keypair();
+        connection
+            .execute(
+                "INSERT INTO keys (key) VALUES (?)",
+                params![tower_sk.display_secret().to_string()],
+            )
+            .unwrap();
+        let pending_user_id = get_random_user_id();
+        let invoice = get_random_invoice();
+        connection
+            .execute(
+                "INSERT INTO pending_registrations (user_id, invoice, payment_hash, amount_msat, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    pending_user_id.to_vec(),
+                    invoice.bolt11,
+                    invoice.payment_hash.to_vec(),
+                    invoice.amount_msat,
+                    invoice.expires_at,
+                ],
+            )
+            .unwrap();
+        drop(connection);
+
+        let dbm = DBM::new(db_path.clone()).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().unwrap(),
+            UserInfo::with_appointments(
+                AVAILABLE_SLOTS,
+                SUBSCRIPTION_START,
+                SUBSCRIPTION_EXPIRY,
+                HashMap::from_iter([(uuid, 1)])
+            )
+        );
+        assert_eq!(dbm.load_appointment(uuid).unwrap(), appointment);
+        assert_eq!(
+            dbm.load_tower_key().unwrap().unwrap(),
+            TowerKey::Plaintext(tower_sk)
+        );
+        assert_eq!(
+            dbm.load_pending_registrations(),
+            HashMap::from_iter([(
+                pending_user_id,
+                vec![PendingRegistration {
+                    tier: String::new(),
+                    operation: SubscriptionOperation::Register,
+                    invoice,
+                }]
+            )])
+        );
+        drop(dbm);
 
-        // User info should be updatable but only via the update_user method
-        user = UserInfo::new(AVAILABLE_SLOTS * 2, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
-        assert!(matches!(
-            dbm.store_user(user_id, &user),
-            Err(Error::AlreadyExists)
-        ));
+        // Opening the database again leaves it as is
+        let dbm = DBM::new(db_path).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert!(dbm.load_user(user_id).unwrap().is_some());
     }
 
     #[test]
-    fn test_store_load_user_with_appointments() {
-        let dbm = DBM::in_memory().unwrap();
+    #[cfg(feature = "encryption")]
+    fn test_new_encrypted() {
+        let dir = TempDir::new("teos").unwrap();
+        let db_path = dir.path().join("teos_db.sql3");
 
+        // Unencrypted databases are encrypted keeping their data
+        let dbm = DBM::new(db_path.clone()).unwrap();
         let user_id = get_random_user_id();
-        let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
-
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
         dbm.store_user(user_id, &user).unwrap();
-
-        // Add some appointments to the user
-        for _ in 0..10 {
-            let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-            dbm.store_appointment(uuid, &appointment).unwrap();
-            user.appointments.insert(uuid, 1);
-        }
-
-        // Check both loading the whole user info or only the associated appointments
-        assert_eq!(dbm.load_user(user_id).unwrap(), user);
-        assert_eq!(dbm.load_user_appointments(user_id), user.appointments);
+        drop(dbm);
+        assert!(is_plaintext_database(&db_path));
+
+        let dbm = DBM::new_encrypted(db_path.clone(), "passphrase").unwrap();
+        assert!(!is_plaintext_database(&db_path));
+        assert!(!db_path.with_extension("encrypted").exists());
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+        drop(dbm);
+
+        // Encrypted databases cannot be opened without the right passphrase
+        assert!(DBM::new(db_path.clone()).is_err());
+        assert!(DBM::new_encrypted(db_path.clone(), "wrong passphrase").is_err());
+
+        // Opening the database again leaves it as is
+        let dbm = DBM::new_encrypted(db_path.clone(), "passphrase").unwrap();
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
+
+        // The passphrase can be changed
+        let mut dbm = dbm;
+        dbm.change_passphrase(
+            "passphrase",
+            "new passphrase",
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        drop(dbm);
+        assert!(DBM::new_encrypted(db_path.clone(), "passphrase").is_err());
+        let dbm = DBM::new_encrypted(db_path, "new passphrase").unwrap();
+        assert_eq!(dbm.load_user(user_id).unwrap().unwrap(), user);
     }
 
     #[test]
-    fn test_load_nonexistent_user() {
-        let dbm = DBM::in_memory().unwrap();
-
-        let user_id = get_random_user_id();
-        assert!(dbm.load_user(user_id).is_none());
+    #[cfg(feature = "encryption")]
+    fn test_change_passphrase() {
+        let dir = TempDir::new("teos").unwrap();
+        let db_path = dir.path().join("teos_db.sql3");
+
+        let mut dbm = DBM::new_encrypted(db_path.clone(), "passphrase").unwrap();
+        let key = TowerKey::Encrypted(get_random_bytes(76));
+        dbm.store_tower_key(&key).unwrap();
+        let (id, _) = dbm.load_all_tower_keys().into_iter().next().unwrap();
+        let wallet_key = TowerKey::Encrypted(get_random_bytes(76));
+        dbm.store_wallet_key(&wallet_key).unwrap();
+        let (wallet_id, _) = dbm.load_all_wallet_keys().into_iter().next().unwrap();
+
+        // If the keys cannot be replaced, the database is left under the current passphrase alongside them
+        dbm.connection
+            .execute(
+                "CREATE TRIGGER fail_keys_update BEFORE UPDATE ON keys BEGIN SELECT RAISE(ABORT, 'Cannot update'); END",
+                [],
+            )
+            .unwrap();
+        let new_key = TowerKey::Encrypted(get_random_bytes(76));
+        let new_keys = HashMap::from_iter([(id, new_key.clone())]);
+        let new_wallet_key = TowerKey::Encrypted(get_random_bytes(76));
+        let new_wallet_keys = HashMap::from_iter([(wallet_id, new_wallet_key.clone())]);
+        assert!(dbm
+            .change_passphrase("passphrase", "new passphrase", &new_keys, &new_wallet_keys)
+            .is_err());
+        drop(dbm);
+        assert!(DBM::new_encrypted(db_path.clone(), "new passphrase").is_err());
+        let mut dbm = DBM::new_encrypted(db_path.clone(), "passphrase").unwrap();
+        assert_eq!(dbm.load_tower_key().unwrap().unwrap(), key);
+        assert_eq!(dbm.load_wallet_key().unwrap().unwrap(), wallet_key);
+
+        // Otherwise, both are changed
+        dbm.connection
+            .execute("DROP TRIGGER fail_keys_update", [])
+            .unwrap();
+        dbm.change_passphrase("passphrase", "new passphrase", &new_keys, &new_wallet_keys)
+            .unwrap();
+        drop(dbm);
+        assert!(DBM::new_encrypted(db_path.clone(), "passphrase").is_err());
+        let dbm = DBM::new_encrypted(db_path, "new passphrase").unwrap();
+        assert_eq!(dbm.load_tower_key().unwrap().unwrap(), new_key);
+        assert_eq!(dbm.load_wallet_key().unwrap().unwrap(), new_wallet_key);
     }
 
     #[test]
-    fn test_update_user() {
-        let dbm = DBM::in_memory().unwrap();
+    #[cfg(feature = "encryption")]
+    fn test_new_encrypted_fresh_database() {
+        let dir = TempDir::new("teos").unwrap();
+        let db_path = dir.path().join("teos_db.sql3");
+
+        let dbm = DBM::new_encrypted(db_path.clone(), "passphrase").unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        drop(dbm);
+        assert!(!is_plaintext_database(&db_path));
+    }
 
-        let user_id = get_random_user_id();
-        let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+    #[test]
+    fn test_open_newer_database() {
+        // Databases migrated by a newer version of the tower are not opened
+        let dir = TempDir::new("teos").unwrap();
+        let db_path = dir.path().join("teos_db.sql3");
 
-        dbm.store_user(user_id, &user).unwrap();
-        assert_eq!(dbm.load_user(user_id).unwrap(), user);
+        let dbm = DBM::new(db_path.clone()).unwrap();
+        let version = MIGRATIONS.len() as u32 + 1;
+        dbm.connection
+            .execute(
+                "INSERT INTO schema_version (version, description, checksum) VALUES (?1, 'Newer migration', '')",
+                params![version],
+            )
+            .unwrap();
+        drop(dbm);
 
-        user.available_slots *= 2;
-        dbm.update_user(user_id, &user);
-        assert_eq!(dbm.load_user(user_id).unwrap(), user);
+        assert!(matches!(
+            DBM::new(db_path),
+            Err(MigrationError::NewerSchema { version: v, latest }) if v == version && latest == MIGRATIONS.len() as u32
+        ));
     }
 
-    #[test]
-    fn test_load_all_users() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_load_all_users(dbm: impl TestStorage) {
         let mut users = HashMap::new();
 
         for i in 1..11 {
@@ -725,15 +2067,11 @@ mod tests {
         assert_eq!(dbm.load_all_users(), users);
     }
 
-    #[test]
-    fn test_batch_remove_users() {
-        let mut dbm = DBM::in_memory().unwrap();
-
-        // Set a limit value for the maximum number of variables in SQLite so we can
+    fn test_batch_remove_users(mut dbm: impl TestStorage) {
+        // Set a limit value for the maximum number of entries a query can deal with so we can
         // test splitting big queries into chunks.
         let limit = 10;
-        dbm.connection
-            .set_limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER, limit);
+        dbm.set_batch_size(limit);
 
         let mut to_be_deleted = HashSet::new();
         let mut rest = HashSet::new();
@@ -776,7 +2114,7 @@ mod tests {
         ));
 
         dbm.batch_remove_users(&HashSet::from_iter(vec![appointment.user_id]));
-        assert!(dbm.load_user(appointment.user_id).is_none());
+        assert!(dbm.load_user(appointment.user_id).unwrap().is_none());
         assert!(dbm.load_appointment(uuid).is_none());
 
         // Appointment + Tracker
@@ -788,24 +2126,19 @@ mod tests {
         assert!(matches!(dbm.store_tracker(uuid, &tracker), Ok { .. }));
 
         dbm.batch_remove_users(&HashSet::from_iter(vec![appointment.user_id]));
-        assert!(dbm.load_user(appointment.user_id).is_none());
+        assert!(dbm.load_user(appointment.user_id).unwrap().is_none());
         assert!(dbm.load_appointment(uuid).is_none());
         assert!(dbm.load_tracker(uuid).is_none());
     }
 
-    #[test]
-    fn test_batch_remove_nonexistent_users() {
-        let mut dbm = DBM::in_memory().unwrap();
+    fn test_batch_remove_nonexistent_users(mut dbm: impl TestStorage) {
         let users = (0..10).map(|_| get_random_user_id()).collect();
 
         // Test it does not fail even if the user does not exist (it will log though)
         dbm.batch_remove_users(&users);
     }
 
-    #[test]
-    fn test_store_load_appointment() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_load_appointment(dbm: impl TestStorage) {
         // In order to add an appointment we need the associated user to be present
         let user_id = get_random_user_id();
         let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
@@ -826,10 +2159,7 @@ mod tests {
         ));
     }
 
-    #[test]
-    fn test_store_appointment_missing_user() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_appointment_missing_user(dbm: impl TestStorage) {
         let uuid = generate_uuid();
         let appointment = generate_dummy_appointment(None);
 
@@ -840,18 +2170,12 @@ mod tests {
         assert!((dbm.load_tracker(uuid).is_none()));
     }
 
-    #[test]
-    fn test_load_nonexistent_appointment() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_load_nonexistent_appointment(dbm: impl TestStorage) {
         let uuid = generate_uuid();
         assert!(dbm.load_appointment(uuid).is_none());
     }
 
-    #[test]
-    fn test_update_appointment() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_update_appointment(dbm: impl TestStorage) {
         let user_id = get_random_user_id();
         let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
         dbm.store_user(user_id, &user).unwrap();
@@ -879,9 +2203,7 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_load_all_appointments() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_load_all_appointments(dbm: impl TestStorage) {
         let mut appointments = HashMap::new();
 
         for i in 1..11 {
@@ -917,9 +2239,7 @@ mod tests {
         assert_eq!(dbm.load_appointments(None), appointments);
     }
 
-    #[test]
-    fn test_load_appointments_with_locator() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_load_appointments_with_locator(dbm: impl TestStorage) {
         let mut appointments = HashMap::new();
         let dispute_tx = get_random_tx();
         let dispute_txid = dispute_tx.txid();
@@ -970,15 +2290,11 @@ mod tests {
         assert_eq!(dbm.load_appointments(Some(locator)), appointments);
     }
 
-    #[test]
-    fn test_batch_remove_appointments() {
-        let mut dbm = DBM::in_memory().unwrap();
-
-        // Set a limit value for the maximum number of variables in SQLite so we can
+    fn test_batch_remove_appointments(mut dbm: impl TestStorage) {
+        // Set a limit value for the maximum number of entries a query can deal with so we can
         // test splitting big queries into chunks.
         let limit = 10;
-        dbm.connection
-            .set_limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER, limit);
+        dbm.set_batch_size(limit);
 
         let user_id = get_random_user_id();
         let mut user = UserInfo::new(
@@ -1010,20 +2326,102 @@ mod tests {
             // Check that the db transaction had i queries on it
             assert_eq!(
                 dbm.batch_remove_appointments(&to_be_deleted, &updated_users),
-                i as usize
+                i
             );
             // Check appointment data was deleted and users properly updated
             assert_eq!(rest, dbm.load_appointments(None).keys().cloned().collect());
             assert_eq!(
-                dbm.load_user(user_id).unwrap().available_slots,
+                dbm.load_user(user_id).unwrap().unwrap().available_slots,
                 user.available_slots
             );
         }
     }
 
-    #[test]
-    fn test_batch_remove_appointments_cascade() {
-        let mut dbm = DBM::in_memory().unwrap();
+    fn test_batch_store_appointments(mut dbm: impl TestStorage) {
+        let user_id = get_random_user_id();
+        let mut user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        let mut appointments = Vec::new();
+        for _ in 0..5 {
+            appointments.push(generate_dummy_appointment_with_user(user_id, None));
+        }
+        // A later entry with the same UUID should update the former
+        let (uuid, mut update) = appointments[0].clone();
+        update.start_block += 1;
+        appointments.push((uuid, update.clone()));
+
+        // Breaches of triggered appointments are queued alongside them
+        let breach = get_random_breach();
+        let triggered_uuid = appointments[1].0;
+
+        user.available_slots -= 5;
+        assert!(dbm
+            .batch_store_appointments(
+                &appointments
+                    .iter()
+                    .map(|(u, a)| (*u, a))
+                    .collect::<Vec<_>>(),
+                &[(triggered_uuid, &breach)],
+                user_id,
+                &user,
+            )
+            .is_ok());
+        assert_eq!(
+            dbm.load_queued_breaches(),
+            HashMap::from_iter([(triggered_uuid, breach.clone())])
+        );
+
+        let loaded = dbm.load_appointments(None);
+        assert_eq!(loaded.len(), 5);
+        for (uuid, appointment) in appointments[1..5].iter() {
+            assert_eq!(&loaded[uuid], appointment);
+        }
+        assert_eq!(loaded[&uuid], update);
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().unwrap().available_slots,
+            user.available_slots
+        );
+
+        // If any of the appointments cannot be stored, none of them are
+        let (new_uuid, new_appointment) = generate_dummy_appointment_with_user(user_id, None);
+        let (unknown_uuid, unknown_appointment) =
+            generate_dummy_appointment_with_user(get_random_user_id(), None);
+        assert!(matches!(
+            dbm.batch_store_appointments(
+                &[
+                    (new_uuid, &new_appointment),
+                    (unknown_uuid, &unknown_appointment)
+                ],
+                &[],
+                user_id,
+                &UserInfo::new(0, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY),
+            ),
+            Err(Error::Unknown { .. }) | Err(Error::Backend { .. })
+        ));
+        assert!(dbm.load_appointment(new_uuid).is_none());
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().unwrap().available_slots,
+            user.available_slots
+        );
+
+        // Same if any of the breaches cannot be queued
+        assert!(dbm
+            .batch_store_appointments(
+                &[(new_uuid, &new_appointment)],
+                &[(triggered_uuid, &breach)],
+                user_id,
+                &UserInfo::new(0, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY),
+            )
+            .is_err());
+        assert!(dbm.load_appointment(new_uuid).is_none());
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().unwrap().available_slots,
+            user.available_slots
+        );
+    }
+
+    fn test_batch_remove_appointments_cascade(mut dbm: impl TestStorage) {
         let uuid = generate_uuid();
         let appointment = generate_dummy_appointment(None);
         // The confirmation status doesn't really matter here, it can be any of {ConfirmedIn, InMempoolSince}.
@@ -1061,18 +2459,13 @@ mod tests {
         assert!(dbm.load_tracker(uuid).is_none());
     }
 
-    #[test]
-    fn test_batch_remove_nonexistent_appointments() {
-        let mut dbm = DBM::in_memory().unwrap();
+    fn test_batch_remove_nonexistent_appointments(mut dbm: impl TestStorage) {
         let appointments = (0..10).map(|_| generate_uuid()).collect();
 
         // Test it does not fail even if the user does not exist (it will log though)
         dbm.batch_remove_appointments(&appointments, &HashMap::new());
     }
-    #[test]
-    fn test_load_locator() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_load_locator(dbm: impl TestStorage) {
         // In order to add an appointment we need the associated user to be present
         let user_id = get_random_user_id();
         let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
@@ -1089,18 +2482,12 @@ mod tests {
         assert_eq!(dbm.load_locator(uuid).unwrap(), appointment.locator());
     }
 
-    #[test]
-    fn test_load_nonexistent_locator() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_load_nonexistent_locator(dbm: impl TestStorage) {
         let (uuid, _) = generate_dummy_appointment_with_user(get_random_user_id(), None);
         assert!(dbm.load_locator(uuid).is_none());
     }
 
-    #[test]
-    fn test_store_load_tracker() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_load_tracker(dbm: impl TestStorage) {
         // In order to add a tracker we need the associated appointment to be present (which
         // at the same time requires an associated user to be present)
         let user_id = get_random_user_id();
@@ -1116,10 +2503,7 @@ mod tests {
         assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
     }
 
-    #[test]
-    fn test_store_duplicate_tracker() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_duplicate_tracker(dbm: impl TestStorage) {
         let user_id = get_random_user_id();
         let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
         dbm.store_user(user_id, &user).unwrap();
@@ -1138,10 +2522,7 @@ mod tests {
         ));
     }
 
-    #[test]
-    fn test_store_tracker_missing_appointment() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_store_tracker_missing_appointment(dbm: impl TestStorage) {
         let uuid = generate_uuid();
         let user_id = get_random_user_id();
 
@@ -1154,17 +2535,32 @@ mod tests {
         ));
     }
 
-    #[test]
-    fn test_load_nonexistent_tracker() {
-        let dbm = DBM::in_memory().unwrap();
-
+    fn test_load_nonexistent_tracker(dbm: impl TestStorage) {
         let uuid = generate_uuid();
         assert!(dbm.load_tracker(uuid).is_none());
     }
 
-    #[test]
-    fn test_load_all_trackers() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_remove_tracker(dbm: impl TestStorage) {
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+
+        let tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+
+        // Removing the tracker leaves the appointment untouched
+        dbm.remove_tracker(uuid);
+        assert!(dbm.load_tracker(uuid).is_none());
+        assert_eq!(dbm.load_appointment(uuid).unwrap(), appointment);
+
+        // The appointment is back in the set of appointments to be watched
+        assert!(dbm.load_appointments(None).contains_key(&uuid));
+    }
+
+    fn test_load_all_trackers(dbm: impl TestStorage) {
         let mut trackers = HashMap::new();
 
         for i in 1..11 {
@@ -1188,9 +2584,7 @@ mod tests {
         assert_eq!(dbm.load_trackers(None), trackers);
     }
 
-    #[test]
-    fn test_load_trackers_with_locator() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_load_trackers_with_locator(dbm: impl TestStorage) {
         let mut trackers = HashMap::new();
         let dispute_tx = get_random_tx();
         let dispute_txid = dispute_tx.txid();
@@ -1224,36 +2618,400 @@ mod tests {
         assert_eq!(dbm.load_trackers(Some(locator)), trackers);
     }
 
-    #[test]
-    fn test_store_load_last_known_block() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_loads_fail_on_broken_database(dbm: impl TestStorage) {
+        for table in ["users", "last_known_block", "keys", "wallet_keys"] {
+            dbm.drop_table(table);
+        }
+
+        // Data the tower cannot run without is not reported as missing if it cannot be read
+        assert!(dbm.load_user(get_random_user_id()).is_err());
+        assert!(dbm.load_last_known_block().is_err());
+        assert!(dbm.load_tower_key().is_err());
+        assert!(dbm.load_wallet_key().is_err());
 
+        // Neither do updates, which cannot report errors
+        dbm.update_user(
+            get_random_user_id(),
+            &UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY),
+        );
+    }
+
+    fn test_store_load_last_known_block(dbm: impl TestStorage) {
         let mut block_hash = BlockHash::from_slice(&get_random_bytes(32)).unwrap();
         dbm.store_last_known_block(&block_hash).unwrap();
-        assert_eq!(dbm.load_last_known_block().unwrap(), block_hash);
+        assert_eq!(dbm.load_last_known_block().unwrap().unwrap(), block_hash);
 
         // Update with a new hash to check it can be done
         block_hash = BlockHash::from_slice(&get_random_bytes(32)).unwrap();
         dbm.store_last_known_block(&block_hash).unwrap();
-        assert_eq!(dbm.load_last_known_block().unwrap(), block_hash);
+        assert_eq!(dbm.load_last_known_block().unwrap().unwrap(), block_hash);
     }
 
-    #[test]
-    fn test_store_load_nonexistent_last_known_block() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_store_load_nonexistent_last_known_block(dbm: impl TestStorage) {
+        assert!(dbm.load_last_known_block().unwrap().is_none());
+    }
+
+    fn test_has_appointments(dbm: impl TestStorage) {
+        assert!(!dbm.has_appointments());
 
-        assert!(dbm.load_last_known_block().is_none());
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        assert!(dbm.has_appointments());
     }
 
-    #[test]
-    fn test_store_load_tower_key() {
-        let dbm = DBM::in_memory().unwrap();
+    fn test_store_load_pending_registrations(dbm: impl TestStorage) {
+        assert!(dbm.load_pending_registrations().is_empty());
+
+        let mut pending_registrations = HashMap::new();
+        for i in 0..10 {
+            let user_id = get_random_user_id();
+            let registration = PendingRegistration {
+                tier: format!("tier{i}"),
+                operation: SubscriptionOperation::from(i % 3),
+                invoice: get_random_invoice(),
+            };
+            dbm.store_pending_registration(user_id, &registration)
+                .unwrap();
+            pending_registrations.insert(user_id, vec![registration]);
+        }
+        assert_eq!(dbm.load_pending_registrations(), pending_registrations);
+
+        // Storing a registration for a user with a pending one keeps both
+        let user_id = *pending_registrations.keys().next().unwrap();
+        let mut invoice = get_random_invoice();
+        invoice.expires_at += 1;
+        let registration = PendingRegistration {
+            tier: String::new(),
+            operation: SubscriptionOperation::Renew,
+            invoice,
+        };
+        dbm.store_pending_registration(user_id, &registration)
+            .unwrap();
+        pending_registrations
+            .get_mut(&user_id)
+            .unwrap()
+            .push(registration.clone());
+        assert_eq!(dbm.load_pending_registrations(), pending_registrations);
+
+        // Pending registrations can also be removed, either one by one or all the ones of a user at once
+        dbm.remove_pending_registration(&registration.invoice.payment_hash);
+        pending_registrations.get_mut(&user_id).unwrap().pop();
+        assert_eq!(dbm.load_pending_registrations(), pending_registrations);
+
+        dbm.remove_pending_registrations(user_id);
+        pending_registrations.remove(&user_id);
+        assert_eq!(dbm.load_pending_registrations(), pending_registrations);
+    }
+
+    fn test_store_load_banned_users(dbm: impl TestStorage) {
+        assert!(dbm.load_banned_users().is_empty());
+
+        // Users can be banned without being registered
+        let banned_users: HashSet<UserId> = (0..10).map(|_| get_random_user_id()).collect();
+        for user_id in banned_users.iter() {
+            dbm.store_banned_user(*user_id).unwrap();
+        }
+        assert_eq!(dbm.load_banned_users(), banned_users);
+
+        // Users cannot be banned twice
+        let user_id = *banned_users.iter().next().unwrap();
+        assert!(matches!(
+            dbm.store_banned_user(user_id),
+            Err(Error::AlreadyExists)
+        ));
+
+        dbm.remove_banned_user(user_id);
+        assert!(!dbm.load_banned_users().contains(&user_id));
+    }
+
+    fn test_store_load_audit_log(mut dbm: impl TestStorage) {
+        assert!(dbm.load_audit_log().is_empty());
+
+        // Entries are loaded in the same order they were stored
+        let user_id = get_random_user_id();
+        let audit_log: Vec<AuditLogEntry> = (0..10)
+            .map(|i| AuditLogEntry {
+                timestamp: 1000 - i,
+                user_id,
+                action: format!("action{i}"),
+                details: format!("details{i}"),
+            })
+            .collect();
+        for entry in audit_log.iter() {
+            dbm.store_audit_log_entry(entry).unwrap();
+        }
+        assert_eq!(dbm.load_audit_log(), audit_log);
+
+        // And they outlive the users they refer to
+        dbm.batch_remove_users(&HashSet::from_iter([user_id]));
+        assert_eq!(dbm.load_audit_log(), audit_log);
+    }
+
+    fn test_store_remove_redeemed_token(dbm: impl TestStorage) {
+        let token = get_random_bytes(32);
+
+        // Tokens can only be stored once, unless they are removed
+        dbm.store_redeemed_token(&token).unwrap();
+        assert!(matches!(
+            dbm.store_redeemed_token(&token),
+            Err(Error::AlreadyExists)
+        ));
+        dbm.store_redeemed_token(&get_random_bytes(32)).unwrap();
+
+        dbm.remove_redeemed_token(&token);
+        dbm.store_redeemed_token(&token).unwrap();
+    }
+
+    fn get_random_block_summary(keep_txdata: bool) -> BlockSummary {
+        let mut header = genesis_block(Network::Regtest).header;
+        header.nonce = rand::random();
+        let txdata: Vec<_> = (0..3).map(|_| get_random_tx()).collect();
+        BlockSummary::new(header, &txdata, keep_txdata)
+    }
+
+    fn test_store_load_block_summaries(dbm: impl TestStorage) {
+        assert!(dbm.load_block_summaries().is_empty());
+
+        // Summaries are loaded from the most recent backwards, with or without transactions
+        let summaries: Vec<_> = (0..5)
+            .map(|i| get_random_block_summary(i % 2 == 0))
+            .collect();
+        for (height, summary) in summaries.iter().enumerate() {
+            dbm.store_block_summary(height as u32, summary).unwrap();
+        }
+        assert_eq!(
+            dbm.load_block_summaries(),
+            summaries.iter().rev().cloned().collect::<Vec<_>>()
+        );
+
+        // Storing a summary at a known height replaces the old one (e.g. after a reorg)
+        let summary = get_random_block_summary(true);
+        dbm.store_block_summary(4, &summary).unwrap();
+        assert_eq!(dbm.load_block_summaries()[0], summary);
+
+        // Summaries can also be removed
+        dbm.remove_block_summary(4);
+        assert_eq!(dbm.load_block_summaries()[0], summaries[3]);
+    }
+
+    fn test_prune_block_summaries(dbm: impl TestStorage) {
+        let summaries: Vec<_> = (0..10).map(|_| get_random_block_summary(true)).collect();
+        for (height, summary) in summaries.iter().enumerate() {
+            dbm.store_block_summary(height as u32, summary).unwrap();
+        }
+
+        dbm.prune_block_summaries(3, 6);
+        let pruned = dbm.load_block_summaries();
+        assert_eq!(pruned.len(), 6);
+        for (summary, height) in pruned.iter().zip((4..10).rev()) {
+            assert_eq!(summary.header, summaries[height].header);
+            assert_eq!(summary.txids, summaries[height].txids);
+            assert_eq!(summary.txdata.is_some(), height > 6);
+        }
+    }
+
+    fn test_store_load_tower_key(dbm: impl TestStorage) {
+        assert!(dbm.load_tower_key().unwrap().is_none());
+        for i in 0..7 {
+            // The backend does not care about the keys being encrypted, so no real encryption is needed here
+            let key = if i % 2 == 0 {
+                TowerKey::Plaintext(get_random_keypair().0)
+            } else {
+                TowerKey::Encrypted(get_random_bytes(76))
+            };
+            dbm.store_tower_key(&key).unwrap();
+            assert_eq!(dbm.load_tower_key().unwrap().unwrap(), key);
+        }
+        assert_eq!(dbm.load_all_tower_keys().len(), 7);
+    }
+
+    fn test_update_tower_keys(mut dbm: impl TestStorage) {
+        let mut keys = HashMap::new();
+        let mut wallet_keys = HashMap::new();
+        for _ in 0..5 {
+            dbm.store_tower_key(&TowerKey::Plaintext(get_random_keypair().0))
+                .unwrap();
+            dbm.store_wallet_key(&TowerKey::Plaintext(get_random_keypair().0))
+                .unwrap();
+        }
+        for (id, key) in dbm.load_all_tower_keys() {
+            assert!(!key.is_encrypted());
+            keys.insert(id, TowerKey::Encrypted(get_random_bytes(76)));
+        }
+        for (id, key) in dbm.load_all_wallet_keys() {
+            assert!(!key.is_encrypted());
+            wallet_keys.insert(id, TowerKey::Encrypted(get_random_bytes(76)));
+        }
+
+        dbm.update_tower_keys(&keys, &wallet_keys).unwrap();
+        assert_eq!(dbm.load_all_tower_keys(), keys);
+        assert_eq!(dbm.load_all_wallet_keys(), wallet_keys);
+        // The last keys are still the ones loaded by default
+        let last_id = keys.keys().max().unwrap();
+        assert_eq!(
+            &dbm.load_tower_key().unwrap().unwrap(),
+            keys.get(last_id).unwrap()
+        );
+        assert_eq!(
+            &dbm.load_wallet_key().unwrap().unwrap(),
+            wallet_keys.get(wallet_keys.keys().max().unwrap()).unwrap()
+        );
+
+        // Updating a missing key fails, leaving the rest untouched
+        let mut updated_keys = keys.clone();
+        for key in updated_keys.values_mut() {
+            *key = TowerKey::Plaintext(get_random_keypair().0);
+        }
+        updated_keys.insert(last_id + 1, TowerKey::Plaintext(get_random_keypair().0));
+        assert!(matches!(
+            dbm.update_tower_keys(&updated_keys, &HashMap::new()),
+            Err(Error::NotFound)
+        ));
+        assert_eq!(dbm.load_all_tower_keys(), keys);
+
+        // Same for a missing wallet key, even if the tower keys can be updated
+        let mut updated_wallet_keys = wallet_keys.clone();
+        updated_wallet_keys.insert(
+            wallet_keys.keys().max().unwrap() + 1,
+            TowerKey::Plaintext(get_random_keypair().0),
+        );
+        updated_keys.remove(&(last_id + 1));
+        assert!(matches!(
+            dbm.update_tower_keys(&updated_keys, &updated_wallet_keys),
+            Err(Error::NotFound)
+        ));
+        assert_eq!(dbm.load_all_tower_keys(), keys);
+        assert_eq!(dbm.load_all_wallet_keys(), wallet_keys);
+    }
+
+    fn test_store_load_wallet_key(dbm: impl TestStorage) {
+        assert!(dbm.load_wallet_key().unwrap().is_none());
+        for i in 0..7 {
+            let key = if i % 2 == 0 {
+                TowerKey::Plaintext(get_random_keypair().0)
+            } else {
+                TowerKey::Encrypted(get_random_bytes(76))
+            };
+            dbm.store_wallet_key(&key).unwrap();
+            assert_eq!(dbm.load_wallet_key().unwrap().unwrap(), key);
+        }
+        assert_eq!(dbm.load_all_wallet_keys().len(), 7);
+
+        // Wallet keys and tower keys are independent
+        assert!(dbm.load_tower_key().unwrap().is_none());
+    }
+
+    fn test_store_load_fee_bumps(dbm: impl TestStorage) {
+        let uuid = generate_uuid();
+        assert!(dbm.load_fee_bumps(uuid).is_empty());
+
+        // Fee bumps need a tracker to be stored
+        let fee_bump = FeeBump {
+            child_tx: get_random_tx(),
+            fee: 1000,
+            fee_rate: 5,
+            height: 100,
+        };
+        assert!(matches!(
+            dbm.store_fee_bump(uuid, &fee_bump),
+            Err(Error::MissingForeignKey)
+        ));
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        dbm.store_tracker(
+            uuid,
+            &get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(100)),
+        )
+        .unwrap();
+
+        let mut fee_bumps = Vec::new();
+        for i in 0..3 {
+            let fee_bump = FeeBump {
+                child_tx: get_random_tx(),
+                fee: 1000 * (i + 1),
+                fee_rate: 5 * (i + 1),
+                height: 100 + i as u32,
+            };
+            dbm.store_fee_bump(uuid, &fee_bump).unwrap();
+            fee_bumps.push(fee_bump);
+        }
+        assert_eq!(dbm.load_fee_bumps(uuid), fee_bumps);
+
+        // Fee bumps are deleted alongside the tracker
+        dbm.remove_tracker(uuid);
+        assert!(dbm.load_fee_bumps(uuid).is_empty());
+    }
+
+    fn test_store_remove_queued_breaches(dbm: impl TestStorage) {
+        assert!(dbm.load_queued_breaches().is_empty());
+
+        // Queued breaches need an appointment to be stored
+        let uuid = generate_uuid();
+        assert!(matches!(
+            dbm.store_queued_breach(uuid, &get_random_breach()),
+            Err(Error::MissingForeignKey)
+        ));
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+        let mut breaches = HashMap::new();
+        for _ in 0..3 {
+            let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+            dbm.store_appointment(uuid, &appointment).unwrap();
+            let breach = get_random_breach();
+            dbm.store_queued_breach(uuid, &breach).unwrap();
+            breaches.insert(uuid, breach);
+        }
+        assert_eq!(dbm.load_queued_breaches(), breaches);
+
+        // Breaches can be removed once handled, or alongside their appointment otherwise
+        let mut uuids = breaches.keys().cloned();
+        let (handled, rejected) = (uuids.next().unwrap(), uuids.next().unwrap());
+        dbm.remove_queued_breach(handled);
+        dbm.remove_appointment(rejected);
+        breaches.remove(&handled);
+        breaches.remove(&rejected);
+        assert_eq!(dbm.load_queued_breaches(), breaches);
+    }
+
+    fn test_store_remove_mempool_triggers(dbm: impl TestStorage) {
+        assert!(dbm.load_mempool_triggers().is_empty());
+
+        // Mempool triggers need an appointment to be stored
+        let uuid = generate_uuid();
+        assert!(matches!(
+            dbm.store_mempool_trigger(uuid, &get_random_tx().txid()),
+            Err(Error::MissingForeignKey)
+        ));
 
-        assert!(dbm.load_tower_key().is_none());
-        for _ in 0..7 {
-            let sk = get_random_keypair().0;
-            dbm.store_tower_key(&sk).unwrap();
-            assert_eq!(dbm.load_tower_key().unwrap(), sk);
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+        let mut triggers = HashMap::new();
+        for _ in 0..3 {
+            let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+            dbm.store_appointment(uuid, &appointment).unwrap();
+            let dispute_txid = get_random_tx().txid();
+            dbm.store_mempool_trigger(uuid, &dispute_txid).unwrap();
+            triggers.insert(uuid, dispute_txid);
         }
+        assert_eq!(dbm.load_mempool_triggers(), triggers);
+
+        // Triggers can be removed once the dispute is settled, or alongside their appointment otherwise
+        let mut uuids = triggers.keys().cloned();
+        let (settled, deleted) = (uuids.next().unwrap(), uuids.next().unwrap());
+        dbm.remove_mempool_trigger(settled);
+        dbm.remove_appointment(deleted);
+        triggers.remove(&settled);
+        triggers.remove(&deleted);
+        assert_eq!(dbm.load_mempool_triggers(), triggers);
     }
 }
@@ -0,0 +1,213 @@
+//! Logic related to the encryption at rest of the tower data.
+//!
+//! The tower secret key can be kept encrypted under a passphrase, and so can the whole `SQLite` database. The
+//! passphrase is read from a file, from the environment or prompted on startup, in that order.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bitcoin::secp256k1::SecretKey;
+
+use teos_common::cryptography::{decrypt_with_passphrase, encrypt_with_passphrase};
+
+/// Environment variable the tower passphrase can be supplied through.
+pub const PASSPHRASE_ENV: &str = "TEOS_PASSPHRASE";
+
+/// Environment variable the new tower passphrase can be supplied through when changing it.
+pub const NEW_PASSPHRASE_ENV: &str = "TEOS_NEW_PASSPHRASE";
+
+/// Packs the errors than can raise when dealing with data encrypted at rest.
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The passphrase is empty.
+    EmptyPassphrase,
+    /// The passphrase and its confirmation do not match.
+    PassphraseMismatch,
+    /// The data cannot be decrypted under the given passphrase.
+    WrongPassphrase,
+    /// The passphrase cannot be read.
+    Io(io::Error),
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptionError::EmptyPassphrase => write!(f, "The passphrase cannot be empty"),
+            EncryptionError::PassphraseMismatch => write!(f, "The passphrases do not match"),
+            EncryptionError::WrongPassphrase => write!(f, "Wrong passphrase"),
+            EncryptionError::Io(e) => write!(f, "Cannot read the passphrase: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+impl From<io::Error> for EncryptionError {
+    fn from(e: io::Error) -> Self {
+        EncryptionError::Io(e)
+    }
+}
+
+/// A tower secret key, as held by the storage backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TowerKey {
+    Plaintext(SecretKey),
+    /// The key encrypted under the tower passphrase (see [encrypt_with_passphrase]).
+    Encrypted(Vec<u8>),
+}
+
+impl TowerKey {
+    /// Creates a [TowerKey] by encrypting the given secret key under a passphrase.
+    pub fn encrypt(sk: &SecretKey, passphrase: &str) -> Self {
+        TowerKey::Encrypted(encrypt_with_passphrase(&sk.secret_bytes(), passphrase))
+    }
+
+    /// Whether the key is encrypted or not.
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, TowerKey::Encrypted(_))
+    }
+
+    /// Gets the secret key, decrypting it under the given passphrase if needed.
+    pub fn unlock(&self, passphrase: &str) -> Result<SecretKey, EncryptionError> {
+        match self {
+            TowerKey::Plaintext(sk) => Ok(*sk),
+            TowerKey::Encrypted(encrypted) => decrypt_with_passphrase(encrypted, passphrase)
+                .ok()
+                .and_then(|raw_sk| SecretKey::from_slice(&raw_sk).ok())
+                .ok_or(EncryptionError::WrongPassphrase),
+        }
+    }
+}
+
+/// Re-encrypts the encrypted tower keys, identified by their id, under a new passphrase. Keys held in plaintext are left
+/// out.
+///
+/// Nothing is re-encrypted if any of the keys cannot be decrypted under the current passphrase.
+pub fn reencrypt_tower_keys(
+    keys: HashMap<u32, TowerKey>,
+    passphrase: &str,
+    new_passphrase: &str,
+) -> Result<HashMap<u32, TowerKey>, EncryptionError> {
+    keys.into_iter()
+        .filter(|(_, key)| key.is_encrypted())
+        .map(|(id, key)| {
+            key.unlock(passphrase)
+                .map(|sk| (id, TowerKey::encrypt(&sk, new_passphrase)))
+        })
+        .collect()
+}
+
+/// Checks that a passphrase can be used to encrypt data.
+fn check_passphrase(passphrase: String) -> Result<String, EncryptionError> {
+    if passphrase.is_empty() {
+        Err(EncryptionError::EmptyPassphrase)
+    } else {
+        Ok(passphrase)
+    }
+}
+
+/// Reads the passphrase from a file. Trailing line breaks are not part of the passphrase.
+pub fn read_passphrase_file(path: &Path) -> Result<String, EncryptionError> {
+    let passphrase = fs::read_to_string(path)?;
+    check_passphrase(passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned())
+}
+
+/// Gets the tower passphrase.
+///
+/// The passphrase is read from `passphrase_file` if set, from [PASSPHRASE_ENV] if present, or prompted otherwise.
+pub fn get_passphrase(passphrase_file: &str) -> Result<String, EncryptionError> {
+    if !passphrase_file.is_empty() {
+        read_passphrase_file(Path::new(passphrase_file))
+    } else if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        check_passphrase(passphrase)
+    } else {
+        check_passphrase(rpassword::prompt_password_stderr("Tower passphrase: ")?)
+    }
+}
+
+/// Gets the new tower passphrase when changing it.
+///
+/// The passphrase is read from [NEW_PASSPHRASE_ENV] if present. Otherwise, it is prompted twice so typos are caught.
+pub fn get_new_passphrase() -> Result<String, EncryptionError> {
+    if let Ok(passphrase) = std::env::var(NEW_PASSPHRASE_ENV) {
+        return check_passphrase(passphrase);
+    }
+
+    let passphrase =
+        check_passphrase(rpassword::prompt_password_stderr("New tower passphrase: ")?)?;
+    if rpassword::prompt_password_stderr("Confirm new tower passphrase: ")? != passphrase {
+        return Err(EncryptionError::PassphraseMismatch);
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::FromIterator;
+    use tempdir::TempDir;
+
+    use teos_common::cryptography::get_random_keypair;
+
+    #[test]
+    fn test_tower_key_unlock() {
+        let sk = get_random_keypair().0;
+
+        // Plaintext keys do not care about the passphrase
+        assert_eq!(TowerKey::Plaintext(sk).unlock("").unwrap(), sk);
+
+        let key = TowerKey::encrypt(&sk, "passphrase");
+        assert!(key.is_encrypted());
+        assert_eq!(key.unlock("passphrase").unwrap(), sk);
+        assert!(matches!(
+            key.unlock("wrong passphrase"),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_reencrypt_tower_keys() {
+        let (sk, old_sk) = (get_random_keypair().0, get_random_keypair().0);
+        let keys = HashMap::from_iter([
+            (1, TowerKey::Plaintext(old_sk)),
+            (2, TowerKey::encrypt(&sk, "passphrase")),
+        ]);
+
+        // Keys cannot be re-encrypted under the wrong passphrase
+        assert!(matches!(
+            reencrypt_tower_keys(keys.clone(), "wrong passphrase", "new passphrase"),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+
+        // Plaintext keys are left out
+        let reencrypted = reencrypt_tower_keys(keys, "passphrase", "new passphrase").unwrap();
+        assert_eq!(reencrypted.len(), 1);
+        assert_eq!(reencrypted[&2].unlock("new passphrase").unwrap(), sk);
+    }
+
+    #[test]
+    fn test_read_passphrase_file() {
+        let tmp_dir = TempDir::new("passphrase").unwrap();
+        let path = tmp_dir.path().join("passphrase");
+
+        // Missing files cannot be read
+        assert!(matches!(
+            read_passphrase_file(&path),
+            Err(EncryptionError::Io(_))
+        ));
+
+        // Trailing line breaks are dropped, but other whitespaces are kept
+        fs::write(&path, " passphrase \r\n").unwrap();
+        assert_eq!(read_passphrase_file(&path).unwrap(), " passphrase ");
+
+        fs::write(&path, "\n").unwrap();
+        assert!(matches!(
+            read_passphrase_file(&path),
+            Err(EncryptionError::EmptyPassphrase)
+        ));
+    }
+}
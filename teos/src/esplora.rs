@@ -0,0 +1,818 @@
+//! Logic related to the Esplora backend, an alternative to `bitcoind` built on top of an Esplora-compatible REST API.
+//!
+//! [EsploraClient] is used as the tower's [BlockSource] while [EsploraBroadcaster] takes care of the interaction with the
+//! network on behalf of the [Responder](crate::responder::Responder).
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus;
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::util::uint::Uint256;
+use bitcoin::{Address, Block, BlockHash, BlockHeader, Network, OutPoint, Transaction, Txid};
+use lightning_block_sync::{
+    AsyncBlockSourceResult, BlockHeaderData, BlockSource, BlockSourceError, BlockSourceResult,
+};
+
+use crate::backends::{BroadcastBackend, EsploraBackend};
+use crate::carrier::TxBroadcaster;
+use crate::responder::ConfirmationStatus;
+use crate::wallet::Utxo;
+
+/// Time to wait for Esplora to answer a request.
+const REQUEST_TIMEOUT: u64 = 30;
+
+/// A [BlockSource] backed by an Esplora-compatible REST API.
+///
+/// Esplora does not report the chainwork of the blocks it serves, so it is computed based on the headers already served by the client:
+/// either building on top of a known ancestor, or backwards from a known child. The first header served sets the starting point.
+/// This works as long as new headers are connected to the ones already served, which holds given the tower keeps track of the
+/// latest blocks of the chain.
+pub struct EsploraClient {
+    /// The base url of the API.
+    url: String,
+    /// The underlying HTTP client.
+    client: reqwest::Client,
+    /// The headers served so far. Used to compute the chainwork of new ones.
+    headers: Mutex<HashMap<BlockHash, BlockHeaderData>>,
+}
+
+impl EsploraClient {
+    /// Creates a new [EsploraClient] instance.
+    ///
+    /// Fails if the API cannot be reached or if it is not running on the given network.
+    pub async fn new(url: &str, network: Network) -> std::io::Result<Self> {
+        let client = Self {
+            url: url.trim_end_matches('/').to_owned(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+                .build()
+                .map_err(Error::other)?,
+            headers: Mutex::new(HashMap::new()),
+        };
+
+        // Assert teos runs on the same chain/network as the API.
+        let genesis_hash = client
+            .get_text("block-height/0")
+            .await
+            .map_err(|e| Error::new(ErrorKind::NotConnected, e.into_inner()))?;
+        if genesis_hash != genesis_block(network).block_hash().to_string() {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{url} is not running on {network}"),
+            ))
+        } else {
+            Ok(client)
+        }
+    }
+
+    /// Performs a GET request to the API. Client errors are considered persistent, while the rest are considered transient.
+    async fn get(&self, path: &str) -> BlockSourceResult<reqwest::Response> {
+        let response = self
+            .client
+            .get(format!("{}/{path}", self.url))
+            .send()
+            .await
+            .map_err(BlockSourceError::transient)?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else if response.status().is_client_error() {
+            Err(BlockSourceError::persistent(format!(
+                "{path}: {}",
+                response.status()
+            )))
+        } else {
+            Err(BlockSourceError::transient(format!(
+                "{path}: {}",
+                response.status()
+            )))
+        }
+    }
+
+    /// Performs a GET request to the API and returns the response body as text.
+    async fn get_text(&self, path: &str) -> BlockSourceResult<String> {
+        self.get(path)
+            .await?
+            .text()
+            .await
+            .map_err(BlockSourceError::transient)
+    }
+
+    /// Gets a block header, alongside its height, given its hash.
+    async fn fetch_header(&self, header_hash: &BlockHash) -> BlockSourceResult<(BlockHeader, u32)> {
+        let json: serde_json::Value =
+            serde_json::from_str(&self.get_text(&format!("block/{header_hash}")).await?)
+                .map_err(BlockSourceError::persistent)?;
+        parse_header(&json).ok_or_else(|| BlockSourceError::persistent("invalid block header"))
+    }
+
+    /// Computes the chainwork of a given header based on the headers known by the client (see [EsploraClient]).
+    async fn compute_chainwork(
+        &self,
+        header: &BlockHeader,
+        height: u32,
+    ) -> BlockSourceResult<Uint256> {
+        let known_child = {
+            let headers = self.headers.lock().unwrap();
+            if headers.is_empty() {
+                // The starting point is computed as if all the previous blocks had the same work.
+                return Ok(header.work() * Uint256::from_u64(height as u64 + 1).unwrap());
+            }
+            headers
+                .values()
+                .find(|known| known.header.prev_blockhash == header.block_hash())
+                .map(|child| child.chainwork - child.header.work())
+        };
+        if let Some(chainwork) = known_child {
+            return Ok(chainwork);
+        }
+
+        // Walk back until a known ancestor is found
+        let mut ancestors = Vec::new();
+        let mut prev_hash = header.prev_blockhash;
+        let mut chainwork = loop {
+            let known = self
+                .headers
+                .lock()
+                .unwrap()
+                .get(&prev_hash)
+                .map(|known| known.chainwork);
+            if let Some(chainwork) = known {
+                break chainwork;
+            }
+            let (ancestor, ancestor_height) = self.fetch_header(&prev_hash).await?;
+            prev_hash = ancestor.prev_blockhash;
+            ancestors.push((ancestor, ancestor_height));
+        };
+
+        // Keep the ancestors so they do not have to be walked again
+        let mut headers = self.headers.lock().unwrap();
+        for (ancestor, height) in ancestors.into_iter().rev() {
+            chainwork = chainwork + ancestor.work();
+            headers.insert(
+                ancestor.block_hash(),
+                BlockHeaderData {
+                    header: ancestor,
+                    height,
+                    chainwork,
+                },
+            );
+        }
+
+        Ok(chainwork + header.work())
+    }
+}
+
+/// Builds a [BlockHeader], alongside its height, from the block data returned by Esplora's `block/:hash` endpoint.
+fn parse_header(json: &serde_json::Value) -> Option<(BlockHeader, u32)> {
+    let prev_blockhash = match json["previousblockhash"].as_str() {
+        Some(hash) => BlockHash::from_str(hash).ok()?,
+        // Only the genesis block has no parent
+        None => BlockHash::default(),
+    };
+    let header = BlockHeader {
+        version: json["version"].as_i64()? as i32,
+        prev_blockhash,
+        merkle_root: TxMerkleNode::from_str(json["merkle_root"].as_str()?).ok()?,
+        time: json["timestamp"].as_u64()? as u32,
+        bits: json["bits"].as_u64()? as u32,
+        nonce: json["nonce"].as_u64()? as u32,
+    };
+
+    Some((header, json["height"].as_u64()? as u32))
+}
+
+impl BlockSource for EsploraClient {
+    /// Gets a block header given its hash.
+    fn get_header<'a>(
+        &'a self,
+        header_hash: &'a BlockHash,
+        _height_hint: Option<u32>,
+    ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+        Box::pin(async move {
+            if let Some(header_data) = self.headers.lock().unwrap().get(header_hash) {
+                return Ok(*header_data);
+            }
+
+            let (header, height) = self.fetch_header(header_hash).await?;
+            let header_data = BlockHeaderData {
+                header,
+                height,
+                chainwork: self.compute_chainwork(&header, height).await?,
+            };
+            self.headers
+                .lock()
+                .unwrap()
+                .insert(*header_hash, header_data);
+
+            Ok(header_data)
+        })
+    }
+
+    /// Gets a block given its hash.
+    fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, Block> {
+        Box::pin(async move {
+            let raw_block = self
+                .get(&format!("block/{header_hash}/raw"))
+                .await?
+                .bytes()
+                .await
+                .map_err(BlockSourceError::transient)?;
+            consensus::deserialize(&raw_block).map_err(BlockSourceError::persistent)
+        })
+    }
+
+    /// Gets the best block known by the API.
+    fn get_best_block(&self) -> AsyncBlockSourceResult<'_, (BlockHash, Option<u32>)> {
+        Box::pin(async move {
+            let tip = self.get_text("blocks/tip/hash").await?;
+            let tip_hash = BlockHash::from_str(tip.trim()).map_err(BlockSourceError::persistent)?;
+            Ok((tip_hash, None))
+        })
+    }
+}
+
+/// Result of a blocking request to the API.
+enum Response {
+    /// The request succeeded. Contains the response body.
+    Ok(String),
+    /// The API answered with a client error (e.g. the requested data was not found).
+    ClientError,
+    /// The API could not be reached (or failed to process the request).
+    Unreachable,
+}
+
+/// A [TxBroadcaster] backed by an Esplora-compatible REST API.
+///
+/// Requests are performed using a blocking client, which cannot be used from within an async context, so they are sent from
+/// a dedicated thread. As the [Carrier](crate::carrier::Carrier), sending transactions hangs until the API is reachable, while
+/// the rest of the queries are best effort.
+#[derive(Debug)]
+pub struct EsploraBroadcaster {
+    /// The base url of the API.
+    url: String,
+    /// A flag that indicates whether the API is reachable or not.
+    reachable: Arc<(Mutex<bool>, Condvar)>,
+    /// A map of receipts already issued by the [EsploraBroadcaster].
+    /// Used to prevent potentially re-sending the same transaction over and over.
+    issued_receipts: HashMap<Txid, ConfirmationStatus>,
+    /// The last known block height.
+    block_height: u32,
+}
+
+impl EsploraBroadcaster {
+    /// Creates a new [EsploraBroadcaster] instance.
+    pub fn new(
+        url: &str,
+        reachable: Arc<(Mutex<bool>, Condvar)>,
+        last_known_block_height: u32,
+    ) -> Self {
+        EsploraBroadcaster {
+            url: url.trim_end_matches('/').to_owned(),
+            reachable,
+            issued_receipts: HashMap::new(),
+            block_height: last_known_block_height,
+        }
+    }
+
+    /// Hangs the process until the API is reachable. If the API is already reachable it just passes trough.
+    fn hang_until_reachable(&self) {
+        let (lock, notifier) = &*self.reachable;
+        let mut reachable = lock.lock().unwrap();
+        while !*reachable {
+            reachable = notifier.wait(reachable).unwrap();
+        }
+    }
+
+    /// Checks whether the API is flagged as reachable.
+    fn is_reachable(&self) -> bool {
+        *self.reachable.0.lock().unwrap()
+    }
+
+    /// Flags the API as unreachable.
+    fn flag_unreachable(&self) {
+        let (lock, _) = &*self.reachable;
+        *lock.lock().unwrap() = false;
+    }
+
+    /// Performs a blocking GET request to the API from a dedicated thread.
+    fn get(&self, path: &str) -> Response {
+        let url = format!("{}/{path}", self.url);
+        thread::spawn(move || {
+            let response = reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+                .build()
+                .and_then(|client| client.get(&url).send());
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    response.text().map_or(Response::Unreachable, Response::Ok)
+                }
+                Ok(response) if response.status().is_client_error() => Response::ClientError,
+                _ => Response::Unreachable,
+            }
+        })
+        .join()
+        .unwrap_or(Response::Unreachable)
+    }
+}
+
+impl TxBroadcaster for EsploraBroadcaster {
+    fn block_height(&self) -> u32 {
+        self.block_height
+    }
+
+    fn update_height(&mut self, height: u32) {
+        self.block_height = height
+    }
+
+    fn clear_receipts(&mut self) {
+        if !self.issued_receipts.is_empty() {
+            self.issued_receipts = HashMap::new()
+        }
+    }
+
+    fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
+        self.hang_until_reachable();
+
+        if let Some(receipt) = self.issued_receipts.get(&tx.txid()) {
+            log::info!("Transaction already sent: {}", tx.txid());
+            return *receipt;
+        }
+
+        log::info!("Pushing transaction to the network: {}", tx.txid());
+        let backend = EsploraBackend::new(&self.url);
+        let (to_send, height) = (tx.clone(), self.block_height);
+        match thread::spawn(move || backend.send_transaction(&to_send, height))
+            .join()
+            .unwrap_or(None)
+        {
+            Some(receipt) => {
+                if let ConfirmationStatus::Rejected(e) = receipt {
+                    log::error!(
+                        "Transaction couldn't be broadcast: {} (reason: {e})",
+                        tx.txid()
+                    );
+                } else {
+                    log::info!("Transaction successfully delivered: {}", tx.txid());
+                }
+                self.issued_receipts.insert(tx.txid(), receipt);
+                receipt
+            }
+            None => {
+                log::error!(
+                    "Connection lost with {}, retrying request when possible",
+                    self.url
+                );
+                self.flag_unreachable();
+                self.send_transaction(tx)
+            }
+        }
+    }
+
+    /// Transactions are sent one by one, in order, given Esplora does not support package relay.
+    fn send_package(&mut self, txs: &[Transaction]) -> Vec<ConfirmationStatus> {
+        txs.iter().map(|tx| self.send_transaction(tx)).collect()
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> bool {
+        self.hang_until_reachable();
+
+        match self.get(&format!("tx/{txid}/status")) {
+            Response::Ok(status) => serde_json::from_str::<serde_json::Value>(&status)
+                .is_ok_and(|status| status["confirmed"] == false),
+            Response::ClientError => {
+                log::info!("Transaction not found in mempool: {txid}");
+                false
+            }
+            Response::Unreachable => {
+                log::error!(
+                    "Connection lost with {}, retrying request when possible",
+                    self.url
+                );
+                self.flag_unreachable();
+                self.in_mempool(txid)
+            }
+        }
+    }
+
+    fn get_mempool_txids(&self) -> Vec<Txid> {
+        if !self.is_reachable() {
+            return Vec::new();
+        }
+
+        match self.get("mempool/txids") {
+            Response::Ok(txids) => serde_json::from_str::<Vec<String>>(&txids)
+                .map(|txids| {
+                    txids
+                        .iter()
+                        .filter_map(|txid| Txid::from_str(txid).ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Response::ClientError => Vec::new(),
+            Response::Unreachable => {
+                log::error!("Connection lost with {}, skipping mempool query", self.url);
+                self.flag_unreachable();
+                Vec::new()
+            }
+        }
+    }
+
+    fn get_mempool_transaction(&self, txid: &Txid) -> Option<Transaction> {
+        if !self.is_reachable() {
+            return None;
+        }
+
+        match self.get(&format!("tx/{txid}/hex")) {
+            Response::Ok(raw_tx) => hex::decode(raw_tx.trim())
+                .ok()
+                .and_then(|raw_tx| consensus::deserialize(&raw_tx).ok()),
+            Response::ClientError => {
+                // The transaction may have left the mempool since the txids were queried.
+                log::debug!("Transaction not found in mempool: {txid}");
+                None
+            }
+            Response::Unreachable => {
+                log::error!("Connection lost with {}, skipping mempool query", self.url);
+                self.flag_unreachable();
+                None
+            }
+        }
+    }
+
+    /// Esplora only reports estimates for some confirmation targets, so the one for the closest target
+    /// below the requested one (or the lowest available target otherwise) is used.
+    fn estimate_fee_rate(&self, blocks: u16) -> Option<u64> {
+        if !self.is_reachable() {
+            return None;
+        }
+
+        match self.get("fee-estimates") {
+            // The fee rates are reported in sat/vB
+            Response::Ok(estimates) => {
+                let estimates: HashMap<String, f64> = serde_json::from_str(&estimates).ok()?;
+                let mut estimates: Vec<(u16, f64)> = estimates
+                    .into_iter()
+                    .filter_map(|(target, fee_rate)| Some((target.parse().ok()?, fee_rate)))
+                    .collect();
+                estimates.sort_by_key(|(target, _)| *target);
+                estimates
+                    .iter()
+                    .rev()
+                    .find(|(target, _)| *target <= blocks)
+                    .or_else(|| estimates.first())
+                    .map(|(_, fee_rate)| fee_rate.ceil() as u64)
+            }
+            Response::ClientError => None,
+            Response::Unreachable => {
+                log::error!("Connection lost with {}, skipping fee estimation", self.url);
+                self.flag_unreachable();
+                None
+            }
+        }
+    }
+
+    fn get_utxos(&self, address: &Address) -> Vec<Utxo> {
+        if !self.is_reachable() {
+            return Vec::new();
+        }
+
+        match self.get(&format!("address/{address}/utxo")) {
+            Response::Ok(utxos) => serde_json::from_str::<Vec<serde_json::Value>>(&utxos)
+                .unwrap_or_default()
+                .iter()
+                .filter(|utxo| utxo["status"]["confirmed"] == true)
+                .filter_map(|utxo| {
+                    Some(Utxo::new(
+                        OutPoint::new(
+                            Txid::from_str(utxo["txid"].as_str()?).ok()?,
+                            utxo["vout"].as_u64()? as u32,
+                        ),
+                        utxo["value"].as_u64()?,
+                    ))
+                })
+                .collect(),
+            Response::ClientError => Vec::new(),
+            Response::Unreachable => {
+                log::error!("Connection lost with {}, skipping utxo query", self.url);
+                self.flag_unreachable();
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lightning_block_sync::init::validate_best_block_header;
+    use lightning_block_sync::poll::{ChainPoller, ChainTip, Poll, Validate};
+
+    use teos_common::test_utils::TXID_HEX;
+
+    use crate::test_utils::{
+        get_random_tx, Blockchain, EsploraMock, MOCK_FEE_RATE, MOCK_UTXO_VALUE, START_HEIGHT,
+    };
+
+    const HEIGHT: u32 = START_HEIGHT as u32;
+
+    fn create_broadcaster(url: &str) -> EsploraBroadcaster {
+        EsploraBroadcaster::new(url, Arc::new((Mutex::new(true), Condvar::new())), HEIGHT)
+    }
+
+    #[tokio::test]
+    async fn test_new() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        assert!(EsploraClient::new(esplora_mock.url(), Network::Bitcoin)
+            .await
+            .is_ok());
+
+        // The client cannot be built if the API is running on a different network
+        assert!(matches!(
+            EsploraClient::new(esplora_mock.url(), Network::Testnet).await,
+            Err(e) if e.kind() == ErrorKind::InvalidInput
+        ));
+
+        // Or if it cannot be reached
+        assert!(matches!(
+            EsploraClient::new("http://127.0.0.1:1", Network::Bitcoin).await,
+            Err(e) if e.kind() == ErrorKind::NotConnected
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_best_block() {
+        let chain = Blockchain::default().with_height(10);
+        let tip = chain.tip();
+        let esplora_mock = EsploraMock::new(chain);
+        let client = EsploraClient::new(esplora_mock.url(), Network::Bitcoin)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.get_best_block().await.unwrap(),
+            (tip.header.block_hash(), None)
+        );
+
+        // The best block header can be validated (and its height is properly reported)
+        let best_block = validate_best_block_header(&client).await.unwrap();
+        assert_eq!(best_block.header, tip.header);
+        assert_eq!(best_block.height, tip.height);
+    }
+
+    #[tokio::test]
+    async fn test_get_header() {
+        let chain = Blockchain::default().with_height(10);
+        let esplora_mock = EsploraMock::new(chain.clone());
+        let client = EsploraClient::new(esplora_mock.url(), Network::Bitcoin)
+            .await
+            .unwrap();
+
+        let header_hash = chain.blocks[5].block_hash();
+        let header_data = client.get_header(&header_hash, None).await.unwrap();
+        assert_eq!(header_data.header, chain.blocks[5].header);
+        assert_eq!(header_data.height, 5);
+        assert!(header_data.validate(header_hash).is_ok());
+
+        // Unknown headers are reported as persistent errors
+        let unknown_hash = BlockHash::default();
+        assert!(matches!(
+            client.get_header(&unknown_hash, None).await,
+            Err(e) if e.kind() == lightning_block_sync::BlockSourceErrorKind::Persistent
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chainwork() {
+        // The chainwork of the served headers must be consistent for the poller to accept them
+        let mut chain = Blockchain::default().with_height(10);
+        let esplora_mock = EsploraMock::new(chain.clone());
+        let client = EsploraClient::new(esplora_mock.url(), Network::Bitcoin)
+            .await
+            .unwrap();
+        let poller = ChainPoller::new(&client, Network::Bitcoin);
+
+        // Walk back from the tip
+        let tip = validate_best_block_header(&client).await.unwrap();
+        let mut header = tip;
+        for _ in 0..5 {
+            header = poller.look_up_previous_header(&header).await.unwrap();
+        }
+        assert_eq!(header.header, chain.blocks[5].header);
+
+        // New blocks build on top of the known ones
+        let block = chain.generate(None);
+        esplora_mock
+            .chain
+            .lock()
+            .unwrap()
+            .blocks
+            .push(block.clone());
+        match poller.poll_chain_tip(tip).await.unwrap() {
+            ChainTip::Better(new_tip) => {
+                assert_eq!(new_tip.header, block.header);
+                assert_eq!(new_tip.chainwork, tip.chainwork + block.header.work());
+            }
+            _ => panic!("a better tip was expected"),
+        }
+
+        // And so do forks
+        let fork = chain.fork_at_height(6);
+        *esplora_mock.chain.lock().unwrap() = fork.clone();
+        let fork_tip = fork.blocks.last().unwrap().block_hash();
+        let mut header = client
+            .get_header(&fork_tip, None)
+            .await
+            .unwrap()
+            .validate(fork_tip)
+            .unwrap();
+        while header.height > 6 {
+            header = poller.look_up_previous_header(&header).await.unwrap();
+        }
+        assert_eq!(header.header, chain.blocks[6].header);
+    }
+
+    #[tokio::test]
+    async fn test_get_block() {
+        let chain = Blockchain::default().with_height_and_txs(10, 5);
+        let esplora_mock = EsploraMock::new(chain.clone());
+        let client = EsploraClient::new(esplora_mock.url(), Network::Bitcoin)
+            .await
+            .unwrap();
+
+        let block = &chain.blocks[7];
+        assert_eq!(&client.get_block(&block.block_hash()).await.unwrap(), block);
+
+        // Unknown blocks are reported as persistent errors
+        assert!(matches!(
+            client.get_block(&BlockHash::default()).await,
+            Err(e) if e.kind() == lightning_block_sync::BlockSourceErrorKind::Persistent
+        ));
+    }
+
+    #[test]
+    fn test_send_transaction() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let mut broadcaster = create_broadcaster(esplora_mock.url());
+
+        let tx = get_random_tx();
+        assert_eq!(
+            broadcaster.send_transaction(&tx),
+            ConfirmationStatus::InMempoolSince(HEIGHT)
+        );
+        assert!(esplora_mock
+            .mempool
+            .lock()
+            .unwrap()
+            .contains_key(&tx.txid()));
+
+        // The receipt is cached, so the transaction is not sent again
+        esplora_mock.mempool.lock().unwrap().clear();
+        assert_eq!(
+            broadcaster.send_transaction(&tx),
+            ConfirmationStatus::InMempoolSince(HEIGHT)
+        );
+        assert!(esplora_mock.mempool.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_send_transaction_connection_error() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let reachable = Arc::new((Mutex::new(false), Condvar::new()));
+        let mut broadcaster =
+            EsploraBroadcaster::new(esplora_mock.url(), reachable.clone(), HEIGHT);
+        let delay = std::time::Duration::new(3, 0);
+
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let (reachable, notifier) = &*reachable;
+            *reachable.lock().unwrap() = true;
+            notifier.notify_all();
+        });
+
+        let before = std::time::Instant::now();
+        broadcaster.send_transaction(&get_random_tx());
+
+        // Check the request has hanged for ~delay
+        assert_eq!(
+            (std::time::Instant::now() - before).as_secs(),
+            delay.as_secs()
+        );
+    }
+
+    #[test]
+    fn test_send_package() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let mut broadcaster = create_broadcaster(esplora_mock.url());
+
+        let txs = vec![get_random_tx(), get_random_tx()];
+        assert_eq!(
+            broadcaster.send_package(&txs),
+            vec![ConfirmationStatus::InMempoolSince(HEIGHT); 2]
+        );
+        for tx in txs.iter() {
+            assert!(esplora_mock
+                .mempool
+                .lock()
+                .unwrap()
+                .contains_key(&tx.txid()));
+        }
+    }
+
+    #[test]
+    fn test_in_mempool() {
+        let mut chain = Blockchain::default().with_height(10);
+        let confirmed_tx = get_random_tx();
+        chain.generate(Some(vec![confirmed_tx.clone()]));
+        let esplora_mock = EsploraMock::new(chain);
+        let broadcaster = create_broadcaster(esplora_mock.url());
+
+        let tx = get_random_tx();
+        esplora_mock
+            .mempool
+            .lock()
+            .unwrap()
+            .insert(tx.txid(), tx.clone());
+
+        assert!(broadcaster.in_mempool(&tx.txid()));
+        // Neither confirmed nor unknown transactions are in the mempool
+        assert!(!broadcaster.in_mempool(&confirmed_tx.txid()));
+        assert!(!broadcaster.in_mempool(&get_random_tx().txid()));
+    }
+
+    #[test]
+    fn test_get_mempool_txids() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let broadcaster = create_broadcaster(esplora_mock.url());
+        assert!(broadcaster.get_mempool_txids().is_empty());
+
+        let tx = get_random_tx();
+        esplora_mock
+            .mempool
+            .lock()
+            .unwrap()
+            .insert(tx.txid(), tx.clone());
+        assert_eq!(broadcaster.get_mempool_txids(), vec![tx.txid()]);
+        assert_eq!(broadcaster.get_mempool_transaction(&tx.txid()), Some(tx));
+        assert_eq!(
+            broadcaster.get_mempool_transaction(&get_random_tx().txid()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mempool_queries_connection_error() {
+        // Mempool queries do not hang, but flag the API as unreachable
+        let broadcaster = create_broadcaster("http://127.0.0.1:1");
+        assert!(broadcaster.get_mempool_txids().is_empty());
+        assert!(!broadcaster.is_reachable());
+
+        // Once flagged, the API is not queried until it is back
+        assert_eq!(
+            broadcaster.get_mempool_transaction(&get_random_tx().txid()),
+            None
+        );
+        assert_eq!(broadcaster.estimate_fee_rate(6), None);
+    }
+
+    #[test]
+    fn test_estimate_fee_rate() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let broadcaster = create_broadcaster(esplora_mock.url());
+
+        assert_eq!(broadcaster.estimate_fee_rate(6), Some(MOCK_FEE_RATE));
+        // The closest target below the requested one is used (rounding up)
+        assert_eq!(broadcaster.estimate_fee_rate(12), Some(MOCK_FEE_RATE));
+        assert_eq!(broadcaster.estimate_fee_rate(5), Some(43));
+        assert_eq!(broadcaster.estimate_fee_rate(1), Some(88));
+        // Or the lowest target if there is none
+        assert_eq!(broadcaster.estimate_fee_rate(0), Some(88));
+    }
+
+    #[test]
+    fn test_get_utxos() {
+        let esplora_mock = EsploraMock::new(Blockchain::default().with_height(10));
+        let broadcaster = create_broadcaster(esplora_mock.url());
+        let address = Address::p2wpkh(
+            &bitcoin::PublicKey::new(teos_common::cryptography::get_random_keypair().1),
+            Network::Bitcoin,
+        )
+        .unwrap();
+
+        // Only confirmed outputs are returned
+        assert_eq!(
+            broadcaster.get_utxos(&address),
+            vec![Utxo::new(
+                OutPoint::new(Txid::from_str(TXID_HEX).unwrap(), 0),
+                MOCK_UTXO_VALUE
+            )]
+        );
+    }
+}
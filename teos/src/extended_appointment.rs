@@ -12,7 +12,7 @@ use teos_common::UserId;
 /// Unique identifier used to identify appointments.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub(crate) struct UUID([u8; 20]);
+pub struct UUID([u8; 20]);
 
 impl UUID {
     /// Creates a new [UUID].
@@ -49,7 +49,7 @@ impl std::fmt::Display for UUID {
 /// Notice [ExtendedAppointment]s are not kept in memory but persisted on disk. The [Watcher](crate::watcher::Watcher)
 /// keeps [AppointmentSummary] instead.
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub(crate) struct ExtendedAppointment {
+pub struct ExtendedAppointment {
     /// The underlying appointment extended by [ExtendedAppointment].
     pub inner: Appointment,
     /// The user this [Appointment] belongs to.
@@ -104,7 +104,7 @@ impl ExtendedAppointment {
     }
 
     /// Computes the summary of the [ExtendedAppointment].
-    pub fn get_summary(&self) -> AppointmentSummary {
+    pub(crate) fn get_summary(&self) -> AppointmentSummary {
         AppointmentSummary {
             locator: self.locator(),
             user_id: self.user_id,
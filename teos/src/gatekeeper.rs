@@ -5,20 +5,24 @@ use std::iter::FromIterator;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
+use serde::{Deserialize, Serialize};
+
 use lightning::chain;
 
 use teos_common::appointment::compute_appointment_slots;
 use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
 use teos_common::cryptography;
-use teos_common::receipts::RegistrationReceipt;
+use teos_common::receipts::{RegistrationReceipt, RenewalReceipt, TopUpReceipt};
 use teos_common::UserId;
 
-use crate::dbm::DBM;
+use crate::dbm::Storage;
 use crate::extended_appointment::{ExtendedAppointment, UUID};
+use crate::payments::{unix_time, Invoice, PaymentVerifier};
+use crate::rate_limiter::{RateLimited, RateLimiter};
 
 /// Data regarding a user subscription with the tower.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct UserInfo {
+pub struct UserInfo {
     /// Number of appointment slots available for a given user.
     pub(crate) available_slots: u32,
     /// Block height where the user subscription starts.
@@ -27,6 +31,8 @@ pub(crate) struct UserInfo {
     pub(crate) subscription_expiry: u32,
     /// Map of appointment ids and the how many slots they take from the subscription.
     pub(crate) appointments: HashMap<UUID, u32>,
+    /// Name of the subscription tier the user is registered to.
+    pub(crate) tier: String,
 }
 
 impl UserInfo {
@@ -37,6 +43,7 @@ impl UserInfo {
             subscription_start,
             subscription_expiry,
             appointments: HashMap::new(),
+            tier: String::new(),
         }
     }
 
@@ -52,17 +59,136 @@ impl UserInfo {
             subscription_start,
             subscription_expiry,
             appointments,
+            tier: String::new(),
+        }
+    }
+
+    /// Sets the subscription tier of the user.
+    pub fn with_tier(mut self, tier: String) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    /// Gets the block height from which the subscription can be renewed, given the duration of a renewal.
+    ///
+    /// Subscriptions can only be renewed once they have less than a renewal worth of time left (or have expired), so they
+    /// can never be extended further than two renewals from the current block.
+    fn renewable_from(&self, duration: u32) -> u32 {
+        std::cmp::min(
+            self.subscription_expiry.saturating_sub(duration) + 1,
+            self.subscription_expiry,
+        )
+    }
+
+    /// Extends the subscription by a given duration. Subscriptions that have already expired (but are still within the
+    /// renewal grace period) start over from the given block height.
+    fn extend_subscription(&mut self, duration: u32, block_height: u32) {
+        if block_height >= self.subscription_expiry {
+            self.subscription_start = block_height;
+            self.subscription_expiry = block_height.saturating_add(duration);
+        } else {
+            self.subscription_expiry = self.subscription_expiry.saturating_add(duration);
+        }
+    }
+}
+
+/// Maximum length of subscription tier names, in bytes.
+pub const MAX_TIER_NAME_LEN: usize = 32;
+
+/// How often (in seconds) the pending registrations whose invoice has expired are resolved.
+pub const EXPIRED_REGISTRATIONS_SWEEP_DELTA: u64 = 60;
+
+fn default_max_blob_size() -> usize {
+    ENCRYPTED_BLOB_MAX_SIZE
+}
+
+/// A subscription plan offered by the tower.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionTier {
+    /// Name the tier is referred by. The default subscription of towers that do not define any tiers is unnamed.
+    pub name: String,
+    /// Number of slots a subscription to the tier gets.
+    pub slots: u32,
+    /// Duration of a subscription to the tier, in blocks.
+    pub duration: u32,
+    /// Price of a subscription to the tier, in millisatoshis. Subscriptions are free if zero.
+    #[serde(default)]
+    pub price: u64,
+    /// Encrypted blob size covered by a slot, in bytes. Bigger appointments take multiple slots.
+    #[serde(default = "default_max_blob_size")]
+    pub max_blob_size: usize,
+}
+
+impl SubscriptionTier {
+    /// Creates a new [SubscriptionTier] instance.
+    pub fn new(name: String, slots: u32, duration: u32, price: u64, max_blob_size: usize) -> Self {
+        SubscriptionTier {
+            name,
+            slots,
+            duration,
+            price,
+            max_blob_size,
+        }
+    }
+}
+
+/// Subscription operations users can be charged for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionOperation {
+    /// Registering to a tier. For users already registered, this tops up the subscription (and renews it, if possible).
+    Register = 0,
+    /// Extending the subscription period.
+    Renew = 1,
+    /// Adding slots to the subscription.
+    TopUp = 2,
+}
+
+impl From<u8> for SubscriptionOperation {
+    fn from(x: u8) -> Self {
+        match x {
+            1 => SubscriptionOperation::Renew,
+            2 => SubscriptionOperation::TopUp,
+            _ => SubscriptionOperation::Register,
         }
     }
 }
 
+/// A subscription operation waiting to be paid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRegistration {
+    /// Name of the subscription tier being paid for.
+    pub(crate) tier: String,
+    /// The operation being paid for.
+    pub(crate) operation: SubscriptionOperation,
+    /// The invoice to be paid.
+    pub(crate) invoice: Invoice,
+}
+
+/// An admin action performed over a user, as recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLogEntry {
+    /// Unix time (in seconds) at which the action was performed.
+    pub(crate) timestamp: u64,
+    /// The user the action was performed over.
+    pub(crate) user_id: UserId,
+    /// The performed action.
+    pub(crate) action: String,
+    /// Human readable details of the action.
+    pub(crate) details: String,
+}
+
 /// Error raised if the user cannot be authenticated.
 #[derive(Debug, PartialEq)]
 pub(crate) struct AuthenticationFailure<'a>(&'a str);
 
 /// Error raised if the user subscription has not enough slots to fit a new appointment.
+///
+/// Reports the slots the appointment would have required and the slots the user had available.
 #[derive(Debug, PartialEq)]
-pub(crate) struct NotEnoughSlots;
+pub(crate) struct NotEnoughSlots {
+    pub required_slots: u32,
+    pub available_slots: u32,
+}
 
 /// Error raised if the user subscription slots limit has been reached.
 ///
@@ -70,6 +196,79 @@ pub(crate) struct NotEnoughSlots;
 #[derive(Debug, PartialEq)]
 pub(crate) struct MaxSlotsReached;
 
+/// Reasons why a user registration (or renewal / top up) cannot be completed.
+#[derive(Debug, PartialEq)]
+pub(crate) enum RegistrationFailure {
+    /// The user subscription slots limit has been reached.
+    MaxSlotsReached,
+    /// The registration needs to be paid. Contains the invoice to be paid.
+    PaymentRequired(Invoice),
+    /// Payments cannot be processed at the moment.
+    PaymentsUnavailable,
+    /// The requested subscription tier is not offered by the tower.
+    UnknownTier(String),
+    /// The user is registered to a different subscription tier. Contains the user's current tier.
+    TierMismatch(String),
+    /// The user is not registered to the tower.
+    UserNotFound,
+    /// The user subscription has expired. Contains the block height it expired at.
+    SubscriptionExpired(u32),
+    /// The user subscription cannot be renewed yet. Contains the block height from which it can be renewed.
+    RenewalTooEarly(u32),
+    /// The user has made too many requests. Contains the seconds until a new one will be accepted.
+    RateLimited(u64),
+    /// The user has been banned from the tower.
+    UserBanned,
+}
+
+impl From<MaxSlotsReached> for RegistrationFailure {
+    fn from(_: MaxSlotsReached) -> Self {
+        RegistrationFailure::MaxSlotsReached
+    }
+}
+
+/// Reasons why an admin action over a user cannot be completed.
+#[derive(Debug, PartialEq)]
+pub(crate) enum UserManagementFailure {
+    /// The user is not registered to the tower.
+    UserNotFound,
+    /// The user has already been banned.
+    AlreadyBanned,
+    /// The user has not been banned.
+    NotBanned,
+    /// The user subscription slots limit would be exceeded.
+    MaxSlotsReached,
+    /// The subscription expiry would be in the past. Contains the current block height.
+    ExpiryInThePast(u32),
+    /// Some of the user appointments are being handed to the Responder.
+    PendingTriggers,
+}
+
+impl From<RateLimited> for RegistrationFailure {
+    fn from(e: RateLimited) -> Self {
+        RegistrationFailure::RateLimited(e.0)
+    }
+}
+
+/// Settings of paid subscriptions.
+#[derive(Debug)]
+struct PaymentSettings {
+    /// The component in charge of issuing invoices and checking whether they have been paid.
+    verifier: Arc<dyn PaymentVerifier>,
+    /// Time issued invoices can be paid for, in seconds.
+    invoice_expiry: u32,
+}
+
+/// Outcome of charging a user for a subscription operation (see [Gatekeeper::charge]).
+#[derive(Debug, PartialEq)]
+enum Charge<'a> {
+    /// The operation is free, so it is up to the caller to complete it over the given tier.
+    Free(&'a SubscriptionTier),
+    /// The operation has been paid for and completed, either by this call or by someone else checking the same
+    /// payment (e.g. [Gatekeeper::resolve_expired_registrations]).
+    Completed,
+}
+
 /// Component in charge of managing access to the tower resources.
 ///
 /// The [Gatekeeper] keeps track of user subscriptions and allow users to interact with the tower based on it.
@@ -81,38 +280,160 @@ pub(crate) struct MaxSlotsReached;
 pub struct Gatekeeper {
     /// last known block header by the [Gatekeeper].
     last_known_block_height: AtomicU32,
-    /// Number of slots new subscriptions get by default.
-    subscription_slots: u32,
-    /// Expiry time new subscription get by default, in blocks (starting from the block the subscription is requested).
-    subscription_duration: u32,
+    /// Subscription tiers offered by the tower. The first one is given to users that do not pick any.
+    tiers: Vec<SubscriptionTier>,
     /// Grace period given to renew subscriptions, in blocks.
     expiry_delta: u32,
     /// Map of users registered within the tower.
     registered_users: Mutex<HashMap<UserId, UserInfo>>,
-    /// A [DBM] (database manager) instance. Used to persist appointment data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// Settings of paid subscriptions. Subscriptions are given for free if not set.
+    payments: Option<PaymentSettings>,
+    /// Map of users waiting for their registrations to be paid, and the invoices they have been issued.
+    pending_registrations: Mutex<HashMap<UserId, Vec<PendingRegistration>>>,
+    /// Limits the requests each user can make. Users are not limited if not set.
+    rate_limiter: Option<RateLimiter<UserId>>,
+    /// Limits the subscription requests (registrations, renewals and top ups) made for each user. These are not
+    /// authenticated, so they are kept apart from [rate_limiter](Self::rate_limiter) so they cannot be used to use up
+    /// someone else's requests. Not limited if not set.
+    subscription_rate_limiter: Option<RateLimiter<UserId>>,
+    /// Set of users banned from the tower by the admin.
+    banned_users: Mutex<HashSet<UserId>>,
+    /// A [Storage] (database manager) instance. Used to persist appointment data into disk.
+    dbm: Arc<Mutex<dyn Storage>>,
 }
 
 impl Gatekeeper {
     /// Creates a new [Gatekeeper] instance.
+    ///
+    /// Subscriptions are given `subscription_slots` slots for `subscription_duration` blocks, unless a set of
+    /// subscription tiers is defined using [Gatekeeper::with_tiers].
     pub fn new(
         last_known_block_height: u32,
         subscription_slots: u32,
         subscription_duration: u32,
         expiry_delta: u32,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
     ) -> Self {
         let registered_users = dbm.lock().unwrap().load_all_users();
+        let pending_registrations = dbm.lock().unwrap().load_pending_registrations();
+        let banned_users = dbm.lock().unwrap().load_banned_users();
         Gatekeeper {
             last_known_block_height: AtomicU32::new(last_known_block_height),
-            subscription_slots,
-            subscription_duration,
+            tiers: vec![SubscriptionTier::new(
+                String::new(),
+                subscription_slots,
+                subscription_duration,
+                0,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            )],
             expiry_delta,
             registered_users: Mutex::new(registered_users),
+            payments: None,
+            pending_registrations: Mutex::new(pending_registrations),
+            rate_limiter: None,
+            subscription_rate_limiter: None,
+            banned_users: Mutex::new(banned_users),
             dbm,
         }
     }
 
+    /// Replaces the default subscription by a set of subscription tiers. The first one is given to users that do
+    /// not pick any. An empty set keeps the default subscription (configs defining one are rejected beforehand).
+    ///
+    /// The slots taken by the appointments of already registered users are recomputed based on their tier.
+    pub fn with_tiers(mut self, tiers: Vec<SubscriptionTier>) -> Self {
+        if !tiers.is_empty() {
+            self.tiers = tiers;
+        }
+
+        let dbm = self.dbm.lock().unwrap();
+        for (user_id, user_info) in self.registered_users.get_mut().unwrap().iter_mut() {
+            match self.tiers.iter().find(|tier| tier.name == user_info.tier) {
+                Some(tier) => {
+                    if tier.max_blob_size != ENCRYPTED_BLOB_MAX_SIZE {
+                        user_info.appointments =
+                            dbm.load_user_appointments(*user_id, tier.max_blob_size);
+                    }
+                }
+                None => log::warn!(
+                    "{user_id} is registered to a tier that is not offered anymore ({})",
+                    user_info.tier
+                ),
+            }
+        }
+        drop(dbm);
+
+        self
+    }
+
+    /// Makes users pay for their subscriptions (given the price of the tier they register to). Registrations are
+    /// only completed once the invoice issued by the [PaymentVerifier] has been settled.
+    pub fn with_payments(
+        mut self,
+        verifier: Arc<dyn PaymentVerifier>,
+        invoice_expiry: u32,
+    ) -> Self {
+        self.payments = Some(PaymentSettings {
+            verifier,
+            invoice_expiry,
+        });
+        self
+    }
+
+    /// Limits the requests each user can make to `rate` requests per minute, allowing bursts of up to `burst`
+    /// requests. Requests over the limit are rejected.
+    ///
+    /// Subscription requests are limited the same way, but counted separately from the rest.
+    pub fn with_rate_limit(mut self, rate: u32, burst: u32) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(rate, burst));
+        self.subscription_rate_limiter = Some(RateLimiter::new(rate, burst));
+        self
+    }
+
+    /// Gets the subscription tiers offered by the tower.
+    pub(crate) fn get_tiers(&self) -> &[SubscriptionTier] {
+        &self.tiers
+    }
+
+    /// Gets a subscription tier given its name.
+    fn get_tier(&self, name: &str) -> Option<&SubscriptionTier> {
+        self.tiers.iter().find(|tier| tier.name == name)
+    }
+
+    /// Gets the encrypted blob size covered by a slot for a given tier. Falls back to [ENCRYPTED_BLOB_MAX_SIZE] if the
+    /// tier is not offered anymore.
+    fn get_slot_size(&self, tier: &str) -> usize {
+        self.get_tier(tier)
+            .map_or(ENCRYPTED_BLOB_MAX_SIZE, |tier| tier.max_blob_size)
+    }
+
+    /// Gets the tier a user is registering to. Users that do not pick any keep their current tier, if already registered,
+    /// or are given the default one otherwise. Registered users cannot switch tiers.
+    fn get_registration_tier(
+        &self,
+        user_id: UserId,
+        tier: &str,
+    ) -> Result<&SubscriptionTier, RegistrationFailure> {
+        let current_tier = self
+            .registered_users
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map(|user_info| user_info.tier.clone());
+
+        let name = match current_tier {
+            Some(current_tier) if tier.is_empty() => current_tier,
+            Some(current_tier) if current_tier != tier => {
+                return Err(RegistrationFailure::TierMismatch(current_tier))
+            }
+            None if tier.is_empty() => return Ok(&self.tiers[0]),
+            _ => tier.to_owned(),
+        };
+
+        self.get_tier(&name)
+            .ok_or(RegistrationFailure::UnknownTier(name))
+    }
+
     /// Returns whether the [Gatekeeper] has been created from scratch (fresh) or from backed-up data.
     pub fn is_fresh(&self) -> bool {
         self.registered_users.lock().unwrap().is_empty()
@@ -152,44 +473,467 @@ impl Gatekeeper {
                 .map_err(|_| AuthenticationFailure("Wrong message or signature."))?,
         );
 
-        if self.registered_users.lock().unwrap().contains_key(&user_id) {
+        if self.is_banned(user_id) {
+            Err(AuthenticationFailure("User banned."))
+        } else if self.registered_users.lock().unwrap().contains_key(&user_id) {
             Ok(user_id)
         } else {
             Err(AuthenticationFailure("User not found."))
         }
     }
 
-    /// Adds a new user to the tower (or updates its subscription if already registered).
+    /// Consumes one of the requests a user is allowed to make, if rate limiting is enabled.
+    ///
+    /// This should be called once the user has been identified (e.g. after [Gatekeeper::authenticate_user]).
+    pub(crate) fn check_rate_limit(&self, user_id: UserId) -> Result<(), RateLimited> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter.check(user_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Consumes one of the subscription requests that can be made for a user, if rate limiting is enabled.
+    ///
+    /// Subscription requests are not signed, so anyone can make them on behalf of a user. They are therefore
+    /// counted apart from the ones checked by [Gatekeeper::check_rate_limit].
+    fn check_subscription_rate_limit(&self, user_id: UserId) -> Result<(), RateLimited> {
+        match &self.subscription_rate_limiter {
+            Some(rate_limiter) => rate_limiter.check(user_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks whether a user has been banned from the tower.
+    pub(crate) fn is_banned(&self, user_id: UserId) -> bool {
+        self.banned_users.lock().unwrap().contains(&user_id)
+    }
+
+    /// Fails if the user has been banned, so banned users cannot subscribe to the tower.
+    fn check_banned(&self, user_id: UserId) -> Result<(), RegistrationFailure> {
+        if self.is_banned(user_id) {
+            Err(RegistrationFailure::UserBanned)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Registers a user to a given subscription tier of the tower.
+    ///
+    /// Users already registered get the slots of their tier added to the subscription, which is also renewed if possible
+    /// (see [Gatekeeper::renew]). Registering again does not extend the subscription otherwise.
+    ///
+    /// If the tier is paid, the user is handed an invoice, and the registration is completed once it has been
+    /// settled. Calls made while the invoice is pending return the same invoice, unless it has expired. Picking a
+    /// different tier issues a new invoice, but the previous one is still honored if paid (see [Gatekeeper::charge]).
+    pub(crate) fn register(
+        &self,
+        user_id: UserId,
+        tier: &str,
+    ) -> Result<RegistrationReceipt, RegistrationFailure> {
+        self.check_subscription_rate_limit(user_id)?;
+        self.check_banned(user_id)?;
+        let tier = self.get_registration_tier(user_id, tier)?;
+
+        // Do not charge for subscriptions that cannot be granted
+        if let Some(user_info) = self.registered_users.lock().unwrap().get(&user_id) {
+            if user_info.available_slots.checked_add(tier.slots).is_none() {
+                return Err(RegistrationFailure::MaxSlotsReached);
+            }
+        }
+
+        match self.charge(user_id, tier, SubscriptionOperation::Register)? {
+            Charge::Free(tier) => Ok(self.add_update_user(user_id, tier)?),
+            Charge::Completed => {
+                let user_info = self.get_paid_user_info(user_id)?;
+                Ok(RegistrationReceipt::new(
+                    user_id,
+                    user_info.available_slots,
+                    user_info.subscription_start,
+                    user_info.subscription_expiry,
+                    user_info.tier,
+                ))
+            }
+        }
+    }
+
+    /// Renews the subscription of a user, extending it by the duration of their tier. Slots are left untouched.
+    ///
+    /// Subscriptions can be renewed once they have less than a renewal worth of time left, including those that have
+    /// already expired but are still within the renewal grace period ([expiry_delta](Self::expiry_delta)). The latter
+    /// start over from the current block. Renewals are charged as registrations are (see [Gatekeeper::register]).
+    pub(crate) fn renew(&self, user_id: UserId) -> Result<RenewalReceipt, RegistrationFailure> {
+        self.check_subscription_rate_limit(user_id)?;
+        self.check_banned(user_id)?;
+        let tier = self.get_user_tier(user_id)?;
+
+        let block_count = self.last_known_block_height.load(Ordering::Acquire);
+        let renewable_from = self
+            .registered_users
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .ok_or(RegistrationFailure::UserNotFound)?
+            .renewable_from(tier.duration);
+        if block_count < renewable_from {
+            return Err(RegistrationFailure::RenewalTooEarly(renewable_from));
+        }
+
+        match self.charge(user_id, tier, SubscriptionOperation::Renew)? {
+            Charge::Free(tier) => self.renew_user(user_id, tier),
+            Charge::Completed => {
+                let user_info = self.get_paid_user_info(user_id)?;
+                Ok(RenewalReceipt::new(
+                    user_id,
+                    user_info.subscription_start,
+                    user_info.subscription_expiry,
+                ))
+            }
+        }
+    }
+
+    /// Tops up the subscription of a user, adding the slots of their tier. The subscription period is left untouched.
+    ///
+    /// Expired subscriptions cannot be topped up, they need to be renewed first. Top ups are charged as registrations
+    /// are (see [Gatekeeper::register]).
+    pub(crate) fn top_up(&self, user_id: UserId) -> Result<TopUpReceipt, RegistrationFailure> {
+        self.check_subscription_rate_limit(user_id)?;
+        self.check_banned(user_id)?;
+        let tier = self.get_user_tier(user_id)?;
+
+        let block_count = self.last_known_block_height.load(Ordering::Acquire);
+        if let Some(user_info) = self.registered_users.lock().unwrap().get(&user_id) {
+            if block_count >= user_info.subscription_expiry {
+                return Err(RegistrationFailure::SubscriptionExpired(
+                    user_info.subscription_expiry,
+                ));
+            }
+            // Do not charge for slots that cannot be granted
+            if user_info.available_slots.checked_add(tier.slots).is_none() {
+                return Err(RegistrationFailure::MaxSlotsReached);
+            }
+        }
+
+        match self.charge(user_id, tier, SubscriptionOperation::TopUp)? {
+            Charge::Free(tier) => self.top_up_user(user_id, tier),
+            Charge::Completed => {
+                let user_info = self.get_paid_user_info(user_id)?;
+                Ok(TopUpReceipt::new(
+                    user_id,
+                    user_info.available_slots,
+                    user_info.subscription_expiry,
+                ))
+            }
+        }
+    }
+
+    /// Gets the tier of a registered user.
+    fn get_user_tier(&self, user_id: UserId) -> Result<&SubscriptionTier, RegistrationFailure> {
+        let tier = self
+            .registered_users
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map(|user_info| user_info.tier.clone())
+            .ok_or(RegistrationFailure::UserNotFound)?;
+
+        self.get_tier(&tier)
+            .ok_or(RegistrationFailure::UnknownTier(tier))
+    }
+
+    /// Gets the info of a user whose paid operation has been completed, from which its receipt is built.
+    fn get_paid_user_info(&self, user_id: UserId) -> Result<UserInfo, RegistrationFailure> {
+        self.get_user_info(user_id)
+            .ok_or(RegistrationFailure::UserNotFound)
+    }
+
+    /// Charges a user for a subscription operation over a given tier. Paid operations are completed here once the
+    /// invoice issued for them has been settled, so the same payment cannot be honored twice no matter who checks it
+    /// first. Free ones are left to the caller. The invoice to be paid is returned otherwise.
+    ///
+    /// Every invoice issued to a user is kept until it is paid or expires, and paid ones are always honored: if the
+    /// user comes back requesting a different operation, the paid ones are completed and the user is charged for the
+    /// new one. Subscription requests are not authenticated, so this prevents anyone from discarding an invoice a user
+    /// is about to pay by requesting something else on their behalf. At most one invoice is pending for each
+    /// operation and tier, given requesting the same thing again returns the pending one.
+    fn charge<'a>(
+        &'a self,
+        user_id: UserId,
+        tier: &'a SubscriptionTier,
+        operation: SubscriptionOperation,
+    ) -> Result<Charge<'a>, RegistrationFailure> {
+        let payments = match &self.payments {
+            Some(payments) => payments,
+            None => return Ok(Charge::Free(tier)),
+        };
+
+        // The pending registrations are not kept locked while the payment backend is queried, so a slow backend does
+        // not stall the requests of every other user
+        let pending_registrations = self
+            .pending_registrations
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default();
+        let now = unix_time();
+        let mut completed = None;
+        let mut pending_invoice = None;
+        for registration in pending_registrations {
+            match payments
+                .verifier
+                .is_settled(&registration.invoice.payment_hash)
+            {
+                Ok(true) => {
+                    let tier = self.get_tier(&registration.tier).ok_or_else(|| {
+                        RegistrationFailure::UnknownTier(registration.tier.clone())
+                    })?;
+                    // The registration may have been completed by someone else in the meantime, in which case the
+                    // user gets the receipt of the subscription as it is instead of being charged again
+                    let result = self
+                        .complete_pending_registration(user_id, &registration, tier)
+                        .unwrap_or(Ok(()));
+                    if registration.operation == operation && completed.is_none() {
+                        completed = Some(result);
+                    } else if let Err(e) = result {
+                        log::error!(
+                            "Cannot complete the {:?} of {user_id}: {:?}",
+                            registration.operation,
+                            e
+                        );
+                    }
+                }
+                Ok(false) if !registration.invoice.has_expired(now) => {
+                    if registration.operation == operation && registration.tier == tier.name {
+                        pending_invoice = Some(registration.invoice);
+                    }
+                }
+                // The invoice cannot be paid anymore
+                Ok(false) => {
+                    self.take_pending_registration(user_id, &registration);
+                }
+                Err(e) => {
+                    log::error!("Cannot check the payment of {user_id}: {e}");
+                    return Err(RegistrationFailure::PaymentsUnavailable);
+                }
+            }
+        }
+
+        if let Some(result) = completed {
+            return result.map(|_| Charge::Completed);
+        }
+        if let Some(invoice) = pending_invoice {
+            return Err(RegistrationFailure::PaymentRequired(invoice));
+        }
+        // Free tiers need no invoice. Any pending one is kept, so it is still honored if paid
+        if tier.price == 0 {
+            return Ok(Charge::Free(tier));
+        }
+
+        let description = match operation {
+            SubscriptionOperation::Register => format!(
+                "Watchtower subscription: {} slots for {} blocks",
+                tier.slots, tier.duration
+            ),
+            SubscriptionOperation::Renew => {
+                format!("Watchtower subscription renewal: {} blocks", tier.duration)
+            }
+            SubscriptionOperation::TopUp => {
+                format!("Watchtower subscription top up: {} slots", tier.slots)
+            }
+        };
+        let invoice = payments
+            .verifier
+            .create_invoice(tier.price, &description, payments.invoice_expiry)
+            .map_err(|e| {
+                log::error!("Cannot create an invoice for {user_id}: {e}");
+                RegistrationFailure::PaymentsUnavailable
+            })?;
+        let registration = PendingRegistration {
+            tier: tier.name.clone(),
+            operation,
+            invoice: invoice.clone(),
+        };
+        let mut pending_registrations = self.pending_registrations.lock().unwrap();
+        // The invoice is not handed to the user unless it can be kept track of, otherwise the payment could be lost
+        self.dbm
+            .lock()
+            .unwrap()
+            .store_pending_registration(user_id, &registration)
+            .map_err(|e| {
+                log::error!("Cannot store the pending registration of {user_id}: {e:?}");
+                RegistrationFailure::PaymentsUnavailable
+            })?;
+        pending_registrations
+            .entry(user_id)
+            .or_default()
+            .push(registration);
+
+        Err(RegistrationFailure::PaymentRequired(invoice))
+    }
+
+    /// Completes a subscription operation that has already been paid for, discarding its receipt.
+    fn complete_operation(
+        &self,
+        user_id: UserId,
+        tier: &SubscriptionTier,
+        operation: SubscriptionOperation,
+    ) -> Result<(), RegistrationFailure> {
+        match operation {
+            SubscriptionOperation::Register => self.add_update_user(user_id, tier).map(|_| ())?,
+            SubscriptionOperation::Renew => self.renew_user(user_id, tier).map(|_| ())?,
+            SubscriptionOperation::TopUp => self.top_up_user(user_id, tier).map(|_| ())?,
+        };
+
+        Ok(())
+    }
+
+    /// Completes the operation of a paid registration of a user, if still pending. Returns the outcome of the operation,
+    /// or [None] if the registration has already been resolved.
+    ///
+    /// The pending registrations are kept locked until the operation is completed, so anyone checking the same payment
+    /// meanwhile finds the subscription already updated.
+    fn complete_pending_registration(
+        &self,
+        user_id: UserId,
+        registration: &PendingRegistration,
+        tier: &SubscriptionTier,
+    ) -> Option<Result<(), RegistrationFailure>> {
+        let mut pending_registrations = self.pending_registrations.lock().unwrap();
+        if !self.remove_pending_registration(&mut pending_registrations, user_id, registration) {
+            return None;
+        }
+        log::info!("{:?} of {user_id} paid", registration.operation);
+        Some(self.complete_operation(user_id, tier, registration.operation))
+    }
+
+    /// Removes a pending registration of a user, if still pending. Returns whether it was removed.
+    ///
+    /// Used to resolve registrations once their payment has been checked, given the pending registrations are not
+    /// kept locked while doing so. This prevents paid operations from being completed twice.
+    fn take_pending_registration(
+        &self,
+        user_id: UserId,
+        registration: &PendingRegistration,
+    ) -> bool {
+        let mut pending_registrations = self.pending_registrations.lock().unwrap();
+        self.remove_pending_registration(&mut pending_registrations, user_id, registration)
+    }
+
+    /// Removes a pending registration of a user from the given (locked) map and the database, if present.
+    fn remove_pending_registration(
+        &self,
+        pending_registrations: &mut HashMap<UserId, Vec<PendingRegistration>>,
+        user_id: UserId,
+        registration: &PendingRegistration,
+    ) -> bool {
+        let user_registrations = match pending_registrations.get_mut(&user_id) {
+            Some(user_registrations) => user_registrations,
+            None => return false,
+        };
+        match user_registrations.iter().position(|r| r == registration) {
+            Some(i) => user_registrations.remove(i),
+            None => return false,
+        };
+        if user_registrations.is_empty() {
+            pending_registrations.remove(&user_id);
+        }
+        self.dbm
+            .lock()
+            .unwrap()
+            .remove_pending_registration(&registration.invoice.payment_hash);
+        true
+    }
+
+    /// Resolves the pending registrations whose invoice has expired. Paid ones are completed, while the rest are dropped.
+    ///
+    /// Registrations are normally completed when users come back after paying, this covers the ones that did not. This
+    /// queries the payment backend, so it is meant to be run periodically on its own (see
+    /// [EXPIRED_REGISTRATIONS_SWEEP_DELTA]) instead of as part of the block processing.
+    pub fn resolve_expired_registrations(&self) {
+        let payments = match &self.payments {
+            Some(payments) => payments,
+            None => return,
+        };
+
+        let now = unix_time();
+        let expired: Vec<(UserId, PendingRegistration)> = self
+            .pending_registrations
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(user_id, registrations)| {
+                registrations
+                    .iter()
+                    .filter(|registration| registration.invoice.has_expired(now))
+                    .map(move |registration| (*user_id, registration.clone()))
+            })
+            .collect();
+
+        for (user_id, registration) in expired {
+            match payments
+                .verifier
+                .is_settled(&registration.invoice.payment_hash)
+            {
+                Ok(true) => {
+                    let result = match self.get_tier(&registration.tier) {
+                        Some(tier) => {
+                            match self.complete_pending_registration(user_id, &registration, tier) {
+                                Some(result) => result,
+                                // The user may have come back in the meantime, completing the registration already
+                                None => continue,
+                            }
+                        }
+                        None => {
+                            self.take_pending_registration(user_id, &registration);
+                            Err(RegistrationFailure::UnknownTier(registration.tier.clone()))
+                        }
+                    };
+                    if let Err(e) = result {
+                        log::error!(
+                            "Cannot complete the {:?} of {user_id}: {:?}",
+                            registration.operation,
+                            e
+                        );
+                    }
+                }
+                Ok(false) => {
+                    self.take_pending_registration(user_id, &registration);
+                }
+                // Try again on the next sweep
+                Err(e) => log::error!("Cannot check the payment of {user_id}: {e}"),
+            }
+        }
+    }
+
+    /// Adds a new user to the tower given a subscription tier (or updates its subscription if already registered).
+    ///
+    /// Registered users get the tier slots added to their subscription, which is only extended if it can be renewed.
     pub(crate) fn add_update_user(
         &self,
         user_id: UserId,
+        tier: &SubscriptionTier,
     ) -> Result<RegistrationReceipt, MaxSlotsReached> {
         let block_count = self.last_known_block_height.load(Ordering::Acquire);
 
-        // TODO: For now, new calls to `add_update_user` add subscription_slots to the current count and reset the expiry time
         let mut registered_users = self.registered_users.lock().unwrap();
         let user_info = match registered_users.get_mut(&user_id) {
             // User already exists, updating the info
             Some(user_info) => {
                 user_info.available_slots = user_info
                     .available_slots
-                    .checked_add(self.subscription_slots)
+                    .checked_add(tier.slots)
                     .ok_or(MaxSlotsReached)?;
-                user_info.subscription_expiry = user_info
-                    .subscription_expiry
-                    .checked_add(self.subscription_duration)
-                    .unwrap_or(u32::MAX);
+                if block_count >= user_info.renewable_from(tier.duration) {
+                    user_info.extend_subscription(tier.duration, block_count);
+                }
                 self.dbm.lock().unwrap().update_user(user_id, user_info);
 
                 user_info
             }
             // New user
             None => {
-                let user_info = UserInfo::new(
-                    self.subscription_slots,
-                    block_count,
-                    block_count + self.subscription_duration,
-                );
+                let user_info = UserInfo::new(tier.slots, block_count, block_count + tier.duration)
+                    .with_tier(tier.name.clone());
                 self.dbm
                     .lock()
                     .unwrap()
@@ -206,6 +950,52 @@ impl Gatekeeper {
             user_info.available_slots,
             user_info.subscription_start,
             user_info.subscription_expiry,
+            user_info.tier.clone(),
+        ))
+    }
+
+    /// Extends the subscription of a registered user by the duration of a given tier.
+    fn renew_user(
+        &self,
+        user_id: UserId,
+        tier: &SubscriptionTier,
+    ) -> Result<RenewalReceipt, RegistrationFailure> {
+        let block_count = self.last_known_block_height.load(Ordering::Acquire);
+
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users
+            .get_mut(&user_id)
+            .ok_or(RegistrationFailure::UserNotFound)?;
+        user_info.extend_subscription(tier.duration, block_count);
+        self.dbm.lock().unwrap().update_user(user_id, user_info);
+
+        Ok(RenewalReceipt::new(
+            user_id,
+            user_info.subscription_start,
+            user_info.subscription_expiry,
+        ))
+    }
+
+    /// Adds the slots of a given tier to the subscription of a registered user.
+    fn top_up_user(
+        &self,
+        user_id: UserId,
+        tier: &SubscriptionTier,
+    ) -> Result<TopUpReceipt, RegistrationFailure> {
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users
+            .get_mut(&user_id)
+            .ok_or(RegistrationFailure::UserNotFound)?;
+        user_info.available_slots = user_info
+            .available_slots
+            .checked_add(tier.slots)
+            .ok_or(RegistrationFailure::MaxSlotsReached)?;
+        self.dbm.lock().unwrap().update_user(user_id, user_info);
+
+        Ok(TopUpReceipt::new(
+            user_id,
+            user_info.available_slots,
+            user_info.subscription_expiry,
         ))
     }
 
@@ -216,27 +1006,61 @@ impl Gatekeeper {
         uuid: UUID,
         appointment: &ExtendedAppointment,
     ) -> Result<u32, NotEnoughSlots> {
-        // For updates, the difference between the existing appointment size and the update is computed.
         let mut registered_users = self.registered_users.lock().unwrap();
         let user_info = registered_users.get_mut(&user_id).unwrap();
-        let used_slots = user_info.appointments.get(&uuid).map_or(0, |x| *x);
-
-        let required_slots =
-            compute_appointment_slots(appointment.encrypted_blob().len(), ENCRYPTED_BLOB_MAX_SIZE);
-
-        let diff = required_slots as i64 - used_slots as i64;
-        if diff <= user_info.available_slots as i64 {
-            // Filling / freeing slots depending on whether this is an update or not, and if it is bigger or smaller
-            // than the old appointment
-            user_info.appointments.insert(uuid, required_slots);
-            user_info.available_slots = (user_info.available_slots as i64 - diff) as u32;
+        let slot_size = self.get_slot_size(&user_info.tier);
+        let available_slots = Gatekeeper::update_slots(user_info, uuid, appointment, slot_size)?;
+        self.dbm.lock().unwrap().update_user(user_id, user_info);
 
-            self.dbm.lock().unwrap().update_user(user_id, user_info);
-
-            Ok(user_info.available_slots)
-        } else {
-            Err(NotEnoughSlots)
-        }
+        Ok(available_slots)
+    }
+
+    /// Updates the data of a registered user through `f`, which is handed a copy of the user's [UserInfo] alongside
+    /// the blob size a slot covers for their tier (see [Gatekeeper::update_slots]).
+    ///
+    /// The data held in memory is only replaced if `f` succeeds, so the caller can persist the updated data first.
+    /// The user is locked while `f` runs, so concurrent updates of the same user are not lost.
+    pub(crate) fn try_update_user<T, E>(
+        &self,
+        user_id: UserId,
+        f: impl FnOnce(&mut UserInfo, usize) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users.get_mut(&user_id).unwrap();
+        let mut updated_info = user_info.clone();
+        let result = f(&mut updated_info, self.get_slot_size(&user_info.tier))?;
+        *user_info = updated_info;
+
+        Ok(result)
+    }
+
+    /// Fills / frees the slots of a user when adding / updating an appointment, given the blob size a slot covers.
+    pub(crate) fn update_slots(
+        user_info: &mut UserInfo,
+        uuid: UUID,
+        appointment: &ExtendedAppointment,
+        slot_size: usize,
+    ) -> Result<u32, NotEnoughSlots> {
+        // For updates, the difference between the existing appointment size and the update is computed.
+        let used_slots = user_info.appointments.get(&uuid).map_or(0, |x| *x);
+
+        let required_slots =
+            compute_appointment_slots(appointment.encrypted_blob().len(), slot_size);
+
+        let diff = required_slots as i64 - used_slots as i64;
+        if diff <= user_info.available_slots as i64 {
+            // Filling / freeing slots depending on whether this is an update or not, and if it is bigger or smaller
+            // than the old appointment
+            user_info.appointments.insert(uuid, required_slots);
+            user_info.available_slots = (user_info.available_slots as i64 - diff) as u32;
+
+            Ok(user_info.available_slots)
+        } else {
+            Err(NotEnoughSlots {
+                required_slots: diff as u32,
+                available_slots: user_info.available_slots,
+            })
+        }
     }
 
     /// Checks whether a subscription has expired.
@@ -262,7 +1086,9 @@ impl Gatekeeper {
         let registered_users = self.registered_users.lock().unwrap().clone();
         registered_users
             .into_iter()
-            .filter(|(_, info)| block_height == info.subscription_expiry + self.expiry_delta)
+            .filter(|(_, info)| {
+                block_height == info.subscription_expiry.saturating_add(self.expiry_delta)
+            })
             .map(|(id, info)| (id, info.appointments.keys().cloned().collect()))
             .collect()
     }
@@ -309,6 +1135,141 @@ impl Gatekeeper {
 
         updated_users
     }
+
+    /// Bans a user from the tower. Banned users can neither authenticate nor subscribe, but their data is kept (see
+    /// [Watcher::delete_user](crate::watcher::Watcher::delete_user)). Users do not need to be registered to be banned.
+    pub(crate) fn ban_user(&self, user_id: UserId) -> Result<(), UserManagementFailure> {
+        if !self.banned_users.lock().unwrap().insert(user_id) {
+            return Err(UserManagementFailure::AlreadyBanned);
+        }
+        self.dbm.lock().unwrap().store_banned_user(user_id).unwrap();
+        self.audit(user_id, "ban", "User banned".to_owned());
+
+        Ok(())
+    }
+
+    /// Lifts the ban of a user.
+    pub(crate) fn unban_user(&self, user_id: UserId) -> Result<(), UserManagementFailure> {
+        if !self.banned_users.lock().unwrap().remove(&user_id) {
+            return Err(UserManagementFailure::NotBanned);
+        }
+        self.dbm.lock().unwrap().remove_banned_user(user_id);
+        self.audit(user_id, "unban", "User unbanned".to_owned());
+
+        Ok(())
+    }
+
+    /// Deletes a user from the tower, alongside any registration pending payment. The user data is removed from the
+    /// database in cascade (appointments, trackers and fee bumps).
+    ///
+    /// Returns the appointments the user had, so they can be removed from the memory of the components holding them.
+    pub(crate) fn delete_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<HashSet<UUID>, UserManagementFailure> {
+        let user_info = self
+            .registered_users
+            .lock()
+            .unwrap()
+            .remove(&user_id)
+            .ok_or(UserManagementFailure::UserNotFound)?;
+        self.pending_registrations.lock().unwrap().remove(&user_id);
+
+        let mut dbm = self.dbm.lock().unwrap();
+        dbm.batch_remove_users(&HashSet::from_iter([user_id]));
+        dbm.remove_pending_registrations(user_id);
+        drop(dbm);
+        self.audit(
+            user_id,
+            "delete",
+            format!(
+                "User deleted alongside {} appointments",
+                user_info.appointments.len()
+            ),
+        );
+
+        Ok(user_info.appointments.into_keys().collect())
+    }
+
+    /// Grants a number of extra slots to a user. The subscription period is left untouched.
+    pub(crate) fn grant_slots(
+        &self,
+        user_id: UserId,
+        slots: u32,
+    ) -> Result<UserInfo, UserManagementFailure> {
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users
+            .get_mut(&user_id)
+            .ok_or(UserManagementFailure::UserNotFound)?;
+        user_info.available_slots = user_info
+            .available_slots
+            .checked_add(slots)
+            .ok_or(UserManagementFailure::MaxSlotsReached)?;
+        let user_info = user_info.clone();
+        drop(registered_users);
+
+        self.dbm.lock().unwrap().update_user(user_id, &user_info);
+        self.audit(
+            user_id,
+            "grant_slots",
+            format!(
+                "Granted {slots} slots ({} available)",
+                user_info.available_slots
+            ),
+        );
+
+        Ok(user_info)
+    }
+
+    /// Sets the expiry of the subscription of a user. The new expiry cannot be in the past, so expired subscriptions
+    /// can still go through the renewal grace period before being outdated.
+    pub(crate) fn set_subscription_expiry(
+        &self,
+        user_id: UserId,
+        subscription_expiry: u32,
+    ) -> Result<UserInfo, UserManagementFailure> {
+        let block_count = self.last_known_block_height.load(Ordering::Acquire);
+        if subscription_expiry < block_count {
+            return Err(UserManagementFailure::ExpiryInThePast(block_count));
+        }
+
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users
+            .get_mut(&user_id)
+            .ok_or(UserManagementFailure::UserNotFound)?;
+        let previous_expiry = user_info.subscription_expiry;
+        user_info.subscription_expiry = subscription_expiry;
+        let user_info = user_info.clone();
+        drop(registered_users);
+
+        self.dbm.lock().unwrap().update_user(user_id, &user_info);
+        self.audit(
+            user_id,
+            "set_subscription_expiry",
+            format!("Subscription expiry moved from {previous_expiry} to {subscription_expiry}"),
+        );
+
+        Ok(user_info)
+    }
+
+    /// Gets the audit log of admin actions, from oldest to newest.
+    pub(crate) fn get_audit_log(&self) -> Vec<AuditLogEntry> {
+        self.dbm.lock().unwrap().load_audit_log()
+    }
+
+    /// Records an admin action over a user in the audit log.
+    fn audit(&self, user_id: UserId, action: &str, details: String) {
+        log::info!("Admin action over {user_id} ({action}): {details}");
+        let entry = AuditLogEntry {
+            timestamp: unix_time(),
+            user_id,
+            action: action.to_owned(),
+            details,
+        };
+        if let Err(e) = self.dbm.lock().unwrap().store_audit_log_entry(&entry) {
+            log::error!("Cannot store the audit log entry: {e:?}");
+        }
+    }
 }
 
 impl chain::Listen for Gatekeeper {
@@ -351,11 +1312,14 @@ impl chain::Listen for Gatekeeper {
 mod tests {
     use super::*;
 
+    use crate::dbm::DBM;
     use crate::test_utils::{
-        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid, Blockchain,
+        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid,
+        Blockchain, MockPaymentVerifier, INVOICE_EXPIRY, PRICE_MSAT,
     };
     use lightning::chain::Listen;
     use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+    use teos_common::dbm::DatabaseConnection;
     use teos_common::test_utils::get_random_user_id;
 
     const SLOTS: u32 = 21;
@@ -365,10 +1329,12 @@ mod tests {
 
     impl PartialEq for Gatekeeper {
         fn eq(&self, other: &Self) -> bool {
-            self.subscription_slots == other.subscription_slots
-                && self.subscription_duration == other.subscription_duration
+            self.tiers == other.tiers
                 && self.expiry_delta == other.expiry_delta
                 && *self.registered_users.lock().unwrap() == *other.registered_users.lock().unwrap()
+                && *self.pending_registrations.lock().unwrap()
+                    == *other.pending_registrations.lock().unwrap()
+                && *self.banned_users.lock().unwrap() == *other.banned_users.lock().unwrap()
                 && self.last_known_block_height.load(Ordering::Relaxed)
                     == other.last_known_block_height.load(Ordering::Relaxed)
         }
@@ -386,7 +1352,7 @@ mod tests {
             outdates_at: u32,
             appointments: Option<Vec<UUID>>,
         ) {
-            self.add_update_user(user_id).unwrap();
+            self.add_update_user(user_id, &self.tiers[0]).unwrap();
             let mut registered_users = self.registered_users.lock().unwrap();
             let mut user = registered_users.get_mut(&user_id).unwrap();
             user.subscription_expiry = outdates_at - self.expiry_delta;
@@ -403,6 +1369,33 @@ mod tests {
         Gatekeeper::new(chain.get_block_count(), SLOTS, DURATION, EXPIRY_DELTA, dbm)
     }
 
+    fn init_paid_gatekeeper(chain: &Blockchain) -> (Gatekeeper, Arc<MockPaymentVerifier>) {
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let gatekeeper = init_gatekeeper(chain)
+            .with_tiers(vec![SubscriptionTier::new(
+                String::new(),
+                SLOTS,
+                DURATION,
+                PRICE_MSAT,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            )])
+            .with_payments(verifier.clone(), INVOICE_EXPIRY);
+        (gatekeeper, verifier)
+    }
+
+    /// Makes the pending registrations of a given user expire.
+    fn expire_pending_registration(gatekeeper: &Gatekeeper, user_id: UserId) {
+        for registration in gatekeeper
+            .pending_registrations
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+        {
+            registration.invoice.expires_at = unix_time() - 1;
+        }
+    }
+
     #[test]
     fn test_new() {
         // A fresh gatekeeper has no associated data
@@ -422,7 +1415,9 @@ mod tests {
         // (as if simulating a bootstrap from existing data), the data should be properly loaded.
         for _ in 0..10 {
             let user_id = get_random_user_id();
-            gatekeeper.add_update_user(user_id).unwrap();
+            gatekeeper
+                .add_update_user(user_id, &gatekeeper.tiers[0])
+                .unwrap();
 
             let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
             gatekeeper
@@ -437,110 +1432,1021 @@ mod tests {
                 .unwrap();
         }
 
-        // Create a new GK reusing the same DB and check that the data is loaded
-        let another_gk =
-            Gatekeeper::new(chain.get_block_count(), SLOTS, DURATION, EXPIRY_DELTA, dbm);
-        assert!(!another_gk.is_fresh());
-        assert_eq!(gatekeeper, another_gk);
+        // Create a new GK reusing the same DB and check that the data is loaded
+        let another_gk =
+            Gatekeeper::new(chain.get_block_count(), SLOTS, DURATION, EXPIRY_DELTA, dbm);
+        assert!(!another_gk.is_fresh());
+        assert_eq!(gatekeeper, another_gk);
+    }
+
+    #[test]
+    fn test_authenticate_user() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+
+        // Authenticate user returns the UserId if the user is found in the system, or an AuthenticationError otherwise.
+
+        // Let's first check with an unknown user
+        let message = "message".as_bytes();
+        let wrong_signature = "signature";
+        assert_eq!(
+            gatekeeper.authenticate_user(message, wrong_signature),
+            Err(AuthenticationFailure("Wrong message or signature."))
+        );
+
+        // Let's now provide data generated by an actual user, still the user is unknown
+        let (user_sk, user_pk) = get_random_keypair();
+        let signature = cryptography::sign(message, &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Err(AuthenticationFailure("User not found."))
+        );
+
+        // Last, let's add the user to the Gatekeeper and try again.
+        let user_id = UserId(user_pk);
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Ok(user_id)
+        );
+    }
+
+    #[test]
+    fn test_add_update_user() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain);
+
+        // add_update_user adds a user to the system if it is not still registered, otherwise it add slots to the user subscription
+        // and extends the subscription expiry if it can be renewed. Slots are added up to u32:MAX, further call will return an
+        // MaxSlotsReached error.
+
+        // Let's start by adding new user
+        let user_id = get_random_user_id();
+        let receipt = gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+        // The data should have been also added to the database
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            UserInfo::new(
+                receipt.available_slots(),
+                receipt.subscription_start(),
+                receipt.subscription_expiry()
+            )
+        );
+
+        // Let generate a new block and add the user again to check that both the slots and expiry are updated.
+        chain.generate(None);
+        gatekeeper
+            .last_known_block_height
+            .store(chain.get_block_count(), Ordering::Relaxed);
+        let updated_receipt = gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+
+        assert_eq!(updated_receipt.available_slots(), SLOTS * 2);
+        assert_eq!(
+            updated_receipt.subscription_expiry(),
+            START_HEIGHT as u32 + DURATION * 2
+        );
+
+        // Data in the database should have been updated too
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            UserInfo::new(
+                updated_receipt.available_slots(),
+                updated_receipt.subscription_start(),
+                updated_receipt.subscription_expiry()
+            )
+        );
+
+        // The subscription has more than a renewal worth of time left now, so adding the user again only adds slots
+        let updated_receipt = gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+        assert_eq!(updated_receipt.available_slots(), SLOTS * 3);
+        assert_eq!(
+            updated_receipt.subscription_expiry(),
+            START_HEIGHT as u32 + DURATION * 2
+        );
+
+        // If the slot count reaches u32::MAX we should receive an error
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = u32::MAX;
+
+        assert!(matches!(
+            gatekeeper.add_update_user(user_id, &gatekeeper.tiers[0]),
+            Err(MaxSlotsReached)
+        ));
+
+        // Data in the database remains untouched
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            UserInfo::new(
+                updated_receipt.available_slots(),
+                updated_receipt.subscription_start(),
+                updated_receipt.subscription_expiry()
+            )
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        // If subscriptions are not paid, register simply adds / updates the user
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.available_slots(), SLOTS);
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.available_slots(), SLOTS * 2);
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        // Users are not limited unless a rate limit is set
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+        for _ in 0..10 {
+            gatekeeper.check_rate_limit(user_id).unwrap();
+        }
+
+        // Otherwise, each user is limited separately
+        let gatekeeper =
+            init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT)).with_rate_limit(1, 2);
+        gatekeeper.check_rate_limit(user_id).unwrap();
+        gatekeeper.check_rate_limit(user_id).unwrap();
+        assert_eq!(gatekeeper.check_rate_limit(user_id), Err(RateLimited(60)));
+        gatekeeper.check_rate_limit(get_random_user_id()).unwrap();
+
+        // Subscription requests are limited as well, but they do not count towards the limit of the (signed) requests
+        // of the user, since anyone can make them on their behalf
+        let victim_id = get_random_user_id();
+        gatekeeper.register(victim_id, "").unwrap();
+        gatekeeper.register(victim_id, "").unwrap();
+        assert_eq!(
+            gatekeeper.register(victim_id, ""),
+            Err(RegistrationFailure::RateLimited(60))
+        );
+        assert_eq!(
+            gatekeeper.top_up(victim_id),
+            Err(RegistrationFailure::RateLimited(60))
+        );
+        assert_eq!(
+            gatekeeper.renew(victim_id),
+            Err(RegistrationFailure::RateLimited(60))
+        );
+        gatekeeper.check_rate_limit(victim_id).unwrap();
+        gatekeeper.check_rate_limit(victim_id).unwrap();
+        gatekeeper.register(get_random_user_id(), "").unwrap();
+    }
+
+    #[test]
+    fn test_ban_user() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            dbm.clone(),
+        );
+
+        // Banned users cannot authenticate nor subscribe
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        gatekeeper.register(user_id, "").unwrap();
+        gatekeeper.ban_user(user_id).unwrap();
+        assert!(gatekeeper.is_banned(user_id));
+        assert_eq!(
+            gatekeeper.ban_user(user_id),
+            Err(UserManagementFailure::AlreadyBanned)
+        );
+
+        let message = "message".as_bytes();
+        let signature = cryptography::sign(message, &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Err(AuthenticationFailure("User banned."))
+        );
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::UserBanned)
+        );
+        assert_eq!(
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::UserBanned)
+        );
+        assert_eq!(
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::UserBanned)
+        );
+
+        // Their data is kept though, and so are the bans after a restart
+        assert!(gatekeeper.get_user_info(user_id).is_some());
+        let another_gk = Gatekeeper::new(
+            chain.get_block_count(),
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            dbm.clone(),
+        );
+        assert!(another_gk.is_banned(user_id));
+
+        // Users can also be banned before they register
+        let unknown_user_id = get_random_user_id();
+        gatekeeper.ban_user(unknown_user_id).unwrap();
+        assert_eq!(
+            gatekeeper.register(unknown_user_id, ""),
+            Err(RegistrationFailure::UserBanned)
+        );
+
+        // Lifting the ban gives access back
+        gatekeeper.unban_user(user_id).unwrap();
+        assert_eq!(
+            gatekeeper.unban_user(user_id),
+            Err(UserManagementFailure::NotBanned)
+        );
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Ok(user_id)
+        );
+        assert!(!dbm.lock().unwrap().load_banned_users().contains(&user_id));
+
+        // Every action is audited
+        let audit_log = gatekeeper.get_audit_log();
+        assert_eq!(
+            audit_log
+                .iter()
+                .map(|entry| (entry.user_id, entry.action.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (user_id, "ban"),
+                (unknown_user_id, "ban"),
+                (user_id, "unban")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delete_user() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.delete_user(user_id),
+            Err(UserManagementFailure::UserNotFound)
+        );
+
+        // Deleting a user returns their appointments and wipes them from memory and the database
+        gatekeeper.register(user_id, "").unwrap();
+        let mut uuids = HashSet::new();
+        for _ in 0..3 {
+            let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+            gatekeeper
+                .add_update_appointment(user_id, uuid, &appointment)
+                .unwrap();
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .store_appointment(uuid, &appointment)
+                .unwrap();
+            uuids.insert(uuid);
+        }
+
+        assert_eq!(gatekeeper.delete_user(user_id), Ok(uuids.clone()));
+        assert!(gatekeeper.get_user_info(user_id).is_none());
+        let dbm = gatekeeper.dbm.lock().unwrap();
+        assert!(!dbm.load_all_users().contains_key(&user_id));
+        for uuid in uuids {
+            assert!(dbm.load_appointment(uuid).is_none());
+        }
+        drop(dbm);
+
+        let audit_log = gatekeeper.get_audit_log();
+        assert_eq!(audit_log.len(), 1);
+        assert_eq!(audit_log[0].user_id, user_id);
+        assert_eq!(audit_log[0].action, "delete");
+    }
+
+    #[test]
+    fn test_grant_slots() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.grant_slots(user_id, 10),
+            Err(UserManagementFailure::UserNotFound)
+        );
+
+        // Granted slots are added to the available ones, without touching the subscription period
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        let user_info = gatekeeper.grant_slots(user_id, 10).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS + 10);
+        assert_eq!(user_info.subscription_expiry, receipt.subscription_expiry());
+        assert_eq!(
+            gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap(),
+            Some(user_info)
+        );
+
+        // Slots cannot overflow
+        assert_eq!(
+            gatekeeper.grant_slots(user_id, u32::MAX),
+            Err(UserManagementFailure::MaxSlotsReached)
+        );
+        assert_eq!(
+            gatekeeper.get_user_info(user_id).unwrap().available_slots,
+            SLOTS + 10
+        );
+        assert_eq!(gatekeeper.get_audit_log().len(), 1);
+    }
+
+    #[test]
+    fn test_set_subscription_expiry() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain);
+        let height = chain.get_block_count();
+
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.set_subscription_expiry(user_id, height + 1),
+            Err(UserManagementFailure::UserNotFound)
+        );
+
+        // The expiry can be moved both forwards and backwards, as long as it is not in the past
+        gatekeeper.register(user_id, "").unwrap();
+        for expiry in [height + 2 * DURATION, height] {
+            let user_info = gatekeeper.set_subscription_expiry(user_id, expiry).unwrap();
+            assert_eq!(user_info.subscription_expiry, expiry);
+            assert_eq!(
+                gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap(),
+                Some(user_info)
+            );
+        }
+        assert_eq!(
+            gatekeeper.set_subscription_expiry(user_id, height - 1),
+            Err(UserManagementFailure::ExpiryInThePast(height))
+        );
+        assert_eq!(
+            gatekeeper
+                .get_user_info(user_id)
+                .unwrap()
+                .subscription_expiry,
+            height
+        );
+        assert_eq!(gatekeeper.get_audit_log().len(), 2);
+    }
+
+    #[test]
+    fn test_register_paid() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+        let user_id = get_random_user_id();
+
+        // Registering returns an invoice, and the user is not added until it is paid
+        let invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        assert_eq!(invoice.amount_msat, PRICE_MSAT);
+        assert!(gatekeeper.get_user_info(user_id).is_none());
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_pending_registrations()
+                .get(&user_id)
+                .map(|registrations| &registrations[0].invoice),
+            Some(&invoice)
+        );
+
+        // Calling again before paying returns the same invoice
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::PaymentRequired(invoice.clone()))
+        );
+
+        // Pending registrations are loaded on bootstrap
+        let another_gk = Gatekeeper::new(
+            chain.get_block_count(),
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            gatekeeper.dbm.clone(),
+        )
+        .with_tiers(gatekeeper.tiers.clone());
+        assert_eq!(another_gk, gatekeeper);
+
+        // Once paid, the registration is completed
+        verifier.settle(&invoice.payment_hash);
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.available_slots(), SLOTS);
+        assert!(gatekeeper.get_user_info(user_id).is_some());
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+        assert!(gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_pending_registrations()
+            .is_empty());
+
+        // Registering again requires paying again
+        let renewal_invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        assert_ne!(renewal_invoice, invoice);
+        verifier.settle(&renewal_invoice.payment_hash);
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.available_slots(), SLOTS * 2);
+    }
+
+    #[test]
+    fn test_register_paid_expired_invoice() {
+        let (gatekeeper, _) =
+            init_paid_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+
+        // Expired invoices are replaced by a new one
+        let invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        expire_pending_registration(&gatekeeper, user_id);
+
+        let new_invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        assert_ne!(new_invoice.payment_hash, invoice.payment_hash);
+        assert_eq!(
+            gatekeeper.pending_registrations.lock().unwrap()[&user_id],
+            vec![PendingRegistration {
+                tier: String::new(),
+                operation: SubscriptionOperation::Register,
+                invoice: new_invoice
+            }]
+        );
+    }
+
+    #[test]
+    fn test_register_paid_max_slots() {
+        let (gatekeeper, verifier) =
+            init_paid_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = u32::MAX;
+
+        // Users are not charged for subscriptions that cannot be granted
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::MaxSlotsReached)
+        );
+        assert!(verifier.invoices.lock().unwrap().is_empty());
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_register_payments_unavailable() {
+        let (gatekeeper, verifier) =
+            init_paid_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+
+        // If no invoice can be created the registration fails
+        verifier.set_unreachable(true);
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::PaymentsUnavailable)
+        );
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+
+        // Same if the payment cannot be checked. The pending registration is kept
+        verifier.set_unreachable(false);
+        assert!(matches!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::PaymentRequired(_))
+        ));
+        verifier.set_unreachable(true);
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::PaymentsUnavailable)
+        );
+        assert!(gatekeeper
+            .pending_registrations
+            .lock()
+            .unwrap()
+            .contains_key(&user_id));
+
+        // Same if the invoice cannot be stored
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let gatekeeper = Gatekeeper::new(
+            START_HEIGHT as u32,
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            dbm.clone(),
+        )
+        .with_tiers(gatekeeper.tiers.clone())
+        .with_payments(verifier.clone(), INVOICE_EXPIRY);
+        dbm.lock()
+            .unwrap()
+            .get_connection()
+            .execute("DROP TABLE pending_registrations", [])
+            .unwrap();
+        verifier.set_unreachable(false);
+        assert_eq!(
+            gatekeeper.register(user_id, ""),
+            Err(RegistrationFailure::PaymentsUnavailable)
+        );
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+    }
+
+    fn get_test_tiers(premium_price: u64) -> Vec<SubscriptionTier> {
+        vec![
+            SubscriptionTier::new(
+                "basic".to_owned(),
+                SLOTS,
+                DURATION,
+                0,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            ),
+            SubscriptionTier::new(
+                "premium".to_owned(),
+                SLOTS * 10,
+                DURATION * 2,
+                premium_price,
+                ENCRYPTED_BLOB_MAX_SIZE * 2,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_with_tiers() {
+        // The slots taken by appointments depend on the user tier, so they are recomputed when loading users
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain).with_tiers(get_test_tiers(0));
+        let user_id = get_random_user_id();
+        gatekeeper.register(user_id, "premium").unwrap();
+
+        let (uuid, mut appointment) = generate_dummy_appointment_with_user(user_id, None);
+        appointment.inner.encrypted_blob = get_random_bytes(ENCRYPTED_BLOB_MAX_SIZE + 1);
+        assert_eq!(
+            gatekeeper
+                .add_update_appointment(user_id, uuid, &appointment)
+                .unwrap(),
+            SLOTS * 10 - 1
+        );
+        gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .store_appointment(uuid, &appointment)
+            .unwrap();
+
+        let another_gk = Gatekeeper::new(
+            chain.get_block_count(),
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            gatekeeper.dbm.clone(),
+        )
+        .with_tiers(get_test_tiers(0));
+        assert_eq!(another_gk, gatekeeper);
+        assert_eq!(
+            another_gk.get_user_info(user_id).unwrap().appointments[&uuid],
+            1
+        );
+
+        // An empty set of tiers keeps the default subscription
+        let gatekeeper = init_gatekeeper(&chain);
+        let default_tiers = gatekeeper.tiers.clone();
+        assert_eq!(gatekeeper.with_tiers(Vec::new()).tiers, default_tiers);
+    }
+
+    #[test]
+    fn test_register_tiers() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT))
+            .with_tiers(get_test_tiers(0));
+
+        // Users that do not pick a tier are given the first one
+        let user_id = get_random_user_id();
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.tier(), "basic");
+        assert_eq!(receipt.available_slots(), SLOTS);
+
+        // Otherwise they get the one they ask for
+        let premium_user_id = get_random_user_id();
+        let receipt = gatekeeper.register(premium_user_id, "premium").unwrap();
+        assert_eq!(receipt.tier(), "premium");
+        assert_eq!(receipt.available_slots(), SLOTS * 10);
+        assert_eq!(
+            receipt.subscription_expiry(),
+            START_HEIGHT as u32 + DURATION * 2
+        );
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(premium_user_id)
+                .unwrap()
+                .unwrap()
+                .tier,
+            "premium"
+        );
+
+        // Registered users that do not pick a tier keep their current one, and cannot switch to a different one
+        let receipt = gatekeeper.register(premium_user_id, "").unwrap();
+        assert_eq!(receipt.tier(), "premium");
+        assert_eq!(receipt.available_slots(), SLOTS * 20);
+        assert_eq!(
+            gatekeeper.register(premium_user_id, "basic"),
+            Err(RegistrationFailure::TierMismatch("premium".to_owned()))
+        );
+
+        // Tiers not offered by the tower are rejected
+        assert_eq!(
+            gatekeeper.register(get_random_user_id(), "gold"),
+            Err(RegistrationFailure::UnknownTier("gold".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_register_paid_tiers() {
+        // Only tiers with a price are charged for
+        let verifier = Arc::new(MockPaymentVerifier::default());
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT))
+            .with_tiers(get_test_tiers(PRICE_MSAT))
+            .with_payments(verifier.clone(), INVOICE_EXPIRY);
+
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.register(user_id, "basic").unwrap().tier(),
+            "basic"
+        );
+
+        // Users that pick a different tier while their registration is pending keep the pending invoice, which is still
+        // honored if paid
+        let user_id = get_random_user_id();
+        let premium_invoice = match gatekeeper.register(user_id, "premium") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        assert_eq!(premium_invoice.amount_msat, PRICE_MSAT);
+        assert_eq!(
+            gatekeeper.register(user_id, "basic").unwrap().tier(),
+            "basic"
+        );
+        let pending_registrations = gatekeeper.pending_registrations.lock().unwrap().clone();
+        assert_eq!(pending_registrations[&user_id].len(), 1);
+        assert_eq!(pending_registrations[&user_id][0].tier, "premium");
+        assert_eq!(
+            gatekeeper.dbm.lock().unwrap().load_pending_registrations(),
+            pending_registrations
+        );
+        verifier.settle(&premium_invoice.payment_hash);
+        assert_eq!(
+            gatekeeper.register(user_id, "").unwrap().available_slots(),
+            SLOTS + SLOTS * 10
+        );
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+
+        // Paid registrations are completed in the tier they were paid for
+        let user_id = get_random_user_id();
+        let invoice = match gatekeeper.register(user_id, "premium") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        verifier.settle(&invoice.payment_hash);
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(receipt.tier(), "premium");
+        assert_eq!(receipt.available_slots(), SLOTS * 10);
+    }
+
+    #[test]
+    fn test_renew() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain);
+        let start = START_HEIGHT as u32;
+
+        // Only registered users can renew their subscription
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::UserNotFound)
+        );
+
+        // Subscriptions cannot be renewed while they have a renewal worth of time left (or more)
+        gatekeeper.register(user_id, "").unwrap();
+        assert_eq!(
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::RenewalTooEarly(start + 1))
+        );
+
+        // Once they don't, renewing extends the subscription by the tier duration, leaving the slots untouched
+        gatekeeper
+            .last_known_block_height
+            .store(start + 1, Ordering::Relaxed);
+        let receipt = gatekeeper.renew(user_id).unwrap();
+        assert_eq!(receipt.user_id(), user_id);
+        assert_eq!(receipt.subscription_start(), start);
+        assert_eq!(receipt.subscription_expiry(), start + DURATION * 2);
+
+        let user_info = gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert_eq!(user_info.subscription_expiry, start + DURATION * 2);
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            user_info
+        );
+
+        // Renewing straightaway is not possible, so the expiry cannot be pushed indefinitely
+        assert_eq!(
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::RenewalTooEarly(start + DURATION + 1))
+        );
+
+        // Expired subscriptions can be renewed within the grace period, starting over from the current block
+        let height = start + DURATION * 2 + EXPIRY_DELTA - 1;
+        gatekeeper
+            .last_known_block_height
+            .store(height, Ordering::Relaxed);
+        let receipt = gatekeeper.renew(user_id).unwrap();
+        assert_eq!(receipt.subscription_start(), height);
+        assert_eq!(receipt.subscription_expiry(), height + DURATION);
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            gatekeeper.get_user_info(user_id).unwrap()
+        );
+
+        // Users in tiers that are not offered anymore cannot renew
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .tier = "gold".to_owned();
+        assert_eq!(
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::UnknownTier("gold".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_top_up() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain);
+        let start = START_HEIGHT as u32;
+
+        // Only registered users can top up their subscription
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::UserNotFound)
+        );
+
+        // Topping up adds the tier slots, leaving the subscription period untouched
+        gatekeeper.register(user_id, "").unwrap();
+        for i in 2..4 {
+            let receipt = gatekeeper.top_up(user_id).unwrap();
+            assert_eq!(receipt.user_id(), user_id);
+            assert_eq!(receipt.available_slots(), SLOTS * i);
+            assert_eq!(receipt.subscription_expiry(), start + DURATION);
+        }
+        let user_info = gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.subscription_start, start);
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap(),
+            user_info
+        );
+
+        // Slots are added up to u32::MAX
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = u32::MAX;
+        assert_eq!(
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::MaxSlotsReached)
+        );
+
+        // Expired subscriptions need to be renewed before being topped up
+        gatekeeper
+            .last_known_block_height
+            .store(start + DURATION, Ordering::Relaxed);
+        assert_eq!(
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::SubscriptionExpired(start + DURATION))
+        );
+        gatekeeper.renew(user_id).unwrap();
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = 0;
+        assert_eq!(gatekeeper.top_up(user_id).unwrap().available_slots(), SLOTS);
     }
 
     #[test]
-    fn test_authenticate_user() {
-        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+    fn test_renew_top_up_paid() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+        let start = START_HEIGHT as u32;
 
-        // Authenticate user returns the UserId if the user is found in the system, or an AuthenticationError otherwise.
+        let user_id = get_random_user_id();
+        if let Err(RegistrationFailure::PaymentRequired(invoice)) = gatekeeper.register(user_id, "")
+        {
+            verifier.settle(&invoice.payment_hash);
+        }
+        gatekeeper.register(user_id, "").unwrap();
 
-        // Let's first check with an unknown user
-        let message = "message".as_bytes();
-        let wrong_signature = "signature";
+        // Top ups are charged, and calling again before paying returns the same invoice
+        let top_up_invoice = match gatekeeper.top_up(user_id) {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected top up result: {:?}", r),
+        };
+        assert_eq!(top_up_invoice.amount_msat, PRICE_MSAT);
         assert_eq!(
-            gatekeeper.authenticate_user(message, wrong_signature),
-            Err(AuthenticationFailure("Wrong message or signature."))
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::PaymentRequired(top_up_invoice.clone()))
+        );
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_pending_registrations()
+                .get(&user_id)
+                .map(|registrations| registrations[0].operation),
+            Some(SubscriptionOperation::TopUp)
         );
 
-        // Let's now provide data generated by an actual user, still the user is unknown
-        let (user_sk, user_pk) = get_random_keypair();
-        let signature = cryptography::sign(message, &user_sk).unwrap();
+        // A paid top up is completed even if the user comes back asking for a renewal, which is charged anew
+        verifier.settle(&top_up_invoice.payment_hash);
+        gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
+        let renewal_invoice = match gatekeeper.renew(user_id) {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected renewal result: {:?}", r),
+        };
+        assert_ne!(renewal_invoice, top_up_invoice);
         assert_eq!(
-            gatekeeper.authenticate_user(message, &signature),
-            Err(AuthenticationFailure("User not found."))
+            gatekeeper.get_user_info(user_id).unwrap().available_slots,
+            SLOTS * 2
+        );
+        assert_eq!(
+            gatekeeper
+                .get_user_info(user_id)
+                .unwrap()
+                .subscription_expiry,
+            start + DURATION
         );
 
-        // Last, let's add the user to the Gatekeeper and try again.
-        let user_id = UserId(user_pk);
-        gatekeeper.add_update_user(user_id).unwrap();
+        // Once paid, the renewal is completed
+        verifier.settle(&renewal_invoice.payment_hash);
+        let receipt = gatekeeper.renew(user_id).unwrap();
+        assert_eq!(receipt.subscription_expiry(), start + DURATION * 2);
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+
+        // Paid operations whose invoice has expired are completed on the next sweep
+        let top_up_invoice = match gatekeeper.top_up(user_id) {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected top up result: {:?}", r),
+        };
+        verifier.settle(&top_up_invoice.payment_hash);
+        expire_pending_registration(&gatekeeper, user_id);
+        gatekeeper.resolve_expired_registrations();
         assert_eq!(
-            gatekeeper.authenticate_user(message, &signature),
-            Ok(user_id)
+            gatekeeper.get_user_info(user_id).unwrap().available_slots,
+            SLOTS * 3
         );
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
     }
 
     #[test]
-    fn test_add_update_user() {
+    fn test_paid_invoices_are_honored_after_other_requests() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
-        let gatekeeper = init_gatekeeper(&chain);
-
-        // add_update_user adds a user to the system if it is not still registered, otherwise it add slots to the user subscription
-        // and refreshes the subscription expiry. Slots are added up to u32:MAX, further call will return an MaxSlotsReached error.
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+        let start = START_HEIGHT as u32;
 
-        // Let's start by adding new user
         let user_id = get_random_user_id();
-        let receipt = gatekeeper.add_update_user(user_id).unwrap();
-        // The data should have been also added to the database
-        assert_eq!(
-            gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap(),
-            UserInfo::new(
-                receipt.available_slots(),
-                receipt.subscription_start(),
-                receipt.subscription_expiry()
-            )
-        );
+        if let Err(RegistrationFailure::PaymentRequired(invoice)) = gatekeeper.register(user_id, "")
+        {
+            verifier.settle(&invoice.payment_hash);
+        }
+        gatekeeper.register(user_id, "").unwrap();
+        gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
 
-        // Let generate a new block and add the user again to check that both the slots and expiry are updated.
-        chain.generate(None);
-        gatekeeper
-            .last_known_block_height
-            .store(chain.get_block_count(), Ordering::Relaxed);
-        let updated_receipt = gatekeeper.add_update_user(user_id).unwrap();
+        // The user is handed a renewal invoice, but someone else requests a top up on their behalf before it is paid
+        let renewal_invoice = match gatekeeper.renew(user_id) {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected renewal result: {:?}", r),
+        };
+        let top_up_invoice = match gatekeeper.top_up(user_id) {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected top up result: {:?}", r),
+        };
+        assert_ne!(renewal_invoice, top_up_invoice);
 
-        assert_eq!(updated_receipt.available_slots(), SLOTS * 2);
+        // Both invoices are kept, and asking for either operation again returns its own invoice
         assert_eq!(
-            updated_receipt.subscription_expiry(),
-            START_HEIGHT as u32 + DURATION * 2
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::PaymentRequired(
+                renewal_invoice.clone()
+            ))
+        );
+        assert_eq!(
+            gatekeeper.top_up(user_id),
+            Err(RegistrationFailure::PaymentRequired(top_up_invoice.clone()))
+        );
+        assert_eq!(
+            gatekeeper.dbm.lock().unwrap().load_pending_registrations()[&user_id].len(),
+            2
         );
 
-        // Data in the database should have been updated too
+        // The renewal invoice is still honored once paid, and the top up one is kept
+        verifier.settle(&renewal_invoice.payment_hash);
+        let receipt = gatekeeper.renew(user_id).unwrap();
+        assert_eq!(receipt.subscription_expiry(), start + DURATION * 2);
         assert_eq!(
-            gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap(),
-            UserInfo::new(
-                updated_receipt.available_slots(),
-                updated_receipt.subscription_start(),
-                updated_receipt.subscription_expiry()
-            )
+            gatekeeper.pending_registrations.lock().unwrap()[&user_id]
+                .iter()
+                .map(|registration| &registration.invoice)
+                .collect::<Vec<_>>(),
+            vec![&top_up_invoice]
         );
 
-        // If the slot count reaches u32::MAX we should receive an error
+        // Same if the user comes back asking for something else after paying
+        verifier.settle(&top_up_invoice.payment_hash);
         gatekeeper
-            .registered_users
-            .lock()
-            .unwrap()
-            .get_mut(&user_id)
-            .unwrap()
-            .available_slots = u32::MAX;
-
+            .last_known_block_height
+            .store(start + DURATION + 1, Ordering::Relaxed);
         assert!(matches!(
-            gatekeeper.add_update_user(user_id),
-            Err(MaxSlotsReached)
+            gatekeeper.renew(user_id),
+            Err(RegistrationFailure::PaymentRequired(_))
         ));
-
-        // Data in the database remains untouched
         assert_eq!(
-            gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap(),
-            UserInfo::new(
-                updated_receipt.available_slots(),
-                updated_receipt.subscription_start(),
-                updated_receipt.subscription_expiry()
-            )
+            gatekeeper.get_user_info(user_id).unwrap().available_slots,
+            SLOTS * 2
         );
     }
 
@@ -553,7 +2459,9 @@ mod tests {
 
         // Let's first add the a user to the Gatekeeper (inputs are always sanitized here, so we don't need tests for non-registered users)
         let user_id = get_random_user_id();
-        gatekeeper.add_update_user(user_id).unwrap();
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
 
         // Now let's add a new appointment
         let slots_before = gatekeeper
@@ -575,7 +2483,13 @@ mod tests {
 
         // Slots should have been updated in the database too. Notice the appointment won't be there yet
         // given the Watcher is responsible for adding it, and it will do so after calling this method
-        let mut loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        let mut loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, available_slots);
 
         // Adding the exact same appointment should leave the slots count unchanged
@@ -586,7 +2500,13 @@ mod tests {
             .appointments
             .contains_key(&uuid));
         assert_eq!(updated_slot_count, available_slots);
-        loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, updated_slot_count);
 
         // If we add an update to an existing appointment with a bigger data blob (modulo ENCRYPTED_BLOB_MAX_SIZE), additional slots should be taken
@@ -599,7 +2519,13 @@ mod tests {
             .appointments
             .contains_key(&uuid));
         assert_eq!(updated_slot_count, available_slots - 1);
-        loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, updated_slot_count);
 
         // Adding back a smaller update (modulo ENCRYPTED_BLOB_MAX_SIZE) should reduce the count
@@ -610,7 +2536,13 @@ mod tests {
             .appointments
             .contains_key(&uuid));
         assert_eq!(updated_slot_count, available_slots);
-        loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, updated_slot_count);
 
         // Adding an appointment with a different uuid should not count as an update
@@ -622,7 +2554,13 @@ mod tests {
             .appointments
             .contains_key(&new_uuid));
         assert_eq!(updated_slot_count, available_slots - 1);
-        loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, updated_slot_count);
 
         // Finally, trying to add an appointment when the user has no enough slots should fail
@@ -633,15 +2571,70 @@ mod tests {
             .get_mut(&user_id)
             .unwrap()
             .available_slots = 0;
-        assert!(matches!(
+        assert_eq!(
             gatekeeper.add_update_appointment(user_id, generate_uuid(), &appointment),
-            Err(NotEnoughSlots)
-        ));
+            Err(NotEnoughSlots {
+                required_slots: 1,
+                available_slots: 0
+            })
+        );
         // The entry in the database should remain unchanged in this case
-        loaded_user = gatekeeper.dbm.lock().unwrap().load_user(user_id).unwrap();
+        loaded_user = gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(loaded_user.available_slots, updated_slot_count);
     }
 
+    #[test]
+    fn test_try_update_user() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
+        let available_slots = gatekeeper.get_user_info(user_id).unwrap().available_slots;
+        let uuid = generate_uuid();
+        let appointment = generate_dummy_appointment(None);
+
+        // If the update fails, the user is left untouched
+        assert_eq!(
+            gatekeeper.try_update_user(user_id, |user_info, slot_size| {
+                Gatekeeper::update_slots(user_info, uuid, &appointment, slot_size).unwrap();
+                Err::<(), _>(())
+            }),
+            Err(())
+        );
+        assert!(!gatekeeper.registered_users.lock().unwrap()[&user_id]
+            .appointments
+            .contains_key(&uuid));
+
+        // Otherwise, slots are updated in memory, but the database is not touched
+        assert_eq!(
+            gatekeeper.try_update_user(user_id, |user_info, slot_size| {
+                Gatekeeper::update_slots(user_info, uuid, &appointment, slot_size)
+            }),
+            Ok(available_slots - 1)
+        );
+        assert!(gatekeeper.registered_users.lock().unwrap()[&user_id]
+            .appointments
+            .contains_key(&uuid));
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap()
+                .available_slots,
+            available_slots
+        );
+    }
+
     #[test]
     fn test_has_subscription_expired() {
         let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
@@ -654,7 +2647,9 @@ mod tests {
         ));
 
         // If the user is registered and the subscription is active we should get (false, expiry)
-        gatekeeper.add_update_user(user_id).unwrap();
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
         assert_eq!(
             gatekeeper.has_subscription_expired(user_id),
             Ok((false, DURATION + START_HEIGHT as u32))
@@ -687,7 +2682,9 @@ mod tests {
 
         // Adding a user whose subscription is outdated should return an entry
         let user_id = get_random_user_id();
-        gatekeeper.add_update_user(user_id).unwrap();
+        gatekeeper
+            .add_update_user(user_id, &gatekeeper.tiers[0])
+            .unwrap();
 
         // Add also an appointment so we can check the returned data
         let appointment = generate_dummy_appointment(None);
@@ -706,6 +2703,60 @@ mod tests {
         assert_eq!(outdated_users[&user_id], HashSet::from_iter([uuid]));
     }
 
+    #[test]
+    fn test_get_outdated_users_renewed() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = init_gatekeeper(&chain);
+        let user_id = get_random_user_id();
+        let receipt = gatekeeper.register(user_id, "").unwrap();
+
+        // Users are outdated once the grace period after their expiry has passed
+        let outdates_at = receipt.subscription_expiry() + EXPIRY_DELTA;
+        assert_eq!(
+            gatekeeper.get_outdated_user_ids(outdates_at),
+            HashSet::from_iter([user_id])
+        );
+
+        // Renewing within the grace period moves the outdating forward, so the user is kept when the block comes
+        while chain.get_block_count() < outdates_at - 1 {
+            gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
+        }
+        let receipt = gatekeeper.renew(user_id).unwrap();
+        assert!(gatekeeper.get_outdated_users(outdates_at).is_empty());
+        gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
+        assert!(gatekeeper.get_user_info(user_id).is_some());
+
+        // And is outdated following the new expiry instead
+        let outdates_at = receipt.subscription_expiry() + EXPIRY_DELTA;
+        while chain.get_block_count() < outdates_at {
+            assert!(gatekeeper.get_user_info(user_id).is_some());
+            gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
+        }
+        assert!(gatekeeper.get_user_info(user_id).is_none());
+        assert!(gatekeeper
+            .dbm
+            .lock()
+            .unwrap()
+            .load_user(user_id)
+            .unwrap()
+            .is_none());
+
+        // Computing the outdating of subscriptions expiring at the end of time does not overflow
+        gatekeeper.register(user_id, "").unwrap();
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .subscription_expiry = u32::MAX;
+        assert_eq!(
+            gatekeeper.get_outdated_user_ids(u32::MAX),
+            HashSet::from_iter([user_id])
+        );
+        assert!(gatekeeper.get_outdated_users(u32::MAX - 1).is_empty());
+    }
+
     #[test]
     fn test_get_outdated_appointments() {
         let start_height = START_HEIGHT as u32 + EXPIRY_DELTA;
@@ -763,7 +2814,9 @@ mod tests {
 
         // If there's matching data in the gatekeeper it should be deleted
         for (uuid, user_id) in to_be_deleted.iter() {
-            gatekeeper.add_update_user(*user_id).unwrap();
+            gatekeeper
+                .add_update_user(*user_id, &gatekeeper.tiers[0])
+                .unwrap();
             gatekeeper
                 .add_update_appointment(*user_id, *uuid, &generate_dummy_appointment(None))
                 .unwrap();
@@ -779,7 +2832,7 @@ mod tests {
             // The slot count should be decreased now too (both in memory and in the database)
             assert_ne!(
                 gatekeeper.registered_users.lock().unwrap()[user_id].available_slots,
-                gatekeeper.subscription_slots
+                gatekeeper.tiers[0].slots
             );
             assert_ne!(
                 gatekeeper
@@ -788,8 +2841,9 @@ mod tests {
                     .unwrap()
                     .load_user(*user_id)
                     .unwrap()
+                    .unwrap()
                     .available_slots,
-                gatekeeper.subscription_slots
+                gatekeeper.tiers[0].slots
             );
         }
         for (_, user_id) in rest.iter() {
@@ -810,7 +2864,7 @@ mod tests {
             // The slot count is back to default
             assert_eq!(
                 gatekeeper.registered_users.lock().unwrap()[user_id].available_slots,
-                gatekeeper.subscription_slots
+                gatekeeper.tiers[0].slots
             );
         }
         for (_, user_id) in rest.iter() {
@@ -848,7 +2902,13 @@ mod tests {
                 .lock()
                 .unwrap()
                 .contains_key(user_id));
-            assert!(gatekeeper.dbm.lock().unwrap().load_user(*user_id).is_none());
+            assert!(gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(*user_id)
+                .unwrap()
+                .is_none());
         }
 
         // Check that the last_known_block_header has been properly updated
@@ -858,6 +2918,165 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_expired_registrations() {
+        // Pending registrations whose invoice has expired are resolved on every sweep. Paid ones are completed while
+        // the rest are dropped
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+
+        let paid_user_id = get_random_user_id();
+        let unpaid_user_id = get_random_user_id();
+        let pending_user_id = get_random_user_id();
+        for user_id in [paid_user_id, unpaid_user_id, pending_user_id].iter() {
+            if let Err(RegistrationFailure::PaymentRequired(invoice)) =
+                gatekeeper.register(*user_id, "")
+            {
+                if *user_id == paid_user_id {
+                    verifier.settle(&invoice.payment_hash);
+                }
+            }
+        }
+        expire_pending_registration(&gatekeeper, paid_user_id);
+        expire_pending_registration(&gatekeeper, unpaid_user_id);
+
+        // Expired registrations are not resolved by block processing anymore
+        gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
+        assert_eq!(gatekeeper.pending_registrations.lock().unwrap().len(), 3);
+
+        // Registrations are kept if payments cannot be checked
+        verifier.set_unreachable(true);
+        gatekeeper.resolve_expired_registrations();
+        assert_eq!(gatekeeper.pending_registrations.lock().unwrap().len(), 3);
+
+        verifier.set_unreachable(false);
+        gatekeeper.resolve_expired_registrations();
+        let pending_registrations = gatekeeper.pending_registrations.lock().unwrap().clone();
+        assert_eq!(
+            pending_registrations.keys().collect::<Vec<_>>(),
+            vec![&pending_user_id]
+        );
+        assert_eq!(
+            gatekeeper.dbm.lock().unwrap().load_pending_registrations(),
+            pending_registrations
+        );
+
+        let user_info = gatekeeper.get_user_info(paid_user_id).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert_eq!(user_info.subscription_start, chain.get_block_count());
+        assert!(gatekeeper.get_user_info(unpaid_user_id).is_none());
+        assert!(gatekeeper.get_user_info(pending_user_id).is_none());
+    }
+
+    #[test]
+    fn test_payment_checks_do_not_lock_registrations() {
+        // The pending registrations are not kept locked while payments are checked, so a slow payment backend does
+        // not stall everyone else
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+        let gatekeeper = Arc::new(gatekeeper);
+
+        let user_id = get_random_user_id();
+        let invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        let expired_user_id = get_random_user_id();
+        gatekeeper.register(expired_user_id, "").unwrap_err();
+        expire_pending_registration(&gatekeeper, user_id);
+        expire_pending_registration(&gatekeeper, expired_user_id);
+
+        verifier.set_stalled(true);
+        let g = gatekeeper.clone();
+        let registration = std::thread::spawn(move || g.register(user_id, ""));
+        let g = gatekeeper.clone();
+        let sweep = std::thread::spawn(move || g.resolve_expired_registrations());
+        while verifier.stalled_checks() < 2 {
+            std::thread::yield_now();
+        }
+
+        // Other users can register while both checks are stalled
+        let other_user_id = get_random_user_id();
+        assert!(matches!(
+            gatekeeper.register(other_user_id, ""),
+            Err(RegistrationFailure::PaymentRequired(_))
+        ));
+        assert_eq!(gatekeeper.pending_registrations.lock().unwrap().len(), 3);
+
+        // The paid registration is checked by both the user and the sweep, but it is only completed once. If the sweep
+        // gets there first, the user gets the receipt of the registration instead of being charged again
+        verifier.settle(&invoice.payment_hash);
+        verifier.set_stalled(false);
+        sweep.join().unwrap();
+        let receipt = registration.join().unwrap().unwrap();
+        let user_info = gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert_eq!(
+            receipt,
+            RegistrationReceipt::new(
+                user_id,
+                SLOTS,
+                user_info.subscription_start,
+                user_info.subscription_expiry,
+                String::new(),
+            )
+        );
+
+        let pending_registrations = gatekeeper.pending_registrations.lock().unwrap();
+        assert!(pending_registrations.contains_key(&other_user_id));
+        assert!(!pending_registrations.contains_key(&expired_user_id));
+        assert!(gatekeeper.get_user_info(expired_user_id).is_none());
+    }
+
+    #[test]
+    fn test_register_completed_meanwhile() {
+        // Users whose paid registration is completed by someone else (e.g. the sweep) while they are checking its
+        // payment get the receipt of the registration instead of being charged again
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let (gatekeeper, verifier) = init_paid_gatekeeper(&chain);
+        let gatekeeper = Arc::new(gatekeeper);
+
+        let user_id = get_random_user_id();
+        let invoice = match gatekeeper.register(user_id, "") {
+            Err(RegistrationFailure::PaymentRequired(invoice)) => invoice,
+            r => panic!("Unexpected registration result: {:?}", r),
+        };
+        verifier.settle(&invoice.payment_hash);
+
+        verifier.set_stalled(true);
+        let g = gatekeeper.clone();
+        let registration = std::thread::spawn(move || g.register(user_id, ""));
+        while verifier.stalled_checks() < 1 {
+            std::thread::yield_now();
+        }
+        let pending_registration =
+            gatekeeper.pending_registrations.lock().unwrap()[&user_id][0].clone();
+        assert_eq!(
+            gatekeeper.complete_pending_registration(
+                user_id,
+                &pending_registration,
+                &gatekeeper.tiers[0]
+            ),
+            Some(Ok(()))
+        );
+        verifier.set_stalled(false);
+
+        let user_info = gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert_eq!(
+            registration.join().unwrap(),
+            Ok(RegistrationReceipt::new(
+                user_id,
+                SLOTS,
+                user_info.subscription_start,
+                user_info.subscription_expiry,
+                String::new(),
+            ))
+        );
+        assert_eq!(gatekeeper.get_user_info(user_id).unwrap(), user_info);
+        assert!(gatekeeper.pending_registrations.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_block_disconnected() {
         // Block disconnected simply updates the last known block
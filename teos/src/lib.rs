@@ -7,23 +7,33 @@
 pub mod protos {
     tonic::include_proto!("teos.v2");
 }
+pub mod anti_spam;
 pub mod api;
+pub mod backends;
+pub mod backup;
 pub mod bitcoin_cli;
 pub mod carrier;
 pub mod chain_monitor;
 pub mod cli_config;
 pub mod config;
 pub mod dbm;
+pub mod encryption;
 #[doc(hidden)]
 mod errors;
+pub mod esplora;
 mod extended_appointment;
 pub mod gatekeeper;
+pub mod payments;
+pub mod postgres_dbm;
+pub mod rate_limiter;
 pub mod responder;
 #[doc(hidden)]
 mod rpc_errors;
 pub mod tls;
-mod tx_index;
+pub mod tx_index;
+pub mod wallet;
 pub mod watcher;
+pub mod zmq;
 
 #[cfg(test)]
 mod test_utils;
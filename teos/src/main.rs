@@ -1,16 +1,22 @@
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
+use std::collections::HashMap;
 use std::fs;
 use std::io::ErrorKind;
-use std::ops::{Deref, DerefMut};
+use std::ops::DerefMut;
 use std::str::FromStr;
 use std::sync::{Arc, Condvar, Mutex};
+use std::time;
 use structopt::StructOpt;
+use tokio::sync::{mpsc, Notify};
 use tokio::task;
+use tokio::time::{interval, MissedTickBehavior};
 use tonic::transport::{Certificate, Server, ServerTlsConfig};
 
+use bitcoin::consensus;
 use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::Address;
 use bitcoincore_rpc::{Auth, Client, RpcApi};
 use lightning_block_sync::init::validate_best_block_header;
 use lightning_block_sync::poll::{
@@ -18,20 +24,31 @@ use lightning_block_sync::poll::{
 };
 use lightning_block_sync::{BlockSource, BlockSourceError, SpvClient, UnboundedCache};
 
+use teos::anti_spam::{derive_secret, ProofOfWorkGate, RegistrationGate, TokenGate};
 use teos::api::internal::InternalAPI;
 use teos::api::{http, tor::TorAPI};
-use teos::bitcoin_cli::BitcoindClient;
-use teos::carrier::Carrier;
+use teos::backends::{BitcoindBackend, BroadcastBackend, EsploraBackend};
+use teos::backup::{self, Backup, DB_FILE};
+use teos::bitcoin_cli::{BitcoindBackends, BitcoindClient};
+use teos::carrier::{Carrier, TxBroadcaster};
 use teos::chain_monitor::ChainMonitor;
 use teos::config::{self, Config, Opt};
-use teos::dbm::DBM;
-use teos::gatekeeper::Gatekeeper;
+use teos::dbm::{Storage, DBM};
+use teos::encryption::{get_new_passphrase, get_passphrase, reencrypt_tower_keys, TowerKey};
+use teos::esplora::{EsploraBroadcaster, EsploraClient};
+use teos::gatekeeper::{Gatekeeper, EXPIRED_REGISTRATIONS_SWEEP_DELTA};
+use teos::payments::{ClnVerifier, LndVerifier, PaymentVerifier};
+use teos::postgres_dbm::PostgresDBM;
 use teos::protos as msgs;
 use teos::protos::private_tower_services_server::PrivateTowerServicesServer;
 use teos::protos::public_tower_services_server::PublicTowerServicesServer;
+use teos::rate_limiter::RateLimiter;
 use teos::responder::Responder;
 use teos::tls::tls_init;
+use teos::tx_index::BlockSummary;
+use teos::wallet::{FeeRatePolicy, Wallet};
 use teos::watcher::Watcher;
+use teos::zmq::ZmqSubscriber;
 
 use teos_common::constants::IRREVOCABLY_RESOLVED;
 use teos_common::cryptography::get_random_keypair;
@@ -44,7 +61,7 @@ async fn get_last_n_blocks<B, T>(
 ) -> Result<Vec<ValidatedBlock>, BlockSourceError>
 where
     B: DerefMut<Target = T> + Sized + Send + Sync,
-    T: BlockSource,
+    T: BlockSource + ?Sized,
 {
     let mut last_n_blocks = Vec::with_capacity(n);
     for _ in 0..n {
@@ -56,12 +73,95 @@ where
     Ok(last_n_blocks)
 }
 
-fn create_new_tower_keypair(db: &DBM) -> (SecretKey, PublicKey) {
+fn create_new_tower_keypair(db: &dyn Storage, passphrase: Option<&str>) -> (SecretKey, PublicKey) {
     let (sk, pk) = get_random_keypair();
-    db.store_tower_key(&sk).unwrap();
+    let key = match passphrase {
+        Some(passphrase) => TowerKey::encrypt(&sk, passphrase),
+        None => TowerKey::Plaintext(sk),
+    };
+    db.store_tower_key(&key).unwrap();
     (sk, pk)
 }
 
+/// Gets the tower passphrase, asking for it only the first time it is needed.
+fn unlock_passphrase(passphrase: &mut Option<String>, passphrase_file: &str) -> String {
+    passphrase
+        .get_or_insert_with(|| {
+            get_passphrase(passphrase_file).unwrap_or_else(|e| {
+                log::error!("Cannot get the tower passphrase. Error: {e}");
+                std::process::exit(1);
+            })
+        })
+        .clone()
+}
+
+/// Encrypts the keys held in plaintext under the given passphrase, leaving out the ones that are already encrypted.
+fn encrypt_plaintext_keys(
+    keys: HashMap<u32, TowerKey>,
+    passphrase: &str,
+) -> HashMap<u32, TowerKey> {
+    keys.into_iter()
+        .filter_map(|(id, key)| match key {
+            TowerKey::Plaintext(sk) => Some((id, TowerKey::encrypt(&sk, passphrase))),
+            TowerKey::Encrypted(_) => None,
+        })
+        .collect()
+}
+
+/// Encrypts the tower and wallet keys held in plaintext under the given passphrase, old keys included.
+fn encrypt_tower_keys(db: &mut dyn Storage, passphrase: &str) {
+    let keys = encrypt_plaintext_keys(db.load_all_tower_keys(), passphrase);
+    let wallet_keys = encrypt_plaintext_keys(db.load_all_wallet_keys(), passphrase);
+
+    if !keys.is_empty() || !wallet_keys.is_empty() {
+        log::info!("Encrypting the tower keys");
+        db.update_tower_keys(&keys, &wallet_keys)
+            .unwrap_or_else(|e| {
+                log::error!("Cannot encrypt the tower keys. Error: {e:?}");
+                std::process::exit(1);
+            });
+    }
+}
+
+/// Makes bitcoind track the given address, so its utxos can be queried.
+///
+/// Legacy wallets import the address straightaway. Descriptor wallets (the default since Bitcoin Core 23) do not support
+/// `importaddress`, so an `addr()` descriptor is imported instead. This requires the wallet to have private keys disabled.
+fn track_address(rpc: &Client, address: &Address) -> Result<(), String> {
+    let wallet_info: serde_json::Value =
+        rpc.call("getwalletinfo", &[]).map_err(|e| e.to_string())?;
+
+    if wallet_info["descriptors"].as_bool() != Some(true) {
+        return rpc
+            .import_address(address, Some("teos"), Some(false))
+            .map_err(|e| e.to_string());
+    }
+
+    let descriptor = format!("addr({address})");
+    let checksum = rpc
+        .get_descriptor_info(&descriptor)
+        .map_err(|e| e.to_string())?
+        .checksum;
+    let result: serde_json::Value = rpc
+        .call(
+            "importdescriptors",
+            &[serde_json::json!([{
+                "desc": format!("{descriptor}#{checksum}"),
+                "timestamp": "now",
+                "label": "teos",
+            }])],
+        )
+        .map_err(|e| e.to_string())?;
+
+    match result[0]["success"].as_bool() {
+        Some(true) => Ok(()),
+        _ => Err(result[0]["error"]["message"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_owned()),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let opt = Opt::from_args();
@@ -121,115 +221,337 @@ async fn main() {
         conf.log_non_default_options();
     }
 
-    let dbm = Arc::new(Mutex::new(
-        DBM::new(path_network.join("teos_db.sql3")).unwrap(),
-    ));
+    // The passphrase is only needed if some data is (or has to be) encrypted. When changing it, both the current and
+    // the new one are asked for upfront
+    let mut passphrase = None;
+    let new_passphrase = conf.change_passphrase.then(|| {
+        unlock_passphrase(&mut passphrase, &conf.passphrase_file);
+        get_new_passphrase().unwrap_or_else(|e| {
+            log::error!("Cannot get the new tower passphrase. Error: {e}");
+            std::process::exit(1);
+        })
+    });
+
+    // Restore the tower data from a backup, if requested, so the tower starts on top of it. The tower id and chain tip
+    // of the backup are validated before restoring anything, and the chain tip is checked against the chain backend
+    // once it is up
+    if !conf.restore.is_empty() {
+        let backup_path = config::data_dir_absolute_path(conf.restore.clone());
+        let manifest = backup::read_manifest(&backup_path).unwrap_or_else(|e| {
+            log::error!("Cannot restore the tower data. Error: {e}");
+            std::process::exit(1);
+        });
+        if manifest.encrypted_database && !conf.encrypt_database {
+            log::error!("The backup database is encrypted. Set encrypt_database to restore it");
+            std::process::exit(1);
+        }
+        let backup_passphrase = (manifest.encrypted_database || manifest.encrypted_tower_key)
+            .then(|| unlock_passphrase(&mut passphrase, &conf.passphrase_file));
+        match backup::restore(
+            &backup_path,
+            &path,
+            &conf.btc_network,
+            backup_passphrase.as_deref(),
+        ) {
+            Ok(manifest) => log::info!(
+                "Restored the data of tower {} from {:?}",
+                manifest.tower_id,
+                backup_path
+            ),
+            Err(e) => {
+                log::error!("Cannot restore the tower data. Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // The SQLite backend is also kept apart in case the database is encrypted and its passphrase has to be changed
+    let mut sqlite_dbm = None;
+    let dbm: Arc<Mutex<dyn Storage>> = if conf.database_backend == "postgres" {
+        match PostgresDBM::new(&conf.postgres_url) {
+            Ok(dbm) => Arc::new(Mutex::new(dbm)),
+            Err(e) => {
+                log::error!("Cannot open the PostgreSQL database. Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let db_path = path_network.join(DB_FILE);
+        let result = if conf.encrypt_database {
+            DBM::new_encrypted(
+                db_path,
+                &unlock_passphrase(&mut passphrase, &conf.passphrase_file),
+            )
+        } else {
+            DBM::new(db_path)
+        };
+        match result {
+            Ok(dbm) => {
+                let dbm = Arc::new(Mutex::new(dbm));
+                sqlite_dbm = Some(dbm.clone());
+                dbm
+            }
+            Err(e) => {
+                log::error!("Cannot open the database. Error: {e}");
+                if conf.encrypt_database {
+                    log::error!("Make sure the tower passphrase is right");
+                }
+                std::process::exit(1);
+            }
+        }
+    };
+
+    if conf.encrypt_tower_key {
+        let passphrase = unlock_passphrase(&mut passphrase, &conf.passphrase_file);
+        encrypt_tower_keys(&mut *dbm.lock().unwrap(), &passphrase);
+    }
+
+    if let Some(new_passphrase) = new_passphrase {
+        let passphrase = passphrase.unwrap();
+        let (keys, wallet_keys) = {
+            let locked_db = dbm.lock().unwrap();
+            let reencrypt = |keys| {
+                reencrypt_tower_keys(keys, &passphrase, &new_passphrase).unwrap_or_else(|e| {
+                    log::error!("Cannot decrypt the tower keys. Error: {e}");
+                    std::process::exit(1);
+                })
+            };
+            (
+                reencrypt(locked_db.load_all_tower_keys()),
+                reencrypt(locked_db.load_all_wallet_keys()),
+            )
+        };
+
+        // The database and the keys are either both changed or both left under the current passphrase
+        let result = match sqlite_dbm.filter(|_| conf.encrypt_database) {
+            Some(sqlite_dbm) => sqlite_dbm.lock().unwrap().change_passphrase(
+                &passphrase,
+                &new_passphrase,
+                &keys,
+                &wallet_keys,
+            ),
+            None => dbm.lock().unwrap().update_tower_keys(&keys, &wallet_keys),
+        };
+        result.unwrap_or_else(|e| {
+            log::error!("Cannot change the tower passphrase. Error: {e:?}");
+            std::process::exit(1);
+        });
+        log::info!("Tower passphrase changed");
+        std::process::exit(0);
+    }
 
     // Load tower secret key or create a fresh one if none is found. If overwrite key is set, create a new
     // key straightaway
     let (tower_sk, tower_pk) = {
         let locked_db = dbm.lock().unwrap();
+        let stored_key = locked_db.load_tower_key().unwrap_or_else(|e| {
+            log::error!("Cannot load the tower key from the database. Error: {e:?}");
+            std::process::exit(1);
+        });
+        let new_key_passphrase = conf
+            .encrypt_tower_key
+            .then(|| unlock_passphrase(&mut passphrase, &conf.passphrase_file));
         if conf.overwrite_key {
             log::info!("Overwriting tower keys");
-            create_new_tower_keypair(&locked_db)
-        } else if let Some(sk) = locked_db.load_tower_key() {
+            create_new_tower_keypair(&*locked_db, new_key_passphrase.as_deref())
+        } else if let Some(key) = stored_key {
+            let sk = match key {
+                TowerKey::Plaintext(sk) => sk,
+                TowerKey::Encrypted(_) => key
+                    .unlock(&unlock_passphrase(&mut passphrase, &conf.passphrase_file))
+                    .unwrap_or_else(|e| {
+                        log::error!("Cannot decrypt the tower key. Error: {e}");
+                        std::process::exit(1);
+                    }),
+            };
             (sk, PublicKey::from_secret_key(&Secp256k1::new(), &sk))
         } else {
             log::info!("Tower keys not found. Creating a fresh set");
-            create_new_tower_keypair(&locked_db)
+            create_new_tower_keypair(&*locked_db, new_key_passphrase.as_deref())
         }
     };
     log::info!("tower_id: {tower_pk}");
 
-    // Initialize our bitcoind client
-    let (bitcoin_cli, bitcoind_reachable) = match BitcoindClient::new(
-        &conf.btc_rpc_connect,
-        conf.btc_rpc_port,
-        &conf.btc_rpc_user,
-        &conf.btc_rpc_password,
-        &conf.btc_network,
-    )
-    .await
-    {
-        Ok(client) => (
-            Arc::new(client),
-            Arc::new((Mutex::new(true), Condvar::new())),
-        ),
-        Err(e) => {
-            let e_msg = match e.kind() {
-                ErrorKind::InvalidData => "invalid btcrpcuser or btcrpcpassword".into(),
-                _ => e.to_string(),
-            };
-            log::error!("Failed to connect to bitcoind. Error: {e_msg}");
-            std::process::exit(1);
-        }
+    // This is how chain poller names bitcoin networks.
+    let btc_network = match conf.btc_network.as_str() {
+        "main" => "bitcoin",
+        "test" => "testnet",
+        any => any,
     };
+    let network = Network::from_str(btc_network).unwrap();
 
-    // FIXME: Temporary. We're using bitcoin_core_rpc and rust-lightning's rpc until they both get merged
-    // https://github.com/rust-bitcoin/rust-bitcoincore-rpc/issues/166
-    let schema = if !conf.btc_rpc_connect.starts_with("http") {
-        "http://"
-    } else {
-        ""
+    // Initialize our chain backend. Blocks are pulled either from bitcoind or from an Esplora instance.
+    // In the latter, there is no bitcoind RPC client to interact with.
+    let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+    let (block_source, bitcoind): (Box<dyn BlockSource + '_>, _) =
+        if conf.chain_backend == "esplora" {
+            match EsploraClient::new(&conf.esplora_url, network).await {
+                Ok(client) => (Box::new(client), None),
+                Err(e) => {
+                    log::error!("Failed to connect to {}. Error: {e}", conf.esplora_url);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            // Our own bitcoind goes first, followed by the ones to fail over to
+            let bitcoind_backends = Arc::new(
+                conf.bitcoind_endpoints()
+                    .and_then(BitcoindBackends::new)
+                    .unwrap_or_else(|e| {
+                        log::error!("Cannot set the bitcoind backends. {e}");
+                        std::process::exit(1);
+                    }),
+            );
+
+            let bitcoin_cli =
+                match BitcoindClient::new(bitcoind_backends.clone(), &conf.btc_network).await {
+                    Ok(client) => client,
+                    Err(e) => {
+                        let e_msg = match e.kind() {
+                            ErrorKind::InvalidData => "invalid btcrpcuser or btcrpcpassword".into(),
+                            _ => e.to_string(),
+                        };
+                        log::error!("Failed to connect to bitcoind. Error: {e_msg}");
+                        std::process::exit(1);
+                    }
+                };
+            for endpoint in bitcoind_backends.endpoints().iter().skip(1) {
+                log::info!("Using {} as bitcoind fallback", endpoint.url());
+            }
+
+            // FIXME: Temporary. We're using bitcoin_core_rpc and rust-lightning's rpc until they both get merged
+            // https://github.com/rust-bitcoin/rust-bitcoincore-rpc/issues/166
+            let rpcs: Vec<_> = bitcoind_backends
+                .endpoints()
+                .iter()
+                .map(|endpoint| {
+                    Arc::new(
+                        Client::new(
+                            &endpoint.url(),
+                            Auth::UserPass(endpoint.user.clone(), endpoint.password.clone()),
+                        )
+                        .unwrap(),
+                    )
+                })
+                .collect();
+            (Box::new(bitcoin_cli), Some((rpcs, bitcoind_backends)))
+        };
+    let rpc = bitcoind
+        .as_ref()
+        .map(|(rpcs, backends)| rpcs[backends.active()].clone());
+
+    // Load last known block from DB if found. Poll it from the backend otherwise.
+    let last_known_block = dbm
+        .lock()
+        .unwrap()
+        .load_last_known_block()
+        .unwrap_or_else(|e| {
+            log::error!("Cannot load the last known block from the database. Error: {e:?}");
+            std::process::exit(1);
+        });
+    // Esplora serves the whole chain, so only bitcoind may be pruned
+    let prune_height = match &rpc {
+        Some(rpc) => rpc.get_blockchain_info().unwrap().prune_height,
+        None => None,
     };
-    let rpc = Arc::new(
-        Client::new(
-            &format!("{schema}{}:{}", conf.btc_rpc_connect, conf.btc_rpc_port),
-            Auth::UserPass(conf.btc_rpc_user.clone(), conf.btc_rpc_password.clone()),
-        )
-        .unwrap(),
-    );
-    let mut derefed = bitcoin_cli.deref();
-    // Load last known block from DB if found. Poll it from Bitcoind otherwise.
-    let last_known_block = dbm.lock().unwrap().load_last_known_block();
+    let mut last_known_height = None;
+    // Summaries of the blocks the tower indexes are built from, if the blocks themselves cannot be pulled from the backend
+    let mut block_summaries = None;
     let tip = if let Some(block_hash) = last_known_block {
-        let mut last_known_header = derefed
+        let mut last_known_header = block_source
             .get_header(&block_hash, None)
             .await
-            .unwrap()
-            .validate(block_hash)
-            .unwrap();
+            .ok()
+            .and_then(|header| header.validate(block_hash).ok())
+            .unwrap_or_else(|| {
+                log::error!("Cannot find the last known block ({block_hash}) in the chain backend");
+                if !conf.restore.is_empty() {
+                    log::error!("Make sure the backup belongs to a tower running on this chain");
+                }
+                std::process::exit(1);
+            });
 
         log::info!(
             "Last known block: {} (height: {})",
             last_known_header.header.block_hash(),
             last_known_header.height
         );
+        last_known_height = Some(last_known_header.height);
 
         // If we are running in pruned mode some data may be missing (if we happen to have been offline for a while)
-        if let Some(prune_height) = rpc.get_blockchain_info().unwrap().prune_height {
-            if last_known_header.height - IRREVOCABLY_RESOLVED + 1 < prune_height as u32 {
+        if let (Some(prune_height), Some(rpc)) = (prune_height, &rpc) {
+            let prune_height = prune_height as u32;
+            let first_needed = last_known_header.height - IRREVOCABLY_RESOLVED + 1;
+            if last_known_header.height + 1 < prune_height {
+                // Some of the blocks mined while we were offline are gone, so they cannot be checked for breaches
                 log::warn!(
                     "Cannot load blocks in the range {}-{}. Chain has gone too far out of sync",
-                    last_known_header.height - IRREVOCABLY_RESOLVED + 1,
-                    last_known_header.height
+                    last_known_header.height + 1,
+                    prune_height - 1
                 );
-                if conf.force_update {
+                if !dbm.lock().unwrap().has_appointments() {
+                    log::info!("No appointments could have been triggered in the missing blocks. Skipping them");
+                } else if conf.force_update {
                     log::info!("Forcing a backend update");
-                    // We want to grab the first IRREVOCABLY_RESOLVED we know about for the initial cache
-                    // So we can perform transitions from there onwards.
-                    let target_height = prune_height + IRREVOCABLY_RESOLVED as u64;
-                    let target_hash = rpc.get_block_hash(target_height).unwrap();
-                    last_known_header = derefed
-                        .get_header(
-                            &rpc.get_block_hash(target_height).unwrap(),
-                            Some(target_height as u32),
-                        )
-                        .await
-                        .unwrap()
-                        .validate(target_hash)
-                        .unwrap();
                 } else {
                     log::error!(
-                        "The underlying chain has gone too far out of sync. The tower block cache cannot be initialized. Run with --forceupdate to force update. THIS WILL, POTENTIALLY, MAKE THE TOWER MISS SOME OF ITS APPOINTMENTS"
+                        "The missing blocks may contain triggers for the appointments held by the tower, so they cannot be skipped. Connect to a node that still has them or run with --forceupdate to force update. THIS WILL, POTENTIALLY, MAKE THE TOWER MISS SOME OF ITS APPOINTMENTS"
                     );
                     std::process::exit(1);
                 }
+
+                // We want to grab the first IRREVOCABLY_RESOLVED we know about for the initial cache
+                // So we can perform transitions from there onwards.
+                let target_height = (prune_height + IRREVOCABLY_RESOLVED) as u64;
+                let target_hash = rpc.get_block_hash(target_height).unwrap();
+                last_known_header = block_source
+                    .get_header(&target_hash, Some(target_height as u32))
+                    .await
+                    .unwrap()
+                    .validate(target_hash)
+                    .unwrap();
+            } else if first_needed < prune_height {
+                // The missed blocks can still be pulled, but the ones the indexes are built from cannot. Rebuild them from the
+                // summaries stored by the tower, as long as they cover the required range.
+                let summaries = dbm.lock().unwrap().load_block_summaries();
+                if summaries.len() >= IRREVOCABLY_RESOLVED as usize
+                    && summaries[0].header.block_hash() == block_hash
+                    && summaries
+                        .windows(2)
+                        .all(|w| w[0].header.prev_blockhash == w[1].header.block_hash())
+                {
+                    log::info!(
+                        "Blocks in the range {first_needed}-{} have been pruned. Loading them from the database",
+                        last_known_header.height
+                    );
+                    block_summaries = Some(summaries);
+                } else {
+                    log::warn!(
+                        "Cannot load blocks in the range {first_needed}-{}. Chain has gone too far out of sync",
+                        last_known_header.height
+                    );
+                    if conf.force_update {
+                        log::info!("Forcing a backend update");
+                        let target_height = (prune_height + IRREVOCABLY_RESOLVED) as u64;
+                        let target_hash = rpc.get_block_hash(target_height).unwrap();
+                        last_known_header = block_source
+                            .get_header(&target_hash, Some(target_height as u32))
+                            .await
+                            .unwrap()
+                            .validate(target_hash)
+                            .unwrap();
+                    } else {
+                        log::error!(
+                            "The underlying chain has gone too far out of sync. The tower block cache cannot be initialized. Run with --forceupdate to force update. THIS WILL, POTENTIALLY, MAKE THE TOWER MISS SOME OF ITS APPOINTMENTS"
+                        );
+                        std::process::exit(1);
+                    }
+                }
             }
         }
         last_known_header
     } else {
-        validate_best_block_header(&derefed).await.unwrap()
+        validate_best_block_header(&*block_source).await.unwrap()
     };
 
     // DISCUSS: This is not really required (and only triggered in regtest). This is only in place so the caches can be
@@ -250,45 +572,236 @@ async fn main() {
         tip.height
     );
 
-    // This is how chain poller names bitcoin networks.
-    let btc_network = match conf.btc_network.as_str() {
-        "main" => "bitcoin",
-        "test" => "testnet",
-        any => any,
+    // The locator cache can be as deep as configured, as long as the blocks can still be pulled from the backend (or their
+    // transactions can be loaded from the database)
+    let available_blocks = match &block_summaries {
+        Some(summaries) => summaries
+            .iter()
+            .take_while(|summary| summary.txdata.is_some())
+            .count() as u32,
+        None => tip.height - prune_height.unwrap_or(0) as u32,
+    };
+    let locator_cache_depth = if conf.locator_cache_depth > available_blocks {
+        log::warn!(
+            "Cannot load {} blocks into the locator cache. Using the {available_blocks} available instead",
+            conf.locator_cache_depth
+        );
+        available_blocks
+    } else {
+        conf.locator_cache_depth
     };
+    let n_blocks = std::cmp::max(IRREVOCABLY_RESOLVED, locator_cache_depth) as usize;
+    // Blocks between the last known block and the tip (if it has been forced forward) won't be connected by the chain monitor
+    let missed_blocks = last_known_height.map_or(0, |height| {
+        std::cmp::min(tip.height.saturating_sub(height) as usize, n_blocks)
+    });
 
-    let mut poller = ChainPoller::new(&mut derefed, Network::from_str(btc_network).unwrap());
-    let last_n_blocks = get_last_n_blocks(&mut poller, tip, IRREVOCABLY_RESOLVED as usize)
-        .await.unwrap_or_else(|e| {
-            // I'm pretty sure this can only happen if we are pulling blocks from the target to the prune height, and by the time we get to
-            // the end at least one has been pruned.
-            log::error!("Couldn't load the latest {IRREVOCABLY_RESOLVED} blocks. Please try again (Error: {})", e.into_inner());
-            std::process::exit(1);
+    let mut poller = ChainPoller::new(block_source, network);
+    let last_n_blocks: Vec<BlockSummary> = match block_summaries {
+        Some(mut summaries) => {
+            summaries.truncate(n_blocks);
+            summaries
         }
-    );
+        None => {
+            let blocks = get_last_n_blocks(&mut poller, tip, n_blocks)
+                .await
+                .unwrap_or_else(|e| {
+                    // I'm pretty sure this can only happen if we are pulling blocks from the target to the prune height, and by the time we get to
+                    // the end at least one has been pruned.
+                    log::error!(
+                        "Couldn't load the latest {n_blocks} blocks. Please try again (Error: {})",
+                        e.into_inner()
+                    );
+                    std::process::exit(1);
+                });
+
+            // Transactions are only needed for the locator cache and for the blocks that need to be rescanned
+            let keep_txdata = std::cmp::max(locator_cache_depth as usize, missed_blocks);
+            let summaries: Vec<_> = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, block)| BlockSummary::new(block.header, &block.txdata, i < keep_txdata))
+                .collect();
+
+            // Keep a copy of the blocks in the database, so the indexes can be rebuilt even if they get pruned
+            let locked_db = dbm.lock().unwrap();
+            for (i, summary) in summaries.iter().enumerate() {
+                locked_db
+                    .store_block_summary(tip.height - i as u32, summary)
+                    .unwrap();
+            }
+            locked_db.prune_block_summaries(
+                tip.height - n_blocks as u32,
+                tip.height - locator_cache_depth,
+            );
+            summaries
+        }
+    };
 
     // Build components
-    let gatekeeper = Arc::new(Gatekeeper::new(
+    let mut gatekeeper = Gatekeeper::new(
         tip.height,
         conf.subscription_slots,
         conf.subscription_duration,
         conf.expiry_delta,
         dbm.clone(),
-    ));
+    )
+    .with_tiers(conf.subscription_tiers());
+
+    // Make users pay for their subscriptions, if a payment backend is set
+    let payment_verifier: Option<Arc<dyn PaymentVerifier>> = match conf.payment_backend.as_str() {
+        "cln" => Some(Arc::new(ClnVerifier::new(config::data_dir_absolute_path(
+            conf.cln_rpc_path.clone(),
+        )))),
+        "lnd" => {
+            let tls_cert_path = (!conf.lnd_tls_cert_path.is_empty())
+                .then(|| config::data_dir_absolute_path(conf.lnd_tls_cert_path.clone()));
+            match LndVerifier::new(
+                &conf.lnd_rest_url,
+                config::data_dir_absolute_path(conf.lnd_macaroon_path.clone()),
+                tls_cert_path,
+            ) {
+                Ok(verifier) => Some(Arc::new(verifier)),
+                Err(e) => {
+                    log::error!("Cannot load the LND credentials: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    if let Some(verifier) = payment_verifier {
+        log::info!("Subscriptions are paid using {}", conf.payment_backend);
+        gatekeeper = gatekeeper.with_payments(verifier, conf.invoice_expiry);
+    }
+    if conf.user_rate_limit > 0 {
+        gatekeeper = gatekeeper.with_rate_limit(conf.user_rate_limit, conf.user_rate_burst);
+    }
+    let gatekeeper = Arc::new(gatekeeper);
+
+    // Load the wallet used to bump penalty fees (or create a fresh one if none is found), if fee bumping is enabled
+    let wallet = if conf.cpfp {
+        let wallet_sk = {
+            let locked_db = dbm.lock().unwrap();
+            let stored_sk = locked_db.load_wallet_key().unwrap_or_else(|e| {
+                log::error!("Cannot load the wallet key from the database. Error: {e:?}");
+                std::process::exit(1);
+            });
+            match stored_sk {
+                Some(TowerKey::Plaintext(sk)) => sk,
+                Some(key) => key
+                    .unlock(&unlock_passphrase(&mut passphrase, &conf.passphrase_file))
+                    .unwrap_or_else(|e| {
+                        log::error!("Cannot decrypt the wallet key. Error: {e}");
+                        std::process::exit(1);
+                    }),
+                None => {
+                    log::info!("Wallet key not found. Creating a fresh one");
+                    let sk = get_random_keypair().0;
+                    let key = if conf.encrypt_tower_key {
+                        TowerKey::encrypt(
+                            &sk,
+                            &unlock_passphrase(&mut passphrase, &conf.passphrase_file),
+                        )
+                    } else {
+                        TowerKey::Plaintext(sk)
+                    };
+                    locked_db.store_wallet_key(&key).unwrap_or_else(|e| {
+                        log::error!("Cannot store the wallet key. Error: {e:?}");
+                        std::process::exit(1);
+                    });
+                    sk
+                }
+            }
+        };
+        let wallet = Wallet::new(
+            wallet_sk,
+            network,
+            FeeRatePolicy::new(
+                conf.cpfp_min_fee_rate,
+                conf.cpfp_max_fee_rate,
+                conf.cpfp_fee_rate_escalation,
+            ),
+        );
 
-    let carrier = Carrier::new(rpc, bitcoind_reachable.clone(), tip.height);
-    let responder = Arc::new(Responder::new(
-        &last_n_blocks,
+        // bitcoind needs to track the wallet address so its utxos can be queried (Esplora indexes every address).
+        // Every backend tracks it, so fees can still be bumped after failing over
+        if let Some((rpcs, _)) = &bitcoind {
+            for rpc in rpcs.iter() {
+                if let Err(e) = track_address(rpc, &wallet.address()) {
+                    log::warn!("Cannot import the wallet address into bitcoind. Fees won't be bumped unless the address is tracked (Error: {e})");
+                }
+            }
+        }
+        log::info!("Fee bumping wallet address: {}", wallet.address());
+        Some(wallet)
+    } else {
+        None
+    };
+
+    // Additional backends penalties are pushed through, on top of our own bitcoind
+    let mut backends: Vec<Arc<dyn BroadcastBackend>> = Vec::new();
+    let broadcast_endpoints = conf.broadcast_endpoints().unwrap_or_else(|e| {
+        log::error!("Cannot set the broadcast backends. {e}");
+        std::process::exit(1);
+    });
+    for endpoint in broadcast_endpoints {
+        let url = endpoint.url();
+        match BitcoindBackend::new(&url, Auth::UserPass(endpoint.user, endpoint.password)) {
+            Ok(backend) => backends.push(Arc::new(backend)),
+            Err(e) => log::warn!("Cannot use {url} as broadcast backend (Error: {e})"),
+        }
+    }
+    if !conf.esplora_broadcast_url.is_empty() {
+        backends.push(Arc::new(EsploraBackend::new(&conf.esplora_broadcast_url)));
+    }
+
+    let carrier: Box<dyn TxBroadcaster> = match bitcoind.clone() {
+        Some((rpcs, bitcoind_backends)) => {
+            for backend in backends.iter() {
+                log::info!(
+                    "Penalties will also be broadcast through {}",
+                    backend.name()
+                );
+            }
+            Box::new(
+                Carrier::new(rpcs[0].clone(), bitcoind_reachable.clone(), tip.height)
+                    .with_backends(backends)
+                    .with_failover(rpcs[1..].to_vec(), bitcoind_backends)
+                    .unwrap_or_else(|e| {
+                        log::error!("Cannot set the bitcoind backends. {e}");
+                        std::process::exit(1);
+                    }),
+            )
+        }
+        None => {
+            if !backends.is_empty() {
+                log::warn!("Additional broadcast backends are only supported when running on top of bitcoind. Ignoring them");
+            }
+            Box::new(EsploraBroadcaster::new(
+                &conf.esplora_url,
+                bitcoind_reachable.clone(),
+                tip.height,
+            ))
+        }
+    };
+    let mut responder = Responder::new(
+        &last_n_blocks[0..IRREVOCABLY_RESOLVED as usize],
         tip.height,
         carrier,
         gatekeeper.clone(),
         dbm.clone(),
-    ));
+    );
+    if let Some(wallet) = wallet {
+        responder = responder.with_wallet(wallet);
+    }
+    let responder = Arc::new(responder);
     let watcher = Arc::new(Watcher::new(
         gatekeeper.clone(),
         responder.clone(),
-        &last_n_blocks[0..6],
+        &last_n_blocks[0..locator_cache_depth as usize],
         tip.height,
+        conf.min_to_self_delay,
         tower_sk,
         TowerId(tower_pk),
         dbm.clone(),
@@ -298,6 +811,12 @@ async fn main() {
         log::info!("Fresh bootstrap");
     } else {
         log::info!("Bootstrapping from backed up data");
+
+        // Blocks between the last known block and the tip (if it has been forced forward) won't be connected by the chain monitor,
+        // so check the ones that could be pulled from the backend for breaches that happened while the tower was offline.
+        if missed_blocks > 0 {
+            watcher.rescan(&last_n_blocks[0..missed_blocks]);
+        }
     }
 
     let (shutdown_trigger, shutdown_signal_rpc_api) = triggered::trigger();
@@ -305,21 +824,68 @@ async fn main() {
     let shutdown_signal_http = shutdown_signal_rpc_api.clone();
     let shutdown_signal_cm = shutdown_signal_rpc_api.clone();
     let shutdown_signal_tor = shutdown_signal_rpc_api.clone();
+    let shutdown_signal_mempool = shutdown_signal_rpc_api.clone();
+    let shutdown_signal_registrations = shutdown_signal_rpc_api.clone();
+
+    // Subscribe to bitcoind's ZMQ notifications, if set, so new blocks and mempool transactions are processed
+    // straightaway. Polling is kept as a fallback.
+    let (block_notifier, mut mempool_notifier) = if conf.chain_backend == "bitcoind" {
+        let block_notifier = config::parse_zmq_endpoint(&conf.btc_zmq_hashblock).map(|address| {
+            let notifier = Arc::new(Notify::new());
+            let n = notifier.clone();
+            let subscriber = ZmqSubscriber::new(
+                &address,
+                "hashblock",
+                conf.btc_zmq_heartbeat,
+                shutdown_signal_rpc_api.clone(),
+            );
+            task::spawn(subscriber.run(move |_| n.notify_one()));
+            notifier
+        });
+        let mempool_notifier = config::parse_zmq_endpoint(&conf.btc_zmq_rawtx).map(|address| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let subscriber = ZmqSubscriber::new(
+                &address,
+                "rawtx",
+                conf.btc_zmq_heartbeat,
+                shutdown_signal_rpc_api.clone(),
+            );
+            task::spawn(
+                subscriber.run(move |raw_tx| match consensus::deserialize(&raw_tx) {
+                    Ok(tx) => sender.send(tx).unwrap_or(()),
+                    Err(e) => log::debug!("Cannot deserialize ZMQ transaction: {e}"),
+                }),
+            );
+            receiver
+        });
+        (block_notifier, mempool_notifier)
+    } else {
+        if !conf.btc_zmq_hashblock.is_empty() || !conf.btc_zmq_rawtx.is_empty() {
+            log::warn!("ZMQ notifications are only supported when running on top of bitcoind. Ignoring them");
+        }
+        (None, None)
+    };
+
+    let sweeping_gatekeeper = gatekeeper.clone();
 
     // The ordering here actually matters. Listeners are called by order, and we want the gatekeeper to be called
     // last, so both the Watcher and the Responder can query the necessary data from it during data deletion.
     let listener = &(watcher.clone(), &(responder, gatekeeper));
+    let mempool_watcher = watcher.clone();
     let cache = &mut UnboundedCache::new();
     let spv_client = SpvClient::new(tip, poller, cache, listener);
     let mut chain_monitor = ChainMonitor::new(
         spv_client,
         tip,
-        dbm,
+        dbm.clone(),
         conf.polling_delta,
         shutdown_signal_cm,
         bitcoind_reachable.clone(),
     )
     .await;
+    if let Some(block_notifier) = block_notifier {
+        chain_monitor = chain_monitor.with_block_notifier(block_notifier);
+    }
 
     // Get all the components up to date if there's a backlog of blocks
     chain_monitor.poll_best_tip().await;
@@ -353,12 +919,45 @@ async fn main() {
         None
     };
 
-    let internal_api = Arc::new(InternalAPI::new(
+    let mut internal_api = InternalAPI::new(
         watcher,
         addresses,
         bitcoind_reachable.clone(),
         shutdown_trigger,
-    ));
+    );
+    if let Some((_, bitcoind_backends)) = bitcoind {
+        internal_api = internal_api.with_bitcoind_backends(bitcoind_backends);
+    }
+
+    // Gate registrations behind proof of work or blinded tokens, if set. Secrets are derived from the tower key so
+    // challenges and tokens outlive restarts
+    let registration_gate: Option<Arc<dyn RegistrationGate>> = match conf.registration_gate.as_str()
+    {
+        "pow" => Some(Arc::new(ProofOfWorkGate::new(
+            derive_secret(&tower_sk, "registration_challenges"),
+            conf.pow_difficulty,
+            conf.pow_challenge_expiry as u64,
+        ))),
+        "tokens" => Some(Arc::new(TokenGate::new(
+            SecretKey::from_slice(&derive_secret(&tower_sk, "registration_tokens")).unwrap(),
+            dbm.clone(),
+        ))),
+        _ => None,
+    };
+    if let Some(registration_gate) = registration_gate {
+        log::info!("Registrations are gated using {}", conf.registration_gate);
+        internal_api = internal_api.with_registration_gate(registration_gate);
+    }
+
+    // Online backups copy the SQLite database file, so they are not available for other backends
+    if sqlite_dbm.is_some() {
+        let mut backup = Backup::new(path.clone(), conf.btc_network.clone(), TowerId(tower_pk));
+        if conf.encrypt_database {
+            backup = backup.with_passphrase(passphrase.clone().unwrap());
+        }
+        internal_api = internal_api.with_backup(Arc::new(backup));
+    }
+    let internal_api = Arc::new(internal_api);
     let internal_api_cloned = internal_api.clone();
 
     let rpc_api_addr = format!("{}:{}", conf.rpc_bind, conf.rpc_port)
@@ -399,9 +998,12 @@ async fn main() {
     });
 
     let (http_service_ready, ready_signal_http) = triggered::trigger();
+    let ip_rate_limiter = (conf.ip_rate_limit > 0)
+        .then(|| Arc::new(RateLimiter::new(conf.ip_rate_limit, conf.ip_rate_burst)));
     let http_api_task = task::spawn(http::serve(
         http_api_addr,
         internal_api_addr,
+        ip_rate_limiter,
         http_service_ready,
         shutdown_signal_http,
     ));
@@ -426,6 +1028,58 @@ async fn main() {
         ready_signal_tor.await
     }
 
+    // Check the mempool for breaches so they can be reacted to before the dispute gets confirmed.
+    // Transactions notified via ZMQ are checked as soon as they are received, on top of the periodic checks.
+    let mut polling = interval(time::Duration::from_secs(conf.polling_delta as u64));
+    polling.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mempool_task = task::spawn(async move {
+        loop {
+            let w = mempool_watcher.clone();
+            let new_txs = async {
+                match mempool_notifier.as_mut() {
+                    Some(receiver) => receiver.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = shutdown_signal_mempool.clone() => break,
+                _ = polling.tick() => {
+                    task::spawn_blocking(move || w.check_mempool())
+                        .await
+                        .unwrap();
+                }
+                Some(tx) = new_txs => {
+                    // Batch whatever has been notified in the meantime
+                    let mut txs = vec![tx];
+                    while let Some(Ok(tx)) = mempool_notifier.as_mut().map(|receiver| receiver.try_recv()) {
+                        txs.push(tx);
+                    }
+                    task::spawn_blocking(move || w.check_mempool_transactions(txs))
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    });
+
+    // Resolve the pending registrations whose invoice has expired. This queries the payment backend, so it is kept
+    // apart from the block processing.
+    let mut sweeping = interval(time::Duration::from_secs(EXPIRED_REGISTRATIONS_SWEEP_DELTA));
+    sweeping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let registrations_task = task::spawn(async move {
+        loop {
+            let g = sweeping_gatekeeper.clone();
+            tokio::select! {
+                _ = shutdown_signal_registrations.clone() => break,
+                _ = sweeping.tick() => {
+                    task::spawn_blocking(move || g.resolve_expired_registrations())
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    });
+
     log::info!("Tower ready");
     chain_monitor.monitor_chain().await;
 
@@ -433,6 +1087,8 @@ async fn main() {
     http_api_task.await.unwrap();
     private_api_task.await.unwrap();
     public_api_task.await.unwrap();
+    mempool_task.await.unwrap();
+    registrations_task.await.unwrap();
     if let Some(tor_task) = tor_task {
         tor_task.await.unwrap();
     }
@@ -0,0 +1,535 @@
+//! Logic related to paid subscriptions, that is, requesting payments for user registrations and verifying them.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use bitcoin::base64;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+
+use teos_common::cryptography::get_random_bytes;
+
+/// Timeout of the requests sent to the Lightning node, in seconds.
+const REQUEST_TIMEOUT: u64 = 30;
+
+/// Gets the current unix time, in seconds.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// A BOLT11 invoice issued by the tower to get paid for a subscription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invoice {
+    /// The BOLT11-encoded invoice.
+    pub bolt11: String,
+    /// The hash of the payment preimage. Used to check whether the invoice has been paid.
+    pub payment_hash: [u8; 32],
+    /// The requested amount, in millisatoshis.
+    pub amount_msat: u64,
+    /// Unix time (in seconds) after which the invoice cannot be paid anymore.
+    pub expires_at: u64,
+}
+
+impl Invoice {
+    /// Checks whether the invoice has expired at a given unix time.
+    pub fn has_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Errors raised when the Lightning node cannot handle a payment related request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaymentError {
+    /// The node could not be reached.
+    Unreachable(String),
+    /// The node returned an error, or a response that could not be understood.
+    Rejected(String),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentError::Unreachable(e) => write!(f, "Lightning node unreachable: {e}"),
+            PaymentError::Rejected(e) => write!(f, "Lightning node rejected the request: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+/// Interface to the Lightning node in charge of getting the tower paid.
+///
+/// Users are handed invoices created by the node, and their subscriptions are only activated once the node reports them
+/// as settled.
+pub trait PaymentVerifier: Send + Sync + fmt::Debug {
+    /// Requests an invoice for the given amount to the node. The invoice will be payable for `expiry` seconds.
+    fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        expiry: u32,
+    ) -> Result<Invoice, PaymentError>;
+
+    /// Checks whether the invoice with the given payment hash has been settled.
+    fn is_settled(&self, payment_hash: &[u8; 32]) -> Result<bool, PaymentError>;
+}
+
+/// Parses a hex encoded payment hash.
+fn parse_payment_hash(payment_hash: &str) -> Result<[u8; 32], PaymentError> {
+    <[u8; 32]>::from_hex(payment_hash)
+        .map_err(|_| PaymentError::Rejected(format!("Wrong payment hash: {payment_hash}")))
+}
+
+/// A [PaymentVerifier] on top of Core Lightning's JSON-RPC interface, reachable through a unix socket.
+#[derive(Debug)]
+pub struct ClnVerifier {
+    /// Path to the `lightning-rpc` socket.
+    rpc_path: PathBuf,
+}
+
+impl ClnVerifier {
+    /// Creates a new [ClnVerifier] instance.
+    pub fn new(rpc_path: PathBuf) -> Self {
+        ClnVerifier { rpc_path }
+    }
+
+    /// Calls a given RPC method with the given params, returning the result.
+    fn call(&self, method: &str, params: Value) -> Result<Value, PaymentError> {
+        let unreachable = |e: std::io::Error| PaymentError::Unreachable(e.to_string());
+        let mut stream = UnixStream::connect(&self.rpc_path).map_err(unreachable)?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(REQUEST_TIMEOUT)))
+            .map_err(unreachable)?;
+
+        let request = json!({"jsonrpc": "2.0", "id": 0, "method": method, "params": params});
+        stream
+            .write_all(request.to_string().as_bytes())
+            .map_err(unreachable)?;
+
+        let mut response = match serde_json::Deserializer::from_reader(&stream)
+            .into_iter::<Value>()
+            .next()
+        {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => return Err(PaymentError::Unreachable(e.to_string())),
+            None => {
+                return Err(PaymentError::Unreachable(
+                    "Connection closed by the node".to_owned(),
+                ))
+            }
+        };
+
+        if let Some(error) = response.get("error") {
+            return Err(PaymentError::Rejected(
+                error["message"]
+                    .as_str()
+                    .map_or_else(|| error.to_string(), |message| message.to_owned()),
+            ));
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(PaymentError::Rejected(format!(
+                "Unexpected response: {response}"
+            ))),
+        }
+    }
+}
+
+impl PaymentVerifier for ClnVerifier {
+    fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        expiry: u32,
+    ) -> Result<Invoice, PaymentError> {
+        // Labels must be unique within the node
+        let label = format!("teos-{}", get_random_bytes(16).to_hex());
+        let result = self.call(
+            "invoice",
+            json!({
+                "amount_msat": amount_msat,
+                "label": label,
+                "description": description,
+                "expiry": expiry
+            }),
+        )?;
+
+        match (
+            result["bolt11"].as_str(),
+            result["payment_hash"].as_str(),
+            result["expires_at"].as_u64(),
+        ) {
+            (Some(bolt11), Some(payment_hash), Some(expires_at)) => Ok(Invoice {
+                bolt11: bolt11.to_owned(),
+                payment_hash: parse_payment_hash(payment_hash)?,
+                amount_msat,
+                expires_at,
+            }),
+            _ => Err(PaymentError::Rejected(format!(
+                "Unexpected invoice: {result}"
+            ))),
+        }
+    }
+
+    fn is_settled(&self, payment_hash: &[u8; 32]) -> Result<bool, PaymentError> {
+        let result = self.call(
+            "listinvoices",
+            json!({ "payment_hash": payment_hash.to_hex() }),
+        )?;
+
+        match result["invoices"].get(0) {
+            Some(invoice) => Ok(invoice["status"] == "paid"),
+            None => Err(PaymentError::Rejected(format!(
+                "Unknown invoice: {}",
+                payment_hash.to_hex()
+            ))),
+        }
+    }
+}
+
+/// A [PaymentVerifier] on top of LND's REST interface.
+///
+/// Requests are performed using a blocking client, which cannot be used from within an async context, so they are sent from
+/// a dedicated thread.
+pub struct LndVerifier {
+    /// The base url of the REST interface.
+    url: String,
+    /// Hex encoded macaroon used to authenticate the requests.
+    macaroon: String,
+    /// PEM encoded certificate of the node, if self-signed.
+    tls_cert: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for LndVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LndVerifier")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl LndVerifier {
+    /// Creates a new [LndVerifier] instance, loading the macaroon (and the TLS certificate, if any) from disk.
+    pub fn new(
+        url: &str,
+        macaroon_path: PathBuf,
+        tls_cert_path: Option<PathBuf>,
+    ) -> Result<Self, std::io::Error> {
+        Ok(LndVerifier {
+            url: url.trim_end_matches('/').to_owned(),
+            macaroon: fs::read(macaroon_path)?.to_hex(),
+            tls_cert: tls_cert_path.map(fs::read).transpose()?,
+        })
+    }
+
+    /// Sends a request to the REST interface from a dedicated thread, returning the response body.
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, PaymentError> {
+        let url = format!("{}/{path}", self.url);
+        let macaroon = self.macaroon.clone();
+        let tls_cert = self.tls_cert.clone();
+
+        thread::spawn(move || {
+            let mut builder =
+                reqwest::blocking::Client::builder().timeout(Duration::from_secs(REQUEST_TIMEOUT));
+            if let Some(cert) = tls_cert {
+                let cert = reqwest::Certificate::from_pem(&cert)
+                    .map_err(|e| PaymentError::Unreachable(e.to_string()))?;
+                builder = builder.add_root_certificate(cert);
+            }
+            let client = builder
+                .build()
+                .map_err(|e| PaymentError::Unreachable(e.to_string()))?;
+
+            let mut request = client
+                .request(method, &url)
+                .header("Grpc-Metadata-macaroon", macaroon);
+            if let Some(body) = body {
+                request = request
+                    .header("Content-Type", "application/json")
+                    .body(body.to_string());
+            }
+            let response = request
+                .send()
+                .map_err(|e| PaymentError::Unreachable(e.to_string()))?;
+
+            let status = response.status();
+            let body: Value = response
+                .text()
+                .map_err(|e| PaymentError::Unreachable(e.to_string()))
+                .and_then(|body| {
+                    serde_json::from_str(&body).map_err(|e| PaymentError::Rejected(e.to_string()))
+                })?;
+            if status.is_success() {
+                Ok(body)
+            } else {
+                Err(PaymentError::Rejected(
+                    body["message"]
+                        .as_str()
+                        .map_or_else(|| status.to_string(), |message| message.to_owned()),
+                ))
+            }
+        })
+        .join()
+        .unwrap_or_else(|_| {
+            Err(PaymentError::Unreachable(
+                "Request thread panicked".to_owned(),
+            ))
+        })
+    }
+}
+
+impl PaymentVerifier for LndVerifier {
+    fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        expiry: u32,
+    ) -> Result<Invoice, PaymentError> {
+        // 64-bit integers are encoded as strings by LND's REST interface
+        let result = self.request(
+            Method::POST,
+            "v1/invoices",
+            Some(json!({
+                "value_msat": amount_msat.to_string(),
+                "memo": description,
+                "expiry": expiry.to_string()
+            })),
+        )?;
+
+        let payment_hash = result["r_hash"]
+            .as_str()
+            .and_then(|r_hash| base64::decode(r_hash).ok())
+            .and_then(|r_hash| <[u8; 32]>::try_from(r_hash.as_slice()).ok());
+        match (result["payment_request"].as_str(), payment_hash) {
+            (Some(bolt11), Some(payment_hash)) => Ok(Invoice {
+                bolt11: bolt11.to_owned(),
+                payment_hash,
+                amount_msat,
+                expires_at: unix_time() + expiry as u64,
+            }),
+            _ => Err(PaymentError::Rejected(format!(
+                "Unexpected invoice: {result}"
+            ))),
+        }
+    }
+
+    fn is_settled(&self, payment_hash: &[u8; 32]) -> Result<bool, PaymentError> {
+        let result = self.request(
+            Method::GET,
+            &format!("v1/invoice/{}", payment_hash.to_hex()),
+            None,
+        )?;
+        Ok(result["state"] == "SETTLED")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::BufReader;
+    use std::os::unix::net::UnixListener;
+
+    use tempdir::TempDir;
+    use warp::Filter;
+
+    /// Spawns a fake `lightning-rpc` socket that answers every request with the given responses, in order.
+    fn spawn_cln_rpc(dir: &TempDir, responses: Vec<Value>) -> PathBuf {
+        let rpc_path = dir.path().join("lightning-rpc");
+        let listener = UnixListener::bind(&rpc_path).unwrap();
+
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                // Requests are not newline terminated, so read them as JSON
+                let request: Value = serde_json::Deserializer::from_reader(BufReader::new(&stream))
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .unwrap();
+                let mut response = response;
+                response["id"] = request["id"].clone();
+                stream
+                    .write_all(format!("{response}\n\n").as_bytes())
+                    .unwrap();
+            }
+        });
+
+        rpc_path
+    }
+
+    #[test]
+    fn test_invoice_has_expired() {
+        let invoice = Invoice {
+            bolt11: "lnbcrt1".to_owned(),
+            payment_hash: [0; 32],
+            amount_msat: 1000,
+            expires_at: 100,
+        };
+
+        assert!(!invoice.has_expired(99));
+        assert!(invoice.has_expired(100));
+        assert!(invoice.has_expired(101));
+    }
+
+    #[test]
+    fn test_cln_create_invoice() {
+        let dir = TempDir::new("cln").unwrap();
+        let payment_hash = [1; 32];
+        let rpc_path = spawn_cln_rpc(
+            &dir,
+            vec![json!({
+                "jsonrpc": "2.0",
+                "result": {"bolt11": "lnbcrt1", "payment_hash": payment_hash.to_hex(), "expires_at": 42}
+            })],
+        );
+
+        let invoice = ClnVerifier::new(rpc_path)
+            .create_invoice(1000, "description", 3600)
+            .unwrap();
+        assert_eq!(
+            invoice,
+            Invoice {
+                bolt11: "lnbcrt1".to_owned(),
+                payment_hash,
+                amount_msat: 1000,
+                expires_at: 42
+            }
+        );
+    }
+
+    #[test]
+    fn test_cln_is_settled() {
+        let dir = TempDir::new("cln").unwrap();
+        let rpc_path = spawn_cln_rpc(
+            &dir,
+            vec![
+                json!({"jsonrpc": "2.0", "result": {"invoices": [{"status": "unpaid"}]}}),
+                json!({"jsonrpc": "2.0", "result": {"invoices": [{"status": "paid"}]}}),
+                json!({"jsonrpc": "2.0", "result": {"invoices": []}}),
+            ],
+        );
+
+        let verifier = ClnVerifier::new(rpc_path);
+        assert!(!verifier.is_settled(&[0; 32]).unwrap());
+        assert!(verifier.is_settled(&[0; 32]).unwrap());
+        assert!(matches!(
+            verifier.is_settled(&[0; 32]),
+            Err(PaymentError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn test_cln_rpc_error() {
+        let dir = TempDir::new("cln").unwrap();
+        let rpc_path = spawn_cln_rpc(
+            &dir,
+            vec![json!({"jsonrpc": "2.0", "error": {"code": -1, "message": "Duplicate label"}})],
+        );
+
+        assert_eq!(
+            ClnVerifier::new(rpc_path).create_invoice(1000, "description", 3600),
+            Err(PaymentError::Rejected("Duplicate label".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_cln_unreachable() {
+        let dir = TempDir::new("cln").unwrap();
+        let verifier = ClnVerifier::new(dir.path().join("lightning-rpc"));
+
+        assert!(matches!(
+            verifier.create_invoice(1000, "description", 3600),
+            Err(PaymentError::Unreachable(_))
+        ));
+        assert!(matches!(
+            verifier.is_settled(&[0; 32]),
+            Err(PaymentError::Unreachable(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lnd() {
+        let dir = TempDir::new("lnd").unwrap();
+        let macaroon_path = dir.path().join("invoice.macaroon");
+        fs::write(&macaroon_path, [1, 2, 3]).unwrap();
+        let payment_hash = [1; 32];
+
+        // Spawn a fake REST interface that only accepts requests carrying the right macaroon
+        let add_invoice = warp::post()
+            .and(warp::path!("v1" / "invoices"))
+            .and(warp::header::exact("Grpc-Metadata-macaroon", "010203"))
+            .and(warp::body::json())
+            .map(move |body: Value| {
+                assert_eq!(body["value_msat"], "1000");
+                warp::reply::json(&json!({
+                    "r_hash": base64::encode(&payment_hash),
+                    "payment_request": "lnbcrt1"
+                }))
+            });
+        let lookup_invoice = warp::get()
+            .and(warp::path!("v1" / "invoice" / String))
+            .and(warp::header::exact("Grpc-Metadata-macaroon", "010203"))
+            .map(move |hash: String| {
+                let state = if hash == payment_hash.to_hex() {
+                    "SETTLED"
+                } else {
+                    "OPEN"
+                };
+                warp::reply::json(&json!({ "state": state }))
+            });
+        let (addr, server) =
+            warp::serve(add_invoice.or(lookup_invoice)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let verifier = LndVerifier::new(&format!("http://{addr}/"), macaroon_path, None).unwrap();
+        let now = unix_time();
+        let invoice = verifier.create_invoice(1000, "description", 3600).unwrap();
+        assert_eq!(invoice.bolt11, "lnbcrt1");
+        assert_eq!(invoice.payment_hash, payment_hash);
+        assert_eq!(invoice.amount_msat, 1000);
+        assert!(invoice.expires_at >= now + 3600);
+
+        assert!(verifier.is_settled(&payment_hash).unwrap());
+        assert!(!verifier.is_settled(&[0; 32]).unwrap());
+    }
+
+    #[test]
+    fn test_lnd_unreachable() {
+        let dir = TempDir::new("lnd").unwrap();
+        let macaroon_path = dir.path().join("invoice.macaroon");
+        fs::write(&macaroon_path, [1, 2, 3]).unwrap();
+
+        // Missing files cannot be loaded
+        assert!(
+            LndVerifier::new("https://localhost:8080", dir.path().join("missing"), None).is_err()
+        );
+
+        let verifier = LndVerifier::new("http://localhost:1", macaroon_path, None).unwrap();
+        assert_eq!(verifier.macaroon, "010203");
+        assert!(matches!(
+            verifier.create_invoice(1000, "description", 3600),
+            Err(PaymentError::Unreachable(_))
+        ));
+        assert!(matches!(
+            verifier.is_settled(&[0; 32]),
+            Err(PaymentError::Unreachable(_))
+        ));
+    }
+}
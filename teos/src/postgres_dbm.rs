@@ -0,0 +1,1397 @@
+//! Logic related to the `PostgreSQL` storage backend of the tower.
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use postgres::error::SqlState;
+use postgres::types::ToSql;
+use postgres::{Client, Error as PgError, NoTls, Row};
+
+use bitcoin::consensus;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, Txid};
+
+use teos_common::appointment::{compute_appointment_slots, Appointment, Locator};
+use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
+use teos_common::dbm::{
+    pending_migrations, Error, Migration, MigrationError, SCHEMA_VERSION_TABLE,
+};
+use teos_common::UserId;
+
+use crate::dbm::{decode_tower_key, encode_tower_key, Storage};
+use crate::encryption::TowerKey;
+use crate::extended_appointment::{ExtendedAppointment, UUID};
+use crate::gatekeeper::{AuditLogEntry, PendingRegistration, SubscriptionOperation, UserInfo};
+use crate::payments::Invoice;
+use crate::responder::{ConfirmationStatus, TransactionTracker};
+use crate::tx_index::BlockSummary;
+use crate::wallet::FeeBump;
+use crate::watcher::Breach;
+
+/// The tables of the first version of the database schema. Changes to the schema go into [MIGRATIONS] instead.
+const TABLES: [&str; 12] = [
+    "CREATE TABLE IF NOT EXISTS users (
+    user_id BYTEA PRIMARY KEY,
+    available_slots BIGINT NOT NULL,
+    subscription_start BIGINT NOT NULL,
+    subscription_expiry BIGINT NOT NULL,
+    tier TEXT NOT NULL DEFAULT ''
+)",
+    "CREATE TABLE IF NOT EXISTS appointments (
+    UUID BYTEA PRIMARY KEY,
+    locator BYTEA NOT NULL,
+    encrypted_blob BYTEA NOT NULL,
+    to_self_delay BIGINT NOT NULL,
+    user_signature TEXT NOT NULL,
+    start_block BIGINT NOT NULL,
+    user_id BYTEA NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(user_id)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS trackers (
+    UUID BYTEA PRIMARY KEY,
+    dispute_tx BYTEA NOT NULL,
+    penalty_tx BYTEA NOT NULL,
+    height BIGINT NOT NULL,
+    confirmed BOOLEAN NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES appointments(UUID)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS last_known_block (
+    id INT PRIMARY KEY,
+    block_hash BYTEA NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS keys (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS wallet_keys (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS fee_bumps (
+    id BIGSERIAL PRIMARY KEY,
+    UUID BYTEA NOT NULL,
+    child_tx BYTEA NOT NULL,
+    fee BIGINT NOT NULL,
+    fee_rate BIGINT NOT NULL,
+    height BIGINT NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES trackers(UUID)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS block_summaries (
+    height BIGINT PRIMARY KEY,
+    header BYTEA NOT NULL,
+    txids BYTEA NOT NULL,
+    txdata BYTEA
+)",
+    "CREATE TABLE IF NOT EXISTS pending_registrations (
+    user_id BYTEA PRIMARY KEY,
+    invoice TEXT NOT NULL,
+    payment_hash BYTEA NOT NULL,
+    amount_msat BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    tier TEXT NOT NULL DEFAULT '',
+    operation BIGINT NOT NULL DEFAULT 0
+)",
+    "CREATE TABLE IF NOT EXISTS banned_users (
+    user_id BYTEA PRIMARY KEY
+)",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    timestamp BIGINT NOT NULL,
+    user_id BYTEA NOT NULL,
+    action TEXT NOT NULL,
+    details TEXT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS redeemed_tokens (
+    token BYTEA PRIMARY KEY
+)",
+];
+
+/// The migrations of the database schema, from oldest to newest.
+const MIGRATIONS: [Migration; 6] = [
+    Migration {
+        description: "Create the initial schema",
+        statements: &TABLES,
+    },
+    Migration {
+        description: "Allow encrypting the tower keys",
+        statements: &["ALTER TABLE keys ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT FALSE"],
+    },
+    Migration {
+        description: "Persist the breaches queued for the Responder",
+        statements: &["CREATE TABLE IF NOT EXISTS queued_breaches (
+    UUID BYTEA PRIMARY KEY,
+    dispute_tx BYTEA NOT NULL,
+    penalty_tx BYTEA NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES appointments(UUID)
+        ON DELETE CASCADE
+)"],
+    },
+    Migration {
+        description: "Persist the appointments triggered from the mempool",
+        statements: &["CREATE TABLE IF NOT EXISTS mempool_triggers (
+    UUID BYTEA PRIMARY KEY,
+    dispute_txid BYTEA NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES appointments(UUID)
+        ON DELETE CASCADE
+)"],
+    },
+    Migration {
+        description: "Keep every invoice issued to a user",
+        statements: &[
+            "ALTER TABLE pending_registrations DROP CONSTRAINT pending_registrations_pkey",
+            "ALTER TABLE pending_registrations ADD PRIMARY KEY (payment_hash)",
+            "CREATE INDEX IF NOT EXISTS pending_registrations_user_id ON pending_registrations (user_id)",
+        ],
+    },
+    Migration {
+        description: "Allow encrypting the wallet keys",
+        statements: &["ALTER TABLE wallet_keys ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT FALSE"],
+    },
+];
+
+/// Maximum number of entries removed by a single query when removing data in batch.
+const MAX_BATCH_SIZE: usize = 10_000;
+
+/// A set of queries to be run by the database thread. Returns whether the connection failed while running them.
+type Job = Box<dyn FnOnce(&mut Client) -> bool + Send>;
+
+/// The parameters of a query. They are owned so they can be sent to the database thread.
+type Params = Vec<Box<dyn ToSql + Send + Sync>>;
+
+/// Builds the [Params] of a query out of a list of values.
+macro_rules! params {
+    ($($param:expr),* $(,)?) => {
+        vec![$(Box::new($param) as Box<dyn ToSql + Send + Sync>),*] as Params
+    };
+}
+
+/// Maps the errors returned by `PostgreSQL` to the ones returned by every storage backend.
+fn map_error(e: PgError) -> Error {
+    match e.code() {
+        Some(code) if *code == SqlState::UNIQUE_VIOLATION => Error::AlreadyExists,
+        Some(code) if *code == SqlState::FOREIGN_KEY_VIOLATION => Error::MissingForeignKey,
+        _ => Error::Backend(e.to_string()),
+    }
+}
+
+/// Maps the errors returned by `PostgreSQL` while opening the database to [MigrationError]s.
+fn backend_error(e: PgError) -> MigrationError {
+    MigrationError::Backend(e.to_string())
+}
+
+/// Opens a connection to the database at the given url.
+///
+/// If a schema is given, the connection uses it instead of the default one, creating it first if requested.
+fn open(url: &str, schema: Option<&str>, create_schema: bool) -> Result<Client, PgError> {
+    let mut client = Client::connect(url, NoTls)?;
+    if let Some(schema) = schema {
+        if create_schema {
+            client.batch_execute(&format!("CREATE SCHEMA {schema}"))?;
+        }
+        client.batch_execute(&format!("SET search_path TO {schema}"))?;
+    }
+    Ok(client)
+}
+
+/// Applies the pending migrations to the database, in a single transaction.
+///
+/// Fails if the database has been migrated past the given migrations, or if any of the applied ones has been modified.
+fn migrate(client: &mut Client, migrations: &[Migration]) -> Result<(), MigrationError> {
+    let mut tx = client.transaction().map_err(backend_error)?;
+    tx.batch_execute(SCHEMA_VERSION_TABLE)
+        .map_err(backend_error)?;
+
+    let applied = tx
+        .query(
+            "SELECT version, checksum FROM schema_version ORDER BY version",
+            &[],
+        )
+        .map_err(backend_error)?
+        .iter()
+        .map(|row| (row.get::<_, i32>(0) as u32, row.get(1)))
+        .collect::<Vec<(u32, String)>>();
+
+    for (version, migration) in pending_migrations(&applied, migrations)? {
+        tx.batch_execute(&migration.statements.join(";"))
+            .map_err(backend_error)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description, checksum) VALUES ($1, $2, $3)",
+            &[
+                &(version as i32),
+                &migration.description,
+                &migration.checksum(),
+            ],
+        )
+        .map_err(backend_error)?;
+    }
+
+    tx.commit().map_err(backend_error)
+}
+
+/// Builds an [ExtendedAppointment] out of a row holding `locator, encrypted_blob, to_self_delay, user_signature,
+/// start_block, user_id`, starting at the given column.
+fn appointment_from_row(row: &Row, first: usize) -> ExtendedAppointment {
+    let raw_locator: Vec<u8> = row.get(first);
+    let raw_userid: Vec<u8> = row.get(first + 5);
+
+    let appointment = Appointment::new(
+        Locator::from_slice(&raw_locator).unwrap(),
+        row.get(first + 1),
+        row.get::<_, i64>(first + 2) as u32,
+    );
+    ExtendedAppointment::new(
+        appointment,
+        UserId::from_slice(&raw_userid).unwrap(),
+        row.get(first + 3),
+        row.get::<_, i64>(first + 4) as u32,
+    )
+}
+
+/// Builds the entry of a user appointment out of a row holding `UUID, encrypted_blob`, alongside the slots it takes
+/// given the blob size a slot covers.
+fn user_appointment_from_row(row: &Row, slot_size: usize) -> (UUID, u32) {
+    let raw_uuid: Vec<u8> = row.get(0);
+    let e_blob: Vec<u8> = row.get(1);
+    (
+        UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+        compute_appointment_slots(e_blob.len(), slot_size),
+    )
+}
+
+/// Builds a [TransactionTracker] out of a row holding `dispute_tx, penalty_tx, height, confirmed, user_id`, starting
+/// at the given column.
+fn tracker_from_row(row: &Row, first: usize) -> TransactionTracker {
+    let raw_dispute_tx: Vec<u8> = row.get(first);
+    let raw_penalty_tx: Vec<u8> = row.get(first + 1);
+    let height = row.get::<_, i64>(first + 2) as u32;
+    let confirmed: bool = row.get(first + 3);
+    let raw_userid: Vec<u8> = row.get(first + 4);
+
+    TransactionTracker {
+        dispute_tx: consensus::deserialize(&raw_dispute_tx).unwrap(),
+        penalty_tx: consensus::deserialize(&raw_penalty_tx).unwrap(),
+        status: ConfirmationStatus::from_db_data(height, confirmed),
+        user_id: UserId::from_slice(&raw_userid).unwrap(),
+    }
+}
+
+/// Component in charge of interacting with an underlying `PostgreSQL` database.
+///
+/// The blocking [Client] cannot be used from within an async runtime, so the connection is owned by a thread of its
+/// own and queries are sent to it as jobs.
+#[derive(Debug)]
+pub struct PostgresDBM {
+    /// Channel used to send jobs to the database thread. Closing it stops the thread.
+    jobs: Option<Sender<Job>>,
+    /// The thread owning the database connection.
+    worker: Option<JoinHandle<()>>,
+    /// Maximum number of entries removed by a single query when removing data in batch.
+    batch_size: usize,
+}
+
+impl PostgresDBM {
+    /// Creates a new [PostgresDBM] instance, connecting to the database at the given url.
+    pub fn new(url: &str) -> Result<Self, MigrationError> {
+        PostgresDBM::connect(url.to_owned(), None)
+    }
+
+    /// Connects to the database at the given url and migrates its schema to the latest version.
+    ///
+    /// If a schema is given, it is created and used instead of the default one. The schema is dropped, alongside all
+    /// its data, once the connection is closed.
+    fn connect(url: String, schema: Option<String>) -> Result<Self, MigrationError> {
+        let (jobs, pending_jobs) = mpsc::channel::<Job>();
+        let (ready, is_ready) = mpsc::sync_channel(1);
+
+        let worker = thread::spawn(move || {
+            let setup = open(&url, schema.as_deref(), true)
+                .map_err(backend_error)
+                .and_then(|mut client| {
+                    migrate(&mut client, &MIGRATIONS)?;
+                    Ok(client)
+                });
+
+            let mut client = match setup {
+                Ok(client) => {
+                    ready.send(Ok(())).unwrap();
+                    client
+                }
+                Err(e) => {
+                    ready.send(Err(e)).unwrap();
+                    return;
+                }
+            };
+
+            let mut connection_failed = false;
+            for job in pending_jobs {
+                // The connection may have been dropped (e.g. on a server restart). Jobs run on a broken connection
+                // fail, so try to reconnect first, leaving the job to report the error if that fails too
+                if connection_failed || client.is_closed() {
+                    match open(&url, schema.as_deref(), false) {
+                        Ok(new_client) => {
+                            log::info!("Reconnected to the database");
+                            client = new_client;
+                        }
+                        Err(e) => log::error!("Couldn't reconnect to the database. Error: {e:?}"),
+                    }
+                }
+                connection_failed = job(&mut client);
+            }
+
+            if let Some(schema) = schema {
+                if let Err(e) = client.batch_execute(&format!("DROP SCHEMA {schema} CASCADE")) {
+                    log::error!("Couldn't drop schema {schema}. Error: {e:?}");
+                }
+            }
+        });
+
+        is_ready.recv().unwrap()?;
+
+        Ok(Self {
+            jobs: Some(jobs),
+            worker: Some(worker),
+            batch_size: MAX_BATCH_SIZE,
+        })
+    }
+
+    /// Runs a job on the database thread and waits for its result.
+    ///
+    /// Fatal errors, and the ones not coming from the server itself (e.g. I/O errors), are taken as a sign of a broken
+    /// connection, so the database thread reconnects before running the next job.
+    fn run<T, F>(&self, job: F) -> Result<T, PgError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Client) -> Result<T, PgError> + Send + 'static,
+    {
+        let (result, get_result) = mpsc::sync_channel(1);
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(Box::new(move |client: &mut Client| {
+                let job_result = job(client);
+                let connection_failed = match &job_result {
+                    Ok(_) => false,
+                    // Fatal errors are sent by the server right before closing the connection
+                    Err(e) => match e.as_db_error() {
+                        Some(e) => e.severity() == "FATAL" || e.severity() == "PANIC",
+                        None => true,
+                    },
+                };
+                result.send(job_result).unwrap();
+                connection_failed
+            }))
+            .expect("The database thread is not running");
+
+        get_result
+            .recv()
+            .expect("The database thread is not running")
+    }
+
+    /// Runs a query, returning the number of rows it modified.
+    fn execute(&self, query: &str, params: Params) -> Result<u64, PgError> {
+        let query = query.to_owned();
+        self.run(move |client| {
+            let params: Vec<&(dyn ToSql + Sync)> = params
+                .iter()
+                .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            client.execute(query.as_str(), &params)
+        })
+    }
+
+    /// Runs a query, returning the rows it selected.
+    fn query(&self, query: &str, params: Params) -> Result<Vec<Row>, Error> {
+        let query = query.to_owned();
+        self.run(move |client| {
+            let params: Vec<&(dyn ToSql + Sync)> = params
+                .iter()
+                .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            client.query(query.as_str(), &params)
+        })
+        .map_err(map_error)
+    }
+
+    /// Runs a query selecting, at most, a single row.
+    fn query_opt(&self, query: &str, params: Params) -> Result<Option<Row>, Error> {
+        let query = query.to_owned();
+        self.run(move |client| {
+            let params: Vec<&(dyn ToSql + Sync)> = params
+                .iter()
+                .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            client.query_opt(query.as_str(), &params)
+        })
+        .map_err(map_error)
+    }
+
+    /// Runs a query for a method that cannot report errors, returning no rows (after logging the error) if it fails.
+    fn query_or_log(&self, query: &str, params: Params) -> Vec<Row> {
+        self.query(query, params).unwrap_or_else(|e| {
+            log::error!("Couldn't load data from the database. Error: {e:?}");
+            Vec::new()
+        })
+    }
+
+    /// Same as [Self::query_or_log], for queries selecting, at most, a single row.
+    fn query_opt_or_log(&self, query: &str, params: Params) -> Option<Row> {
+        self.query_opt(query, params).unwrap_or_else(|e| {
+            log::error!("Couldn't load data from the database. Error: {e:?}");
+            None
+        })
+    }
+
+    /// Generic method to store data into the database.
+    fn store_data(&self, query: &str, params: Params) -> Result<(), Error> {
+        self.execute(query, params).map(|_| ()).map_err(map_error)
+    }
+
+    /// Generic method to update or remove data from the database. Fails with [Error::NotFound] if no row is modified.
+    fn modify_data(&self, query: &str, params: Params) -> Result<(), Error> {
+        match self.execute(query, params) {
+            Ok(0) => Err(Error::NotFound),
+            Ok(_) => Ok(()),
+            Err(e) => Err(map_error(e)),
+        }
+    }
+
+    /// Loads all the keys stored in a key table (either `keys` or `wallet_keys`), identified by their id.
+    fn load_all_keys(&self, table: &str) -> HashMap<u32, TowerKey> {
+        self.query_or_log(
+            &format!("SELECT id, key, encrypted FROM {table}"),
+            params![],
+        )
+        .iter()
+        .map(|row| {
+            (
+                row.get::<_, i64>(0) as u32,
+                decode_tower_key(row.get(1), row.get(2)),
+            )
+        })
+        .collect()
+    }
+}
+
+impl Drop for PostgresDBM {
+    fn drop(&mut self) {
+        // Closing the job channel makes the database thread finish once pending jobs are processed
+        self.jobs.take();
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                log::error!("The database thread stopped unexpectedly");
+            }
+        }
+    }
+}
+
+impl Storage for PostgresDBM {
+    fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error> {
+        let query =
+            "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry, tier) VALUES ($1, $2, $3, $4, $5)";
+
+        match self.store_data(
+            query,
+            params![
+                user_id.to_vec(),
+                user_info.available_slots as i64,
+                user_info.subscription_start as i64,
+                user_info.subscription_expiry as i64,
+                user_info.tier.clone(),
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("User successfully stored: {user_id}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store user: {user_id}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn update_user(&self, user_id: UserId, user_info: &UserInfo) {
+        let query =
+            "UPDATE users SET available_slots=$1, subscription_start=$2, subscription_expiry=$3, tier=$4 WHERE user_id=$5";
+        match self.modify_data(
+            query,
+            params![
+                user_info.available_slots as i64,
+                user_info.subscription_start as i64,
+                user_info.subscription_expiry as i64,
+                user_info.tier.clone(),
+                user_id.to_vec(),
+            ],
+        ) {
+            Ok(_) => {
+                log::debug!("User's info successfully updated: {user_id}");
+            }
+            Err(_) => {
+                log::error!("User not found, data cannot be updated: {user_id}");
+            }
+        }
+    }
+
+    fn load_user(&self, user_id: UserId) -> Result<Option<UserInfo>, Error> {
+        let row = match self.query_opt(
+            "SELECT available_slots, subscription_start, subscription_expiry, tier FROM users WHERE user_id=$1",
+            params![user_id.to_vec()],
+        )? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let appointments = self
+            .query(
+                "SELECT UUID, encrypted_blob FROM appointments WHERE user_id=$1",
+                params![user_id.to_vec()],
+            )?
+            .iter()
+            .map(|row| user_appointment_from_row(row, ENCRYPTED_BLOB_MAX_SIZE))
+            .collect();
+
+        Ok(Some(
+            UserInfo::with_appointments(
+                row.get::<_, i64>(0) as u32,
+                row.get::<_, i64>(1) as u32,
+                row.get::<_, i64>(2) as u32,
+                appointments,
+            )
+            .with_tier(row.get(3)),
+        ))
+    }
+
+    fn load_user_appointments(&self, user_id: UserId, slot_size: usize) -> HashMap<UUID, u32> {
+        self.query_or_log(
+            "SELECT UUID, encrypted_blob FROM appointments WHERE user_id=$1",
+            params![user_id.to_vec()],
+        )
+        .iter()
+        .map(|row| user_appointment_from_row(row, slot_size))
+        .collect()
+    }
+
+    fn load_all_users(&self) -> HashMap<UserId, UserInfo> {
+        self.query_or_log(
+            "SELECT user_id, available_slots, subscription_start, subscription_expiry, tier FROM users",
+            params![],
+        )
+        .iter()
+        .map(|row| {
+            let raw_userid: Vec<u8> = row.get(0);
+            let user_id = UserId::from_slice(&raw_userid).unwrap();
+            (
+                user_id,
+                UserInfo::with_appointments(
+                    row.get::<_, i64>(1) as u32,
+                    row.get::<_, i64>(2) as u32,
+                    row.get::<_, i64>(3) as u32,
+                    self.load_user_appointments(user_id, ENCRYPTED_BLOB_MAX_SIZE),
+                )
+                .with_tier(row.get(4)),
+            )
+        })
+        .collect()
+    }
+
+    fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize {
+        let keys: Vec<Vec<u8>> = users.iter().map(|user_id| user_id.to_vec()).collect();
+        let batch_size = self.batch_size;
+
+        let result = self.run(move |client| {
+            let mut tx = client.transaction()?;
+            for chunk in keys.chunks(batch_size) {
+                tx.execute("DELETE FROM users WHERE user_id = ANY($1)", &[&chunk])?;
+            }
+            tx.commit()
+        });
+
+        match result {
+            Ok(_) => log::debug!("Users successfully deleted"),
+            Err(e) => log::error!("Couldn't delete users. Error: {e:?}"),
+        }
+
+        (users.len() as f64 / batch_size as f64).ceil() as usize
+    }
+
+    fn store_appointment(
+        &self,
+        uuid: UUID,
+        appointment: &ExtendedAppointment,
+    ) -> Result<(), Error> {
+        let query = "INSERT INTO appointments (UUID, locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                appointment.locator().to_vec(),
+                appointment.encrypted_blob().clone(),
+                appointment.to_self_delay() as i64,
+                appointment.user_signature.clone(),
+                appointment.start_block as i64,
+                appointment.user_id.to_vec(),
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Appointment successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store appointment: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment) {
+        let query =
+            "UPDATE appointments SET encrypted_blob=$1, to_self_delay=$2, user_signature=$3, start_block=$4 WHERE UUID=$5";
+        match self.modify_data(
+            query,
+            params![
+                appointment.encrypted_blob().clone(),
+                appointment.to_self_delay() as i64,
+                appointment.user_signature.clone(),
+                appointment.start_block as i64,
+                uuid.to_vec(),
+            ],
+        ) {
+            Ok(_) => {
+                log::debug!("Appointment successfully updated: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Appointment not found, data cannot be updated: {uuid}");
+            }
+        }
+    }
+
+    fn load_appointment(&self, uuid: UUID) -> Option<ExtendedAppointment> {
+        self.query_opt_or_log(
+            "SELECT locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id
+                FROM appointments WHERE UUID=$1",
+            params![uuid.to_vec()],
+        )
+        .map(|row| appointment_from_row(&row, 0))
+    }
+
+    fn load_appointments(&self, locator: Option<Locator>) -> HashMap<UUID, ExtendedAppointment> {
+        let mut sql =
+            "SELECT a.UUID, a.locator, a.encrypted_blob, a.to_self_delay, a.user_signature, a.start_block, a.user_id
+                FROM appointments as a LEFT JOIN trackers as t ON a.UUID=t.UUID WHERE t.UUID IS NULL".to_string();
+        // If a locator was passed, filter based on it.
+        let params = if let Some(locator) = locator {
+            sql.push_str(" AND a.locator=$1");
+            params![locator.to_vec()]
+        } else {
+            params![]
+        };
+
+        self.query_or_log(&sql, params)
+            .iter()
+            .map(|row| {
+                let raw_uuid: Vec<u8> = row.get(0);
+                (
+                    UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                    appointment_from_row(row, 1),
+                )
+            })
+            .collect()
+    }
+
+    fn remove_appointment(&self, uuid: UUID) {
+        let query = "DELETE FROM appointments WHERE UUID=$1";
+        match self.modify_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Appointment successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Appointment not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn batch_remove_appointments(
+        &mut self,
+        appointments: &HashSet<UUID>,
+        updated_users: &HashMap<UserId, UserInfo>,
+    ) -> usize {
+        let keys: Vec<Vec<u8>> = appointments.iter().map(|uuid| uuid.to_vec()).collect();
+        let updated_users = updated_users
+            .iter()
+            .map(|(id, info)| (id.to_vec(), info.available_slots as i64))
+            .collect::<Vec<_>>();
+        let batch_size = self.batch_size;
+        let result = self.run(move |client| {
+            let mut tx = client.transaction()?;
+            for chunk in keys.chunks(batch_size) {
+                tx.execute("DELETE FROM appointments WHERE UUID = ANY($1)", &[&chunk])?;
+            }
+
+            for (id, available_slots) in updated_users.iter() {
+                tx.execute(
+                    "UPDATE users SET available_slots=$1 WHERE user_id=$2",
+                    &[available_slots, id],
+                )?;
+            }
+
+            tx.commit()
+        });
+
+        match result {
+            Ok(_) => log::debug!("Appointments successfully deleted"),
+            Err(e) => log::error!("Couldn't delete appointments. Error: {e:?}"),
+        }
+
+        (appointments.len() as f64 / batch_size as f64).ceil() as usize
+    }
+
+    fn batch_store_appointments(
+        &mut self,
+        appointments: &[(UUID, &ExtendedAppointment)],
+        breaches: &[(UUID, &Breach)],
+        user_id: UserId,
+        user_info: &UserInfo,
+    ) -> Result<(), Error> {
+        let appointments = appointments
+            .iter()
+            .map(|(uuid, appointment)| (*uuid, (*appointment).clone()))
+            .collect::<Vec<_>>();
+        let breaches = breaches
+            .iter()
+            .map(|(uuid, breach)| {
+                (
+                    uuid.to_vec(),
+                    consensus::serialize(&breach.dispute_tx),
+                    consensus::serialize(&breach.penalty_tx),
+                )
+            })
+            .collect::<Vec<_>>();
+        let count = appointments.len();
+        let available_slots = user_info.available_slots as i64;
+
+        let result = self.run(move |client| {
+            let mut tx = client.transaction()?;
+
+            for (uuid, appointment) in appointments.iter() {
+                let updated = tx.execute(
+                    "UPDATE appointments SET encrypted_blob=$1, to_self_delay=$2, user_signature=$3, start_block=$4 WHERE UUID=$5",
+                    &[
+                        appointment.encrypted_blob(),
+                        &(appointment.to_self_delay() as i64),
+                        &appointment.user_signature,
+                        &(appointment.start_block as i64),
+                        &uuid.to_vec(),
+                    ],
+                )?;
+
+                if updated == 0 {
+                    tx.execute(
+                        "INSERT INTO appointments (UUID, locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                        &[
+                            &uuid.to_vec(),
+                            &appointment.locator().to_vec(),
+                            appointment.encrypted_blob(),
+                            &(appointment.to_self_delay() as i64),
+                            &appointment.user_signature,
+                            &(appointment.start_block as i64),
+                            &appointment.user_id.to_vec(),
+                        ],
+                    )?;
+                }
+            }
+
+            for (uuid, dispute_tx, penalty_tx) in breaches.iter() {
+                tx.execute(
+                    "INSERT INTO queued_breaches (UUID, dispute_tx, penalty_tx) VALUES ($1, $2, $3)",
+                    &[uuid, dispute_tx, penalty_tx],
+                )?;
+            }
+
+            tx.execute(
+                "UPDATE users SET available_slots=$1 WHERE user_id=$2",
+                &[&available_slots, &user_id.to_vec()],
+            )?;
+
+            tx.commit()
+        });
+
+        match result {
+            Ok(_) => {
+                log::debug!("Appointments successfully stored: {count}");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("Couldn't store appointments. Error: {e:?}");
+                Err(Error::Backend(e.to_string()))
+            }
+        }
+    }
+
+    fn load_locator(&self, uuid: UUID) -> Option<Locator> {
+        self.query_opt_or_log(
+            "SELECT locator FROM appointments WHERE UUID=$1",
+            params![uuid.to_vec()],
+        )
+        .map(|row| Locator::from_slice(&row.get::<_, Vec<u8>>(0)).unwrap())
+    }
+
+    fn store_tracker(&self, uuid: UUID, tracker: &TransactionTracker) -> Result<(), Error> {
+        let (height, confirmed) = tracker.status.to_db_data().ok_or(Error::MissingField)?;
+
+        let query =
+            "INSERT INTO trackers (UUID, dispute_tx, penalty_tx, height, confirmed) VALUES ($1, $2, $3, $4, $5)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                consensus::serialize(&tracker.dispute_tx),
+                consensus::serialize(&tracker.penalty_tx),
+                height as i64,
+                confirmed,
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Tracker successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store tracker: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn load_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
+        self.query_opt_or_log(
+            "SELECT t.dispute_tx, t.penalty_tx, t.height, t.confirmed, a.user_id
+                FROM trackers as t INNER JOIN appointments as a ON t.UUID=a.UUID WHERE t.UUID=$1",
+            params![uuid.to_vec()],
+        )
+        .map(|row| tracker_from_row(&row, 0))
+    }
+
+    fn load_trackers(&self, locator: Option<Locator>) -> HashMap<UUID, TransactionTracker> {
+        let mut sql = "SELECT t.UUID, t.dispute_tx, t.penalty_tx, t.height, t.confirmed, a.user_id
+            FROM trackers as t INNER JOIN appointments as a ON t.UUID=a.UUID"
+            .to_string();
+        // If a locator was passed, filter based on it.
+        let params = if let Some(locator) = locator {
+            sql.push_str(" WHERE a.locator=$1");
+            params![locator.to_vec()]
+        } else {
+            params![]
+        };
+
+        self.query_or_log(&sql, params)
+            .iter()
+            .map(|row| {
+                let raw_uuid: Vec<u8> = row.get(0);
+                (
+                    UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                    tracker_from_row(row, 1),
+                )
+            })
+            .collect()
+    }
+
+    fn remove_tracker(&self, uuid: UUID) {
+        let query = "DELETE FROM trackers WHERE UUID=$1";
+        match self.modify_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Tracker successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Tracker not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn store_fee_bump(&self, uuid: UUID, fee_bump: &FeeBump) -> Result<(), Error> {
+        let query =
+            "INSERT INTO fee_bumps (UUID, child_tx, fee, fee_rate, height) VALUES ($1, $2, $3, $4, $5)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                consensus::serialize(&fee_bump.child_tx),
+                fee_bump.fee as i64,
+                fee_bump.fee_rate as i64,
+                fee_bump.height as i64,
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Fee bump successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store fee bump: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn load_fee_bumps(&self, uuid: UUID) -> Vec<FeeBump> {
+        self.query_or_log(
+            "SELECT child_tx, fee, fee_rate, height FROM fee_bumps WHERE UUID=$1 ORDER BY id",
+            params![uuid.to_vec()],
+        )
+        .iter()
+        .map(|row| FeeBump {
+            child_tx: consensus::deserialize(&row.get::<_, Vec<u8>>(0)).unwrap(),
+            fee: row.get::<_, i64>(1) as u64,
+            fee_rate: row.get::<_, i64>(2) as u64,
+            height: row.get::<_, i64>(3) as u32,
+        })
+        .collect()
+    }
+
+    fn store_queued_breach(&self, uuid: UUID, breach: &Breach) -> Result<(), Error> {
+        let query =
+            "INSERT INTO queued_breaches (UUID, dispute_tx, penalty_tx) VALUES ($1, $2, $3)";
+        match self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                consensus::serialize(&breach.dispute_tx),
+                consensus::serialize(&breach.penalty_tx),
+            ],
+        ) {
+            Ok(x) => {
+                log::debug!("Queued breach successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store queued breach: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn remove_queued_breach(&self, uuid: UUID) {
+        let query = "DELETE FROM queued_breaches WHERE UUID=$1";
+        match self.modify_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Queued breach successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Queued breach not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn load_queued_breaches(&self) -> HashMap<UUID, Breach> {
+        self.query_or_log(
+            "SELECT UUID, dispute_tx, penalty_tx FROM queued_breaches",
+            params![],
+        )
+        .iter()
+        .map(|row| {
+            (
+                UUID::from_slice(&row.get::<_, Vec<u8>>(0)).unwrap(),
+                Breach::new(
+                    consensus::deserialize(&row.get::<_, Vec<u8>>(1)).unwrap(),
+                    consensus::deserialize(&row.get::<_, Vec<u8>>(2)).unwrap(),
+                ),
+            )
+        })
+        .collect()
+    }
+
+    fn store_mempool_trigger(&self, uuid: UUID, dispute_txid: &Txid) -> Result<(), Error> {
+        let query = "INSERT INTO mempool_triggers (UUID, dispute_txid) VALUES ($1, $2)";
+        match self.store_data(query, params![uuid.to_vec(), dispute_txid.to_vec()]) {
+            Ok(x) => {
+                log::debug!("Mempool trigger successfully stored: {uuid}");
+                Ok(x)
+            }
+            Err(e) => {
+                log::error!("Couldn't store mempool trigger: {uuid}. Error: {e:?}");
+                Err(e)
+            }
+        }
+    }
+
+    fn remove_mempool_trigger(&self, uuid: UUID) {
+        let query = "DELETE FROM mempool_triggers WHERE UUID=$1";
+        match self.modify_data(query, params![uuid.to_vec()]) {
+            Ok(_) => {
+                log::debug!("Mempool trigger successfully removed: {uuid}");
+            }
+            Err(_) => {
+                log::error!("Mempool trigger not found, data cannot be removed: {uuid}");
+            }
+        }
+    }
+
+    fn load_mempool_triggers(&self) -> HashMap<UUID, Txid> {
+        self.query_or_log("SELECT UUID, dispute_txid FROM mempool_triggers", params![])
+            .iter()
+            .map(|row| {
+                (
+                    UUID::from_slice(&row.get::<_, Vec<u8>>(0)).unwrap(),
+                    Txid::from_slice(&row.get::<_, Vec<u8>>(1)).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn store_pending_registration(
+        &self,
+        user_id: UserId,
+        registration: &PendingRegistration,
+    ) -> Result<(), Error> {
+        let query = "INSERT INTO pending_registrations (user_id, invoice, payment_hash, amount_msat, expires_at, tier, operation) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        let invoice = &registration.invoice;
+        self.store_data(
+            query,
+            params![
+                user_id.to_vec(),
+                invoice.bolt11.clone(),
+                invoice.payment_hash.to_vec(),
+                invoice.amount_msat as i64,
+                invoice.expires_at as i64,
+                registration.tier.clone(),
+                registration.operation as i64,
+            ],
+        )
+    }
+
+    fn remove_pending_registration(&self, payment_hash: &[u8; 32]) {
+        if let Err(e) = self.execute(
+            "DELETE FROM pending_registrations WHERE payment_hash=$1",
+            params![payment_hash.to_vec()],
+        ) {
+            log::error!("Couldn't remove pending registration. Error: {e:?}");
+        }
+    }
+
+    fn remove_pending_registrations(&self, user_id: UserId) {
+        if let Err(e) = self.execute(
+            "DELETE FROM pending_registrations WHERE user_id=$1",
+            params![user_id.to_vec()],
+        ) {
+            log::error!("Couldn't remove pending registrations of {user_id}. Error: {e:?}");
+        }
+    }
+
+    fn load_pending_registrations(&self) -> HashMap<UserId, Vec<PendingRegistration>> {
+        let mut pending_registrations: HashMap<UserId, Vec<PendingRegistration>> = HashMap::new();
+        for row in self.query_or_log(
+            "SELECT user_id, invoice, payment_hash, amount_msat, expires_at, tier, operation FROM pending_registrations ORDER BY expires_at",
+            params![],
+        ) {
+            let raw_userid: Vec<u8> = row.get(0);
+            let raw_payment_hash: Vec<u8> = row.get(2);
+            pending_registrations
+                .entry(UserId::from_slice(&raw_userid).unwrap())
+                .or_default()
+                .push(PendingRegistration {
+                    tier: row.get(5),
+                    operation: SubscriptionOperation::from(row.get::<_, i64>(6) as u8),
+                    invoice: Invoice {
+                        bolt11: row.get(1),
+                        payment_hash: raw_payment_hash.try_into().unwrap(),
+                        amount_msat: row.get::<_, i64>(3) as u64,
+                        expires_at: row.get::<_, i64>(4) as u64,
+                    },
+                });
+        }
+
+        pending_registrations
+    }
+
+    fn store_banned_user(&self, user_id: UserId) -> Result<(), Error> {
+        let query = "INSERT INTO banned_users (user_id) VALUES ($1)";
+        self.store_data(query, params![user_id.to_vec()])
+    }
+
+    fn remove_banned_user(&self, user_id: UserId) {
+        if let Err(e) = self.execute(
+            "DELETE FROM banned_users WHERE user_id=$1",
+            params![user_id.to_vec()],
+        ) {
+            log::error!("Couldn't remove banned user: {user_id}. Error: {e:?}");
+        }
+    }
+
+    fn load_banned_users(&self) -> HashSet<UserId> {
+        self.query_or_log("SELECT user_id FROM banned_users", params![])
+            .iter()
+            .map(|row| UserId::from_slice(&row.get::<_, Vec<u8>>(0)).unwrap())
+            .collect()
+    }
+
+    fn store_audit_log_entry(&self, entry: &AuditLogEntry) -> Result<(), Error> {
+        let query =
+            "INSERT INTO audit_log (timestamp, user_id, action, details) VALUES ($1, $2, $3, $4)";
+        self.store_data(
+            query,
+            params![
+                entry.timestamp as i64,
+                entry.user_id.to_vec(),
+                entry.action.clone(),
+                entry.details.clone(),
+            ],
+        )
+    }
+
+    fn load_audit_log(&self) -> Vec<AuditLogEntry> {
+        self.query_or_log(
+            "SELECT timestamp, user_id, action, details FROM audit_log ORDER BY id",
+            params![],
+        )
+        .iter()
+        .map(|row| AuditLogEntry {
+            timestamp: row.get::<_, i64>(0) as u64,
+            user_id: UserId::from_slice(&row.get::<_, Vec<u8>>(1)).unwrap(),
+            action: row.get(2),
+            details: row.get(3),
+        })
+        .collect()
+    }
+
+    fn store_redeemed_token(&self, token: &[u8]) -> Result<(), Error> {
+        let query = "INSERT INTO redeemed_tokens (token) VALUES ($1)";
+        self.store_data(query, params![token.to_vec()])
+    }
+
+    fn remove_redeemed_token(&self, token: &[u8]) {
+        if let Err(e) = self.execute(
+            "DELETE FROM redeemed_tokens WHERE token=$1",
+            params![token.to_vec()],
+        ) {
+            log::error!("Couldn't remove redeemed token. Error: {e:?}");
+        }
+    }
+
+    fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error> {
+        let query = "INSERT INTO last_known_block (id, block_hash) VALUES (0, $1)
+            ON CONFLICT (id) DO UPDATE SET block_hash=EXCLUDED.block_hash";
+        self.store_data(query, params![block_hash.to_vec()])
+    }
+
+    fn load_last_known_block(&self) -> Result<Option<BlockHash>, Error> {
+        Ok(self
+            .query_opt(
+                "SELECT block_hash FROM last_known_block WHERE id=0",
+                params![],
+            )?
+            .map(|row| BlockHash::from_slice(&row.get::<_, Vec<u8>>(0)).unwrap()))
+    }
+
+    fn has_appointments(&self) -> bool {
+        match self.query_opt("SELECT EXISTS(SELECT 1 FROM appointments)", params![]) {
+            Ok(row) => row.map(|row| row.get(0)).unwrap_or_default(),
+            Err(e) => {
+                // Assume there are, so no block is skipped for lack of appointments to check
+                log::error!("Couldn't check for appointments in the database. Error: {e:?}");
+                true
+            }
+        }
+    }
+
+    fn store_block_summary(&self, height: u32, summary: &BlockSummary) -> Result<(), Error> {
+        let query = "INSERT INTO block_summaries (height, header, txids, txdata) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (height) DO UPDATE SET header=EXCLUDED.header, txids=EXCLUDED.txids, txdata=EXCLUDED.txdata";
+        self.store_data(
+            query,
+            params![
+                height as i64,
+                consensus::serialize(&summary.header),
+                summary
+                    .txids
+                    .iter()
+                    .flat_map(|txid| txid.into_inner())
+                    .collect::<Vec<u8>>(),
+                summary.txdata.as_ref().map(consensus::serialize),
+            ],
+        )
+    }
+
+    fn remove_block_summary(&self, height: u32) {
+        if let Err(e) = self.execute(
+            "DELETE FROM block_summaries WHERE height=$1",
+            params![height as i64],
+        ) {
+            log::error!("Couldn't remove block summary at height {height}. Error: {e:?}");
+        }
+    }
+
+    fn prune_block_summaries(&self, height: u32, txdata_height: u32) {
+        if let Err(e) = self.execute(
+            "DELETE FROM block_summaries WHERE height<=$1",
+            params![height as i64],
+        ) {
+            log::error!("Couldn't remove block summaries up to height {height}. Error: {e:?}");
+        }
+        if let Err(e) = self.execute(
+            "UPDATE block_summaries SET txdata=NULL WHERE height<=$1 AND txdata IS NOT NULL",
+            params![txdata_height as i64],
+        ) {
+            log::error!("Couldn't prune block data up to height {txdata_height}. Error: {e:?}");
+        }
+    }
+
+    fn load_block_summaries(&self) -> Vec<BlockSummary> {
+        self.query_or_log(
+            "SELECT header, txids, txdata FROM block_summaries ORDER BY height DESC",
+            params![],
+        )
+        .iter()
+        .map(|row| {
+            let raw_txids: Vec<u8> = row.get(1);
+            let raw_txdata: Option<Vec<u8>> = row.get(2);
+            BlockSummary {
+                header: consensus::deserialize(&row.get::<_, Vec<u8>>(0)).unwrap(),
+                txids: raw_txids
+                    .chunks(32)
+                    .map(|txid| Txid::from_slice(txid).unwrap())
+                    .collect(),
+                txdata: raw_txdata.map(|txdata| consensus::deserialize(&txdata).unwrap()),
+            }
+        })
+        .collect()
+    }
+
+    fn store_tower_key(&self, key: &TowerKey) -> Result<(), Error> {
+        let (key, encrypted) = encode_tower_key(key);
+        let query = "INSERT INTO keys (key, encrypted) VALUES ($1, $2)";
+        self.store_data(query, params![key, encrypted])
+    }
+
+    fn load_tower_key(&self) -> Result<Option<TowerKey>, Error> {
+        Ok(self
+            .query_opt(
+                "SELECT key, encrypted FROM keys ORDER BY id DESC LIMIT 1",
+                params![],
+            )?
+            .map(|row| decode_tower_key(row.get(0), row.get(1))))
+    }
+
+    fn load_all_tower_keys(&self) -> HashMap<u32, TowerKey> {
+        self.load_all_keys("keys")
+    }
+
+    fn update_tower_keys(
+        &mut self,
+        keys: &HashMap<u32, TowerKey>,
+        wallet_keys: &HashMap<u32, TowerKey>,
+    ) -> Result<(), Error> {
+        let encode = |table: &'static str, keys: &HashMap<u32, TowerKey>| {
+            keys.iter()
+                .map(move |(id, key)| (table, *id as i64, encode_tower_key(key)))
+                .collect::<Vec<_>>()
+        };
+        let mut keys = encode("keys", keys);
+        keys.extend(encode("wallet_keys", wallet_keys));
+
+        // Returns whether all the keys were found (and therefore updated)
+        let result = self.run(move |client| {
+            let mut tx = client.transaction()?;
+            for (table, id, (key, encrypted)) in keys.iter() {
+                if tx.execute(
+                    format!("UPDATE {table} SET key=$1, encrypted=$2 WHERE id=$3").as_str(),
+                    &[key, encrypted, id],
+                )? == 0
+                {
+                    return Ok(false);
+                }
+            }
+            tx.commit().map(|_| true)
+        });
+
+        match result {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::NotFound),
+            Err(e) => Err(map_error(e)),
+        }
+    }
+
+    fn store_wallet_key(&self, key: &TowerKey) -> Result<(), Error> {
+        let (key, encrypted) = encode_tower_key(key);
+        let query = "INSERT INTO wallet_keys (key, encrypted) VALUES ($1, $2)";
+        self.store_data(query, params![key, encrypted])
+    }
+
+    fn load_wallet_key(&self) -> Result<Option<TowerKey>, Error> {
+        Ok(self
+            .query_opt(
+                "SELECT key, encrypted FROM wallet_keys ORDER BY id DESC LIMIT 1",
+                params![],
+            )?
+            .map(|row| decode_tower_key(row.get(0), row.get(1))))
+    }
+
+    fn load_all_wallet_keys(&self) -> HashMap<u32, TowerKey> {
+        self.load_all_keys("wallet_keys")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teos_common::cryptography::get_random_bytes;
+
+    /// Environment variable holding the connection string of the database used to test the `PostgreSQL` backend.
+    const TEST_DATABASE_URL: &str = "TEOS_TEST_POSTGRES_URL";
+
+    impl PostgresDBM {
+        /// Creates a [PostgresDBM] instance working on a fresh schema, which is dropped once the instance is dropped.
+        ///
+        /// The test database is set using `TEOS_TEST_POSTGRES_URL`. Tests using it are ignored by default, and can be
+        /// run using `cargo test -- --ignored`.
+        pub(crate) fn for_tests() -> Self {
+            let url = std::env::var(TEST_DATABASE_URL).unwrap_or_else(|_| {
+                panic!(
+                    "{} must be set to run the Postgres tests",
+                    TEST_DATABASE_URL
+                )
+            });
+            let schema = format!("teos_test_{}", hex::encode(get_random_bytes(8)));
+            PostgresDBM::connect(url, Some(schema)).unwrap()
+        }
+
+        pub(crate) fn set_max_batch_size(&mut self, batch_size: usize) {
+            self.batch_size = batch_size;
+        }
+
+        pub(crate) fn drop_table_for_tests(&self, table: &str) {
+            self.execute(&format!("DROP TABLE {table} CASCADE"), params![])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_connect_unreachable_database() {
+        assert!(PostgresDBM::new("postgresql://teos@127.0.0.1:1/teos").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs a Postgres database (set TEOS_TEST_POSTGRES_URL)"]
+    fn test_reconnect() {
+        let dbm = PostgresDBM::for_tests();
+        let block_hash = BlockHash::from_slice(&get_random_bytes(32)).unwrap();
+        dbm.store_last_known_block(&block_hash).unwrap();
+
+        // Have the server close the connection. The query running at the time fails, but it does not take the
+        // database thread down with it
+        assert!(dbm
+            .query("SELECT pg_terminate_backend(pg_backend_pid())", params![])
+            .is_err());
+
+        // The following ones run on a new connection, using the same schema
+        assert_eq!(dbm.load_last_known_block().unwrap(), Some(block_hash));
+        dbm.remove_redeemed_token(&get_random_bytes(32));
+    }
+}
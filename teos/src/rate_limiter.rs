@@ -0,0 +1,158 @@
+//! Logic related to rate limiting requests to the public API.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Number of tracked keys after which full buckets are dropped from memory.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Error raised if a key has run out of requests. Contains the seconds until a new request will be accepted.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RateLimited(pub u64);
+
+/// The requests left to a given key, alongside the last time they were refilled.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket rate limiter.
+///
+/// Each key is given a bucket of `burst` requests, which is refilled at a pace of `rate` requests per minute. Requests
+/// made once the bucket of a key is empty are rejected until it is refilled.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    /// Requests given back to each key per second.
+    refill_rate: f64,
+    /// Maximum number of requests a key can make at once.
+    burst: f64,
+    /// Map of tracked keys and the requests they have left.
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// Creates a new [RateLimiter] instance.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        assert!(rate > 0 && burst > 0, "Rate and burst must be at least 1");
+        RateLimiter {
+            refill_rate: rate as f64 / 60.0,
+            burst: burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Consumes a request for the given key, if it has any left.
+    pub fn check(&self, key: K) -> Result<(), RateLimited> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Consumes a request for the given key at a given time, if it has any left.
+    fn check_at(&self, key: K, now: Instant) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            let (refill_rate, burst) = (self.refill_rate, self.burst);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + elapsed_secs(bucket.last_refill, now) * refill_rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        bucket.tokens = (bucket.tokens + elapsed_secs(bucket.last_refill, now) * self.refill_rate)
+            .min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(RateLimited(
+                ((1.0 - bucket.tokens) / self.refill_rate).ceil() as u64,
+            ))
+        }
+    }
+}
+
+/// Gets the seconds elapsed between two instants, or zero if `now` is older than `then`.
+fn elapsed_secs(then: Instant, now: Instant) -> f64 {
+    now.saturating_duration_since(then).as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_check() {
+        let rate_limiter = RateLimiter::new(60, 3);
+        let now = Instant::now();
+
+        // A key can make up to burst requests at once
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.check_at("key", now), Ok(()));
+        }
+        assert_eq!(rate_limiter.check_at("key", now), Err(RateLimited(1)));
+
+        // Other keys are not affected
+        assert_eq!(rate_limiter.check_at("other_key", now), Ok(()));
+
+        // Requests are given back at the configured pace (one per second in this case)
+        let later = now + Duration::from_millis(500);
+        assert_eq!(rate_limiter.check_at("key", later), Err(RateLimited(1)));
+        let later = now + Duration::from_secs(1);
+        assert_eq!(rate_limiter.check_at("key", later), Ok(()));
+        assert_eq!(rate_limiter.check_at("key", later), Err(RateLimited(1)));
+
+        // But never above the burst
+        let later = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.check_at("key", later), Ok(()));
+        }
+        assert_eq!(rate_limiter.check_at("key", later), Err(RateLimited(1)));
+    }
+
+    #[test]
+    fn test_check_retry_after() {
+        // With a rate of one request every 4 seconds, an empty bucket needs 4 seconds to accept a new request
+        let rate_limiter = RateLimiter::new(15, 1);
+        let now = Instant::now();
+
+        assert_eq!(rate_limiter.check_at("key", now), Ok(()));
+        assert_eq!(rate_limiter.check_at("key", now), Err(RateLimited(4)));
+        assert_eq!(
+            rate_limiter.check_at("key", now + Duration::from_secs(1)),
+            Err(RateLimited(3))
+        );
+        assert_eq!(
+            rate_limiter.check_at("key", now + Duration::from_secs(4)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_check_prune() {
+        let rate_limiter = RateLimiter::new(60, 1);
+        let now = Instant::now();
+
+        for i in 0..PRUNE_THRESHOLD {
+            rate_limiter.check_at(i, now).unwrap();
+        }
+        assert_eq!(rate_limiter.buckets.lock().unwrap().len(), PRUNE_THRESHOLD);
+
+        // Keys whose bucket has been refilled are dropped once the threshold is reached, while the rest are kept
+        let later = now + Duration::from_secs(1);
+        rate_limiter.check_at(0, later).unwrap();
+        rate_limiter.check_at(PRUNE_THRESHOLD, later).unwrap();
+        let buckets = rate_limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert!(buckets.contains_key(&0) && buckets.contains_key(&PRUNE_THRESHOLD));
+    }
+}
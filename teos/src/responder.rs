@@ -7,17 +7,17 @@ use std::sync::{Arc, Mutex};
 use bitcoin::{consensus, BlockHash};
 use bitcoin::{BlockHeader, Transaction, Txid};
 use lightning::chain;
-use lightning_block_sync::poll::ValidatedBlock;
 
 use teos_common::constants;
 use teos_common::protos as common_msgs;
 use teos_common::UserId;
 
-use crate::carrier::Carrier;
-use crate::dbm::DBM;
+use crate::carrier::TxBroadcaster;
+use crate::dbm::Storage;
 use crate::extended_appointment::UUID;
 use crate::gatekeeper::{Gatekeeper, UserInfo};
-use crate::tx_index::TxIndex;
+use crate::tx_index::{IndexableBlock, TxIndex};
+use crate::wallet::{compute_fee, CpfpError, FeeBump, Wallet};
 use crate::watcher::Breach;
 
 /// Number of missed confirmations to wait before rebroadcasting a transaction.
@@ -38,6 +38,8 @@ enum DeletionReason {
     Outdated,
     Rejected,
     Completed,
+    DisputeDropped,
+    UserDeleted,
 }
 
 impl ConfirmationStatus {
@@ -87,7 +89,7 @@ pub(crate) struct TrackerSummary {
 ///
 /// It is analogous to [ExtendedAppointment](crate::extended_appointment::ExtendedAppointment) for the [`Watcher`](crate::watcher::Watcher).
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct TransactionTracker {
+pub struct TransactionTracker {
     /// Matches the corresponding [Breach] `dispute_tx` field.
     pub dispute_tx: Transaction,
     /// Matches the corresponding [Breach] penalty_tx field.
@@ -100,7 +102,7 @@ pub(crate) struct TransactionTracker {
 
 impl TransactionTracker {
     /// Creates a new [TransactionTracker] instance.
-    pub fn new(breach: Breach, user_id: UserId, status: ConfirmationStatus) -> Self {
+    pub(crate) fn new(breach: Breach, user_id: UserId, status: ConfirmationStatus) -> Self {
         Self {
             dispute_tx: breach.dispute_tx,
             penalty_tx: breach.penalty_tx,
@@ -110,7 +112,7 @@ impl TransactionTracker {
     }
 
     /// Computes the [TrackerSummary] of the [TransactionTracker].
-    pub fn get_summary(&self) -> TrackerSummary {
+    pub(crate) fn get_summary(&self) -> TrackerSummary {
         TrackerSummary {
             user_id: self.user_id,
             penalty_txid: self.penalty_tx.txid(),
@@ -132,10 +134,10 @@ impl From<TransactionTracker> for common_msgs::Tracker {
 /// Component in charge of keeping track of triggered appointments.
 ///
 /// The [Responder] receives data from the [Watcher](crate::watcher::Watcher) in form of a [Breach].
-/// From there, a [TransactionTracker] is created and the penalty transaction is sent to the network via the [Carrier].
+/// From there, a [TransactionTracker] is created and the penalty transaction is sent to the network via its [TxBroadcaster].
 /// The [Transaction] is then monitored to make sure it makes it to a block and it gets [irrevocably resolved](https://github.com/lightning/bolts/blob/master/05-onchain.md#general-nomenclature).
 #[derive(Debug)]
-pub struct Responder {
+pub struct Responder<B: TxBroadcaster = Box<dyn TxBroadcaster>> {
     /// A map holding a summary of every tracker ([TransactionTracker]) hold by the [Responder], identified by [UUID].
     /// The identifiers match those used by the [Watcher](crate::watcher::Watcher).
     trackers: Mutex<HashMap<UUID, TrackerSummary>>,
@@ -143,22 +145,24 @@ pub struct Responder {
     tx_tracker_map: Mutex<HashMap<Txid, HashSet<UUID>>>,
     /// A local, pruned, [TxIndex] used to avoid the need of `txindex=1`.
     tx_index: Mutex<TxIndex<Txid, BlockHash>>,
-    /// A [Carrier] instance. Data is sent to the `bitcoind` through it.
-    carrier: Mutex<Carrier>,
+    /// A [TxBroadcaster] instance (e.g. a [Carrier](crate::carrier::Carrier)). Data is sent to the Bitcoin network through it.
+    carrier: Mutex<B>,
     /// A [Gatekeeper] instance. Data regarding users is requested to it.
     gatekeeper: Arc<Gatekeeper>,
-    /// A [DBM] (database manager) instance. Used to persist tracker data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// A [Storage] (database manager) instance. Used to persist tracker data into disk.
+    dbm: Arc<Mutex<dyn Storage>>,
+    /// A [Wallet] instance, if any. Used to bump the fees of penalty transactions that keep missing confirmations.
+    wallet: Option<Wallet>,
 }
 
-impl Responder {
+impl<B: TxBroadcaster> Responder<B> {
     /// Creates a new [Responder] instance.
-    pub fn new(
-        last_n_blocs: &[ValidatedBlock],
+    pub fn new<I: IndexableBlock>(
+        last_n_blocs: &[I],
         last_known_block_height: u32,
-        carrier: Carrier,
+        carrier: B,
         gatekeeper: Arc<Gatekeeper>,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
     ) -> Self {
         let mut trackers = HashMap::new();
         let mut tx_tracker_map: HashMap<Txid, HashSet<UUID>> = HashMap::new();
@@ -180,9 +184,16 @@ impl Responder {
             tx_index: Mutex::new(TxIndex::new(last_n_blocs, last_known_block_height)),
             dbm,
             gatekeeper,
+            wallet: None,
         }
     }
 
+    /// Sets the [Wallet] used by the [Responder] to bump the fees of penalty transactions.
+    pub fn with_wallet(mut self, wallet: Wallet) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
     /// Returns whether the [Responder] has been created from scratch (fresh) or from backed-up data.
     pub fn is_fresh(&self) -> bool {
         self.trackers.lock().unwrap().is_empty()
@@ -193,6 +204,11 @@ impl Responder {
         self.trackers.lock().unwrap().len()
     }
 
+    /// Gets the [TxBroadcaster] used by the [Responder]. The [Watcher](crate::watcher::Watcher) uses it to query the mempool.
+    pub(crate) fn get_carrier(&self) -> &Mutex<B> {
+        &self.carrier
+    }
+
     /// Data entry point for the [Responder]. Handles a [Breach] provided by the [Watcher](crate::watcher::Watcher).
     ///
     /// Breaches can either be added to the [Responder] in the form of a [TransactionTracker] if the [penalty transaction](Breach::penalty_tx)
@@ -233,7 +249,7 @@ impl Responder {
     ///
     /// From this point on, transactions are accepted as valid. They may not end up being confirmed, but they
     /// have been checked syntactically by the [Watcher](crate::watcher::Watcher) and against consensus / network
-    /// acceptance rules by the [Carrier].
+    /// acceptance rules by the [TxBroadcaster].
     ///
     /// Some transaction may already be confirmed by the time the tower tries to send them to the network. If that's the case,
     /// the [Responder] will simply continue tracking the job until its completion.
@@ -266,6 +282,29 @@ impl Responder {
         log::info!("New tracker added (uuid={uuid})");
     }
 
+    /// Removes a [TransactionTracker] triggered by a dispute transaction that never made it to the chain.
+    ///
+    /// This is only the case for breaches spotted in the mempool, where the dispute transaction may be replaced or
+    /// dropped before confirming. The tracker is removed from memory and the database, but the appointment is kept
+    /// so the [Watcher](crate::watcher::Watcher) can keep watching for it.
+    pub(crate) fn remove_tracker(&self, uuid: UUID) {
+        if self.trackers.lock().unwrap().contains_key(&uuid) {
+            self.delete_trackers_from_memory(
+                &HashSet::from_iter([uuid]),
+                DeletionReason::DisputeDropped,
+            );
+            self.dbm.lock().unwrap().remove_tracker(uuid);
+        }
+    }
+
+    /// Removes the trackers of a user that has been deleted from the tower.
+    ///
+    /// Trackers are removed from memory only, the database deletion is handled by the
+    /// [Gatekeeper](crate::gatekeeper::Gatekeeper) alongside the user.
+    pub(crate) fn remove_user_trackers(&self, uuids: &HashSet<UUID>) {
+        self.delete_trackers_from_memory(uuids, DeletionReason::UserDeleted);
+    }
+
     /// Checks whether a given tracker can be found in the [Responder].
     pub(crate) fn has_tracker(&self, uuid: UUID) -> bool {
         // has_tracker should return true as long as the given tracker is hold by the Responder.
@@ -294,7 +333,7 @@ impl Responder {
 
     /// Gets a tracker from the [Responder] if found. [None] otherwise.
     ///
-    /// The [TransactionTracker] is queried to the [DBM].
+    /// The [TransactionTracker] is queried to the [Storage].
     pub(crate) fn get_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
         if self.trackers.lock().unwrap().contains_key(&uuid) {
             self.dbm.lock().unwrap().load_tracker(uuid)
@@ -341,7 +380,7 @@ impl Responder {
     /// reorged out of the chain. If the transaction has been reorged out, the commitment transaction is also returned.
     ///
     /// Given the [Responder] only keeps around the minimal data to track transactions, the [TransactionTracker]s
-    /// are queried to the [DBM].
+    /// are queried to the [Storage].
     fn get_txs_to_rebroadcast(
         &self,
         height: u32,
@@ -388,8 +427,8 @@ impl Responder {
     /// Rebroadcasts a list of penalty transactions that have missed too many confirmations (or that have been reorged out).
     ///
     /// This covers both the case where a transaction is not getting confirmations (most likely due to low fess, and needs to be bumped),
-    /// and the case where the transaction has been reorged out of the chain. For the former, there's no much to be done at the moment (until anchors),
-    /// for the latter, we need to rebroadcast the penalty (and potentially the commitment if that has also been reorged).
+    /// and the case where the transaction has been reorged out of the chain. For the former, the fee is bumped via CPFP if the [Responder]
+    /// has a [Wallet] (see [Responder::create_fee_bump]), for the latter, we need to rebroadcast the penalty (and potentially the commitment if that has also been reorged).
     ///
     /// Given how the confirmation status and reorgs work with a bitcoind backend, we will be rebroadcasting this during the first new connected block
     /// after a reorg, but bitcoind will already be at the new tip. If the transaction is accepted, we won't do anything else until passed the new tip,
@@ -420,16 +459,16 @@ impl Responder {
                     // DISCUSS: For lightning transactions, if the dispute has been reorged the penalty cannot make it to the network.
                     // If we keep this general, the dispute can simply be a trigger and the penalty doesn't necessarily have to spend from it.
                     // We'll keel it lightning specific, at least for now.
-                    let status = carrier.send_transaction(&dispute_tx);
-                    if let ConfirmationStatus::Rejected(e) = status {
+                    // Both are sent as a package so they are relayed atomically.
+                    let receipts = carrier.send_package(&[dispute_tx.clone(), penalty_tx.clone()]);
+                    if let ConfirmationStatus::Rejected(e) = receipts[0] {
                         log::error!(
-                        "Reorged dispute transaction rejected during rebroadcast: {} (reason: {e})",
-                        dispute_tx.txid()
-                    );
-                        status
+                            "Reorged dispute transaction rejected during rebroadcast: {} (reason: {e})",
+                            dispute_tx.txid()
+                        );
+                        receipts[0]
                     } else {
-                        // The dispute was accepted, so we can rebroadcast the penalty.
-                        carrier.send_transaction(&penalty_tx)
+                        receipts[1]
                     }
                 }
             } else {
@@ -438,7 +477,34 @@ impl Responder {
                     "Penalty transaction has missed many confirmations: {}",
                     penalty_tx.txid()
                 );
-                carrier.send_transaction(&penalty_tx)
+                match self.create_fee_bump(uuid, &penalty_tx, &carrier) {
+                    Some(fee_bump) => {
+                        // The penalty and the child are sent as a package, so the penalty does not need to pay for itself to make it to the mempool.
+                        let receipts =
+                            carrier.send_package(&[penalty_tx.clone(), fee_bump.child_tx.clone()]);
+                        if let ConfirmationStatus::Rejected(e) = receipts[1] {
+                            log::error!(
+                                "CPFP transaction rejected: {} (reason: {e})",
+                                fee_bump.child_tx.txid()
+                            );
+                        } else {
+                            log::info!(
+                                "Penalty fee bumped (uuid: {uuid}, child: {}, fee: {} sats, fee rate: {} sat/vB)",
+                                fee_bump.child_tx.txid(),
+                                fee_bump.fee,
+                                fee_bump.fee_rate
+                            );
+                            // The child has already been broadcast, so the bump can only be logged if it cannot be stored.
+                            // The next bump for the tracker will escalate from the last stored one
+                            if let Err(e) = self.dbm.lock().unwrap().store_fee_bump(uuid, &fee_bump)
+                            {
+                                log::error!("Cannot store the fee bump of {uuid}. Error: {e:?}");
+                            }
+                        }
+                        receipts[0]
+                    }
+                    None => carrier.send_transaction(&penalty_tx),
+                }
             };
 
             if let ConfirmationStatus::Rejected(_) = status {
@@ -455,6 +521,63 @@ impl Responder {
         (accepted, rejected)
     }
 
+    /// Creates a [FeeBump] for a penalty transaction that keeps missing confirmations, spending from it with the tower [Wallet] (CPFP).
+    ///
+    /// [None] is returned if the [Responder] has no [Wallet], or if the penalty has no outputs the [Wallet] can spend. The fee rate targeted
+    /// by consecutive bumps of the same tracker is escalated following the [FeeRatePolicy](crate::wallet::FeeRatePolicy). Accepted bumps are
+    /// stored in the database by the caller so the fees spent by the tower are accounted per tracker.
+    fn create_fee_bump(
+        &self,
+        uuid: UUID,
+        penalty_tx: &Transaction,
+        carrier: &B,
+    ) -> Option<FeeBump> {
+        let wallet = self.wallet.as_ref()?;
+
+        // The database is not held while querying the carrier, given the backend may take a while to reply
+        let (fee_bumps, tracker) = {
+            let dbm = self.dbm.lock().unwrap();
+            (dbm.load_fee_bumps(uuid), dbm.load_tracker(uuid))
+        };
+        let fee_rate = match wallet.policy().next_fee_rate(
+            carrier.estimate_fee_rate(CONFIRMATIONS_BEFORE_RETRY as u16),
+            fee_bumps.last().map(|fee_bump| fee_bump.fee_rate),
+        ) {
+            Some(fee_rate) => fee_rate,
+            None => {
+                log::warn!("Maximum fee rate reached, the penalty fee cannot be bumped any further: {uuid}");
+                return None;
+            }
+        };
+
+        // Penalties spend from their dispute, so the fee they already pay can be taken into account (or ignored if unknown)
+        let parent_fee = tracker
+            .and_then(|tracker| compute_fee(penalty_tx, &[&tracker.dispute_tx]))
+            .unwrap_or(0);
+
+        match wallet.create_cpfp(
+            penalty_tx,
+            parent_fee,
+            &carrier.get_utxos(&wallet.address()),
+            fee_rate,
+        ) {
+            Ok((child_tx, fee)) => Some(FeeBump {
+                child_tx,
+                fee,
+                fee_rate,
+                height: carrier.block_height(),
+            }),
+            Err(CpfpError::NoSpendableOutputs) => {
+                log::info!("The penalty has no outputs the tower can spend, its fee cannot be bumped: {uuid}");
+                None
+            }
+            Err(CpfpError::InsufficientFunds) => {
+                log::warn!("Not enough funds in the tower wallet to bump the penalty fee: {uuid}");
+                None
+            }
+        }
+    }
+
     // DISCUSS: Check comment regarding callbacks in watcher.rs
 
     /// Deletes trackers from memory.
@@ -468,6 +591,8 @@ impl Responder {
                 DeletionReason::Completed => log::info!("Appointment completed. Penalty transaction was irrevocably confirmed: {uuid}"),
                 DeletionReason::Outdated => log::info!("Appointment couldn't be completed. Expiry reached but penalty didn't make it to the chain: {uuid}"),
                 DeletionReason::Rejected => log::info!("Appointment couldn't be completed. Either the dispute or the penalty txs where rejected during rebroadcast: {uuid}"),
+                DeletionReason::DisputeDropped => log::info!("Dispute transaction left the mempool before confirming. Handing the appointment back to the Watcher: {uuid}"),
+                DeletionReason::UserDeleted => log::info!("Appointment belongs to a user deleted by the tower admin: {uuid}"),
             }
 
             match trackers.remove(uuid) {
@@ -513,7 +638,7 @@ impl Responder {
 }
 
 /// Listen implementation by the [Responder]. Handles monitoring and reorgs.
-impl chain::Listen for Responder {
+impl<B: TxBroadcaster> chain::Listen for Responder<B> {
     /// Handles the monitoring process by the [Responder].
     ///
     /// Watching is performed in a per-block basis. A [TransactionTracker] is tracked until:
@@ -612,36 +737,38 @@ mod tests {
 
     use std::sync::{Arc, Mutex};
 
+    use crate::carrier::Carrier;
     use crate::dbm::DBM;
     use crate::gatekeeper::UserInfo;
     use crate::rpc_errors;
     use crate::test_utils::{
         create_carrier, generate_dummy_appointment_with_user, generate_uuid, get_last_n_blocks,
         get_random_breach, get_random_tracker, get_random_tx, store_appointment_and_fks_to_db,
-        BitcoindStopper, Blockchain, MockedServerQuery, AVAILABLE_SLOTS, DURATION, EXPIRY_DELTA,
-        SLOTS, START_HEIGHT, SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
+        BitcoindStopper, Blockchain, InMemoryBroadcaster, MockedServerQuery, AVAILABLE_SLOTS,
+        DURATION, EXPIRY_DELTA, MOCK_FEE_RATE, SLOTS, START_HEIGHT, SUBSCRIPTION_EXPIRY,
+        SUBSCRIPTION_START,
     };
+    use crate::wallet::FeeRatePolicy;
 
     use teos_common::constants::IRREVOCABLY_RESOLVED;
+    use teos_common::cryptography::get_random_keypair;
     use teos_common::test_utils::get_random_user_id;
 
-    impl PartialEq for Responder {
+    use bitcoin::{Network, TxOut};
+
+    impl<B: TxBroadcaster> PartialEq for Responder<B> {
         fn eq(&self, other: &Self) -> bool {
             *self.trackers.lock().unwrap() == *other.trackers.lock().unwrap()
                 && *self.tx_tracker_map.lock().unwrap() == *other.tx_tracker_map.lock().unwrap()
         }
     }
-    impl Eq for Responder {}
+    impl<B: TxBroadcaster> Eq for Responder<B> {}
 
-    impl Responder {
+    impl<B: TxBroadcaster> Responder<B> {
         pub(crate) fn get_trackers(&self) -> &Mutex<HashMap<UUID, TrackerSummary>> {
             &self.trackers
         }
 
-        pub(crate) fn get_carrier(&self) -> &Mutex<Carrier> {
-            &self.carrier
-        }
-
         pub(crate) fn add_random_tracker(
             &self,
             uuid: UUID,
@@ -670,7 +797,7 @@ mod tests {
                 tracker.user_id,
                 Some(&tracker.dispute_tx.txid()),
             );
-            store_appointment_and_fks_to_db(&self.dbm.lock().unwrap(), uuid, &appointment);
+            store_appointment_and_fks_to_db(&*self.dbm.lock().unwrap(), uuid, &appointment);
             self.dbm
                 .lock()
                 .unwrap()
@@ -682,9 +809,9 @@ mod tests {
     async fn create_responder(
         chain: &mut Blockchain,
         gatekeeper: Arc<Gatekeeper>,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
         query: MockedServerQuery,
-    ) -> (Responder, BitcoindStopper) {
+    ) -> (Responder<Carrier>, BitcoindStopper) {
         let height = if chain.tip().height < IRREVOCABLY_RESOLVED {
             chain.tip().height
         } else {
@@ -703,8 +830,8 @@ mod tests {
     async fn init_responder_with_chain_and_dbm(
         mocked_query: MockedServerQuery,
         chain: &mut Blockchain,
-        dbm: Arc<Mutex<DBM>>,
-    ) -> (Responder, BitcoindStopper) {
+        dbm: Arc<Mutex<dyn Storage>>,
+    ) -> (Responder<Carrier>, BitcoindStopper) {
         let gk = Gatekeeper::new(
             chain.get_block_count(),
             SLOTS,
@@ -715,12 +842,37 @@ mod tests {
         create_responder(chain, Arc::new(gk), dbm, mocked_query).await
     }
 
-    async fn init_responder(mocked_query: MockedServerQuery) -> (Responder, BitcoindStopper) {
+    async fn init_responder(
+        mocked_query: MockedServerQuery,
+    ) -> (Responder<Carrier>, BitcoindStopper) {
         let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
         let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
         init_responder_with_chain_and_dbm(mocked_query, &mut chain, dbm).await
     }
 
+    async fn init_responder_with_broadcaster(
+        broadcaster: InMemoryBroadcaster,
+    ) -> Responder<InMemoryBroadcaster> {
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let gk = Gatekeeper::new(
+            chain.get_block_count(),
+            SLOTS,
+            DURATION,
+            EXPIRY_DELTA,
+            dbm.clone(),
+        );
+        let last_n_blocks = get_last_n_blocks(&mut chain, IRREVOCABLY_RESOLVED as usize).await;
+
+        Responder::new(
+            &last_n_blocks,
+            chain.tip().height,
+            broadcaster,
+            Arc::new(gk),
+            dbm,
+        )
+    }
+
     #[test]
     fn test_confirmation_status_from_db_data() {
         // These are pretty simple tests. The db can only store trackers with a confirmation status
@@ -777,7 +929,7 @@ mod tests {
             // Add the necessary FKs in the database
             let user_id = get_random_user_id();
             let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-            store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+            store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
             let breach = get_random_breach();
             let s = if i % 2 == 0 {
@@ -802,7 +954,7 @@ mod tests {
 
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         let breach = get_random_breach();
         let penalty_txid = breach.penalty_tx.txid();
@@ -849,7 +1001,7 @@ mod tests {
 
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         let breach = get_random_breach();
         let penalty_txid = breach.penalty_tx.txid();
@@ -876,7 +1028,7 @@ mod tests {
 
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         let breach = get_random_breach();
         let penalty_txid = breach.penalty_tx.txid();
@@ -936,6 +1088,41 @@ mod tests {
             .contains_key(&penalty_txid));
     }
 
+    #[tokio::test]
+    async fn test_handle_breach_in_memory_broadcaster() {
+        let user_id = get_random_user_id();
+        let accepted_breach = get_random_breach();
+        let rejected_breach = get_random_breach();
+
+        let mut broadcaster = InMemoryBroadcaster::new(START_HEIGHT as u32);
+        broadcaster.reject(
+            rejected_breach.penalty_tx.txid(),
+            rpc_errors::RPC_VERIFY_REJECTED,
+        );
+        let responder = init_responder_with_broadcaster(broadcaster).await;
+
+        // Accepted penalties make it to the mempool
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
+        let penalty_txid = accepted_breach.penalty_tx.txid();
+        assert_eq!(
+            responder.handle_breach(uuid, accepted_breach, user_id),
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32)
+        );
+        assert!(responder.trackers.lock().unwrap().contains_key(&uuid));
+        assert!(responder.carrier.lock().unwrap().in_mempool(&penalty_txid));
+
+        // Rejected ones are not tracked
+        let uuid = generate_uuid();
+        let penalty_txid = rejected_breach.penalty_tx.txid();
+        assert_eq!(
+            responder.handle_breach(uuid, rejected_breach, user_id),
+            ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_REJECTED)
+        );
+        assert!(!responder.trackers.lock().unwrap().contains_key(&uuid));
+        assert!(!responder.carrier.lock().unwrap().in_mempool(&penalty_txid));
+    }
+
     #[tokio::test]
     async fn test_add_tracker() {
         let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
@@ -944,7 +1131,7 @@ mod tests {
         // Add the necessary FKs in the database
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         let mut breach = get_random_breach();
         responder.add_tracker(
@@ -1068,7 +1255,7 @@ mod tests {
         // Add a new tracker
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         let breach = get_random_breach();
         responder.add_tracker(
@@ -1098,7 +1285,7 @@ mod tests {
         // Store the user and the appointment in the database so we can add the tracker later on (due to FK restrictions)
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
-        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
         // Data should not be there before adding it
         assert_eq!(responder.get_tracker(uuid), None);
@@ -1129,6 +1316,42 @@ mod tests {
         assert_eq!(responder.get_tracker(uuid), None);
     }
 
+    #[tokio::test]
+    async fn test_remove_tracker() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
+
+        let breach = get_random_breach();
+        responder.add_tracker(
+            uuid,
+            breach,
+            user_id,
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+        assert!(responder.has_tracker(uuid));
+
+        // The tracker is gone both from memory and the database, but the appointment is kept
+        responder.remove_tracker(uuid);
+        assert!(!responder.has_tracker(uuid));
+        assert!(responder.dbm.lock().unwrap().load_tracker(uuid).is_none());
+        assert_eq!(
+            responder
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(uuid)
+                .unwrap(),
+            appointment
+        );
+
+        // Removing a tracker that is not there is a no-op
+        responder.remove_tracker(uuid);
+        assert!(!responder.has_tracker(uuid));
+    }
+
     #[tokio::test]
     async fn test_check_confirmations() {
         let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
@@ -1146,7 +1369,7 @@ mod tests {
             let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
             let breach = get_random_breach();
 
-            store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+            store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
 
             if i % 4 == 0 {
                 responder.add_tracker(
@@ -1527,6 +1750,164 @@ mod tests {
         assert!(accepted.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_rebroadcast_reorged_package() {
+        // Reorged disputes are rebroadcast alongside their penalties, and the tracker outcome depends on the result of each of them
+        let rejected_dispute = get_random_tracker(
+            get_random_user_id(),
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+        let rejected_penalty = get_random_tracker(
+            get_random_user_id(),
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+        let accepted = get_random_tracker(
+            get_random_user_id(),
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+
+        let mut broadcaster = InMemoryBroadcaster::new(START_HEIGHT as u32);
+        broadcaster.reject(
+            rejected_dispute.dispute_tx.txid(),
+            rpc_errors::RPC_VERIFY_REJECTED,
+        );
+        broadcaster.reject(
+            rejected_penalty.penalty_tx.txid(),
+            rpc_errors::RPC_VERIFY_ERROR,
+        );
+        let responder = init_responder_with_broadcaster(broadcaster).await;
+
+        let mut txs = HashMap::new();
+        let mut uuids = Vec::new();
+        for tracker in [rejected_dispute, rejected_penalty, accepted.clone()] {
+            let uuid = generate_uuid();
+            responder.add_dummy_tracker(uuid, &tracker);
+            responder
+                .trackers
+                .lock()
+                .unwrap()
+                .get_mut(&uuid)
+                .unwrap()
+                .status = ConfirmationStatus::ReorgedOut;
+            txs.insert(uuid, (tracker.penalty_tx, Some(tracker.dispute_tx)));
+            uuids.push(uuid);
+        }
+
+        let (accepted_trackers, rejected_trackers) = responder.rebroadcast(txs);
+        assert_eq!(rejected_trackers, HashSet::from_iter([uuids[0], uuids[1]]));
+        assert_eq!(
+            accepted_trackers,
+            HashMap::from_iter([(
+                uuids[2],
+                ConfirmationStatus::InMempoolSince(START_HEIGHT as u32)
+            )])
+        );
+
+        // Both transactions of the accepted package are in the mempool, disputes are sent first
+        let carrier = responder.carrier.lock().unwrap();
+        assert!(carrier.in_mempool(&accepted.dispute_tx.txid()));
+        assert!(carrier.in_mempool(&accepted.penalty_tx.txid()));
+        let sent = &carrier.sent;
+        assert!(
+            sent.iter()
+                .position(|txid| *txid == accepted.dispute_tx.txid())
+                < sent
+                    .iter()
+                    .position(|txid| *txid == accepted.penalty_tx.txid())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bump_fee() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+        let wallet = Wallet::new(
+            get_random_keypair().0,
+            Network::Regtest,
+            FeeRatePolicy::new(1, 40, 50),
+        );
+        let wallet_script = wallet.address().script_pubkey();
+        let responder = responder.with_wallet(wallet);
+
+        // Add a tracker whose penalty pays to the tower wallet
+        let (uuid, appointment) = generate_dummy_appointment_with_user(get_random_user_id(), None);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
+        let mut breach = get_random_breach();
+        breach.penalty_tx.output.push(TxOut {
+            value: 100_000,
+            script_pubkey: wallet_script,
+        });
+        let penalty_tx = breach.penalty_tx.clone();
+        responder.add_tracker(
+            uuid,
+            breach,
+            appointment.user_id,
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+
+        // The first bump follows the fee estimation and consecutive bumps escalate it, up to the maximum
+        for expected_fee_rates in [vec![MOCK_FEE_RATE], vec![20, 30], vec![20, 30, 40]] {
+            responder.rebroadcast(HashMap::from_iter([(uuid, (penalty_tx.clone(), None))]));
+
+            let fee_bumps = responder.dbm.lock().unwrap().load_fee_bumps(uuid);
+            assert_eq!(
+                fee_bumps.iter().map(|b| b.fee_rate).collect::<Vec<_>>(),
+                expected_fee_rates
+            );
+            let child_tx = &fee_bumps.last().unwrap().child_tx;
+            assert_eq!(child_tx.input[0].previous_output.txid, penalty_tx.txid());
+        }
+
+        // Once the maximum is reached no more bumps are performed
+        responder.rebroadcast(HashMap::from_iter([(uuid, (penalty_tx.clone(), None))]));
+        assert_eq!(responder.dbm.lock().unwrap().load_fee_bumps(uuid).len(), 3);
+
+        // Penalties without outputs the wallet can spend cannot be bumped
+        let (uuid, appointment) = generate_dummy_appointment_with_user(get_random_user_id(), None);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
+        let breach = get_random_breach();
+        let penalty_tx = breach.penalty_tx.clone();
+        responder.add_tracker(
+            uuid,
+            breach,
+            appointment.user_id,
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+        let (accepted, _) = responder.rebroadcast(HashMap::from_iter([(uuid, (penalty_tx, None))]));
+        assert!(accepted.contains_key(&uuid));
+        assert!(responder
+            .dbm
+            .lock()
+            .unwrap()
+            .load_fee_bumps(uuid)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bump_fee_without_wallet() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        let (uuid, appointment) = generate_dummy_appointment_with_user(get_random_user_id(), None);
+        store_appointment_and_fks_to_db(&*responder.dbm.lock().unwrap(), uuid, &appointment);
+        let breach = get_random_breach();
+        let penalty_tx = breach.penalty_tx.clone();
+        responder.add_tracker(
+            uuid,
+            breach,
+            appointment.user_id,
+            ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+        );
+
+        // The penalty is rebroadcast but its fee is not bumped
+        let (accepted, _) = responder.rebroadcast(HashMap::from_iter([(uuid, (penalty_tx, None))]));
+        assert!(accepted.contains_key(&uuid));
+        assert!(responder
+            .dbm
+            .lock()
+            .unwrap()
+            .load_fee_bumps(uuid)
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_delete_trackers_from_memory() {
         let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
@@ -1705,6 +2086,7 @@ mod tests {
                     .unwrap()
                     .load_user(id)
                     .unwrap()
+                    .unwrap()
                     .available_slots,
                 info.available_slots
             )
@@ -1736,7 +2118,7 @@ mod tests {
         for _ in 2..23 {
             let user_id = get_random_user_id();
 
-            responder.gatekeeper.add_update_user(user_id).unwrap();
+            responder.gatekeeper.register(user_id, "").unwrap();
             users.push(user_id);
         }
 
@@ -1814,7 +2196,7 @@ mod tests {
         let standalone_user_id = get_random_user_id();
         responder
             .gatekeeper
-            .add_update_user(standalone_user_id)
+            .register(standalone_user_id, "")
             .unwrap();
 
         let mut transactions = Vec::new();
@@ -2,6 +2,13 @@
 // Ported from https://github.com/bitcoin/bitcoin/blob/0.18/src/rpc/protocol.h
 // TODO: Check if we can get rid of this whole module once `bitcoincore-rpc` is fully integrated.
 
+// Standard JSON-RPC 2.0 errors
+pub const RPC_INVALID_REQUEST: i32 = -32600;
+pub const RPC_METHOD_NOT_FOUND: i32 = -32601;
+pub const RPC_INVALID_PARAMS: i32 = -32602;
+pub const RPC_INTERNAL_ERROR: i32 = -32603;
+pub const RPC_PARSE_ERROR: i32 = -32700;
+
 // General application defined errors
 pub const RPC_MISC_ERROR: i32 = -1; // std::exception thrown in command handling
 pub const RPC_TYPE_ERROR: i32 = -3; // Unexpected type was passed as parameter
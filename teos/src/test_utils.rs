@@ -8,11 +8,15 @@
 */
 
 use rand::Rng;
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 
 use jsonrpc_http_server::jsonrpc_core::error::ErrorCode as JsonRpcErrorCode;
-use jsonrpc_http_server::jsonrpc_core::{Error as JsonRpcError, IoHandler, Params, Value};
+use jsonrpc_http_server::jsonrpc_core::{
+    Compatibility, Error as JsonRpcError, IoHandler, Params, Value,
+};
 use jsonrpc_http_server::{CloseHandle, Server, ServerBuilder};
 
 use bitcoincore_rpc::{Auth, Client as BitcoindClient};
@@ -25,40 +29,61 @@ use bitcoin::hash_types::BlockHash;
 use bitcoin::hash_types::Txid;
 use bitcoin::hashes::Hash;
 use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
 use bitcoin::util::hash::bitcoin_merkle_root;
 use bitcoin::util::uint::Uint256;
-use bitcoin::Witness;
+use bitcoin::{Address, Witness};
 use lightning_block_sync::poll::{
     ChainPoller, Poll, Validate, ValidatedBlock, ValidatedBlockHeader,
 };
 use lightning_block_sync::{
     AsyncBlockSourceResult, BlockHeaderData, BlockSource, BlockSourceError, UnboundedCache,
 };
-
-use teos_common::constants::IRREVOCABLY_RESOLVED;
-use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::reply::{with_status, Response};
+use warp::{Filter, Reply};
+
+use teos_common::constants::{ENCRYPTED_BLOB_MAX_SIZE, IRREVOCABLY_RESOLVED};
+use teos_common::cryptography::{self, get_random_bytes, get_random_keypair};
+use teos_common::protos as common_msgs;
+use teos_common::receipts::registration_message;
 use teos_common::test_utils::{generate_random_appointment, get_random_user_id, TXID_HEX, TX_HEX};
-use teos_common::UserId;
+use teos_common::{TowerId, UserId};
 
+use crate::anti_spam::RegistrationGate;
 use crate::api::internal::InternalAPI;
-use crate::carrier::Carrier;
-use crate::dbm::DBM;
+use crate::backup::Backup;
+use crate::bitcoin_cli::RpcEndpoint;
+use crate::carrier::{Carrier, TxBroadcaster};
+use crate::dbm::{Storage, DBM};
 use crate::extended_appointment::{ExtendedAppointment, UUID};
-use crate::gatekeeper::{Gatekeeper, UserInfo};
+use crate::gatekeeper::{Gatekeeper, SubscriptionTier, UserInfo};
+use crate::payments::{unix_time, Invoice, PaymentError, PaymentVerifier};
 use crate::protos as msgs;
 use crate::responder::{ConfirmationStatus, Responder, TransactionTracker};
 use crate::rpc_errors;
+use crate::wallet::Utxo;
 use crate::watcher::{Breach, Watcher};
 
 pub(crate) const SLOTS: u32 = 21;
 pub(crate) const DURATION: u32 = 500;
 pub(crate) const EXPIRY_DELTA: u32 = 42;
 pub(crate) const START_HEIGHT: usize = 100;
+pub(crate) const MIN_TO_SELF_DELAY: u16 = 20;
 
 pub(crate) const AVAILABLE_SLOTS: u32 = 21;
 pub(crate) const SUBSCRIPTION_START: u32 = START_HEIGHT as u32;
 pub(crate) const SUBSCRIPTION_EXPIRY: u32 = SUBSCRIPTION_START + 42;
 
+// Subscription price (msat) and invoice expiry (secs) of paid subscriptions
+pub(crate) const PRICE_MSAT: u64 = 1000;
+pub(crate) const INVOICE_EXPIRY: u32 = 3600;
+
+// Fee rate (sat/vB) and utxo value (sats) reported by the bitcoind mock
+pub(crate) const MOCK_FEE_RATE: u64 = 20;
+pub(crate) const MOCK_UTXO_VALUE: u64 = 1_000_000;
+
 #[derive(Clone, Default, Debug)]
 pub(crate) struct Blockchain {
     pub blocks: Vec<Block>,
@@ -342,7 +367,7 @@ pub(crate) fn get_random_tracker(
 }
 
 pub(crate) fn store_appointment_and_fks_to_db(
-    dbm: &DBM,
+    dbm: &dyn Storage,
     uuid: UUID,
     appointment: &ExtendedAppointment,
 ) {
@@ -391,10 +416,194 @@ pub(crate) fn create_carrier(query: MockedServerQuery, height: u32) -> (Carrier,
     )
 }
 
+/// An in-memory [TxBroadcaster]. Transactions are added to its mempool unless they have been flagged to be rejected.
+#[derive(Debug, Default)]
+pub(crate) struct InMemoryBroadcaster {
+    pub block_height: u32,
+    pub mempool: HashMap<Txid, Transaction>,
+    pub rejected: HashMap<Txid, i32>,
+    pub fee_rate: Option<u64>,
+    pub utxos: Vec<Utxo>,
+    /// The transactions sent through the broadcaster, in order.
+    pub sent: Vec<Txid>,
+}
+
+impl InMemoryBroadcaster {
+    pub fn new(block_height: u32) -> Self {
+        Self {
+            block_height,
+            ..Default::default()
+        }
+    }
+
+    /// Flags a transaction to be rejected with the given error code when sent.
+    pub fn reject(&mut self, txid: Txid, error: i32) {
+        self.rejected.insert(txid, error);
+    }
+}
+
+impl TxBroadcaster for InMemoryBroadcaster {
+    fn block_height(&self) -> u32 {
+        self.block_height
+    }
+
+    fn update_height(&mut self, height: u32) {
+        self.block_height = height
+    }
+
+    fn clear_receipts(&mut self) {}
+
+    fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
+        self.sent.push(tx.txid());
+        if let Some(error) = self.rejected.get(&tx.txid()) {
+            ConfirmationStatus::Rejected(*error)
+        } else {
+            self.mempool.insert(tx.txid(), tx.clone());
+            ConfirmationStatus::InMempoolSince(self.block_height)
+        }
+    }
+
+    fn send_package(&mut self, txs: &[Transaction]) -> Vec<ConfirmationStatus> {
+        txs.iter().map(|tx| self.send_transaction(tx)).collect()
+    }
+
+    fn in_mempool(&self, txid: &Txid) -> bool {
+        self.mempool.contains_key(txid)
+    }
+
+    fn get_mempool_txids(&self) -> Vec<Txid> {
+        self.mempool.keys().cloned().collect()
+    }
+
+    fn get_mempool_transaction(&self, txid: &Txid) -> Option<Transaction> {
+        self.mempool.get(txid).cloned()
+    }
+
+    fn estimate_fee_rate(&self, _: u16) -> Option<u64> {
+        self.fee_rate
+    }
+
+    fn get_utxos(&self, _: &Address) -> Vec<Utxo> {
+        self.utxos.clone()
+    }
+}
+
+pub(crate) fn generate_register_request(
+    tower_id: &TowerId,
+    user_sk: &SecretKey,
+    tier: &str,
+    proof: Option<common_msgs::RegistrationProof>,
+) -> common_msgs::RegisterRequest {
+    let message = registration_message(tier, tower_id);
+    common_msgs::RegisterRequest {
+        user_id: UserId(PublicKey::from_secret_key(&Secp256k1::new(), user_sk)).to_vec(),
+        tier: tier.to_owned(),
+        proof,
+        signature: cryptography::sign(message.as_bytes(), user_sk).unwrap(),
+    }
+}
+
+pub(crate) fn get_random_invoice() -> Invoice {
+    Invoice {
+        bolt11: format!("lnbcrt{}", hex::encode(get_random_bytes(32))),
+        payment_hash: get_random_bytes(32).try_into().unwrap(),
+        amount_msat: PRICE_MSAT,
+        expires_at: unix_time() + INVOICE_EXPIRY as u64,
+    }
+}
+
+/// A local [PaymentVerifier]. Invoices are kept in memory and settled on demand.
+#[derive(Debug, Default)]
+pub(crate) struct MockPaymentVerifier {
+    /// The invoices issued by the verifier, and whether they have been settled.
+    pub invoices: Mutex<HashMap<[u8; 32], (Invoice, bool)>>,
+    /// Whether the verifier can be reached.
+    pub unreachable: Mutex<bool>,
+    /// Whether payment checks are stalled (waiting on the condvar until they are resumed), and how many are waiting.
+    pub stalled: (Mutex<(bool, usize)>, Condvar),
+}
+
+impl MockPaymentVerifier {
+    /// Flags the invoice with the given payment hash as settled.
+    pub fn settle(&self, payment_hash: &[u8; 32]) {
+        self.invoices
+            .lock()
+            .unwrap()
+            .get_mut(payment_hash)
+            .unwrap()
+            .1 = true;
+    }
+
+    /// Sets whether the verifier can be reached.
+    pub fn set_unreachable(&self, unreachable: bool) {
+        *self.unreachable.lock().unwrap() = unreachable;
+    }
+
+    /// Sets whether payment checks are stalled. Stalled checks do not return until they are resumed.
+    pub fn set_stalled(&self, stalled: bool) {
+        let (lock, condvar) = &self.stalled;
+        lock.lock().unwrap().0 = stalled;
+        condvar.notify_all();
+    }
+
+    /// Returns the number of payment checks waiting to be resumed.
+    pub fn stalled_checks(&self) -> usize {
+        self.stalled.0.lock().unwrap().1
+    }
+
+    fn check_reachable(&self) -> Result<(), PaymentError> {
+        if *self.unreachable.lock().unwrap() {
+            Err(PaymentError::Unreachable("Connection refused".to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl PaymentVerifier for MockPaymentVerifier {
+    fn create_invoice(
+        &self,
+        amount_msat: u64,
+        _: &str,
+        expiry: u32,
+    ) -> Result<Invoice, PaymentError> {
+        self.check_reachable()?;
+        let invoice = Invoice {
+            amount_msat,
+            expires_at: unix_time() + expiry as u64,
+            ..get_random_invoice()
+        };
+        self.invoices
+            .lock()
+            .unwrap()
+            .insert(invoice.payment_hash, (invoice.clone(), false));
+        Ok(invoice)
+    }
+
+    fn is_settled(&self, payment_hash: &[u8; 32]) -> Result<bool, PaymentError> {
+        let (lock, condvar) = &self.stalled;
+        let mut stalled = lock.lock().unwrap();
+        stalled.1 += 1;
+        while stalled.0 {
+            stalled = condvar.wait(stalled).unwrap();
+        }
+        stalled.1 -= 1;
+        drop(stalled);
+
+        self.check_reachable()?;
+        self.invoices
+            .lock()
+            .unwrap()
+            .get(payment_hash)
+            .map(|(_, settled)| *settled)
+            .ok_or_else(|| PaymentError::Rejected("Unknown invoice".to_owned()))
+    }
+}
+
 pub(crate) async fn create_responder(
     chain: &mut Blockchain,
     gatekeeper: Arc<Gatekeeper>,
-    dbm: Arc<Mutex<DBM>>,
+    dbm: Arc<Mutex<dyn Storage>>,
     server_url: &str,
 ) -> Responder {
     let height = chain.tip().height;
@@ -407,7 +616,7 @@ pub(crate) async fn create_responder(
     let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
     let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, height);
 
-    Responder::new(&last_n_blocks, height, carrier, gatekeeper, dbm)
+    Responder::new(&last_n_blocks, height, Box::new(carrier), gatekeeper, dbm)
 }
 
 pub(crate) async fn create_watcher(
@@ -415,7 +624,7 @@ pub(crate) async fn create_watcher(
     responder: Arc<Responder>,
     gatekeeper: Arc<Gatekeeper>,
     bitcoind_mock: BitcoindMock,
-    dbm: Arc<Mutex<DBM>>,
+    dbm: Arc<Mutex<dyn Storage>>,
 ) -> (Watcher, BitcoindStopper) {
     let last_n_blocks = get_last_n_blocks(chain, 6).await;
 
@@ -428,6 +637,7 @@ pub(crate) async fn create_watcher(
             responder,
             &last_n_blocks,
             chain.get_block_count(),
+            MIN_TO_SELF_DELAY,
             tower_sk,
             tower_id,
             dbm,
@@ -440,6 +650,11 @@ pub(crate) struct ApiConfig {
     slots: u32,
     duration: u32,
     bitcoind_reachable: bool,
+    payment_verifier: Option<Arc<MockPaymentVerifier>>,
+    tiers: Option<Vec<SubscriptionTier>>,
+    rate_limit: Option<(u32, u32)>,
+    registration_gate: Option<Arc<dyn RegistrationGate>>,
+    backup: Option<Arc<Backup>>,
 }
 
 impl ApiConfig {
@@ -448,6 +663,11 @@ impl ApiConfig {
             slots,
             duration,
             bitcoind_reachable: true,
+            payment_verifier: None,
+            tiers: None,
+            rate_limit: None,
+            registration_gate: None,
+            backup: None,
         }
     }
 
@@ -455,15 +675,36 @@ impl ApiConfig {
         self.bitcoind_reachable = false;
         self.clone()
     }
+
+    pub fn with_payments(&mut self, verifier: Arc<MockPaymentVerifier>) -> Self {
+        self.payment_verifier = Some(verifier);
+        self.clone()
+    }
+
+    pub fn with_tiers(&mut self, tiers: Vec<SubscriptionTier>) -> Self {
+        self.tiers = Some(tiers);
+        self.clone()
+    }
+
+    pub fn with_rate_limit(&mut self, rate: u32, burst: u32) -> Self {
+        self.rate_limit = Some((rate, burst));
+        self.clone()
+    }
+
+    pub fn with_registration_gate(&mut self, registration_gate: Arc<dyn RegistrationGate>) -> Self {
+        self.registration_gate = Some(registration_gate);
+        self.clone()
+    }
+
+    pub fn with_backup(&mut self, backup: Backup) -> Self {
+        self.backup = Some(Arc::new(backup));
+        self.clone()
+    }
 }
 
 impl Default for ApiConfig {
     fn default() -> Self {
-        Self {
-            slots: SLOTS,
-            duration: DURATION,
-            bitcoind_reachable: true,
-        }
+        Self::new(SLOTS, DURATION)
     }
 }
 
@@ -474,13 +715,31 @@ pub(crate) async fn create_api_with_config(
     let mut chain = Blockchain::default().with_height(START_HEIGHT);
 
     let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
-    let gk = Arc::new(Gatekeeper::new(
+    let mut gk = Gatekeeper::new(
         chain.get_block_count(),
         api_config.slots,
         api_config.duration,
         EXPIRY_DELTA,
         dbm.clone(),
-    ));
+    );
+    if let Some(verifier) = api_config.payment_verifier {
+        gk = gk
+            .with_tiers(vec![SubscriptionTier::new(
+                String::new(),
+                api_config.slots,
+                api_config.duration,
+                PRICE_MSAT,
+                ENCRYPTED_BLOB_MAX_SIZE,
+            )])
+            .with_payments(verifier, INVOICE_EXPIRY);
+    }
+    if let Some(tiers) = api_config.tiers {
+        gk = gk.with_tiers(tiers);
+    }
+    if let Some((rate, burst)) = api_config.rate_limit {
+        gk = gk.with_rate_limit(rate, burst);
+    }
+    let gk = Arc::new(gk);
     let responder =
         create_responder(&mut chain, gk.clone(), dbm.clone(), bitcoind_mock.url()).await;
     let (watcher, stopper) = create_watcher(
@@ -494,15 +753,19 @@ pub(crate) async fn create_api_with_config(
 
     let bitcoind_reachable = Arc::new((Mutex::new(api_config.bitcoind_reachable), Condvar::new()));
     let (shutdown_trigger, _) = triggered::trigger();
-    (
-        Arc::new(InternalAPI::new(
-            Arc::new(watcher),
-            vec![msgs::NetworkAddress::from_ipv4("address".to_string(), 21)],
-            bitcoind_reachable,
-            shutdown_trigger,
-        )),
-        stopper,
-    )
+    let mut internal_api = InternalAPI::new(
+        Arc::new(watcher),
+        vec![msgs::NetworkAddress::from_ipv4("address".to_string(), 21)],
+        bitcoind_reachable,
+        shutdown_trigger,
+    );
+    if let Some(registration_gate) = api_config.registration_gate {
+        internal_api = internal_api.with_registration_gate(registration_gate);
+    }
+    if let Some(backup) = api_config.backup {
+        internal_api = internal_api.with_backup(backup);
+    }
+    (Arc::new(internal_api), stopper)
 }
 
 pub(crate) async fn create_api() -> (Arc<InternalAPI>, BitcoindStopper) {
@@ -540,27 +803,44 @@ pub(crate) struct BitcoindMock {
 pub(crate) struct MockOptions {
     error_code: Option<i64>,
     in_mempool: bool,
+    no_package_relay: bool,
+    best_block: Option<(BlockHash, u32)>,
 }
 
 impl MockOptions {
     pub fn with_error(error_code: i64) -> Self {
         Self {
             error_code: Some(error_code),
-            in_mempool: false,
+            ..Default::default()
         }
     }
 
     pub fn in_mempool() -> Self {
         Self {
-            error_code: None,
             in_mempool: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn no_package_relay() -> Self {
+        Self {
+            no_package_relay: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_best_block(hash: BlockHash, height: u32) -> Self {
+        Self {
+            best_block: Some((hash, height)),
+            ..Default::default()
         }
     }
 }
 
 impl BitcoindMock {
     pub fn new(options: MockOptions) -> Self {
-        let mut io = IoHandler::default();
+        // lightning-block-sync's RPC client does not set the JSON-RPC version, as bitcoind does not require it
+        let mut io = IoHandler::with_compatibility(Compatibility::Both);
 
         if let Some(error) = options.error_code {
             io.add_sync_method("error", move |_params: Params| {
@@ -568,10 +848,25 @@ impl BitcoindMock {
             });
             io.add_alias("sendrawtransaction", "error");
             io.add_alias("getrawtransaction", "error");
+            io.add_alias("getrawmempool", "error");
+            io.add_alias("submitpackage", "error");
         } else {
             BitcoindMock::add_sendrawtransaction(&mut io);
+            if !options.no_package_relay {
+                BitcoindMock::add_submitpackage(&mut io);
+            }
             BitcoindMock::add_getrawtransaction(&mut io, options.in_mempool);
+            BitcoindMock::add_getrawmempool(&mut io, options.in_mempool);
+            BitcoindMock::add_estimatesmartfee(&mut io);
+            BitcoindMock::add_listunspent(&mut io);
         }
+        // The mock runs on regtest, and its tip defaults to the genesis block
+        BitcoindMock::add_getblockchaininfo(
+            &mut io,
+            options
+                .best_block
+                .unwrap_or((genesis_block(Network::Regtest).block_hash(), 0)),
+        );
 
         let server = ServerBuilder::new(io)
             .threads(3)
@@ -591,6 +886,27 @@ impl BitcoindMock {
         });
     }
 
+    fn add_submitpackage(io: &mut IoHandler) {
+        io.add_sync_method("submitpackage", |params: Params| {
+            // Every transaction in the package is accepted
+            let raw_txs: Vec<Vec<String>> = params.parse()?;
+            let mut tx_results = serde_json::Map::new();
+            for raw_tx in raw_txs[0].iter() {
+                let tx: Transaction =
+                    bitcoin::consensus::deserialize(&hex::decode(raw_tx).unwrap()).unwrap();
+                tx_results.insert(
+                    tx.wtxid().to_string(),
+                    serde_json::json!({"txid": tx.txid(), "vsize": 0, "fees": {"base": 0}}),
+                );
+            }
+            Ok(serde_json::json!({
+                "package_msg": "success",
+                "tx-results": tx_results,
+                "replaced-transactions": []
+            }))
+        });
+    }
+
     fn add_getrawtransaction(io: &mut IoHandler, in_mempool: bool) {
         io.add_sync_method("getrawtransaction", move |_params: Params|  {
             if !in_mempool {
@@ -614,9 +930,69 @@ impl BitcoindMock {
         })
     }
 
+    fn add_getrawmempool(io: &mut IoHandler, in_mempool: bool) {
+        io.add_sync_method("getrawmempool", move |_params: Params| {
+            if in_mempool {
+                // The mempool contains the transaction returned by getrawtransaction
+                let tx: Transaction =
+                    bitcoin::consensus::deserialize(&hex::decode(TX_HEX).unwrap()).unwrap();
+                Ok(serde_json::json!([tx.txid()]))
+            } else {
+                Ok(serde_json::json!([]))
+            }
+        })
+    }
+
+    fn add_estimatesmartfee(io: &mut IoHandler) {
+        io.add_sync_method("estimatesmartfee", |_params: Params| {
+            // Fee rates are reported in BTC/kvB
+            Ok(serde_json::json!({
+                "feerate": MOCK_FEE_RATE as f64 / 100_000.0,
+                "blocks": 6
+            }))
+        });
+    }
+
+    fn add_listunspent(io: &mut IoHandler) {
+        io.add_sync_method("listunspent", |_params: Params| {
+            Ok(serde_json::json!([{
+                "txid": TXID_HEX,
+                "vout": 0,
+                "scriptPubKey": format!("0014{}", "00".repeat(20)),
+                "amount": MOCK_UTXO_VALUE as f64 / 100_000_000.0,
+                "confirmations": 6,
+                "spendable": false,
+                "solvable": false,
+                "safe": true
+            }]))
+        });
+    }
+
+    fn add_getblockchaininfo(io: &mut IoHandler, best_block: (BlockHash, u32)) {
+        io.add_sync_method("getblockchaininfo", move |_params: Params| {
+            Ok(serde_json::json!({
+                "chain": "regtest",
+                "blocks": best_block.1,
+                "bestblockhash": best_block.0,
+            }))
+        });
+        io.add_sync_method("getblockcount", move |_params: Params| {
+            Ok(serde_json::json!(best_block.1))
+        });
+    }
+
     pub fn url(&self) -> &str {
         &self.url
     }
+
+    pub fn endpoint(&self) -> RpcEndpoint {
+        let (host, port) = self
+            .url
+            .trim_start_matches("http://")
+            .rsplit_once(':')
+            .unwrap();
+        RpcEndpoint::new(host, port.parse().unwrap(), "user", "password")
+    }
 }
 
 pub(crate) fn start_server(server: Server) {
@@ -624,3 +1000,209 @@ pub(crate) fn start_server(server: Server) {
         server.wait();
     });
 }
+
+/// A mocked Esplora API. Blocks are served from a [Blockchain], while transactions are pushed to an in-memory mempool.
+pub(crate) struct EsploraMock {
+    pub url: String,
+    pub chain: Arc<Mutex<Blockchain>>,
+    pub mempool: Arc<Mutex<HashMap<Txid, Transaction>>>,
+}
+
+impl EsploraMock {
+    pub fn new(chain: Blockchain) -> Self {
+        let chain = Arc::new(Mutex::new(chain));
+        let mempool = Arc::new(Mutex::new(HashMap::new()));
+
+        let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+        let (c, m) = (chain.clone(), mempool.clone());
+        thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async move {
+                let (addr, server) =
+                    warp::serve(EsploraMock::routes(c, m)).bind_ephemeral(([127, 0, 0, 1], 0));
+                addr_tx.send(addr).unwrap();
+                server.await
+            })
+        });
+
+        Self {
+            url: format!("http://{}", addr_rx.recv().unwrap()),
+            chain,
+            mempool,
+        }
+    }
+
+    fn routes(
+        chain: Arc<Mutex<Blockchain>>,
+        mempool: Arc<Mutex<HashMap<Txid, Transaction>>>,
+    ) -> BoxedFilter<(Response,)> {
+        let not_found = || with_status("Not found", StatusCode::NOT_FOUND).into_response();
+        let find_block = |chain: &Blockchain, hash: &str| {
+            chain
+                .blocks
+                .iter()
+                .enumerate()
+                .find(|(_, block)| block.block_hash().to_string() == hash)
+                .map(|(height, block)| (height, block.clone()))
+        };
+
+        let c = chain.clone();
+        let block_height = warp::path!("block-height" / usize).map(move |height: usize| {
+            match c.lock().unwrap().blocks.get(height) {
+                Some(block) => block.block_hash().to_string().into_response(),
+                None => not_found(),
+            }
+        });
+
+        let c = chain.clone();
+        let block = warp::path!("block" / String).map(move |hash: String| {
+            match find_block(&c.lock().unwrap(), &hash) {
+                Some((height, block)) => warp::reply::json(&serde_json::json!({
+                    "id": hash,
+                    "height": height,
+                    "version": block.header.version,
+                    "timestamp": block.header.time,
+                    "bits": block.header.bits,
+                    "nonce": block.header.nonce,
+                    "merkle_root": block.header.merkle_root,
+                    "previousblockhash": if height > 0 { Some(block.header.prev_blockhash) } else { None },
+                    "tx_count": block.txdata.len(),
+                }))
+                .into_response(),
+                None => not_found(),
+            }
+        });
+
+        let c = chain.clone();
+        let raw_block = warp::path!("block" / String / "raw").map(move |hash: String| {
+            match find_block(&c.lock().unwrap(), &hash) {
+                Some((_, block)) => bitcoin::consensus::serialize(&block).into_response(),
+                None => not_found(),
+            }
+        });
+
+        let c = chain.clone();
+        let tip = warp::path!("blocks" / "tip" / "hash").map(move || {
+            c.lock()
+                .unwrap()
+                .blocks
+                .last()
+                .unwrap()
+                .block_hash()
+                .to_string()
+                .into_response()
+        });
+
+        let m = mempool.clone();
+        let send_tx = warp::post()
+            .and(warp::path!("tx"))
+            .and(warp::body::bytes())
+            .map(move |body: warp::hyper::body::Bytes| {
+                match hex::decode(&body)
+                    .ok()
+                    .and_then(|raw_tx| bitcoin::consensus::deserialize::<Transaction>(&raw_tx).ok())
+                {
+                    Some(tx) => {
+                        let txid = tx.txid();
+                        m.lock().unwrap().insert(txid, tx);
+                        txid.to_string().into_response()
+                    }
+                    None => with_status(
+                        r#"sendrawtransaction RPC error: {"code":-22,"message":"TX decode failed"}"#,
+                        StatusCode::BAD_REQUEST,
+                    )
+                    .into_response(),
+                }
+            });
+
+        let (c, m) = (chain, mempool.clone());
+        let tx_status = warp::path!("tx" / Txid / "status").map(move |txid: Txid| {
+            if m.lock().unwrap().contains_key(&txid) {
+                return warp::reply::json(&serde_json::json!({ "confirmed": false }))
+                    .into_response();
+            }
+            let chain = c.lock().unwrap();
+            match chain
+                .blocks
+                .iter()
+                .position(|block| block.txdata.iter().any(|tx| tx.txid() == txid))
+            {
+                Some(height) => warp::reply::json(&serde_json::json!({
+                    "confirmed": true,
+                    "block_height": height,
+                    "block_hash": chain.blocks[height].block_hash(),
+                }))
+                .into_response(),
+                None => not_found(),
+            }
+        });
+
+        let m = mempool.clone();
+        let tx_hex = warp::path!("tx" / Txid / "hex").map(move |txid: Txid| {
+            match m.lock().unwrap().get(&txid) {
+                Some(tx) => bitcoin::consensus::encode::serialize_hex(tx).into_response(),
+                None => not_found(),
+            }
+        });
+
+        let m = mempool;
+        let mempool_txids = warp::path!("mempool" / "txids").map(move || {
+            warp::reply::json(&m.lock().unwrap().keys().collect::<Vec<&Txid>>()).into_response()
+        });
+
+        // Fee rates are reported in sat/vB
+        let fee_estimates = warp::path!("fee-estimates").map(|| {
+            warp::reply::json(&serde_json::json!({
+                "1": 87.882,
+                "3": 42.4,
+                "6": MOCK_FEE_RATE as f64,
+                "144": 1.027
+            }))
+            .into_response()
+        });
+
+        let utxos = warp::path!("address" / String / "utxo").map(|_address: String| {
+            warp::reply::json(&serde_json::json!([
+                {
+                    "txid": TXID_HEX,
+                    "vout": 0,
+                    "value": MOCK_UTXO_VALUE,
+                    "status": {"confirmed": true, "block_height": START_HEIGHT}
+                },
+                {
+                    "txid": TXID_HEX,
+                    "vout": 1,
+                    "value": MOCK_UTXO_VALUE,
+                    "status": {"confirmed": false}
+                }
+            ]))
+            .into_response()
+        });
+
+        let get = warp::get().and(
+            block_height
+                .or(block)
+                .unify()
+                .or(raw_block)
+                .unify()
+                .or(tip)
+                .unify()
+                .or(tx_status)
+                .unify()
+                .or(tx_hex)
+                .unify()
+                .or(mempool_txids)
+                .unify()
+                .or(fee_estimates)
+                .unify()
+                .or(utxos)
+                .unify(),
+        );
+
+        get.or(send_tx).unify().boxed()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
@@ -76,6 +76,78 @@ impl Value for Transaction {
     }
 }
 
+/// A block whose data can be loaded into a [TxIndex].
+pub trait IndexableBlock {
+    /// Gets the header of the block.
+    fn header(&self) -> BlockHeader;
+
+    /// Gets the ids of the transactions included in the block.
+    fn txids(&self) -> Vec<Txid>;
+
+    /// Gets the transactions included in the block, if available.
+    fn txdata(&self) -> Option<&[Transaction]>;
+}
+
+impl IndexableBlock for ValidatedBlock {
+    fn header(&self) -> BlockHeader {
+        self.header
+    }
+
+    fn txids(&self) -> Vec<Txid> {
+        self.txdata.iter().map(|tx| tx.txid()).collect()
+    }
+
+    fn txdata(&self) -> Option<&[Transaction]> {
+        Some(&self.txdata)
+    }
+}
+
+/// A summary of a block, persisted by the tower so its indexes can be rebuilt on bootstrap without pulling the block
+/// from the backend (which may have pruned it).
+///
+/// Transactions are only kept for the most recent blocks, the ones the [Watcher](crate::watcher::Watcher)'s locator cache
+/// is built from. Transaction ids are enough for the rest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockSummary {
+    /// The header of the block.
+    pub header: BlockHeader,
+    /// The ids of the transactions included in the block.
+    pub txids: Vec<Txid>,
+    /// The transactions included in the block, if kept.
+    pub txdata: Option<Vec<Transaction>>,
+}
+
+impl BlockSummary {
+    /// Creates a new [BlockSummary] instance. Transactions are only kept if `keep_txdata` is set.
+    pub fn new(header: BlockHeader, txdata: &[Transaction], keep_txdata: bool) -> Self {
+        BlockSummary {
+            header,
+            txids: txdata.iter().map(|tx| tx.txid()).collect(),
+            txdata: keep_txdata.then(|| txdata.to_vec()),
+        }
+    }
+}
+
+impl From<&ValidatedBlock> for BlockSummary {
+    fn from(block: &ValidatedBlock) -> Self {
+        BlockSummary::new(block.header, &block.txdata, true)
+    }
+}
+
+impl IndexableBlock for BlockSummary {
+    fn header(&self) -> BlockHeader {
+        self.header
+    }
+
+    fn txids(&self) -> Vec<Txid> {
+        self.txids.clone()
+    }
+
+    fn txdata(&self) -> Option<&[Transaction]> {
+        self.txdata.as_deref()
+    }
+}
+
 /// Data structure used to index locators computed from parsed blocks.
 ///
 /// Holds up to `size` blocks with their corresponding computed [Locator]s.
@@ -99,7 +171,10 @@ where
     V: Value + Clone,
     Self: Sized,
 {
-    pub fn new(last_n_blocks: &[ValidatedBlock], height: u32) -> Self {
+    /// Creates a new [TxIndex] from the last `n` blocks, sorted from the tip backwards.
+    ///
+    /// Blocks whose transactions are not available are indexed empty if the index holds transactions.
+    pub fn new<B: IndexableBlock>(last_n_blocks: &[B], height: u32) -> Self {
         let size = last_n_blocks.len();
         let mut tx_index = Self {
             index: HashMap::new(),
@@ -110,34 +185,55 @@ where
         };
 
         for block in last_n_blocks.iter().rev() {
+            let header = block.header();
             if let Some(prev_block_hash) = tx_index.blocks.back() {
-                if block.header.prev_blockhash != *prev_block_hash {
+                if header.prev_blockhash != *prev_block_hash {
                     panic!("last_n_blocks contains unchained blocks");
                 }
             };
 
-            let map = block
-                .txdata
-                .iter()
-                .map(|tx| {
-                    (
-                        K::from_txid(tx.txid()),
-                        match V::get_type() {
-                            Type::Transaction => V::from_data(Data::Transaction(tx.clone())),
-                            Type::BlockHash => {
-                                V::from_data(Data::BlockHash(block.header.block_hash()))
-                            }
-                        },
-                    )
-                })
-                .collect();
-
-            tx_index.update(block.header, &map);
+            let map = match V::get_type() {
+                Type::Transaction => match block.txdata() {
+                    Some(txdata) => txdata
+                        .iter()
+                        .map(|tx| {
+                            (
+                                K::from_txid(tx.txid()),
+                                V::from_data(Data::Transaction(tx.clone())),
+                            )
+                        })
+                        .collect(),
+                    None => {
+                        log::warn!(
+                            "Transactions of block {} not available. Skipping them",
+                            header.block_hash()
+                        );
+                        HashMap::new()
+                    }
+                },
+                Type::BlockHash => block
+                    .txids()
+                    .into_iter()
+                    .map(|txid| {
+                        (
+                            K::from_txid(txid),
+                            V::from_data(Data::BlockHash(header.block_hash())),
+                        )
+                    })
+                    .collect(),
+            };
+
+            tx_index.update(header, &map);
         }
 
         tx_index
     }
 
+    /// Gets the maximum number of blocks held by the index.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     /// Gets an item from the index if present. [None] otherwise.
     pub fn get<'a>(&'a self, k: &'a K) -> Option<&V> {
         self.index.get(k)
@@ -221,6 +317,7 @@ impl<K: std::fmt::Debug, V: std::fmt::Debug> fmt::Display for TxIndex<K, V> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     use std::ops::Deref;
 
     use crate::test_utils::{get_last_n_blocks, Blockchain};
@@ -268,6 +365,45 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_new_from_summaries() {
+        let height = 10;
+        let mut chain = Blockchain::default().with_height_and_txs(height, 3);
+        let last_six_blocks = get_last_n_blocks(&mut chain, 6).await;
+
+        // Transactions are only kept for the three most recent blocks
+        let summaries: Vec<BlockSummary> = last_six_blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| BlockSummary::new(block.header, &block.txdata, i < 3))
+            .collect();
+
+        // Transaction ids are enough to rebuild an index of block hashes
+        let index: TxIndex<Txid, BlockHash> = TxIndex::new(&summaries, height as u32);
+        let expected: TxIndex<Txid, BlockHash> = TxIndex::new(&last_six_blocks, height as u32);
+        assert_eq!(index.index, expected.index);
+        assert_eq!(index.blocks, expected.blocks);
+        // The order within each block depends on the index map, so compare the contents
+        assert_eq!(index.tx_in_block.len(), expected.tx_in_block.len());
+        for (block_hash, txids) in expected.tx_in_block.iter() {
+            let txids: HashSet<_> = txids.iter().collect();
+            assert_eq!(
+                index.tx_in_block[block_hash].iter().collect::<HashSet<_>>(),
+                txids
+            );
+        }
+
+        // Blocks without transactions are indexed empty if the index holds transactions
+        let cache: TxIndex<Locator, Transaction> = TxIndex::new(&summaries, height as u32);
+        assert_eq!(cache.size, 6);
+        for (i, block) in last_six_blocks.iter().enumerate() {
+            assert!(cache.blocks().contains(&block.block_hash()));
+            for tx in block.txdata.iter() {
+                assert_eq!(cache.contains_key(&Locator::new(tx.txid())), i < 3);
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_get_height() {
         let cache_size = 10;
@@ -0,0 +1,478 @@
+//! Logic related to the tower wallet, the component in charge of fee bumping penalty transactions via child-pays-for-parent (CPFP).
+
+use std::cmp;
+
+use bitcoin::blockdata::opcodes::all::OP_PUSHNUM_1;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::util::sighash::SighashCache;
+use bitcoin::{
+    Address, EcdsaSig, EcdsaSighashType, Network, OutPoint, PublicKey, Script, Transaction, TxIn,
+    TxOut, Witness,
+};
+
+/// Outputs below this value are considered dust by the network (for P2WPKH outputs).
+const DUST_LIMIT: u64 = 294;
+/// Sequence used by the CPFP transactions so they signal for replacement (BIP125).
+const RBF_SEQUENCE: u32 = 0xFFFFFFFD;
+
+/// An unspent output owned by the [Wallet].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub value: u64,
+}
+
+impl Utxo {
+    /// Creates a new [Utxo] instance.
+    pub fn new(outpoint: OutPoint, value: u64) -> Self {
+        Utxo { outpoint, value }
+    }
+}
+
+/// A fee bump performed by the tower for a given tracker. Used to account for the fees spent by the tower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBump {
+    /// The CPFP transaction spending from the penalty.
+    pub child_tx: Transaction,
+    /// The fee paid by the CPFP transaction (in sats).
+    pub fee: u64,
+    /// The package fee rate targeted by the CPFP transaction (in sat/vB).
+    pub fee_rate: u64,
+    /// The height at which the bump was performed.
+    pub height: u32,
+}
+
+/// Reasons why a CPFP transaction cannot be created.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum CpfpError {
+    NoSpendableOutputs,
+    InsufficientFunds,
+}
+
+/// Fee-rate escalation policy followed when bumping the fees of a penalty transaction.
+///
+/// The first bump targets the fee rate estimated by the backend (never below `min_fee_rate`). Consecutive bumps for
+/// the same tracker increase the previous fee rate by, at least, `escalation` percent, so every CPFP transaction can
+/// replace its predecessor. Fee rates are capped at `max_fee_rate`, no more bumps are performed once it is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeRatePolicy {
+    /// The minimum fee rate to target (in sat/vB).
+    pub min_fee_rate: u64,
+    /// The maximum fee rate to target (in sat/vB).
+    pub max_fee_rate: u64,
+    /// The percentage by which the fee rate is increased between consecutive bumps.
+    pub escalation: u64,
+}
+
+impl FeeRatePolicy {
+    /// Creates a new [FeeRatePolicy] instance.
+    pub fn new(min_fee_rate: u64, max_fee_rate: u64, escalation: u64) -> Self {
+        FeeRatePolicy {
+            min_fee_rate,
+            max_fee_rate,
+            escalation,
+        }
+    }
+
+    /// Computes the fee rate to target for the next bump given the fee rate estimated by the backend (if any) and the last one
+    /// targeted for the same tracker (if any).
+    ///
+    /// Returns [None] if the maximum fee rate has already been reached.
+    pub fn next_fee_rate(&self, estimate: Option<u64>, last_fee_rate: Option<u64>) -> Option<u64> {
+        let mut fee_rate = cmp::max(estimate.unwrap_or(self.min_fee_rate), self.min_fee_rate);
+
+        if let Some(last_fee_rate) = last_fee_rate {
+            if last_fee_rate >= self.max_fee_rate {
+                return None;
+            }
+            let escalated = cmp::max(
+                last_fee_rate * (100 + self.escalation) / 100,
+                last_fee_rate + 1,
+            );
+            fee_rate = cmp::max(fee_rate, escalated);
+        }
+
+        Some(cmp::min(fee_rate, self.max_fee_rate))
+    }
+}
+
+impl Default for FeeRatePolicy {
+    fn default() -> Self {
+        FeeRatePolicy::new(1, 500, 50)
+    }
+}
+
+/// Types of outputs the [Wallet] can spend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    /// An output paying to the wallet address (P2WPKH).
+    Owned,
+    /// An anyone-can-spend anchor output (P2WSH(OP_TRUE)).
+    Anchor,
+}
+
+/// Computes the virtual size of a transaction.
+fn vsize(tx: &Transaction) -> u64 {
+    (tx.weight() as u64).div_ceil(4)
+}
+
+/// Computes the fee paid by a transaction provided the transactions it spends from.
+///
+/// Returns [None] if some of the spent outputs cannot be found in `prev_txs`.
+pub(crate) fn compute_fee(tx: &Transaction, prev_txs: &[&Transaction]) -> Option<u64> {
+    let mut input_value = 0;
+    for txin in tx.input.iter() {
+        let prev_tx = prev_txs
+            .iter()
+            .find(|prev_tx| prev_tx.txid() == txin.previous_output.txid)?;
+        input_value += prev_tx
+            .output
+            .get(txin.previous_output.vout as usize)?
+            .value;
+    }
+
+    input_value.checked_sub(tx.output.iter().map(|txout| txout.value).sum())
+}
+
+/// The witness script of the anchor outputs the [Wallet] can spend.
+fn anchor_witness_script() -> Script {
+    Builder::new().push_opcode(OP_PUSHNUM_1).into_script()
+}
+
+/// Component in charge of bumping the fees of penalty transactions that are not getting confirmed.
+///
+/// The wallet holds a single key, whose P2WPKH address needs to be funded by the tower operator (and tracked by the
+/// backend so its [Utxo]s can be queried). Fees are bumped by creating child-pays-for-parent transactions that spend
+/// from the outputs of penalty transactions that either pay to the wallet or are anyone-can-spend anchors.
+#[derive(Debug)]
+pub struct Wallet {
+    /// The wallet secret key.
+    sk: SecretKey,
+    /// The wallet public key.
+    pk: PublicKey,
+    /// The network the wallet addresses belong to.
+    network: Network,
+    /// The policy followed when escalating fee rates.
+    policy: FeeRatePolicy,
+}
+
+impl Wallet {
+    /// Creates a new [Wallet] instance.
+    pub fn new(sk: SecretKey, network: Network, policy: FeeRatePolicy) -> Self {
+        let pk = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &sk,
+        ));
+        Wallet {
+            sk,
+            pk,
+            network,
+            policy,
+        }
+    }
+
+    /// Getter for the wallet address.
+    pub fn address(&self) -> Address {
+        Address::p2wpkh(&self.pk, self.network).unwrap()
+    }
+
+    /// Getter for the wallet [FeeRatePolicy].
+    pub fn policy(&self) -> &FeeRatePolicy {
+        &self.policy
+    }
+
+    /// Gets the kind of an output given its script, if the wallet can spend it.
+    fn input_kind(&self, script_pubkey: &Script) -> Option<InputKind> {
+        if *script_pubkey == self.address().script_pubkey() {
+            Some(InputKind::Owned)
+        } else if *script_pubkey == Script::new_v0_p2wsh(&anchor_witness_script().wscript_hash()) {
+            Some(InputKind::Anchor)
+        } else {
+            None
+        }
+    }
+
+    /// Creates a CPFP transaction for the given parent transaction, so the package reaches `fee_rate` (in sat/vB).
+    ///
+    /// The child spends every output of the parent the wallet can spend, plus as many [Utxo]s as needed to cover the fee
+    /// (biggest first). The remaining funds are sent back to the wallet. Returns the signed transaction alongside the fee it pays.
+    pub(crate) fn create_cpfp(
+        &self,
+        parent: &Transaction,
+        parent_fee: u64,
+        utxos: &[Utxo],
+        fee_rate: u64,
+    ) -> Result<(Transaction, u64), CpfpError> {
+        let mut inputs: Vec<(OutPoint, u64, InputKind)> = parent
+            .output
+            .iter()
+            .enumerate()
+            .filter_map(|(vout, txout)| {
+                self.input_kind(&txout.script_pubkey)
+                    .map(|kind| (OutPoint::new(parent.txid(), vout as u32), txout.value, kind))
+            })
+            .collect();
+
+        if inputs.is_empty() {
+            return Err(CpfpError::NoSpendableOutputs);
+        }
+
+        let mut utxos = utxos.to_vec();
+        utxos.sort_by_key(|u| std::cmp::Reverse(u.value));
+        let mut utxos = utxos.into_iter();
+
+        let parent_vsize = vsize(parent);
+        loop {
+            // The transaction is built with placeholder witnesses so its size can be estimated
+            let mut child = self.build_child(&inputs);
+            let child_vsize = vsize(&child);
+            let fee = cmp::max(
+                (fee_rate * (parent_vsize + child_vsize)).saturating_sub(parent_fee),
+                fee_rate * child_vsize,
+            );
+            let input_value: u64 = inputs.iter().map(|(_, value, _)| value).sum();
+
+            if input_value >= fee + DUST_LIMIT {
+                child.output[0].value = input_value - fee;
+                self.sign(&mut child, &inputs);
+                return Ok((child, fee));
+            }
+
+            match utxos.next() {
+                Some(utxo) => inputs.push((utxo.outpoint, utxo.value, InputKind::Owned)),
+                None => return Err(CpfpError::InsufficientFunds),
+            }
+        }
+    }
+
+    /// Builds an unsigned transaction spending the given inputs to the wallet address. Witnesses are filled with
+    /// placeholders of the maximum size they can have.
+    fn build_child(&self, inputs: &[(OutPoint, u64, InputKind)]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: inputs
+                .iter()
+                .map(|(outpoint, _, kind)| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: Script::new(),
+                    sequence: RBF_SEQUENCE,
+                    witness: match kind {
+                        InputKind::Owned => Witness::from_vec(vec![vec![0; 72], vec![0; 33]]),
+                        InputKind::Anchor => {
+                            Witness::from_vec(vec![anchor_witness_script().to_bytes()])
+                        }
+                    },
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: self.address().script_pubkey(),
+            }],
+        }
+    }
+
+    /// Signs the inputs of a transaction owned by the wallet.
+    fn sign(&self, tx: &mut Transaction, inputs: &[(OutPoint, u64, InputKind)]) {
+        let secp = Secp256k1::new();
+        let script_code = Script::new_p2pkh(&self.pk.pubkey_hash());
+        let mut witnesses = Vec::with_capacity(inputs.len());
+
+        let mut cache = SighashCache::new(&*tx);
+        for (i, (_, value, kind)) in inputs.iter().enumerate() {
+            witnesses.push(match kind {
+                InputKind::Owned => {
+                    let sighash = cache
+                        .segwit_signature_hash(i, &script_code, *value, EcdsaSighashType::All)
+                        .unwrap();
+                    let sig = EcdsaSig {
+                        sig: secp.sign_ecdsa(&Message::from_slice(&sighash).unwrap(), &self.sk),
+                        hash_ty: EcdsaSighashType::All,
+                    };
+                    Witness::from_vec(vec![sig.to_vec(), self.pk.to_bytes()])
+                }
+                InputKind::Anchor => Witness::from_vec(vec![anchor_witness_script().to_bytes()]),
+            });
+        }
+
+        for (txin, witness) in tx.input.iter_mut().zip(witnesses) {
+            txin.witness = witness;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+
+    use crate::test_utils::get_random_tx;
+
+    fn get_wallet() -> Wallet {
+        Wallet::new(
+            get_random_keypair().0,
+            Network::Regtest,
+            FeeRatePolicy::default(),
+        )
+    }
+
+    fn get_random_utxo(value: u64) -> Utxo {
+        Utxo::new(
+            OutPoint::new(Txid::from_slice(&get_random_bytes(32)).unwrap(), 0),
+            value,
+        )
+    }
+
+    /// Creates a parent transaction with an output paying to the given script.
+    fn get_parent(script_pubkey: Script, value: u64) -> Transaction {
+        let mut parent = get_random_tx();
+        parent.output.push(TxOut {
+            value,
+            script_pubkey,
+        });
+        parent
+    }
+
+    #[test]
+    fn test_next_fee_rate() {
+        let policy = FeeRatePolicy::new(2, 100, 50);
+
+        // The first bump follows the estimate, bounded by the policy
+        assert_eq!(policy.next_fee_rate(Some(10), None), Some(10));
+        assert_eq!(policy.next_fee_rate(Some(1), None), Some(2));
+        assert_eq!(policy.next_fee_rate(None, None), Some(2));
+        assert_eq!(policy.next_fee_rate(Some(1000), None), Some(100));
+
+        // Consecutive bumps escalate the previous fee rate, unless the estimate is higher
+        assert_eq!(policy.next_fee_rate(Some(10), Some(10)), Some(15));
+        assert_eq!(policy.next_fee_rate(Some(30), Some(10)), Some(30));
+        assert_eq!(policy.next_fee_rate(None, Some(2)), Some(3));
+        assert_eq!(policy.next_fee_rate(None, Some(80)), Some(100));
+
+        // No more bumps are performed once the maximum has been reached
+        assert_eq!(policy.next_fee_rate(Some(10), Some(100)), None);
+    }
+
+    #[test]
+    fn test_compute_fee() {
+        let mut prev_tx = get_random_tx();
+        prev_tx.output[0].value = 10_000;
+        let mut tx = get_random_tx();
+        tx.input[0].previous_output = OutPoint::new(prev_tx.txid(), 0);
+        tx.output[0].value = 9_000;
+
+        assert_eq!(compute_fee(&tx, &[&prev_tx]), Some(1000));
+        // Fees cannot be computed if the spent outputs are unknown
+        assert_eq!(compute_fee(&tx, &[&get_random_tx()]), None);
+    }
+
+    #[test]
+    fn test_create_cpfp_owned_output() {
+        let wallet = get_wallet();
+        let parent = get_parent(wallet.address().script_pubkey(), 100_000);
+        let fee_rate = 10;
+
+        // The output paying to the wallet is enough to bump the fee
+        let (child, fee) = wallet
+            .create_cpfp(&parent, 0, &[get_random_utxo(50_000)], fee_rate)
+            .unwrap();
+
+        assert_eq!(child.input.len(), 1);
+        assert_eq!(
+            child.input[0].previous_output,
+            OutPoint::new(parent.txid(), parent.output.len() as u32 - 1)
+        );
+        assert_eq!(child.input[0].sequence, RBF_SEQUENCE);
+        assert_eq!(child.output.len(), 1);
+        assert_eq!(child.output[0].value, 100_000 - fee);
+        assert_eq!(
+            child.output[0].script_pubkey,
+            wallet.address().script_pubkey()
+        );
+
+        // The package pays, at least, the requested fee rate
+        assert!(fee >= fee_rate * (vsize(&parent) + vsize(&child)));
+
+        // The input is properly signed
+        let witness = child.input[0].witness.to_vec();
+        let sig = EcdsaSig::from_slice(&witness[0]).unwrap();
+        assert_eq!(witness[1], wallet.pk.to_bytes());
+        let sighash = SighashCache::new(&child)
+            .segwit_signature_hash(
+                0,
+                &Script::new_p2pkh(&wallet.pk.pubkey_hash()),
+                100_000,
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        Secp256k1::new()
+            .verify_ecdsa(
+                &Message::from_slice(&sighash).unwrap(),
+                &sig.sig,
+                &wallet.pk.inner,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_cpfp_anchor() {
+        let wallet = get_wallet();
+        let anchor_script = Script::new_v0_p2wsh(&anchor_witness_script().wscript_hash());
+        let parent = get_parent(anchor_script, 330);
+        let parent_fee = 1000;
+        let fee_rate = 20;
+
+        // The anchor cannot cover the fee on its own, so the wallet funds are used
+        let utxos = [get_random_utxo(1000), get_random_utxo(100_000)];
+        let (child, fee) = wallet
+            .create_cpfp(&parent, parent_fee, &utxos, fee_rate)
+            .unwrap();
+
+        // The biggest UTXO is picked first
+        assert_eq!(child.input.len(), 2);
+        assert_eq!(
+            child.input[0].witness.to_vec(),
+            vec![anchor_witness_script().to_bytes()]
+        );
+        assert_eq!(child.input[1].previous_output, utxos[1].outpoint);
+        assert_eq!(child.output[0].value, 100_000 + 330 - fee);
+
+        // The fees already paid by the parent are taken into account
+        assert_eq!(
+            fee,
+            fee_rate
+                * (vsize(&parent)
+                    + vsize(&wallet.build_child(&[
+                        (child.input[0].previous_output, 330, InputKind::Anchor),
+                        (utxos[1].outpoint, utxos[1].value, InputKind::Owned)
+                    ])))
+                - parent_fee
+        );
+    }
+
+    #[test]
+    fn test_create_cpfp_no_spendable_outputs() {
+        let wallet = get_wallet();
+
+        assert_eq!(
+            wallet.create_cpfp(&get_random_tx(), 0, &[get_random_utxo(100_000)], 10),
+            Err(CpfpError::NoSpendableOutputs)
+        );
+    }
+
+    #[test]
+    fn test_create_cpfp_insufficient_funds() {
+        let wallet = get_wallet();
+        let parent = get_parent(wallet.address().script_pubkey(), 1000);
+
+        assert_eq!(
+            wallet.create_cpfp(&parent, 0, &[get_random_utxo(1000)], 100),
+            Err(CpfpError::InsufficientFunds)
+        );
+    }
+}
@@ -6,31 +6,39 @@ use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
+use std::thread;
 
 use bitcoin::secp256k1::SecretKey;
-use bitcoin::{BlockHeader, Transaction};
+use bitcoin::{BlockHeader, Transaction, Txid};
 use lightning::chain;
-use lightning_block_sync::poll::ValidatedBlock;
 
-use teos_common::appointment::{Appointment, Locator};
+use teos_common::appointment::{deletion_message, Appointment, Locator};
+use teos_common::constants::IRREVOCABLY_RESOLVED;
 use teos_common::cryptography;
-use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
+use teos_common::receipts::{
+    AppointmentReceipt, DeletionReceipt, RegistrationReceipt, RenewalReceipt, TopUpReceipt,
+};
 use teos_common::{TowerId, UserId};
 
-use crate::dbm::DBM;
+use crate::carrier::TxBroadcaster;
+use crate::dbm::Storage;
 use crate::extended_appointment::{AppointmentSummary, ExtendedAppointment, UUID};
-use crate::gatekeeper::{Gatekeeper, MaxSlotsReached, UserInfo};
+use crate::gatekeeper::{
+    AuditLogEntry, Gatekeeper, RegistrationFailure, SubscriptionTier, UserInfo,
+    UserManagementFailure,
+};
 use crate::responder::{ConfirmationStatus, Responder, TransactionTracker};
-use crate::tx_index::TxIndex;
+use crate::tx_index::{BlockSummary, IndexableBlock, TxIndex};
 
 /// Structure holding data regarding a breach.
 ///
 /// Breaches are computed after spotting a [Locator] on chain and
 /// using the resulting dispute transaction id to decipher the encrypted blob of an ongoing [Appointment].
 /// Breaches are passed to the [Responder] once created.
-#[derive(Debug, Clone)]
-pub(crate) struct Breach {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breach {
     /// Transaction that triggered the breach.
     pub dispute_tx: Transaction,
     /// Transaction that will be used as a response to the breach.
@@ -48,21 +56,46 @@ impl Breach {
 }
 
 /// Packs the reasons why trying to add an appointment may fail.
-// TODO: It may be nice to create richer errors so the API can return richer rejection
+///
+/// Each reason carries the data needed to let the user know how to react to the rejection.
 #[derive(Debug)]
 pub(crate) enum AddAppointmentFailure {
     AuthenticationFailure,
-    NotEnoughSlots,
-    SubscriptionExpired(u32),
+    NotEnoughSlots {
+        required_slots: u32,
+        available_slots: u32,
+    },
+    SubscriptionExpired {
+        expiry: u32,
+        current_height: u32,
+    },
     AlreadyTriggered,
+    ToSelfDelayTooSmall(u16),
+    RateLimited(u64),
+    StorageFailure,
 }
 
+/// The outcome of adding an [Appointment] that is part of a batch: either the [AppointmentReceipt] alongside the
+/// slots available after adding it, or the reason why it was rejected.
+pub(crate) type BatchedAppointmentResult = Result<(AppointmentReceipt, u32), AddAppointmentFailure>;
+
 /// Packs the reasons why trying to query an appointment may fail.
 #[derive(Debug)]
 pub(crate) enum GetAppointmentFailure {
     AuthenticationFailure,
     SubscriptionExpired(u32),
     NotFound,
+    RateLimited(u64),
+}
+
+/// Packs the reasons why trying to delete an appointment may fail.
+#[derive(Debug)]
+pub(crate) enum DeleteAppointmentFailure {
+    AuthenticationFailure,
+    SubscriptionExpired(u32),
+    NotFound,
+    AlreadyTriggered,
+    RateLimited(u64),
 }
 
 /// Packs the reasons why trying to query a subscription info may fail.
@@ -70,6 +103,7 @@ pub(crate) enum GetAppointmentFailure {
 pub(crate) enum GetSubscriptionInfoFailure {
     AuthenticationFailure,
     SubscriptionExpired(u32),
+    RateLimited(u64),
 }
 
 /// Wraps the returning information regarding a queried appointment.
@@ -87,6 +121,8 @@ enum DeletionReason {
     Outdated,
     Invalid,
     Accepted,
+    Withdrawn,
+    UserDeleted,
 }
 
 /// Types of new appointments stored in the [Watcher].
@@ -100,11 +136,50 @@ enum StoredAppointment {
 /// Types of new triggered appointments handled by the [Watcher].
 #[derive(Debug, PartialEq, Eq)]
 enum TriggeredAppointment {
-    Accepted,
-    Rejected,
+    Queued,
     Invalid,
 }
 
+/// A breach found for an appointment that was triggered before being received, waiting to be handed to the [Responder].
+#[derive(Debug)]
+struct QueuedBreach {
+    uuid: UUID,
+    breach: Breach,
+    user_id: UserId,
+}
+
+/// Hands the queued breaches to the [Responder] one by one, until the sending end of the queue is dropped.
+///
+/// This runs on its own thread so the [Watcher] does not have to wait for the penalty transactions to be broadcast
+/// (e.g. if bitcoind is unreachable, the [Carrier](crate::carrier::Carrier) will hang until it is back and retry).
+/// Queued breaches are kept in the database until handled, so they survive restarts. Breaches rejected by the
+/// [Responder] are wiped from the database alongside their appointment.
+fn respond_to_queued_breaches(
+    queue: Receiver<QueuedBreach>,
+    responder: Arc<Responder>,
+    dbm: Arc<Mutex<dyn Storage>>,
+    pending_triggers: Arc<Mutex<HashSet<UUID>>>,
+) {
+    for QueuedBreach {
+        uuid,
+        breach,
+        user_id,
+    } in queue
+    {
+        if let ConfirmationStatus::Rejected(reason) = responder.handle_breach(uuid, breach, user_id)
+        {
+            // DISCUSS: We could either free the slots or keep it occupied as if this was misbehavior.
+            // Keeping it for now.
+            log::warn!("Appointment bounced in the Responder. Reason: {reason:?}");
+            dbm.lock().unwrap().remove_appointment(uuid);
+        } else {
+            log::info!("Appointment went straight to the Responder");
+            dbm.lock().unwrap().remove_queued_breach(uuid);
+        }
+        pending_triggers.lock().unwrap().remove(&uuid);
+    }
+}
+
 /// Component in charge of watching for triggers in the chain (aka channel breaches for lightning).
 #[derive(Debug)]
 pub struct Watcher {
@@ -114,34 +189,58 @@ pub struct Watcher {
     locator_uuid_map: Mutex<HashMap<Locator, HashSet<UUID>>>,
     /// A cache of the [Locator]s computed for the transactions in the last few blocks.
     locator_cache: Mutex<TxIndex<Locator, Transaction>>,
+    /// A map between the [UUID]s of the appointments triggered by unconfirmed transactions and their dispute [Txid]s.
+    /// These appointments have already been handed to the [Responder], but are kept by the [Watcher] until the
+    /// dispute transaction is confirmed, given it may still be replaced or dropped from the mempool.
+    mempool_triggers: Mutex<HashMap<UUID, Txid>>,
+    /// The [UUID]s of the triggered appointments that have been queued but not yet handled by the [Responder].
+    pending_triggers: Arc<Mutex<HashSet<UUID>>>,
+    /// The sending end of the queue of breaches waiting to be handed to the [Responder] by a background thread.
+    breach_queue: Mutex<Sender<QueuedBreach>>,
     /// A [Responder] instance. Data will be passed to it once triggered (if valid).
     responder: Arc<Responder>,
     /// A [Gatekeeper] instance. Data regarding users is requested to it.
     gatekeeper: Arc<Gatekeeper>,
     /// The last known block height.
     last_known_block_height: AtomicU32,
+    /// The minimum `to_self_delay` an [Appointment] must have to be accepted by the tower.
+    min_to_self_delay: u16,
     /// The tower signing key. Used to sign messages going to users.
     signing_key: SecretKey,
     /// The tower identifier.
     pub tower_id: TowerId,
-    /// A [DBM] (database manager) instance. Used to persist appointment data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// A [Storage] (database manager) instance. Used to persist appointment data into disk.
+    dbm: Arc<Mutex<dyn Storage>>,
 }
 
 impl Watcher {
     /// Creates a new [Watcher] instance.
-    pub fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<B: IndexableBlock>(
         gatekeeper: Arc<Gatekeeper>,
         responder: Arc<Responder>,
-        last_n_blocks: &[ValidatedBlock],
+        last_n_blocks: &[B],
         last_known_block_height: u32,
+        min_to_self_delay: u16,
         signing_key: SecretKey,
         tower_id: TowerId,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
     ) -> Self {
         let mut appointments = HashMap::new();
         let mut locator_uuid_map: HashMap<Locator, HashSet<UUID>> = HashMap::new();
+        let mut queued_breaches = dbm.lock().unwrap().load_queued_breaches();
+        let mut pending_breaches = Vec::new();
         for (uuid, appointment) in dbm.lock().unwrap().load_appointments(None) {
+            // Appointments whose breach was queued before a restart are not watched, but queued again
+            if let Some(breach) = queued_breaches.remove(&uuid) {
+                pending_breaches.push(QueuedBreach {
+                    uuid,
+                    breach,
+                    user_id: appointment.user_id,
+                });
+                continue;
+            }
+
             appointments.insert(uuid, appointment.get_summary());
 
             if let Some(map) = locator_uuid_map.get_mut(&appointment.locator()) {
@@ -151,13 +250,47 @@ impl Watcher {
             }
         }
 
+        // Appointments triggered from the mempool are kept being watched until their dispute is confirmed. Since they already
+        // have a tracker they are not loaded above, so they are brought back from their persisted triggers
+        let mut mempool_triggers = HashMap::new();
+        let stored_triggers = dbm.lock().unwrap().load_mempool_triggers();
+        for (uuid, dispute_txid) in stored_triggers {
+            let appointment = dbm.lock().unwrap().load_appointment(uuid);
+            match appointment {
+                Some(appointment) if responder.has_tracker(uuid) => {
+                    appointments.insert(uuid, appointment.get_summary());
+                    locator_uuid_map
+                        .entry(appointment.locator())
+                        .or_default()
+                        .insert(uuid);
+                    mempool_triggers.insert(uuid, dispute_txid);
+                }
+                _ => dbm.lock().unwrap().remove_mempool_trigger(uuid),
+            }
+        }
+
+        let pending_triggers = Arc::new(Mutex::new(HashSet::from_iter(
+            pending_breaches.iter().map(|queued| queued.uuid),
+        )));
+        let (breach_queue, queued_breaches) = mpsc::channel();
+        for queued in pending_breaches {
+            log::info!("Queuing the breach for {} again", queued.uuid);
+            breach_queue.send(queued).unwrap();
+        }
+        let (r, d, p) = (responder.clone(), dbm.clone(), pending_triggers.clone());
+        thread::spawn(move || respond_to_queued_breaches(queued_breaches, r, d, p));
+
         Watcher {
             appointments: Mutex::new(appointments),
             locator_uuid_map: Mutex::new(locator_uuid_map),
             locator_cache: Mutex::new(TxIndex::new(last_n_blocks, last_known_block_height)),
+            mempool_triggers: Mutex::new(mempool_triggers),
+            pending_triggers,
+            breach_queue: Mutex::new(breach_queue),
             responder,
             gatekeeper,
             last_known_block_height: AtomicU32::new(last_known_block_height),
+            min_to_self_delay,
             signing_key,
             tower_id,
             dbm,
@@ -169,10 +302,30 @@ impl Watcher {
         self.appointments.lock().unwrap().is_empty()
     }
 
-    /// Registers a new user within the [Watcher]. This request is passed to the [Gatekeeper], who is in
-    /// charge of managing users.
-    pub(crate) fn register(&self, user_id: UserId) -> Result<RegistrationReceipt, MaxSlotsReached> {
-        let mut receipt = self.gatekeeper.add_update_user(user_id)?;
+    /// Registers a new user to a given subscription tier within the [Watcher]. This request is passed to the [Gatekeeper],
+    /// who is in charge of managing users (and getting paid for their subscriptions, if required).
+    pub(crate) fn register(
+        &self,
+        user_id: UserId,
+        tier: &str,
+    ) -> Result<RegistrationReceipt, RegistrationFailure> {
+        let mut receipt = self.gatekeeper.register(user_id, tier)?;
+        receipt.sign(&self.signing_key);
+
+        Ok(receipt)
+    }
+
+    /// Renews the subscription of a user within the [Watcher]. This request is passed to the [Gatekeeper].
+    pub(crate) fn renew(&self, user_id: UserId) -> Result<RenewalReceipt, RegistrationFailure> {
+        let mut receipt = self.gatekeeper.renew(user_id)?;
+        receipt.sign(&self.signing_key);
+
+        Ok(receipt)
+    }
+
+    /// Tops up the subscription of a user within the [Watcher]. This request is passed to the [Gatekeeper].
+    pub(crate) fn top_up(&self, user_id: UserId) -> Result<TopUpReceipt, RegistrationFailure> {
+        let mut receipt = self.gatekeeper.top_up(user_id)?;
         receipt.sign(&self.signing_key);
 
         Ok(receipt)
@@ -182,14 +335,17 @@ impl Watcher {
     ///
     /// Appointments are only added provided:
     /// - The user is registered into the system
+    /// - The user has not exceeded their rate limit
     /// - The user subscription has not expired
+    /// - The appointment `to_self_delay` is not smaller than the tower's `min_to_self_delay`
     /// - The user has enough available slots to fit the appointment
     /// - The appointment hasn't been responded to yet (data cannot be found in the [Responder])
     ///
     /// If an appointment is accepted, an [AppointmentSummary] will be added to the the watching pool and
     /// monitored by the [Watcher]. An [ExtendedAppointment] (constructed from the [Appointment]) will be persisted on disk.
     /// In case the locator for the given appointment can be found in the cache (meaning the appointment has been
-    /// triggered recently) the data will be passed to the [Responder] straightaway (modulo it being valid).
+    /// triggered recently) the data will be queued to be passed to the [Responder] straightaway (modulo it being valid).
+    /// The receipt is returned without waiting for the [Responder] to broadcast the penalty transaction.
     pub(crate) fn add_appointment(
         &self,
         appointment: Appointment,
@@ -199,12 +355,25 @@ impl Watcher {
             .gatekeeper
             .authenticate_user(&appointment.to_vec(), &user_signature)
             .map_err(|_| AddAppointmentFailure::AuthenticationFailure)?;
+        self.gatekeeper
+            .check_rate_limit(user_id)
+            .map_err(|e| AddAppointmentFailure::RateLimited(e.0))?;
 
         let (has_subscription_expired, expiry) =
             self.gatekeeper.has_subscription_expired(user_id).unwrap();
 
         if has_subscription_expired {
-            return Err(AddAppointmentFailure::SubscriptionExpired(expiry));
+            return Err(AddAppointmentFailure::SubscriptionExpired {
+                expiry,
+                current_height: self.last_known_block_height.load(Ordering::Acquire),
+            });
+        }
+
+        // Appointments whose to_self_delay is too small may not leave enough time to react to a breach
+        if appointment.to_self_delay < self.min_to_self_delay as u32 {
+            return Err(AddAppointmentFailure::ToSelfDelayTooSmall(
+                self.min_to_self_delay,
+            ));
         }
 
         let extended_appointment = ExtendedAppointment::new(
@@ -216,7 +385,7 @@ impl Watcher {
 
         let uuid = UUID::new(extended_appointment.locator(), user_id);
 
-        if self.responder.has_tracker(uuid) {
+        if self.is_triggered(uuid) {
             log::info!("Tracker for {uuid} already found in Responder");
             return Err(AddAppointmentFailure::AlreadyTriggered);
         }
@@ -224,12 +393,11 @@ impl Watcher {
         let available_slots = self
             .gatekeeper
             .add_update_appointment(user_id, uuid, &extended_appointment)
-            .map_err(|_| AddAppointmentFailure::NotEnoughSlots)?;
+            .map_err(|e| AddAppointmentFailure::NotEnoughSlots {
+                required_slots: e.required_slots,
+                available_slots: e.available_slots,
+            })?;
 
-        // FIXME: There's an edge case here if store_triggered_appointment is called and bitcoind is unreachable.
-        // This will hang, the request will timeout but be accepted. However, the user will not be handed the receipt.
-        // This could be fixed adding a thread to take care of storing while the main thread returns the receipt.
-        // Not fixing this atm since working with threads that call self.method is surprisingly non-trivial.
         match self
             .locator_cache
             .lock()
@@ -255,6 +423,184 @@ impl Watcher {
         Ok((receipt, available_slots, expiry))
     }
 
+    /// Adds a batch of [Appointment]s belonging to the same user to the tower.
+    ///
+    /// The user is authenticated once, using the first appointment of the batch, and the signatures of the rest
+    /// of appointments are checked against the recovered user. The whole batch is rejected if the user cannot be
+    /// authenticated or their subscription has expired. Otherwise, every appointment is checked the same way as in
+    /// [Watcher::add_appointment], and either a receipt (alongside the slots available after adding it) or the reason
+    /// why it was rejected is returned for each of them, following the order of the batch.
+    ///
+    /// The accepted appointments (and the breaches of the ones that have already been triggered) are persisted,
+    /// alongside the updated user data, in a single database transaction before being applied in memory. If they
+    /// cannot be persisted, the whole batch is rejected and the tower is left as it was.
+    pub(crate) fn add_appointments(
+        &self,
+        appointments: Vec<(Appointment, String)>,
+    ) -> Result<(Vec<BatchedAppointmentResult>, u32, u32), AddAppointmentFailure> {
+        let (first_appointment, first_signature) = appointments
+            .first()
+            .ok_or(AddAppointmentFailure::AuthenticationFailure)?;
+        let user_id = self
+            .gatekeeper
+            .authenticate_user(&first_appointment.to_vec(), first_signature)
+            .map_err(|_| AddAppointmentFailure::AuthenticationFailure)?;
+        self.gatekeeper
+            .check_rate_limit(user_id)
+            .map_err(|e| AddAppointmentFailure::RateLimited(e.0))?;
+
+        let (has_subscription_expired, expiry) =
+            self.gatekeeper.has_subscription_expired(user_id).unwrap();
+
+        if has_subscription_expired {
+            return Err(AddAppointmentFailure::SubscriptionExpired {
+                expiry,
+                current_height: self.last_known_block_height.load(Ordering::Acquire),
+            });
+        }
+
+        let (results, available_slots) =
+            self.gatekeeper
+                .try_update_user(user_id, |user_info, slot_size| {
+                    let mut to_store = Vec::new();
+                    let results = appointments
+                        .into_iter()
+                        .map(|(appointment, user_signature)| {
+                            self.add_batched_appointment(
+                                user_id,
+                                appointment,
+                                user_signature,
+                                user_info,
+                                slot_size,
+                                &mut to_store,
+                            )
+                        })
+                        .collect();
+
+                    let appointments = to_store
+                        .iter()
+                        .map(|(uuid, appointment, _)| (*uuid, appointment))
+                        .collect::<Vec<_>>();
+                    let breaches = to_store
+                        .iter()
+                        .filter_map(|(uuid, _, breach)| breach.as_ref().map(|b| (*uuid, b)))
+                        .collect::<Vec<_>>();
+                    self.dbm
+                        .lock()
+                        .unwrap()
+                        .batch_store_appointments(&appointments, &breaches, user_id, user_info)
+                        .map_err(|e| {
+                            log::error!(
+                                "Couldn't store the appointments of {user_id}. Error: {e:?}"
+                            );
+                            AddAppointmentFailure::StorageFailure
+                        })?;
+
+                    for (uuid, appointment, breach) in to_store {
+                        match breach {
+                            Some(breach) => self.queue_breach(uuid, breach, user_id),
+                            None => {
+                                self.store_appointment_in_memory(uuid, &appointment);
+                            }
+                        }
+                    }
+
+                    Ok((results, user_info.available_slots))
+                })?;
+
+        Ok((results, available_slots, expiry))
+    }
+
+    /// Checks an [Appointment] that is part of a batch. The appointment must be signed by the already authenticated
+    /// user, whose data (`user_info`) is updated with the slots the appointment takes.
+    ///
+    /// Accepted appointments are added to `to_store`, alongside the [Breach] to be handed to the [Responder] if they
+    /// have already been triggered, so they can be persisted at once before being applied in memory.
+    fn add_batched_appointment(
+        &self,
+        user_id: UserId,
+        appointment: Appointment,
+        user_signature: String,
+        user_info: &mut UserInfo,
+        slot_size: usize,
+        to_store: &mut Vec<(UUID, ExtendedAppointment, Option<Breach>)>,
+    ) -> BatchedAppointmentResult {
+        if !cryptography::verify(&appointment.to_vec(), &user_signature, &user_id.0) {
+            return Err(AddAppointmentFailure::AuthenticationFailure);
+        }
+
+        if appointment.to_self_delay < self.min_to_self_delay as u32 {
+            return Err(AddAppointmentFailure::ToSelfDelayTooSmall(
+                self.min_to_self_delay,
+            ));
+        }
+
+        let extended_appointment = ExtendedAppointment::new(
+            appointment,
+            user_id,
+            user_signature,
+            self.last_known_block_height.load(Ordering::Acquire),
+        );
+
+        let uuid = UUID::new(extended_appointment.locator(), user_id);
+
+        // Appointments triggered earlier in the same batch are not queued yet
+        if self.is_triggered(uuid)
+            || to_store
+                .iter()
+                .any(|(u, _, breach)| *u == uuid && breach.is_some())
+        {
+            log::info!("Tracker for {uuid} already found in Responder");
+            return Err(AddAppointmentFailure::AlreadyTriggered);
+        }
+
+        let available_slots =
+            Gatekeeper::update_slots(user_info, uuid, &extended_appointment, slot_size).map_err(
+                |e| AddAppointmentFailure::NotEnoughSlots {
+                    required_slots: e.required_slots,
+                    available_slots: e.available_slots,
+                },
+            )?;
+
+        match self
+            .locator_cache
+            .lock()
+            .unwrap()
+            .get(&extended_appointment.locator())
+        {
+            Some(dispute_tx) => {
+                log::info!(
+                    "Trigger for locator {} found in cache",
+                    extended_appointment.locator()
+                );
+                // Appointments with invalid data are accepted, but the data is dropped (see store_triggered_appointment)
+                match cryptography::decrypt(
+                    extended_appointment.encrypted_blob(),
+                    &dispute_tx.txid(),
+                ) {
+                    Ok(penalty_tx) => to_store.push((
+                        uuid,
+                        extended_appointment.clone(),
+                        Some(Breach::new(dispute_tx.clone(), penalty_tx)),
+                    )),
+                    Err(_) => log::info!(
+                        "The appointment contained invalid data {}",
+                        extended_appointment.locator()
+                    ),
+                }
+            }
+            None => to_store.push((uuid, extended_appointment.clone(), None)),
+        };
+
+        let mut receipt = AppointmentReceipt::new(
+            extended_appointment.user_signature,
+            extended_appointment.start_block,
+        );
+        receipt.sign(&self.signing_key);
+
+        Ok((receipt, available_slots))
+    }
+
     /// Stores an appointment in the [Watcher] memory and into the database (or updates it if it already exists).
     ///
     /// Data is stored in `locator_uuid_map` and `appointments`.
@@ -262,6 +608,24 @@ impl Watcher {
         &self,
         uuid: UUID,
         appointment: &ExtendedAppointment,
+    ) -> StoredAppointment {
+        let stored = self.store_appointment_in_memory(uuid, appointment);
+        let dbm = self.dbm.lock().unwrap();
+        match stored {
+            StoredAppointment::Update => dbm.update_appointment(uuid, appointment),
+            _ => dbm.store_appointment(uuid, appointment).unwrap(),
+        }
+
+        stored
+    }
+
+    /// Stores an appointment in the [Watcher] memory (or updates it if it already exists).
+    ///
+    /// Data is stored in `locator_uuid_map` and `appointments`.
+    fn store_appointment_in_memory(
+        &self,
+        uuid: UUID,
+        appointment: &ExtendedAppointment,
     ) -> StoredAppointment {
         self.appointments
             .lock()
@@ -271,12 +635,6 @@ impl Watcher {
         if let Entry::Vacant(e) = locator_uuid_map.entry(appointment.locator()) {
             // New appointment
             e.insert(HashSet::from_iter(vec![uuid]));
-
-            self.dbm
-                .lock()
-                .unwrap()
-                .store_appointment(uuid, appointment)
-                .unwrap();
             StoredAppointment::New
         } else {
             // Either an update or an appointment from another user sharing the same locator
@@ -289,24 +647,15 @@ impl Watcher {
                     "Adding an additional appointment to locator {}: {uuid}",
                     appointment.locator()
                 );
-                self.dbm
-                    .lock()
-                    .unwrap()
-                    .store_appointment(uuid, appointment)
-                    .unwrap();
                 StoredAppointment::Collision
             } else {
                 log::debug!("Update received for {uuid}, locator map not modified");
-                self.dbm
-                    .lock()
-                    .unwrap()
-                    .update_appointment(uuid, appointment);
                 StoredAppointment::Update
             }
         }
     }
 
-    /// Stores and already triggered appointment in the database and hands it to the [Responder].
+    /// Stores and already triggered appointment in the database and queues it to be handed to the [Responder].
     ///
     /// If the appointment is rejected by the [Responder] (i.e. for being invalid), the data is wiped
     /// from the database but the slot is not freed.
@@ -325,27 +674,16 @@ impl Watcher {
             Ok(penalty_tx) => {
                 // Data needs to be added the database straightaway since appointments are
                 // FKs to trackers. If handle breach fails, data will be deleted later.
-                self.dbm
-                    .lock()
-                    .unwrap()
-                    .store_appointment(uuid, appointment)
-                    .unwrap();
-
-                if let ConfirmationStatus::Rejected(reason) = self.responder.handle_breach(
-                    uuid,
-                    Breach::new(dispute_tx.clone(), penalty_tx),
-                    user_id,
-                ) {
-                    // DISCUSS: We could either free the slots or keep it occupied as if this was misbehavior.
-                    // Keeping it for now.
-                    log::warn!("Appointment bounced in the Responder. Reason: {reason:?}");
-
-                    self.dbm.lock().unwrap().remove_appointment(uuid);
-                    TriggeredAppointment::Rejected
-                } else {
-                    log::info!("Appointment went straight to the Responder");
-                    TriggeredAppointment::Accepted
+                // The breach is persisted too, so it can be queued again if the tower restarts before it is handled.
+                let breach = Breach::new(dispute_tx.clone(), penalty_tx);
+                {
+                    let dbm = self.dbm.lock().unwrap();
+                    dbm.store_appointment(uuid, appointment).unwrap();
+                    dbm.store_queued_breach(uuid, &breach).unwrap();
                 }
+
+                self.queue_breach(uuid, breach, user_id);
+                TriggeredAppointment::Queued
             }
 
             // DISCUSS: Check if this makes sense or if we should just drop the data altogether
@@ -362,6 +700,20 @@ impl Watcher {
         }
     }
 
+    /// Queues an already persisted [Breach] to be handed to the [Responder].
+    fn queue_breach(&self, uuid: UUID, breach: Breach, user_id: UserId) {
+        self.pending_triggers.lock().unwrap().insert(uuid);
+        self.breach_queue
+            .lock()
+            .unwrap()
+            .send(QueuedBreach {
+                uuid,
+                breach,
+                user_id,
+            })
+            .unwrap();
+    }
+
     /// Retrieves an [Appointment] from the tower.
     ///
     /// Appointments can only be retrieved provided:
@@ -380,6 +732,9 @@ impl Watcher {
             .gatekeeper
             .authenticate_user(message.as_bytes(), user_signature)
             .map_err(|_| GetAppointmentFailure::AuthenticationFailure)?;
+        self.gatekeeper
+            .check_rate_limit(user_id)
+            .map_err(|e| GetAppointmentFailure::RateLimited(e.0))?;
 
         let (has_subscription_expired, expiry) =
             self.gatekeeper.has_subscription_expired(user_id).unwrap();
@@ -390,7 +745,10 @@ impl Watcher {
 
         let uuid = UUID::new(locator, user_id);
 
-        if self.appointments.lock().unwrap().contains_key(&uuid) {
+        // Triggered appointments waiting for the Responder are only found in the database
+        if self.appointments.lock().unwrap().contains_key(&uuid)
+            || self.pending_triggers.lock().unwrap().contains(&uuid)
+        {
             Ok(AppointmentInfo::Appointment(
                 self.dbm
                     .lock()
@@ -410,6 +768,226 @@ impl Watcher {
         }
     }
 
+    /// Deletes an [Appointment] from the tower at the user's request, giving back the slots it was using.
+    ///
+    /// Appointments can only be deleted provided:
+    /// - The user is registered into the system
+    /// - The user subscription has not expired
+    /// - The appointment belongs to the user
+    /// - The appointment is being watched by the [Watcher] and has not been triggered yet
+    /// - The appointment was started to be watched at `start_block`, which is covered by the user signature alongside
+    ///   the tower id (see [deletion_message]) so the request cannot be replayed against the appointment once sent again
+    ///
+    /// If the deletion succeeds, a [DeletionReceipt] signed by the tower is returned alongside the updated
+    /// number of available slots of the user.
+    pub(crate) fn delete_appointment(
+        &self,
+        locator: Locator,
+        start_block: u32,
+        user_signature: String,
+    ) -> Result<(DeletionReceipt, u32), DeleteAppointmentFailure> {
+        let message = deletion_message(&locator, start_block, &self.tower_id);
+
+        let user_id = self
+            .gatekeeper
+            .authenticate_user(message.as_bytes(), &user_signature)
+            .map_err(|_| DeleteAppointmentFailure::AuthenticationFailure)?;
+        self.gatekeeper
+            .check_rate_limit(user_id)
+            .map_err(|e| DeleteAppointmentFailure::RateLimited(e.0))?;
+
+        let (has_subscription_expired, expiry) =
+            self.gatekeeper.has_subscription_expired(user_id).unwrap();
+
+        if has_subscription_expired {
+            return Err(DeleteAppointmentFailure::SubscriptionExpired(expiry));
+        }
+
+        let uuid = UUID::new(locator, user_id);
+
+        // Holding the mempool triggers lock prevents the appointment from being triggered while it is being deleted
+        let _mempool_triggers = self.mempool_triggers.lock().unwrap();
+
+        // Appointments that have already been handed to the Responder cannot be withdrawn
+        if self.is_triggered(uuid) {
+            log::info!("Tracker for {uuid} already found in Responder");
+            return Err(DeleteAppointmentFailure::AlreadyTriggered);
+        }
+
+        if !self.appointments.lock().unwrap().contains_key(&uuid)
+            || self
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(uuid)
+                .map(|appointment| appointment.start_block)
+                != Some(start_block)
+        {
+            log::info!("Cannot find {locator} (start block: {start_block})");
+            return Err(DeleteAppointmentFailure::NotFound);
+        }
+
+        let updated_users = self
+            .gatekeeper
+            .delete_appointments_from_memory(&HashMap::from_iter([(uuid, user_id)]));
+        let available_slots = updated_users[&user_id].available_slots;
+        self.delete_appointments(
+            &HashSet::from_iter([uuid]),
+            &updated_users,
+            DeletionReason::Withdrawn,
+        );
+
+        let mut receipt = DeletionReceipt::new(
+            locator,
+            user_signature,
+            self.last_known_block_height.load(Ordering::Acquire),
+        );
+        receipt.sign(&self.signing_key);
+
+        Ok((receipt, available_slots))
+    }
+
+    /// Deletes a user from the tower alongside all their data, both the appointments being watched and the ones that
+    /// have already been handed to the [Responder].
+    ///
+    /// Returns the number of deleted appointments.
+    pub(crate) fn delete_user(&self, user_id: UserId) -> Result<usize, UserManagementFailure> {
+        // Holding the mempool triggers lock prevents the user appointments from being triggered while being deleted
+        let mut mempool_triggers = self.mempool_triggers.lock().unwrap();
+
+        // Breaches already queued will make it to the Responder no matter what, so the deletion has to wait for them
+        let user_info = self
+            .gatekeeper
+            .get_user_info(user_id)
+            .ok_or(UserManagementFailure::UserNotFound)?;
+        let pending_triggers = self.pending_triggers.lock().unwrap();
+        if user_info
+            .appointments
+            .keys()
+            .any(|uuid| pending_triggers.contains(uuid))
+        {
+            return Err(UserManagementFailure::PendingTriggers);
+        }
+        drop(pending_triggers);
+
+        let uuids = self.gatekeeper.delete_user(user_id)?;
+        let (trackers, appointments): (HashSet<UUID>, HashSet<UUID>) = uuids
+            .iter()
+            .copied()
+            .partition(|uuid| self.responder.has_tracker(*uuid));
+        self.delete_appointments_from_memory(&appointments, DeletionReason::UserDeleted);
+        self.responder.remove_user_trackers(&trackers);
+        mempool_triggers.retain(|uuid, _| !uuids.contains(uuid));
+
+        Ok(uuids.len())
+    }
+
+    /// Hands the breaches found in the given `(locator, transaction)` map to the [Responder].
+    ///
+    /// Appointments whose breaches are accepted by the [Responder] are removed from memory (their data is kept in the
+    /// database as trackers' FKs), while appointments resulting in invalid breaches, or in breaches rejected by the
+    /// [Responder], are deleted altogether.
+    fn handle_breaches(
+        &self,
+        locator_tx_map: HashMap<Locator, Transaction>,
+        mempool_triggers: &mut HashMap<UUID, Txid>,
+    ) {
+        // Filter out those breaches that do not yield a valid transaction
+        let (valid_breaches, invalid_breaches) =
+            self.filter_breaches(self.get_breaches(locator_tx_map));
+
+        // Send data to the Responder
+        let mut appointments_to_delete = HashSet::from_iter(invalid_breaches.into_keys());
+        let mut delivered_appointments = HashSet::new();
+        for (uuid, breach) in valid_breaches {
+            log::info!("Notifying Responder and deleting appointment (uuid: {uuid})");
+            // If the breach was already spotted in the mempool, the Responder will simply report the existing tracker
+            if mempool_triggers.remove(&uuid).is_some() {
+                self.dbm.lock().unwrap().remove_mempool_trigger(uuid);
+            }
+
+            if let ConfirmationStatus::Rejected(_) = self.responder.handle_breach(
+                uuid,
+                breach,
+                self.appointments.lock().unwrap()[&uuid].user_id,
+            ) {
+                appointments_to_delete.insert(uuid);
+            } else {
+                delivered_appointments.insert(uuid);
+            }
+        }
+
+        // Delete data
+        let appointments_to_delete_gatekeeper = {
+            let appointments = self.appointments.lock().unwrap();
+            appointments_to_delete
+                .iter()
+                .map(|uuid| (*uuid, appointments[uuid].user_id))
+                .collect()
+        };
+        self.delete_appointments_from_memory(&delivered_appointments, DeletionReason::Accepted);
+        self.delete_appointments(
+            &appointments_to_delete,
+            &self
+                .gatekeeper
+                .delete_appointments_from_memory(&appointments_to_delete_gatekeeper),
+            DeletionReason::Invalid,
+        );
+    }
+
+    /// Checks a batch of blocks that have not been processed by the [Watcher] for breaches of the stored appointments.
+    ///
+    /// This is meant to be used on bootstrap for blocks that were mined while the tower was offline but that will not
+    /// be connected by the chain monitor (e.g. when the tower has been forced to skip part of the chain), so the breaches
+    /// they contain are still answered. The blocks do not need to be part of the locator cache, so gaps deeper than the
+    /// cache can be rescanned too.
+    pub fn rescan<B: IndexableBlock>(&self, blocks: &[B]) {
+        let mut mempool_triggers = self.mempool_triggers.lock().unwrap();
+        if self.appointments.lock().unwrap().is_empty() {
+            return;
+        }
+
+        log::info!("Rescanning {} blocks for breaches", blocks.len());
+        let locator_tx_map = blocks
+            .iter()
+            .filter_map(|block| block.txdata())
+            .flatten()
+            .map(|tx| (Locator::new(tx.txid()), tx.clone()))
+            .collect();
+        self.handle_breaches(locator_tx_map, &mut mempool_triggers);
+    }
+
+    /// Stores the summary of a connected block into the database, keeping a rolling copy of the last [IRREVOCABLY_RESOLVED] blocks
+    /// (or `locator_cache_depth`, if deeper) so the tower indexes can be rebuilt on bootstrap even if the blocks have been pruned
+    /// by the backend. Transactions are only kept for the blocks covered by the locator cache.
+    fn store_block_summary(
+        &self,
+        header: &BlockHeader,
+        txdata: &chain::transaction::TransactionData,
+        height: u32,
+        locator_cache_depth: u32,
+    ) {
+        let txdata: Vec<Transaction> = txdata.iter().map(|(_, tx)| (*tx).clone()).collect();
+        let dbm = self.dbm.lock().unwrap();
+        if let Err(e) = dbm.store_block_summary(height, &BlockSummary::new(*header, &txdata, true))
+        {
+            log::error!(
+                "Couldn't store the summary of block {}. Error: {e:?}",
+                header.block_hash()
+            );
+        }
+        dbm.prune_block_summaries(
+            height.saturating_sub(std::cmp::max(IRREVOCABLY_RESOLVED, locator_cache_depth)),
+            height.saturating_sub(locator_cache_depth),
+        );
+    }
+
+    /// Checks whether an appointment has already been triggered, that is, whether it is held by the [Responder]
+    /// or queued to be handed to it.
+    fn is_triggered(&self, uuid: UUID) -> bool {
+        self.pending_triggers.lock().unwrap().contains(&uuid) || self.responder.has_tracker(uuid)
+    }
+
     /// Gets a map of breaches provided a map between locators and transactions.
     ///
     /// The provided map if intersected with the map of all locators monitored by [Watcher] and the result
@@ -494,22 +1072,186 @@ impl Watcher {
         (valid_breaches, invalid_breaches)
     }
 
-    // DISCUSS:: For outdated data this may be nicer if implemented with a callback from the GK given that:
-    // - The GK is queried for the data to be deleted
-    // - Appointment and tracker data can be deleted in cascade when a user is deleted
-    // If done, the GK can notify the Watcher and Responder to delete data in memory and
-    // take care of the database itself.
-
-    /// Deletes appointments from memory.
+    /// Checks the mempool for breaches.
     ///
-    /// The appointments are deleted from the appointments and locator_uuid_map maps.
-    /// Logs a different message depending on whether the appointments have been outdated, invalid, or accepted.
-    fn delete_appointments_from_memory(&self, uuids: &HashSet<UUID>, reason: DeletionReason) {
-        let mut appointments = self.appointments.lock().unwrap();
-        let mut locator_uuid_map = self.locator_uuid_map.lock().unwrap();
+    /// Locators are computed straight from the ids of the transactions in the mempool, so only the transactions matching
+    /// an appointment are pulled from `bitcoind`. Valid breaches are handed to the [Responder] straightaway, so the penalty
+    /// can make it to the same block as the dispute. However, the appointments are kept by the [Watcher] until the dispute
+    /// transaction is confirmed (see [filtered_block_connected](Self::filtered_block_connected)).
+    ///
+    /// Breaches that cannot be handled here (e.g. the penalty is rejected because of mempool policy) are simply left to be
+    /// detected once the dispute transaction is confirmed.
+    pub fn check_mempool(&self) {
+        // Holding this lock for the whole check prevents it from interleaving with the block processing.
+        let mut mempool_triggers = self.mempool_triggers.lock().unwrap();
+
+        if self.appointments.lock().unwrap().is_empty() {
+            return;
+        }
 
-        for uuid in uuids {
-            match reason {
+        let txids = self
+            .responder
+            .get_carrier()
+            .lock()
+            .unwrap()
+            .get_mempool_txids();
+        let candidates: Vec<Txid> = {
+            let locator_uuid_map = self.locator_uuid_map.lock().unwrap();
+            txids
+                .into_iter()
+                .filter(|txid| match locator_uuid_map.get(&Locator::new(*txid)) {
+                    Some(uuids) => uuids
+                        .iter()
+                        .any(|uuid| !mempool_triggers.contains_key(uuid)),
+                    None => false,
+                })
+                .collect()
+        };
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let locator_tx_map = {
+            let carrier = self.responder.get_carrier().lock().unwrap();
+            candidates
+                .iter()
+                .filter_map(|txid| {
+                    carrier
+                        .get_mempool_transaction(txid)
+                        .map(|tx| (Locator::new(*txid), tx))
+                })
+                .collect()
+        };
+
+        self.handle_mempool_breaches(locator_tx_map, &mut mempool_triggers);
+    }
+
+    /// Checks a set of transactions that have just made it to the mempool (e.g. notified via ZMQ) for breaches.
+    ///
+    /// Works as [check_mempool](Self::check_mempool), but the transactions are provided instead of pulled from `bitcoind`.
+    pub fn check_mempool_transactions(&self, txs: Vec<Transaction>) {
+        let mut mempool_triggers = self.mempool_triggers.lock().unwrap();
+
+        let locator_tx_map: HashMap<Locator, Transaction> = {
+            let locator_uuid_map = self.locator_uuid_map.lock().unwrap();
+            txs.into_iter()
+                .map(|tx| (Locator::new(tx.txid()), tx))
+                .filter(|(locator, _)| match locator_uuid_map.get(locator) {
+                    Some(uuids) => uuids
+                        .iter()
+                        .any(|uuid| !mempool_triggers.contains_key(uuid)),
+                    None => false,
+                })
+                .collect()
+        };
+
+        if !locator_tx_map.is_empty() {
+            self.handle_mempool_breaches(locator_tx_map, &mut mempool_triggers);
+        }
+    }
+
+    /// Hands the valid breaches found in the given mempool transactions to the [Responder], and deletes the appointments
+    /// found to be invalid.
+    fn handle_mempool_breaches(
+        &self,
+        locator_tx_map: HashMap<Locator, Transaction>,
+        mempool_triggers: &mut HashMap<UUID, Txid>,
+    ) {
+        let (valid_breaches, invalid_breaches) =
+            self.filter_breaches(self.get_breaches(locator_tx_map));
+
+        for (uuid, breach) in valid_breaches {
+            if mempool_triggers.contains_key(&uuid) {
+                continue;
+            }
+
+            let user_id = match self.appointments.lock().unwrap().get(&uuid) {
+                Some(appointment) => appointment.user_id,
+                None => continue,
+            };
+            let dispute_txid = breach.dispute_tx.txid();
+
+            log::info!("Notifying Responder of an unconfirmed breach (uuid: {uuid})");
+            let status = self.responder.handle_breach(uuid, breach, user_id);
+            if status.accepted() {
+                if let Err(e) = self
+                    .dbm
+                    .lock()
+                    .unwrap()
+                    .store_mempool_trigger(uuid, &dispute_txid)
+                {
+                    log::error!(
+                        "Couldn't persist the mempool trigger (uuid: {uuid}). Error: {e:?}"
+                    );
+                }
+                mempool_triggers.insert(uuid, dispute_txid);
+            } else {
+                log::info!(
+                    "Penalty for unconfirmed breach couldn't be broadcast (uuid: {uuid}). Waiting for the dispute to be confirmed"
+                );
+            }
+        }
+
+        // The decryption key is the dispute txid no matter whether the dispute is confirmed or not, so these are invalid for good.
+        let appointments_to_delete = HashSet::from_iter(invalid_breaches.into_keys());
+        let appointments_to_delete_gatekeeper = {
+            let appointments = self.appointments.lock().unwrap();
+            appointments_to_delete
+                .iter()
+                .map(|uuid| (*uuid, appointments[uuid].user_id))
+                .collect()
+        };
+        self.delete_appointments(
+            &appointments_to_delete,
+            &self
+                .gatekeeper
+                .delete_appointments_from_memory(&appointments_to_delete_gatekeeper),
+            DeletionReason::Invalid,
+        );
+    }
+
+    /// Checks whether the dispute transactions of the appointments triggered from the mempool are still there.
+    ///
+    /// Dispute transactions that have been replaced, or dropped, from the mempool cannot make it to the chain (at least for now), so the
+    /// corresponding trackers are removed from the [Responder] and the appointments are kept being watched by the [Watcher].
+    ///
+    /// This must be called once the breaches confirmed in a block have been processed, so the remaining triggers are all unconfirmed.
+    fn check_dropped_disputes(&self, mempool_triggers: &mut HashMap<UUID, Txid>) {
+        let carrier = self.responder.get_carrier().lock().unwrap();
+        let dropped: Vec<UUID> = mempool_triggers
+            .iter()
+            .filter(|(_, dispute_txid)| !carrier.in_mempool(dispute_txid))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        drop(carrier);
+
+        for uuid in dropped {
+            let dispute_txid = mempool_triggers.remove(&uuid).unwrap();
+            log::info!(
+                "Dispute transaction is no longer in mempool: {dispute_txid} (uuid: {uuid})"
+            );
+            self.dbm.lock().unwrap().remove_mempool_trigger(uuid);
+            self.responder.remove_tracker(uuid);
+        }
+    }
+
+    // DISCUSS:: For outdated data this may be nicer if implemented with a callback from the GK given that:
+    // - The GK is queried for the data to be deleted
+    // - Appointment and tracker data can be deleted in cascade when a user is deleted
+    // If done, the GK can notify the Watcher and Responder to delete data in memory and
+    // take care of the database itself.
+
+    /// Deletes appointments from memory.
+    ///
+    /// The appointments are deleted from the appointments and locator_uuid_map maps.
+    /// Logs a different message depending on whether the appointments have been outdated, invalid, or accepted.
+    fn delete_appointments_from_memory(&self, uuids: &HashSet<UUID>, reason: DeletionReason) {
+        let mut appointments = self.appointments.lock().unwrap();
+        let mut locator_uuid_map = self.locator_uuid_map.lock().unwrap();
+
+        for uuid in uuids {
+            match reason {
                 DeletionReason::Outdated => {
                     log::info!("End time reached by {uuid} without breach. Deleting appointment")
                 }
@@ -519,6 +1261,12 @@ impl Watcher {
                 DeletionReason::Accepted => {
                     log::info!("{uuid} accepted by the Responder. Deleting appointment")
                 }
+                DeletionReason::Withdrawn => {
+                    log::info!("{uuid} withdrawn by the user. Deleting appointment")
+                }
+                DeletionReason::UserDeleted => {
+                    log::info!("{uuid} belongs to a deleted user. Deleting appointment")
+                }
             };
             match appointments.remove(uuid) {
                 Some(appointment) => {
@@ -556,6 +1304,16 @@ impl Watcher {
         }
     }
 
+    /// Gets the minimum `to_self_delay` an [Appointment] must have to be accepted by the tower.
+    pub(crate) fn get_min_to_self_delay(&self) -> u16 {
+        self.min_to_self_delay
+    }
+
+    /// Gets the subscription tiers offered by the tower.
+    pub(crate) fn get_subscription_tiers(&self) -> Vec<SubscriptionTier> {
+        self.gatekeeper.get_tiers().to_vec()
+    }
+
     /// Ges the number of users currently registered with the tower.
     pub(crate) fn get_registered_users_count(&self) -> usize {
         self.gatekeeper.get_registered_users_count()
@@ -607,6 +1365,45 @@ impl Watcher {
         self.gatekeeper.get_user_info(user_id)
     }
 
+    /// Checks whether a given user has been banned from the tower.
+    pub(crate) fn is_user_banned(&self, user_id: UserId) -> bool {
+        self.gatekeeper.is_banned(user_id)
+    }
+
+    /// Bans a user from the tower.
+    pub(crate) fn ban_user(&self, user_id: UserId) -> Result<(), UserManagementFailure> {
+        self.gatekeeper.ban_user(user_id)
+    }
+
+    /// Lifts the ban of a user.
+    pub(crate) fn unban_user(&self, user_id: UserId) -> Result<(), UserManagementFailure> {
+        self.gatekeeper.unban_user(user_id)
+    }
+
+    /// Grants a number of extra slots to a user.
+    pub(crate) fn grant_slots(
+        &self,
+        user_id: UserId,
+        slots: u32,
+    ) -> Result<UserInfo, UserManagementFailure> {
+        self.gatekeeper.grant_slots(user_id, slots)
+    }
+
+    /// Sets the expiry of the subscription of a user.
+    pub(crate) fn set_subscription_expiry(
+        &self,
+        user_id: UserId,
+        subscription_expiry: u32,
+    ) -> Result<UserInfo, UserManagementFailure> {
+        self.gatekeeper
+            .set_subscription_expiry(user_id, subscription_expiry)
+    }
+
+    /// Gets the audit log of admin actions.
+    pub(crate) fn get_audit_log(&self) -> Vec<AuditLogEntry> {
+        self.gatekeeper.get_audit_log()
+    }
+
     /// Gets information about a user's subscription.
     pub(crate) fn get_subscription_info(
         &self,
@@ -618,6 +1415,9 @@ impl Watcher {
             .gatekeeper
             .authenticate_user(message.as_bytes(), signature)
             .map_err(|_| GetSubscriptionInfoFailure::AuthenticationFailure)?;
+        self.gatekeeper
+            .check_rate_limit(user_id)
+            .map_err(|e| GetSubscriptionInfoFailure::RateLimited(e.0))?;
 
         let (has_subscription_expired, expiry) =
             self.gatekeeper.has_subscription_expired(user_id).unwrap();
@@ -656,8 +1456,8 @@ impl Watcher {
 impl chain::Listen for Watcher {
     /// Handles the monitoring process by the [Watcher].
     ///
-    /// Watching is performed in a per-block basis. Therefore, a breach is only considered (and detected) if seen
-    /// in a block.
+    /// Watching is performed in a per-block basis. Breaches can also be spotted early in the mempool (see [Watcher::check_mempool]),
+    /// but they are only considered final once seen in a block.
     ///
     /// Every time a new block is received a list of all potential locators is computed using the transaction data.
     /// Then, the potential locators are checked against the data being monitored by the [Watcher] and passed to the
@@ -678,56 +1478,27 @@ impl chain::Listen for Watcher {
             .map(|(_, tx)| (Locator::new(tx.txid()), (*tx).clone()))
             .collect();
 
-        self.locator_cache
-            .lock()
-            .unwrap()
-            .update(*header, &locator_tx_map);
+        let locator_cache_depth = {
+            let mut locator_cache = self.locator_cache.lock().unwrap();
+            locator_cache.update(*header, &locator_tx_map);
+            locator_cache.size() as u32
+        };
+        self.store_block_summary(header, txdata, height, locator_cache_depth);
 
+        let mut mempool_triggers = self.mempool_triggers.lock().unwrap();
         if !self.appointments.lock().unwrap().is_empty() {
             // Start by removing outdated data so it is not taken into account from this point on
-            self.delete_appointments_from_memory(
-                &self.gatekeeper.get_outdated_appointments(height),
-                DeletionReason::Outdated,
-            );
-
-            // Filter out those breaches that do not yield a valid transaction
-            let (valid_breaches, invalid_breaches) =
-                self.filter_breaches(self.get_breaches(locator_tx_map));
+            let outdated_appointments = self.gatekeeper.get_outdated_appointments(height);
+            self.delete_appointments_from_memory(&outdated_appointments, DeletionReason::Outdated);
+            mempool_triggers.retain(|uuid, _| !outdated_appointments.contains(uuid));
 
-            // Send data to the Responder
-            let mut appointments_to_delete = HashSet::from_iter(invalid_breaches.into_keys());
-            let mut delivered_appointments = HashSet::new();
-            for (uuid, breach) in valid_breaches {
-                log::info!("Notifying Responder and deleting appointment (uuid: {uuid})");
+            self.handle_breaches(locator_tx_map, &mut mempool_triggers);
 
-                if let ConfirmationStatus::Rejected(_) = self.responder.handle_breach(
-                    uuid,
-                    breach,
-                    self.appointments.lock().unwrap()[&uuid].user_id,
-                ) {
-                    appointments_to_delete.insert(uuid);
-                } else {
-                    delivered_appointments.insert(uuid);
-                }
+            // Check the disputes of the breaches triggered from the mempool that have not been confirmed in this block
+            if !mempool_triggers.is_empty() {
+                self.check_dropped_disputes(&mut mempool_triggers);
             }
 
-            // Delete data
-            let appointments_to_delete_gatekeeper = {
-                let appointments = self.appointments.lock().unwrap();
-                appointments_to_delete
-                    .iter()
-                    .map(|uuid| (*uuid, appointments[uuid].user_id))
-                    .collect()
-            };
-            self.delete_appointments_from_memory(&delivered_appointments, DeletionReason::Accepted);
-            self.delete_appointments(
-                &appointments_to_delete,
-                &self
-                    .gatekeeper
-                    .delete_appointments_from_memory(&appointments_to_delete_gatekeeper),
-                DeletionReason::Invalid,
-            );
-
             if self.appointments.lock().unwrap().is_empty() {
                 log::info!("No more pending appointments");
             }
@@ -747,6 +1518,7 @@ impl chain::Listen for Watcher {
             .lock()
             .unwrap()
             .remove_disconnected_block(&header.block_hash());
+        self.dbm.lock().unwrap().remove_block_summary(height);
         self.last_known_block_height
             .store(height - 1, Ordering::Release);
     }
@@ -756,23 +1528,30 @@ impl chain::Listen for Watcher {
 mod tests {
     use super::*;
     use std::ops::Deref;
-    use std::sync::{Arc, Mutex};
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::Duration;
 
+    use crate::carrier::Carrier;
     use crate::dbm::DBM;
     use crate::responder::ConfirmationStatus;
     use crate::rpc_errors;
     use crate::test_utils::{
         create_carrier, create_responder, create_watcher, generate_dummy_appointment,
-        generate_dummy_appointment_with_user, generate_uuid, get_random_breach, get_random_tx,
-        store_appointment_and_fks_to_db, BitcoindMock, BitcoindStopper, Blockchain, MockOptions,
-        MockedServerQuery, AVAILABLE_SLOTS, DURATION, EXPIRY_DELTA, SLOTS, START_HEIGHT,
-        SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
+        generate_dummy_appointment_with_user, generate_uuid, get_last_n_blocks, get_random_breach,
+        get_random_tracker, get_random_tx, store_appointment_and_fks_to_db, BitcoindMock,
+        BitcoindStopper, Blockchain, MockOptions, MockedServerQuery, AVAILABLE_SLOTS, DURATION,
+        EXPIRY_DELTA, MIN_TO_SELF_DELAY, SLOTS, START_HEIGHT, SUBSCRIPTION_EXPIRY,
+        SUBSCRIPTION_START,
     };
     use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+    use teos_common::test_utils::TX_HEX;
 
+    use bitcoin::consensus;
     use bitcoin::hash_types::Txid;
+    use bitcoin::hashes::hex::FromHex;
     use bitcoin::hashes::Hash;
     use bitcoin::secp256k1::{PublicKey, Secp256k1};
+    use bitcoincore_rpc::{Auth, Client as BitcoindClient};
 
     use lightning::chain::Listen;
 
@@ -787,6 +1566,13 @@ mod tests {
     impl Eq for Watcher {}
 
     impl Watcher {
+        /// Waits until all the queued breaches have been handled by the Responder.
+        pub(crate) fn wait_for_queued_breaches(&self) {
+            while !self.pending_triggers.lock().unwrap().is_empty() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
         pub(crate) fn add_dummy_tracker_to_responder(
             &self,
             uuid: UUID,
@@ -809,9 +1595,17 @@ mod tests {
 
     async fn init_watcher_with_db(
         chain: &mut Blockchain,
-        dbm: Arc<Mutex<DBM>>,
+        dbm: Arc<Mutex<dyn Storage>>,
     ) -> (Watcher, BitcoindStopper) {
-        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        init_watcher_with_options(chain, dbm, MockOptions::default()).await
+    }
+
+    async fn init_watcher_with_options(
+        chain: &mut Blockchain,
+        dbm: Arc<Mutex<dyn Storage>>,
+        options: MockOptions,
+    ) -> (Watcher, BitcoindStopper) {
+        let bitcoind_mock = BitcoindMock::new(options);
 
         let gk = Arc::new(Gatekeeper::new(
             chain.get_block_count(),
@@ -858,7 +1652,7 @@ mod tests {
 
         let (user_sk, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        watcher.register(user_id).unwrap();
+        watcher.register(user_id, "").unwrap();
         let appointment = generate_dummy_appointment(None).inner;
 
         // If we add some trackers to the system and create a new Responder reusing the same db
@@ -887,7 +1681,7 @@ mod tests {
 
         let (_, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        let receipt = watcher.register(user_id).unwrap();
+        let receipt = watcher.register(user_id, "").unwrap();
 
         assert_eq!(receipt.user_id(), user_id);
         assert_eq!(receipt.available_slots(), SLOTS);
@@ -903,6 +1697,69 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_renew() {
+        // Same as with register, the renewal logic is covered in the Gatekeeper
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        // Subscriptions cannot be renewed right after registering
+        assert_eq!(
+            watcher.renew(user_id),
+            Err(RegistrationFailure::RenewalTooEarly(
+                START_HEIGHT as u32 + 1
+            ))
+        );
+
+        // Expired subscriptions start over once renewed
+        watcher
+            .gatekeeper
+            .get_registered_users()
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .subscription_expiry = START_HEIGHT as u32;
+        let receipt = watcher.renew(user_id).unwrap();
+
+        assert_eq!(receipt.user_id(), user_id);
+        assert_eq!(receipt.subscription_start(), START_HEIGHT as u32);
+        assert_eq!(
+            receipt.subscription_expiry(),
+            START_HEIGHT as u32 + DURATION
+        );
+        assert!(receipt.verify(&watcher.tower_id));
+    }
+
+    #[tokio::test]
+    async fn test_top_up() {
+        // Same as with register, the top up logic is covered in the Gatekeeper
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        assert_eq!(
+            watcher.top_up(user_id),
+            Err(RegistrationFailure::UserNotFound)
+        );
+
+        watcher.register(user_id, "").unwrap();
+        let receipt = watcher.top_up(user_id).unwrap();
+
+        assert_eq!(receipt.user_id(), user_id);
+        assert_eq!(receipt.available_slots(), SLOTS * 2);
+        assert_eq!(
+            receipt.subscription_expiry(),
+            START_HEIGHT as u32 + DURATION
+        );
+        assert!(receipt.verify(&watcher.tower_id));
+    }
+
     #[tokio::test]
     async fn test_add_appointment() {
         let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
@@ -925,7 +1782,7 @@ mod tests {
         ));
         let (user_sk, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        watcher.register(user_id).unwrap();
+        watcher.register(user_id, "").unwrap();
         let appointment = generate_dummy_appointment(None).inner;
 
         // Add the appointment for a new user (twice so we can check that updates work)
@@ -941,7 +1798,7 @@ mod tests {
         // Add the same appointment but for another user
         let (user2_sk, user2_pk) = get_random_keypair();
         let user2_id = UserId(user2_pk);
-        watcher.register(user2_id).unwrap();
+        watcher.register(user2_id, "").unwrap();
 
         let user2_sig = cryptography::sign(&appointment.to_vec(), &user2_sk).unwrap();
         let (receipt, slots, expiry) = watcher
@@ -1000,6 +1857,7 @@ mod tests {
 
         // The appointment should have been accepted, slots should have been decreased, and data should have been deleted from
         // the Watcher's memory. Moreover, a new tracker should be found in the Responder
+        watcher.wait_for_queued_breaches();
         assert_appointment_added(slots, SLOTS - 3, expiry, receipt, &user_sig, tower_id);
         assert_eq!(watcher.appointments.lock().unwrap().len(), 3);
         assert!(!watcher
@@ -1037,7 +1895,7 @@ mod tests {
             MockedServerQuery::Error(rpc_errors::RPC_VERIFY_ERROR as i64),
             chain.tip().deref().height,
         );
-        *watcher.responder.get_carrier().lock().unwrap() = carrier;
+        *watcher.responder.get_carrier().lock().unwrap() = Box::new(carrier);
 
         let dispute_tx = &tip_txs[tip_txs.len() - 2];
         let invalid_appointment = generate_dummy_appointment(Some(&dispute_tx.txid())).inner;
@@ -1045,6 +1903,7 @@ mod tests {
         let (receipt, slots, expiry) = watcher
             .add_appointment(invalid_appointment, user_sig.clone())
             .unwrap();
+        watcher.wait_for_queued_breaches();
 
         assert_appointment_added(slots, SLOTS - 4, expiry, receipt, &user_sig, tower_id);
         assert_eq!(watcher.appointments.lock().unwrap().len(), 3);
@@ -1082,7 +1941,10 @@ mod tests {
 
         assert!(matches!(
             watcher.add_appointment(new_appointment, new_app_sig),
-            Err(AddAppointmentFailure::NotEnoughSlots)
+            Err(AddAppointmentFailure::NotEnoughSlots {
+                required_slots: 1,
+                available_slots: 0
+            })
         ));
         // Data should not be in the database
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
@@ -1099,10 +1961,252 @@ mod tests {
 
         assert!(matches!(
             watcher.add_appointment(appointment, user2_sig),
-            Err(AddAppointmentFailure::SubscriptionExpired { .. })
+            Err(AddAppointmentFailure::SubscriptionExpired {
+                expiry,
+                current_height
+            }) if expiry == START_HEIGHT as u32 && current_height == START_HEIGHT as u32
         ));
         // Data should not be in the database
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
+
+        // If the appointment to_self_delay is below the tower's minimum, the appointment should be rejected.
+        let (user3_sk, user3_pk) = get_random_keypair();
+        let user3_id = UserId(user3_pk);
+        watcher.register(user3_id, "").unwrap();
+        let mut short_delay_appointment = generate_dummy_appointment(None).inner;
+        short_delay_appointment.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+        let short_delay_uuid = UUID::new(short_delay_appointment.locator, user3_id);
+        let user3_sig = cryptography::sign(&short_delay_appointment.to_vec(), &user3_sk).unwrap();
+
+        assert!(matches!(
+            watcher.add_appointment(short_delay_appointment.clone(), user3_sig),
+            Err(AddAppointmentFailure::ToSelfDelayTooSmall(
+                MIN_TO_SELF_DELAY
+            ))
+        ));
+        // Data should not be in the database and no slots should have been consumed
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_appointment(short_delay_uuid)
+            .is_none());
+        assert_eq!(
+            watcher.gatekeeper.get_registered_users().lock().unwrap()[&user3_id].available_slots,
+            SLOTS
+        );
+
+        // An appointment matching the minimum is accepted
+        short_delay_appointment.to_self_delay = MIN_TO_SELF_DELAY as u32;
+        let user3_sig = cryptography::sign(&short_delay_appointment.to_vec(), &user3_sk).unwrap();
+        assert!(watcher
+            .add_appointment(short_delay_appointment, user3_sig)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let tip_txs = chain.blocks.last().unwrap().txdata.clone();
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let tower_id = TowerId(PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &watcher.signing_key,
+        ));
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+
+        // If the user cannot be authenticated (using the first appointment), the whole batch is rejected
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.add_appointments(vec![(appointment.clone(), user_sig.clone())]),
+            Err(AddAppointmentFailure::AuthenticationFailure)
+        ));
+        watcher.register(user_id, "").unwrap();
+
+        // Otherwise, each appointment is added or rejected on its own
+        let (other_sk, _) = get_random_keypair();
+        let wrongly_signed = generate_dummy_appointment(None).inner;
+        let mut short_delay = generate_dummy_appointment(None).inner;
+        short_delay.to_self_delay = MIN_TO_SELF_DELAY as u32 - 1;
+        let mut update = appointment.clone();
+        update.encrypted_blob.reverse();
+        let dispute_tx = tip_txs.last().unwrap();
+        let (triggered_uuid, triggered) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+
+        let batch = vec![
+            (appointment.clone(), user_sig.clone()),
+            (
+                wrongly_signed.clone(),
+                cryptography::sign(&wrongly_signed.to_vec(), &other_sk).unwrap(),
+            ),
+            (
+                short_delay.clone(),
+                cryptography::sign(&short_delay.to_vec(), &user_sk).unwrap(),
+            ),
+            (
+                update.clone(),
+                cryptography::sign(&update.to_vec(), &user_sk).unwrap(),
+            ),
+            (
+                triggered.inner.clone(),
+                cryptography::sign(&triggered.inner.to_vec(), &user_sk).unwrap(),
+            ),
+        ];
+
+        let (results, slots, expiry) = watcher.add_appointments(batch.clone()).unwrap();
+        assert_eq!(results.len(), batch.len());
+        assert_eq!(slots, SLOTS - 2);
+        assert_eq!(expiry, START_HEIGHT as u32 + DURATION);
+
+        let (receipt, slots) = results[0].as_ref().unwrap();
+        assert_appointment_added(
+            *slots,
+            SLOTS - 1,
+            expiry,
+            receipt.clone(),
+            &user_sig,
+            tower_id,
+        );
+        assert!(matches!(
+            results[1],
+            Err(AddAppointmentFailure::AuthenticationFailure)
+        ));
+        assert!(matches!(
+            results[2],
+            Err(AddAppointmentFailure::ToSelfDelayTooSmall(
+                MIN_TO_SELF_DELAY
+            ))
+        ));
+        // The update does not consume additional slots
+        let (receipt, slots) = results[3].as_ref().unwrap();
+        assert_appointment_added(
+            *slots,
+            SLOTS - 1,
+            expiry,
+            receipt.clone(),
+            &batch[3].1,
+            tower_id,
+        );
+        let (receipt, slots) = results[4].as_ref().unwrap();
+        assert_appointment_added(
+            *slots,
+            SLOTS - 2,
+            expiry,
+            receipt.clone(),
+            &batch[4].1,
+            tower_id,
+        );
+
+        // The accepted appointment is in memory and in the database (updated), the rejected ones are nowhere to be found
+        let uuid = UUID::new(appointment.locator, user_id);
+        assert_eq!(watcher.appointments.lock().unwrap().len(), 1);
+        assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert_eq!(
+            watcher
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(uuid)
+                .unwrap()
+                .inner,
+            update
+        );
+        for a in [&wrongly_signed, &short_delay] {
+            assert!(watcher
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(UUID::new(a.locator, user_id))
+                .is_none());
+        }
+
+        // The triggered one went straight to the Responder
+        watcher.wait_for_queued_breaches();
+        assert!(watcher.responder.has_tracker(triggered_uuid));
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_tracker(triggered_uuid)
+            .is_some());
+
+        // The user slots are persisted too
+        assert_eq!(
+            watcher
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap()
+                .available_slots,
+            SLOTS - 2
+        );
+
+        // If the user subscription has expired, the whole batch is rejected
+        watcher
+            .gatekeeper
+            .get_registered_users()
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .subscription_expiry = START_HEIGHT as u32;
+        assert!(matches!(
+            watcher.add_appointments(batch),
+            Err(AddAppointmentFailure::SubscriptionExpired { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_storage_failure() {
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let dispute_tx = chain.blocks.last().unwrap().txdata.last().unwrap().clone();
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        // Remove the user from the database behind the Watcher's back, so the appointments cannot be stored
+        watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .batch_remove_users(&HashSet::from_iter([user_id]));
+
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        let (triggered_uuid, triggered) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+        let batch = vec![appointment.inner, triggered.inner]
+            .into_iter()
+            .map(|a| {
+                let signature = cryptography::sign(&a.to_vec(), &user_sk).unwrap();
+                (a, signature)
+            })
+            .collect();
+
+        // The whole batch is rejected and nothing is applied in memory
+        assert!(matches!(
+            watcher.add_appointments(batch),
+            Err(AddAppointmentFailure::StorageFailure)
+        ));
+        assert!(watcher.appointments.lock().unwrap().is_empty());
+        assert!(!watcher.is_triggered(triggered_uuid));
+        let user_info = watcher.gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert!(!user_info.appointments.contains_key(&uuid));
+        assert!(!user_info.appointments.contains_key(&triggered_uuid));
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_queued_breaches()
+            .is_empty());
     }
 
     #[tokio::test]
@@ -1113,7 +2217,7 @@ mod tests {
         // Register the user
         let (_, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        watcher.register(user_id).unwrap();
+        watcher.register(user_id, "").unwrap();
 
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
 
@@ -1174,17 +2278,18 @@ mod tests {
         // Register the user
         let (_, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        watcher.register(user_id).unwrap();
+        watcher.register(user_id, "").unwrap();
 
         let dispute_tx = get_random_tx();
         let (uuid, appointment) =
             generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
 
-        // Valid triggered appointments should be accepted by the Responder
+        // Valid triggered appointments should be queued and accepted by the Responder
         assert_eq!(
             watcher.store_triggered_appointment(uuid, &appointment, user_id, &dispute_tx),
-            TriggeredAppointment::Accepted,
+            TriggeredAppointment::Queued,
         );
+        watcher.wait_for_queued_breaches();
         // In this case the appointment is kept in the Responder and, therefore, in the database
         assert!(watcher.responder.has_tracker(uuid));
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_some());
@@ -1195,14 +2300,15 @@ mod tests {
             MockedServerQuery::Error(rpc_errors::RPC_VERIFY_ERROR as i64),
             chain.tip().deref().height,
         );
-        *watcher.responder.get_carrier().lock().unwrap() = carrier;
+        *watcher.responder.get_carrier().lock().unwrap() = Box::new(carrier);
         let dispute_tx = get_random_tx();
         let (uuid, appointment) =
             generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
         assert_eq!(
             watcher.store_triggered_appointment(uuid, &appointment, user_id, &dispute_tx),
-            TriggeredAppointment::Rejected,
+            TriggeredAppointment::Queued,
         );
+        watcher.wait_for_queued_breaches();
         // In this case the appointment is not kept in the Responder nor in the database
         assert!(!watcher.responder.has_tracker(uuid));
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
@@ -1220,6 +2326,141 @@ mod tests {
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
     }
 
+    #[tokio::test]
+    async fn test_add_triggered_appointment_bitcoind_unreachable() {
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let dispute_tx = chain.blocks.last().unwrap().txdata.last().unwrap().clone();
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        // Register the user
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        // Update the Responder with a Carrier whose bitcoind is flagged as unreachable, so the breach is held until the
+        // flag is set back
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(false), Condvar::new()));
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        *watcher.responder.get_carrier().lock().unwrap() = Box::new(Carrier::new(
+            bitcoin_cli,
+            bitcoind_reachable.clone(),
+            chain.get_block_count(),
+        ));
+
+        // The receipt for an appointment whose trigger is in the cache is returned straightaway
+        let (uuid, appointment) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        let (receipt, slots, expiry) = watcher
+            .add_appointment(appointment.inner.clone(), user_sig.clone())
+            .unwrap();
+        assert_appointment_added(
+            slots,
+            SLOTS - 1,
+            expiry,
+            receipt,
+            &user_sig,
+            watcher.tower_id,
+        );
+
+        // The breach is queued until bitcoind is reachable again
+        assert!(watcher.pending_triggers.lock().unwrap().contains(&uuid));
+        assert!(!watcher.responder.has_tracker(uuid));
+        assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_some());
+
+        // Meanwhile, the appointment can be queried but it cannot be sent again nor deleted
+        assert!(matches!(
+            watcher.get_appointment(
+                appointment.locator(),
+                &cryptography::sign(
+                    format!("get appointment {}", appointment.locator()).as_bytes(),
+                    &user_sk
+                )
+                .unwrap()
+            ),
+            Ok(AppointmentInfo::Appointment(a)) if a == appointment.inner
+        ));
+        assert!(matches!(
+            watcher.add_appointment(appointment.inner.clone(), user_sig),
+            Err(AddAppointmentFailure::AlreadyTriggered)
+        ));
+        assert!(matches!(
+            watcher.delete_appointment(
+                appointment.locator(),
+                appointment.start_block,
+                cryptography::sign(
+                    deletion_message(
+                        &appointment.locator(),
+                        appointment.start_block,
+                        &watcher.tower_id
+                    )
+                    .as_bytes(),
+                    &user_sk
+                )
+                .unwrap()
+            ),
+            Err(DeleteAppointmentFailure::AlreadyTriggered)
+        ));
+
+        // The breach is still queued after all the above
+        assert!(watcher.pending_triggers.lock().unwrap().contains(&uuid));
+        assert!(!watcher.responder.has_tracker(uuid));
+
+        // Once bitcoind is back, the broadcast is retried and the tracker is added to the Responder
+        let (reachable, notifier) = &*bitcoind_reachable;
+        *reachable.lock().unwrap() = true;
+        notifier.notify_all();
+
+        watcher.wait_for_queued_breaches();
+        assert!(watcher.responder.has_tracker(uuid));
+        assert!(watcher.dbm.lock().unwrap().load_tracker(uuid).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queued_breaches_survive_restart() {
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let dispute_tx = chain.blocks.last().unwrap().txdata.last().unwrap().clone();
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (watcher, _s) = init_watcher_with_db(&mut chain, dbm.clone()).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        // Queue a breach while bitcoind is flagged as unreachable
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        *watcher.responder.get_carrier().lock().unwrap() = Box::new(Carrier::new(
+            bitcoin_cli,
+            Arc::new((Mutex::new(false), Condvar::new())),
+            chain.get_block_count(),
+        ));
+        let (uuid, appointment) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+        assert!(watcher.pending_triggers.lock().unwrap().contains(&uuid));
+        assert!(dbm
+            .lock()
+            .unwrap()
+            .load_queued_breaches()
+            .contains_key(&uuid));
+
+        // The tower restarts before the breach is handled. The breach is queued again on bootstrap and makes it to the
+        // Responder, instead of being watched as a regular appointment
+        let (another_w, _as) = init_watcher_with_db(&mut chain, dbm.clone()).await;
+        assert!(!another_w.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(another_w.is_triggered(uuid));
+
+        another_w.wait_for_queued_breaches();
+        assert!(another_w.responder.has_tracker(uuid));
+        assert!(dbm.lock().unwrap().load_tracker(uuid).is_some());
+        assert!(dbm.lock().unwrap().load_queued_breaches().is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_appointment() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -1237,7 +2478,7 @@ mod tests {
         // If the user does exist and there's an appointment with the given locator belonging to him, it will be returned
         let (user_sk, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk);
-        watcher.register(user_id).unwrap();
+        watcher.register(user_id, "").unwrap();
         watcher
             .add_appointment(
                 appointment.clone(),
@@ -1297,16 +2538,179 @@ mod tests {
             AppointmentInfo::Tracker(t) => assert_eq!(t, tracker),
         }
 
-        // If the user does exists but the requested locator does not belong to any of their associated appointments, NotFound
-        // should be returned.
-        let (user2_sk, user2_pk) = get_random_keypair();
-        let user2_id = UserId(user2_pk);
-        watcher.register(user2_id).unwrap();
+        // If the user does exists but the requested locator does not belong to any of their associated appointments, NotFound
+        // should be returned.
+        let (user2_sk, user2_pk) = get_random_keypair();
+        let user2_id = UserId(user2_pk);
+        watcher.register(user2_id, "").unwrap();
+
+        let signature2 = cryptography::sign(message.as_bytes(), &user2_sk).unwrap();
+        assert!(matches!(
+            watcher.get_appointment(appointment.locator, &signature2),
+            Err(GetAppointmentFailure::NotFound { .. })
+        ));
+
+        // If the user subscription has expired, the request will fail
+        watcher
+            .gatekeeper
+            .get_registered_users()
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .subscription_expiry = START_HEIGHT as u32;
+
+        assert!(matches!(
+            watcher.get_appointment(appointment.locator, &signature),
+            Err(GetAppointmentFailure::SubscriptionExpired { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_appointment() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let start_block = START_HEIGHT as u32;
+        let message = deletion_message(&appointment.locator, start_block, &watcher.tower_id);
+
+        // If the user cannot be properly identified, the request will fail. This can be simulated by providing a wrong signature
+        let wrong_sig = String::from_utf8((0..65).collect()).unwrap();
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block, wrong_sig),
+            Err(DeleteAppointmentFailure::AuthenticationFailure)
+        ));
+
+        // If the user is registered but the appointment cannot be found, NotFound is returned
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let available_slots = watcher.register(user_id, "").unwrap().available_slots();
+        let signature = cryptography::sign(message.as_bytes(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block, signature.clone()),
+            Err(DeleteAppointmentFailure::NotFound)
+        ));
+
+        // If the appointment belongs to the user, it is deleted from memory and the database and the slots are given back
+        watcher
+            .add_appointment(
+                appointment.clone(),
+                cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+            )
+            .unwrap();
+        let uuid = UUID::new(appointment.locator, user_id);
+        assert_eq!(
+            watcher
+                .gatekeeper
+                .get_user_info(user_id)
+                .unwrap()
+                .available_slots,
+            available_slots - 1
+        );
+
+        // Other users cannot delete it though
+        let (user2_sk, user2_pk) = get_random_keypair();
+        watcher.register(UserId(user2_pk), "").unwrap();
+        let signature2 = cryptography::sign(message.as_bytes(), &user2_sk).unwrap();
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block, signature2),
+            Err(DeleteAppointmentFailure::NotFound)
+        ));
+
+        // Signatures are bound to the tower, and to the block the appointment was started to be watched at
+        let other_tower_signature = cryptography::sign(
+            deletion_message(
+                &appointment.locator,
+                start_block,
+                &TowerId(get_random_keypair().1),
+            )
+            .as_bytes(),
+            &user_sk,
+        )
+        .unwrap();
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block, other_tower_signature),
+            Err(DeleteAppointmentFailure::AuthenticationFailure)
+        ));
+        let wrong_block_signature = cryptography::sign(
+            deletion_message(&appointment.locator, start_block + 1, &watcher.tower_id).as_bytes(),
+            &user_sk,
+        )
+        .unwrap();
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block + 1, wrong_block_signature),
+            Err(DeleteAppointmentFailure::NotFound)
+        ));
+
+        let (receipt, slots) = watcher
+            .delete_appointment(appointment.locator, start_block, signature.clone())
+            .unwrap();
+        assert_eq!(slots, available_slots);
+        assert_eq!(receipt.locator(), appointment.locator);
+        assert_eq!(receipt.user_signature(), signature);
+        assert_eq!(receipt.deletion_block(), START_HEIGHT as u32);
+        assert!(receipt.verify(&watcher.tower_id));
+
+        assert!(!watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(!watcher
+            .locator_uuid_map
+            .lock()
+            .unwrap()
+            .contains_key(&appointment.locator));
+        assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
+        let user_info = watcher.gatekeeper.get_user_info(user_id).unwrap();
+        assert_eq!(user_info.available_slots, available_slots);
+        assert!(!user_info.appointments.contains_key(&uuid));
+        assert_eq!(
+            watcher
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .unwrap()
+                .available_slots,
+            available_slots
+        );
+
+        // The request cannot be replayed once the appointment is sent again
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        let receipt = watcher
+            .add_appointment(
+                appointment.clone(),
+                cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+            )
+            .unwrap()
+            .0;
+        assert_eq!(receipt.start_block(), start_block + 1);
+        assert!(matches!(
+            watcher.delete_appointment(appointment.locator, start_block, signature.clone()),
+            Err(DeleteAppointmentFailure::NotFound)
+        ));
+        assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
 
-        let signature2 = cryptography::sign(message.as_bytes(), &user2_sk).unwrap();
+        // Appointments that have already been triggered cannot be deleted
+        let (triggered_uuid, triggered_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+        watcher.add_random_tracker_to_responder(triggered_uuid);
+        let triggered_signature = cryptography::sign(
+            deletion_message(
+                &triggered_appointment.locator(),
+                triggered_appointment.start_block,
+                &watcher.tower_id,
+            )
+            .as_bytes(),
+            &user_sk,
+        )
+        .unwrap();
         assert!(matches!(
-            watcher.get_appointment(appointment.locator, &signature2),
-            Err(GetAppointmentFailure::NotFound { .. })
+            watcher.delete_appointment(
+                triggered_appointment.locator(),
+                triggered_appointment.start_block,
+                triggered_signature
+            ),
+            Err(DeleteAppointmentFailure::AlreadyTriggered)
         ));
 
         // If the user subscription has expired, the request will fail
@@ -1320,8 +2724,8 @@ mod tests {
             .subscription_expiry = START_HEIGHT as u32;
 
         assert!(matches!(
-            watcher.get_appointment(appointment.locator, &signature),
-            Err(GetAppointmentFailure::SubscriptionExpired { .. })
+            watcher.delete_appointment(appointment.locator, start_block, signature),
+            Err(DeleteAppointmentFailure::SubscriptionExpired { .. })
         ));
     }
 
@@ -1402,7 +2806,7 @@ mod tests {
                     .insert(*locator, HashSet::from_iter(vec![uuid]));
 
                 // Store data in the database (the user needs to be there as well since it is a FK for appointments)
-                store_appointment_and_fks_to_db(&watcher.dbm.lock().unwrap(), uuid, &appointment);
+                store_appointment_and_fks_to_db(&*watcher.dbm.lock().unwrap(), uuid, &appointment);
             }
         }
 
@@ -1427,6 +2831,81 @@ mod tests {
             .all(|v| matches!(v, cryptography::DecryptingError::AED { .. }));
     }
 
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        assert_eq!(
+            watcher.delete_user(user_id),
+            Err(UserManagementFailure::UserNotFound)
+        );
+
+        // Give the user an appointment that has already been handed to the Responder
+        let tracker_uuid = generate_uuid();
+        watcher.add_dummy_tracker_to_responder(
+            tracker_uuid,
+            &get_random_tracker(
+                user_id,
+                ConfirmationStatus::InMempoolSince(START_HEIGHT as u32),
+            ),
+        );
+        let mut user_info = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        user_info.appointments.insert(tracker_uuid, 1);
+        watcher.dbm.lock().unwrap().update_user(user_id, &user_info);
+        watcher
+            .gatekeeper
+            .get_registered_users()
+            .lock()
+            .unwrap()
+            .insert(user_id, user_info);
+
+        // Plus a couple being watched
+        let mut uuids: HashSet<UUID> = HashSet::from_iter([tracker_uuid]);
+        for _ in 0..2 {
+            let appointment = generate_dummy_appointment(None).inner;
+            let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+            watcher
+                .add_appointment(appointment.clone(), signature)
+                .unwrap();
+            uuids.insert(UUID::new(appointment.locator, user_id));
+        }
+
+        // Users cannot be deleted while some of their breaches are queued
+        let pending_uuid = *uuids.iter().find(|uuid| **uuid != tracker_uuid).unwrap();
+        watcher
+            .pending_triggers
+            .lock()
+            .unwrap()
+            .insert(pending_uuid);
+        assert_eq!(
+            watcher.delete_user(user_id),
+            Err(UserManagementFailure::PendingTriggers)
+        );
+        assert!(watcher.get_user_info(user_id).is_some());
+        watcher
+            .pending_triggers
+            .lock()
+            .unwrap()
+            .remove(&pending_uuid);
+
+        // Otherwise the user is deleted alongside all their appointments, both in the Watcher and the Responder
+        assert_eq!(watcher.delete_user(user_id), Ok(3));
+        assert!(watcher.get_user_info(user_id).is_none());
+        assert!(watcher.appointments.lock().unwrap().is_empty());
+        assert!(watcher.locator_uuid_map.lock().unwrap().is_empty());
+        assert!(!watcher.responder.has_tracker(tracker_uuid));
+
+        let dbm = watcher.dbm.lock().unwrap();
+        assert!(dbm.load_user(user_id).unwrap().is_none());
+        for uuid in uuids {
+            assert!(dbm.load_appointment(uuid).is_none());
+        }
+        assert!(dbm.load_tracker(tracker_uuid).is_none());
+    }
+
     #[tokio::test]
     async fn test_delete_appointments_from_memory() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -1449,7 +2928,7 @@ mod tests {
                 .unwrap()
                 .insert(appointment.locator(), HashSet::from_iter([uuid]));
 
-            store_appointment_and_fks_to_db(&watcher.dbm.lock().unwrap(), uuid, &appointment);
+            store_appointment_and_fks_to_db(&*watcher.dbm.lock().unwrap(), uuid, &appointment);
             to_be_deleted.insert(uuid, appointment.locator());
         }
 
@@ -1503,7 +2982,7 @@ mod tests {
                 .insert(appointment.locator(), HashSet::from_iter([uuid]));
 
             // Add data to the database to check data deletion
-            store_appointment_and_fks_to_db(&watcher.dbm.lock().unwrap(), uuid, &appointment);
+            store_appointment_and_fks_to_db(&*watcher.dbm.lock().unwrap(), uuid, &appointment);
 
             // Make it so some of the locators have multiple associated uuids
             if i % 3 == 0 {
@@ -1594,12 +3073,223 @@ mod tests {
                     .unwrap()
                     .load_user(id)
                     .unwrap()
+                    .unwrap()
                     .available_slots,
                 info.available_slots
             );
         }
     }
 
+    #[tokio::test]
+    async fn test_check_mempool() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (watcher, _s) =
+            init_watcher_with_options(&mut chain, dbm, MockOptions::in_mempool()).await;
+
+        // Checking the mempool with no appointments does nothing
+        watcher.check_mempool();
+        assert!(watcher.mempool_triggers.lock().unwrap().is_empty());
+
+        // The mocked mempool only contains the transaction built from TX_HEX. Add two appointments triggered by it,
+        // one with a valid blob and one with a blob that cannot be decrypted.
+        let dispute_tx: Transaction =
+            consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let (user2_sk, user2_pk) = get_random_keypair();
+        let user2_id = UserId(user2_pk);
+        watcher.register(user_id, "").unwrap();
+        watcher.register(user2_id, "").unwrap();
+
+        let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
+        let uuid = UUID::new(appointment.locator(), user_id);
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+
+        let mut invalid_appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
+        invalid_appointment.inner.encrypted_blob.reverse();
+        let invalid_uuid = UUID::new(invalid_appointment.locator(), user2_id);
+        let user2_sig = cryptography::sign(&invalid_appointment.inner.to_vec(), &user2_sk).unwrap();
+        watcher
+            .add_appointment(invalid_appointment.inner, user2_sig)
+            .unwrap();
+
+        // The valid breach is handed to the Responder, but the appointment is kept by the Watcher until the dispute is confirmed.
+        // The invalid one is deleted straightaway.
+        watcher.check_mempool();
+        assert!(watcher.responder.has_tracker(uuid));
+        assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert_eq!(
+            watcher.mempool_triggers.lock().unwrap()[&uuid],
+            dispute_tx.txid()
+        );
+        assert!(!watcher.responder.has_tracker(invalid_uuid));
+        assert!(!watcher
+            .appointments
+            .lock()
+            .unwrap()
+            .contains_key(&invalid_uuid));
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_appointment(invalid_uuid)
+            .is_none());
+
+        // Checking the mempool again does not trigger the same appointment twice
+        watcher.check_mempool();
+        assert_eq!(watcher.mempool_triggers.lock().unwrap().len(), 1);
+        assert_eq!(watcher.responder.get_trackers_count(), 1);
+
+        // Once the dispute is confirmed, the appointment is removed from the Watcher and the Responder keeps the tracker
+        watcher.block_connected(
+            &chain.generate(Some(vec![dispute_tx])),
+            chain.get_block_count(),
+        );
+        assert!(!watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(watcher.mempool_triggers.lock().unwrap().is_empty());
+        assert!(watcher.responder.has_tracker(uuid));
+    }
+
+    #[tokio::test]
+    async fn test_mempool_triggers_survive_restart() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (watcher, _s) =
+            init_watcher_with_options(&mut chain, dbm.clone(), MockOptions::in_mempool()).await;
+
+        let dispute_tx: Transaction =
+            consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+        let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
+        let uuid = UUID::new(appointment.locator(), user_id);
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+        watcher.check_mempool();
+        assert!(watcher.mempool_triggers.lock().unwrap().contains_key(&uuid));
+
+        // The tower restarts before the dispute is confirmed. The trigger is loaded back alongside its appointment
+        let (another_w, _as) =
+            init_watcher_with_options(&mut chain, dbm, MockOptions::in_mempool()).await;
+        assert_eq!(
+            another_w.mempool_triggers.lock().unwrap()[&uuid],
+            dispute_tx.txid()
+        );
+        assert!(another_w.appointments.lock().unwrap().contains_key(&uuid));
+
+        // So the appointment is not triggered twice
+        another_w.check_mempool();
+        assert_eq!(another_w.responder.get_trackers_count(), 1);
+
+        // And it is handled as any other mempool trigger once the dispute is confirmed
+        another_w.block_connected(
+            &chain.generate(Some(vec![dispute_tx])),
+            chain.get_block_count(),
+        );
+        assert!(!another_w.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(another_w.mempool_triggers.lock().unwrap().is_empty());
+        assert!(another_w.responder.has_tracker(uuid));
+    }
+
+    #[tokio::test]
+    async fn test_check_mempool_transactions() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        let dispute_tx = get_random_tx();
+        let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
+        let uuid = UUID::new(appointment.locator(), user_id);
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+
+        // Transactions not matching any appointment are ignored
+        watcher.check_mempool_transactions(vec![get_random_tx(), get_random_tx()]);
+        assert!(watcher.mempool_triggers.lock().unwrap().is_empty());
+        assert!(!watcher.responder.has_tracker(uuid));
+
+        // Notified disputes trigger the appointment without having to query the mempool
+        watcher.check_mempool_transactions(vec![get_random_tx(), dispute_tx.clone()]);
+        assert!(watcher.responder.has_tracker(uuid));
+        assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert_eq!(
+            watcher.mempool_triggers.lock().unwrap()[&uuid],
+            dispute_tx.txid()
+        );
+
+        // Being notified again does not trigger the same appointment twice
+        watcher.check_mempool_transactions(vec![dispute_tx]);
+        assert_eq!(watcher.mempool_triggers.lock().unwrap().len(), 1);
+        assert_eq!(watcher.responder.get_trackers_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_dropped_disputes() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        let dispute_tx = get_random_tx();
+        let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
+        let uuid = UUID::new(appointment.locator(), user_id);
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+
+        // Simulate the appointment being triggered from the mempool
+        let penalty_tx =
+            cryptography::decrypt(appointment.encrypted_blob(), &dispute_tx.txid()).unwrap();
+        let status = watcher.responder.handle_breach(
+            uuid,
+            Breach::new(dispute_tx.clone(), penalty_tx),
+            user_id,
+        );
+        assert!(status.accepted());
+        watcher
+            .mempool_triggers
+            .lock()
+            .unwrap()
+            .insert(uuid, dispute_tx.txid());
+
+        // The mocked bitcoind reports the dispute as not being in mempool. Mining a block that does not include it
+        // means it has been replaced or dropped, so the tracker is removed and the appointment is watched again.
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        assert!(watcher.mempool_triggers.lock().unwrap().is_empty());
+        assert!(!watcher.responder.has_tracker(uuid));
+        assert!(watcher.dbm.lock().unwrap().load_tracker(uuid).is_none());
+        assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_appointments(None)
+            .contains_key(&uuid));
+
+        // The appointment can still be triggered on chain
+        watcher.block_connected(
+            &chain.generate(Some(vec![dispute_tx])),
+            chain.get_block_count(),
+        );
+        assert!(!watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(watcher.responder.has_tracker(uuid));
+    }
+
     #[tokio::test]
     async fn test_filtered_block_connected() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -1632,8 +3322,8 @@ mod tests {
         let user_id = UserId(user_pk);
         let (user2_sk, user2_pk) = get_random_keypair();
         let user2_id = UserId(user2_pk);
-        watcher.register(user_id).unwrap();
-        watcher.register(user2_id).unwrap();
+        watcher.register(user_id, "").unwrap();
+        watcher.register(user2_id, "").unwrap();
 
         let appointment = generate_dummy_appointment(None);
         let uuid1 = UUID::new(appointment.locator(), user_id);
@@ -1751,7 +3441,7 @@ mod tests {
             MockedServerQuery::Error(rpc_errors::RPC_VERIFY_ERROR as i64),
             chain.tip().deref().height,
         );
-        *watcher.responder.get_carrier().lock().unwrap() = carrier;
+        *watcher.responder.get_carrier().lock().unwrap() = Box::new(carrier);
 
         watcher.block_connected(
             &chain.generate(Some(vec![dispute_tx])),
@@ -1808,6 +3498,107 @@ mod tests {
         assert!(watcher.dbm.lock().unwrap().load_appointment(uuid).is_none());
     }
 
+    #[tokio::test]
+    async fn test_rescan() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        // Add an appointment with a valid penalty and another one with an invalid one
+        let dispute_tx = get_random_tx();
+        let (uuid, appointment) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+
+        let invalid_dispute_tx = get_random_tx();
+        let (invalid_uuid, mut invalid_appointment) =
+            generate_dummy_appointment_with_user(user_id, Some(&invalid_dispute_tx.txid()));
+        invalid_appointment.inner.encrypted_blob.reverse();
+        let user_sig = cryptography::sign(&invalid_appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(invalid_appointment.inner.clone(), user_sig)
+            .unwrap();
+
+        // Mine the disputes without letting the Watcher know, as if the tower was offline
+        chain.generate(Some(vec![dispute_tx, invalid_dispute_tx]));
+        chain.generate(None);
+        let missed_blocks = get_last_n_blocks(&mut chain, 2).await;
+
+        watcher.rescan(&missed_blocks);
+
+        // The valid breach is handed to the Responder, while the invalid one is deleted
+        assert!(watcher.responder.has_tracker(uuid));
+        assert!(!watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(watcher.dbm.lock().unwrap().load_tracker(uuid).is_some());
+
+        assert!(!watcher.responder.has_tracker(invalid_uuid));
+        assert!(!watcher
+            .appointments
+            .lock()
+            .unwrap()
+            .contains_key(&invalid_uuid));
+        assert!(watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .load_appointment(invalid_uuid)
+            .is_none());
+        assert!(watcher.appointments.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rescan_on_bootstrap() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (watcher, _s) = init_watcher_with_db(&mut chain, dbm.clone()).await;
+        let last_known_height = chain.get_block_count();
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id, "").unwrap();
+
+        let dispute_tx = get_random_tx();
+        let (uuid, appointment) =
+            generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
+        let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
+        watcher
+            .add_appointment(appointment.inner.clone(), user_sig)
+            .unwrap();
+        let locator_cache_depth = watcher.locator_cache.lock().unwrap().blocks().len();
+        drop(watcher);
+
+        // Mine the dispute while the tower is offline, and bury it deeper than the locator cache
+        chain.generate(Some(vec![dispute_tx]));
+        for _ in 0..locator_cache_depth {
+            chain.generate(None);
+        }
+
+        // Bootstrap from the same database at the current tip, as if the tower had been forced past the missed blocks
+        let (watcher, _s) = init_watcher_with_db(&mut chain, dbm).await;
+        assert!(!watcher.is_fresh());
+        assert!(!watcher
+            .locator_cache
+            .lock()
+            .unwrap()
+            .contains_key(&appointment.locator()));
+
+        let missed_blocks = (chain.get_block_count() - last_known_height) as usize;
+        assert!(missed_blocks > locator_cache_depth);
+        let skipped_blocks = get_last_n_blocks(&mut chain, missed_blocks).await;
+        watcher.rescan(&skipped_blocks);
+
+        // The breach is found even though the block that contains it was skipped and is not part of the cache
+        assert!(watcher.responder.has_tracker(uuid));
+        assert!(!watcher.appointments.lock().unwrap().contains_key(&uuid));
+        assert!(watcher.dbm.lock().unwrap().load_tracker(uuid).is_some());
+    }
+
     #[tokio::test]
     async fn test_block_disconnected() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -1837,4 +3628,39 @@ mod tests {
             .blocks()
             .contains(&last_block_header.block_hash()));
     }
+
+    #[tokio::test]
+    async fn test_block_summaries() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (watcher, _s) = init_watcher_with_db(&mut chain, dbm.clone()).await;
+        let locator_cache_depth = watcher.locator_cache.lock().unwrap().blocks().len();
+
+        // Connected blocks are summarized into the database, keeping a rolling copy of the last IRREVOCABLY_RESOLVED blocks.
+        // Only the ones within the locator cache depth keep their transactions
+        let mut headers = Vec::new();
+        for _ in 0..IRREVOCABLY_RESOLVED + 2 {
+            let block = chain.generate(None);
+            watcher.block_connected(&block, chain.get_block_count());
+            headers.push(block.header);
+        }
+
+        let summaries = dbm.lock().unwrap().load_block_summaries();
+        assert_eq!(summaries.len(), IRREVOCABLY_RESOLVED as usize);
+        for (summary, header) in summaries.iter().zip(headers.iter().rev()) {
+            assert_eq!(summary.header, *header);
+        }
+        assert!(summaries[..locator_cache_depth]
+            .iter()
+            .all(|summary| summary.txdata.is_some()));
+        assert!(summaries[locator_cache_depth..]
+            .iter()
+            .all(|summary| summary.txdata.is_none()));
+
+        // Disconnected blocks are removed from the copy
+        watcher.block_disconnected(headers.last().unwrap(), chain.get_block_count());
+        let summaries = dbm.lock().unwrap().load_block_summaries();
+        assert_eq!(summaries.len(), IRREVOCABLY_RESOLVED as usize - 1);
+        assert_eq!(summaries[0].header, headers[headers.len() - 2]);
+    }
 }
@@ -0,0 +1,370 @@
+//! Logic related to the subscription to `bitcoind`'s ZMQ notifications (`zmqpubhashblock`, `zmqpubrawtx`, ...).
+//!
+//! Only the bits of ZMTP 3.0 needed to act as a `SUB` socket over TCP (using the `NULL` security mechanism) are implemented,
+//! which is what `bitcoind` publishes its notifications through.
+
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use triggered::Listener;
+
+/// Time to wait before trying to reconnect to a ZMQ publisher.
+const RECONNECT_DELAY: u64 = 5;
+/// The maximum frame size accepted from a publisher. Notifications are, at most, as big as a raw transaction.
+const MAX_FRAME_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Frame flags (see https://rfc.zeromq.org/spec/23/#framing).
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// Builds the greeting sent to the publisher: ZMTP 3.0 using the `NULL` security mechanism, as client.
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0; 64];
+    // Signature
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    // Version
+    greeting[10] = 3;
+    greeting[11] = 0;
+    // Mechanism
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+/// Encodes a frame with the given flags.
+fn encode_frame(body: &[u8], flags: u8) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend((body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend(body);
+    frame
+}
+
+/// Encodes a `READY` command announcing the given socket type.
+fn ready_command(socket_type: &str) -> Vec<u8> {
+    let mut command = vec![5];
+    command.extend(b"READY");
+    command.push(11);
+    command.extend(b"Socket-Type");
+    command.extend((socket_type.len() as u32).to_be_bytes());
+    command.extend(socket_type.as_bytes());
+    command
+}
+
+/// Reads a frame from the stream. Returns its flags alongside its body.
+async fn read_frame<S: AsyncRead + Unpin>(stream: &mut S) -> std::io::Result<(u8, Vec<u8>)> {
+    let flags = stream.read_u8().await?;
+    let size = if flags & FLAG_LONG != 0 {
+        stream.read_u64().await?
+    } else {
+        stream.read_u8().await? as u64
+    };
+    if size > MAX_FRAME_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("frame too big ({size} bytes)"),
+        ));
+    }
+
+    let mut body = vec![0; size as usize];
+    stream.read_exact(&mut body).await?;
+    Ok((flags, body))
+}
+
+/// Reads a (potentially multipart) message from the stream. Commands sent by the publisher are skipped.
+async fn read_message<S: AsyncRead + Unpin>(stream: &mut S) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut parts = Vec::new();
+    loop {
+        let (flags, body) = read_frame(stream).await?;
+        if flags & FLAG_COMMAND != 0 {
+            continue;
+        }
+        parts.push(body);
+        if flags & FLAG_MORE == 0 {
+            return Ok(parts);
+        }
+    }
+}
+
+/// Performs the ZMTP handshake with the publisher and subscribes to the given topic.
+async fn subscribe<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    topic: &str,
+) -> std::io::Result<()> {
+    stream.write_all(&greeting()).await?;
+    let mut peer_greeting = [0; 64];
+    stream.read_exact(&mut peer_greeting).await?;
+    if peer_greeting[0] != 0xff || peer_greeting[9] & 0x01 != 0x01 || peer_greeting[10] < 3 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "peer does not speak ZMTP 3",
+        ));
+    }
+    if &peer_greeting[12..17] != b"NULL\0" {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "unsupported security mechanism",
+        ));
+    }
+
+    stream
+        .write_all(&encode_frame(&ready_command("SUB"), FLAG_COMMAND))
+        .await?;
+    let (flags, command) = read_frame(stream).await?;
+    if flags & FLAG_COMMAND == 0 || !command.starts_with(b"\x05READY") {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "unexpected handshake from peer",
+        ));
+    }
+
+    // Subscriptions are sent as messages in ZMTP 3.0
+    let mut subscription = vec![1];
+    subscription.extend(topic.as_bytes());
+    stream.write_all(&encode_frame(&subscription, 0)).await
+}
+
+/// Component in charge of subscribing to one of `bitcoind`'s ZMQ notification topics.
+///
+/// The subscription is kept alive for as long as the tower runs: if the connection with the publisher is lost, or if no
+/// notification is received within the heartbeat interval, the subscriber reconnects.
+pub struct ZmqSubscriber {
+    /// The address (`host:port`) of the publisher.
+    address: String,
+    /// The topic to subscribe to (e.g. `hashblock` or `rawtx`).
+    topic: String,
+    /// The maximum time without notifications before considering the subscription stale.
+    heartbeat: Duration,
+    /// A signal from the main thread indicating the tower is shuting down.
+    shutdown_signal: Listener,
+}
+
+impl ZmqSubscriber {
+    /// Creates a new [ZmqSubscriber] instance.
+    pub fn new(address: &str, topic: &str, heartbeat_sec: u32, shutdown_signal: Listener) -> Self {
+        ZmqSubscriber {
+            address: address.to_owned(),
+            topic: topic.to_owned(),
+            heartbeat: Duration::from_secs(heartbeat_sec as u64),
+            shutdown_signal,
+        }
+    }
+
+    /// Receives notifications until the subscription fails, or goes silent for longer than [heartbeat](Self::heartbeat).
+    async fn receive<F: FnMut(Vec<u8>)>(&self, handler: &mut F) -> std::io::Result<()> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        subscribe(&mut stream, &self.topic).await?;
+        log::info!(
+            "Subscribed to {} notifications from {}",
+            self.topic,
+            self.address
+        );
+
+        loop {
+            let mut parts = match timeout(self.heartbeat, read_message(&mut stream)).await {
+                Ok(parts) => parts?,
+                Err(_) => {
+                    return Err(Error::new(
+                        ErrorKind::TimedOut,
+                        format!("no notifications in the last {}s", self.heartbeat.as_secs()),
+                    ))
+                }
+            };
+
+            // bitcoind notifications are formatted as [topic, body, sequence]
+            if parts.len() >= 2 && parts[0] == self.topic.as_bytes() {
+                handler(parts.swap_remove(1));
+            }
+        }
+    }
+
+    /// Subscribes to the publisher, handing the body of every notification received to `handler`.
+    ///
+    /// Runs until the shutdown signal is received.
+    pub async fn run<F: FnMut(Vec<u8>)>(self, mut handler: F) {
+        loop {
+            tokio::select! {
+                _ = self.shutdown_signal.clone() => break,
+                result = self.receive(&mut handler) => {
+                    if let Err(e) = result {
+                        log::warn!(
+                            "ZMQ subscription to {} ({}) interrupted: {e}. Reconnecting in {RECONNECT_DELAY}s",
+                            self.address,
+                            self.topic
+                        );
+                    }
+                }
+            }
+
+            if timeout(
+                Duration::from_secs(RECONNECT_DELAY),
+                self.shutdown_signal.clone(),
+            )
+            .await
+            .is_ok()
+            {
+                break;
+            }
+        }
+        log::debug!("Closing {} subscription to {}", self.topic, self.address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    // A mocked ZMQ publisher. Notifications sent through the channel are published to the first subscriber
+    async fn start_publisher(
+        mut notifications: mpsc::UnboundedReceiver<(String, Vec<u8>)>,
+    ) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            // Handshake (publisher side)
+            let mut peer_greeting = [0; 64];
+            stream.read_exact(&mut peer_greeting).await.unwrap();
+            let mut greeting = greeting();
+            greeting[32] = 1;
+            stream.write_all(&greeting).await.unwrap();
+            let (flags, command) = read_frame(&mut stream).await.unwrap();
+            assert_eq!(flags, FLAG_COMMAND);
+            assert_eq!(command, ready_command("SUB"));
+            stream
+                .write_all(&encode_frame(&ready_command("PUB"), FLAG_COMMAND))
+                .await
+                .unwrap();
+            let (_, subscription) = read_frame(&mut stream).await.unwrap();
+            assert_eq!(subscription[0], 1);
+
+            let mut sequence: u32 = 0;
+            while let Some((topic, body)) = notifications.recv().await {
+                let mut message = encode_frame(topic.as_bytes(), FLAG_MORE);
+                message.extend(encode_frame(&body, FLAG_MORE));
+                message.extend(encode_frame(&sequence.to_le_bytes(), 0));
+                stream.write_all(&message).await.unwrap();
+                sequence += 1;
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        assert_eq!(encode_frame(b"rawtx", FLAG_MORE), b"\x01\x05rawtx");
+
+        let body = vec![0; 300];
+        let frame = encode_frame(&body, 0);
+        assert_eq!(frame[0], FLAG_LONG);
+        assert_eq!(frame[1..9], 300u64.to_be_bytes());
+        assert_eq!(frame[9..], body);
+    }
+
+    #[tokio::test]
+    async fn test_read_message() {
+        let mut data = encode_frame(&ready_command("PUB"), FLAG_COMMAND);
+        data.extend(encode_frame(b"hashblock", FLAG_MORE));
+        data.extend(encode_frame(&[7; 300], FLAG_MORE));
+        data.extend(encode_frame(&[0; 4], 0));
+
+        // Commands are skipped
+        assert_eq!(
+            read_message(&mut data.as_slice()).await.unwrap(),
+            vec![b"hashblock".to_vec(), vec![7; 300], vec![0; 4]]
+        );
+
+        // Frames that are too big are rejected
+        let mut data = vec![FLAG_LONG];
+        data.extend((MAX_FRAME_SIZE + 1).to_be_bytes());
+        assert!(matches!(
+            read_message(&mut data.as_slice()).await,
+            Err(e) if e.kind() == ErrorKind::InvalidData
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_wrong_peer() {
+        // A peer not speaking ZMTP is rejected
+        let mut stream = peer_stream(&[0; 64]);
+        assert!(matches!(
+            subscribe(&mut stream, "hashblock").await,
+            Err(e) if e.kind() == ErrorKind::InvalidData
+        ));
+
+        // And so is one using a security mechanism other than NULL
+        let mut peer_greeting = greeting();
+        peer_greeting[12..17].copy_from_slice(b"PLAIN");
+        let mut stream = peer_stream(&peer_greeting);
+        assert!(matches!(
+            subscribe(&mut stream, "hashblock").await,
+            Err(e) if e.kind() == ErrorKind::InvalidData
+        ));
+    }
+
+    // A duplex stream whose read side returns the given data
+    fn peer_stream(data: &[u8]) -> tokio::io::DuplexStream {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let data = data.to_vec();
+        tokio::spawn(async move {
+            server.write_all(&data).await.unwrap();
+            // Keep the server side open until the client is done
+            let mut buf = Vec::new();
+            let _ = server.read_to_end(&mut buf).await;
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let addr = start_publisher(receiver).await;
+        let (shutdown_trigger, shutdown_signal) = triggered::trigger();
+        let subscriber = ZmqSubscriber::new(&addr.to_string(), "hashblock", 60, shutdown_signal);
+
+        let (notified_sender, mut notified) = mpsc::unbounded_channel();
+        let task = tokio::spawn(subscriber.run(move |body| notified_sender.send(body).unwrap()));
+
+        // Notifications for other topics are ignored
+        sender.send(("rawtx".to_owned(), vec![1; 10])).unwrap();
+        sender.send(("hashblock".to_owned(), vec![2; 32])).unwrap();
+        assert_eq!(notified.recv().await.unwrap(), vec![2; 32]);
+
+        shutdown_trigger.trigger();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_heartbeat() {
+        // If the publisher goes silent the subscriber reconnects
+        let (_sender, receiver) = mpsc::unbounded_channel();
+        let addr = start_publisher(receiver).await;
+        let (shutdown_trigger, shutdown_signal) = triggered::trigger();
+        let subscriber = ZmqSubscriber::new(&addr.to_string(), "hashblock", 1, shutdown_signal);
+
+        let mut handler = |_| ();
+        let result = subscriber.receive(&mut handler).await;
+        assert!(matches!(result, Err(e) if e.kind() == ErrorKind::TimedOut));
+
+        shutdown_trigger.trigger();
+        timeout(Duration::from_secs(1), subscriber.run(handler))
+            .await
+            .unwrap();
+    }
+}
@@ -8,12 +8,13 @@ use rusqlite::{params, Connection, Error as SqliteError};
 use bitcoin::secp256k1::SecretKey;
 
 use teos_common::appointment::{Appointment, Locator};
-use teos_common::dbm::{DatabaseConnection, DatabaseManager, Error};
+use teos_common::dbm::{DatabaseConnection, DatabaseManager, Error, Migration, MigrationError};
 use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
 use teos_common::{TowerId, UserId};
 
 use crate::{AppointmentStatus, MisbehaviorProof, TowerInfo, TowerStatus, TowerSummary};
 
+/// The tables of the first version of the database schema. Changes to the schema go into [MIGRATIONS] instead.
 const TABLES: [&str; 8] = [
     "CREATE TABLE IF NOT EXISTS towers (
     tower_id INT PRIMARY KEY,
@@ -53,6 +54,7 @@ const TABLES: [&str; 8] = [
     subscription_start INT NOT NULL,
     subscription_expiry INT NOT NULL,
     signature BLOB NOT NULL,
+    tier TEXT NOT NULL DEFAULT '',
     PRIMARY KEY (tower_id, subscription_expiry),
     FOREIGN KEY(tower_id)
         REFERENCES towers(tower_id)
@@ -83,6 +85,18 @@ const TABLES: [&str; 8] = [
 )",
 ];
 
+/// Columns added to tables before the database schema was versioned, as (table, column, definition).
+///
+/// Used to bring databases created back then up to the first version of the schema.
+const ADDED_COLUMNS: [(&str, &str, &str); 1] =
+    [("registration_receipts", "tier", "TEXT NOT NULL DEFAULT ''")];
+
+/// The migrations of the database schema, from oldest to newest.
+const MIGRATIONS: [Migration; 1] = [Migration {
+    description: "Create the initial schema",
+    statements: &TABLES,
+}];
+
 /// Component in charge of interacting with the underlying database.
 ///
 /// Currently works for `SQLite`. `PostgreSQL` should also be added in the future.
@@ -104,11 +118,22 @@ impl DatabaseConnection for DBM {
 
 impl DBM {
     /// Creates a new [DBM] instance.
-    pub fn new(db_path: &PathBuf) -> Result<Self, SqliteError> {
-        let connection = Connection::open(db_path)?;
+    pub fn new(db_path: &PathBuf) -> Result<Self, MigrationError> {
+        DBM::from_connection(Connection::open(db_path)?)
+    }
+
+    /// Creates a new [DBM] instance from an open connection, migrating the database schema to the latest version.
+    fn from_connection(connection: Connection) -> Result<Self, MigrationError> {
         connection.execute("PRAGMA foreign_keys=1;", [])?;
         let mut dbm = Self { connection };
-        dbm.create_tables(Vec::from_iter(TABLES))?;
+
+        // Databases created before the schema was versioned may be missing some of the tables and columns of its first
+        // version, so they are brought up to it before migrating
+        if dbm.schema_version()? == 0 {
+            dbm.create_tables(Vec::from_iter(TABLES))?;
+            dbm.add_missing_columns(&ADDED_COLUMNS)?;
+        }
+        dbm.migrate(&MIGRATIONS)?;
 
         Ok(dbm)
     }
@@ -158,10 +183,11 @@ impl DBM {
             params![tower_id.to_vec(), net_addr, receipt.available_slots()],
         )
         .map_err(Error::Unknown)?;
+        // Receipts covering the same period as a previous one (e.g. slots being topped up) supersede it
         tx.execute(
-                "INSERT INTO registration_receipts (tower_id, available_slots, subscription_start, subscription_expiry, signature) 
-                    VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![tower_id.to_vec(), receipt.available_slots(), receipt.subscription_start(), receipt.subscription_expiry(), receipt.signature()]).map_err( Error::Unknown)?;
+                "INSERT OR REPLACE INTO registration_receipts (tower_id, available_slots, subscription_start, subscription_expiry, signature, tier) 
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![tower_id.to_vec(), receipt.available_slots(), receipt.subscription_start(), receipt.subscription_expiry(), receipt.signature(), receipt.tier()]).map_err( Error::Unknown)?;
 
         tx.commit().map_err(Error::Unknown)
     }
@@ -220,7 +246,7 @@ impl DBM {
         let mut stmt = self
             .connection
             .prepare(
-                "SELECT available_slots, subscription_start, subscription_expiry, signature
+                "SELECT available_slots, subscription_start, subscription_expiry, signature, tier
                     FROM registration_receipts 
                     WHERE tower_id = ?1 AND subscription_expiry = (SELECT MAX(subscription_expiry) 
                         FROM registration_receipts 
@@ -233,9 +259,10 @@ impl DBM {
             let start: u32 = row.get(1).unwrap();
             let expiry: u32 = row.get(2).unwrap();
             let signature: String = row.get(3).unwrap();
+            let tier: String = row.get(4).unwrap();
 
             Ok(RegistrationReceipt::with_signature(
-                user_id, slots, start, expiry, signature,
+                user_id, slots, start, expiry, tier, signature,
             ))
         })
         .ok()
@@ -647,6 +674,8 @@ impl DBM {
 mod tests {
     use super::*;
 
+    use tempdir::TempDir;
+
     use teos_common::cryptography::get_random_keypair;
     use teos_common::test_utils::{
         generate_random_appointment, get_random_registration_receipt, get_random_user_id,
@@ -654,13 +683,8 @@ mod tests {
     };
 
     impl DBM {
-        pub(crate) fn in_memory() -> Result<Self, SqliteError> {
-            let connection = Connection::open_in_memory()?;
-            connection.execute("PRAGMA foreign_keys=1;", [])?;
-            let mut dbm = Self { connection };
-            dbm.create_tables(Vec::from_iter(TABLES))?;
-
-            Ok(dbm)
+        pub(crate) fn in_memory() -> Result<Self, MigrationError> {
+            DBM::from_connection(Connection::open_in_memory()?)
         }
 
         pub(crate) fn appointment_exists(&self, locator: Locator) -> bool {
@@ -692,6 +716,101 @@ mod tests {
         dbm.create_tables(Vec::from_iter(TABLES)).unwrap();
     }
 
+    #[test]
+    fn test_upgrade_unversioned_database() {
+        // Databases created before the schema was versioned (and before receipts had a tier) are migrated keeping their data
+        let dir = TempDir::new("watchtowers").unwrap();
+        let db_path = dir.path().join("watchtowers_db.sql3");
+
+        let connection = Connection::open(&db_path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE towers (
+                    tower_id INT PRIMARY KEY,
+                    net_addr TEXT NOT NULL,
+                    available_slots INT NOT NULL
+                );
+                CREATE TABLE registration_receipts (
+                    tower_id INT NOT NULL,
+                    available_slots INT NOT NULL,
+                    subscription_start INT NOT NULL,
+                    subscription_expiry INT NOT NULL,
+                    signature BLOB NOT NULL,
+                    PRIMARY KEY (tower_id, subscription_expiry),
+                    FOREIGN KEY(tower_id)
+                        REFERENCES towers(tower_id)
+                        ON DELETE CASCADE
+                );",
+            )
+            .unwrap();
+        let tower_id = get_random_user_id();
+        let net_addr = "talaia.watch";
+        let receipt = get_random_registration_receipt();
+        connection
+            .execute(
+                "INSERT INTO towers (tower_id, net_addr, available_slots) VALUES (?1, ?2, ?3)",
+                params![tower_id.to_vec(), net_addr, receipt.available_slots()],
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO registration_receipts (tower_id, available_slots, subscription_start, subscription_expiry, signature)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    tower_id.to_vec(),
+                    receipt.available_slots(),
+                    receipt.subscription_start(),
+                    receipt.subscription_expiry(),
+                    receipt.signature()
+                ],
+            )
+            .unwrap();
+        drop(connection);
+
+        let dbm = DBM::new(&db_path).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(
+            dbm.load_tower_record(tower_id).unwrap(),
+            TowerInfo::new(
+                net_addr.to_owned(),
+                receipt.available_slots(),
+                receipt.subscription_start(),
+                receipt.subscription_expiry(),
+                HashMap::new(),
+                Vec::new(),
+                Vec::new(),
+            )
+        );
+        drop(dbm);
+
+        // Opening the database again leaves it as is
+        let dbm = DBM::new(&db_path).unwrap();
+        assert_eq!(dbm.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert!(dbm.load_tower_record(tower_id).is_some());
+    }
+
+    #[test]
+    fn test_open_newer_database() {
+        // Databases migrated by a newer version of the client are not opened
+        let dir = TempDir::new("watchtowers").unwrap();
+        let db_path = dir.path().join("watchtowers_db.sql3");
+
+        let dbm = DBM::new(&db_path).unwrap();
+        let version = MIGRATIONS.len() as u32 + 1;
+        dbm.connection
+            .execute(
+                "INSERT INTO schema_version (version, description, checksum) VALUES (?1, 'Newer migration', '')",
+                params![version],
+            )
+            .unwrap();
+        drop(dbm);
+
+        assert!(matches!(
+            DBM::new(&db_path),
+            Err(MigrationError::NewerSchema { version: v, latest }) if v == version && latest == MIGRATIONS.len() as u32
+        ));
+    }
+
     #[test]
     fn test_store_load_tower_record() {
         let mut dbm = DBM::in_memory().unwrap();
@@ -757,6 +876,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_registration_receipt_with_tier() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        // The tier is part of the signed receipt, so it must be kept for the receipt to verify once loaded
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+        let mut receipt =
+            RegistrationReceipt::new(get_random_user_id(), 21, 42, 420, "premium".to_owned());
+        receipt.sign(&tower_sk);
+
+        dbm.store_tower_record(tower_id, "talaia.watch", &receipt)
+            .unwrap();
+        let loaded_receipt = dbm
+            .load_registration_receipt(tower_id, receipt.user_id())
+            .unwrap();
+        assert_eq!(loaded_receipt, receipt);
+        assert!(loaded_receipt.verify(&tower_id));
+    }
+
     #[test]
     fn test_load_same_registration_receipt() {
         let mut dbm = DBM::in_memory().unwrap();
@@ -775,12 +914,27 @@ mod tests {
             receipt
         );
 
-        // Store the same again, this should fail due to UNIQUE PK constrains.
-        // Notice store_tower_record is guarded against this by WTClient::add_update_tower though.
-        assert!(matches!(
-            dbm.store_tower_record(tower_id, net_addr, &receipt),
-            Err { .. }
-        ));
+        // Storing a receipt for the same period again (e.g. after topping up the slots) replaces the previous one.
+        // Notice store_tower_record is guarded against receipts that do not add slots by WTClient::add_update_tower though.
+        let topped_up_receipt = RegistrationReceipt::with_signature(
+            receipt.user_id(),
+            receipt.available_slots() + 1,
+            receipt.subscription_start(),
+            receipt.subscription_expiry(),
+            receipt.tier().to_owned(),
+            receipt.signature().unwrap(),
+        );
+        dbm.store_tower_record(tower_id, net_addr, &topped_up_receipt)
+            .unwrap();
+        assert_eq!(
+            dbm.load_registration_receipt(tower_id, receipt.user_id())
+                .unwrap(),
+            topped_up_receipt
+        );
+        assert_eq!(
+            dbm.load_tower_record(tower_id).unwrap().available_slots,
+            receipt.available_slots() + 1
+        );
     }
 
     #[test]
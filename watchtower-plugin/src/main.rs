@@ -69,7 +69,10 @@ async fn register(
     let params = RegisterParams::try_from(v).map_err(|x| anyhow!(x))?;
     let mut host = params.host.unwrap_or_else(|| "localhost".to_owned());
     let tower_id = params.tower_id;
-    let user_id = plugin.state().lock().unwrap().user_id;
+    let (user_id, user_sk) = {
+        let state = plugin.state().lock().unwrap();
+        (state.user_id, state.user_sk)
+    };
 
     // TODO: The user should pick the start_time or, at least, check the returned start time against it's known block height.
     // Otherwise the tower could just generate a subscription starting far in the future. For this we need to access lightning RPC
@@ -89,7 +92,7 @@ async fn register(
 
     let proxy = plugin.state().lock().unwrap().proxy.clone();
 
-    let receipt = http::register(tower_id, user_id, &tower_net_addr, &proxy)
+    let receipt = http::register(tower_id, user_id, &user_sk, &tower_net_addr, &proxy)
         .await
         .map_err(|e| {
             let mut state = plugin.state().lock().unwrap();
@@ -111,7 +114,7 @@ async fn register(
         .unwrap()
         .add_update_tower(tower_id, tower_net_addr.net_addr(), &receipt).map_err(|e| {
             if e.is_expiry() {
-                anyhow!("Registration receipt contains a subscription expiry that is not higher than the one we are currently registered for")
+                anyhow!("Registration receipt contains a subscription expiry that is lower than the one we are currently registered for")
             } else {
                 anyhow!("Registration receipt does not contain more slots than the ones we are currently registered for")
             }
@@ -466,7 +469,8 @@ async fn on_commitment_revocation(
                     AddAppointmentError::ApiError(e) => match e.error_code {
                         errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR => {
                             log::warn!(
-                                "There is a subscription issue with {tower_id}. Adding {} to pending",
+                                "There is a subscription issue with {tower_id}: {}. Adding {} to pending",
+                                e.subscription_issue(),
                                 appointment.locator
                             );
                             let mut state = plugin.state().lock().unwrap();
@@ -474,6 +478,16 @@ async fn on_commitment_revocation(
                             state.add_pending_appointment(tower_id, &appointment);
                             send_to_retrier(&state, tower_id, appointment.locator);
                         }
+                        errors::RATE_LIMITED => {
+                            log::warn!(
+                                "{tower_id} is rate limiting our requests. Adding {} to pending appointments",
+                                appointment.locator
+                            );
+                            let mut state = plugin.state().lock().unwrap();
+                            state.set_tower_status(tower_id, TowerStatus::TemporaryUnreachable);
+                            state.add_pending_appointment(tower_id, &appointment);
+                            send_to_retrier(&state, tower_id, appointment.locator);
+                        }
 
                         _ => {
                             log::warn!(
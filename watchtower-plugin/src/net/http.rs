@@ -1,12 +1,16 @@
 use reqwest::{Method, Response};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use teos_common::appointment::Appointment;
+use bitcoin::secp256k1::SecretKey;
+
+use teos_common::anti_spam::{solve_pow, MAX_POW_DIFFICULTY};
+use teos_common::appointment::{Appointment, RejectionReason};
 use teos_common::cryptography;
+use teos_common::errors;
 use teos_common::net::http::Endpoint;
 use teos_common::net::NetAddr;
 use teos_common::protos as common_msgs;
-use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
+use teos_common::receipts::{registration_message, AppointmentReceipt, RegistrationReceipt};
 use teos_common::{TowerId, UserId};
 
 use crate::net::ProxyInfo;
@@ -25,6 +29,70 @@ pub enum ApiResponse<T> {
 pub struct ApiError {
     pub error: String,
     pub error_code: u8,
+    /// Details on why an appointment was rejected. Only sent by the tower on `add_appointment` failures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<common_msgs::AppointmentRejection>,
+    /// Invoice to be paid to complete a registration. Only sent by the tower on `register` failures, if subscriptions are paid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice: Option<common_msgs::RegistrationInvoice>,
+    /// Challenge to be solved to complete a registration. Only sent by the tower on `register` failures, if registrations
+    /// are gated behind proof of work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<common_msgs::RegistrationChallenge>,
+}
+
+impl ApiError {
+    /// Builds the error of an appointment rejected as part of a batch. Error codes are derived from the rejection reason,
+    /// matching the ones the tower sends for single appointments.
+    fn from_rejection(error: String, rejection: Option<common_msgs::AppointmentRejection>) -> Self {
+        let error_code = match rejection.as_ref().map(|r| RejectionReason::from(r.reason)) {
+            Some(
+                RejectionReason::AuthenticationFailure
+                | RejectionReason::NotEnoughSlots
+                | RejectionReason::SubscriptionExpired,
+            ) => errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+            Some(RejectionReason::AlreadyTriggered) => errors::APPOINTMENT_ALREADY_TRIGGERED,
+            Some(RejectionReason::ToSelfDelayTooSmall) => {
+                errors::APPOINTMENT_TO_SELF_DELAY_TOO_SMALL
+            }
+            Some(RejectionReason::RateLimited) => errors::RATE_LIMITED,
+            _ => errors::UNEXPECTED_ERROR,
+        };
+
+        ApiError {
+            error,
+            error_code,
+            rejection,
+            invoice: None,
+            challenge: None,
+        }
+    }
+
+    /// Describes a subscription issue reported by the tower, using the rejection details if available.
+    pub fn subscription_issue(&self) -> String {
+        match &self.rejection {
+            Some(r) => match RejectionReason::from(r.reason) {
+                RejectionReason::NotEnoughSlots => format!(
+                    "not enough slots available (required: {}, available: {})",
+                    r.required_slots, r.available_slots
+                ),
+                RejectionReason::SubscriptionExpired => format!(
+                    "subscription expired at {} (current height: {})",
+                    r.subscription_expiry, r.current_height
+                ),
+                _ => "invalid signature or unknown user".to_owned(),
+            },
+            None => self.error.clone(),
+        }
+    }
+
+    /// Describes a registration issue reported by the tower, including the invoice to be paid if available.
+    pub fn registration_issue(&self) -> String {
+        match &self.invoice {
+            Some(invoice) => format!("{}. Invoice: {}", self.error, invoice.invoice),
+            None => self.error.clone(),
+        }
+    }
 }
 
 /// Errors related to requests sent to the tower.
@@ -59,30 +127,80 @@ impl From<RequestError> for AddAppointmentError {
 pub async fn register(
     tower_id: TowerId,
     user_id: UserId,
+    user_sk: &SecretKey,
     tower_net_addr: &NetAddr,
     proxy: &Option<ProxyInfo>,
 ) -> Result<RegistrationReceipt, RequestError> {
     log::info!("Registering in the Eye of Satoshi (tower_id={tower_id})");
-    process_post_response(
-        post_request(
-            tower_net_addr,
-            Endpoint::Register,
-            &common_msgs::RegisterRequest {
-                user_id: user_id.to_vec(),
-            },
-            proxy,
-        )
-        .await,
-    )
-    .await
-    .map(|r: common_msgs::RegisterResponse| {
-        RegistrationReceipt::with_signature(
-            user_id,
-            r.available_slots,
-            r.subscription_start,
-            r.subscription_expiry,
-            r.subscription_signature,
+    let signature =
+        cryptography::sign(registration_message("", &tower_id).as_bytes(), user_sk).unwrap();
+    let mut proof = None;
+    loop {
+        match process_post_response(
+            post_request(
+                tower_net_addr,
+                Endpoint::Register,
+                &common_msgs::RegisterRequest {
+                    user_id: user_id.to_vec(),
+                    tier: String::new(),
+                    proof: proof.clone(),
+                    signature: signature.clone(),
+                },
+                proxy,
+            )
+            .await,
         )
+        .await?
+        {
+            ApiResponse::Response::<common_msgs::RegisterResponse>(r) => {
+                return Ok(RegistrationReceipt::with_signature(
+                    user_id,
+                    r.available_slots,
+                    r.subscription_start,
+                    r.subscription_expiry,
+                    r.tier,
+                    r.subscription_signature,
+                ))
+            }
+            // Challenges are only solved once, so a tower that keeps sending them cannot keep us busy
+            ApiResponse::Error(ApiError {
+                challenge: Some(challenge),
+                ..
+            }) if proof.is_none() => {
+                proof = Some(solve_challenge(tower_id, challenge).await?);
+            }
+            ApiResponse::Error(e) => return Err(RequestError::Unexpected(e.registration_issue())),
+        }
+    }
+}
+
+/// Solves a registration challenge sent by the tower, returning the proof to be attached to the registration.
+async fn solve_challenge(
+    tower_id: TowerId,
+    challenge: common_msgs::RegistrationChallenge,
+) -> Result<common_msgs::RegistrationProof, RequestError> {
+    if challenge.difficulty > MAX_POW_DIFFICULTY as u32 {
+        return Err(RequestError::Unexpected(format!(
+            "Registration challenge is too hard (difficulty: {}, max: {MAX_POW_DIFFICULTY})",
+            challenge.difficulty
+        )));
+    }
+
+    log::info!(
+        "Solving registration challenge (tower_id={tower_id}, difficulty={})",
+        challenge.difficulty
+    );
+    let (data, difficulty) = (challenge.challenge, challenge.difficulty as u8);
+    let nonce = tokio::task::spawn_blocking(move || solve_pow(&data, difficulty))
+        .await
+        .map_err(|e| RequestError::Unexpected(e.to_string()))?;
+
+    Ok(common_msgs::RegistrationProof {
+        pow: Some(common_msgs::ProofOfWork {
+            expires_at: challenge.expires_at,
+            nonce,
+        }),
+        token: None,
     })
 }
 
@@ -132,28 +250,106 @@ pub async fn send_appointment(
     .await?
     {
         ApiResponse::Response::<common_msgs::AddAppointmentResponse>(r) => {
-            let receipt = AppointmentReceipt::with_signature(
-                signature.to_owned(),
-                r.start_block,
-                r.signature.clone(),
-            );
-            let recovered_id = TowerId(
-                cryptography::recover_pk(&receipt.to_vec(), &receipt.signature().unwrap()).unwrap(),
-            );
-            if recovered_id == tower_id {
-                Ok((r, receipt))
-            } else {
-                Err(AddAppointmentError::SignatureError(MisbehaviorProof::new(
-                    appointment.locator,
-                    receipt,
-                    recovered_id,
-                )))
-            }
+            let receipt = verify_receipt(tower_id, appointment, signature, &r)?;
+            Ok((r, receipt))
         }
         ApiResponse::Error(e) => Err(AddAppointmentError::ApiError(e)),
     }
 }
 
+/// Handles the logic of interacting with the `add_appointments` endpoint of the tower.
+///
+/// The outer error covers failures of the whole batch. Otherwise, the outcome of each appointment is returned in the
+/// same order they were sent: the available slots and receipt if accepted, or the tower error if rejected.
+pub async fn add_appointments(
+    tower_id: TowerId,
+    tower_net_addr: &NetAddr,
+    proxy: &Option<ProxyInfo>,
+    appointments: &[(Appointment, String)],
+) -> Result<Vec<Result<(u32, AppointmentReceipt), AddAppointmentError>>, AddAppointmentError> {
+    log::debug!(
+        "Sending {} appointments to tower {tower_id}",
+        appointments.len()
+    );
+    let request_data = common_msgs::AddAppointmentsRequest {
+        appointments: appointments
+            .iter()
+            .map(
+                |(appointment, signature)| common_msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.clone().into()),
+                    signature: signature.clone(),
+                },
+            )
+            .collect(),
+    };
+
+    let response = match process_post_response(
+        post_request(
+            tower_net_addr,
+            Endpoint::AddAppointments,
+            &request_data,
+            proxy,
+        )
+        .await,
+    )
+    .await?
+    {
+        ApiResponse::Response::<common_msgs::AddAppointmentsResponse>(r) => r,
+        ApiResponse::Error(e) => return Err(AddAppointmentError::ApiError(e)),
+    };
+
+    if response.results.len() != appointments.len() {
+        return Err(RequestError::Unexpected(format!(
+            "Unexpected number of results (expected: {}, received: {})",
+            appointments.len(),
+            response.results.len()
+        ))
+        .into());
+    }
+    log::debug!("Remaining slots: {}", response.available_slots);
+
+    let mut outcomes = Vec::with_capacity(appointments.len());
+    for ((appointment, signature), result) in appointments.iter().zip(response.results) {
+        outcomes.push(match result.response {
+            Some(r) => verify_receipt(tower_id, appointment, signature, &r)
+                .map(|receipt| (r.available_slots, receipt)),
+            None => Err(AddAppointmentError::ApiError(ApiError::from_rejection(
+                result.error,
+                result.rejection,
+            ))),
+        });
+    }
+
+    Ok(outcomes)
+}
+
+/// Builds the receipt of an accepted appointment, checking it has been signed by the tower we sent the appointment to.
+#[allow(clippy::result_large_err)]
+fn verify_receipt(
+    tower_id: TowerId,
+    appointment: &Appointment,
+    signature: &str,
+    response: &common_msgs::AddAppointmentResponse,
+) -> Result<AppointmentReceipt, AddAppointmentError> {
+    let receipt = AppointmentReceipt::with_signature(
+        signature.to_owned(),
+        response.start_block,
+        response.signature.clone(),
+    );
+    let recovered_id = TowerId(
+        cryptography::recover_pk(&receipt.to_vec(), &receipt.signature().unwrap()).unwrap(),
+    );
+    if recovered_id == tower_id {
+        Ok(receipt)
+    } else {
+        Err(AddAppointmentError::SignatureError(MisbehaviorProof::new(
+            appointment.locator,
+            receipt,
+            recovered_id,
+        )))
+    }
+}
+
 /// A generic function to send a request to a tower.
 async fn request<S: Serialize>(
     tower_net_addr: &NetAddr,
@@ -238,9 +434,12 @@ pub async fn process_post_response<T: DeserializeOwned>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mockito::Matcher;
     use serde_json::json;
 
-    use crate::test_utils::get_dummy_add_appointment_response;
+    use crate::test_utils::{
+        get_dummy_add_appointment_response, get_dummy_add_appointments_response,
+    };
     use teos_common::test_utils::{
         generate_random_appointment, get_random_appointment_receipt,
         get_random_registration_receipt, get_random_user_id,
@@ -284,21 +483,168 @@ mod tests {
         let receipt = register(
             TowerId(tower_pk),
             registration_receipt.user_id(),
+            &cryptography::get_random_keypair().0,
+            &NetAddr::new(server.url()),
+            &None,
+        )
+        .await
+        .unwrap();
+
+        api_mock.assert_async().await;
+        assert_eq!(receipt, registration_receipt);
+    }
+
+    #[tokio::test]
+    async fn test_register_payment_required() {
+        let invoice = common_msgs::RegistrationInvoice {
+            invoice: "lnbcrt1".to_owned(),
+            payment_hash: vec![0; 32],
+            amount_msat: 1000,
+            expires_at: 42,
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::Register.path().as_str())
+            .with_status(402)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "error": "Subscription payment required",
+                    "error_code": errors::REGISTRATION_PAYMENT_REQUIRED,
+                    "invoice": invoice
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let error = register(
+            get_random_user_id(),
+            get_random_user_id(),
+            &cryptography::get_random_keypair().0,
+            &NetAddr::new(server.url()),
+            &None,
+        )
+        .await
+        .unwrap_err();
+
+        api_mock.assert_async().await;
+        assert_eq!(
+            error,
+            RequestError::Unexpected("Subscription payment required. Invoice: lnbcrt1".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_proof_of_work() {
+        let (tower_sk, tower_pk) = cryptography::get_random_keypair();
+        let mut registration_receipt = get_random_registration_receipt();
+        registration_receipt.sign(&tower_sk);
+        let user_id = registration_receipt.user_id();
+        let (user_sk, _) = cryptography::get_random_keypair();
+        let signature = cryptography::sign(
+            registration_message("", &TowerId(tower_pk)).as_bytes(),
+            &user_sk,
+        )
+        .unwrap();
+
+        let challenge = common_msgs::RegistrationChallenge {
+            challenge: vec![1; 32],
+            difficulty: 4,
+            expires_at: 42,
+        };
+        let nonce = solve_pow(&challenge.challenge, 4);
+
+        // The challenge is sent back if the request does not come with a proof, and solved before retrying
+        let mut server = mockito::Server::new_async().await;
+        let challenge_mock = server
+            .mock("POST", Endpoint::Register.path().as_str())
+            .match_body(Matcher::JsonString(
+                json!({"user_id": user_id, "tier": "", "signature": signature}).to_string(),
+            ))
+            .with_status(403)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "error": "Registration requires solving a challenge",
+                    "error_code": errors::REGISTRATION_PROOF_REQUIRED,
+                    "challenge": challenge
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let api_mock = server
+            .mock("POST", Endpoint::Register.path().as_str())
+            .match_body(Matcher::PartialJson(
+                json!({"proof": {"pow": {"expires_at": 42, "nonce": nonce}}}),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!(registration_receipt).to_string())
+            .create_async()
+            .await;
+
+        let receipt = register(
+            TowerId(tower_pk),
+            user_id,
+            &user_sk,
             &NetAddr::new(server.url()),
             &None,
         )
         .await
         .unwrap();
 
+        challenge_mock.assert_async().await;
         api_mock.assert_async().await;
         assert_eq!(receipt, registration_receipt);
     }
 
+    #[tokio::test]
+    async fn test_register_challenge_too_hard() {
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::Register.path().as_str())
+            .with_status(403)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "error": "Registration requires solving a challenge",
+                    "error_code": errors::REGISTRATION_PROOF_REQUIRED,
+                    "challenge": common_msgs::RegistrationChallenge {
+                        challenge: vec![1; 32],
+                        difficulty: MAX_POW_DIFFICULTY as u32 + 1,
+                        expires_at: 42,
+                    }
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let error = register(
+            get_random_user_id(),
+            get_random_user_id(),
+            &cryptography::get_random_keypair().0,
+            &NetAddr::new(server.url()),
+            &None,
+        )
+        .await
+        .unwrap_err();
+
+        api_mock.assert_async().await;
+        assert!(
+            matches!(error, RequestError::Unexpected(e) if e.starts_with("Registration challenge is too hard"))
+        );
+    }
+
     #[tokio::test]
     async fn test_register_connection_error() {
         let error = register(
             get_random_user_id(),
             get_random_user_id(),
+            &cryptography::get_random_keypair().0,
             &NetAddr::new("http://server_addr".to_owned()),
             &None,
         )
@@ -322,6 +668,7 @@ mod tests {
         let error = register(
             get_random_user_id(),
             get_random_user_id(),
+            &cryptography::get_random_keypair().0,
             &NetAddr::new(server.url()),
             &None,
         )
@@ -497,6 +844,9 @@ mod tests {
         let api_error = ApiError {
             error: "error_msg".to_owned(),
             error_code: 1,
+            rejection: None,
+            invoice: None,
+            challenge: None,
         };
 
         let mut server = mockito::Server::new_async().await;
@@ -522,6 +872,204 @@ mod tests {
         assert!(matches!(error, AddAppointmentError::ApiError { .. }));
     }
 
+    #[tokio::test]
+    async fn test_send_appointment_api_error_with_rejection() {
+        let body = json!({
+            "error": "Invalid signature or user does not have enough slots available",
+            "error_code": errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+            "rejection": {
+                "reason": "not_enough_slots",
+                "required_slots": 2,
+                "available_slots": 1,
+                "subscription_expiry": 0,
+                "current_height": 0,
+                "min_to_self_delay": 0
+            }
+        });
+
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .with_status(401)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create_async()
+            .await;
+
+        let error = send_appointment(
+            get_random_user_id(),
+            &NetAddr::new(server.url()),
+            &None,
+            &generate_random_appointment(None),
+            "user_sig",
+        )
+        .await
+        .unwrap_err();
+
+        api_mock.assert_async().await;
+        if let AddAppointmentError::ApiError(e) = error {
+            let rejection = e.rejection.as_ref().unwrap();
+            assert_eq!(
+                RejectionReason::from(rejection.reason),
+                RejectionReason::NotEnoughSlots
+            );
+            assert_eq!(
+                e.subscription_issue(),
+                "not enough slots available (required: 2, available: 1)"
+            );
+        } else {
+            panic!("ApiError was expected")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let (tower_sk, tower_pk) = cryptography::get_random_keypair();
+        let user_sk = cryptography::get_random_keypair().0;
+        let appointments: Vec<(Appointment, String)> = (0..3)
+            .map(|_| {
+                let appointment = generate_random_appointment(None);
+                let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+                (appointment, signature)
+            })
+            .collect();
+
+        // The first two appointments are accepted while the last one is rejected
+        let receipts: Vec<AppointmentReceipt> = appointments[..2]
+            .iter()
+            .map(|(_, signature)| {
+                let mut receipt = AppointmentReceipt::new(signature.clone(), 42);
+                receipt.sign(&tower_sk);
+                receipt
+            })
+            .collect();
+        let mut add_appointments_response = get_dummy_add_appointments_response(
+            appointments
+                .iter()
+                .zip(receipts.iter())
+                .map(|((appointment, _), receipt)| {
+                    get_dummy_add_appointment_response(appointment.locator, receipt)
+                })
+                .collect(),
+        );
+        add_appointments_response
+            .results
+            .push(common_msgs::AddAppointmentResult {
+                locator: appointments[2].0.locator.to_vec(),
+                error: "Appointment already triggered".to_owned(),
+                rejection: Some(common_msgs::AppointmentRejection {
+                    reason: RejectionReason::AlreadyTriggered as i32,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!(add_appointments_response).to_string())
+            .create_async()
+            .await;
+
+        let mut results = add_appointments(
+            TowerId(tower_pk),
+            &NetAddr::new(server.url()),
+            &None,
+            &appointments,
+        )
+        .await
+        .unwrap();
+
+        api_mock.assert_async().await;
+        assert_eq!(results.len(), appointments.len());
+        match results.pop().unwrap() {
+            Err(AddAppointmentError::ApiError(e)) => {
+                assert_eq!(e.error_code, errors::APPOINTMENT_ALREADY_TRIGGERED)
+            }
+            _ => panic!("ApiError was expected"),
+        }
+        for (result, receipt) in results.into_iter().zip(receipts) {
+            assert_eq!(result.unwrap(), (21, receipt));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_misbehaving() {
+        let (sybil_tower_sk, sibyl_tower_pk) = cryptography::get_random_keypair();
+        let appointment = generate_random_appointment(None);
+
+        let appointment_receipt = get_random_appointment_receipt(sybil_tower_sk);
+        let add_appointments_response =
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &appointment_receipt,
+            )]);
+
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!(add_appointments_response).to_string())
+            .create_async()
+            .await;
+
+        let mut results = add_appointments(
+            get_random_user_id(),
+            &NetAddr::new(server.url()),
+            &None,
+            &[(
+                appointment.clone(),
+                appointment_receipt.user_signature().to_owned(),
+            )],
+        )
+        .await
+        .unwrap();
+
+        api_mock.assert_async().await;
+        if let Err(AddAppointmentError::SignatureError(proof)) = results.pop().unwrap() {
+            assert_eq!(
+                MisbehaviorProof::new(
+                    appointment.locator,
+                    appointment_receipt,
+                    TowerId(sibyl_tower_pk)
+                ),
+                proof
+            )
+        } else {
+            panic!("SignatureError was expected")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_wrong_number_of_results() {
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!(get_dummy_add_appointments_response(Vec::new())).to_string())
+            .create_async()
+            .await;
+
+        let error = add_appointments(
+            get_random_user_id(),
+            &NetAddr::new(server.url()),
+            &None,
+            &[(generate_random_appointment(None), "user_sig".to_owned())],
+        )
+        .await
+        .unwrap_err();
+
+        api_mock.assert_async().await;
+        assert!(matches!(
+            error,
+            AddAppointmentError::RequestError(RequestError::Unexpected(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_request() {
         let mut server = mockito::Server::new_async().await;
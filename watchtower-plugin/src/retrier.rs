@@ -7,7 +7,8 @@ use tokio::sync::mpsc::{error::TryRecvError, UnboundedReceiver};
 use backoff::future::retry_notify;
 use backoff::{Error, ExponentialBackoff};
 
-use teos_common::appointment::Locator;
+use teos_common::appointment::{Appointment, Locator};
+use teos_common::constants::MAX_APPOINTMENTS_PER_BATCH;
 use teos_common::cryptography;
 use teos_common::errors;
 use teos_common::UserId as TowerId;
@@ -449,7 +450,7 @@ impl Retrier {
 
         // If the tower state is subscription_error we need to re-register first. If we cannot, then the retry is aborted.
         if status.is_subscription_error() {
-            let receipt = http::register(tower_id, user_id, &net_addr, &proxy)
+            let receipt = http::register(tower_id, user_id, &user_sk, &net_addr, &proxy)
                 .await
                 .map_err(|e| {
                     log::debug!("Cannot renew registration with tower. Error: {e:?}");
@@ -467,7 +468,7 @@ impl Retrier {
                 .add_update_tower(tower_id, net_addr.net_addr(), &receipt)
                 .map_err(|e| {
                     let reason = if e.is_expiry() {
-                        "Registration receipt contains a subscription expiry that is not higher than the one we are currently registered for"
+                        "Registration receipt contains a subscription expiry that is lower than the one we are currently registered for"
                     } else {
                         "Registration receipt does not contain more slots than the ones we are currently registered for"
                     };
@@ -476,86 +477,153 @@ impl Retrier {
         }
 
         while self.has_pending_appointments() {
-            let locators = self.pending_appointments.lock().unwrap().clone();
-            for locator in locators.into_iter() {
-                let appointment = self
-                    .wt_client
-                    .lock()
-                    .unwrap()
-                    .dbm
-                    .load_appointment(locator)
-                    .unwrap();
-
-                match http::add_appointment(
+            let locators: Vec<Locator> = self
+                .pending_appointments
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect();
+            for chunk in locators.chunks(MAX_APPOINTMENTS_PER_BATCH) {
+                let appointments: Vec<(Appointment, String)> = {
+                    let wt_client = self.wt_client.lock().unwrap();
+                    chunk
+                        .iter()
+                        .map(|locator| {
+                            let appointment = wt_client.dbm.load_appointment(*locator).unwrap();
+                            let signature =
+                                cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+                            (appointment, signature)
+                        })
+                        .collect()
+                };
+
+                let results = match http::add_appointments(
                     tower_id,
                     &net_addr,
                     &proxy,
-                    &appointment,
-                    &cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                    &appointments,
                 )
                 .await
                 {
-                    Ok((slots, receipt)) => {
-                        self.pending_appointments.lock().unwrap().remove(&locator);
-                        let mut wt_client = self.wt_client.lock().unwrap();
-                        wt_client.add_appointment_receipt(
-                            tower_id,
-                            appointment.locator,
-                            slots,
-                            &receipt,
-                        );
-                        wt_client.remove_pending_appointment(tower_id, appointment.locator);
-                        log::debug!("Response verified and data stored in the database");
-                    }
-                    Err(e) => {
-                        match e {
-                            AddAppointmentError::RequestError(e) => {
-                                if e.is_connection() {
-                                    log::warn!(
-                                        "{tower_id} cannot be reached. Tower will be retried later"
-                                    );
-                                    return Err(Error::transient(RetryError::Unreachable));
-                                }
-                            }
-                            AddAppointmentError::ApiError(e) => match e.error_code {
-                                errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR => {
-                                    log::warn!("There is a subscription issue with {tower_id}");
-                                    self.wt_client
-                                        .lock()
-                                        .unwrap()
-                                        .set_tower_status(tower_id, TowerStatus::SubscriptionError);
-                                    return Err(Error::transient(RetryError::Subscription(
-                                        "Subscription error".to_owned(),
-                                        false,
-                                    )));
-                                }
-                                _ => {
-                                    log::warn!(
-                                        "{tower_id} rejected the appointment. Error: {}, error_code: {}",
-                                        e.error,
-                                        e.error_code
-                                    );
-                                    // We need to move the appointment from pending to invalid
-                                    // Add it first to invalid and remove it from pending later so a cascade delete is not triggered
-                                    self.pending_appointments.lock().unwrap().remove(&locator);
-                                    let mut wt_client = self.wt_client.lock().unwrap();
-                                    wt_client.add_invalid_appointment(tower_id, &appointment);
-                                    wt_client
-                                        .remove_pending_appointment(tower_id, appointment.locator);
-                                }
-                            },
-                            AddAppointmentError::SignatureError(proof) => {
-                                return Err(Error::permanent(RetryError::Misbehaving(proof)));
+                    Ok(results) => results,
+                    Err(e) => match self.handle_error(tower_id, &appointments, e) {
+                        Some(e) => return Err(e),
+                        None => continue,
+                    },
+                };
+
+                // Store all the accepted appointments before giving up on the tower due to any of the rejected ones.
+                let mut retry_error = None;
+                for (entry, result) in appointments.iter().zip(results) {
+                    let appointment = &entry.0;
+                    match result {
+                        Ok((slots, receipt)) => {
+                            self.pending_appointments
+                                .lock()
+                                .unwrap()
+                                .remove(&appointment.locator);
+                            let mut wt_client = self.wt_client.lock().unwrap();
+                            wt_client.add_appointment_receipt(
+                                tower_id,
+                                appointment.locator,
+                                slots,
+                                &receipt,
+                            );
+                            wt_client.remove_pending_appointment(tower_id, appointment.locator);
+                            log::debug!("Response verified and data stored in the database");
+                        }
+                        Err(e) => {
+                            if let Some(e) =
+                                self.handle_error(tower_id, std::slice::from_ref(entry), e)
+                            {
+                                retry_error.get_or_insert(e);
                             }
                         }
                     }
                 }
+                if let Some(e) = retry_error {
+                    return Err(e);
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Handles an error received when sending some appointments to the tower.
+    ///
+    /// Appointments rejected by the tower are flagged as invalid. Errors that prevent the tower from being retried
+    /// any further in this round are returned.
+    fn handle_error(
+        &self,
+        tower_id: TowerId,
+        appointments: &[(Appointment, String)],
+        error: AddAppointmentError,
+    ) -> Option<Error<RetryError>> {
+        match error {
+            AddAppointmentError::RequestError(e) => {
+                if e.is_connection() {
+                    log::warn!("{tower_id} cannot be reached. Tower will be retried later");
+                    return Some(Error::transient(RetryError::Unreachable));
+                }
+            }
+            AddAppointmentError::ApiError(e) => match e.error_code {
+                errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR => {
+                    log::warn!(
+                        "There is a subscription issue with {tower_id}: {}",
+                        e.subscription_issue()
+                    );
+                    self.wt_client
+                        .lock()
+                        .unwrap()
+                        .set_tower_status(tower_id, TowerStatus::SubscriptionError);
+                    return Some(Error::transient(RetryError::Subscription(
+                        "Subscription error".to_owned(),
+                        false,
+                    )));
+                }
+                errors::RATE_LIMITED => {
+                    log::warn!(
+                        "{tower_id} is rate limiting our requests. Tower will be retried later"
+                    );
+                    return Some(Error::transient(RetryError::Unreachable));
+                }
+                errors::SERVICE_UNAVAILABLE => {
+                    log::warn!(
+                        "{tower_id} cannot store appointments at the moment. Tower will be retried later"
+                    );
+                    return Some(Error::transient(RetryError::Unreachable));
+                }
+                _ => {
+                    log::warn!(
+                        "{tower_id} rejected the appointment. Error: {}, error_code: {}",
+                        e.error,
+                        e.error_code
+                    );
+                    // We need to move the appointments from pending to invalid
+                    // Add them first to invalid and remove them from pending later so a cascade delete is not triggered
+                    {
+                        let mut pending_appointments = self.pending_appointments.lock().unwrap();
+                        for (appointment, _) in appointments {
+                            pending_appointments.remove(&appointment.locator);
+                        }
+                    }
+                    let mut wt_client = self.wt_client.lock().unwrap();
+                    for (appointment, _) in appointments {
+                        wt_client.add_invalid_appointment(tower_id, appointment);
+                        wt_client.remove_pending_appointment(tower_id, appointment.locator);
+                    }
+                }
+            },
+            AddAppointmentError::SignatureError(proof) => {
+                return Some(Error::permanent(RetryError::Misbehaving(proof)));
+            }
+        }
+
+        None
+    }
+
     /// Removed our retrier identifier from the WTClient if the retrier has failed
     pub fn remove_if_failed(&self) {
         if self.failed() {
@@ -580,9 +648,10 @@ mod tests {
     use tempdir::TempDir;
     use tokio::sync::mpsc::unbounded_channel;
 
+    use teos_common::appointment::RejectionReason;
     use teos_common::errors;
     use teos_common::net::http::Endpoint;
-    use teos_common::protos::AddAppointmentRequest;
+    use teos_common::protos::{self as common_msgs, AddAppointmentsRequest};
     use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
     use teos_common::test_utils::{
         generate_random_appointment, get_random_registration_receipt, get_random_user_id,
@@ -590,7 +659,9 @@ mod tests {
     };
 
     use crate::net::http::ApiError;
-    use crate::test_utils::get_dummy_add_appointment_response;
+    use crate::test_utils::{
+        get_dummy_add_appointment_response, get_dummy_add_appointments_response,
+    };
 
     const LONG_AUTO_RETRY_DELAY: u32 = 60;
     const SHORT_AUTO_RETRY_DELAY: u32 = 3;
@@ -657,10 +728,13 @@ mod tests {
         );
         add_appointment_receipt.sign(&tower_sk);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
 
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body_from_request(move |_| {
@@ -798,9 +872,12 @@ mod tests {
         );
         add_appointment_receipt.sign(&tower_sk);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body(json!(add_appointment_response).to_string())
@@ -871,7 +948,7 @@ mod tests {
 
         // Prepare the mock response
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(400)
             .with_header("content-type", "application/json")
             .with_body_from_request(|_| {
@@ -879,6 +956,9 @@ mod tests {
                 json!(ApiError {
                     error: "error_msg".to_owned(),
                     error_code: 1,
+                    rejection: None,
+                    invoice: None,
+                    challenge: None,
                 })
                 .to_string()
                 .into()
@@ -980,9 +1060,12 @@ mod tests {
         // Sign with a random key so it counts as misbehaving
         add_appointment_receipt.sign(&cryptography::get_random_keypair().0);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body_from_request(move |_| {
@@ -1091,8 +1174,13 @@ mod tests {
         // Add a tower with pending appointments
         let (tower_sk, tower_pk) = cryptography::get_random_keypair();
         let tower_id = TowerId(tower_pk);
-        let mut registration_receipt =
-            RegistrationReceipt::new(wt_client.lock().unwrap().user_id, 21, 42, 420);
+        let mut registration_receipt = RegistrationReceipt::new(
+            wt_client.lock().unwrap().user_id,
+            21,
+            42,
+            420,
+            String::new(),
+        );
         registration_receipt.sign(&tower_sk);
         wt_client
             .lock()
@@ -1118,7 +1206,10 @@ mod tests {
         );
         add_appointment_receipt.sign(&tower_sk);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
 
         let api_mock = server
             .mock("POST", mockito::Matcher::Any)
@@ -1128,7 +1219,7 @@ mod tests {
                 let response = if request.path() == Endpoint::Register.path().as_str() {
                     std::thread::sleep(Duration::from_secs_f64(API_DELAY));
                     json!(re_registration_receipt).to_string()
-                } else if request.path() == Endpoint::AddAppointment.path().as_str() {
+                } else if request.path() == Endpoint::AddAppointments.path().as_str() {
                     json!(add_appointment_response).to_string()
                 } else {
                     panic!("Wrong endpoint hit")
@@ -1281,22 +1372,35 @@ mod tests {
         let mut server = mockito::Server::new_async().await;
 
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body_from_request(move |request| {
-                let body = serde_json::from_slice::<AddAppointmentRequest>(request.body().unwrap())
-                    .unwrap();
-
-                let response = if body.appointment.unwrap().locator == appointment.locator.to_vec()
-                {
-                    get_dummy_add_appointment_response(appointment.locator, &appointment_receipt)
-                } else {
-                    get_dummy_add_appointment_response(appointment2.locator, &appointment2_receipt)
-                };
+                let body =
+                    serde_json::from_slice::<AddAppointmentsRequest>(request.body().unwrap())
+                        .unwrap();
+
+                // Both appointments are sent in the same batch, so the results must follow the request order
+                let response = get_dummy_add_appointments_response(
+                    body.appointments
+                        .into_iter()
+                        .map(|r| {
+                            if r.appointment.unwrap().locator == appointment.locator.to_vec() {
+                                get_dummy_add_appointment_response(
+                                    appointment.locator,
+                                    &appointment_receipt,
+                                )
+                            } else {
+                                get_dummy_add_appointment_response(
+                                    appointment2.locator,
+                                    &appointment2_receipt,
+                                )
+                            }
+                        })
+                        .collect(),
+                );
                 json!(response).to_string().into()
             })
-            .expect(2)
             .create_async()
             .await;
 
@@ -1373,9 +1477,12 @@ mod tests {
         );
         add_appointment_receipt.sign(&tower_sk);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body(json!(add_appointment_response).to_string())
@@ -1444,9 +1551,12 @@ mod tests {
         );
         add_appointment_receipt.sign(&cryptography::get_random_keypair().0);
         let add_appointment_response =
-            get_dummy_add_appointment_response(appointment.locator, &add_appointment_receipt);
+            get_dummy_add_appointments_response(vec![get_dummy_add_appointment_response(
+                appointment.locator,
+                &add_appointment_receipt,
+            )]);
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body(json!(add_appointment_response).to_string())
@@ -1513,13 +1623,16 @@ mod tests {
             .unwrap();
 
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(400)
             .with_header("content-type", "application/json")
             .with_body(
                 json!(ApiError {
                     error: "error_msg".to_owned(),
                     error_code: errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+                    rejection: None,
+                    invoice: None,
+                    challenge: None,
                 })
                 .to_string(),
             )
@@ -1547,6 +1660,67 @@ mod tests {
         api_mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_retry_tower_rate_limited() {
+        let (_, tower_pk) = cryptography::get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+        let tmp_path = TempDir::new(&format!("watchtower_{}", get_random_user_id())).unwrap();
+        let wt_client = Arc::new(Mutex::new(
+            WTClient::new(tmp_path.path().to_path_buf(), unbounded_channel().0).await,
+        ));
+        let mut server = mockito::Server::new_async().await;
+
+        // The tower we'd like to retry sending appointments to has to exist within the plugin
+        let receipt = get_random_registration_receipt();
+        wt_client
+            .lock()
+            .unwrap()
+            .add_update_tower(tower_id, &server.url(), &receipt)
+            .unwrap();
+
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!(ApiError {
+                    error: "error_msg".to_owned(),
+                    error_code: errors::RATE_LIMITED,
+                    rejection: None,
+                    invoice: None,
+                    challenge: None,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let appointment = generate_random_appointment(None);
+        wt_client
+            .lock()
+            .unwrap()
+            .add_pending_appointment(tower_id, &appointment);
+
+        // Rate limited appointments are kept as pending, and the tower retried later on
+        let retrier = Retrier::new(
+            wt_client.clone(),
+            tower_id,
+            HashSet::from([appointment.locator]),
+        );
+        let r = retrier.run().await;
+
+        assert_eq!(r, Err(Error::transient(RetryError::Unreachable)));
+        assert!(wt_client
+            .lock()
+            .unwrap()
+            .towers
+            .get(&tower_id)
+            .unwrap()
+            .pending_appointments
+            .contains(&appointment.locator));
+        api_mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_retry_tower_rejected() {
         let (_, tower_pk) = cryptography::get_random_keypair();
@@ -1566,13 +1740,16 @@ mod tests {
             .unwrap();
 
         let api_mock = server
-            .mock("POST", Endpoint::AddAppointment.path().as_str())
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
             .with_status(400)
             .with_header("content-type", "application/json")
             .with_body(
                 json!(ApiError {
                     error: "error_msg".to_owned(),
                     error_code: 1,
+                    rejection: None,
+                    invoice: None,
+                    challenge: None,
                 })
                 .to_string(),
             )
@@ -1606,6 +1783,107 @@ mod tests {
         api_mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_retry_tower_partially_rejected() {
+        let (tower_sk, tower_pk) = cryptography::get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+        let tmp_path = TempDir::new(&format!("watchtower_{}", get_random_user_id())).unwrap();
+        let wt_client = Arc::new(Mutex::new(
+            WTClient::new(tmp_path.path().to_path_buf(), unbounded_channel().0).await,
+        ));
+        let mut server = mockito::Server::new_async().await;
+
+        // The tower we'd like to retry sending appointments to has to exist within the plugin
+        let receipt = get_random_registration_receipt();
+        wt_client
+            .lock()
+            .unwrap()
+            .add_update_tower(tower_id, &server.url(), &receipt)
+            .unwrap();
+
+        let accepted = generate_random_appointment(None);
+        let rejected = generate_random_appointment(None);
+        for appointment in [&accepted, &rejected] {
+            wt_client
+                .lock()
+                .unwrap()
+                .add_pending_appointment(tower_id, appointment);
+        }
+        let mut add_appointment_receipt = AppointmentReceipt::new(
+            cryptography::sign(&accepted.to_vec(), &wt_client.lock().unwrap().user_sk).unwrap(),
+            42,
+        );
+        add_appointment_receipt.sign(&tower_sk);
+
+        // The tower accepts one of the appointments and rejects the other one for not having enough slots
+        let (accepted_locator, receipt_clone) = (accepted.locator, add_appointment_receipt.clone());
+        let api_mock = server
+            .mock("POST", Endpoint::AddAppointments.path().as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let body =
+                    serde_json::from_slice::<AddAppointmentsRequest>(request.body().unwrap())
+                        .unwrap();
+                let mut response = get_dummy_add_appointments_response(Vec::new());
+                for r in body.appointments {
+                    let locator = r.appointment.unwrap().locator;
+                    response
+                        .results
+                        .push(if locator == accepted_locator.to_vec() {
+                            common_msgs::AddAppointmentResult {
+                                locator: locator.clone(),
+                                response: Some(get_dummy_add_appointment_response(
+                                    accepted_locator,
+                                    &receipt_clone,
+                                )),
+                                ..Default::default()
+                            }
+                        } else {
+                            common_msgs::AddAppointmentResult {
+                                locator,
+                                error: "Not enough slots".to_owned(),
+                                rejection: Some(common_msgs::AppointmentRejection {
+                                    reason: RejectionReason::NotEnoughSlots as i32,
+                                    ..Default::default()
+                                }),
+                                ..Default::default()
+                            }
+                        });
+                }
+                json!(response).to_string().into()
+            })
+            .create_async()
+            .await;
+
+        let retrier = Retrier::new(
+            wt_client.clone(),
+            tower_id,
+            HashSet::from([accepted.locator, rejected.locator]),
+        );
+        let r = retrier.run().await;
+
+        // The accepted appointment is stored even though the retry is interrupted by the subscription issue
+        assert_eq!(
+            r,
+            Err(Error::transient(RetryError::Subscription(
+                "Subscription error".to_owned(),
+                false
+            )))
+        );
+        api_mock.assert_async().await;
+        let state = wt_client.lock().unwrap();
+        let tower = state.towers.get(&tower_id).unwrap();
+        assert_eq!(tower.status, TowerStatus::SubscriptionError);
+        assert!(!tower.pending_appointments.contains(&accepted.locator));
+        assert!(tower.pending_appointments.contains(&rejected.locator));
+        assert!(!tower.invalid_appointments.contains(&rejected.locator));
+        assert_eq!(
+            state.get_appointment_receipt(tower_id, accepted.locator),
+            Some(add_appointment_receipt)
+        );
+    }
+
     #[tokio::test]
     async fn test_retry_tower_abandoned() {
         let (_, tower_pk) = cryptography::get_random_keypair();
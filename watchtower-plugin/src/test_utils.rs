@@ -14,3 +14,20 @@ pub fn get_dummy_add_appointment_response(
         subscription_expiry: 1000,
     }
 }
+
+pub fn get_dummy_add_appointments_response(
+    responses: Vec<common_msgs::AddAppointmentResponse>,
+) -> common_msgs::AddAppointmentsResponse {
+    common_msgs::AddAppointmentsResponse {
+        available_slots: responses.last().map_or(21, |r| r.available_slots),
+        subscription_expiry: 1000,
+        results: responses
+            .into_iter()
+            .map(|r| common_msgs::AddAppointmentResult {
+                locator: r.locator.clone(),
+                response: Some(r),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
@@ -94,7 +94,10 @@ impl WTClient {
             std::process::exit(1);
         });
 
-        let dbm = DBM::new(&data_dir.join("watchtowers_db.sql3")).unwrap();
+        let dbm = DBM::new(&data_dir.join("watchtowers_db.sql3")).unwrap_or_else(|e| {
+            log::error!("Cannot open the database: {e}");
+            std::process::exit(1);
+        });
 
         let (user_sk, user_id) = if let Some(sk) = dbm.load_client_key() {
             (
@@ -141,9 +144,9 @@ impl WTClient {
         receipt: &RegistrationReceipt,
     ) -> Result<(), SubscriptionError> {
         if let Some(tower) = self.towers.get(&tower_id) {
-            // TODO: For now we're forcing updates to increase both slots and expiry. This is not mandatory and may
-            // be changed in the future, but the tower is currently set to do this anyway so let's keep it simple.
-            if receipt.subscription_expiry() <= tower.subscription_expiry {
+            // Registering again always adds slots, but only extends the subscription if the tower deems it renewable,
+            // so updates are required to increase the slots without reducing the expiry.
+            if receipt.subscription_expiry() < tower.subscription_expiry {
                 return Err(SubscriptionError::Expiry);
             } else {
                 let tower_info = self.dbm.load_tower_record(tower_id).unwrap();
@@ -364,12 +367,13 @@ mod tests {
             updated_tower_info
         );
 
-        // If we try to update without increasing both the end_time and the slots, this will fail
+        // If we try to update without increasing the slots, or reducing the end_time, this will fail
         let mut receipt_same_slots = RegistrationReceipt::new(
             receipt.user_id(),
             receipt.available_slots(),
             receipt.subscription_start(),
             receipt.subscription_expiry() + 1,
+            receipt.tier().to_owned(),
         );
         receipt_same_slots.sign(&tower_sk);
         let mut receipt_same_expiry = RegistrationReceipt::new(
@@ -377,12 +381,21 @@ mod tests {
             receipt.available_slots() + 1,
             receipt.subscription_start(),
             receipt.subscription_expiry(),
+            receipt.tier().to_owned(),
         );
         receipt_same_expiry.sign(&tower_sk);
+        let mut receipt_lower_expiry = RegistrationReceipt::new(
+            receipt.user_id(),
+            receipt.available_slots() + 1,
+            receipt.subscription_start(),
+            receipt.subscription_expiry() - 1,
+            receipt.tier().to_owned(),
+        );
+        receipt_lower_expiry.sign(&tower_sk);
 
         assert!(matches!(
             wt_client.add_update_tower(tower_id, &updated_tower_info.net_addr, &receipt),
-            Err(SubscriptionError::Expiry)
+            Err(SubscriptionError::Slots)
         ));
         assert!(matches!(
             wt_client.add_update_tower(tower_id, &updated_tower_info.net_addr, &receipt_same_slots),
@@ -392,11 +405,16 @@ mod tests {
             wt_client.add_update_tower(
                 tower_id,
                 &updated_tower_info.net_addr,
-                &receipt_same_expiry
+                &receipt_lower_expiry
             ),
             Err(SubscriptionError::Expiry)
         ));
 
+        // Updates that only increase the slots are accepted
+        wt_client
+            .add_update_tower(tower_id, &updated_tower_info.net_addr, &receipt_same_expiry)
+            .unwrap();
+
         // Decrease the slots count (simulate exhaustion) and update with more than the current count it should work
         let locator = generate_random_appointment(None).locator;
         wt_client.add_appointment_receipt(